@@ -0,0 +1,86 @@
+# This file hooks up shell integration for wezterm.
+# It is suitable for fish; load it at the end of your config.fish with:
+#
+#   wezterm shell-integration --shell fish | source
+#
+# Although wezterm is mentioned here, the sequences used are not wezterm
+# specific and may provide the same functionality for other terminals.  Most
+# terminals are good at ignoring OSC sequences that they don't understand, but
+# if not there are some bypasses:
+#
+# WEZTERM_SHELL_SKIP_ALL - disables all
+# WEZTERM_SHELL_SKIP_SEMANTIC_ZONES - disables zones
+# WEZTERM_SHELL_SKIP_CWD - disables OSC 7 cwd setting
+
+if test "$WEZTERM_SHELL_SKIP_ALL" = "1"
+  exit 0
+end
+
+if not status is-interactive
+  # Shell integration is only useful in interactive sessions
+  exit 0
+end
+
+switch "$TERM"
+  case linux dumb
+    # Avoid terminals that don't like OSC sequences
+    exit 0
+end
+
+# Sets $status to its argument, so that the status of the last
+# command can be passed through to the original prompt
+function __wezterm_set_status
+  return $argv[1]
+end
+
+# The prompt and preexec functions generate semantic zones, marking
+# up the prompt, the user input and the command output so that the
+# terminal can better reason about the display.
+if test -z "$WEZTERM_SHELL_SKIP_SEMANTIC_ZONES"
+  if functions -q fish_prompt
+    functions -c fish_prompt __wezterm_original_fish_prompt
+  else
+    function __wezterm_original_fish_prompt
+      echo -n '> '
+    end
+  end
+
+  function fish_prompt
+    set -l ret $status
+    if set -q __wezterm_semantic_executing
+      # Report last command status
+      printf "\033]133;D;%s;aid=%s\007" $ret $fish_pid
+      set -e __wezterm_semantic_executing
+    end
+    # Fresh line and start the prompt
+    printf "\033]133;A;cl=m;aid=%s\007" $fish_pid
+    # Markup the prompt so that the terminal knows that it is
+    # semantically prompt output
+    printf "\033]133;P;k=i\007"
+    __wezterm_set_status $ret
+    __wezterm_original_fish_prompt
+    printf "\033]133;B\007"
+  end
+
+  function __wezterm_semantic_preexec --on-event fish_preexec
+    # Indicate that the command output begins here
+    printf "\033]133;C;\007"
+    set -g __wezterm_semantic_executing 1
+  end
+end
+
+# This function emits an OSC 7 sequence to inform the terminal
+# of the current working directory.  It prefers to use a helper
+# command provided by wezterm if wezterm is installed, but falls
+# back to a simple printf command otherwise.
+if test -z "$WEZTERM_SHELL_SKIP_CWD"
+  function __wezterm_osc7 --on-variable PWD
+    if type -q wezterm
+      wezterm set-working-directory 2>/dev/null; and return 0
+      # If the command failed (perhaps the installed wezterm
+      # is too old?) then fall back to the simple version below.
+    end
+    printf "\033]7;file://%s%s\033\\" $hostname $PWD
+  end
+  __wezterm_osc7
+end
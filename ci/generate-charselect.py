@@ -0,0 +1,38 @@
+#!/usr/bin/env python3
+# Generates wezterm-gui/src/overlay/charselect.txt, the list of characters
+# and their names that is presented by the CharSelect overlay.
+# Each line holds the hex codepoint followed by the unicode name.
+import os
+import unicodedata
+
+SYMBOL_CATEGORIES = {"So", "Sm", "Sc", "Sk"}
+
+
+def wanted(cp, category):
+    if category in SYMBOL_CATEGORIES:
+        return True
+    # Accented latin, greek and cyrillic letters
+    if category.startswith("L") and 0x80 <= cp < 0x530:
+        return True
+    # Punctuation such as dashes, quotes and bullets
+    if category.startswith("P") and 0x80 <= cp < 0x3000:
+        return True
+    return False
+
+
+def main():
+    dest = os.path.join(
+        os.path.dirname(__file__), "..", "wezterm-gui", "src", "overlay", "charselect.txt"
+    )
+    with open(dest, "w") as f:
+        for cp in range(0x20, 0x30000):
+            c = chr(cp)
+            name = unicodedata.name(c, None)
+            if name is None:
+                continue
+            if not wanted(cp, unicodedata.category(c)):
+                continue
+            f.write("%04X %s\n" % (cp, name))
+
+
+main()
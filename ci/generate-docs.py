@@ -141,12 +141,14 @@ TOC = [
             ),
             Page("Scrollback", "scrollback.md"),
             Page("Copy Mode", "copymode.md"),
+            Page("Quick Select Mode", "quickselect.md"),
             Page("Hyperlinks", "hyperlinks.md"),
             Page("Shell Integration", "shell-integration.md"),
             Page("iTerm Image Protocol", "imgcat.md"),
             Page("SSH", "ssh.md"),
             Page("Serial Ports & Arduino", "serial.md"),
             Page("Mulitplexing", "multiplexing.md"),
+            Page("Mux Protocol", "mux-protocol.md"),
             Page("Escape Sequences", "escape-sequences.md"),
             Page("F.A.Q.", "faq.md"),
             Page("Getting Help", "help.md"),
@@ -0,0 +1,237 @@
+//! The framing layer of the mux protocol.
+//! Each PDU is sent as a frame consisting of a leb128 encoded length,
+//! serial number and identifier, followed by the serialized PDU data.
+//! See docs/mux-protocol.md for a description of the complete protocol.
+//! This module is public when the `public-api` feature is enabled,
+//! which allows tools to relay or inspect frames without needing to
+//! understand every PDU type.
+use anyhow::Context as _;
+use smol::io::AsyncWriteExt;
+use smol::prelude::*;
+
+/// Returns the encoded length of the leb128 representation of value
+pub fn encoded_length(value: u64) -> usize {
+    struct NullWrite {};
+    impl std::io::Write for NullWrite {
+        fn write(&mut self, buf: &[u8]) -> std::result::Result<usize, std::io::Error> {
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::result::Result<(), std::io::Error> {
+            Ok(())
+        }
+    };
+
+    leb128::write::unsigned(&mut NullWrite {}, value).unwrap()
+}
+
+pub const COMPRESSED_MASK: u64 = 1 << 63;
+
+/// Encode a frame into a new buffer.
+/// See encode_raw() for the frame format.
+pub fn encode_raw_as_vec(
+    ident: u64,
+    serial: u64,
+    data: &[u8],
+    is_compressed: bool,
+) -> anyhow::Result<Vec<u8>> {
+    let len = data.len() + encoded_length(ident) + encoded_length(serial);
+    let masked_len = if is_compressed {
+        (len as u64) | COMPRESSED_MASK
+    } else {
+        len as u64
+    };
+
+    // Double-buffer the data; since we run with nodelay enabled, it is
+    // desirable for the write to be a single packet (or at least, for
+    // the header portion to go out in a single packet)
+    let mut buffer = Vec::with_capacity(len + encoded_length(masked_len));
+
+    leb128::write::unsigned(&mut buffer, masked_len).context("writing pdu len")?;
+    leb128::write::unsigned(&mut buffer, serial).context("writing pdu serial")?;
+    leb128::write::unsigned(&mut buffer, ident).context("writing pdu ident")?;
+    buffer.extend_from_slice(data);
+
+    if is_compressed {
+        metrics::histogram!("pdu.encode.compressed.size", buffer.len() as f64);
+    } else {
+        metrics::histogram!("pdu.encode.size", buffer.len() as f64);
+    }
+
+    Ok(buffer)
+}
+
+/// Encode a frame.  If the data is compressed, the high bit of the length
+/// is set to indicate that.  The data written out has the format:
+/// tagged_len: leb128  (u64 msb is set if data is compressed)
+/// serial: leb128
+/// ident: leb128
+/// data bytes
+pub fn encode_raw<W: std::io::Write>(
+    ident: u64,
+    serial: u64,
+    data: &[u8],
+    is_compressed: bool,
+    mut w: W,
+) -> anyhow::Result<usize> {
+    let buffer = encode_raw_as_vec(ident, serial, data, is_compressed)?;
+    w.write_all(&buffer).context("writing pdu data buffer")?;
+    Ok(buffer.len())
+}
+
+pub async fn encode_raw_async<W: Unpin + AsyncWriteExt>(
+    ident: u64,
+    serial: u64,
+    data: &[u8],
+    is_compressed: bool,
+    w: &mut W,
+) -> anyhow::Result<usize> {
+    let buffer = encode_raw_as_vec(ident, serial, data, is_compressed)?;
+    w.write_all(&buffer)
+        .await
+        .context("writing pdu data buffer")?;
+    Ok(buffer.len())
+}
+
+/// Read a single leb128 encoded value from the stream
+pub async fn read_u64_async<R>(r: &mut R) -> anyhow::Result<u64>
+where
+    R: Unpin + AsyncRead + std::fmt::Debug,
+{
+    let mut buf = vec![];
+    loop {
+        let mut byte = [0u8];
+        let nread = r.read(&mut byte).await?;
+        if nread == 0 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "EOF while reading leb128 encoded value",
+            )
+            .into());
+        }
+        buf.push(byte[0]);
+
+        match leb128::read::unsigned(&mut buf.as_slice()) {
+            Ok(n) => {
+                return Ok(n);
+            }
+            Err(leb128::read::Error::IoError(_)) => continue,
+            Err(leb128::read::Error::Overflow) => anyhow::bail!("leb128 is too large"),
+        }
+    }
+}
+
+/// Read a single leb128 encoded value from the stream
+pub fn read_u64<R: std::io::Read>(mut r: R) -> anyhow::Result<u64> {
+    leb128::read::unsigned(&mut r)
+        .map_err(|err| match err {
+            leb128::read::Error::IoError(ioerr) => anyhow::Error::new(ioerr),
+            err => anyhow::Error::new(err),
+        })
+        .context("reading leb128")
+}
+
+/// A decoded frame.  `data` holds the serialized PDU, which must be
+/// decompressed with zstd prior to deserializing if `is_compressed` is set.
+#[derive(Debug)]
+pub struct Decoded {
+    pub ident: u64,
+    pub serial: u64,
+    pub data: Vec<u8>,
+    pub is_compressed: bool,
+}
+
+/// Decode a frame.
+/// See encode_raw() for the frame format.
+pub async fn decode_raw_async<R: Unpin + AsyncRead + std::fmt::Debug>(
+    r: &mut R,
+) -> anyhow::Result<Decoded> {
+    let len = read_u64_async(r).await.context("reading PDU length")?;
+    let (len, is_compressed) = if (len & COMPRESSED_MASK) != 0 {
+        (len & !COMPRESSED_MASK, true)
+    } else {
+        (len, false)
+    };
+    let serial = read_u64_async(r).await.context("reading PDU serial")?;
+    let ident = read_u64_async(r).await.context("reading PDU ident")?;
+    let data_len =
+        match (len as usize).overflowing_sub(encoded_length(ident) + encoded_length(serial)) {
+            (_, true) => {
+                anyhow::bail!(
+                    "sizes don't make sense: len:{} serial:{} (enc={}) ident:{} (enc={})",
+                    len,
+                    serial,
+                    encoded_length(serial),
+                    ident,
+                    encoded_length(ident)
+                );
+            }
+            (data_len, false) => data_len,
+        };
+
+    if is_compressed {
+        metrics::histogram!("pdu.decode.compressed.size", data_len as f64);
+    } else {
+        metrics::histogram!("pdu.decode.size", data_len as f64);
+    }
+
+    let mut data = vec![0u8; data_len];
+    r.read_exact(&mut data).await.with_context(|| {
+        format!(
+            "reading {} bytes of data for PDU of length {} with serial={} ident={}",
+            data_len, len, serial, ident
+        )
+    })?;
+    Ok(Decoded {
+        ident,
+        serial,
+        data,
+        is_compressed,
+    })
+}
+
+/// Decode a frame.
+/// See encode_raw() for the frame format.
+pub fn decode_raw<R: std::io::Read>(mut r: R) -> anyhow::Result<Decoded> {
+    let len = read_u64(r.by_ref()).context("reading PDU length")?;
+    let (len, is_compressed) = if (len & COMPRESSED_MASK) != 0 {
+        (len & !COMPRESSED_MASK, true)
+    } else {
+        (len, false)
+    };
+    let serial = read_u64(r.by_ref()).context("reading PDU serial")?;
+    let ident = read_u64(r.by_ref()).context("reading PDU ident")?;
+    let data_len =
+        match (len as usize).overflowing_sub(encoded_length(ident) + encoded_length(serial)) {
+            (_, true) => {
+                anyhow::bail!(
+                    "sizes don't make sense: len:{} serial:{} (enc={}) ident:{} (enc={})",
+                    len,
+                    serial,
+                    encoded_length(serial),
+                    ident,
+                    encoded_length(ident)
+                );
+            }
+            (data_len, false) => data_len,
+        };
+
+    if is_compressed {
+        metrics::histogram!("pdu.decode.compressed.size", data_len as f64);
+    } else {
+        metrics::histogram!("pdu.decode.size", data_len as f64);
+    }
+
+    let mut data = vec![0u8; data_len];
+    r.read_exact(&mut data).with_context(|| {
+        format!(
+            "reading {} bytes of data for PDU of length {} with serial={} ident={}",
+            data_len, len, serial, ident
+        )
+    })?;
+    Ok(Decoded {
+        ident,
+        serial,
+        data,
+        is_compressed,
+    })
+}
@@ -8,11 +8,12 @@
 //! client and server instances that are built from different versions
 //! of this code; in this way the client and server can more gracefully
 //! manage unknown enum variants.
+//! The protocol is described in docs/mux-protocol.md; the PDU types
+//! defined here, together with CODEC_VERSION, form its public API.
 #![allow(dead_code)]
 #![cfg_attr(feature = "cargo-clippy", allow(clippy::range_plus_one))]
 
 use anyhow::{bail, Context as _, Error};
-use leb128;
 use mux::domain::DomainId;
 use mux::pane::PaneId;
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
@@ -23,237 +24,22 @@ use rangeset::*;
 use serde::{Deserialize, Serialize};
 use smol::io::AsyncWriteExt;
 use smol::prelude::*;
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::io::Cursor;
 use std::ops::Range;
 use std::sync::Arc;
+use std::time::Duration;
 use termwiz::hyperlink::Hyperlink;
 use termwiz::surface::Line;
 use varbincode;
 use wezterm_term::{ClipboardSelection, StableRowIndex};
 
-/// Returns the encoded length of the leb128 representation of value
-fn encoded_length(value: u64) -> usize {
-    struct NullWrite {};
-    impl std::io::Write for NullWrite {
-        fn write(&mut self, buf: &[u8]) -> std::result::Result<usize, std::io::Error> {
-            Ok(buf.len())
-        }
-        fn flush(&mut self) -> std::result::Result<(), std::io::Error> {
-            Ok(())
-        }
-    };
-
-    leb128::write::unsigned(&mut NullWrite {}, value).unwrap()
-}
-
-const COMPRESSED_MASK: u64 = 1 << 63;
-
-fn encode_raw_as_vec(
-    ident: u64,
-    serial: u64,
-    data: &[u8],
-    is_compressed: bool,
-) -> anyhow::Result<Vec<u8>> {
-    let len = data.len() + encoded_length(ident) + encoded_length(serial);
-    let masked_len = if is_compressed {
-        (len as u64) | COMPRESSED_MASK
-    } else {
-        len as u64
-    };
-
-    // Double-buffer the data; since we run with nodelay enabled, it is
-    // desirable for the write to be a single packet (or at least, for
-    // the header portion to go out in a single packet)
-    let mut buffer = Vec::with_capacity(len + encoded_length(masked_len));
-
-    leb128::write::unsigned(&mut buffer, masked_len).context("writing pdu len")?;
-    leb128::write::unsigned(&mut buffer, serial).context("writing pdu serial")?;
-    leb128::write::unsigned(&mut buffer, ident).context("writing pdu ident")?;
-    buffer.extend_from_slice(data);
-
-    if is_compressed {
-        metrics::histogram!("pdu.encode.compressed.size", buffer.len() as f64);
-    } else {
-        metrics::histogram!("pdu.encode.size", buffer.len() as f64);
-    }
-
-    Ok(buffer)
-}
-
-/// Encode a frame.  If the data is compressed, the high bit of the length
-/// is set to indicate that.  The data written out has the format:
-/// tagged_len: leb128  (u64 msb is set if data is compressed)
-/// serial: leb128
-/// ident: leb128
-/// data bytes
-fn encode_raw<W: std::io::Write>(
-    ident: u64,
-    serial: u64,
-    data: &[u8],
-    is_compressed: bool,
-    mut w: W,
-) -> anyhow::Result<usize> {
-    let buffer = encode_raw_as_vec(ident, serial, data, is_compressed)?;
-    w.write_all(&buffer).context("writing pdu data buffer")?;
-    Ok(buffer.len())
-}
-
-async fn encode_raw_async<W: Unpin + AsyncWriteExt>(
-    ident: u64,
-    serial: u64,
-    data: &[u8],
-    is_compressed: bool,
-    w: &mut W,
-) -> anyhow::Result<usize> {
-    let buffer = encode_raw_as_vec(ident, serial, data, is_compressed)?;
-    w.write_all(&buffer)
-        .await
-        .context("writing pdu data buffer")?;
-    Ok(buffer.len())
-}
-
-/// Read a single leb128 encoded value from the stream
-async fn read_u64_async<R>(r: &mut R) -> anyhow::Result<u64>
-where
-    R: Unpin + AsyncRead + std::fmt::Debug,
-{
-    let mut buf = vec![];
-    loop {
-        let mut byte = [0u8];
-        let nread = r.read(&mut byte).await?;
-        if nread == 0 {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::UnexpectedEof,
-                "EOF while reading leb128 encoded value",
-            )
-            .into());
-        }
-        buf.push(byte[0]);
-
-        match leb128::read::unsigned(&mut buf.as_slice()) {
-            Ok(n) => {
-                return Ok(n);
-            }
-            Err(leb128::read::Error::IoError(_)) => continue,
-            Err(leb128::read::Error::Overflow) => anyhow::bail!("leb128 is too large"),
-        }
-    }
-}
-
-/// Read a single leb128 encoded value from the stream
-fn read_u64<R: std::io::Read>(mut r: R) -> anyhow::Result<u64> {
-    leb128::read::unsigned(&mut r)
-        .map_err(|err| match err {
-            leb128::read::Error::IoError(ioerr) => anyhow::Error::new(ioerr),
-            err => anyhow::Error::new(err),
-        })
-        .context("reading leb128")
-}
-
-#[derive(Debug)]
-struct Decoded {
-    ident: u64,
-    serial: u64,
-    data: Vec<u8>,
-    is_compressed: bool,
-}
-
-/// Decode a frame.
-/// See encode_raw() for the frame format.
-async fn decode_raw_async<R: Unpin + AsyncRead + std::fmt::Debug>(
-    r: &mut R,
-) -> anyhow::Result<Decoded> {
-    let len = read_u64_async(r).await.context("reading PDU length")?;
-    let (len, is_compressed) = if (len & COMPRESSED_MASK) != 0 {
-        (len & !COMPRESSED_MASK, true)
-    } else {
-        (len, false)
-    };
-    let serial = read_u64_async(r).await.context("reading PDU serial")?;
-    let ident = read_u64_async(r).await.context("reading PDU ident")?;
-    let data_len =
-        match (len as usize).overflowing_sub(encoded_length(ident) + encoded_length(serial)) {
-            (_, true) => {
-                anyhow::bail!(
-                    "sizes don't make sense: len:{} serial:{} (enc={}) ident:{} (enc={})",
-                    len,
-                    serial,
-                    encoded_length(serial),
-                    ident,
-                    encoded_length(ident)
-                );
-            }
-            (data_len, false) => data_len,
-        };
-
-    if is_compressed {
-        metrics::histogram!("pdu.decode.compressed.size", data_len as f64);
-    } else {
-        metrics::histogram!("pdu.decode.size", data_len as f64);
-    }
-
-    let mut data = vec![0u8; data_len];
-    r.read_exact(&mut data).await.with_context(|| {
-        format!(
-            "reading {} bytes of data for PDU of length {} with serial={} ident={}",
-            data_len, len, serial, ident
-        )
-    })?;
-    Ok(Decoded {
-        ident,
-        serial,
-        data,
-        is_compressed,
-    })
-}
-
-/// Decode a frame.
-/// See encode_raw() for the frame format.
-fn decode_raw<R: std::io::Read>(mut r: R) -> anyhow::Result<Decoded> {
-    let len = read_u64(r.by_ref()).context("reading PDU length")?;
-    let (len, is_compressed) = if (len & COMPRESSED_MASK) != 0 {
-        (len & !COMPRESSED_MASK, true)
-    } else {
-        (len, false)
-    };
-    let serial = read_u64(r.by_ref()).context("reading PDU serial")?;
-    let ident = read_u64(r.by_ref()).context("reading PDU ident")?;
-    let data_len =
-        match (len as usize).overflowing_sub(encoded_length(ident) + encoded_length(serial)) {
-            (_, true) => {
-                anyhow::bail!(
-                    "sizes don't make sense: len:{} serial:{} (enc={}) ident:{} (enc={})",
-                    len,
-                    serial,
-                    encoded_length(serial),
-                    ident,
-                    encoded_length(ident)
-                );
-            }
-            (data_len, false) => data_len,
-        };
-
-    if is_compressed {
-        metrics::histogram!("pdu.decode.compressed.size", data_len as f64);
-    } else {
-        metrics::histogram!("pdu.decode.size", data_len as f64);
-    }
-
-    let mut data = vec![0u8; data_len];
-    r.read_exact(&mut data).with_context(|| {
-        format!(
-            "reading {} bytes of data for PDU of length {} with serial={} ident={}",
-            data_len, len, serial, ident
-        )
-    })?;
-    Ok(Decoded {
-        ident,
-        serial,
-        data,
-        is_compressed,
-    })
-}
+#[cfg(feature = "public-api")]
+pub mod frame;
+#[cfg(not(feature = "public-api"))]
+mod frame;
+use frame::*;
 
 #[derive(Debug, PartialEq)]
 pub struct DecodedPdu {
@@ -400,7 +186,7 @@ macro_rules! pdu {
 /// The overall version of the codec.
 /// This must be bumped when backwards incompatible changes
 /// are made to the types and protocol.
-pub const CODEC_VERSION: usize = 7;
+pub const CODEC_VERSION: usize = 20;
 
 // Defines the Pdu enum.
 // Each struct has an explicit identifying number.
@@ -434,6 +220,24 @@ pdu! {
     SearchScrollbackResponse: 32,
     SetPaneZoomed: 33,
     SplitPane: 34,
+    MovePaneToNewTab: 35,
+    MovePaneToNewTabResponse: 36,
+    SetPaneUserVar: 37,
+    GetMetrics: 38,
+    GetMetricsResponse: 39,
+    ShutdownServer: 40,
+    ServerShuttingDown: 41,
+    CreateInvite: 42,
+    CreateInviteResponse: 43,
+    RedeemInvite: 44,
+    MoveTabInWindow: 45,
+    ReadFile: 46,
+    ReadFileResponse: 47,
+    WriteFile: 48,
+    SetPaneOutputPipe: 49,
+    EraseScrollbackRequest: 50,
+    SpawnV2: 51,
+    MovePaneToSplit: 52,
 }
 
 impl Pdu {
@@ -531,6 +335,85 @@ pub struct GetCodecVersionResponse {
     pub version_string: String,
 }
 
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetMetrics {}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct GetMetricsResponse {
+    /// The metrics in the Prometheus text exposition format
+    pub metrics: String,
+}
+
+/// Asks the server to stop accepting new panes, close its
+/// existing panes and exit.  Panes that are still running
+/// once `grace` has elapsed are killed.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ShutdownServer {
+    pub grace: Duration,
+}
+
+/// Sent by the server to its clients when it begins to shut down
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ServerShuttingDown {
+    pub grace: Duration,
+}
+
+/// Asks the server to mint a one-time invitation token that
+/// allows a client without a certificate to connect to the TLS
+/// server and attach to the panes of `window_id`, or of all windows
+/// if it is None.  The token must be redeemed within `ttl`.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct CreateInvite {
+    pub window_id: Option<WindowId>,
+    pub read_only: bool,
+    pub ttl: Duration,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct CreateInviteResponse {
+    pub token: String,
+    /// The address on which the TLS server that accepts
+    /// the invitation is listening
+    pub bind_address: String,
+    /// The SHA-256 fingerprint of the certificate presented
+    /// by that server, hex encoded
+    pub fingerprint: String,
+}
+
+/// Sent by a client that connected without a certificate
+/// to authenticate using an invitation token
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct RedeemInvite {
+    pub token: String,
+}
+
+/// Reads up to `len` bytes, starting at `offset`, from a file on the
+/// host of the server.  A relative `path` is resolved against the home
+/// directory of the user running the server.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ReadFile {
+    pub path: String,
+    pub offset: u64,
+    pub len: u64,
+}
+
+/// Holds fewer than the requested number of bytes only when
+/// the end of the file has been reached
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct ReadFileResponse {
+    pub data: Vec<u8>,
+}
+
+/// Writes `data` at `offset` into a file on the host of the server.
+/// The file is created, or truncated, when `offset` is 0.  A relative
+/// `path` is resolved as for `ReadFile`.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct WriteFile {
+    pub path: String,
+    pub offset: u64,
+    pub data: Vec<u8>,
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct Ping {}
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
@@ -568,6 +451,20 @@ pub struct Spawn {
     pub size: PtySize,
 }
 
+/// Like `Spawn`, but the server resolves the domain, so that the
+/// client doesn't need to know the server's domain ids.
+/// If `window_id` is an existing window, the new tab is activated
+/// and the window is raised.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SpawnV2 {
+    pub domain: config::keyassignment::SpawnTabDomain,
+    /// If None, create a new window for this new tab
+    pub window_id: Option<WindowId>,
+    pub command: Option<CommandBuilder>,
+    pub command_dir: Option<String>,
+    pub size: PtySize,
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct SplitPane {
     pub pane_id: PaneId,
@@ -577,6 +474,60 @@ pub struct SplitPane {
     pub domain: config::keyassignment::SpawnTabDomain,
 }
 
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct MovePaneToNewTab {
+    pub pane_id: PaneId,
+    /// If None, create a new window for the new tab
+    pub window_id: Option<WindowId>,
+}
+
+/// Moves `pane_id` out of its tab and splits `target_pane_id` to make
+/// room for it.  The response is a `MovePaneToNewTabResponse` that
+/// identifies the tab that now contains the pane.
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct MovePaneToSplit {
+    pub pane_id: PaneId,
+    pub target_pane_id: PaneId,
+    pub direction: SplitDirection,
+}
+
+/// Moves the tab containing `pane_id` to `tab_idx` within its window
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct MoveTabInWindow {
+    pub pane_id: PaneId,
+    pub tab_idx: usize,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct MovePaneToNewTabResponse {
+    pub tab_id: TabId,
+    pub window_id: WindowId,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SetPaneUserVar {
+    pub pane_id: PaneId,
+    pub name: String,
+    pub value: String,
+}
+
+/// Starts sending a copy of the output of the pane to the stdin of
+/// `command`, which is run by the shell on the host of the server,
+/// or stops doing so when `command` is None
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct SetPaneOutputPipe {
+    pub pane_id: PaneId,
+    pub command: Option<String>,
+}
+
+/// Clears the scrollback and/or viewport of the pane, as requested
+/// by the `ClearScrollback` and `ClearScreen` key assignments
+#[derive(Deserialize, Serialize, PartialEq, Debug)]
+pub struct EraseScrollbackRequest {
+    pub pane_id: PaneId,
+    pub erase_mode: config::keyassignment::ScrollbackEraseMode,
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug)]
 pub struct SpawnResponse {
     pub tab_id: TabId,
@@ -680,11 +631,17 @@ pub struct LivenessResponse {
 pub struct GetPaneRenderChangesResponse {
     pub pane_id: PaneId,
     pub mouse_grabbed: bool,
+    /// Whether the output of the pane is being sent to a command,
+    /// as requested by `SetPaneOutputPipe`
+    pub output_piped: bool,
     pub cursor_position: StableCursorPosition,
     pub dimensions: RenderableDimensions,
     pub dirty_lines: Vec<Range<StableRowIndex>>,
     pub title: String,
     pub working_dir: Option<SerdeUrl>,
+    /// The user vars that were set by the application in the pane,
+    /// such as those that drive the status and badges in the tab bar
+    pub user_vars: HashMap<String, String>,
     /// Lines that the server thought we'd almost certainly
     /// want to fetch as soon as we received this response
     pub bonus_lines: SerializedLines,
@@ -892,6 +849,53 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_pdu_split_pane() {
+        use config::keyassignment::SpawnTabDomain;
+        for domain in vec![
+            SpawnTabDomain::DefaultDomain,
+            SpawnTabDomain::CurrentPaneDomain,
+            SpawnTabDomain::DomainName("prod-ssh".to_string()),
+        ] {
+            let pdu = Pdu::SplitPane(SplitPane {
+                pane_id: 1,
+                direction: SplitDirection::Horizontal,
+                command: None,
+                command_dir: Some("/tmp".to_string()),
+                domain,
+            });
+            let mut encoded = Vec::new();
+            pdu.encode(&mut encoded, 0x42).unwrap();
+            assert_eq!(
+                Pdu::decode(encoded.as_slice()).unwrap(),
+                DecodedPdu { serial: 0x42, pdu }
+            );
+        }
+    }
+
+    #[test]
+    fn test_pdu_spawn_v2() {
+        use config::keyassignment::SpawnTabDomain;
+        for domain in vec![
+            SpawnTabDomain::DefaultDomain,
+            SpawnTabDomain::DomainName("local".to_string()),
+        ] {
+            let pdu = Pdu::SpawnV2(SpawnV2 {
+                domain,
+                window_id: Some(3),
+                command: None,
+                command_dir: Some("/tmp".to_string()),
+                size: PtySize::default(),
+            });
+            let mut encoded = Vec::new();
+            pdu.encode(&mut encoded, 0x42).unwrap();
+            assert_eq!(
+                Pdu::decode(encoded.as_slice()).unwrap(),
+                DecodedPdu { serial: 0x42, pdu }
+            );
+        }
+    }
+
     #[test]
     fn stream_decode() {
         let mut encoded = Vec::new();
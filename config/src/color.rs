@@ -49,6 +49,10 @@ pub struct Palette {
     pub scrollbar_thumb: Option<RgbColor>,
     /// The color of the split line between panes
     pub split: Option<RgbColor>,
+    /// The color of the border around the terminal area that is
+    /// enabled by `window_frame.border_thickness`.
+    /// Defaults to the `split` color.
+    pub border: Option<RgbColor>,
 }
 impl_lua_conversion!(Palette);
 
@@ -71,6 +75,8 @@ impl From<Palette> for wezterm_term::color::ColorPalette {
         apply_color!(selection_bg);
         apply_color!(scrollbar_thumb);
         apply_color!(split);
+        // `border` is only used by the GUI to draw the window frame,
+        // so it is not part of the terminal palette
 
         if let Some(ansi) = cfg.ansi {
             for (idx, col) in ansi.iter().enumerate() {
@@ -86,6 +92,48 @@ impl From<Palette> for wezterm_term::color::ColorPalette {
     }
 }
 
+impl Palette {
+    /// The palette used in high contrast mode when `high_contrast_colors`
+    /// is not specified: white text on a black background, with ANSI
+    /// colors that are saturated enough to remain legible against it.
+    pub fn high_contrast() -> Self {
+        let rgb = |red, green, blue| RgbColor::new(red, green, blue);
+        Self {
+            foreground: Some(rgb(0xff, 0xff, 0xff)),
+            background: Some(rgb(0x00, 0x00, 0x00)),
+            cursor_fg: Some(rgb(0x00, 0x00, 0x00)),
+            cursor_bg: Some(rgb(0xff, 0xff, 0x00)),
+            cursor_border: Some(rgb(0xff, 0xff, 0x00)),
+            selection_fg: Some(rgb(0x00, 0x00, 0x00)),
+            selection_bg: Some(rgb(0x00, 0xff, 0xff)),
+            ansi: Some([
+                rgb(0x00, 0x00, 0x00),
+                rgb(0xff, 0x40, 0x40),
+                rgb(0x00, 0xff, 0x00),
+                rgb(0xff, 0xff, 0x00),
+                rgb(0x40, 0x80, 0xff),
+                rgb(0xff, 0x40, 0xff),
+                rgb(0x00, 0xff, 0xff),
+                rgb(0xff, 0xff, 0xff),
+            ]),
+            brights: Some([
+                rgb(0x80, 0x80, 0x80),
+                rgb(0xff, 0x80, 0x80),
+                rgb(0x80, 0xff, 0x80),
+                rgb(0xff, 0xff, 0x80),
+                rgb(0x80, 0xb0, 0xff),
+                rgb(0xff, 0x80, 0xff),
+                rgb(0x80, 0xff, 0xff),
+                rgb(0xff, 0xff, 0xff),
+            ]),
+            tab_bar: None,
+            scrollbar_thumb: Some(rgb(0xff, 0xff, 0xff)),
+            split: Some(rgb(0xff, 0xff, 0xff)),
+            border: Some(rgb(0xff, 0xff, 0xff)),
+        }
+    }
+}
+
 /// Specify the text styling for a tab in the tab bar
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct TabBarColor {
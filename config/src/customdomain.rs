@@ -0,0 +1,29 @@
+use crate::*;
+use std::collections::HashMap;
+
+/// Describes a domain whose implementation is provided by a domain
+/// type that has been registered with the mux, rather than one of
+/// the built-in ssh, tls or unix domains.
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct CustomDomain {
+    /// The name of this specific domain.  Must be unique amongst
+    /// all types of domain in the configuration file.
+    pub name: String,
+
+    /// The name of the registered domain type that implements
+    /// this domain
+    pub domain_type: String,
+
+    /// Parameters that are interpreted by the domain type
+    #[serde(default)]
+    pub options: HashMap<String, String>,
+
+    /// The program to run in new panes when none is specified.
+    /// How this is interpreted depends upon the domain type.
+    pub default_prog: Option<Vec<String>>,
+
+    /// The working directory for new panes when none is specified.
+    /// How this is interpreted depends upon the domain type.
+    pub default_cwd: Option<PathBuf>,
+}
+impl_lua_conversion!(CustomDomain);
@@ -0,0 +1,70 @@
+use crate::*;
+
+/// Adjusts the environment of the commands that are spawned in a
+/// particular domain, so that variables such as `SSH_AUTH_SOCK` or
+/// proxy settings that make sense on one host don't leak into another
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct DomainEnvironment {
+    /// Variables that are removed, so that the value that would
+    /// otherwise be inherited from wezterm is not passed on
+    #[serde(default)]
+    pub unset_environment_variables: Vec<String>,
+
+    /// Variables that are set, taking precedence over both
+    /// `set_environment_variables` and those specified by the
+    /// command being spawned
+    #[serde(default)]
+    pub set_environment_variables: HashMap<String, String>,
+}
+impl_lua_conversion!(DomainEnvironment);
+
+impl DomainEnvironment {
+    pub fn apply_to_command(&self, cmd: &mut CommandBuilder) {
+        for k in &self.unset_environment_variables {
+            cmd.env_remove(k);
+        }
+        for (k, v) in &self.set_environment_variables {
+            cmd.env(k, v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ffi::OsStr;
+
+    #[test]
+    fn apply_to_command() {
+        let config: Config = toml::from_str(
+            r#"
+            [set_environment_variables]
+            http_proxy = "http://proxy.local"
+
+            [domain_environment.local]
+            unset_environment_variables = ["SSH_AUTH_SOCK", "http_proxy"]
+
+            [domain_environment.local.set_environment_variables]
+            LANG = "C.UTF-8"
+            "#,
+        )
+        .unwrap();
+
+        let mut cmd = CommandBuilder::new("true");
+        cmd.env("SSH_AUTH_SOCK", "/tmp/agent");
+        config.apply_environment_variables(&mut cmd);
+        config.apply_domain_environment("local", &mut cmd);
+
+        assert_eq!(cmd.get_env("SSH_AUTH_SOCK"), None);
+        assert_eq!(cmd.get_env("http_proxy"), None);
+        assert_eq!(cmd.get_env("LANG"), Some(OsStr::new("C.UTF-8")));
+        assert_eq!(
+            cmd.iter_removed_env().collect::<Vec<_>>(),
+            vec![OsStr::new("SSH_AUTH_SOCK"), OsStr::new("http_proxy")]
+        );
+
+        let mut cmd = CommandBuilder::new("true");
+        config.apply_domain_environment("other", &mut cmd);
+        assert_eq!(cmd.iter_removed_env().count(), 0);
+    }
+}
@@ -117,7 +117,7 @@ impl Default for FontAntiAliasing {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FontAttributes {
     /// The font family name
     pub family: String,
@@ -128,9 +128,45 @@ pub struct FontAttributes {
     #[serde(default)]
     pub italic: bool,
     pub is_fallback: bool,
+    /// Scales the glyphs from this font, relative to the size
+    /// that they would otherwise be rendered at.
+    /// Useful for icon fonts whose glyphs are too large or
+    /// too small compared to the primary font.
+    #[serde(default)]
+    pub scale: Option<f64>,
+    /// Moves the glyphs from this font up (if positive) or
+    /// down (if negative), as a fraction of the cell height.
+    #[serde(default)]
+    pub baseline_offset: Option<f64>,
 }
 impl_lua_conversion!(FontAttributes);
 
+// f64 is neither Eq nor Hash, so compare and hash the adjustments
+// by their bit patterns; they are only ever compared to determine
+// whether the configuration has changed.
+impl PartialEq for FontAttributes {
+    fn eq(&self, other: &Self) -> bool {
+        self.family == other.family
+            && self.bold == other.bold
+            && self.italic == other.italic
+            && self.is_fallback == other.is_fallback
+            && self.scale.map(f64::to_bits) == other.scale.map(f64::to_bits)
+            && self.baseline_offset.map(f64::to_bits) == other.baseline_offset.map(f64::to_bits)
+    }
+}
+impl Eq for FontAttributes {}
+
+impl std::hash::Hash for FontAttributes {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.family.hash(state);
+        self.bold.hash(state);
+        self.italic.hash(state);
+        self.is_fallback.hash(state);
+        self.scale.map(f64::to_bits).hash(state);
+        self.baseline_offset.map(f64::to_bits).hash(state);
+    }
+}
+
 impl std::fmt::Display for FontAttributes {
     fn fmt(&self, fmt: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
         write!(
@@ -148,6 +184,8 @@ impl FontAttributes {
             bold: false,
             italic: false,
             is_fallback: false,
+            scale: None,
+            baseline_offset: None,
         }
     }
 
@@ -157,6 +195,8 @@ impl FontAttributes {
             bold: false,
             italic: false,
             is_fallback: true,
+            scale: None,
+            baseline_offset: None,
         }
     }
 }
@@ -168,6 +208,8 @@ impl Default for FontAttributes {
             bold: false,
             italic: false,
             is_fallback: false,
+            scale: None,
+            baseline_offset: None,
         }
     }
 }
@@ -183,6 +225,13 @@ pub struct TextStyle {
     /// useful in a `[[font_rules]]` section to implement changing
     /// the text color for eg: bold text.
     pub foreground: Option<RgbColor>,
+
+    /// If set, the font is loaded at this size, in hundredths of a point,
+    /// rather than at font_size scaled by the current zoom level.
+    /// This isn't read from the configuration; it is set for the
+    /// window frame font when `window_frame.font_size` is specified.
+    #[serde(skip)]
+    pub fixed_size: Option<u32>,
 }
 impl_lua_conversion!(TextStyle);
 
@@ -191,6 +240,7 @@ impl Default for TextStyle {
         Self {
             foreground: None,
             font: vec![FontAttributes::default()],
+            fixed_size: None,
         }
     }
 }
@@ -229,6 +279,7 @@ impl TextStyle {
         }
         Self {
             foreground: self.foreground,
+            fixed_size: self.fixed_size,
             font: self
                 .font
                 .iter()
@@ -248,6 +299,7 @@ impl TextStyle {
     pub fn make_bold(&self) -> Self {
         Self {
             foreground: self.foreground,
+            fixed_size: self.fixed_size,
             font: self
                 .font
                 .iter()
@@ -264,6 +316,7 @@ impl TextStyle {
     pub fn make_italic(&self) -> Self {
         Self {
             foreground: self.foreground,
+            fixed_size: self.fixed_size,
             font: self
                 .font
                 .iter()
@@ -0,0 +1,75 @@
+use luahelper::impl_lua_conversion;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use termwiz::color::{ColorAttribute, RgbColor};
+use wezterm_term::CellAttributes;
+
+/// A rule that restyles text in the viewport that matches `regex`,
+/// regardless of the attributes set by the application that output it.
+/// The rules are applied when rendering, so the content of the
+/// scrollback is unaffected.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HighlightRule {
+    /// The regex to match against each line in the viewport
+    #[serde(
+        deserialize_with = "deserialize_regex",
+        serialize_with = "serialize_regex"
+    )]
+    pub regex: Regex,
+    /// If present, the text color of the matching text
+    pub foreground: Option<RgbColor>,
+    /// If present, the background color of the matching text
+    pub background: Option<RgbColor>,
+    /// If present, the intensity of the matching text.
+    /// Valid values are "Bold", "Normal", "Half".
+    pub intensity: Option<wezterm_term::Intensity>,
+    /// If present, the underline style of the matching text.
+    /// Valid values are "None", "Single", "Double".
+    pub underline: Option<wezterm_term::Underline>,
+    pub italic: Option<bool>,
+    pub reverse: Option<bool>,
+    pub strikethrough: Option<bool>,
+}
+impl_lua_conversion!(HighlightRule);
+
+impl HighlightRule {
+    /// Applies the styling of this rule to `attrs`
+    pub fn apply(&self, attrs: &mut CellAttributes) {
+        if let Some(fg) = self.foreground {
+            attrs.set_foreground(ColorAttribute::TrueColorWithDefaultFallback(fg));
+        }
+        if let Some(bg) = self.background {
+            attrs.set_background(ColorAttribute::TrueColorWithDefaultFallback(bg));
+        }
+        if let Some(intensity) = self.intensity {
+            attrs.set_intensity(intensity);
+        }
+        if let Some(underline) = self.underline {
+            attrs.set_underline(underline);
+        }
+        if let Some(italic) = self.italic {
+            attrs.set_italic(italic);
+        }
+        if let Some(reverse) = self.reverse {
+            attrs.set_reverse(reverse);
+        }
+        if let Some(strikethrough) = self.strikethrough {
+            attrs.set_strikethrough(strikethrough);
+        }
+    }
+}
+
+fn deserialize_regex<'de, D>(deserializer: D) -> Result<Regex, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    Regex::new(&s).map_err(|e| serde::de::Error::custom(format!("{:?}", e)))
+}
+
+fn serialize_regex<S>(regex: &Regex, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    regex.as_str().serialize(serializer)
+}
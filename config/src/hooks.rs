@@ -0,0 +1,26 @@
+use crate::*;
+
+/// The events for which a hook command can be run
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum HookEvent {
+    /// The bell was rung in a pane
+    Bell,
+    /// The title of a pane was changed by the application running in it
+    TitleChanged,
+    /// The program running in a pane exited
+    ChildExited,
+    /// A multiplexer domain was connected
+    DomainAttached,
+    /// A multiplexer domain was disconnected
+    DomainDetached,
+}
+
+/// A command to run when an event occurs.
+/// A JSON object describing the event is written to its stdin.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EventHook {
+    pub event: HookEvent,
+    /// The command and its arguments
+    pub args: Vec<String>,
+}
+impl_lua_conversion!(EventHook);
@@ -0,0 +1,163 @@
+//! Implements the `include` configuration option, which merges
+//! additional TOML configuration fragments into the configuration
+//! returned by the main config file.
+use crate::Config;
+use anyhow::{bail, Context};
+use luahelper::JsonLua;
+use mlua::{Lua, Table, ToLua, Value};
+use std::path::{Path, PathBuf};
+
+/// Resolves the `include` patterns of `config` relative to `config_dir`
+/// and merges the fragments that they match into it.
+/// Returns the merged configuration along with the paths of the
+/// fragments, in the order that they were applied.
+pub(crate) fn apply_includes<'l>(
+    lua: &'l Lua,
+    config: Value<'l>,
+    config_dir: &Path,
+) -> anyhow::Result<(Value<'l>, Vec<PathBuf>)> {
+    let table = match &config {
+        Value::Table(table) => table.clone(),
+        _ => return Ok((config, vec![])),
+    };
+    let patterns: Vec<String> = match table.get::<_, Option<Vec<String>>>("include") {
+        Ok(Some(patterns)) => patterns,
+        Ok(None) => return Ok((config, vec![])),
+        Err(err) => bail!("`include` must be a list of file names: {}", err),
+    };
+
+    let paths = resolve_patterns(&patterns, config_dir)?;
+
+    let mut merged = serde_json::Value::Object(Default::default());
+    for path in &paths {
+        let fragment = load_fragment(lua, path)?;
+        merge_json(&mut merged, fragment);
+    }
+
+    // The main config file takes precedence over the fragments
+    let base = match JsonLua(merged).to_lua(lua)? {
+        Value::Table(base) => base,
+        _ => unreachable!("merged fragments are always a table"),
+    };
+    merge_lua(&base, &table)?;
+
+    Ok((Value::Table(base), paths))
+}
+
+/// Expands the patterns into the list of fragment paths.  The matches
+/// of each pattern are sorted so that the order in which the fragments
+/// are applied doesn't depend upon the filesystem.
+fn resolve_patterns(patterns: &[String], config_dir: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let mut paths: Vec<PathBuf> = vec![];
+    for pattern in patterns {
+        let glob = filenamegen::Glob::new(pattern)
+            .with_context(|| format!("invalid include pattern `{}`", pattern))?;
+        let mut matches: Vec<PathBuf> = glob
+            .walk(config_dir)
+            .map(|path| config_dir.join(path))
+            .collect();
+        matches.sort();
+
+        if matches.is_empty() && !pattern.contains(|c| "*?[{".contains(c)) {
+            bail!(
+                "included config file {} was not found",
+                config_dir.join(pattern).display()
+            );
+        }
+
+        for path in matches {
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    Ok(paths)
+}
+
+/// Parses a fragment and checks that it is a valid partial
+/// configuration, so that any problem is attributed to it
+fn load_fragment(lua: &Lua, path: &Path) -> anyhow::Result<serde_json::Value> {
+    let s = std::fs::read_to_string(path)
+        .with_context(|| format!("Error reading included config {}", path.display()))?;
+    let fragment: toml::Value = toml::from_str(&s)
+        .with_context(|| format!("Error parsing included config {}", path.display()))?;
+    let fragment = serde_json::to_value(fragment)?;
+
+    if fragment.get("include").is_some() {
+        bail!(
+            "included config {} cannot itself use `include`",
+            path.display()
+        );
+    }
+
+    let value = JsonLua(fragment.clone()).to_lua(lua)?;
+    luahelper::from_lua_value::<Config>(value)
+        .with_context(|| format!("Error in included config {}", path.display()))?;
+
+    Ok(fragment)
+}
+
+/// Merges `src` into `dest`.  Tables are merged key by key, while any
+/// other value, including an array, replaces the value in `dest`.
+fn merge_json(dest: &mut serde_json::Value, src: serde_json::Value) {
+    match (dest, src) {
+        (serde_json::Value::Object(dest), serde_json::Value::Object(src)) => {
+            for (key, value) in src {
+                match dest.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        dest.insert(key, value);
+                    }
+                }
+            }
+        }
+        (dest, src) => *dest = src,
+    }
+}
+
+fn is_map(table: &Table) -> bool {
+    table.raw_len() == 0
+}
+
+/// The lua equivalent of `merge_json`; a table with array
+/// elements is treated as a value rather than merged
+pub(crate) fn merge_lua<'l>(dest: &Table<'l>, src: &Table<'l>) -> mlua::Result<()> {
+    for pair in src.clone().pairs::<Value, Value>() {
+        let (key, value) = pair?;
+        if let (Value::Table(existing), Value::Table(value)) = (dest.raw_get(key.clone())?, &value)
+        {
+            if is_map(&existing) && is_map(value) {
+                merge_lua(&existing, value)?;
+                continue;
+            }
+        }
+        dest.raw_set(key, value)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn merge() {
+        let mut merged = json!({});
+        merge_json(
+            &mut merged,
+            json!({"font_size": 12, "colors": {"foreground": "white", "ansi": ["a", "b"]}}),
+        );
+        merge_json(
+            &mut merged,
+            json!({"colors": {"background": "black", "ansi": ["c"]}}),
+        );
+        assert_eq!(
+            merged,
+            json!({
+                "font_size": 12,
+                "colors": {"foreground": "white", "background": "black", "ansi": ["c"]}
+            })
+        );
+    }
+}
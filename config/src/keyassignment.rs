@@ -1,7 +1,8 @@
 use crate::configuration;
-use crate::LeaderKey;
+use crate::{Config, LeaderKey};
 use luahelper::impl_lua_conversion;
-use serde::{Deserialize, Serialize};
+use serde::de::{MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use wezterm_input_types::{KeyCode, Modifiers};
@@ -75,6 +76,54 @@ impl Default for SpawnTabDomain {
     }
 }
 
+/// In addition to the usual `{DomainName="name"}` form, allows
+/// a domain to be specified by its name alone.
+/// This is only used for fields that are read from the configuration;
+/// `SpawnTabDomain` itself keeps the derived implementation because it
+/// is also sent over the mux protocol, whose encoding doesn't support
+/// `deserialize_any`.
+fn deserialize_spawn_tab_domain<'de, D>(deserializer: D) -> Result<SpawnTabDomain, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct DomainVisitor;
+
+    impl<'de> Visitor<'de> for DomainVisitor {
+        type Value = SpawnTabDomain;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str(
+                "\"DefaultDomain\", \"CurrentPaneDomain\", \
+                 a domain name or {DomainName=\"name\"}",
+            )
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<SpawnTabDomain, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(match value {
+                "DefaultDomain" => SpawnTabDomain::DefaultDomain,
+                "CurrentPaneDomain" => SpawnTabDomain::CurrentPaneDomain,
+                name => SpawnTabDomain::DomainName(name.to_string()),
+            })
+        }
+
+        fn visit_map<A>(self, mut map: A) -> Result<SpawnTabDomain, A::Error>
+        where
+            A: MapAccess<'de>,
+        {
+            match map.next_key::<String>()?.as_deref() {
+                Some("DomainName") => Ok(SpawnTabDomain::DomainName(map.next_value()?)),
+                Some(key) => Err(serde::de::Error::unknown_variant(key, &["DomainName"])),
+                None => Err(serde::de::Error::invalid_length(0, &self)),
+            }
+        }
+    }
+
+    deserializer.deserialize_any(DomainVisitor)
+}
+
 #[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub struct SpawnCommand {
     /// Optional descriptive label
@@ -99,10 +148,43 @@ pub struct SpawnCommand {
     #[serde(default)]
     pub set_environment_variables: HashMap<String, String>,
 
-    #[serde(default)]
+    #[serde(default, deserialize_with = "deserialize_spawn_tab_domain")]
     pub domain: SpawnTabDomain,
 }
 
+/// What to do with the output of a `RunProgram` command
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum RunProgramOutput {
+    /// Discard the output
+    Discard,
+    /// Paste the output into the pane that was active when
+    /// the program was run
+    PasteIntoPane,
+    /// Run the program in a new tab so that its output is shown there
+    NewTab,
+}
+
+impl Default for RunProgramOutput {
+    fn default() -> Self {
+        Self::Discard
+    }
+}
+
+/// The argument to `RunProgram`: a program that is run on the host
+/// with environment variables that describe the active pane
+#[derive(Default, Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct RunProgram {
+    /// The command line to run
+    pub args: Vec<String>,
+
+    /// Additional environment variables to set for the program
+    #[serde(default)]
+    pub set_environment_variables: HashMap<String, String>,
+
+    #[serde(default)]
+    pub output: RunProgramOutput,
+}
+
 #[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
 pub enum PaneDirection {
     Up,
@@ -115,6 +197,7 @@ pub enum PaneDirection {
 pub enum ScrollbackEraseMode {
     ScrollbackOnly,
     ScrollbackAndViewport,
+    ViewportOnly,
 }
 
 impl Default for ScrollbackEraseMode {
@@ -150,9 +233,11 @@ impl Default for ClipboardPasteSource {
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
 pub enum KeyAssignment {
-    SpawnTab(SpawnTabDomain),
+    SpawnTab(#[serde(deserialize_with = "deserialize_spawn_tab_domain")] SpawnTabDomain),
     SpawnWindow,
     ToggleFullScreen,
+    ToggleAlwaysOnTop,
+    ToggleVisibleOnAllWorkspaces,
     Copy,
     CopyTo(ClipboardCopyDestination),
     Paste,
@@ -164,6 +249,7 @@ pub enum KeyAssignment {
     ResetFontSize,
     ResetFontAndWindowSize,
     ActivateTab(isize),
+    ActivateLastTab,
     SendString(String),
     Nop,
     DisableDefaultAssignment,
@@ -184,7 +270,9 @@ pub enum KeyAssignment {
     SplitHorizontal(SpawnCommand),
     SplitVertical(SpawnCommand),
     ShowLauncher,
+    CharSelect,
     ClearScrollback(ScrollbackEraseMode),
+    ClearScreen,
     Search(Pattern),
     ActivateCopyMode,
 
@@ -198,8 +286,20 @@ pub enum KeyAssignment {
     AdjustPaneSize(PaneDirection, usize),
     ActivatePaneDirection(PaneDirection),
     TogglePaneZoomState,
+    ToggleInvisibleCharacters,
     CloseCurrentPane { confirm: bool },
     EmitEvent(String),
+    AttachDomain(String),
+    DetachDomain(#[serde(deserialize_with = "deserialize_spawn_tab_domain")] SpawnTabDomain),
+    ShowDomains,
+    ToggleInputLock,
+    ToggleClientInputLock,
+    ShowDebugOverlay,
+    TogglePersistScrollback,
+    PreviewTheme,
+    QuickSelect,
+    TogglePaneOutputPipe(String),
+    RunProgram(RunProgram),
 }
 impl_lua_conversion!(KeyAssignment);
 
@@ -211,7 +311,14 @@ pub struct InputMap {
 
 impl InputMap {
     pub fn new() -> Self {
-        let config = configuration();
+        Self::from_config(&configuration())
+    }
+
+    /// Combines the assignments from `config` with the defaults, unless
+    /// they have been disabled.  An assignment in `config` takes
+    /// precedence over the default for the same keys, and
+    /// `DisableDefaultAssignment` removes the default entirely.
+    pub fn from_config(config: &Config) -> Self {
         let mut mouse = config.mouse_bindings();
 
         let mut keys = config.key_bindings();
@@ -378,6 +485,8 @@ impl InputMap {
                 [Modifiers::SHIFT, KeyCode::PageDown, ScrollByPage(1)],
                 [Modifiers::ALT, KeyCode::Char('9'), ShowTabNavigator],
                 [Modifiers::CTRL, KeyCode::Char('X'), ActivateCopyMode],
+                [ctrl_shift, KeyCode::Char(' '), QuickSelect],
+                [Modifiers::CTRL, KeyCode::Char('U'), CharSelect],
                 [
                     Modifiers::CTRL | Modifiers::ALT | Modifiers::SHIFT,
                     KeyCode::Char('"'),
@@ -589,3 +698,128 @@ impl InputMap {
             .cloned()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn spawn_domain() {
+        let parse = |s: &str| toml::from_str::<SpawnCommand>(s).unwrap().domain;
+        assert_eq!(parse(""), SpawnTabDomain::CurrentPaneDomain);
+        assert_eq!(
+            parse("domain = \"DefaultDomain\""),
+            SpawnTabDomain::DefaultDomain
+        );
+        assert_eq!(
+            parse("domain = \"prod-ssh\""),
+            SpawnTabDomain::DomainName("prod-ssh".to_string())
+        );
+        assert_eq!(
+            parse("domain = { DomainName = \"prod-ssh\" }"),
+            SpawnTabDomain::DomainName("prod-ssh".to_string())
+        );
+    }
+
+    #[test]
+    fn run_program() {
+        let parse = |s: &str| toml::from_str::<RunProgram>(s).unwrap();
+        assert_eq!(
+            parse("args = [\"git\", \"status\"]"),
+            RunProgram {
+                args: vec!["git".to_string(), "status".to_string()],
+                set_environment_variables: HashMap::new(),
+                output: RunProgramOutput::Discard,
+            }
+        );
+
+        let program = parse(
+            "args = [\"vim\", \"-\"]\n\
+             output = \"NewTab\"\n\
+             set_environment_variables = { EDITOR = \"vim\" }",
+        );
+        assert_eq!(program.output, RunProgramOutput::NewTab);
+        assert_eq!(
+            program.set_environment_variables.get("EDITOR"),
+            Some(&"vim".to_string())
+        );
+
+        assert!(toml::from_str::<RunProgram>("output = \"NewTab\"").is_err());
+        assert!(toml::from_str::<RunProgram>("args = []\noutput = \"Bogus\"").is_err());
+    }
+
+    #[test]
+    fn default_key_bindings() {
+        use KeyAssignment::*;
+        let input_map = |s: &str| InputMap::from_config(&toml::from_str::<Config>(s).unwrap());
+        let ctrl_shift = Modifiers::CTRL | Modifiers::SHIFT;
+
+        let map = input_map("");
+        assert_eq!(
+            map.lookup_key(&KeyCode::Char('T'), ctrl_shift),
+            Some(SpawnTab(SpawnTabDomain::CurrentPaneDomain))
+        );
+
+        let map = input_map(
+            "keys = [\n\
+             { key = \"T\", mods = \"CTRL|SHIFT\", action = \"DisableDefaultAssignment\" },\n\
+             { key = \"N\", mods = \"CTRL|SHIFT\", action = \"Nop\" },\n\
+             ]",
+        );
+        assert_eq!(map.lookup_key(&KeyCode::Char('T'), ctrl_shift), None);
+        assert_eq!(map.lookup_key(&KeyCode::Char('N'), ctrl_shift), Some(Nop));
+        assert_eq!(
+            map.lookup_key(&KeyCode::Char('C'), ctrl_shift),
+            Some(CopyTo(ClipboardCopyDestination::Clipboard))
+        );
+
+        let map = input_map(
+            "disable_default_key_bindings = true\n\
+             keys = [{ key = \"N\", mods = \"CTRL|SHIFT\", action = \"SpawnWindow\" }]",
+        );
+        assert_eq!(map.lookup_key(&KeyCode::Char('C'), ctrl_shift), None);
+        assert_eq!(
+            map.lookup_key(&KeyCode::Char('N'), ctrl_shift),
+            Some(SpawnWindow)
+        );
+    }
+
+    #[test]
+    fn spawn_assignments() {
+        #[derive(Deserialize)]
+        struct Action {
+            action: KeyAssignment,
+        }
+        let parse = |s: &str| toml::from_str::<Action>(s).unwrap().action;
+        assert_eq!(parse("action = \"SpawnWindow\""), SpawnWindow);
+        assert_eq!(
+            parse("action = { SpawnTab = \"CurrentPaneDomain\" }"),
+            SpawnTab(SpawnTabDomain::CurrentPaneDomain)
+        );
+        assert_eq!(
+            parse("action = { SpawnTab = \"prod-ssh\" }"),
+            SpawnTab(SpawnTabDomain::DomainName("prod-ssh".to_string()))
+        );
+        assert_eq!(
+            parse("action = { SpawnTab = { DomainName = \"prod-ssh\" } }"),
+            SpawnTab(SpawnTabDomain::DomainName("prod-ssh".to_string()))
+        );
+        assert_eq!(
+            parse(
+                "action = { SpawnCommandInNewTab = { args = [\"top\"], domain = \"prod-ssh\" } }"
+            ),
+            SpawnCommandInNewTab(SpawnCommand {
+                args: Some(vec!["top".to_string()]),
+                domain: SpawnTabDomain::DomainName("prod-ssh".to_string()),
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse("action = { SpawnCommandInNewWindow = { cwd = \"/tmp\" } }"),
+            SpawnCommandInNewWindow(SpawnCommand {
+                cwd: Some("/tmp".into()),
+                ..Default::default()
+            })
+        );
+    }
+}
@@ -0,0 +1,52 @@
+use crate::keyassignment::SpawnCommand;
+use crate::*;
+
+/// Describes a window to create when wezterm starts up
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct StartupWindow {
+    /// The tabs to create in this window, from left to right
+    #[serde(default)]
+    pub tabs: Vec<StartupTab>,
+}
+impl_lua_conversion!(StartupWindow);
+
+/// Describes a tab to create when wezterm starts up
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct StartupTab {
+    /// The panes to create in this tab.  The first pane occupies
+    /// the whole tab; each subsequent pane is created by splitting
+    /// the pane that was created before it.
+    /// If empty, the tab holds a single pane running the default
+    /// program.
+    #[serde(default)]
+    pub panes: Vec<StartupPane>,
+}
+impl_lua_conversion!(StartupTab);
+
+/// Describes a pane to create when wezterm starts up
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct StartupPane {
+    /// How to split the prior pane to make room for this one.
+    /// Ignored for the first pane in a tab.
+    #[serde(default)]
+    pub split: StartupSplit,
+
+    /// What to run in the pane, and where.
+    #[serde(flatten)]
+    pub command: SpawnCommand,
+}
+impl_lua_conversion!(StartupPane);
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq)]
+pub enum StartupSplit {
+    /// The new pane is placed to the right of the prior pane
+    Horizontal,
+    /// The new pane is placed below the prior pane
+    Vertical,
+}
+
+impl Default for StartupSplit {
+    fn default() -> Self {
+        Self::Horizontal
+    }
+}
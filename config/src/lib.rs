@@ -25,27 +25,45 @@ use std::time::Duration;
 use termwiz::hyperlink;
 use termwiz::surface::CursorShape;
 use toml;
-use wezterm_input_types::{KeyCode, Modifiers, WindowDecorations};
+use wezterm_input_types::{
+    GpuPowerPreference, KeyCode, MacOsTitlebarAppearance, Modifiers, WindowBackgroundMaterial,
+    WindowDecorations,
+};
 
 mod color;
+mod customdomain;
 mod daemon;
+mod environment;
 mod font;
 mod frontend;
+mod highlight;
+mod hooks;
+mod include;
 pub mod keyassignment;
 mod keys;
+mod layout;
 pub mod lua;
+mod overrides;
+mod platform;
 mod ssh;
+mod starter;
 mod terminal;
 mod tls;
 mod unix;
 mod version;
 
 pub use color::*;
+pub use customdomain::*;
 pub use daemon::*;
+pub use environment::*;
 pub use font::*;
 pub use frontend::*;
+pub use highlight::*;
+pub use hooks::*;
 pub use keys::*;
+pub use layout::*;
 pub use ssh::*;
+pub use starter::*;
 pub use terminal::*;
 pub use tls::*;
 pub use unix::*;
@@ -53,11 +71,22 @@ pub use version::*;
 
 type LuaFactory = fn(&Path) -> anyhow::Result<Lua>;
 type ErrorCallback = fn(&str);
+type ReloadSubscriber = Box<dyn Fn() -> bool + Send>;
 
 lazy_static! {
     pub static ref HOME_DIR: PathBuf = dirs_next::home_dir().expect("can't find HOME dir");
+    /// In portable mode, the directory that holds the executable,
+    /// alongside which the config and all of the files that wezterm
+    /// writes are kept
+    pub static ref PORTABLE_DIR: Option<PathBuf> = compute_portable_dir();
     pub static ref CONFIG_DIR: PathBuf = xdg_config_home();
     pub static ref RUNTIME_DIR: PathBuf = compute_runtime_dir().unwrap();
+    pub static ref DATA_DIR: PathBuf = compute_data_dir();
+    /// Holds files that can be regenerated if they are removed
+    pub static ref CACHE_DIR: PathBuf = compute_cache_dir();
+    /// Holds state that persists across restarts, such as the
+    /// saved scrollback and log files
+    pub static ref STATE_DIR: PathBuf = compute_state_dir();
     static ref CONFIG: Configuration = Configuration::new();
     static ref CONFIG_FILE_OVERRIDE: Mutex<Option<PathBuf>> = Mutex::new(None);
     static ref CONFIG_OVERRIDES: Mutex<Vec<(String, String)>> = Mutex::new(vec![]);
@@ -65,7 +94,17 @@ lazy_static! {
     static ref SHOW_ERROR: Mutex<Option<ErrorCallback>> =
         Mutex::new(Some(|e| log::error!("{}", e)));
     static ref LUA_PIPE: LuaPipe = LuaPipe::new();
-    static ref COLOR_SCHEMES: HashMap<String, Palette> = build_default_schemes();
+    static ref RELOAD_SUBSCRIBERS: Mutex<Vec<ReloadSubscriber>> = Mutex::new(vec![]);
+    /// The data of the built in color schemes, keyed by name.
+    /// Parsing all of them is comparatively slow, so each one is
+    /// only parsed when it is first used; see `builtin_color_scheme`
+    static ref BUILTIN_SCHEMES: HashMap<&'static str, &'static str> =
+        SCHEMES.iter().cloned().collect();
+    static ref PARSED_SCHEMES: Mutex<HashMap<&'static str, &'static Palette>> =
+        Mutex::new(HashMap::new());
+    /// The defaults of all of the options, which are the same
+    /// every time that they are computed
+    static ref DEFAULT_CONFIG: Config = toml::from_str("").unwrap();
 }
 
 thread_local! {
@@ -82,6 +121,20 @@ pub fn build_default_schemes() -> HashMap<String, Palette> {
     color_schemes
 }
 
+/// Returns the built in color scheme named `name`, parsing it
+/// if this is the first time that it has been used
+fn builtin_color_scheme(name: &str) -> Option<&'static Palette> {
+    let (name, data) = BUILTIN_SCHEMES.get_key_value(name)?;
+    let mut parsed = PARSED_SCHEMES.lock().unwrap();
+    let palette = parsed.entry(*name).or_insert_with(|| {
+        let scheme: ColorSchemeFile = toml::from_str(data).unwrap();
+        // There is a bounded number of built in schemes, and once
+        // parsed they are kept for the life of the process
+        Box::leak(Box::new(scheme.colors))
+    });
+    Some(*palette)
+}
+
 struct LuaPipe {
     sender: Sender<mlua::Lua>,
     receiver: Receiver<mlua::Lua>,
@@ -275,13 +328,91 @@ pub fn create_user_owned_dirs(p: &Path) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// A file with this name in the same directory as the executable
+/// enables portable mode
+const PORTABLE_MARKER: &str = "wezterm-portable";
+
+/// Enables portable mode, as requested by the `--portable` option.
+/// This must be called before any of the directories are used.  The
+/// mode is recorded in the environment so that it also applies to
+/// processes that are spawned by this one, such as the mux server.
+pub fn enable_portable_mode() -> anyhow::Result<()> {
+    let exe = std::env::current_exe()?;
+    let exe_dir = exe
+        .parent()
+        .ok_or_else(|| anyhow!("{} has no parent dir", exe.display()))?;
+    std::env::set_var("WEZTERM_PORTABLE_DIR", exe_dir);
+    Ok(())
+}
+
+fn compute_portable_dir() -> Option<PathBuf> {
+    if let Some(dir) = std::env::var_os("WEZTERM_PORTABLE_DIR") {
+        if !dir.is_empty() {
+            return Some(PathBuf::from(dir));
+        }
+    }
+    let exe_dir = std::env::current_exe().ok()?.parent()?.to_path_buf();
+    if exe_dir.join(PORTABLE_MARKER).exists() {
+        std::env::set_var("WEZTERM_PORTABLE_DIR", &exe_dir);
+        Some(exe_dir)
+    } else {
+        None
+    }
+}
+
+/// In portable mode, returns the directory `name` beneath the
+/// `wezterm-data` directory alongside the executable
+fn portable_dir(name: &str) -> Option<PathBuf> {
+    PORTABLE_DIR
+        .as_ref()
+        .map(|dir| dir.join("wezterm-data").join(name))
+}
+
+/// Returns the wezterm directory within the directory named by the
+/// XDG environment variable `var`.  The XDG base directory spec requires
+/// that relative paths are ignored, as are empty values.
+fn xdg_dir(var: &str) -> Option<PathBuf> {
+    std::env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .map(|p| p.join("wezterm"))
+}
+
 fn xdg_config_home() -> PathBuf {
-    match std::env::var_os("XDG_CONFIG_HOME").map(|s| PathBuf::from(s).join("wezterm")) {
+    if let Some(dir) = PORTABLE_DIR.as_ref() {
+        return dir.clone();
+    }
+    match xdg_dir("XDG_CONFIG_HOME") {
         Some(p) => p,
         None => HOME_DIR.join(".config").join("wezterm"),
     }
 }
 
+/// The system wide config directories from `$XDG_CONFIG_DIRS`,
+/// in order of preference
+fn xdg_config_dirs() -> Vec<PathBuf> {
+    let dirs = match std::env::var_os("XDG_CONFIG_DIRS") {
+        Some(dirs) if !dirs.is_empty() => dirs,
+        _ => "/etc/xdg".into(),
+    };
+    std::env::split_paths(&dirs)
+        .filter(|p| p.is_absolute())
+        .map(|p| p.join("wezterm"))
+        .collect()
+}
+
+/// Returns `path`, unless it doesn't exist but `legacy_path` does.
+/// This allows files that were written before wezterm followed the
+/// XDG base directory spec to continue to be used from their
+/// original location.
+pub fn path_with_legacy_fallback(path: PathBuf, legacy_path: PathBuf) -> PathBuf {
+    if !path.exists() && legacy_path.exists() {
+        legacy_path
+    } else {
+        path
+    }
+}
+
 pub fn set_config_file_override(path: &Path) {
     CONFIG_FILE_OVERRIDE
         .lock()
@@ -289,6 +420,11 @@ pub fn set_config_file_override(path: &Path) {
         .replace(path.to_path_buf());
 }
 
+/// Returns the path passed via `--config-file`, if any
+pub fn config_file_override() -> Option<PathBuf> {
+    CONFIG_FILE_OVERRIDE.lock().unwrap().clone()
+}
+
 pub fn set_config_overrides(items: &[(String, String)]) {
     *CONFIG_OVERRIDES.lock().unwrap() = items.to_vec();
 }
@@ -324,6 +460,43 @@ pub fn reload() {
     CONFIG.reload();
 }
 
+/// Arrange for `subscriber` to be called each time that the configuration
+/// is successfully reloaded.  It is called on the thread that performed
+/// the reload, which is not necessarily the main thread.
+/// The subscriber is removed if it returns false.
+pub fn subscribe_to_config_reload<F>(subscriber: F)
+where
+    F: Fn() -> bool + Send + 'static,
+{
+    RELOAD_SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .push(Box::new(subscriber));
+}
+
+fn notify_config_reloaded() {
+    RELOAD_SUBSCRIBERS
+        .lock()
+        .unwrap()
+        .retain(|subscriber| subscriber());
+}
+
+/// Reload the configuration each time that the process receives SIGUSR1.
+/// SIGHUP is not used because it is delivered when the controlling
+/// terminal goes away, which is expected to end the process rather
+/// than reload it, and the mux server may be started from a terminal.
+#[cfg(unix)]
+pub fn reload_on_sigusr1() -> anyhow::Result<()> {
+    let signals = signal_hook::iterator::Signals::new(&[libc::SIGUSR1])?;
+    std::thread::spawn(move || {
+        for _ in signals.forever() {
+            log::info!("SIGUSR1 received; reloading configuration");
+            reload();
+        }
+    });
+    Ok(())
+}
+
 /// If there was an error loading the preferred configuration,
 /// return it, otherwise return the current configuration
 pub fn configuration_result() -> Result<ConfigHandle, Error> {
@@ -333,9 +506,34 @@ pub fn configuration_result() -> Result<ConfigHandle, Error> {
     Ok(CONFIG.get())
 }
 
+/// Returns true if the current configuration is the default
+/// configuration because no configuration file was found
+pub fn no_config_file_found() -> bool {
+    CONFIG.no_config_file_found()
+}
+
+/// Returns the names of the color schemes that are built in to wezterm
+pub fn builtin_color_scheme_names() -> Vec<&'static str> {
+    let mut names: Vec<&'static str> = BUILTIN_SCHEMES.keys().copied().collect();
+    names.sort_unstable();
+    names
+}
+
+/// Returns a description of the problems that were ignored
+/// when the current configuration was loaded, if any
+pub fn configuration_warnings() -> Option<String> {
+    CONFIG.get_warnings()
+}
+
 struct ConfigInner {
     config: Arc<Config>,
     error: Option<String>,
+    /// Problems with the configuration that didn't prevent it from
+    /// being loaded, such as unknown options
+    warnings: Option<String>,
+    /// True if the configuration was loaded without finding
+    /// a configuration file
+    no_config_file: bool,
     generation: usize,
     watcher: Option<notify::RecommendedWatcher>,
 }
@@ -345,6 +543,8 @@ impl ConfigInner {
         Self {
             config: Arc::new(Config::default_config()),
             error: None,
+            warnings: None,
+            no_config_file: false,
             generation: 0,
             watcher: None,
         }
@@ -405,16 +605,33 @@ impl ConfigInner {
     /// configuration.
     /// On failure, retain the existing configuration but
     /// replace any captured error message.
-    fn reload(&mut self) {
+    /// Returns true if the configuration was replaced.
+    fn reload(&mut self) -> bool {
         match Config::load() {
             Ok(LoadedConfig {
                 config,
                 file_name,
+                included,
                 lua,
+                warnings,
             }) => {
                 self.config = Arc::new(config);
                 self.error.take();
+                self.no_config_file = file_name.is_none();
+                self.warnings = if warnings.is_empty() {
+                    None
+                } else {
+                    let warnings = format!(
+                        "The configuration has problems that were ignored:\n{}",
+                        warnings.join("\n")
+                    );
+                    if self.generation > 0 {
+                        show_error(&warnings);
+                    }
+                    Some(warnings)
+                };
                 self.generation += 1;
+                self.publish_codepoint_widths();
 
                 // If we loaded a user config, publish this latest version of
                 // the lua state to the LUA_PIPE.  This allows a subsequent
@@ -429,7 +646,11 @@ impl ConfigInner {
                     if let Some(path) = file_name {
                         self.watch_path(path);
                     }
+                    for path in included {
+                        self.watch_path(path);
+                    }
                 }
+                true
             }
             Err(err) => {
                 let err = format!("{:#}", err);
@@ -438,6 +659,7 @@ impl ConfigInner {
                     show_error(&err);
                 }
                 self.error.replace(err);
+                false
             }
         }
     }
@@ -448,13 +670,31 @@ impl ConfigInner {
     fn use_defaults(&mut self) {
         self.config = Arc::new(Config::default_config());
         self.error.take();
+        self.warnings.take();
+        self.no_config_file = false;
         self.generation += 1;
+        self.publish_codepoint_widths();
     }
 
     fn use_this_config(&mut self, cfg: Config) {
         self.config = Arc::new(cfg);
         self.error.take();
+        self.warnings.take();
+        self.no_config_file = false;
         self.generation += 1;
+        self.publish_codepoint_widths();
+    }
+
+    /// The width of a cell is computed without reference to the
+    /// configuration, so the overrides are installed globally
+    fn publish_codepoint_widths(&self) {
+        termwiz::cell::set_codepoint_width_overrides(
+            self.config
+                .single_width_codepoints
+                .iter()
+                .map(|range| (range.first..=range.last, 1))
+                .collect(),
+        );
     }
 
     fn overridden(&mut self, overrides: &serde_json::Value) -> Result<ConfigHandle, Error> {
@@ -484,6 +724,7 @@ impl ConfigInner {
         self.config = Arc::new(config);
         self.error.take();
         self.generation += 1;
+        self.publish_codepoint_widths();
     }
 }
 
@@ -530,10 +771,16 @@ impl Configuration {
         inner.use_test();
     }
 
-    /// Reload the configuration
+    /// Reload the configuration, and notify any subscribers
+    /// if it was successfully replaced
     pub fn reload(&self) {
-        let mut inner = self.inner.lock().unwrap();
-        inner.reload();
+        let reloaded = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.reload()
+        };
+        if reloaded {
+            notify_config_reloaded();
+        }
     }
 
     /// Returns a copy of any captured error message.
@@ -543,6 +790,18 @@ impl Configuration {
         inner.error.as_ref().cloned()
     }
 
+    /// Returns a copy of any problems that were ignored when
+    /// the configuration was loaded
+    pub fn get_warnings(&self) -> Option<String> {
+        let inner = self.inner.lock().unwrap();
+        inner.warnings.as_ref().cloned()
+    }
+
+    pub fn no_config_file_found(&self) -> bool {
+        let inner = self.inner.lock().unwrap();
+        inner.no_config_file
+    }
+
     /// Returns any captured error message, and clears
     /// it from the config state.
     #[allow(dead_code)]
@@ -607,6 +866,72 @@ where
     deserializer.deserialize_any(Number)
 }
 
+/// Controls the rate at which the cursor trail animation progresses
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum CursorAnimationEasing {
+    Linear,
+    /// Starts quickly and slows down towards the end
+    EaseOut,
+    /// Starts and ends slowly
+    EaseInOut,
+}
+
+impl Default for CursorAnimationEasing {
+    fn default() -> Self {
+        CursorAnimationEasing::EaseOut
+    }
+}
+
+impl CursorAnimationEasing {
+    /// Maps the linear progress `t`, in the range 0.0 to 1.0,
+    /// to the eased progress
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.max(0.).min(1.);
+        match self {
+            Self::Linear => t,
+            Self::EaseOut => 1. - (1. - t).powi(3),
+            Self::EaseInOut => {
+                if t < 0.5 {
+                    4. * t * t * t
+                } else {
+                    1. - (-2. * t + 2.).powi(3) / 2.
+                }
+            }
+        }
+    }
+}
+
+/// Controls whether the high contrast palette and thicker cursor and
+/// underlines are used
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum HighContrastMode {
+    /// Follow the high contrast setting of the system
+    Auto,
+    Always,
+    Never,
+}
+
+impl Default for HighContrastMode {
+    fn default() -> Self {
+        HighContrastMode::Auto
+    }
+}
+
+/// How to notify about long running commands that have completed
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum CommandNotificationStyle {
+    /// Show a desktop notification
+    Toast,
+    /// Show a badge in the tab bar until the tab is activated
+    TabBadge,
+}
+
+impl Default for CommandNotificationStyle {
+    fn default() -> Self {
+        CommandNotificationStyle::Toast
+    }
+}
+
 /// Behavior when the program spawned by wezterm terminates
 #[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
 pub enum ExitBehavior {
@@ -624,6 +949,43 @@ impl Default for ExitBehavior {
     }
 }
 
+/// What to do with the process running in a pane when the pane is closed
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum ChildTerminationBehavior {
+    /// Send SIGHUP and allow child_termination_grace_period_ms for
+    /// the process to exit before forcibly killing it
+    HangupThenKill,
+    /// Forcibly kill the process immediately
+    Kill,
+    /// Don't signal the process and allow it to continue running
+    LeaveRunning,
+}
+
+impl Default for ChildTerminationBehavior {
+    fn default() -> Self {
+        ChildTerminationBehavior::HangupThenKill
+    }
+}
+
+/// What to do when the tabs don't all fit in the tab bar
+/// at their full width
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum TabBarOverflow {
+    /// Shrink each of the tabs by the same amount
+    Shrink,
+    /// Show as many tabs as fit at their full width, scrolling
+    /// the tabs so that the active tab is always visible
+    Scroll,
+    /// Show only the index of each tab, rather than its title
+    Numbers,
+}
+
+impl Default for TabBarOverflow {
+    fn default() -> Self {
+        TabBarOverflow::Shrink
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
     /// The font size, measured in points
@@ -636,9 +998,26 @@ pub struct Config {
     #[serde(default)]
     pub allow_square_glyphs_to_overflow_width: AllowSquareGlyphOverflow,
 
+    /// Ranges of codepoints, such as the Powerline and Nerd Font
+    /// symbols, that are always treated as occupying a single cell,
+    /// and whose glyphs are vertically centered within it
+    #[serde(default)]
+    pub single_width_codepoints: Vec<CodepointRange>,
+
     #[serde(default)]
     pub window_decorations: WindowDecorations,
 
+    /// If true, ask the window manager to keep wezterm windows
+    /// stacked above other windows.
+    #[serde(default)]
+    pub window_always_on_top: bool,
+
+    /// If true, ask the window manager to show wezterm windows
+    /// on all virtual desktops/workspaces rather than just the
+    /// current one.
+    #[serde(default)]
+    pub window_visible_on_all_workspaces: bool,
+
     /// When using FontKitXXX font systems, a set of directories to
     /// search ahead of the standard font locations for fonts.
     /// Relative paths are taken to be relative to the directory
@@ -670,6 +1049,16 @@ pub struct Config {
     /// The color palette
     pub colors: Option<Palette>,
 
+    /// Whether to use `high_contrast_colors` in place of the normal
+    /// palette, and draw the cursor and underlines more thickly.
+    /// The default follows the high contrast setting of the system.
+    #[serde(default)]
+    pub high_contrast: HighContrastMode,
+
+    /// The palette to use in high contrast mode.  If not specified,
+    /// a built-in palette of white on black is used.
+    pub high_contrast_colors: Option<Palette>,
+
     #[serde(default)]
     pub tab_bar_style: TabBarStyle,
 
@@ -688,6 +1077,26 @@ pub struct Config {
     #[serde(default = "default_scrollback_lines")]
     pub scrollback_lines: usize,
 
+    /// When true, the scrollback of each local pane is periodically
+    /// saved to disk, so that it can be browsed in a recovered tab
+    /// after wezterm crashes or is restarted.  The
+    /// TogglePersistScrollback key assignment inverts this for the
+    /// panes of a particular tab.
+    #[serde(default)]
+    pub persist_scrollback: bool,
+
+    /// If set, the persisted scrollback is encrypted using the
+    /// key held in this file, which is created if it doesn't exist
+    #[serde(default)]
+    pub persist_scrollback_key_file: Option<PathBuf>,
+
+    /// If set, limits the memory, in bytes, that each tab may use
+    /// to hold its scrollback; it is divided evenly between the panes
+    /// of the tab.  The oldest lines are discarded when the limit
+    /// would be exceeded.
+    #[serde(default)]
+    pub scrollback_memory_limit: Option<usize>,
+
     /// If no `prog` is specified on the command line, use this
     /// instead of running the user's shell.
     /// For example, to have `wezterm` always run `top` by default,
@@ -707,15 +1116,92 @@ pub struct Config {
     /// info!)
     pub default_cwd: Option<PathBuf>,
 
+    /// Glob patterns, relative to the directory containing the config
+    /// file, that match TOML files whose contents are merged into the
+    /// configuration.  Values in the config file take precedence over
+    /// those in the included files.
+    #[serde(default)]
+    pub include: Vec<String>,
+
+    /// The name of the domain in which the first window is spawned,
+    /// and which is used when spawning with `DefaultDomain`.
+    /// If unset, the local domain is used.
+    pub default_domain: Option<String>,
+
     #[serde(default)]
     pub exit_behavior: ExitBehavior,
 
+    /// What to do with the process running in a pane when the
+    /// pane is closed
+    #[serde(default)]
+    pub child_termination_behavior: ChildTerminationBehavior,
+
+    /// When child_termination_behavior is HangupThenKill, how long
+    /// to wait for the process to exit after sending SIGHUP before
+    /// forcibly killing it
+    #[serde(default = "default_child_termination_grace_period_ms")]
+    pub child_termination_grace_period_ms: u64,
+
+    /// Show a key icon in the tab bar when the program in the
+    /// active pane of a tab appears to be reading a password
+    #[serde(default = "default_true")]
+    pub show_password_input_indicator: bool,
+
+    /// When the program running in a pane hasn't set a title, use
+    /// the name of its foreground process and its working directory
+    /// as the title, as determined by inspecting the process
+    #[serde(default = "default_true")]
+    pub title_from_foreground_process: bool,
+
+    /// When a pane is held open after its program has exited, show
+    /// the exit status and how long the program ran for, rather than
+    /// just a "Process completed" message
+    #[serde(default = "default_true")]
+    pub exit_status_banner: bool,
+
+    /// If set, a command that runs for at least this many seconds
+    /// and then completes in a pane that doesn't have the focus
+    /// triggers a notification.  Commands are delimited by the
+    /// semantic prompt escapes emitted by the shell integration.
+    #[serde(default)]
+    pub long_running_command_notification_seconds: Option<u64>,
+
+    /// How to notify about completed long running commands
+    #[serde(default)]
+    pub long_running_command_notification_style: CommandNotificationStyle,
+
+    /// Describes the windows, tabs and panes to create when the
+    /// gui starts up without an explicit program to run.
+    /// If empty, a single window running the default program
+    /// is created.
+    #[serde(default)]
+    pub startup_windows: Vec<StartupWindow>,
+
+    /// Commands to run in response to events such as the bell
+    /// or a program exiting.
+    #[serde(default)]
+    pub event_hooks: Vec<EventHook>,
+
     /// Specifies a map of environment variables that should be set
-    /// when spawning commands in the local domain.
-    /// This is not used when working with remote domains.
+    /// when spawning commands in the local domain, and in the
+    /// multiplexer domains.
+    /// Variables specified by a SpawnCommand take precedence.
     #[serde(default)]
     pub set_environment_variables: HashMap<String, String>,
 
+    /// Specifies environment variables to remove or override
+    /// when spawning commands in particular domains, keyed by
+    /// the name of the domain
+    #[serde(default)]
+    pub domain_environment: HashMap<String, DomainEnvironment>,
+
+    /// When true, processes spawned into local panes are placed into
+    /// a dedicated cgroup (linux) or job object (windows) so that
+    /// closing the pane kills the whole process tree, including any
+    /// processes that detached themselves from the session.
+    #[serde(default)]
+    pub contain_spawned_processes: bool,
+
     /// Specifies the height of a new window, expressed in character cells.
     #[serde(default = "default_initial_rows")]
     pub initial_rows: u16,
@@ -727,6 +1213,32 @@ pub struct Config {
     #[serde(default = "default_hyperlink_rules")]
     pub hyperlink_rules: Vec<hyperlink::Rule>,
 
+    /// If set, the command that is used to open hyperlinks that are
+    /// clicked, rather than the default handler for the system.
+    /// The 0th element is the program to run and the remaining
+    /// elements are its arguments.  `$URI` in any of the elements
+    /// is replaced by the URI; if none of them contain `$URI`, then
+    /// the URI is passed as the final argument.
+    #[serde(default)]
+    pub open_uri_command: Option<Vec<String>>,
+
+    /// Rules that restyle the text in the viewport that matches
+    /// a regex, such as coloring `ERROR` red, independently of
+    /// the attributes that the application set on that text
+    #[serde(default)]
+    pub highlight_rules: Vec<HighlightRule>,
+
+    /// Additional regexes that the QuickSelect key assignment uses
+    /// to find the text that can be selected.  If a regex has a
+    /// capture group, then the text of the first capture is selected.
+    #[serde(default)]
+    pub quick_select_patterns: Vec<String>,
+
+    /// If true, QuickSelect only uses `quick_select_patterns`,
+    /// rather than adding them to its built-in patterns
+    #[serde(default)]
+    pub disable_default_quick_select_patterns: bool,
+
     /// What to set the TERM variable to
     #[serde(default = "default_term")]
     pub term: String,
@@ -799,6 +1311,11 @@ pub struct Config {
     #[serde(default)]
     pub ssh_domains: Vec<SshDomain>,
 
+    /// Domains that are implemented by domain types that have
+    /// been registered with the mux
+    #[serde(default)]
+    pub custom_domains: Vec<CustomDomain>,
+
     /// When running in server mode, defines configuration for
     /// each of the endpoints that we'll listen for connections
     #[serde(default)]
@@ -816,20 +1333,52 @@ pub struct Config {
     #[serde(default = "default_ratelimit_line_prefetches_per_second")]
     pub ratelimit_mux_line_prefetches_per_second: u32,
 
+    /// Constrains the rate at which each remote address may open
+    /// connections to the `tls_servers` of the multiplexer.
+    /// Connections in excess of this rate are closed without
+    /// performing the TLS handshake.
+    #[serde(default = "default_ratelimit_tls_connections_per_second")]
+    pub ratelimit_tls_connections_per_second: u32,
+
+    /// If set, the mux server serves its metrics over HTTP at
+    /// `/metrics` on this address, such as `127.0.0.1:9845`,
+    /// so that they can be scraped by Prometheus
+    #[serde(default)]
+    pub metrics_http_address: Option<String>,
+
     #[serde(default)]
     pub keys: Vec<Key>,
     #[serde(default)]
     pub debug_key_events: bool,
 
+    /// When true, none of the default key assignments are registered,
+    /// so only those listed in `keys` are active
     #[serde(default)]
     pub disable_default_key_bindings: bool,
     pub leader: Option<LeaderKey>,
 
     #[serde(default)]
     pub mouse_bindings: Vec<Mouse>,
+    /// When true, none of the default mouse assignments are registered,
+    /// so only those listed in `mouse_bindings` are active
     #[serde(default)]
     pub disable_default_mouse_bindings: bool,
 
+    /// The longest time, in milliseconds, between successive clicks
+    /// for them to be counted as a double or triple click
+    #[serde(default = "default_double_click_interval_ms")]
+    pub double_click_interval_ms: u64,
+
+    /// How far, in pixels, the mouse may move between successive
+    /// clicks for them to be counted as a double or triple click
+    #[serde(default = "default_click_radius")]
+    pub click_radius: u16,
+
+    /// How far, in pixels, the mouse must move while a button is held
+    /// down before it is considered to be a drag
+    #[serde(default)]
+    pub drag_threshold: u16,
+
     #[serde(default)]
     pub daemon_options: DaemonOptions,
 
@@ -871,11 +1420,24 @@ pub struct Config {
     #[serde(default)]
     pub tab_and_split_indices_are_zero_based: bool,
 
+    /// If set, a template that produces the title of each tab in the
+    /// tab bar.  `{index}`, `{title}`, `{cwd}` and `{domain}` are
+    /// replaced by the corresponding values for the active pane in the
+    /// tab, and `{title:20}` truncates the value to 20 columns.
+    /// When set, show_tab_index_in_tab_bar is ignored.
+    #[serde(default)]
+    pub tab_title_format: Option<String>,
+
     /// Specifies the maximum width that a tab can have in the
     /// tab bar.  Defaults to 16 glyphs in width.
     #[serde(default = "default_tab_max_width")]
     pub tab_max_width: usize,
 
+    /// What to do when there are too many tabs for them all to fit
+    /// in the tab bar at their full width
+    #[serde(default)]
+    pub tab_bar_overflow: TabBarOverflow,
+
     /// If true, hide the tab bar if the window only has a single tab.
     #[serde(default)]
     pub hide_tab_bar_if_only_one_tab: bool,
@@ -898,6 +1460,11 @@ pub struct Config {
     #[serde(default = "default_true")]
     pub prefer_egl: bool,
 
+    /// Which GPU to render with on systems that have more than one.
+    /// The default is to prefer the integrated, low power GPU.
+    #[serde(default)]
+    pub gpu_power_preference: GpuPowerPreference,
+
     #[serde(default = "default_true")]
     pub custom_block_glyphs: bool,
 
@@ -905,6 +1472,12 @@ pub struct Config {
     #[serde(default)]
     pub window_padding: WindowPadding,
 
+    /// Controls the appearance of the parts of the window that are
+    /// drawn by wezterm rather than by the terminal, such as the tab
+    /// bar and overlays like the launcher and tab navigator.
+    #[serde(default)]
+    pub window_frame: WindowFrameConfig,
+
     /// Specifies the path to a background image attachment file.
     /// The file can be any image format that the rust `image`
     /// crate is able to identify and load.
@@ -932,6 +1505,12 @@ pub struct Config {
     #[serde(default = "default_one_point_oh")]
     pub window_background_opacity: f32,
 
+    /// Selects a compositor effect, such as blur, acrylic or mica,
+    /// to render behind the window.  This is only visible when
+    /// window_background_opacity is less than 1.0.
+    #[serde(default)]
+    pub window_background_material: WindowBackgroundMaterial,
+
     /// inactive_pane_hue, inactive_pane_saturation and
     /// inactive_pane_brightness allow for transforming the color
     /// of inactive panes.
@@ -976,6 +1555,17 @@ pub struct Config {
     #[serde(default = "default_cursor_blink_rate")]
     pub cursor_blink_rate: u64,
 
+    /// If non-zero, specifies the duration in milliseconds of a short
+    /// trail that is animated from the prior position of the cursor
+    /// when it moves.  The animation is disabled when the system has
+    /// been asked to reduce motion.
+    #[serde(default)]
+    pub cursor_animation_duration: u64,
+
+    /// How the cursor trail is eased over `cursor_animation_duration`
+    #[serde(default)]
+    pub cursor_animation_easing: CursorAnimationEasing,
+
     /// Specifies the default cursor style.  various escape sequences
     /// can override the default style in different situations (eg:
     /// an editor can change it depending on the mode), but this value
@@ -1000,8 +1590,20 @@ pub struct Config {
     #[serde(default = "default_true")]
     pub scroll_to_bottom_on_input: bool,
 
+    /// If true, text that is pasted or composed via an input method
+    /// or dead keys is normalized to Unicode NFC before it is sent to
+    /// the terminal, so that programs that don't handle decomposed
+    /// sequences, such as those in file names from macOS, see the
+    /// precomposed characters instead.
+    #[serde(default)]
+    pub normalize_input_to_unicode_nfc: bool,
+
     #[serde(default)]
     pub use_ime: bool,
+
+    /// If false, dead keys are sent immediately as their spacing
+    /// character (eg: `^` or `~`) rather than being held until the
+    /// next key is pressed and composed with it
     #[serde(default = "default_true")]
     pub use_dead_keys: bool,
 
@@ -1016,9 +1618,22 @@ pub struct Config {
     #[serde(default = "default_true")]
     pub automatically_reload_config: bool,
 
+    /// When true, options in the configuration that wezterm doesn't
+    /// recognize are treated as errors rather than being ignored
+    /// with a warning.
+    #[serde(default)]
+    pub strict: bool,
+
     #[serde(default = "default_true")]
     pub add_wsl_distributions_to_launch_menu: bool,
 
+    /// When true, launching the gui while another instance of the
+    /// gui is already running causes the requested program to be
+    /// spawned in a new tab in one of the windows of that instance,
+    /// rather than starting a second gui process.
+    #[serde(default)]
+    pub single_instance: bool,
+
     #[serde(default = "default_true")]
     pub check_for_updates: bool,
     #[serde(default)]
@@ -1034,12 +1649,29 @@ pub struct Config {
     #[serde(default)]
     pub enable_csi_u_key_encoding: bool,
 
+    /// When set to true, applications may use the kitty keyboard
+    /// protocol (`CSI > flags u`) to request unambiguous encodings
+    /// of keys and modifiers.
+    /// <https://sw.kovidgoyal.net/kitty/keyboard-protocol/>
+    #[serde(default)]
+    pub enable_kitty_keyboard: bool,
+
     #[serde(default)]
     pub window_close_confirmation: WindowCloseConfirmation,
 
     #[serde(default)]
     pub native_macos_fullscreen_mode: bool,
 
+    /// Controls whether the titlebar is drawn on macOS, and whether
+    /// the close/minimize/zoom buttons are visible
+    #[serde(default)]
+    pub macos_titlebar_appearance: MacOsTitlebarAppearance,
+
+    /// If set, moves the macOS close/minimize/zoom buttons to
+    /// the specified position relative to the top left of the window
+    #[serde(default)]
+    pub macos_window_button_position: Option<WindowButtonPosition>,
+
     #[serde(default = "default_word_boundary")]
     pub selection_word_boundary: String,
 
@@ -1061,6 +1693,18 @@ fn default_status_update_interval() -> u64 {
     1_000
 }
 
+fn default_child_termination_grace_period_ms() -> u64 {
+    200
+}
+
+fn default_double_click_interval_ms() -> u64 {
+    500
+}
+
+fn default_click_radius() -> u16 {
+    4
+}
+
 fn default_alternate_buffer_wheel_scroll_speed() -> u8 {
     3
 }
@@ -1139,6 +1783,77 @@ pub struct WindowPadding {
 }
 impl_lua_conversion!(WindowPadding);
 
+/// An inclusive range of unicode codepoints
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct CodepointRange {
+    pub first: u32,
+    pub last: u32,
+}
+impl_lua_conversion!(CodepointRange);
+
+impl CodepointRange {
+    pub fn contains(&self, c: char) -> bool {
+        (self.first..=self.last).contains(&(c as u32))
+    }
+}
+
+#[derive(Default, Deserialize, Serialize, Clone, Debug)]
+pub struct WindowFrameConfig {
+    /// The font to use for the tab bar and overlays.
+    /// If not specified, the terminal font is used.
+    #[serde(default)]
+    pub font: Option<TextStyle>,
+    /// The size, in points, of the font used for the tab bar and
+    /// overlays.  Unlike the terminal font, it doesn't change with
+    /// the zoom level.  If not specified, the frame font follows
+    /// the terminal font size.
+    #[serde(default)]
+    pub font_size: Option<f64>,
+    /// The thickness, in pixels, of the lines drawn between split
+    /// panes.  If not specified, a box drawing character from the
+    /// terminal font is used.
+    #[serde(default)]
+    pub split_thickness: Option<u16>,
+    /// The thickness, in pixels, of a border drawn around the
+    /// terminal cells, within the window_padding.  The default
+    /// of 0 disables the border.
+    #[serde(default)]
+    pub border_thickness: u16,
+    /// The bold, italic and bold italic variants of `font`, which
+    /// are derived from it when the config is loaded
+    #[serde(skip)]
+    pub font_variants: Vec<TextStyle>,
+}
+impl_lua_conversion!(WindowFrameConfig);
+
+impl WindowFrameConfig {
+    /// Returns the frame font to use for text with `attrs`, choosing
+    /// its bold and italic variants in the same way that the default
+    /// font_rules do for the terminal font
+    pub fn font_for(&self, attrs: &wezterm_term::CellAttributes) -> Option<&TextStyle> {
+        let font = self.font.as_ref()?;
+        let bold = attrs.intensity() == wezterm_term::Intensity::Bold;
+        let idx = match (bold, attrs.italic()) {
+            (false, false) => return Some(font),
+            (true, false) => 0,
+            (false, true) => 1,
+            (true, true) => 2,
+        };
+        Some(self.font_variants.get(idx).unwrap_or(font))
+    }
+}
+
+/// Position of the macOS close/minimize/zoom buttons, measured in
+/// points from the top left corner of the window
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub struct WindowButtonPosition {
+    #[serde(deserialize_with = "de_number")]
+    pub x: f64,
+    #[serde(deserialize_with = "de_number")]
+    pub y: f64,
+}
+impl_lua_conversion!(WindowButtonPosition);
+
 #[derive(Deserialize, Serialize, Clone, Copy, Debug)]
 pub enum WindowCloseConfirmation {
     AlwaysPrompt,
@@ -1158,15 +1873,21 @@ impl Default for Config {
     fn default() -> Self {
         // Ask serde to provide the defaults based on the attributes
         // specified in the struct so that we don't have to repeat
-        // the same thing in a different form down here
-        toml::from_str("").unwrap()
+        // the same thing in a different form down here.
+        // That is done once and then cloned, as the defaults are
+        // needed each time that the config is loaded.
+        DEFAULT_CONFIG.clone()
     }
 }
 
 pub struct LoadedConfig {
     config: Config,
     file_name: Option<PathBuf>,
+    /// The config fragments that were merged via `include`
+    included: Vec<PathBuf>,
     lua: Option<mlua::Lua>,
+    /// Messages about options that were not recognized
+    warnings: Vec<String>,
 }
 
 struct PathPossibility {
@@ -1188,7 +1909,36 @@ impl PathPossibility {
     }
 }
 
+/// Converts a JSON value to its TOML equivalent.  TOML has no
+/// null, so options that are not set are omitted.
+fn json_to_toml(value: serde_json::Value) -> Option<toml::Value> {
+    use serde_json::Value;
+    Some(match value {
+        Value::Null => return None,
+        Value::Bool(b) => toml::Value::Boolean(b),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => toml::Value::Integer(i),
+            None => toml::Value::Float(n.as_f64()?),
+        },
+        Value::String(s) => toml::Value::String(s),
+        Value::Array(a) => toml::Value::Array(a.into_iter().filter_map(json_to_toml).collect()),
+        Value::Object(o) => toml::Value::Table(
+            o.into_iter()
+                .filter_map(|(k, v)| Some((k, json_to_toml(v)?)))
+                .collect(),
+        ),
+    })
+}
+
 impl Config {
+    /// Returns the palette to use when high contrast mode is in effect
+    pub fn high_contrast_palette(&self) -> wezterm_term::color::ColorPalette {
+        self.high_contrast_colors
+            .clone()
+            .unwrap_or_else(Palette::high_contrast)
+            .into()
+    }
+
     pub fn load() -> Result<LoadedConfig, Error> {
         Self::load_with_overrides(&serde_json::Value::default())
     }
@@ -1199,10 +1949,20 @@ impl Config {
         // multiple.  In addition, it spawns a lot of subprocesses,
         // so we do this bit "by-hand"
 
-        let mut paths = vec![
-            PathPossibility::optional(CONFIG_DIR.join("wezterm.lua")),
-            PathPossibility::optional(HOME_DIR.join(".wezterm.lua")),
-        ];
+        let mut paths = vec![PathPossibility::optional(CONFIG_DIR.join("wezterm.lua"))];
+        // In portable mode, CONFIG_DIR is the directory that holds the
+        // executable, and only the config there is used, as that is
+        // what travels with it
+        if PORTABLE_DIR.is_none() {
+            paths.push(PathPossibility::optional(HOME_DIR.join(".wezterm.lua")));
+            if cfg!(all(unix, not(target_os = "macos"))) {
+                // A system wide default, which is useful for distributions
+                // and for managed systems
+                for dir in xdg_config_dirs() {
+                    paths.push(PathPossibility::optional(dir.join("wezterm.lua")));
+                }
+            }
+        }
         if cfg!(windows) {
             // On Windows, a common use case is to maintain a thumb drive
             // with a set of portable tools that don't need to be installed
@@ -1250,18 +2010,39 @@ impl Config {
                     .set_name(p.to_string_lossy().as_bytes())?
                     .eval_async(),
             )?;
+            let config_dir = p.parent().unwrap_or_else(|| Path::new("."));
+            let (config, included) = include::apply_includes(&lua, config, config_dir)?;
+            let (config, platform_warnings) = platform::apply_platform_sections(config)?;
             let config = Self::apply_overrides_to(&lua, config)?;
             let config = Self::apply_overrides_obj_to(config, overrides)?;
-            cfg = luahelper::from_lua_value(config).with_context(|| {
-                format!(
-                    "Error converting lua value returned by script {} to Config struct",
-                    p.display()
-                )
-            })?;
+            let (parsed, mut unknown_fields) = luahelper::from_lua_value_with_warnings(config)
+                .with_context(|| {
+                    format!(
+                        "Error converting lua value returned by script {} to Config struct",
+                        p.display()
+                    )
+                })?;
+            cfg = parsed;
+            unknown_fields.extend(platform_warnings);
+            if !unknown_fields.is_empty() {
+                if cfg.strict {
+                    bail!(
+                        "{} contains unknown options:\n{}",
+                        p.display(),
+                        unknown_fields.join("\n")
+                    );
+                }
+                for warning in &unknown_fields {
+                    log::warn!("{}", warning);
+                }
+            }
 
             // Compute but discard the key bindings here so that we raise any
             // problems earlier than we use them.
             let _ = cfg.key_bindings();
+            for rule in &cfg.hyperlink_rules {
+                rule.validate().context("invalid hyperlink_rules entry")?;
+            }
 
             std::env::set_var("WEZTERM_CONFIG_FILE", p);
             if let Some(dir) = p.parent() {
@@ -1270,14 +2051,26 @@ impl Config {
             return Ok(LoadedConfig {
                 config: cfg.compute_extra_defaults(Some(p)),
                 file_name: Some(p.to_path_buf()),
+                included,
                 lua: Some(lua),
+                warnings: unknown_fields,
             });
         }
 
+        // There is no config file, but the command line
+        // overrides still need to be applied
+        let config = if CONFIG_OVERRIDES.lock().unwrap().is_empty() {
+            Self::default()
+        } else {
+            default_config_with_overrides_applied()?
+        };
+
         Ok(LoadedConfig {
-            config: Self::default().compute_extra_defaults(None),
+            config: config.compute_extra_defaults(None),
             file_name: None,
+            included: vec![],
             lua: None,
+            warnings: vec![],
         })
     }
 
@@ -1305,19 +2098,9 @@ impl Config {
     ) -> anyhow::Result<mlua::Value<'l>> {
         let overrides = CONFIG_OVERRIDES.lock().unwrap();
         for (key, value) in &*overrides {
-            let code = format!(
-                r#"
-                local wezterm = require 'wezterm';
-                config.{} = {};
-                return config;
-                "#,
-                key, value
-            );
-            let chunk = lua.load(&code);
-            let chunk = chunk.set_name(&format!("--config {}={}", key, value))?;
-            lua.globals().set("config", config.clone())?;
             log::debug!("Apply {}={} to config", key, value);
-            config = chunk.eval()?;
+            config = overrides::apply_override(lua, config, key, value)
+                .with_context(|| format!("Error applying --config {}={}", key, value))?;
         }
         Ok(config)
     }
@@ -1326,6 +2109,19 @@ impl Config {
         Self::default().compute_extra_defaults(None)
     }
 
+    /// Renders the configuration as TOML, with the options sorted
+    /// by name.  Options that are not set are omitted.
+    pub fn to_toml_string(&self) -> anyhow::Result<String> {
+        // The toml serializer can't represent enum variants that hold
+        // data, such as `ActivateTab(0)`, so the config is converted
+        // via JSON, which represents them as single entry tables.
+        // Going via toml::Value also ensures that tables are emitted
+        // after the plain values, as is required by TOML
+        let value = serde_json::to_value(self).context("converting config to TOML")?;
+        let value = json_to_toml(value).unwrap_or_else(|| toml::Value::Table(Default::default()));
+        Ok(toml::to_string_pretty(&value)?)
+    }
+
     pub fn key_bindings(&self) -> HashMap<(KeyCode, Modifiers), KeyAssignment> {
         let mut map = HashMap::new();
 
@@ -1395,6 +2191,25 @@ impl Config {
             });
         }
 
+        // A fixed frame font size applies to the terminal font
+        // if no specific frame font was given
+        if let Some(size) = self.window_frame.font_size {
+            let mut font = self
+                .window_frame
+                .font
+                .clone()
+                .unwrap_or_else(|| self.font.clone());
+            font.fixed_size = Some((size * 100.0).round() as u32);
+            cfg.window_frame.font = Some(font);
+        }
+        if let Some(font) = &cfg.window_frame.font {
+            let reduced = font.reduce_first_font_to_family();
+            let bold = reduced.make_bold();
+            let italic = reduced.make_italic();
+            let bold_italic = bold.make_italic();
+            cfg.window_frame.font_variants = vec![bold, italic, bold_italic];
+        }
+
         // Load any additional color schemes into the color_schemes map
         cfg.load_color_schemes(&cfg.compute_color_scheme_dirs())
             .ok();
@@ -1487,8 +2302,27 @@ impl Config {
 
         if let Some(palette) = self.color_schemes.get(scheme_name) {
             Some(palette)
+        } else if let Some(palette) = builtin_color_scheme(scheme_name) {
+            Some(palette)
         } else {
-            COLOR_SCHEMES.get(scheme_name)
+            // Allow eg: "solarized dark" to select "Builtin Solarized Dark"
+            let wanted = loose_scheme_name(scheme_name);
+            let mut user: Vec<_> = self
+                .color_schemes
+                .keys()
+                .filter(|name| loose_scheme_name(name) == wanted)
+                .collect();
+            let mut builtin: Vec<_> = BUILTIN_SCHEMES
+                .keys()
+                .filter(|name| loose_scheme_name(name) == wanted)
+                .collect();
+            // Sort for a deterministic choice if several names match
+            user.sort();
+            builtin.sort();
+            match user.first() {
+                Some(name) => self.color_schemes.get(*name),
+                None => builtin.first().and_then(|name| builtin_color_scheme(name)),
+            }
         }
     }
 
@@ -1535,6 +2369,52 @@ impl Config {
         Ok(cmd)
     }
 
+    /// Produces the command to spawn in a local domain from the one
+    /// requested by the caller, if any.  A builder created via
+    /// `CommandBuilder::new_default_prog` runs the `default_prog`,
+    /// with the environment and cwd that it carries applied.
+    pub fn build_command(&self, command: Option<CommandBuilder>) -> Result<CommandBuilder, Error> {
+        match command {
+            Some(requested) if requested.is_default_prog() => {
+                let mut cmd = self.build_prog(None)?;
+                for k in requested.iter_removed_env() {
+                    cmd.env_remove(k);
+                }
+                for (k, v) in requested.iter_env() {
+                    cmd.env(k, v);
+                }
+                if let Some(cwd) = requested.get_cwd() {
+                    cmd.cwd(cwd);
+                }
+                Ok(cmd)
+            }
+            Some(mut cmd) => {
+                self.apply_cmd_defaults(&mut cmd);
+                Ok(cmd)
+            }
+            None => self.build_prog(None),
+        }
+    }
+
+    /// Sets the `set_environment_variables` in `cmd`, except for
+    /// those that it already sets, so that the variables specified
+    /// for a particular spawn take precedence
+    pub fn apply_environment_variables(&self, cmd: &mut CommandBuilder) {
+        for (k, v) in &self.set_environment_variables {
+            if cmd.get_env(k).is_none() {
+                cmd.env(k, v);
+            }
+        }
+    }
+
+    /// Applies the `domain_environment` configured for the domain
+    /// named `domain_name`, if any, to `cmd`
+    pub fn apply_domain_environment(&self, domain_name: &str, cmd: &mut CommandBuilder) {
+        if let Some(env) = self.domain_environment.get(domain_name) {
+            env.apply_to_command(cmd);
+        }
+    }
+
     pub fn apply_cmd_defaults(&self, cmd: &mut CommandBuilder) {
         // Apply `default_cwd` only if `cwd` is not already set, allows `--cwd`
         // option to take precedence
@@ -1542,9 +2422,7 @@ impl Config {
             cmd.cwd(cwd);
         }
 
-        for (k, v) in &self.set_environment_variables {
-            cmd.env(k, v);
-        }
+        self.apply_environment_variables(cmd);
 
         #[cfg(unix)]
         cmd.umask(umask::UmaskSaver::saved_umask());
@@ -1556,10 +2434,24 @@ impl Config {
     }
 }
 
+/// Normalizes a color scheme name for a case insensitive comparison
+/// that ignores the `Builtin` prefix that some of the bundled schemes have
+fn loose_scheme_name(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+    match name.strip_prefix("builtin ") {
+        Some(rest) => rest.to_string(),
+        None => name,
+    }
+}
+
 fn default_ratelimit_line_prefetches_per_second() -> u32 {
     10
 }
 
+fn default_ratelimit_tls_connections_per_second() -> u32 {
+    2
+}
+
 fn default_true() -> bool {
     true
 }
@@ -1613,6 +2505,12 @@ fn default_font_size() -> f64 {
 }
 
 fn compute_runtime_dir() -> Result<PathBuf, Error> {
+    if let Some(runtime) = portable_dir("runtime") {
+        return Ok(runtime);
+    }
+    if let Some(runtime) = xdg_dir("XDG_RUNTIME_DIR") {
+        return Ok(runtime);
+    }
     if let Some(runtime) = dirs_next::runtime_dir() {
         return Ok(runtime.join("wezterm"));
     }
@@ -1620,6 +2518,50 @@ fn compute_runtime_dir() -> Result<PathBuf, Error> {
     Ok(HOME_DIR.join(".local/share/wezterm"))
 }
 
+/// Unlike the runtime directory, the data directory persists
+/// across reboots
+fn compute_data_dir() -> PathBuf {
+    if let Some(data) = portable_dir("data") {
+        return data;
+    }
+    if let Some(data) = xdg_dir("XDG_DATA_HOME") {
+        return data;
+    }
+    match dirs_next::data_dir() {
+        Some(data) => data.join("wezterm"),
+        None => HOME_DIR.join(".local/share/wezterm"),
+    }
+}
+
+fn compute_cache_dir() -> PathBuf {
+    if let Some(cache) = portable_dir("cache") {
+        return cache;
+    }
+    if let Some(cache) = xdg_dir("XDG_CACHE_HOME") {
+        return cache;
+    }
+    match dirs_next::cache_dir() {
+        Some(cache) => cache.join("wezterm"),
+        None => HOME_DIR.join(".cache/wezterm"),
+    }
+}
+
+/// Only the XDG base directory spec has a notion of a state directory;
+/// the data directory is used for that purpose on other systems
+fn compute_state_dir() -> PathBuf {
+    if let Some(state) = portable_dir("state") {
+        return state;
+    }
+    if let Some(state) = xdg_dir("XDG_STATE_HOME") {
+        return state;
+    }
+    if cfg!(all(unix, not(target_os = "macos"))) {
+        HOME_DIR.join(".local/state/wezterm")
+    } else {
+        compute_data_dir()
+    }
+}
+
 pub fn pki_dir() -> anyhow::Result<PathBuf> {
     compute_runtime_dir().map(|d| d.join("pki"))
 }
@@ -1631,3 +2573,121 @@ fn default_read_timeout() -> Duration {
 fn default_write_timeout() -> Duration {
     Duration::from_secs(60)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn toml_string_with_keys() {
+        let config: Config = toml::from_str(
+            "keys = [\n\
+             { key = \"1\", mods = \"ALT\", action = { ActivateTab = 0 } },\n\
+             { key = \"t\", mods = \"ALT\", action = { SpawnTab = \"DefaultDomain\" } },\n\
+             { key = \"n\", mods = \"ALT\", action = \"Nop\" },\n\
+             ]\n\
+             mouse_bindings = [\n\
+             { event = { Up = { streak = 1, button = \"Left\" } }, mods = \"NONE\", \
+               action = { CompleteSelection = \"PrimarySelection\" } },\n\
+             ]",
+        )
+        .unwrap();
+        let rendered = config.to_toml_string().unwrap();
+
+        let value: toml::Value = rendered.parse().unwrap();
+        let keys = value["keys"].as_array().unwrap();
+        assert_eq!(keys.len(), 3);
+        assert_eq!(keys[0]["action"]["ActivateTab"].as_integer(), Some(0));
+        assert_eq!(
+            keys[1]["action"]["SpawnTab"].as_str(),
+            Some("DefaultDomain")
+        );
+        assert_eq!(keys[2]["action"].as_str(), Some("Nop"));
+        assert_eq!(value["mouse_bindings"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn window_frame_font_variants() {
+        let mut config = Config::default();
+        assert!(config
+            .window_frame
+            .font_for(&wezterm_term::CellAttributes::default())
+            .is_none());
+
+        config.window_frame.font_size = Some(10.0);
+        let config = config.compute_extra_defaults(None);
+        let frame = &config.window_frame;
+
+        let mut attrs = wezterm_term::CellAttributes::default();
+        let regular = frame.font_for(&attrs).unwrap();
+        assert_eq!(regular.fixed_size, Some(1000));
+        assert!(!regular.font[0].bold);
+
+        attrs.set_intensity(wezterm_term::Intensity::Bold);
+        attrs.set_italic(true);
+        let bold_italic = frame.font_for(&attrs).unwrap();
+        assert_eq!(bold_italic.fixed_size, Some(1000));
+        assert!(bold_italic.font[0].bold);
+        assert!(bold_italic.font[0].italic);
+    }
+
+    #[test]
+    fn builtin_color_schemes_are_parsed_on_use() {
+        let mut config = Config::default_config();
+        config.color_scheme = Some("solarized dark".to_string());
+        let palette = config.resolve_color_scheme().unwrap();
+        assert!(std::ptr::eq(
+            palette,
+            builtin_color_scheme("Builtin Solarized Dark").unwrap()
+        ));
+        assert!(builtin_color_scheme("No Such Scheme").is_none());
+        assert!(builtin_color_scheme_names().contains(&"Builtin Solarized Dark"));
+    }
+
+    #[test]
+    fn xdg_dir_ignores_relative_and_empty_paths() {
+        // Each case uses its own variable, as the tests run concurrently
+        assert_eq!(xdg_dir("WEZTERM_TEST_XDG_UNSET"), None);
+
+        std::env::set_var("WEZTERM_TEST_XDG_EMPTY", "");
+        assert_eq!(xdg_dir("WEZTERM_TEST_XDG_EMPTY"), None);
+
+        std::env::set_var("WEZTERM_TEST_XDG_RELATIVE", "relative/dir");
+        assert_eq!(xdg_dir("WEZTERM_TEST_XDG_RELATIVE"), None);
+
+        let absolute = std::env::temp_dir().join("xdg");
+        std::env::set_var("WEZTERM_TEST_XDG_ABSOLUTE", &absolute);
+        assert_eq!(
+            xdg_dir("WEZTERM_TEST_XDG_ABSOLUTE"),
+            Some(absolute.join("wezterm"))
+        );
+    }
+
+    #[test]
+    fn legacy_path_is_used_only_when_new_path_is_missing() {
+        let dir = std::env::temp_dir().join(format!("wezterm-legacy-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("new");
+        let legacy_path = dir.join("legacy");
+
+        // Neither exists: prefer the new location
+        assert_eq!(
+            path_with_legacy_fallback(path.clone(), legacy_path.clone()),
+            path
+        );
+
+        std::fs::write(&legacy_path, "").unwrap();
+        assert_eq!(
+            path_with_legacy_fallback(path.clone(), legacy_path.clone()),
+            legacy_path
+        );
+
+        std::fs::write(&path, "").unwrap();
+        assert_eq!(
+            path_with_legacy_fallback(path.clone(), legacy_path.clone()),
+            path
+        );
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -360,33 +360,62 @@ fn font<'lua>(
         bold: attrs.bold,
         italic: attrs.italic,
         is_fallback: false,
+        scale: None,
+        baseline_offset: None,
     });
     text_style.foreground = attrs.foreground;
 
     Ok(text_style)
 }
 
+/// An entry in the list passed to `wezterm.font_with_fallback`
+/// that adjusts the rendering of that font.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct FallbackFontAttributes {
+    family: String,
+    #[serde(default)]
+    scale: Option<f64>,
+    #[serde(default)]
+    baseline_offset: Option<f64>,
+}
+impl_lua_conversion!(FallbackFontAttributes);
+
 /// Given a list of font family names in order of preference, return a
 /// text style instance for that font configuration.
 ///
 /// `wezterm.font_with_fallback({"Operator Mono", "DengXian"})`
 ///
+/// An entry may instead be a table that specifies the family name
+/// along with a `scale` and/or `baseline_offset` for that font:
+///
+/// `wezterm.font_with_fallback({"Operator Mono", {family="Font Awesome", scale=0.9}})`
+///
 /// The second optional argument is a list of other TextStyle fields,
 /// as described by the `wezterm.font` documentation.
 fn font_with_fallback<'lua>(
     _lua: &'lua Lua,
-    (fallback, map_defaults): (Vec<String>, Option<TextStyleAttributes>),
+    (fallback, map_defaults): (Vec<Value<'lua>>, Option<TextStyleAttributes>),
 ) -> mlua::Result<TextStyle> {
     let attrs = map_defaults.unwrap_or_else(TextStyleAttributes::default);
     let mut text_style = TextStyle::default();
 
     text_style.font.clear();
-    for (idx, family) in fallback.into_iter().enumerate() {
+    for (idx, entry) in fallback.into_iter().enumerate() {
+        let entry = match entry {
+            Value::String(family) => FallbackFontAttributes {
+                family: family.to_str()?.to_string(),
+                scale: None,
+                baseline_offset: None,
+            },
+            entry => from_lua_value(entry)?,
+        };
         text_style.font.push(FontAttributes {
-            family,
+            family: entry.family,
             bold: attrs.bold,
             italic: attrs.italic,
             is_fallback: idx != 0,
+            scale: entry.scale,
+            baseline_offset: entry.baseline_offset,
         });
     }
     text_style.foreground = attrs.foreground;
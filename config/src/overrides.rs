@@ -0,0 +1,118 @@
+//! Applies the `--config name=value` overrides from the command line
+//! to the configuration table returned by the config file.
+use anyhow::bail;
+use mlua::{Lua, Table, Value};
+
+/// Sets `key` to `value` in `config`.
+/// `key` may be a dotted path such as `colors.background`, in which
+/// case any tables along the path that don't exist are created.
+/// `value` is evaluated as a lua expression; if that fails, or it
+/// evaluates to nil without being `nil`, then it is used as a string
+/// so that values such as `#202020` don't need to be quoted.
+pub(crate) fn apply_override<'l>(
+    lua: &'l Lua,
+    config: Value<'l>,
+    key: &str,
+    value: &str,
+) -> anyhow::Result<Value<'l>> {
+    let config = match config {
+        Value::Table(config) => config,
+        Value::Nil => lua.create_table()?,
+        _ => bail!(
+            "the configuration is not a table, so `{}` can't be set",
+            key
+        ),
+    };
+
+    let path: Vec<&str> = key.split('.').map(str::trim).collect();
+    if path.iter().any(|name| name.is_empty()) {
+        bail!("invalid configuration override name `{}`", key);
+    }
+
+    // Allow the expression to refer to the existing configuration
+    lua.globals().set("config", config.clone())?;
+    let value = eval_value(lua, key, value)?;
+
+    let (last, parents) = path.split_last().expect("split yields at least one item");
+    let mut table: Table = config.clone();
+    for (idx, name) in parents.iter().enumerate() {
+        let name = path_key(lua, name)?;
+        table = match table.raw_get::<_, Value>(name.clone())? {
+            Value::Table(child) => child,
+            Value::Nil => {
+                let child = lua.create_table()?;
+                table.raw_set(name, child.clone())?;
+                child
+            }
+            _ => bail!(
+                "can't set `{}` because `{}` is not a table",
+                key,
+                path[..=idx].join(".")
+            ),
+        };
+    }
+    table.raw_set(path_key(lua, last)?, value)?;
+
+    Ok(Value::Table(config))
+}
+
+/// Numeric path components index into arrays
+fn path_key<'l>(lua: &'l Lua, name: &str) -> mlua::Result<Value<'l>> {
+    match name.parse::<i64>() {
+        Ok(idx) => Ok(Value::Integer(idx)),
+        Err(_) => Ok(Value::String(lua.create_string(name)?)),
+    }
+}
+
+fn eval_value<'l>(lua: &'l Lua, key: &str, value: &str) -> anyhow::Result<Value<'l>> {
+    let code = format!("local wezterm = require 'wezterm'; return {}", value);
+    let chunk = lua
+        .load(&code)
+        .set_name(&format!("--config {}={}", key, value))?;
+    match chunk.eval::<Value>() {
+        Ok(Value::Nil) if value.trim() != "nil" => Ok(Value::String(lua.create_string(value)?)),
+        Ok(value) => Ok(value),
+        Err(err) => {
+            // Something like `#202020` parses, but fails to evaluate.
+            // If a string isn't acceptable for this option either, then
+            // the error will be reported when the config is converted.
+            log::debug!("--config {}: using {} as a string: {}", key, value, err);
+            Ok(Value::String(lua.create_string(value)?))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lua::make_lua_context;
+
+    #[test]
+    fn dotted_paths() -> anyhow::Result<()> {
+        let lua = make_lua_context(&std::env::current_dir()?)?;
+        let config = lua
+            .load("return { colors = { foreground = 'white' } }")
+            .eval()?;
+
+        let config = apply_override(&lua, config, "font_size", "14")?;
+        let config = apply_override(&lua, config, "colors.background", "#202020")?;
+        let config = apply_override(&lua, config, "tab_bar.style.left", "'<'")?;
+        let config = apply_override(&lua, config, "term", "xterm")?;
+
+        let config = match config {
+            Value::Table(config) => config,
+            _ => panic!("config is not a table"),
+        };
+        assert_eq!(config.get::<_, f64>("font_size")?, 14.0);
+        let colors: Table = config.get("colors")?;
+        assert_eq!(colors.get::<_, String>("foreground")?, "white");
+        assert_eq!(colors.get::<_, String>("background")?, "#202020");
+        let style: Table = config.get::<_, Table>("tab_bar")?.get("style")?;
+        assert_eq!(style.get::<_, String>("left")?, "<");
+        assert_eq!(config.get::<_, String>("term")?, "xterm");
+
+        assert!(apply_override(&lua, Value::Table(config.clone()), "font_size.x", "1").is_err());
+        assert!(apply_override(&lua, Value::Table(config), "colors..x", "1").is_err());
+        Ok(())
+    }
+}
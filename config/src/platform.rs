@@ -0,0 +1,145 @@
+//! Implements the platform specific sections of the configuration,
+//! such as `macos = { ... }`, whose options are merged over the rest
+//! of the configuration when running on that platform.
+use crate::Config;
+use anyhow::{bail, Context};
+use mlua::Value;
+
+/// The names of the platform specific sections
+const PLATFORMS: &[&str] = &["windows", "macos", "linux"];
+
+/// Returns the name of the section that applies to the current platform
+fn current_platform() -> Option<&'static str> {
+    if cfg!(windows) {
+        Some("windows")
+    } else if cfg!(target_os = "macos") {
+        Some("macos")
+    } else if cfg!(target_os = "linux") {
+        Some("linux")
+    } else {
+        None
+    }
+}
+
+/// Removes the platform specific sections from `config`, merging
+/// the section for the current platform, if any, over the rest of it.
+/// Each of the sections is checked, even those for other platforms,
+/// so that mistakes are reported regardless of the platform.
+/// Returns the merged config along with messages about the unknown
+/// options in the sections for other platforms; those in the section
+/// for the current platform are reported when the merged config is
+/// parsed.
+pub(crate) fn apply_platform_sections<'l>(
+    config: Value<'l>,
+) -> anyhow::Result<(Value<'l>, Vec<String>)> {
+    let table = match &config {
+        Value::Table(table) => table.clone(),
+        _ => return Ok((config, vec![])),
+    };
+
+    let mut matched = None;
+    let mut warnings = vec![];
+    for &name in PLATFORMS {
+        let section = match table.raw_get::<_, Value>(name)? {
+            Value::Nil => continue,
+            Value::Table(section) => section,
+            _ => bail!("`{}` must be a table of configuration options", name),
+        };
+        let (_, unknown_fields) =
+            luahelper::from_lua_value_with_warnings::<Config>(Value::Table(section.clone()))
+                .with_context(|| format!("Error in the `{}` section of the config", name))?;
+        if Some(name) == current_platform() {
+            matched = Some(section);
+        } else {
+            warnings.extend(
+                unknown_fields
+                    .into_iter()
+                    .map(|warning| format!("In the `{}` section: {}", name, warning)),
+            );
+        }
+        table.raw_set(name, Value::Nil)?;
+    }
+
+    if let Some(section) = matched {
+        crate::include::merge_lua(&table, &section)?;
+    }
+
+    Ok((config, warnings))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lua::make_lua_context;
+    use mlua::Table;
+
+    #[test]
+    fn platform_sections() -> anyhow::Result<()> {
+        let lua = make_lua_context(&std::env::current_dir()?)?;
+        let config = lua
+            .load(
+                r#"return {
+                    font_size = 10,
+                    colors = { foreground = 'white' },
+                    windows = { font_size = 11, colors = { background = 'black' } },
+                    macos = { font_size = 12, colors = { background = 'black' } },
+                    linux = { font_size = 13, colors = { background = 'black' } },
+                }"#,
+            )
+            .eval()?;
+
+        let config = match apply_platform_sections(config)?.0 {
+            Value::Table(config) => config,
+            _ => panic!("config is not a table"),
+        };
+        let expected_size = match current_platform() {
+            Some("windows") => 11.0,
+            Some("macos") => 12.0,
+            Some("linux") => 13.0,
+            _ => 10.0,
+        };
+        assert_eq!(config.get::<_, f64>("font_size")?, expected_size);
+        let colors: Table = config.get("colors")?;
+        assert_eq!(colors.get::<_, String>("foreground")?, "white");
+        for name in PLATFORMS {
+            assert!(config.get::<_, Option<Table>>(*name)?.is_none());
+        }
+
+        let config = lua.load("return { macos = 'nope' }").eval()?;
+        assert!(apply_platform_sections(config).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn platform_section_warnings() -> anyhow::Result<()> {
+        let lua = make_lua_context(&std::env::current_dir()?)?;
+        let config = lua
+            .load(
+                r#"return {
+                    windows = { font_sizee = 11 },
+                    macos = { font_sizee = 12 },
+                    linux = { font_sizee = 13 },
+                }"#,
+            )
+            .eval()?;
+
+        let (config, warnings) = apply_platform_sections(config)?;
+        let other_platforms: Vec<&str> = PLATFORMS
+            .iter()
+            .copied()
+            .filter(|&name| Some(name) != current_platform())
+            .collect();
+        assert_eq!(warnings.len(), other_platforms.len());
+        for (warning, name) in warnings.iter().zip(other_platforms) {
+            assert!(warning.starts_with(&format!("In the `{}` section: ", name)));
+            assert!(warning.contains("`font_sizee`"));
+        }
+
+        // The section for the current platform is merged, so its unknown
+        // options are reported when the whole config is parsed
+        let (_, warnings) = luahelper::from_lua_value_with_warnings::<Config>(config)?;
+        let expected = if current_platform().is_some() { 1 } else { 0 };
+        assert_eq!(warnings.len(), expected);
+        Ok(())
+    }
+}
@@ -27,6 +27,20 @@ pub struct SshDomain {
 
     /// The path to the wezterm binary on the remote host
     pub remote_wezterm_path: Option<String>,
+
+    /// If true, panes in this domain only display output; keyboard
+    /// and mouse input, pastes, resizes and spawning are not sent
+    /// to the server
+    #[serde(default)]
+    pub read_only: bool,
+
+    /// The program to run in new panes when none is specified,
+    /// in place of the default program of the remote host
+    pub default_prog: Option<Vec<String>>,
+
+    /// The working directory on the remote host for new panes
+    /// when none is specified
+    pub default_cwd: Option<PathBuf>,
 }
 impl_lua_conversion!(SshDomain);
 
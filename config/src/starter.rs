@@ -0,0 +1,145 @@
+//! Generates the starter configuration that is offered to the user
+//! the first time that wezterm is launched without a config file.
+use crate::{
+    create_user_owned_dirs, path_with_legacy_fallback, FontAttributes, TextStyle, CONFIG_DIR,
+    DATA_DIR, STATE_DIR,
+};
+use anyhow::{bail, Context};
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// The main config file.  It is deliberately mostly comments; the
+/// choices made in the first-run overlay live in `wezterm.toml`,
+/// which is pulled in via `include`.
+const STARTER_LUA: &str = r#"-- This is your wezterm configuration file.
+-- It was created by wezterm the first time that it was launched.
+-- The documentation for the available options can be found at
+-- https://wezfurlong.org/wezterm/config/files.html
+local wezterm = require 'wezterm';
+
+return {
+  -- The color scheme and font that you picked when this file was
+  -- created are stored in wezterm.toml.  You can edit that file,
+  -- or override those settings here; values in this file take
+  -- precedence over the included files.
+  include = {"wezterm.toml"},
+
+  -- font_size = 12.0,
+  -- hide_tab_bar_if_only_one_tab = true,
+  -- window_background_opacity = 1.0,
+  -- scrollback_lines = 3500,
+
+  -- Set this to true to have wezterm report an error, rather than
+  -- a warning, when this file contains an option that it doesn't
+  -- understand.
+  -- strict = false,
+
+  keys = {
+    -- {key="d", mods="CTRL|SHIFT", action=wezterm.action{SplitHorizontal={domain="CurrentPaneDomain"}}},
+  },
+}
+"#;
+
+const STARTER_TOML_HEADER: &str = "\
+# This file was written by wezterm to record the color scheme and font
+# that you picked the first time that it was launched.
+# It is included by wezterm.lua.
+";
+
+#[derive(Serialize)]
+struct Choices {
+    color_scheme: String,
+    font: TextStyle,
+}
+
+fn starter_toml(color_scheme: &str, font_family: &str) -> anyhow::Result<String> {
+    let choices = Choices {
+        color_scheme: color_scheme.to_string(),
+        font: TextStyle {
+            font: vec![FontAttributes::new(font_family)],
+            foreground: None,
+            fixed_size: None,
+        },
+    };
+    let body = toml::to_string_pretty(&choices)?;
+    Ok(format!("{}\n{}", STARTER_TOML_HEADER, body))
+}
+
+fn first_run_marker() -> PathBuf {
+    path_with_legacy_fallback(
+        STATE_DIR.join("first-run-done"),
+        DATA_DIR.join("first-run-done"),
+    )
+}
+
+/// Records that the first-run experience has been offered, so that
+/// it isn't offered again, even if the user declined it.
+pub fn mark_first_run_done() {
+    fn write_marker() -> anyhow::Result<()> {
+        let marker = first_run_marker();
+        if let Some(dir) = marker.parent() {
+            create_user_owned_dirs(dir)?;
+        }
+        std::fs::write(marker, "")?;
+        Ok(())
+    }
+    if let Err(err) = write_marker() {
+        log::error!("Failed to record that the first run is done: {:#}", err);
+    }
+}
+
+/// Returns true if wezterm was launched without a config file
+/// and hasn't previously offered to create one.
+pub fn should_offer_starter_config() -> bool {
+    crate::no_config_file_found() && !first_run_marker().exists()
+}
+
+/// Writes `wezterm.lua` and `wezterm.toml` to the config directory,
+/// recording the chosen color scheme and font in `wezterm.toml`.
+/// Refuses to replace an existing `wezterm.lua`.
+/// Returns the path to `wezterm.lua`.
+pub fn write_starter_config(color_scheme: &str, font_family: &str) -> anyhow::Result<PathBuf> {
+    create_user_owned_dirs(&CONFIG_DIR)
+        .with_context(|| format!("creating {}", CONFIG_DIR.display()))?;
+
+    let lua_path = CONFIG_DIR.join("wezterm.lua");
+    if lua_path.exists() {
+        bail!("{} already exists; not replacing it", lua_path.display());
+    }
+
+    let toml_path = CONFIG_DIR.join("wezterm.toml");
+    std::fs::write(&toml_path, starter_toml(color_scheme, font_family)?)
+        .with_context(|| format!("writing {}", toml_path.display()))?;
+    std::fs::write(&lua_path, STARTER_LUA)
+        .with_context(|| format!("writing {}", lua_path.display()))?;
+
+    mark_first_run_done();
+    Ok(lua_path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Config;
+
+    #[test]
+    fn toml_round_trip() -> anyhow::Result<()> {
+        let toml = starter_toml("Builtin Solarized Dark", "Fira Code")?;
+        let config: Config = toml::from_str(&toml)?;
+        assert_eq!(
+            config.color_scheme.as_deref(),
+            Some("Builtin Solarized Dark")
+        );
+        assert_eq!(config.font.font[0].family, "Fira Code");
+        Ok(())
+    }
+
+    #[test]
+    fn starter_lua_evaluates() -> anyhow::Result<()> {
+        let lua = crate::lua::make_lua_context(&std::env::current_dir()?)?;
+        let value: mlua::Value = lua.load(STARTER_LUA).eval()?;
+        let config: Config = luahelper::from_lua_value(value)?;
+        assert_eq!(config.include, vec!["wezterm.toml".to_string()]);
+        Ok(())
+    }
+}
@@ -16,6 +16,10 @@ impl wezterm_term::TerminalConfiguration for TermConfig {
         configuration().scrollback_lines
     }
 
+    fn scrollback_memory_limit(&self) -> Option<usize> {
+        configuration().scrollback_memory_limit
+    }
+
     fn hyperlink_rules(&self) -> (usize, Vec<HyperlinkRule>) {
         let config = configuration();
         (config.generation(), config.hyperlink_rules.clone())
@@ -25,6 +29,10 @@ impl wezterm_term::TerminalConfiguration for TermConfig {
         configuration().enable_csi_u_key_encoding
     }
 
+    fn enable_kitty_keyboard(&self) -> bool {
+        configuration().enable_kitty_keyboard
+    }
+
     fn color_palette(&self) -> ColorPalette {
         let config = configuration();
 
@@ -3,8 +3,10 @@ use crate::*;
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
 pub struct TlsDomainServer {
     /// The address:port combination on which the server will listen
-    /// for client connections
-    pub bind_address: String,
+    /// for client connections.  May be either a single string or a list
+    /// of strings to listen on several addresses.
+    #[serde(deserialize_with = "de_string_or_list")]
+    pub bind_address: Vec<String>,
 
     /// the path to an x509 PEM encoded private key file
     pub pem_private_key: Option<PathBuf>,
@@ -22,9 +24,77 @@ pub struct TlsDomainServer {
     /// to the trust store.
     #[serde(default)]
     pub pem_root_certs: Vec<PathBuf>,
+
+    /// If true, clients connect by performing a WebSocket handshake
+    /// after the TLS handshake, and the mux protocol is carried in
+    /// binary WebSocket messages rather than directly on the TLS stream.
+    #[serde(default)]
+    pub websocket: bool,
+
+    /// Additional certificates that are selected according to the
+    /// server name that the client requests via SNI.  Clients that
+    /// don't use SNI, or that request some other name, are presented
+    /// with `pem_cert`.
+    #[serde(default)]
+    pub sni_certificates: Vec<TlsServerCertificate>,
+
+    /// If true, clients may connect without a certificate and
+    /// authenticate using a one-time token minted by
+    /// `wezterm cli invite`.  Such clients are limited to the
+    /// window and access level that the invitation grants.
+    #[serde(default)]
+    pub allow_invitations: bool,
 }
 impl_lua_conversion!(TlsDomainServer);
 
+#[derive(Default, Debug, Clone, Deserialize, Serialize)]
+pub struct TlsServerCertificate {
+    /// The server name for which this certificate is presented
+    pub server_name: String,
+
+    /// the path to an x509 PEM encoded private key file
+    pub pem_private_key: PathBuf,
+
+    /// the path to an x509 PEM encoded certificate file
+    pub pem_cert: PathBuf,
+
+    /// the path to an x509 PEM encoded CA chain file
+    pub pem_ca: Option<PathBuf>,
+}
+impl_lua_conversion!(TlsServerCertificate);
+
+/// Deserialize either a string or a list of strings as a list
+fn de_string_or_list<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    struct StringOrList;
+
+    impl<'de> serde::de::Visitor<'de> for StringOrList {
+        type Value = Vec<String>;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("string or list of strings")
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<Vec<String>, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(vec![value.to_string()])
+        }
+
+        fn visit_seq<A>(self, seq: A) -> Result<Vec<String>, A::Error>
+        where
+            A: serde::de::SeqAccess<'de>,
+        {
+            Deserialize::deserialize(serde::de::value::SeqAccessDeserializer::new(seq))
+        }
+    }
+
+    deserializer.deserialize_any(StringOrList)
+}
+
 #[derive(Default, Debug, Clone, Deserialize, Serialize)]
 pub struct TlsDomainClient {
     /// The name of this specific domain.  Must be unique amongst
@@ -82,6 +152,20 @@ pub struct TlsDomainClient {
 
     /// The path to the wezterm binary on the remote host
     pub remote_wezterm_path: Option<String>,
+
+    /// If true, panes in this domain only display output; keyboard
+    /// and mouse input, pastes, resizes and spawning are not sent
+    /// to the server
+    #[serde(default)]
+    pub read_only: bool,
+
+    /// The program to run in new panes when none is specified,
+    /// in place of the default program of the remote host
+    pub default_prog: Option<Vec<String>>,
+
+    /// The working directory on the remote host for new panes
+    /// when none is specified
+    pub default_cwd: Option<PathBuf>,
 }
 impl_lua_conversion!(TlsDomainClient);
 
@@ -11,6 +11,8 @@ pub struct UnixDomain {
 
     /// The path to the socket.  If unspecified, a resonable default
     /// value will be computed.
+    /// On Linux, a path that begins with `@` names a socket in the
+    /// abstract namespace rather than in the filesystem.
     pub socket_path: Option<PathBuf>,
 
     /// If true, connect to this domain automatically at startup
@@ -25,11 +27,19 @@ pub struct UnixDomain {
     /// If we decide that we need to start the server, the command to run
     /// to set that up.  The default is to spawn:
     /// `wezterm-mux-server --daemonize`
+    /// with `--config-file` appended if one was specified for this
+    /// process, so that the server loads the same configuration,
     /// but it can be useful to set this to eg:
     /// `wsl -e wezterm-mux-server --daemonize` to start up
     /// a unix domain inside a wsl container.
     pub serve_command: Option<Vec<String>>,
 
+    /// If true, and the connection to the server fails because the server
+    /// has died, run `serve_command` to start a new server and attach to it.
+    /// The panes from the previous server are lost.
+    #[serde(default)]
+    pub restart_server_on_failure: bool,
+
     /// If true, bypass checking for secure ownership of the
     /// socket_path.  This is not recommended on a multi-user
     /// system, but is useful for example when running the
@@ -38,11 +48,24 @@ pub struct UnixDomain {
     #[serde(default)]
     pub skip_permissions_check: bool,
 
+    /// If true, panes in this domain only display output; keyboard
+    /// and mouse input, pastes, resizes and spawning are not sent
+    /// to the server
+    #[serde(default)]
+    pub read_only: bool,
+
     #[serde(default = "default_read_timeout")]
     pub read_timeout: Duration,
 
     #[serde(default = "default_write_timeout")]
     pub write_timeout: Duration,
+
+    /// The program to run in new panes when none is specified,
+    /// in place of the default program of the server
+    pub default_prog: Option<Vec<String>>,
+
+    /// The working directory for new panes when none is specified
+    pub default_cwd: Option<PathBuf>,
 }
 impl_lua_conversion!(UnixDomain);
 
@@ -54,9 +77,13 @@ impl Default for UnixDomain {
             connect_automatically: false,
             no_serve_automatically: false,
             serve_command: None,
+            restart_server_on_failure: false,
             skip_permissions_check: false,
+            read_only: false,
             read_timeout: default_read_timeout(),
             write_timeout: default_write_timeout(),
+            default_prog: None,
+            default_cwd: None,
         }
     }
 }
@@ -80,16 +107,123 @@ impl UnixDomain {
     pub fn serve_command(&self) -> anyhow::Result<Vec<OsString>> {
         match self.serve_command.as_ref() {
             Some(cmd) => Ok(cmd.iter().map(Into::into).collect()),
-            None => Ok(vec![
-                std::env::current_exe()?
-                    .with_file_name(if cfg!(windows) {
-                        "wezterm-mux-server.exe"
-                    } else {
-                        "wezterm-mux-server"
-                    })
-                    .into_os_string(),
-                OsString::from("--daemonize"),
-            ]),
+            None => {
+                let mut argv = vec![
+                    std::env::current_exe()?
+                        .with_file_name(if cfg!(windows) {
+                            "wezterm-mux-server.exe"
+                        } else {
+                            "wezterm-mux-server"
+                        })
+                        .into_os_string(),
+                    OsString::from("--daemonize"),
+                ];
+                // The server would otherwise only see the path via
+                // WEZTERM_CONFIG_FILE, which isn't set if the file
+                // failed to load
+                if let Some(path) = config_file_override() {
+                    argv.push(OsString::from("--config-file"));
+                    argv.push(path.into_os_string());
+                }
+                Ok(argv)
+            }
+        }
+    }
+}
+
+/// Returns the name of the abstract namespace socket that `path` refers
+/// to, if it begins with `@`.  The name excludes the leading `@`.
+#[cfg(target_os = "linux")]
+pub fn abstract_socket_name(path: &Path) -> Option<&[u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    let path = path.as_os_str().as_bytes();
+    if path.starts_with(b"@") {
+        Some(&path[1..])
+    } else {
+        None
+    }
+}
+
+/// Abstract namespace sockets aren't supported by std, so these
+/// functions create them using libc and then wrap them in the std types.
+#[cfg(target_os = "linux")]
+mod abstract_socket {
+    use std::io::{Error, ErrorKind, Result};
+    use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    fn socket_with_name(name: &[u8], listen: bool) -> Result<RawFd> {
+        let mut addr: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+        addr.sun_family = libc::AF_UNIX as libc::sa_family_t;
+        // The leading NUL byte of sun_path places the name in the
+        // abstract namespace
+        if name.len() + 1 > addr.sun_path.len() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "abstract socket name is too long",
+            ));
+        }
+        for (dest, src) in addr.sun_path[1..].iter_mut().zip(name) {
+            *dest = *src as libc::c_char;
+        }
+        let len = std::mem::size_of::<libc::sa_family_t>() + 1 + name.len();
+
+        let fd = unsafe { libc::socket(libc::AF_UNIX, libc::SOCK_STREAM | libc::SOCK_CLOEXEC, 0) };
+        if fd == -1 {
+            return Err(Error::last_os_error());
         }
+        let addr = &addr as *const libc::sockaddr_un as *const libc::sockaddr;
+        let len = len as libc::socklen_t;
+        let res = unsafe {
+            if listen {
+                match libc::bind(fd, addr, len) {
+                    0 => libc::listen(fd, 128),
+                    err => err,
+                }
+            } else {
+                libc::connect(fd, addr, len)
+            }
+        };
+        if res != 0 {
+            let err = Error::last_os_error();
+            unsafe { libc::close(fd) };
+            return Err(err);
+        }
+        Ok(fd)
+    }
+
+    pub fn bind_abstract_socket(name: &[u8]) -> Result<UnixListener> {
+        let fd = socket_with_name(name, true)?;
+        Ok(unsafe { UnixListener::from_raw_fd(fd) })
+    }
+
+    pub fn connect_abstract_socket(name: &[u8]) -> Result<UnixStream> {
+        let fd = socket_with_name(name, false)?;
+        Ok(unsafe { UnixStream::from_raw_fd(fd) })
+    }
+
+    /// Returns true if the process on the other end of `stream` is
+    /// running as the same user as this process.  Unlike sockets in
+    /// the filesystem, abstract sockets have no permissions, so this
+    /// is how access to them is restricted.
+    pub fn peer_is_same_user(stream: &UnixStream) -> Result<bool> {
+        let mut cred: libc::ucred = unsafe { std::mem::zeroed() };
+        let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+        let res = unsafe {
+            libc::getsockopt(
+                stream.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_PEERCRED,
+                &mut cred as *mut libc::ucred as *mut libc::c_void,
+                &mut len,
+            )
+        };
+        if res != 0 {
+            return Err(Error::last_os_error());
+        }
+        Ok(cred.uid == unsafe { libc::getuid() })
     }
 }
+
+#[cfg(target_os = "linux")]
+pub use abstract_socket::*;
@@ -0,0 +1,177 @@
+//! The logger wraps the env_logger that is configured by `WEZTERM_LOG`.
+//! In addition to writing to stderr, it retains the most recent
+//! entries in memory, so that they can be shown in the gui, and can
+//! also write them to a file.
+use chrono::prelude::*;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// How many entries are retained in memory
+const MAX_ENTRIES: usize = 500;
+
+/// How many of the log files that are created in the state
+/// directory for an executable are kept, including the new one
+const MAX_LOG_FILES: usize = 10;
+
+lazy_static::lazy_static! {
+    static ref ENTRIES: Mutex<VecDeque<Entry>> = Mutex::new(VecDeque::new());
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub then: DateTime<Local>,
+    pub level: Level,
+    pub target: String,
+    pub msg: String,
+}
+
+impl std::fmt::Display for Entry {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            fmt,
+            "{} {:<5} {} > {}",
+            self.then.format("%H:%M:%S%.3f"),
+            self.level,
+            self.target,
+            self.msg
+        )
+    }
+}
+
+/// Returns the most recently logged entries, oldest first
+pub fn get_entries() -> Vec<Entry> {
+    ENTRIES.lock().unwrap().iter().cloned().collect()
+}
+
+struct Logger {
+    stderr: env_logger::Logger,
+    file: Option<Mutex<File>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.stderr.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.stderr.matches(record) {
+            return;
+        }
+        self.stderr.log(record);
+
+        let entry = Entry {
+            then: Local::now(),
+            level: record.level(),
+            target: record.target().to_string(),
+            msg: record.args().to_string(),
+        };
+
+        if let Some(file) = &self.file {
+            let mut file = file.lock().unwrap();
+            writeln!(file, "{}", entry).ok();
+        }
+
+        let mut entries = ENTRIES.lock().unwrap();
+        if entries.len() == MAX_ENTRIES {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    fn flush(&self) {
+        self.stderr.flush();
+        if let Some(file) = &self.file {
+            file.lock().unwrap().flush().ok();
+        }
+    }
+}
+
+/// Determines where to write the log file, if anywhere.
+/// `WEZTERM_LOG_FILE` may be set to the path of the file.  If it is
+/// set to `1` then the file is created in the state directory and
+/// is named after the executable and the process id.
+fn log_file_path() -> Option<PathBuf> {
+    let value = std::env::var_os("WEZTERM_LOG_FILE")?;
+    if value.is_empty() {
+        return None;
+    }
+    if value != "1" {
+        return Some(PathBuf::from(value));
+    }
+    let exe = std::env::current_exe().ok();
+    let name = exe
+        .as_ref()
+        .and_then(|exe| exe.file_stem())
+        .and_then(|name| name.to_str())
+        .unwrap_or("wezterm");
+    prune_log_files(&config::STATE_DIR, name);
+    Some(config::STATE_DIR.join(format!("{}-log-{}.txt", name, std::process::id())))
+}
+
+/// Removes the oldest of the log files that were previously created
+/// in `dir` for the executable `name`, so that a new file for each
+/// process doesn't accumulate in the state directory without limit
+fn prune_log_files(dir: &Path, name: &str) {
+    let prefix = format!("{}-log-", name);
+    let mut logs: Vec<_> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .map(|file_name| file_name.starts_with(&prefix) && file_name.ends_with(".txt"))
+                    .unwrap_or(false)
+            })
+            .filter_map(|entry| {
+                let modified = entry.metadata().ok()?.modified().ok()?;
+                Some((modified, entry.path()))
+            })
+            .collect(),
+        Err(_) => return,
+    };
+    if logs.len() < MAX_LOG_FILES {
+        return;
+    }
+    // Newest first; leave room for the file that is about to be created
+    logs.sort_by(|a, b| b.0.cmp(&a.0));
+    for (_, path) in logs.into_iter().skip(MAX_LOG_FILES - 1) {
+        if let Err(err) = std::fs::remove_file(&path) {
+            eprintln!("Unable to remove old log file {}: {}", path.display(), err);
+        }
+    }
+}
+
+fn open_log_file(path: &Path) -> anyhow::Result<File> {
+    if let Some(dir) = path.parent() {
+        config::create_user_owned_dirs(dir)?;
+    }
+    Ok(File::create(path)?)
+}
+
+pub fn setup_logger() {
+    let mut builder = pretty_env_logger::formatted_timed_builder();
+    if let Ok(s) = std::env::var("WEZTERM_LOG") {
+        builder.parse_filters(&s);
+    } else {
+        builder.filter(None, LevelFilter::Info);
+    }
+    let stderr = builder.build();
+
+    let file = log_file_path().and_then(|path| match open_log_file(&path) {
+        Ok(file) => Some(Mutex::new(file)),
+        Err(err) => {
+            eprintln!("Unable to open log file {}: {:#}", path.display(), err);
+            None
+        }
+    });
+
+    let max_level = stderr.filter();
+    if log::set_boxed_logger(Box::new(Logger { stderr, file })).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
@@ -4,8 +4,8 @@ use serde::{Deserialize, Serialize};
 
 mod serde_lua;
 pub use mlua;
-pub use serde_lua::from_lua_value;
 pub use serde_lua::ser::to_lua_value;
+pub use serde_lua::{from_lua_value, from_lua_value_with_warnings};
 
 /// Implement lua conversion traits for a type.
 /// This implementation requires that the type implement
@@ -5,6 +5,7 @@ use serde::de::{
     IntoDeserializer, Unexpected, VariantAccess, Visitor,
 };
 use serde::{serde_if_integer128, Deserialize};
+use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::collections::BTreeMap;
 use std::convert::TryInto;
@@ -21,6 +22,25 @@ where
     T::deserialize(ValueWrapper(value))
 }
 
+thread_local! {
+    /// When set, the messages about unknown fields that are produced
+    /// by `visit_table` are collected here rather than logged
+    static UNKNOWN_FIELDS: RefCell<Option<Vec<String>>> = RefCell::new(None);
+}
+
+/// Like `from_lua_value`, but rather than logging a message for each
+/// unknown field that is ignored, the messages are returned alongside
+/// the value so that the caller can decide how to report them.
+pub fn from_lua_value_with_warnings<T>(value: Value) -> Result<(T, Vec<String>), Error>
+where
+    T: DeserializeOwned,
+{
+    let prior = UNKNOWN_FIELDS.with(|w| w.borrow_mut().replace(vec![]));
+    let result = T::deserialize(ValueWrapper(value));
+    let warnings = UNKNOWN_FIELDS.with(|w| std::mem::replace(&mut *w.borrow_mut(), prior));
+    Ok((result?, warnings.unwrap_or_default()))
+}
+
 fn unexpected<'lua>(v: &'lua Value<'lua>) -> Unexpected<'lua> {
     match v {
         Value::Nil => Unexpected::Other("lua nil"),
@@ -302,12 +322,22 @@ where
                                 }
                                 message.push('.');
                             }
-                            log::error!(
+                            let message = format!(
                                 "Ignoring unknown field `{}` in struct of type `{}`. {}",
                                 pair.0,
                                 struct_name.unwrap_or("<unknown>"),
                                 message
                             );
+                            let message = UNKNOWN_FIELDS.with(|w| match w.borrow_mut().as_mut() {
+                                Some(warnings) => {
+                                    warnings.push(message);
+                                    None
+                                }
+                                None => Some(message),
+                            });
+                            if let Some(message) = message {
+                                log::error!("{}", message);
+                            }
 
                             continue;
                         }
@@ -745,9 +775,12 @@ impl<'de, 'lua> VariantAccess<'de> for VariantDeserializer<'lua> {
     {
         match self.value {
             Some(value) => seed.deserialize(ValueWrapper(value)),
-            None => Err(Error::custom(format!(
-                "Expected a variant with parameters but got a unit variant instead"
-            ))),
+            // Allow the parameter to be omitted if it is optional
+            None => seed.deserialize(ValueWrapper(Value::Nil)).map_err(|_| {
+                Error::custom(format!(
+                    "Expected a variant with parameters but got a unit variant instead"
+                ))
+            }),
         }
     }
 
@@ -944,6 +977,20 @@ mod test {
             {\n    \"age\": true,\n    \"hello\": \"hello\",\n}\n\
             invalid type: boolean `true`, expected integer",
         );
+
+        let (res, warnings) = from_lua_value_with_warnings::<MyMap>(
+            lua.load("{hello=\"hello\", age=42, agee=1}")
+                .eval()
+                .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.age, 42);
+        assert_eq!(
+            warnings,
+            vec!["Ignoring unknown field `agee` in struct of type `MyMap`. \
+                Did you mean `age`? Other possible fields are `hello`."
+                .to_string()]
+        );
     }
 
     #[test]
@@ -991,5 +1038,9 @@ mod test {
 
         let res: AnotherEnum = from_lua_value(lua.load("{ThisOne={}}").eval().unwrap()).unwrap();
         assert_eq!(res, AnotherEnum::ThisOne(None));
+
+        let res: AnotherEnum = from_lua_value(lua.load("\"ThisOne\"").eval().unwrap()).unwrap();
+        assert_eq!(res, AnotherEnum::ThisOne(None));
+        round_trip(res);
     }
 }
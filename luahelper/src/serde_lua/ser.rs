@@ -322,6 +322,12 @@ impl<'lua> serde::Serializer for LuaSerializer<'lua> {
     ) -> Result<Value<'lua>, Error> {
         let value = value.serialize(LuaSerializer { lua: self.lua })?;
 
+        // A variant whose optional parameter is absent is represented
+        // by its name alone, which the deserializer accepts in turn
+        if let Value::Nil = value {
+            return self.serialize_str(variant);
+        }
+
         let table = self.lua.create_table().map_err(Error::lua)?;
         table.set(variant, value).map_err(Error::lua)?;
         Ok(Value::Table(table))
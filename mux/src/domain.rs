@@ -12,10 +12,13 @@ use crate::window::WindowId;
 use crate::Mux;
 use anyhow::{bail, Error};
 use async_trait::async_trait;
-use config::configuration;
+use config::{configuration, ConfigHandle, CustomDomain};
 use downcast_rs::{impl_downcast, Downcast};
 use portable_pty::{native_pty_system, CommandBuilder, PtySize, PtySystem};
+use std::collections::HashMap;
 use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use url::Url;
 
 static DOMAIN_ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
 pub type DomainId = usize;
@@ -30,6 +33,94 @@ pub fn alloc_domain_id() -> DomainId {
     DOMAIN_ID.fetch_add(1, ::std::sync::atomic::Ordering::Relaxed)
 }
 
+/// Converts the working directory of a pane, as reported via OSC 7,
+/// into the `command_dir` that is passed to `Domain::spawn` and
+/// `Domain::split_pane` so that new panes can start in the same
+/// directory.  Only `file` URLs can be used.
+pub fn command_dir_from_url(url: &Url) -> Option<String> {
+    if url.scheme() != "file" {
+        return None;
+    }
+    let path = url.path().to_string();
+    // On Windows the file URI can produce a path like:
+    // `/C:\Users` which is valid in a file URI, but the leading slash
+    // is not liked by the windows file APIs, so we strip it off here.
+    let bytes = path.as_bytes();
+    if bytes.len() > 2 && bytes[0] == b'/' && bytes[2] == b':' {
+        Some(path[1..].to_owned())
+    } else {
+        Some(path)
+    }
+}
+
+/// Something that can be spawned in a domain, as suggested
+/// by `Domain::spawn_targets`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpawnTarget {
+    /// Describes the target to the user
+    pub label: String,
+    /// The command to spawn in the domain
+    pub args: Vec<String>,
+}
+
+/// Creates a domain from its entry in the `custom_domains`
+/// section of the configuration
+pub type DomainFactory = fn(&CustomDomain) -> anyhow::Result<Arc<dyn Domain>>;
+
+lazy_static::lazy_static! {
+    static ref DOMAIN_TYPES: Mutex<HashMap<String, DomainFactory>> = {
+        let mut types: HashMap<String, DomainFactory> = HashMap::new();
+        types.insert(
+            "kubernetes".to_string(),
+            crate::kubernetes::KubernetesDomain::create,
+        );
+        Mutex::new(types)
+    };
+}
+
+/// Registers `factory` as the implementation of the domain type
+/// named `domain_type`, so that `custom_domains` entries with that
+/// type can be created by `create_custom_domains`.
+/// This must be called before the domains are created at startup.
+pub fn register_domain_type(domain_type: &str, factory: DomainFactory) {
+    DOMAIN_TYPES
+        .lock()
+        .unwrap()
+        .insert(domain_type.to_string(), factory);
+}
+
+/// Creates the domains described by the `custom_domains` section of
+/// the configuration.  Entries that have an unregistered type, or that
+/// fail to be created, are logged and skipped.
+pub fn create_custom_domains(config: &ConfigHandle) -> Vec<Arc<dyn Domain>> {
+    let types = DOMAIN_TYPES.lock().unwrap();
+    config
+        .custom_domains
+        .iter()
+        .filter_map(|dom| {
+            let factory = match types.get(&dom.domain_type) {
+                Some(factory) => factory,
+                None => {
+                    log::error!(
+                        "custom domain `{}` has type `{}`, but no such \
+                         domain type has been registered",
+                        dom.name,
+                        dom.domain_type
+                    );
+                    return None;
+                }
+            };
+            match factory(dom) {
+                Ok(domain) => Some(domain),
+                Err(err) => {
+                    log::error!("failed to create custom domain `{}`: {:#}", dom.name, err);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
 #[async_trait(?Send)]
 pub trait Domain: Downcast {
     /// Spawn a new command within this domain
@@ -71,6 +162,21 @@ pub trait Domain: Downcast {
         self.domain_name()
     }
 
+    /// Returns the things that can be spawned in this domain other
+    /// than its default program, such as the containers that it can
+    /// reach.  They are listed in the launcher menu once the domain
+    /// is attached.
+    fn spawn_targets(&self) -> Vec<SpawnTarget> {
+        vec![]
+    }
+
+    /// Called after the tab `tab_id` has been moved to a different
+    /// position within the window `window_id`, so that domains that
+    /// mirror a remote window can reorder it to match
+    async fn tab_was_moved(&self, _window_id: WindowId, _tab_id: TabId) -> anyhow::Result<()> {
+        Ok(())
+    }
+
     /// Re-attach to any tabs that might be pre-existing in this domain
     async fn attach(&self) -> anyhow::Result<()>;
 
@@ -113,13 +219,9 @@ impl Domain for LocalDomain {
         window: WindowId,
     ) -> Result<Rc<Tab>, Error> {
         let config = configuration();
-        let mut cmd = match command {
-            Some(mut cmd) => {
-                config.apply_cmd_defaults(&mut cmd);
-                cmd
-            }
-            None => config.build_prog(None)?,
-        };
+        let mut cmd = config.build_command(command)?;
+        config.apply_domain_environment(&self.name, &mut cmd);
+        cmd.contain_process_tree(config.contain_spawned_processes);
         if let Some(dir) = command_dir {
             // I'm not normally a fan of existence checking, but not checking here
             // can be painful; in the case where a tab is local but has connected
@@ -131,7 +233,9 @@ impl Domain for LocalDomain {
         }
         let pair = self.pty_system.openpty(size)?;
         let pane_id = alloc_pane_id();
+        let tab = Rc::new(Tab::new(&size));
         cmd.env("WEZTERM_PANE", pane_id.to_string());
+        cmd.env("WEZTERM_TAB", tab.tab_id().to_string());
 
         let child = pair.slave.spawn_command(cmd)?;
         log::trace!("spawned: {:?}", child);
@@ -155,7 +259,6 @@ impl Domain for LocalDomain {
             self.id,
         ));
 
-        let tab = Rc::new(Tab::new(&size));
         tab.assign_pane(&pane);
 
         mux.add_tab_and_active_pane(&tab)?;
@@ -193,13 +296,9 @@ impl Domain for LocalDomain {
         };
 
         let config = configuration();
-        let mut cmd = match command {
-            Some(mut cmd) => {
-                config.apply_cmd_defaults(&mut cmd);
-                cmd
-            }
-            None => config.build_prog(None)?,
-        };
+        let mut cmd = config.build_command(command)?;
+        config.apply_domain_environment(&self.name, &mut cmd);
+        cmd.contain_process_tree(config.contain_spawned_processes);
         if let Some(dir) = command_dir {
             // I'm not normally a fan of existence checking, but not checking here
             // can be painful; in the case where a tab is local but has connected
@@ -212,6 +311,7 @@ impl Domain for LocalDomain {
         let pair = self.pty_system.openpty(split_size.second)?;
         let pane_id = alloc_pane_id();
         cmd.env("WEZTERM_PANE", pane_id.to_string());
+        cmd.env("WEZTERM_TAB", tab.tab_id().to_string());
         let child = pair.slave.spawn_command(cmd)?;
         log::trace!("spawned: {:?}", child);
 
@@ -0,0 +1,64 @@
+//! Runs the user defined event_hooks commands
+use config::{configuration, HookEvent};
+use serde_json::{json, Value};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Runs each of the configured hooks for `event`, writing a JSON
+/// object describing the event to its stdin.  `details` is merged
+/// into that object alongside the name of the event.
+/// The hooks run asynchronously; we don't wait for them to complete.
+pub fn run_event_hooks(event: HookEvent, details: Value) {
+    let config = configuration();
+    let mut hooks = config
+        .event_hooks
+        .iter()
+        .filter(|hook| hook.event == event)
+        .peekable();
+    if hooks.peek().is_none() {
+        return;
+    }
+
+    let mut payload = json!({ "event": event });
+    if let (Some(payload), Value::Object(details)) = (payload.as_object_mut(), details) {
+        payload.extend(details);
+    }
+    let payload = payload.to_string();
+
+    for hook in hooks {
+        let (prog, args) = match hook.args.split_first() {
+            Some(split) => split,
+            None => {
+                log::error!("event_hooks entry for {:?} has no args", event);
+                continue;
+            }
+        };
+
+        let mut child = match Command::new(prog)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+        {
+            Ok(child) => child,
+            Err(err) => {
+                log::error!(
+                    "failed to run hook {:?} for {:?}: {}",
+                    hook.args,
+                    event,
+                    err
+                );
+                continue;
+            }
+        };
+
+        let payload = payload.clone();
+        std::thread::spawn(move || {
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(payload.as_bytes()).ok();
+            }
+            // Reap the child so that it doesn't linger as a zombie
+            child.wait().ok();
+        });
+    }
+}
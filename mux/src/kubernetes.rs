@@ -0,0 +1,502 @@
+//! The kubernetes domain spawns panes that run `kubectl exec` to
+//! attach to a shell, or some other command, in a pod.
+//! It is configured through the `custom_domains` section of the
+//! configuration, using the `kubernetes` domain type.
+use crate::domain::{Domain, DomainId, DomainState, LocalDomain, SpawnTarget};
+use crate::pane::{Pane, PaneId};
+use crate::tab::{SplitDirection, Tab, TabId};
+use crate::window::WindowId;
+use anyhow::{anyhow, bail, Context};
+use async_trait::async_trait;
+use config::{configuration, Config, CustomDomain};
+use portable_pty::{CommandBuilder, PtySize};
+use std::ffi::OsString;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long the list of pods is used before it is refreshed
+const POD_LIST_TTL: Duration = Duration::from_secs(30);
+
+#[derive(Default)]
+struct PodList {
+    pods: Vec<String>,
+    updated: Option<Instant>,
+    refreshing: bool,
+}
+
+#[derive(Clone)]
+struct KubectlArgs {
+    kubectl: String,
+    context: Option<String>,
+    namespace: Option<String>,
+}
+
+impl KubectlArgs {
+    fn command(&self) -> Vec<String> {
+        let mut args = vec![self.kubectl.clone()];
+        if let Some(context) = &self.context {
+            args.push("--context".to_string());
+            args.push(context.clone());
+        }
+        if let Some(namespace) = &self.namespace {
+            args.push("--namespace".to_string());
+            args.push(namespace.clone());
+        }
+        args
+    }
+
+    /// Returns the names of the running pods, in the `pod/NAME`
+    /// form that is accepted by `kubectl exec`
+    fn list_pods(&self) -> anyhow::Result<Vec<String>> {
+        let args = self.command();
+        let output = std::process::Command::new(&args[0])
+            .args(&args[1..])
+            .args(&[
+                "get",
+                "pods",
+                "--output=name",
+                "--field-selector=status.phase=Running",
+            ])
+            .output()
+            .with_context(|| format!("running {}", self.kubectl))?;
+        if !output.status.success() {
+            bail!(
+                "kubectl get pods failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect())
+    }
+}
+
+pub struct KubernetesDomain {
+    local: LocalDomain,
+    kubectl: KubectlArgs,
+    /// The pod used when the command doesn't name one
+    pod: Option<String>,
+    container: Option<String>,
+    /// The command run in the pod when none is specified
+    default_prog: Vec<String>,
+    pods: Arc<Mutex<PodList>>,
+    attached: Mutex<bool>,
+}
+
+impl KubernetesDomain {
+    pub fn new(config: &CustomDomain) -> anyhow::Result<Self> {
+        let option = |name: &str| config.options.get(name).cloned();
+        Ok(Self {
+            local: LocalDomain::new(&config.name)?,
+            kubectl: KubectlArgs {
+                kubectl: option("kubectl").unwrap_or_else(|| "kubectl".to_string()),
+                context: option("context"),
+                namespace: option("namespace"),
+            },
+            pod: option("pod").map(|pod| qualify_pod(&pod)),
+            container: option("container"),
+            default_prog: match &config.default_prog {
+                Some(prog) if !prog.is_empty() => prog.clone(),
+                _ => vec![option("shell").unwrap_or_else(|| "sh".to_string())],
+            },
+            pods: Arc::new(Mutex::new(PodList::default())),
+            attached: Mutex::new(false),
+        })
+    }
+
+    /// The `DomainFactory` for the `kubernetes` domain type
+    pub fn create(config: &CustomDomain) -> anyhow::Result<Arc<dyn Domain>> {
+        Ok(Arc::new(Self::new(config)?))
+    }
+
+    /// Maps `command` to the `kubectl exec` invocation that runs it.
+    /// If the first argument is of the form `pod/NAME` then it selects
+    /// the pod, otherwise the configured pod is used.  The remaining
+    /// arguments are the command to run in the pod; the `default_prog`
+    /// of the domain, or the configured shell, is used if there are none.
+    fn build_command(&self, command: Option<CommandBuilder>) -> anyhow::Result<CommandBuilder> {
+        let mut argv: Vec<OsString> = command
+            .as_ref()
+            .map(|cmd| cmd.get_argv().clone())
+            .unwrap_or_default();
+
+        let pod = match argv.first().and_then(|arg| arg.to_str()) {
+            Some(arg) if arg.starts_with("pod/") => {
+                let pod = arg.to_string();
+                argv.remove(0);
+                pod
+            }
+            _ => self.pod.clone().ok_or_else(|| {
+                anyhow!(
+                    "kubernetes domain `{}` has no `pod` option, so the \
+                     command must start with pod/NAME",
+                    self.local.domain_name()
+                )
+            })?,
+        };
+        if argv.is_empty() {
+            argv = self.default_prog.iter().map(Into::into).collect();
+        }
+        // The environment of kubectl isn't passed on to the pod, so
+        // the environment of the spawn is applied in the pod by `env`
+        let env = pod_environment(&configuration(), self.local.domain_name(), command.as_ref());
+        argv.splice(0..0, env);
+
+        let mut cmd = CommandBuilder::new(&self.kubectl.kubectl);
+        cmd.args(&self.kubectl.command()[1..]);
+        cmd.args(&["exec", "--stdin", "--tty", pod.as_str()]);
+        if let Some(container) = &self.container {
+            cmd.args(&["--container", container.as_str()]);
+        }
+        cmd.arg("--");
+        cmd.args(&argv);
+        Ok(cmd)
+    }
+
+    /// Refreshes the list of pods in the background if it is stale
+    fn refresh_pods(&self) {
+        let mut pods = self.pods.lock().unwrap();
+        let stale = pods
+            .updated
+            .map(|updated| updated.elapsed() > POD_LIST_TTL)
+            .unwrap_or(true);
+        if !stale || pods.refreshing {
+            return;
+        }
+        pods.refreshing = true;
+
+        let list = Arc::clone(&self.pods);
+        let kubectl = self.kubectl.clone();
+        std::thread::spawn(move || {
+            let result = kubectl.list_pods();
+            let mut list = list.lock().unwrap();
+            list.refreshing = false;
+            list.updated.replace(Instant::now());
+            match result {
+                Ok(pods) => list.pods = pods,
+                Err(err) => log::error!("{:#}", err),
+            }
+        });
+    }
+}
+
+/// Returns the `env` invocation that applies the environment of the
+/// spawned `command`, the `set_environment_variables` and the
+/// `domain_environment` of `domain_name` to the command run in the pod,
+/// in the same order of precedence as a local spawn, or nothing if
+/// there is no environment to apply
+fn pod_environment(
+    config: &Config,
+    domain_name: &str,
+    command: Option<&CommandBuilder>,
+) -> Vec<OsString> {
+    let mut env = CommandBuilder::new_default_prog();
+    if let Some(command) = command {
+        for (k, v) in command.iter_env() {
+            env.env(k, v);
+        }
+        for k in command.iter_removed_env() {
+            env.env_remove(k);
+        }
+    }
+    config.apply_environment_variables(&mut env);
+    config.apply_domain_environment(domain_name, &mut env);
+
+    let mut args: Vec<OsString> = vec![];
+    for k in env.iter_removed_env() {
+        args.push("-u".into());
+        args.push(k.into());
+    }
+    let mut keys: Vec<_> = env.iter_env().map(|(k, _)| k).collect();
+    keys.sort();
+    keys.dedup();
+    for k in keys {
+        if let Some(v) = env.get_env(k) {
+            let mut var = k.to_owned();
+            var.push("=");
+            var.push(v);
+            args.push(var);
+        }
+    }
+    if !args.is_empty() {
+        args.insert(0, "env".into());
+    }
+    args
+}
+
+/// Allows the `pod` option to be specified with or without
+/// the `pod/` prefix
+fn qualify_pod(pod: &str) -> String {
+    if pod.contains('/') {
+        pod.to_string()
+    } else {
+        format!("pod/{}", pod)
+    }
+}
+
+#[async_trait(?Send)]
+impl Domain for KubernetesDomain {
+    async fn spawn(
+        &self,
+        size: PtySize,
+        command: Option<CommandBuilder>,
+        _command_dir: Option<String>,
+        window: WindowId,
+    ) -> anyhow::Result<Rc<Tab>> {
+        // The working directory of the local pane has no
+        // meaning inside the pod, so it isn't passed on
+        let cmd = self.build_command(command)?;
+        self.local.spawn(size, Some(cmd), None, window).await
+    }
+
+    async fn split_pane(
+        &self,
+        command: Option<CommandBuilder>,
+        _command_dir: Option<String>,
+        tab: TabId,
+        pane_id: PaneId,
+        direction: SplitDirection,
+    ) -> anyhow::Result<Rc<dyn Pane>> {
+        let cmd = self.build_command(command)?;
+        self.local
+            .split_pane(Some(cmd), None, tab, pane_id, direction)
+            .await
+    }
+
+    fn domain_id(&self) -> DomainId {
+        // The panes are created by the local domain, so
+        // they must be able to find us by its id
+        self.local.domain_id()
+    }
+
+    fn domain_name(&self) -> &str {
+        self.local.domain_name()
+    }
+
+    fn spawn_targets(&self) -> Vec<SpawnTarget> {
+        self.refresh_pods();
+        self.pods
+            .lock()
+            .unwrap()
+            .pods
+            .iter()
+            .map(|pod| SpawnTarget {
+                label: pod.trim_start_matches("pod/").to_string(),
+                args: vec![pod.clone()],
+            })
+            .collect()
+    }
+
+    async fn attach(&self) -> anyhow::Result<()> {
+        let kubectl = self.kubectl.clone();
+        let pods = promise::spawn::spawn_into_new_thread(move || kubectl.list_pods()).await?;
+        {
+            let mut list = self.pods.lock().unwrap();
+            list.pods = pods;
+            list.updated.replace(Instant::now());
+        }
+        *self.attached.lock().unwrap() = true;
+        Ok(())
+    }
+
+    fn detach(&self) -> anyhow::Result<()> {
+        bail!("detach not implemented");
+    }
+
+    fn state(&self) -> DomainState {
+        if *self.attached.lock().unwrap() {
+            DomainState::Attached
+        } else {
+            DomainState::Detached
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn domain(options: &[(&str, &str)]) -> KubernetesDomain {
+        KubernetesDomain::new(&CustomDomain {
+            name: "k8s".to_string(),
+            domain_type: "kubernetes".to_string(),
+            options: options
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>(),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    fn argv(cmd: &CommandBuilder) -> Vec<&str> {
+        cmd.get_argv().iter().map(|s| s.to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn commands() {
+        let dom = domain(&[("namespace", "prod"), ("pod", "web")]);
+        assert_eq!(
+            argv(&dom.build_command(None).unwrap()),
+            vec![
+                "kubectl",
+                "--namespace",
+                "prod",
+                "exec",
+                "--stdin",
+                "--tty",
+                "pod/web",
+                "--",
+                "sh"
+            ]
+        );
+
+        let cmd = CommandBuilder::from_argv(vec!["pod/db".into(), "psql".into()]);
+        assert_eq!(
+            argv(&dom.build_command(Some(cmd)).unwrap()),
+            vec![
+                "kubectl",
+                "--namespace",
+                "prod",
+                "exec",
+                "--stdin",
+                "--tty",
+                "pod/db",
+                "--",
+                "psql"
+            ]
+        );
+
+        let dom = domain(&[("context", "staging"), ("container", "app")]);
+        assert!(dom.build_command(None).is_err());
+        let cmd = CommandBuilder::from_argv(vec!["pod/api".into()]);
+        assert_eq!(
+            argv(&dom.build_command(Some(cmd)).unwrap()),
+            vec![
+                "kubectl",
+                "--context",
+                "staging",
+                "exec",
+                "--stdin",
+                "--tty",
+                "pod/api",
+                "--container",
+                "app",
+                "--",
+                "sh"
+            ]
+        );
+    }
+
+    #[test]
+    fn environment() {
+        let mut config = Config::default_config();
+        config.domain_environment.insert(
+            "k8s".to_string(),
+            config::DomainEnvironment {
+                unset_environment_variables: vec!["SSH_AUTH_SOCK".to_string()],
+                set_environment_variables: [("TZ", "UTC"), ("LANG", "C.UTF-8")]
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            },
+        );
+        assert_eq!(
+            pod_environment(&config, "k8s", None),
+            vec!["env", "-u", "SSH_AUTH_SOCK", "LANG=C.UTF-8", "TZ=UTC"]
+        );
+        assert!(pod_environment(&config, "other", None).is_empty());
+    }
+
+    #[test]
+    fn spawn_environment() {
+        let mut config = Config::default_config();
+        config.set_environment_variables = [("COLORTERM", "truecolor"), ("TZ", "GMT")]
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        config.domain_environment.insert(
+            "k8s".to_string(),
+            config::DomainEnvironment {
+                unset_environment_variables: vec![],
+                set_environment_variables: [("TZ", "UTC")]
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            },
+        );
+
+        // The domain environment takes precedence over the
+        // set_environment_variables
+        assert_eq!(
+            pod_environment(&config, "other", None),
+            vec!["env", "COLORTERM=truecolor", "TZ=GMT"]
+        );
+        assert_eq!(
+            pod_environment(&config, "k8s", None),
+            vec!["env", "COLORTERM=truecolor", "TZ=UTC"]
+        );
+
+        // and the environment of the spawn takes precedence over
+        // the set_environment_variables
+        let mut cmd = CommandBuilder::new("top");
+        cmd.env("COLORTERM", "24bit");
+        cmd.env("EDITOR", "vim");
+        cmd.env_remove("PAGER");
+        assert_eq!(
+            pod_environment(&config, "other", Some(&cmd)),
+            vec![
+                "env",
+                "-u",
+                "PAGER",
+                "COLORTERM=24bit",
+                "EDITOR=vim",
+                "TZ=GMT"
+            ]
+        );
+    }
+
+    #[test]
+    fn spawn_command_environment() {
+        let dom = domain(&[("pod", "web")]);
+        let mut cmd = CommandBuilder::new("top");
+        cmd.env("EDITOR", "vim");
+        assert_eq!(
+            argv(&dom.build_command(Some(cmd)).unwrap()),
+            vec![
+                "kubectl",
+                "exec",
+                "--stdin",
+                "--tty",
+                "pod/web",
+                "--",
+                "env",
+                "EDITOR=vim",
+                "top"
+            ]
+        );
+    }
+
+    #[test]
+    fn default_prog() {
+        let dom = KubernetesDomain::new(&CustomDomain {
+            name: "k8s".to_string(),
+            domain_type: "kubernetes".to_string(),
+            options: [("pod".to_string(), "web".to_string())]
+                .iter()
+                .cloned()
+                .collect(),
+            default_prog: Some(vec!["bash".to_string(), "-l".to_string()]),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            argv(&dom.build_command(None).unwrap()),
+            vec!["kubectl", "exec", "--stdin", "--tty", "pod/web", "--", "bash", "-l"]
+        );
+    }
+}
@@ -1,8 +1,8 @@
 use crate::pane::{Pane, PaneId};
-use crate::tab::{Tab, TabId};
+use crate::tab::{SplitDirection, Tab, TabId};
 use crate::window::{Window, WindowId};
 use anyhow::{anyhow, Error};
-use config::{configuration, ExitBehavior};
+use config::{configuration, ExitBehavior, HookEvent};
 use domain::{Domain, DomainId};
 use log::error;
 use portable_pty::ExitStatus;
@@ -11,7 +11,7 @@ use std::collections::HashMap;
 use std::io::Read;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
-use std::sync::mpsc::{sync_channel, Receiver, TryRecvError};
+use std::sync::mpsc::{channel, sync_channel, Receiver, Sender, TryRecvError};
 use std::sync::Arc;
 use std::thread;
 use thiserror::*;
@@ -19,8 +19,12 @@ use thiserror::*;
 pub mod activity;
 pub mod connui;
 pub mod domain;
+pub mod hooks;
+pub mod kubernetes;
 pub mod localpane;
 pub mod pane;
+pub mod pipe;
+pub mod recovered;
 pub mod renderable;
 pub mod ssh;
 pub mod tab;
@@ -29,15 +33,28 @@ pub mod tmux;
 pub mod window;
 
 use crate::activity::Activity;
+use crate::pipe::OutputPipe;
 
 #[derive(Clone, Debug)]
 pub enum MuxNotification {
     PaneOutput(PaneId),
+    /// The pane has been removed from the mux
+    PaneRemoved(PaneId),
     WindowCreated(WindowId),
+    /// A client asked for a tab to be activated in the window,
+    /// which should be raised by the gui
+    WindowActivated(WindowId),
     Alert {
         pane_id: PaneId,
         alert: wezterm_term::Alert,
     },
+    /// The mux server has been asked to shut down; any panes that
+    /// remain after `grace` has elapsed will be killed
+    ShuttingDown {
+        grace: std::time::Duration,
+    },
+    /// The configuration has been reloaded
+    ConfigReloaded,
 }
 
 static SUB_ID: AtomicUsize = AtomicUsize::new(0);
@@ -51,18 +68,26 @@ pub struct Mux {
     domains_by_name: RefCell<HashMap<String, Arc<dyn Domain>>>,
     subscribers: RefCell<HashMap<usize, Box<dyn Fn(MuxNotification) -> bool>>>,
     banner: RefCell<Option<String>>,
+    output_pipes: RefCell<HashMap<PaneId, OutputPipe>>,
 }
 
 /// This function bounces the data over to the main thread to feed to
 /// the pty in the mux.  It blocks until the mux has finished consuming
 /// the data.
-fn send_to_mux(pane_id: PaneId, dead: &Arc<AtomicBool>, data: Vec<u8>) {
+/// Parses the first `len` bytes of `data` on the mux thread.
+/// `data` is returned once it has been parsed so that the caller
+/// can reuse its allocation for subsequent reads.
+fn send_to_mux(pane_id: PaneId, dead: &Arc<AtomicBool>, data: Vec<u8>, len: usize) -> Vec<u8> {
     promise::spawn::block_on(promise::spawn::spawn_into_main_thread_with_low_priority({
         let dead = Arc::clone(&dead);
         async move {
             let mux = Mux::get().unwrap();
             if let Some(pane) = mux.get_pane(pane_id) {
-                pane.advance_bytes(&data);
+                let start = std::time::Instant::now();
+                pane.advance_bytes(&data[..len]);
+                mux.write_to_output_pipe(pane_id, &data[..len]);
+                metrics::histogram!("pane.parse", start.elapsed());
+                metrics::histogram!("pane.parse.size", len as f64);
                 mux.notify(MuxNotification::PaneOutput(pane_id));
             } else {
                 // Something else removed the pane from
@@ -70,8 +95,22 @@ fn send_to_mux(pane_id: PaneId, dead: &Arc<AtomicBool>, data: Vec<u8>) {
                 // process it.
                 dead.store(true, Ordering::Relaxed);
             }
+            data
         }
-    }));
+    }))
+}
+
+/// Moves the content of `data` to the end of `buf` and hands the
+/// allocation that is no longer needed back to the reader thread.
+fn append_data(buf: &mut Vec<u8>, mut data: Vec<u8>, recycle: &Sender<Vec<u8>>) {
+    if buf.is_empty() {
+        // Adopt the read buffer rather than copying from it
+        std::mem::swap(buf, &mut data);
+    } else {
+        buf.extend_from_slice(&data);
+    }
+    data.clear();
+    recycle.send(data).ok();
 }
 
 /// The accumulator tries to keep runs of text together, which is important
@@ -83,30 +122,41 @@ fn send_to_mux(pane_id: PaneId, dead: &Arc<AtomicBool>, data: Vec<u8>) {
 /// combined together.
 /// If this function takes too long to batch the data together then text
 /// input/output latency suffers and feels janky.
-fn accumulator(pane_id: PaneId, dead: &Arc<AtomicBool>, rx: Receiver<Vec<u8>>) {
+/// The buffers received from the reader are returned to it via `recycle`
+/// so that sustained output doesn't allocate for every read.
+fn accumulator(
+    pane_id: PaneId,
+    dead: &Arc<AtomicBool>,
+    rx: Receiver<Vec<u8>>,
+    recycle: Sender<Vec<u8>>,
+) {
     let mut buf = vec![];
 
-    'outer: while let Ok(mut data) = rx.recv() {
-        buf.append(&mut data);
+    'outer: while let Ok(data) = rx.recv() {
+        append_data(&mut buf, data, &recycle);
 
         while !buf.is_empty() {
             if let Some(idx) = buf.iter().rposition(|&b| b == b'\n') {
-                let mut split = buf.split_off(idx + 1);
-                std::mem::swap(&mut split, &mut buf);
-                send_to_mux(pane_id, &dead, split);
+                count_output(pane_id, idx + 1);
+                buf = send_to_mux(pane_id, &dead, buf, idx + 1);
+                // Retain any trailing partial line
+                buf.drain(..idx + 1);
             }
 
             match rx.try_recv() {
-                Ok(mut extra) => {
-                    buf.append(&mut extra);
+                Ok(extra) => {
+                    append_data(&mut buf, extra, &recycle);
                 }
                 Err(TryRecvError::Empty) => {
                     // No more data to read right now, so pass whatever
                     // we have pending on to the mux thread and then block
                     // waiting for the next.
-                    let mut to_send = vec![];
-                    std::mem::swap(&mut to_send, &mut buf);
-                    send_to_mux(pane_id, &dead, to_send);
+                    if !buf.is_empty() {
+                        let len = buf.len();
+                        count_output(pane_id, len);
+                        buf = send_to_mux(pane_id, &dead, buf, len);
+                        buf.clear();
+                    }
                 }
                 Err(TryRecvError::Disconnected) => break 'outer,
             }
@@ -120,7 +170,14 @@ fn accumulator(pane_id: PaneId, dead: &Arc<AtomicBool>, rx: Receiver<Vec<u8>>) {
         ExitBehavior::Hold | ExitBehavior::CloseOnCleanExit => {
             // We don't know if we can unilaterally close
             // this pane right now, so don't!
-            send_to_mux(pane_id, &dead, b"\n[Process completed]".to_vec());
+            if configuration().exit_status_banner {
+                // The pane will render the banner once it has
+                // collected the exit status of the process
+                return;
+            }
+            let message = b"\n[Process completed]".to_vec();
+            let len = message.len();
+            send_to_mux(pane_id, &dead, message, len);
             return;
         }
         ExitBehavior::Close => {
@@ -133,13 +190,18 @@ fn accumulator(pane_id: PaneId, dead: &Arc<AtomicBool>, rx: Receiver<Vec<u8>>) {
     }
 }
 
+/// Counts output per batch rather than per read, as the label
+/// is allocated each time that it is recorded
+fn count_output(pane_id: PaneId, len: usize) {
+    metrics::counter!("pane.output.bytes", len as u64, "pane_id" => pane_id.to_string());
+}
+
 /// This function is run in a separate thread; its purpose is to perform
 /// blocking reads from the pty (non-blocking reads are not portable to
 /// all platforms and pty/tty types) and relay the data to the `accumulator`
 /// function above that this function spawns a new thread.
 fn read_from_pane_pty(pane_id: PaneId, banner: Option<String>, mut reader: Box<dyn std::io::Read>) {
     const BUFSIZE: usize = 4 * 1024;
-    let mut buf = [0; BUFSIZE];
 
     // This is used to signal that an error occurred either in this thread,
     // in the accumulator, or in the main mux thread.  If `true`, both this
@@ -147,10 +209,11 @@ fn read_from_pane_pty(pane_id: PaneId, banner: Option<String>, mut reader: Box<d
     let dead = Arc::new(AtomicBool::new(false));
 
     let (tx, rx) = sync_channel(1);
+    let (recycle_tx, recycle_rx) = channel();
     std::thread::spawn({
         let dead = Arc::clone(&dead);
         move || {
-            accumulator(pane_id, &dead, rx);
+            accumulator(pane_id, &dead, rx, recycle_tx);
         }
     });
 
@@ -159,6 +222,13 @@ fn read_from_pane_pty(pane_id: PaneId, banner: Option<String>, mut reader: Box<d
     }
 
     while !dead.load(Ordering::Relaxed) {
+        // Read directly into a buffer that can be handed over to the
+        // accumulator, preferring one that it has finished with
+        let mut buf = recycle_rx
+            .try_recv()
+            .unwrap_or_else(|_| Vec::with_capacity(BUFSIZE));
+        buf.resize(BUFSIZE, 0);
+
         match reader.read(&mut buf) {
             Ok(size) if size == 0 => {
                 log::trace!("read_pty EOF: pane_id {}", pane_id);
@@ -169,8 +239,8 @@ fn read_from_pane_pty(pane_id: PaneId, banner: Option<String>, mut reader: Box<d
                 break;
             }
             Ok(size) => {
-                let buf = &buf[..size];
-                if tx.send(buf.to_vec()).is_err() {
+                buf.truncate(size);
+                if tx.send(buf).is_err() {
                     break;
                 }
             }
@@ -253,6 +323,7 @@ impl Mux {
             domains: RefCell::new(domains),
             subscribers: RefCell::new(HashMap::new()),
             banner: RefCell::new(None),
+            output_pipes: RefCell::new(HashMap::new()),
         }
     }
 
@@ -307,10 +378,30 @@ impl Mux {
         MUX.with(|m| {
             *m.borrow_mut() = Some(Rc::clone(mux));
         });
+
+        // Config reloads happen on a background thread; relay them
+        // to the subscribers of the mux on the main thread.
+        // The relay finds the current mux when it runs, so it only
+        // needs to be registered once, however often the mux is set.
+        static RELAY_CONFIG_RELOAD: std::sync::Once = std::sync::Once::new();
+        RELAY_CONFIG_RELOAD.call_once(|| {
+            config::subscribe_to_config_reload(|| {
+                promise::spawn::spawn_into_main_thread(async {
+                    if let Some(mux) = Mux::get() {
+                        mux.notify(MuxNotification::ConfigReloaded);
+                    }
+                })
+                .detach();
+                true
+            });
+        });
     }
 
+    /// Drops the mux, and with it the panes, then waits for their
+    /// processes to be terminated
     pub fn shutdown() {
         MUX.with(|m| drop(m.borrow_mut().take()));
+        localpane::wait_for_terminating_children();
     }
 
     pub fn get() -> Option<Rc<Mux>> {
@@ -327,6 +418,49 @@ impl Mux {
         self.panes.borrow().get(&pane_id).map(Rc::clone)
     }
 
+    /// Starts sending a copy of the output of a pane to the stdin of
+    /// `command`, replacing any existing pipe, or stops doing so when
+    /// `command` is None.  The command runs in the working directory
+    /// of the pane, if that is known.
+    pub fn set_pane_output_pipe(
+        &self,
+        pane_id: PaneId,
+        command: Option<&str>,
+    ) -> anyhow::Result<()> {
+        let command = match command {
+            Some(command) => command,
+            None => {
+                self.output_pipes.borrow_mut().remove(&pane_id);
+                return Ok(());
+            }
+        };
+        let pane = self
+            .get_pane(pane_id)
+            .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+        let cwd = pane
+            .get_current_working_dir()
+            .filter(|url| url.scheme() == "file")
+            .and_then(|url| url.to_file_path().ok())
+            .filter(|path| path.is_dir());
+        let pipe = OutputPipe::spawn(pane_id, command, cwd.as_deref())?;
+        self.output_pipes.borrow_mut().insert(pane_id, pipe);
+        Ok(())
+    }
+
+    pub fn has_pane_output_pipe(&self, pane_id: PaneId) -> bool {
+        self.output_pipes.borrow().contains_key(&pane_id)
+    }
+
+    fn write_to_output_pipe(&self, pane_id: PaneId, data: &[u8]) {
+        let mut pipes = self.output_pipes.borrow_mut();
+        if let Some(pipe) = pipes.get(&pane_id) {
+            if !pipe.write(data) {
+                log::warn!("the output pipe of pane {} has closed", pane_id);
+                pipes.remove(&pane_id);
+            }
+        }
+    }
+
     pub fn get_tab(&self, tab_id: TabId) -> Option<Rc<Tab>> {
         self.tabs.borrow().get(&tab_id).map(Rc::clone)
     }
@@ -356,9 +490,18 @@ impl Mux {
 
     fn remove_pane_internal(&self, pane_id: PaneId) {
         log::debug!("removing pane {}", pane_id);
+        self.output_pipes.borrow_mut().remove(&pane_id);
         if let Some(pane) = self.panes.borrow_mut().remove(&pane_id) {
             log::debug!("killing pane {}", pane_id);
             pane.kill();
+            // Panes may be removed while the subscribers are being
+            // notified of something else, so defer this notification
+            promise::spawn::spawn(async move {
+                if let Some(mux) = Mux::get() {
+                    mux.notify(MuxNotification::PaneRemoved(pane_id));
+                }
+            })
+            .detach();
         }
     }
 
@@ -524,6 +667,149 @@ impl Mux {
         Some((domain_id, window_id, tab_id))
     }
 
+    /// Detach the pane from its current tab and place it into a newly
+    /// created tab.  The new tab is added to window_id, or to a newly
+    /// created window if window_id is None.
+    /// Only panes that are hosted locally can be moved; panes that
+    /// belong to a remote multiplexer are managed by that multiplexer.
+    pub fn move_pane_to_new_tab(
+        &self,
+        pane_id: PaneId,
+        window_id: Option<WindowId>,
+    ) -> anyhow::Result<(Rc<Tab>, WindowId)> {
+        let (_domain_id, _src_window_id, src_tab_id) = self
+            .resolve_pane_id(pane_id)
+            .ok_or_else(|| anyhow!("pane {} not found", pane_id))?;
+
+        let pane = self
+            .get_pane(pane_id)
+            .ok_or_else(|| anyhow!("pane {} not found", pane_id))?;
+        if pane.downcast_ref::<localpane::LocalPane>().is_none() {
+            anyhow::bail!("pane {} is not a local pane and cannot be moved", pane_id);
+        }
+
+        let src_tab = self
+            .get_tab(src_tab_id)
+            .ok_or_else(|| anyhow!("tab {} not found", src_tab_id))?;
+
+        if let Some(window_id) = window_id {
+            if self.get_window(window_id).is_none() {
+                anyhow::bail!("window {} not found", window_id);
+            }
+        }
+
+        let size = src_tab.get_size();
+        let pane = src_tab
+            .remove_pane(pane_id)
+            .ok_or_else(|| anyhow!("pane {} not found in tab {}", pane_id, src_tab_id))?;
+
+        let tab = Rc::new(Tab::new(&size));
+        tab.assign_pane(&pane);
+        pane.resize(size)?;
+        self.add_tab_no_panes(&tab);
+
+        let window_id = match window_id {
+            Some(window_id) => {
+                self.add_tab_to_window(&tab, window_id)?;
+                window_id
+            }
+            None => {
+                // Keep the builder alive until the tab has been added so
+                // that the window has content when the gui learns of it
+                let builder = self.new_empty_window();
+                self.add_tab_to_window(&tab, *builder)?;
+                *builder
+            }
+        };
+
+        if src_tab.count_panes() == 0 {
+            self.remove_tab(src_tab_id);
+        }
+
+        Ok((tab, window_id))
+    }
+
+    /// Detach the pane from its current tab and split the pane
+    /// `target_pane_id` to make room for it, which allows moving
+    /// a pane into any existing tab, in this or another window.
+    /// If there is no room for the split, the pane is placed into
+    /// a new tab in the window that it came from and an error is
+    /// returned.
+    /// Only panes that are hosted locally can be moved.
+    pub fn move_pane_to_split(
+        &self,
+        pane_id: PaneId,
+        target_pane_id: PaneId,
+        direction: SplitDirection,
+    ) -> anyhow::Result<(Rc<Tab>, WindowId)> {
+        if pane_id == target_pane_id {
+            anyhow::bail!("pane {} cannot be moved next to itself", pane_id);
+        }
+        let (_domain_id, src_window_id, src_tab_id) = self
+            .resolve_pane_id(pane_id)
+            .ok_or_else(|| anyhow!("pane {} not found", pane_id))?;
+        let (_domain_id, window_id, tab_id) = self
+            .resolve_pane_id(target_pane_id)
+            .ok_or_else(|| anyhow!("pane {} not found", target_pane_id))?;
+
+        let pane = self
+            .get_pane(pane_id)
+            .ok_or_else(|| anyhow!("pane {} not found", pane_id))?;
+        if pane.downcast_ref::<localpane::LocalPane>().is_none() {
+            anyhow::bail!("pane {} is not a local pane and cannot be moved", pane_id);
+        }
+
+        let src_tab = self
+            .get_tab(src_tab_id)
+            .ok_or_else(|| anyhow!("tab {} not found", src_tab_id))?;
+        let tab = self
+            .get_tab(tab_id)
+            .ok_or_else(|| anyhow!("tab {} not found", tab_id))?;
+
+        let pane = src_tab
+            .remove_pane(pane_id)
+            .ok_or_else(|| anyhow!("pane {} not found in tab {}", pane_id, src_tab_id))?;
+
+        // The index of the target may have changed if it was in
+        // the same tab as the pane that was just removed
+        tab.set_zoomed(false);
+        let result = tab
+            .iter_panes()
+            .iter()
+            .find(|p| p.pane.pane_id() == target_pane_id)
+            .map(|p| p.index)
+            .ok_or_else(|| anyhow!("pane {} not found in tab {}", target_pane_id, tab_id))
+            .and_then(|index| tab.split_and_insert(index, direction, Rc::clone(&pane)));
+
+        if let Err(err) = result {
+            // Rather than losing the pane, give it a tab of its own
+            let size = src_tab.get_size();
+            let new_tab = Rc::new(Tab::new(&size));
+            new_tab.assign_pane(&pane);
+            pane.resize(size)?;
+            self.add_tab_no_panes(&new_tab);
+            self.add_tab_to_window(&new_tab, src_window_id)?;
+            if src_tab.count_panes() == 0 {
+                self.remove_tab(src_tab_id);
+            }
+            return Err(err);
+        }
+
+        if src_tab.count_panes() == 0 {
+            self.remove_tab(src_tab_id);
+        }
+
+        Ok((tab, window_id))
+    }
+
+    pub fn domain_was_attached(&self, domain: DomainId) {
+        let name = self.get_domain(domain).map(|d| d.domain_name().to_string());
+        hooks::run_event_hooks(
+            HookEvent::DomainAttached,
+            serde_json::json!({"domain_id": domain, "domain_name": name}),
+        );
+    }
+
     pub fn domain_was_detached(&self, domain: DomainId) {
         let mut dead_panes = vec![];
         for pane in self.panes.borrow().values() {
@@ -541,6 +827,12 @@ impl Mux {
             }
         }
 
+        let name = self.get_domain(domain).map(|d| d.domain_name().to_string());
+        hooks::run_event_hooks(
+            HookEvent::DomainDetached,
+            serde_json::json!({"domain_id": domain, "domain_name": name}),
+        );
+
         log::error!("domain detached panes: {:?}", dead_panes);
         for pane_id in dead_panes {
             self.remove_pane_internal(pane_id);
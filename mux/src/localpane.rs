@@ -1,17 +1,21 @@
 use crate::domain::DomainId;
-use crate::pane::{Pane, PaneId, Pattern, SearchResult};
+use crate::hooks::run_event_hooks;
+use crate::pane::{format_cwd, search_lines, Pane, PaneId, Pattern, SearchResult};
 use crate::renderable::*;
 use crate::tmux::{TmuxDomain, TmuxDomainState};
 use crate::{Domain, Mux, MuxNotification};
 use anyhow::Error;
 use async_trait::async_trait;
 use config::keyassignment::ScrollbackEraseMode;
-use config::{configuration, ExitBehavior};
-use portable_pty::{Child, MasterPty, PtySize};
+use config::{configuration, ChildTerminationBehavior, ExitBehavior, HookEvent};
+use portable_pty::{Child, ExitStatus, MasterPty, PtySize};
 use rangeset::RangeSet;
 use std::cell::{RefCell, RefMut};
+use std::collections::HashMap;
 use std::ops::Range;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
 use termwiz::escape::DeviceControlMode;
 use termwiz::surface::Line;
 use url::Url;
@@ -24,7 +28,7 @@ use wezterm_term::{
 #[derive(Debug)]
 enum ProcessState {
     Running {
-        child: Box<dyn Child>,
+        child: Box<dyn Child + Send>,
         // Whether we've explicitly killed the child
         killed: bool,
     },
@@ -41,6 +45,15 @@ pub struct LocalPane {
     pty: RefCell<Box<dyn MasterPty>>,
     domain_id: DomainId,
     tmux_domain: RefCell<Option<Arc<TmuxDomainState>>>,
+    spawned: Instant,
+    /// Set while the exit banner is queued to be rendered; the pane
+    /// is not reported as dead until it has been, so that the banner
+    /// is in place before the pane is removed
+    exit_banner_pending: Arc<AtomicBool>,
+    hyperlink_rules: RefCell<HyperlinkRuleCache>,
+    /// The name of the foreground process, and the id of the
+    /// process that it was determined for
+    foreground_process_name: RefCell<Option<(u32, Option<String>)>>,
 }
 
 #[async_trait(?Send)]
@@ -61,8 +74,26 @@ impl Pane for LocalPane {
         terminal_get_dirty_lines(&mut self.terminal.borrow_mut(), lines)
     }
 
+    fn copy_lines(&self, lines: Range<StableRowIndex>) -> Option<(StableRowIndex, Vec<Line>)> {
+        Some(terminal_copy_lines(&self.terminal.borrow(), lines))
+    }
+
     fn get_lines(&self, lines: Range<StableRowIndex>) -> (StableRowIndex, Vec<Line>) {
-        let (first, mut lines) = terminal_get_lines(&mut self.terminal.borrow_mut(), lines);
+        let config = configuration();
+        let mut cache = self.hyperlink_rules.borrow_mut();
+        let (rules, changed) = cache.rules(
+            &config,
+            self.domain_id,
+            self.foreground_process_id(),
+            || self.get_foreground_process_name(),
+        );
+        let (first, mut lines) = {
+            let mut term = self.terminal.borrow_mut();
+            if changed {
+                terminal_invalidate_implicit_hyperlinks(&mut term);
+            }
+            terminal_get_lines(&mut term, lines, rules)
+        };
 
         if self.tmux_domain.borrow().is_some() {
             let cursor = terminal_get_cursor_position(&mut self.terminal.borrow_mut());
@@ -92,15 +123,15 @@ impl Pane for LocalPane {
             self.pane_id,
             proc
         );
-        match &mut *proc {
-            ProcessState::Running { child, killed } => {
-                let _ = child.kill();
-                *killed = true;
+        match std::mem::replace(&mut *proc, ProcessState::Dead) {
+            ProcessState::Running { child, .. } => {
+                terminate_child(self.pane_id, child);
+                *proc = ProcessState::DeadPendingClose { killed: true };
             }
-            ProcessState::DeadPendingClose { killed } => {
-                *killed = true;
+            ProcessState::DeadPendingClose { .. } => {
+                *proc = ProcessState::DeadPendingClose { killed: true };
             }
-            _ => {}
+            ProcessState::Dead => {}
         }
     }
 
@@ -110,6 +141,13 @@ impl Pane for LocalPane {
         match &mut *proc {
             ProcessState::Running { child, killed } => {
                 if let Ok(Some(status)) = child.try_wait() {
+                    run_event_hooks(
+                        HookEvent::ChildExited,
+                        serde_json::json!({
+                            "pane_id": self.pane_id,
+                            "success": status.success(),
+                        }),
+                    );
                     match (configuration().exit_behavior, status.success(), killed) {
                         (ExitBehavior::Close, _, _) => *proc = ProcessState::Dead,
                         (ExitBehavior::CloseOnCleanExit, false, false) => {
@@ -122,10 +160,15 @@ impl Pane for LocalPane {
                         (ExitBehavior::Hold, _, true) => *proc = ProcessState::Dead,
                     }
                     log::debug!("child terminated, new state is {:?}", proc);
+                    if let ProcessState::DeadPendingClose { .. } = &*proc {
+                        if configuration().exit_status_banner {
+                            self.render_exit_banner(&status);
+                        }
+                    }
                 }
             }
             ProcessState::DeadPendingClose { killed } => {
-                if *killed {
+                if *killed && !self.exit_banner_pending.load(Ordering::SeqCst) {
                     *proc = ProcessState::Dead;
                     log::debug!("child state -> {:?}", proc);
                 }
@@ -192,7 +235,15 @@ impl Pane for LocalPane {
     }
 
     fn get_title(&self) -> String {
-        self.terminal.borrow_mut().get_title().to_string()
+        let term = self.terminal.borrow();
+        // If the application hasn't set a title, describe the
+        // process instead
+        if !term.has_title() && configuration().title_from_foreground_process {
+            if let Some(title) = self.divine_title() {
+                return title;
+            }
+        }
+        term.get_title().to_string()
     }
 
     fn palette(&self) -> ColorPalette {
@@ -211,6 +262,13 @@ impl Pane for LocalPane {
             ScrollbackEraseMode::ScrollbackAndViewport => {
                 self.terminal.borrow_mut().erase_scrollback_and_viewport();
             }
+            ScrollbackEraseMode::ViewportOnly => {
+                self.terminal.borrow_mut().erase_viewport();
+            }
+        }
+        // The mux server relies on this to push the change to its clients
+        if let Some(mux) = Mux::get() {
+            mux.notify(MuxNotification::PaneOutput(self.pane_id));
         }
     }
 
@@ -242,115 +300,69 @@ impl Pane for LocalPane {
             .or_else(|| self.divine_current_working_dir())
     }
 
-    fn get_semantic_zones(&self) -> anyhow::Result<Vec<SemanticZone>> {
-        let term = self.terminal.borrow();
-        term.get_semantic_zones()
-    }
-
-    async fn search(&self, mut pattern: Pattern) -> anyhow::Result<Vec<SearchResult>> {
-        let term = self.terminal.borrow();
-        let screen = term.screen();
-
-        if let Pattern::CaseInSensitiveString(s) = &mut pattern {
-            // normalize the case so we match everything lowercase
-            *s = s.to_lowercase()
-        }
-
-        let mut results = vec![];
-        let mut haystack = String::new();
-        let mut coords = vec![];
-
-        struct Coord {
-            byte_idx: usize,
-            grapheme_idx: usize,
-            stable_row: StableRowIndex,
-        }
-
-        fn haystack_idx_to_coord(idx: usize, coords: &[Coord]) -> (usize, StableRowIndex) {
-            let c = coords
-                .binary_search_by(|ele| ele.byte_idx.cmp(&idx))
-                .or_else(|i| -> Result<usize, usize> { Ok(i) })
-                .unwrap();
-            let coord = coords.get(c).or_else(|| coords.last()).unwrap();
-            (coord.grapheme_idx, coord.stable_row)
-        }
-
-        fn collect_matches(
-            results: &mut Vec<SearchResult>,
-            pattern: &Pattern,
-            haystack: &str,
-            coords: &[Coord],
-        ) {
-            if haystack.is_empty() {
-                return;
+    fn get_foreground_process_name(&self) -> Option<String> {
+        // Inspecting the process is relatively expensive, and this
+        // is used each time that the title is painted, so the name
+        // is only determined again when the foreground process changes
+        let id = self.foreground_process_id()?;
+        if let Some((cached_id, name)) = &*self.foreground_process_name.borrow() {
+            if *cached_id == id {
+                return name.clone();
             }
-            match pattern {
-                // Rust only provides a case sensitive match_indices function, so
-                // we have to pre-arrange to lowercase both the pattern and the
-                // haystack strings
-                Pattern::CaseInSensitiveString(s) | Pattern::CaseSensitiveString(s) => {
-                    for (idx, s) in haystack.match_indices(s) {
-                        let (start_x, start_y) = haystack_idx_to_coord(idx, coords);
-                        let (end_x, end_y) = haystack_idx_to_coord(idx + s.len(), coords);
-                        results.push(SearchResult {
-                            start_x,
-                            start_y,
-                            end_x,
-                            end_y,
-                        });
-                    }
-                }
-                Pattern::Regex(r) => {
-                    if let Ok(re) = regex::Regex::new(r) {
-                        for m in re.find_iter(haystack) {
-                            let (start_x, start_y) = haystack_idx_to_coord(m.start(), coords);
-                            let (end_x, end_y) = haystack_idx_to_coord(m.end(), coords);
-                            results.push(SearchResult {
-                                start_x,
-                                start_y,
-                                end_x,
-                                end_y,
-                            });
-                        }
-                    }
-                }
+        }
+        let name = self.divine_foreground_process_path().and_then(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        });
+        self.foreground_process_name
+            .borrow_mut()
+            .replace((id, name.clone()));
+        name
+    }
+
+    #[cfg(unix)]
+    fn is_password_input(&self) -> bool {
+        match self.pty.borrow().get_termios() {
+            Some(termios) => {
+                termios.c_lflag & libc::ICANON != 0 && termios.c_lflag & libc::ECHO == 0
             }
+            None => false,
         }
+    }
 
-        for (idx, line) in screen.lines.iter().enumerate() {
-            let stable_row = screen.phys_to_stable_row_index(idx);
+    fn copy_user_vars(&self) -> HashMap<String, String> {
+        self.terminal.borrow().user_vars().clone()
+    }
 
-            let mut wrapped = false;
-            for (grapheme_idx, cell) in line.visible_cells() {
-                coords.push(Coord {
-                    byte_idx: haystack.len(),
-                    grapheme_idx,
-                    stable_row,
-                });
+    fn memory_usage(&self) -> usize {
+        self.terminal.borrow().memory_usage()
+    }
 
-                let s = cell.str();
-                if let Pattern::CaseInSensitiveString(_) = &pattern {
-                    // normalize the case so we match everything lowercase
-                    haystack.push_str(&s.to_lowercase());
-                } else {
-                    haystack.push_str(cell.str());
-                }
-                wrapped = cell.attrs().wrapped();
-            }
+    fn set_scrollback_memory_share(&self, share: usize) {
+        self.terminal
+            .borrow_mut()
+            .set_scrollback_memory_share(share);
+    }
 
-            if !wrapped {
-                if let Pattern::Regex(_) = &pattern {
-                    haystack.push('\n');
-                } else {
-                    collect_matches(&mut results, &pattern, &haystack, &coords);
-                    haystack.clear();
-                    coords.clear();
-                }
-            }
-        }
+    fn set_user_var(&self, name: String, value: String) -> anyhow::Result<()> {
+        self.terminal.borrow_mut().set_user_var(name, value);
+        Ok(())
+    }
 
-        collect_matches(&mut results, &pattern, &haystack, &coords);
-        Ok(results)
+    fn get_semantic_zones(&self) -> anyhow::Result<Vec<SemanticZone>> {
+        let term = self.terminal.borrow();
+        term.get_semantic_zones()
+    }
+
+    async fn search(&self, pattern: Pattern) -> anyhow::Result<Vec<SearchResult>> {
+        let term = self.terminal.borrow();
+        let screen = term.screen();
+        let lines = screen
+            .lines
+            .iter()
+            .enumerate()
+            .map(|(idx, line)| (screen.phys_to_stable_row_index(idx), line));
+        Ok(search_lines(lines, pattern))
     }
 }
 
@@ -428,6 +440,17 @@ struct LocalPaneNotifHandler {
 
 impl AlertHandler for LocalPaneNotifHandler {
     fn alert(&mut self, alert: Alert) {
+        match &alert {
+            Alert::Bell => run_event_hooks(
+                HookEvent::Bell,
+                serde_json::json!({ "pane_id": self.pane_id }),
+            ),
+            Alert::TitleChanged(title) => run_event_hooks(
+                HookEvent::TitleChanged,
+                serde_json::json!({ "pane_id": self.pane_id, "title": title }),
+            ),
+            _ => {}
+        }
         if let Some(mux) = Mux::get() {
             mux.notify(MuxNotification::Alert {
                 pane_id: self.pane_id,
@@ -441,7 +464,7 @@ impl LocalPane {
     pub fn new(
         pane_id: PaneId,
         mut terminal: Terminal,
-        process: Box<dyn Child>,
+        process: Box<dyn Child + Send>,
         pty: Box<dyn MasterPty>,
         domain_id: DomainId,
     ) -> Self {
@@ -460,9 +483,42 @@ impl LocalPane {
             pty: RefCell::new(pty),
             domain_id,
             tmux_domain: RefCell::new(None),
+            spawned: Instant::now(),
+            exit_banner_pending: Arc::new(AtomicBool::new(false)),
+            hyperlink_rules: RefCell::new(HyperlinkRuleCache::default()),
+            foreground_process_name: RefCell::new(None),
         }
     }
 
+    /// Renders the exit status and the runtime of the process into
+    /// the terminal of a pane that is being held open after it exited.
+    /// This is routed via the main thread in the same way as the output
+    /// from the pty, so that it follows any output that is already queued.
+    fn render_exit_banner(&self, status: &ExitStatus) {
+        let outcome = match status.signal() {
+            Some(signal) => format!("terminated by signal {}", signal),
+            None if status.success() => "completed".to_string(),
+            None => format!("exited with code {}", status.exit_code()),
+        };
+        let banner = format!(
+            "\r\n\x1b[2m[Process {} after {}]\x1b[0m",
+            outcome,
+            format_elapsed(self.spawned.elapsed())
+        );
+        let pane_id = self.pane_id;
+        let pending = Arc::clone(&self.exit_banner_pending);
+        pending.store(true, Ordering::SeqCst);
+        promise::spawn::spawn_into_main_thread(async move {
+            let mux = Mux::get().unwrap();
+            if let Some(pane) = mux.get_pane(pane_id) {
+                pane.advance_bytes(banner.as_bytes());
+                mux.notify(MuxNotification::PaneOutput(pane_id));
+            }
+            pending.store(false, Ordering::SeqCst);
+        })
+        .detach();
+    }
+
     #[cfg(target_os = "macos")]
     fn divine_current_working_dir_macos(&self) -> Option<Url> {
         if let Some(pid) = self.pty.borrow().process_group_leader() {
@@ -535,6 +591,30 @@ impl LocalPane {
         None
     }
 
+    /// Identifies the foreground process, so that what is derived
+    /// from it need only be determined again when it changes
+    fn foreground_process_id(&self) -> Option<u32> {
+        #[cfg(unix)]
+        {
+            return self
+                .pty
+                .borrow()
+                .process_group_leader()
+                .map(|pid| pid as u32);
+        }
+
+        #[cfg(windows)]
+        {
+            return match &*self.process.borrow() {
+                ProcessState::Running { child, .. } => child.process_id(),
+                _ => None,
+            };
+        }
+
+        #[allow(unreachable_code)]
+        None
+    }
+
     #[cfg(target_os = "linux")]
     fn divine_current_working_dir_linux(&self) -> Option<Url> {
         if let Some(pid) = self.pty.borrow().process_group_leader() {
@@ -545,6 +625,89 @@ impl LocalPane {
         None
     }
 
+    /// Produces a title of the form `name: cwd` from the
+    /// foreground process, for use when the application hasn't
+    /// set a title
+    fn divine_title(&self) -> Option<String> {
+        let name = self.get_foreground_process_name()?;
+        match self.get_current_working_dir() {
+            Some(cwd) => Some(format!("{}: {}", name, format_cwd(&cwd))),
+            None => Some(name),
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn divine_foreground_process_path_linux(&self) -> Option<std::path::PathBuf> {
+        let pid = self.pty.borrow().process_group_leader()?;
+        std::fs::read_link(format!("/proc/{}/exe", pid)).ok()
+    }
+
+    #[cfg(target_os = "macos")]
+    fn divine_foreground_process_path_macos(&self) -> Option<std::path::PathBuf> {
+        let pid = self.pty.borrow().process_group_leader()?;
+        extern "C" {
+            fn proc_pidpath(pid: libc::pid_t, buffer: *mut u8, buffersize: u32) -> libc::c_int;
+        }
+        const PROC_PIDPATHINFO_MAXSIZE: usize = 4096;
+        let mut buffer = vec![0u8; PROC_PIDPATHINFO_MAXSIZE];
+        let len = unsafe { proc_pidpath(pid, buffer.as_mut_ptr(), buffer.len() as u32) };
+        if len <= 0 {
+            return None;
+        }
+        buffer.truncate(len as usize);
+        use std::os::unix::ffi::OsStringExt;
+        Some(std::ffi::OsString::from_vec(buffer).into())
+    }
+
+    /// There is no notion of a foreground process for a console on
+    /// Windows, so this reports the process that was spawned into it
+    #[cfg(windows)]
+    fn divine_foreground_process_path_windows(&self) -> Option<std::path::PathBuf> {
+        use std::os::windows::ffi::OsStringExt;
+        use winapi::um::handleapi::CloseHandle;
+        use winapi::um::processthreadsapi::OpenProcess;
+        use winapi::um::winbase::QueryFullProcessImageNameW;
+        use winapi::um::winnt::PROCESS_QUERY_LIMITED_INFORMATION;
+
+        let pid = match &*self.process.borrow() {
+            ProcessState::Running { child, .. } => child.process_id()?,
+            _ => return None,
+        };
+        let handle = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+        if handle.is_null() {
+            return None;
+        }
+        let mut buffer = vec![0u16; 4096];
+        let mut len = buffer.len() as u32;
+        let ok = unsafe { QueryFullProcessImageNameW(handle, 0, buffer.as_mut_ptr(), &mut len) };
+        unsafe { CloseHandle(handle) };
+        if ok == 0 {
+            return None;
+        }
+        buffer.truncate(len as usize);
+        Some(std::ffi::OsString::from_wide(&buffer).into())
+    }
+
+    fn divine_foreground_process_path(&self) -> Option<std::path::PathBuf> {
+        #[cfg(target_os = "linux")]
+        {
+            return self.divine_foreground_process_path_linux();
+        }
+
+        #[cfg(target_os = "macos")]
+        {
+            return self.divine_foreground_process_path_macos();
+        }
+
+        #[cfg(windows)]
+        {
+            return self.divine_foreground_process_path_windows();
+        }
+
+        #[allow(unreachable_code)]
+        None
+    }
+
     fn divine_current_working_dir(&self) -> Option<Url> {
         #[cfg(target_os = "linux")]
         {
@@ -564,9 +727,76 @@ impl LocalPane {
 impl Drop for LocalPane {
     fn drop(&mut self) {
         // Avoid lingering zombies
-        if let ProcessState::Running { child, .. } = &mut *self.process.borrow_mut() {
-            let _ = child.kill();
-            let _ = child.wait();
+        if let ProcessState::Running { child, .. } =
+            std::mem::replace(&mut *self.process.borrow_mut(), ProcessState::Dead)
+        {
+            terminate_child(self.pane_id, child);
         }
     }
 }
+
+lazy_static::lazy_static! {
+    /// The number of processes that terminate_child is still in
+    /// the middle of terminating
+    static ref TERMINATING: (Mutex<usize>, Condvar) = (Mutex::new(0), Condvar::new());
+}
+
+/// Blocks until the processes of closed panes have been terminated.
+/// The threads that do that would otherwise be torn down when the
+/// process exits, before they got around to killing them.
+pub fn wait_for_terminating_children() {
+    let (count, cvar) = &*TERMINATING;
+    let mut count = count.lock().unwrap();
+    while *count > 0 {
+        count = cvar.wait(count).unwrap();
+    }
+}
+
+/// Disposes of the process of a pane that is being closed, according
+/// to the configured child_termination_behavior, and then reaps it.
+/// This happens on a separate thread so that a long grace period
+/// doesn't block the caller; see wait_for_terminating_children.
+fn terminate_child(pane_id: PaneId, mut child: Box<dyn Child + Send>) {
+    let config = configuration();
+    let behavior = config.child_termination_behavior;
+    let grace_period = Duration::from_millis(config.child_termination_grace_period_ms);
+
+    *TERMINATING.0.lock().unwrap() += 1;
+    std::thread::spawn(move || {
+        let result = match behavior {
+            ChildTerminationBehavior::HangupThenKill => child.kill_with_grace_period(grace_period),
+            ChildTerminationBehavior::Kill => child.kill_with_grace_period(Duration::from_secs(0)),
+            ChildTerminationBehavior::LeaveRunning => Ok(()),
+        };
+        if let Err(err) = result {
+            log::debug!("failed to terminate process in pane {}: {}", pane_id, err);
+        }
+        {
+            let (count, cvar) = &*TERMINATING;
+            *count.lock().unwrap() -= 1;
+            cvar.notify_all();
+        }
+
+        if let Ok(status) = child.wait() {
+            run_event_hooks(
+                HookEvent::ChildExited,
+                serde_json::json!({
+                    "pane_id": pane_id,
+                    "success": status.success(),
+                }),
+            );
+        }
+    });
+}
+
+/// Formats a duration as eg: `1h2m`, `3m4s` or `5.2s`
+fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    if secs >= 3600 {
+        format!("{}h{}m", secs / 3600, (secs % 3600) / 60)
+    } else if secs >= 60 {
+        format!("{}m{}s", secs / 60, secs % 60)
+    } else {
+        format!("{:.1}s", elapsed.as_secs_f64())
+    }
+}
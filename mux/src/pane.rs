@@ -8,6 +8,7 @@ use portable_pty::PtySize;
 use rangeset::RangeSet;
 use serde::{Deserialize, Serialize};
 use std::cell::RefMut;
+use std::collections::HashMap;
 use std::ops::Range;
 use std::sync::{Arc, Mutex};
 use termwiz::surface::Line;
@@ -15,6 +16,14 @@ use url::Url;
 use wezterm_term::color::ColorPalette;
 use wezterm_term::{Clipboard, KeyCode, KeyModifiers, MouseEvent, SemanticZone, StableRowIndex};
 
+/// The name of the user var whose value is shown in the status
+/// area of the tab bar while the pane is active
+pub const STATUS_USER_VAR: &str = "WEZTERM_STATUS";
+
+/// The name of the user var whose value is shown as a badge
+/// ahead of the title of the tab while the pane is active in that tab
+pub const BADGE_USER_VAR: &str = "WEZTERM_BADGE";
+
 static PANE_ID: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
 pub type PaneId = usize;
 
@@ -22,6 +31,17 @@ pub fn alloc_pane_id() -> PaneId {
     PANE_ID.fetch_add(1, ::std::sync::atomic::Ordering::Relaxed)
 }
 
+/// Returns the working directory `url` as a path, abbreviating
+/// the home directory to `~`
+pub fn format_cwd(url: &Url) -> String {
+    let path = url.to_file_path().unwrap_or_else(|_| url.path().into());
+    match path.strip_prefix(&*config::HOME_DIR) {
+        Ok(rel) if rel.as_os_str().is_empty() => "~".to_string(),
+        Ok(rel) => format!("~/{}", rel.display()),
+        Err(_) => path.display().to_string(),
+    }
+}
+
 #[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct SearchResult {
     pub start_y: StableRowIndex,
@@ -34,6 +54,112 @@ pub struct SearchResult {
 
 pub use config::keyassignment::Pattern;
 
+/// Searches `lines`, which are pairs of the stable row index and
+/// the content of each line, for matches of `pattern`
+pub(crate) fn search_lines<'a>(
+    lines: impl Iterator<Item = (StableRowIndex, &'a Line)>,
+    mut pattern: Pattern,
+) -> Vec<SearchResult> {
+    if let Pattern::CaseInSensitiveString(s) = &mut pattern {
+        // normalize the case so we match everything lowercase
+        *s = s.to_lowercase()
+    }
+
+    let mut results = vec![];
+    let mut haystack = String::new();
+    let mut coords = vec![];
+
+    struct Coord {
+        byte_idx: usize,
+        grapheme_idx: usize,
+        stable_row: StableRowIndex,
+    }
+
+    fn haystack_idx_to_coord(idx: usize, coords: &[Coord]) -> (usize, StableRowIndex) {
+        let c = coords
+            .binary_search_by(|ele| ele.byte_idx.cmp(&idx))
+            .or_else(|i| -> Result<usize, usize> { Ok(i) })
+            .unwrap();
+        let coord = coords.get(c).or_else(|| coords.last()).unwrap();
+        (coord.grapheme_idx, coord.stable_row)
+    }
+
+    fn collect_matches(
+        results: &mut Vec<SearchResult>,
+        pattern: &Pattern,
+        haystack: &str,
+        coords: &[Coord],
+    ) {
+        if haystack.is_empty() {
+            return;
+        }
+        match pattern {
+            // Rust only provides a case sensitive match_indices function, so
+            // we have to pre-arrange to lowercase both the pattern and the
+            // haystack strings
+            Pattern::CaseInSensitiveString(s) | Pattern::CaseSensitiveString(s) => {
+                for (idx, s) in haystack.match_indices(s) {
+                    let (start_x, start_y) = haystack_idx_to_coord(idx, coords);
+                    let (end_x, end_y) = haystack_idx_to_coord(idx + s.len(), coords);
+                    results.push(SearchResult {
+                        start_x,
+                        start_y,
+                        end_x,
+                        end_y,
+                    });
+                }
+            }
+            Pattern::Regex(r) => {
+                if let Ok(re) = regex::Regex::new(r) {
+                    for m in re.find_iter(haystack) {
+                        let (start_x, start_y) = haystack_idx_to_coord(m.start(), coords);
+                        let (end_x, end_y) = haystack_idx_to_coord(m.end(), coords);
+                        results.push(SearchResult {
+                            start_x,
+                            start_y,
+                            end_x,
+                            end_y,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    for (stable_row, line) in lines {
+        let mut wrapped = false;
+        for (grapheme_idx, cell) in line.visible_cells() {
+            coords.push(Coord {
+                byte_idx: haystack.len(),
+                grapheme_idx,
+                stable_row,
+            });
+
+            let s = cell.str();
+            if let Pattern::CaseInSensitiveString(_) = &pattern {
+                // normalize the case so we match everything lowercase
+                haystack.push_str(&s.to_lowercase());
+            } else {
+                haystack.push_str(cell.str());
+            }
+            wrapped = cell.attrs().wrapped();
+        }
+
+        if !wrapped {
+            if let Pattern::Regex(_) = &pattern {
+                haystack.push('\n');
+            } else {
+                collect_matches(&mut results, &pattern, &haystack, &coords);
+                haystack.clear();
+                coords.clear();
+            }
+        }
+    }
+
+    collect_matches(&mut results, &pattern, &haystack, &coords);
+    results
+}
+
 const PASTE_CHUNK_SIZE: usize = 1024;
 
 struct Paste {
@@ -99,6 +225,15 @@ pub trait Pane: Downcast {
     /// have its dirty bit set appropriately.
     fn get_lines(&self, lines: Range<StableRowIndex>) -> (StableRowIndex, Vec<Line>);
 
+    /// Returns a copy of a set of lines, as for get_lines, but without
+    /// clearing their dirty flags, so that the copy doesn't interfere
+    /// with rendering.  The range is not adjusted; only those lines
+    /// that are present in the range are returned.
+    /// Returns None if the pane doesn't hold its content locally.
+    fn copy_lines(&self, _lines: Range<StableRowIndex>) -> Option<(StableRowIndex, Vec<Line>)> {
+        None
+    }
+
     /// Returns render related dimensions
     fn get_dimensions(&self) -> RenderableDimensions;
 
@@ -151,6 +286,58 @@ pub trait Pane: Downcast {
 
     fn get_current_working_dir(&self) -> Option<Url>;
 
+    /// Returns the name of the executable of the foreground process
+    /// in this pane, as determined by inspecting the local process
+    fn get_foreground_process_name(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns true if the program in this pane appears to be
+    /// reading a password; that is, the tty is in canonical mode
+    /// but with echo disabled
+    fn is_password_input(&self) -> bool {
+        false
+    }
+
+    /// Returns a copy of the user defined variables that were
+    /// set by the application running in this pane
+    fn copy_user_vars(&self) -> HashMap<String, String> {
+        HashMap::new()
+    }
+
+    /// Returns an estimate of the number of bytes of memory used
+    /// to hold the content and scrollback of this pane
+    fn memory_usage(&self) -> usize {
+        0
+    }
+
+    /// Informs the pane that its tab has `share` panes, between
+    /// which the scrollback_memory_limit is divided
+    fn set_scrollback_memory_share(&self, _share: usize) {}
+
+    /// Assigns a user defined variable, as though the application
+    /// running in the pane had set it via an escape sequence
+    fn set_user_var(&self, _name: String, _value: String) -> anyhow::Result<()> {
+        anyhow::bail!("this pane does not support user vars");
+    }
+
+    /// Starts sending a copy of the output of the pane to the stdin
+    /// of `command`, replacing any existing pipe, or stops doing so
+    /// when `command` is None
+    fn set_output_pipe(&self, command: Option<String>) -> anyhow::Result<()> {
+        Mux::get()
+            .ok_or_else(|| anyhow::anyhow!("no mux"))?
+            .set_pane_output_pipe(self.pane_id(), command.as_deref())
+    }
+
+    /// Returns true if the output of the pane is being sent to
+    /// a command via `set_output_pipe`
+    fn has_output_pipe(&self) -> bool {
+        Mux::get()
+            .map(|mux| mux.has_pane_output_pipe(self.pane_id()))
+            .unwrap_or(false)
+    }
+
     fn trickle_paste(&self, text: String) -> anyhow::Result<()> {
         if text.len() <= PASTE_CHUNK_SIZE {
             // Send it all now
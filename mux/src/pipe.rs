@@ -0,0 +1,186 @@
+//! Sends a copy of the output of a pane to the stdin of another
+//! process, as requested by the `TogglePaneOutputPipe` key assignment
+//! and `wezterm cli pipe-pane`.
+use crate::pane::PaneId;
+use anyhow::{anyhow, Context};
+use std::cell::Cell;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{sync_channel, SyncSender, TrySendError};
+
+/// How many chunks of output may be queued for a process that isn't
+/// keeping up, after which further output is dropped until it does
+const PIPE_CAPACITY: usize = 256;
+
+/// A process that receives the output of a pane.  Its stdin is
+/// closed when this is dropped, so that it can finish up and exit.
+pub struct OutputPipe {
+    pane_id: PaneId,
+    tx: SyncSender<Vec<u8>>,
+    /// The number of bytes that have been dropped since the
+    /// process last kept up with the output
+    dropped: Cell<usize>,
+}
+
+impl OutputPipe {
+    /// Runs `command` via the shell, with `WEZTERM_PANE` set to
+    /// `pane_id` in its environment
+    pub fn spawn(pane_id: PaneId, command: &str, cwd: Option<&Path>) -> anyhow::Result<Self> {
+        Self::spawn_with_capacity(pane_id, command, cwd, PIPE_CAPACITY)
+    }
+
+    fn spawn_with_capacity(
+        pane_id: PaneId,
+        command: &str,
+        cwd: Option<&Path>,
+        capacity: usize,
+    ) -> anyhow::Result<Self> {
+        let mut cmd = if cfg!(windows) {
+            let mut cmd = Command::new("cmd.exe");
+            cmd.arg("/c");
+            cmd
+        } else {
+            let mut cmd = Command::new("/bin/sh");
+            cmd.arg("-c");
+            cmd
+        };
+        cmd.arg(command)
+            .env("WEZTERM_PANE", pane_id.to_string())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null());
+        if let Some(cwd) = cwd {
+            cmd.current_dir(cwd);
+        }
+
+        let mut child = cmd
+            .spawn()
+            .with_context(|| format!("spawning `{}` to pipe pane {}", command, pane_id))?;
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| anyhow!("no stdin for `{}`", command))?;
+
+        // The data is written from a separate thread so that a slow
+        // reader doesn't stall the processing of the pane output
+        let (tx, rx) = sync_channel::<Vec<u8>>(capacity);
+        let command = command.to_string();
+        std::thread::spawn(move || {
+            for data in rx {
+                if let Err(err) = stdin.write_all(&data) {
+                    log::error!(
+                        "writing output of pane {} to `{}`: {}",
+                        pane_id,
+                        command,
+                        err
+                    );
+                    break;
+                }
+            }
+            drop(stdin);
+            match child.wait() {
+                Ok(status) if !status.success() => {
+                    log::warn!(
+                        "`{}` piped from pane {} exited with {}",
+                        command,
+                        pane_id,
+                        status
+                    );
+                }
+                Ok(_) => {}
+                Err(err) => log::error!("waiting for `{}`: {}", command, err),
+            }
+        });
+
+        Ok(Self {
+            pane_id,
+            tx,
+            dropped: Cell::new(0),
+        })
+    }
+
+    /// Queues `data` to be written to the process.  If the process
+    /// isn't keeping up, then `data` is dropped with a warning.
+    /// Returns false once the process is no longer reading it.
+    pub fn write(&self, data: &[u8]) -> bool {
+        match self.tx.try_send(data.to_vec()) {
+            Ok(()) => {
+                let dropped = self.dropped.replace(0);
+                if dropped > 0 {
+                    log::warn!(
+                        "dropped {} bytes of the output of pane {} \
+                         because its output pipe wasn't keeping up",
+                        dropped,
+                        self.pane_id
+                    );
+                }
+                true
+            }
+            Err(TrySendError::Full(data)) => {
+                if self.dropped.get() == 0 {
+                    log::warn!(
+                        "the output pipe of pane {} isn't keeping up; dropping output",
+                        self.pane_id
+                    );
+                }
+                self.dropped.set(self.dropped.get() + data.len());
+                true
+            }
+            Err(TrySendError::Disconnected(_)) => false,
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn wait_for(mut condition: impl FnMut() -> bool) -> bool {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while Instant::now() < deadline {
+            if condition() {
+                return true;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        false
+    }
+
+    #[test]
+    fn delivers_output() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir().join(format!("wezterm-pipe-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        let out = dir.join("out");
+
+        let pipe = OutputPipe::spawn(1, "cat > out", Some(&dir))?;
+        assert!(pipe.write(b"hello "));
+        assert!(pipe.write(b"world"));
+        drop(pipe);
+
+        let delivered = wait_for(|| std::fs::read(&out).ok().as_deref() == Some(b"hello world"));
+        std::fs::remove_dir_all(&dir)?;
+        assert!(delivered);
+        Ok(())
+    }
+
+    #[test]
+    fn drops_output_when_full() -> anyhow::Result<()> {
+        // sleep doesn't read its stdin, so once the OS pipe buffer
+        // is full, the queue fills up too
+        let pipe = OutputPipe::spawn_with_capacity(1, "sleep 10", None, 2)?;
+        let chunk = vec![b'a'; 1024 * 1024];
+        for _ in 0..4 {
+            assert!(pipe.write(&chunk));
+        }
+        assert!(pipe.dropped.get() > 0);
+        Ok(())
+    }
+
+    #[test]
+    fn reports_exited_process() -> anyhow::Result<()> {
+        let pipe = OutputPipe::spawn(1, "exit 0", None)?;
+        assert!(wait_for(|| !pipe.write(b"data")));
+        Ok(())
+    }
+}
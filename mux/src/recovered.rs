@@ -0,0 +1,192 @@
+//! A read-only pane that shows content that was recovered from a
+//! pane in an earlier wezterm process, such as its persisted scrollback.
+
+use crate::domain::DomainId;
+use crate::pane::{alloc_pane_id, search_lines, Pane, PaneId, Pattern, SearchResult};
+use crate::renderable::*;
+use async_trait::async_trait;
+use filedescriptor::{FileDescriptor, Pipe};
+use portable_pty::PtySize;
+use rangeset::RangeSet;
+use std::cell::{RefCell, RefMut};
+use std::ops::Range;
+use termwiz::surface::{CursorShape, CursorVisibility, Line};
+use url::Url;
+use wezterm_term::color::ColorPalette;
+use wezterm_term::{KeyCode, KeyModifiers, MouseEvent, StableRowIndex};
+
+pub struct RecoveredPane {
+    pane_id: PaneId,
+    domain_id: DomainId,
+    title: String,
+    cwd: Option<Url>,
+    lines: Vec<Line>,
+    size: RefCell<PtySize>,
+    /// The reader returned by `reader` reports EOF once this
+    /// is dropped, which happens when the pane is killed
+    keep_alive: RefCell<Option<FileDescriptor>>,
+    reader: FileDescriptor,
+    writer: RefCell<std::io::Sink>,
+}
+
+impl RecoveredPane {
+    /// Creates a pane holding `lines`.  `domain_id` is the domain used
+    /// when spawning new panes alongside this one.
+    pub fn new(
+        domain_id: DomainId,
+        title: String,
+        cwd: Option<Url>,
+        lines: Vec<Line>,
+        size: PtySize,
+    ) -> anyhow::Result<Self> {
+        let pipe = Pipe::new()?;
+        Ok(Self {
+            pane_id: alloc_pane_id(),
+            domain_id,
+            title,
+            cwd,
+            lines,
+            size: RefCell::new(size),
+            keep_alive: RefCell::new(Some(pipe.write)),
+            reader: pipe.read,
+            writer: RefCell::new(std::io::sink()),
+        })
+    }
+
+    /// The number of rows, which is at least enough to fill the viewport
+    fn total_rows(&self) -> usize {
+        self.lines.len().max(self.size.borrow().rows as usize)
+    }
+}
+
+#[async_trait(?Send)]
+impl Pane for RecoveredPane {
+    fn pane_id(&self) -> PaneId {
+        self.pane_id
+    }
+
+    fn get_cursor_position(&self) -> StableCursorPosition {
+        StableCursorPosition {
+            x: 0,
+            y: self.total_rows().saturating_sub(1) as StableRowIndex,
+            shape: CursorShape::Default,
+            visibility: CursorVisibility::Hidden,
+        }
+    }
+
+    fn get_dirty_lines(&self, _lines: Range<StableRowIndex>) -> RangeSet<StableRowIndex> {
+        // The content never changes
+        RangeSet::new()
+    }
+
+    fn get_lines(&self, lines: Range<StableRowIndex>) -> (StableRowIndex, Vec<Line>) {
+        let total = self.total_rows() as StableRowIndex;
+        let len = lines.end.saturating_sub(lines.start).min(total);
+        let first = lines.start.max(0).min(total - len);
+        let cols = self.size.borrow().cols as usize;
+        let result = (first..first + len)
+            .map(|idx| {
+                self.lines
+                    .get(idx as usize)
+                    .cloned()
+                    .unwrap_or_else(|| Line::with_width(cols))
+            })
+            .collect();
+        (first, result)
+    }
+
+    fn copy_lines(&self, lines: Range<StableRowIndex>) -> Option<(StableRowIndex, Vec<Line>)> {
+        Some(self.get_lines(lines))
+    }
+
+    fn get_dimensions(&self) -> RenderableDimensions {
+        let size = self.size.borrow();
+        let total = self.total_rows();
+        RenderableDimensions {
+            cols: size.cols as usize,
+            viewport_rows: size.rows as usize,
+            scrollback_rows: total,
+            physical_top: total.saturating_sub(size.rows as usize) as StableRowIndex,
+            scrollback_top: 0,
+        }
+    }
+
+    fn get_title(&self) -> String {
+        self.title.clone()
+    }
+
+    fn can_close_without_prompting(&self) -> bool {
+        true
+    }
+
+    fn send_paste(&self, _text: &str) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn reader(&self) -> anyhow::Result<Box<dyn std::io::Read + Send>> {
+        Ok(Box::new(self.reader.try_clone()?))
+    }
+
+    fn writer(&self) -> RefMut<dyn std::io::Write> {
+        self.writer.borrow_mut()
+    }
+
+    fn resize(&self, size: PtySize) -> anyhow::Result<()> {
+        *self.size.borrow_mut() = size;
+        Ok(())
+    }
+
+    fn key_down(&self, _key: KeyCode, _mods: KeyModifiers) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn mouse_event(&self, _event: MouseEvent) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn advance_bytes(&self, _buf: &[u8]) {}
+
+    fn is_dead(&self) -> bool {
+        self.keep_alive.borrow().is_none()
+    }
+
+    fn kill(&self) {
+        self.keep_alive.borrow_mut().take();
+    }
+
+    fn palette(&self) -> ColorPalette {
+        config::configuration().resolved_palette.clone().into()
+    }
+
+    fn domain_id(&self) -> DomainId {
+        self.domain_id
+    }
+
+    async fn search(&self, pattern: Pattern) -> anyhow::Result<Vec<SearchResult>> {
+        let lines = self
+            .lines
+            .iter()
+            .enumerate()
+            .map(|(idx, line)| (idx as StableRowIndex, line));
+        Ok(search_lines(lines, pattern))
+    }
+
+    fn is_mouse_grabbed(&self) -> bool {
+        false
+    }
+
+    fn is_alt_screen_active(&self) -> bool {
+        false
+    }
+
+    fn get_current_working_dir(&self) -> Option<Url> {
+        self.cwd.clone()
+    }
+
+    fn memory_usage(&self) -> usize {
+        self.lines
+            .iter()
+            .map(|line| line.cells().len() * std::mem::size_of::<termwiz::cell::Cell>())
+            .sum()
+    }
+}
@@ -1,8 +1,12 @@
-use config::configuration;
+use crate::domain::DomainId;
+use crate::Mux;
+use config::ConfigHandle;
 use luahelper::impl_lua_conversion;
 use rangeset::RangeSet;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::ops::Range;
+use termwiz::hyperlink::Rule;
 use wezterm_term::{Line, StableRowIndex, Terminal};
 
 /// Describes the location of the cursor
@@ -70,14 +74,97 @@ pub fn terminal_get_dirty_lines(
     set
 }
 
-/// Implements Pane::get_lines for Terminal
+/// Returns the subset of `rules` that apply to text in a pane that
+/// belongs to `domain_id`.  `program` is called to determine the name
+/// of the foreground process of the pane, but only if a rule depends
+/// on it, as that can be relatively expensive.
+pub fn hyperlink_rules_for_pane<'a>(
+    rules: &'a [Rule],
+    domain_id: DomainId,
+    program: impl FnOnce() -> Option<String>,
+) -> Cow<'a, [Rule]> {
+    if rules.iter().all(Rule::is_unconditional) {
+        return Cow::Borrowed(rules);
+    }
+
+    let domain_name = Mux::get()
+        .and_then(|mux| mux.get_domain(domain_id))
+        .map(|domain| domain.domain_name().to_string());
+    let program = if rules.iter().any(Rule::depends_on_program) {
+        program()
+    } else {
+        None
+    };
+
+    Cow::Owned(
+        rules
+            .iter()
+            .filter(|rule| rule.applies_to(domain_name.as_deref(), program.as_deref()))
+            .cloned()
+            .collect(),
+    )
+}
+
+/// Caches the hyperlink rules that apply to a pane, so that they are
+/// only determined again when the configuration is reloaded or, if a
+/// rule depends upon it, when the foreground process changes
+#[derive(Default)]
+pub struct HyperlinkRuleCache {
+    generation: Option<usize>,
+    process_id: Option<u32>,
+    rules: Vec<Rule>,
+}
+
+impl HyperlinkRuleCache {
+    /// Returns the rules that apply to a pane that belongs to
+    /// `domain_id`, and whether they have changed since they were
+    /// last returned, in which case the implicit hyperlinks that were
+    /// created using the old rules must be invalidated.
+    /// `process_id` identifies the foreground process of the pane;
+    /// `program` is only called to determine its name when that changes.
+    pub fn rules(
+        &mut self,
+        config: &ConfigHandle,
+        domain_id: DomainId,
+        process_id: Option<u32>,
+        program: impl FnOnce() -> Option<String>,
+    ) -> (&[Rule], bool) {
+        let process_id = if config.hyperlink_rules.iter().any(Rule::depends_on_program) {
+            process_id
+        } else {
+            None
+        };
+        let generation = Some(config.generation());
+        if self.generation == generation && self.process_id == process_id {
+            return (&self.rules, false);
+        }
+
+        let changed = self.generation.is_some();
+        self.rules =
+            hyperlink_rules_for_pane(&config.hyperlink_rules, domain_id, program).into_owned();
+        self.generation = generation;
+        self.process_id = process_id;
+        (&self.rules, changed)
+    }
+}
+
+/// Removes the implicit hyperlinks from the lines of the terminal,
+/// so that they are found again using a different set of rules
+pub fn terminal_invalidate_implicit_hyperlinks(term: &mut Terminal) {
+    for line in term.screen_mut().lines.iter_mut() {
+        line.invalidate_implicit_hyperlinks();
+    }
+}
+
+/// Implements Pane::get_lines for Terminal.
+/// `rules` are the hyperlink rules that apply to the pane.
 pub fn terminal_get_lines(
     term: &mut Terminal,
     lines: Range<StableRowIndex>,
+    rules: &[Rule],
 ) -> (StableRowIndex, Vec<Line>) {
     let screen = term.screen_mut();
     let phys_range = screen.stable_range(&lines);
-    let config = configuration();
     (
         screen.phys_to_stable_row_index(phys_range.start),
         screen
@@ -86,7 +173,7 @@ pub fn terminal_get_lines(
             .skip(phys_range.start)
             .take(phys_range.end - phys_range.start)
             .map(|line| {
-                line.scan_and_create_hyperlinks(&config.hyperlink_rules);
+                line.scan_and_create_hyperlinks(rules);
                 let cloned = line.clone();
                 line.clear_dirty();
                 cloned
@@ -95,6 +182,29 @@ pub fn terminal_get_lines(
     )
 }
 
+/// Implements Pane::copy_lines for Terminal
+pub fn terminal_copy_lines(
+    term: &Terminal,
+    lines: Range<StableRowIndex>,
+) -> (StableRowIndex, Vec<Line>) {
+    let screen = term.screen();
+    let first = lines.start.max(screen.phys_to_stable_row_index(0));
+    let end = lines
+        .end
+        .min(screen.phys_to_stable_row_index(screen.lines.len()));
+    let lines = match screen.stable_row_to_phys(first) {
+        Some(phys) if first < end => screen
+            .lines
+            .iter()
+            .skip(phys)
+            .take((end - first) as usize)
+            .cloned()
+            .collect(),
+        _ => vec![],
+    };
+    (first, lines)
+}
+
 /// Implements Pane::get_dimensions for Terminal
 pub fn terminal_get_dimensions(term: &mut Terminal) -> RenderableDimensions {
     let screen = term.screen();
@@ -7,6 +7,7 @@ use crate::window::WindowId;
 use crate::Mux;
 use anyhow::{anyhow, bail, Context, Error};
 use async_trait::async_trait;
+use config::configuration;
 use portable_pty::cmdbuilder::CommandBuilder;
 use portable_pty::{PtySize, PtySystem};
 use promise::{Future, Promise};
@@ -247,9 +248,14 @@ impl Domain for RemoteSshDomain {
             Some(c) => c,
             None => CommandBuilder::new_default_prog(),
         };
+        // Only the variables that are explicitly set are passed to
+        // the remote host, so removing one just stops it being sent
+        configuration().apply_domain_environment(&self.name, &mut cmd);
         let pair = self.pty_system.openpty(size)?;
         let pane_id = alloc_pane_id();
+        let tab = Rc::new(Tab::new(&size));
         cmd.env("WEZTERM_PANE", pane_id.to_string());
+        cmd.env("WEZTERM_TAB", tab.tab_id().to_string());
         let child = pair.slave.spawn_command(cmd)?;
         log::trace!("spawned: {:?}", child);
 
@@ -271,7 +277,6 @@ impl Domain for RemoteSshDomain {
             pair.master,
             self.id,
         ));
-        let tab = Rc::new(Tab::new(&size));
         tab.assign_pane(&pane);
 
         mux.add_tab_and_active_pane(&tab)?;
@@ -181,6 +181,7 @@ fn pane_tree(
                     pixel_width: 0,
                 },
                 working_dir: working_dir.map(Into::into),
+                memory_usage: pane.memory_usage(),
             })
         }
     }
@@ -512,6 +513,35 @@ impl Tab {
         }
     }
 
+    /// Divides the scrollback_memory_limit between the panes of
+    /// this tab, so that the limit applies to the tab as a whole.
+    /// Called whenever panes are added to or removed from the tab.
+    fn share_scrollback_memory(&self) {
+        let mut panes = vec![];
+        {
+            let mut root = self.pane.borrow_mut();
+            let mut cursor = match root.take() {
+                Some(tree) => tree.cursor(),
+                None => return,
+            };
+            loop {
+                if let Some(pane) = cursor.leaf_mut() {
+                    panes.push(Rc::clone(pane));
+                }
+                match cursor.preorder_next() {
+                    Ok(c) => cursor = c,
+                    Err(c) => {
+                        root.replace(c.tree());
+                        break;
+                    }
+                }
+            }
+        }
+        for pane in &panes {
+            pane.set_scrollback_memory_share(panes.len());
+        }
+    }
+
     pub fn set_zoomed(&self, zoomed: bool) {
         if self.zoomed.borrow().is_some() == zoomed {
             // Current zoom state matches intended zoom state,
@@ -1089,18 +1119,33 @@ impl Tab {
     }
 
     pub fn prune_dead_panes(&self) -> bool {
-        self.remove_pane_if(|_, pane| pane.is_dead())
+        !self
+            .remove_pane_if(|_, pane| pane.is_dead(), true)
+            .is_empty()
     }
 
     pub fn kill_pane(&self, pane_id: PaneId) -> bool {
-        self.remove_pane_if(|_, pane| pane.pane_id() == pane_id)
+        !self
+            .remove_pane_if(|_, pane| pane.pane_id() == pane_id, true)
+            .is_empty()
     }
 
     pub fn kill_panes_in_domain(&self, domain: DomainId) -> bool {
-        self.remove_pane_if(|_, pane| pane.domain_id() == domain)
+        !self
+            .remove_pane_if(|_, pane| pane.domain_id() == domain, true)
+            .is_empty()
     }
 
-    fn remove_pane_if<F>(&self, f: F) -> bool
+    /// Remove the pane from this tab without killing it, returning it
+    /// so that it can be placed elsewhere.  The remaining panes are
+    /// resized to fill the space that it occupied.
+    pub fn remove_pane(&self, pane_id: PaneId) -> Option<Rc<dyn Pane>> {
+        self.set_zoomed(false);
+        self.remove_pane_if(|_, pane| pane.pane_id() == pane_id, false)
+            .pop()
+    }
+
+    fn remove_pane_if<F>(&self, f: F, kill: bool) -> Vec<Rc<dyn Pane>>
     where
         F: Fn(usize, &Rc<dyn Pane>) -> bool,
     {
@@ -1136,7 +1181,7 @@ impl Tab {
                         let parent;
                         match cursor.unsplit_leaf() {
                             Ok((c, dead, p)) => {
-                                dead_panes.push(dead.pane_id());
+                                dead_panes.push(dead);
                                 parent = p.unwrap();
                                 cursor = c;
                             }
@@ -1144,7 +1189,7 @@ impl Tab {
                                 // We might be the root, for example
                                 if c.is_top() && c.is_leaf() {
                                     root.replace(Tree::Empty);
-                                    dead_panes.push(pane.pane_id());
+                                    dead_panes.push(pane);
                                 } else {
                                     root.replace(c.tree());
                                 }
@@ -1187,17 +1232,20 @@ impl Tab {
         }
 
         if !dead_panes.is_empty() {
+            self.share_scrollback_memory();
+        }
+
+        if kill && !dead_panes.is_empty() {
+            let dead_pane_ids: Vec<PaneId> = dead_panes.iter().map(|p| p.pane_id()).collect();
             promise::spawn::spawn_into_main_thread(async move {
                 let mux = Mux::get().unwrap();
-                for pane_id in dead_panes.into_iter() {
+                for pane_id in dead_pane_ids.into_iter() {
                     mux.remove_pane(pane_id);
                 }
             })
             .detach();
-            true
-        } else {
-            false
         }
+        dead_panes
     }
 
     pub fn can_close_without_prompting(&self) -> bool {
@@ -1259,6 +1307,7 @@ impl Tab {
             Ok(c) => *self.pane.borrow_mut() = Some(c.tree()),
             Err(_) => panic!("tried to assign root pane to non-empty tree"),
         }
+        self.share_scrollback_memory();
     }
 
     fn cell_dimensions(&self) -> PtySize {
@@ -1388,6 +1437,8 @@ impl Tab {
             *self.active.borrow_mut() = pane_index + 1;
         }
 
+        self.share_scrollback_memory();
+
         log::debug!("split info after split: {:#?}", self.iter_splits());
         log::debug!("pane info after split: {:#?}", self.iter_panes());
 
@@ -1453,6 +1504,8 @@ pub struct PaneEntry {
     pub working_dir: Option<SerdeUrl>,
     pub is_active_pane: bool,
     pub is_zoomed_pane: bool,
+    /// Estimated number of bytes used to hold the pane content
+    pub memory_usage: usize,
 }
 
 #[derive(Deserialize, Clone, Serialize, PartialEq, Debug)]
@@ -1737,4 +1790,86 @@ mod test {
         assert_eq!(390, panes[2].pixel_width);
         assert_eq!(600, panes[2].pixel_height);
     }
+
+    #[test]
+    fn tab_remove_pane() {
+        let size = PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 800,
+            pixel_height: 600,
+        };
+
+        let tab = Tab::new(&size);
+        tab.assign_pane(&FakePane::new(1, size));
+
+        let horz_size = tab
+            .compute_split_size(0, SplitDirection::Horizontal)
+            .unwrap();
+        tab.split_and_insert(
+            0,
+            SplitDirection::Horizontal,
+            FakePane::new(2, horz_size.second),
+        )
+        .unwrap();
+        assert_eq!(2, tab.count_panes());
+
+        assert!(tab.remove_pane(3).is_none());
+
+        let removed = tab.remove_pane(2).unwrap();
+        assert_eq!(2, removed.pane_id());
+
+        let panes = tab.iter_panes();
+        assert_eq!(1, panes.len());
+        assert_eq!(1, panes[0].pane.pane_id());
+        assert_eq!(80, panes[0].width);
+        assert_eq!(24, panes[0].height);
+        assert!(!tab.is_dead());
+
+        let removed = tab.remove_pane(1).unwrap();
+        assert_eq!(1, removed.pane_id());
+        assert_eq!(0, tab.count_panes());
+    }
+
+    #[test]
+    fn tab_move_pane_between_tabs() {
+        let size = PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 800,
+            pixel_height: 600,
+        };
+
+        let src = Tab::new(&size);
+        src.assign_pane(&FakePane::new(1, size));
+        let horz_size = src
+            .compute_split_size(0, SplitDirection::Horizontal)
+            .unwrap();
+        src.split_and_insert(
+            0,
+            SplitDirection::Horizontal,
+            FakePane::new(2, horz_size.second),
+        )
+        .unwrap();
+
+        let dest = Tab::new(&size);
+        dest.assign_pane(&FakePane::new(3, size));
+
+        let moved = src.remove_pane(1).unwrap();
+        dest.split_and_insert(0, SplitDirection::Vertical, moved)
+            .unwrap();
+
+        let panes = src.iter_panes();
+        assert_eq!(1, panes.len());
+        assert_eq!(2, panes[0].pane.pane_id());
+        assert_eq!(80, panes[0].width);
+
+        let panes = dest.iter_panes();
+        assert_eq!(2, panes.len());
+        assert_eq!(3, panes[0].pane.pane_id());
+        assert_eq!(1, panes[1].pane.pane_id());
+        assert_eq!(80, panes[1].width);
+        assert_eq!(11, panes[1].height);
+        assert_eq!(13, panes[1].top);
+    }
 }
@@ -11,6 +11,7 @@ use crate::window::WindowId;
 use crate::Mux;
 use anyhow::bail;
 use async_trait::async_trait;
+use config::configuration;
 use config::keyassignment::ScrollbackEraseMode;
 use crossbeam::channel::{unbounded as channel, Receiver, Sender};
 use filedescriptor::{FileDescriptor, Pipe};
@@ -145,7 +146,9 @@ impl Pane for TermWizTerminalPane {
     }
 
     fn get_lines(&self, lines: Range<StableRowIndex>) -> (StableRowIndex, Vec<Line>) {
-        terminal_get_lines(&mut self.terminal.borrow_mut(), lines)
+        let config = configuration();
+        let rules = hyperlink_rules_for_pane(&config.hyperlink_rules, self.domain_id, || None);
+        terminal_get_lines(&mut self.terminal.borrow_mut(), lines, &rules)
     }
 
     fn get_dimensions(&self) -> RenderableDimensions {
@@ -261,6 +264,9 @@ impl Pane for TermWizTerminalPane {
             ScrollbackEraseMode::ScrollbackAndViewport => {
                 self.terminal.borrow_mut().erase_scrollback_and_viewport();
             }
+            ScrollbackEraseMode::ViewportOnly => {
+                self.terminal.borrow_mut().erase_viewport();
+            }
         }
     }
 }
@@ -10,6 +10,7 @@ pub struct Window {
     id: WindowId,
     tabs: Vec<Rc<Tab>>,
     active: usize,
+    last_active: Option<TabId>,
     clipboard: Option<Arc<dyn Clipboard>>,
     invalidated: bool,
 }
@@ -20,6 +21,7 @@ impl Window {
             id: WIN_ID.fetch_add(1, ::std::sync::atomic::Ordering::Relaxed),
             tabs: vec![],
             active: 0,
+            last_active: None,
             clipboard: None,
             invalidated: false,
         }
@@ -124,12 +126,43 @@ impl Window {
         self.active
     }
 
+    /// Returns the index of the previously active tab, if it
+    /// is still present in this window
+    pub fn get_last_active_idx(&self) -> Option<usize> {
+        self.last_active.and_then(|tab_id| self.idx_by_id(tab_id))
+    }
+
     pub fn set_active(&mut self, idx: usize) {
         assert!(idx < self.tabs.len());
         self.invalidated = true;
         self.active = idx;
     }
 
+    /// Remember the currently active tab as the last active tab,
+    /// then activate the tab at idx.  This is used for user
+    /// initiated tab switches so that ActivateLastTab can return
+    /// to the prior tab.
+    pub fn save_and_then_set_active(&mut self, idx: usize) {
+        if idx == self.active {
+            return;
+        }
+        self.last_active = self.get_active().map(|tab| tab.tab_id());
+        self.set_active(idx);
+    }
+
+    /// Moves the tab at `from_idx` so that it is at `to_idx`,
+    /// shifting the tabs in between.  The same tab remains active.
+    pub fn move_tab(&mut self, from_idx: usize, to_idx: usize) {
+        assert!(from_idx < self.tabs.len() && to_idx < self.tabs.len());
+        let active = self.get_active().map(|tab| tab.tab_id());
+        let tab = self.tabs.remove(from_idx);
+        self.tabs.insert(to_idx, tab);
+        if let Some(idx) = active.and_then(|tab_id| self.idx_by_id(tab_id)) {
+            self.active = idx;
+        }
+        self.invalidated = true;
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = &Rc<Tab>> {
         self.tabs.iter()
     }
@@ -179,3 +212,42 @@ impl Window {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use portable_pty::PtySize;
+
+    #[test]
+    fn move_tab() {
+        let size = PtySize {
+            rows: 24,
+            cols: 80,
+            pixel_width: 800,
+            pixel_height: 600,
+        };
+        let tabs: Vec<Rc<Tab>> = (0..3).map(|_| Rc::new(Tab::new(&size))).collect();
+        let ids = |window: &Window| -> Vec<TabId> { window.iter().map(|t| t.tab_id()).collect() };
+
+        let mut window = Window::new();
+        for tab in &tabs {
+            window.push(tab);
+        }
+        window.set_active(0);
+
+        window.move_tab(0, 2);
+        assert_eq!(
+            ids(&window),
+            vec![tabs[1].tab_id(), tabs[2].tab_id(), tabs[0].tab_id()]
+        );
+        assert_eq!(window.get_active_idx(), 2);
+
+        // Moving another tab past the active tab keeps it active
+        window.move_tab(0, 2);
+        assert_eq!(
+            ids(&window),
+            vec![tabs[2].tab_id(), tabs[0].tab_id(), tabs[1].tab_id()]
+        );
+        assert_eq!(window.get_active_idx(), 1);
+    }
+}
@@ -0,0 +1,149 @@
+//! Linux cgroup v2 containment for spawned processes.
+//! See `CommandBuilder::contain_process_tree`.
+use crate::{Child, ExitStatus};
+use anyhow::Context;
+use std::fs::{File, OpenOptions};
+use std::io::Result as IoResult;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// Returns the path to the cgroup v2 directory that holds the
+/// current process
+fn own_cgroup() -> anyhow::Result<PathBuf> {
+    let data = std::fs::read_to_string("/proc/self/cgroup").context("reading /proc/self/cgroup")?;
+    for line in data.lines() {
+        if let Some(path) = line.strip_prefix("0::") {
+            return Ok(Path::new(CGROUP_ROOT).join(path.trim_start_matches('/')));
+        }
+    }
+    anyhow::bail!("the unified (v2) cgroup hierarchy is not available");
+}
+
+/// Sends SIGKILL to every process that is a member of the cgroup
+fn kill_cgroup(path: &Path) -> IoResult<()> {
+    // cgroup.kill is available in linux 5.14 and later and kills
+    // the whole cgroup atomically, so that a process that is forking
+    // cannot escape
+    if std::fs::write(path.join("cgroup.kill"), b"1").is_ok() {
+        return Ok(());
+    }
+
+    let procs = std::fs::read_to_string(path.join("cgroup.procs"))?;
+    for pid in procs
+        .lines()
+        .filter_map(|line| line.trim().parse::<libc::pid_t>().ok())
+    {
+        unsafe {
+            libc::kill(pid, libc::SIGKILL);
+        }
+    }
+    Ok(())
+}
+
+/// A newly created cgroup that a child process is about to move into
+pub struct ProcessCgroup {
+    path: PathBuf,
+    /// Opened prior to forking so that the child can add itself
+    /// to the cgroup from `pre_exec` without allocating
+    procs: File,
+}
+
+impl ProcessCgroup {
+    /// Creates a cgroup beneath the one that holds the current process
+    pub fn new() -> anyhow::Result<Self> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let path = own_cgroup()?.join(format!(
+            "wezterm-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir(&path)
+            .with_context(|| format!("creating cgroup {}", path.display()))?;
+
+        match OpenOptions::new()
+            .write(true)
+            .open(path.join("cgroup.procs"))
+        {
+            Ok(procs) => Ok(Self { path, procs }),
+            Err(err) => {
+                std::fs::remove_dir(&path).ok();
+                Err(err).with_context(|| format!("opening {}/cgroup.procs", path.display()))
+            }
+        }
+    }
+
+    /// The descriptor that the child writes "0" into in order to
+    /// move itself into the cgroup
+    pub fn procs_fd(&self) -> RawFd {
+        self.procs.as_raw_fd()
+    }
+
+    /// Associates the spawned child with the cgroup
+    pub fn contain(self, child: std::process::Child) -> ContainedChild {
+        ContainedChild {
+            child,
+            cgroup: self.path,
+        }
+    }
+}
+
+/// A child process whose descendants all live in a dedicated cgroup.
+/// Killing or dropping it kills any processes that remain in that cgroup,
+/// including those that have detached themselves from the session.
+#[derive(Debug)]
+pub struct ContainedChild {
+    child: std::process::Child,
+    cgroup: PathBuf,
+}
+
+impl Child for ContainedChild {
+    fn try_wait(&mut self) -> IoResult<Option<ExitStatus>> {
+        Child::try_wait(&mut self.child)
+    }
+
+    fn kill(&mut self) -> IoResult<()> {
+        self.kill_with_grace_period(crate::DEFAULT_KILL_GRACE_PERIOD)
+    }
+
+    fn kill_with_grace_period(&mut self, grace_period: Duration) -> IoResult<()> {
+        // Give the leader its grace period first,
+        // then take out anything that it left behind
+        let result = Child::kill_with_grace_period(&mut self.child, grace_period);
+        if let Err(err) = kill_cgroup(&self.cgroup) {
+            log::debug!("failed to kill cgroup {}: {}", self.cgroup.display(), err);
+        }
+        result
+    }
+
+    fn wait(&mut self) -> IoResult<ExitStatus> {
+        Child::wait(&mut self.child)
+    }
+
+    fn process_id(&self) -> Option<u32> {
+        Child::process_id(&self.child)
+    }
+}
+
+impl Drop for ContainedChild {
+    fn drop(&mut self) {
+        kill_cgroup(&self.cgroup).ok();
+
+        // The cgroup can only be removed once all of its members
+        // have exited, which can take a moment after being killed
+        let path = self.cgroup.clone();
+        std::thread::spawn(move || {
+            for _ in 0..20 {
+                if std::fs::remove_dir(&path).is_ok() {
+                    return;
+                }
+                std::thread::sleep(Duration::from_millis(50));
+            }
+            log::debug!("unable to remove cgroup {}", path.display());
+        });
+    }
+}
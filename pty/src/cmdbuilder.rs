@@ -13,9 +13,16 @@ use std::os::windows::ffi::OsStrExt;
 pub struct CommandBuilder {
     args: Vec<OsString>,
     envs: Vec<(OsString, OsString)>,
+    /// Variables that are removed from the inherited environment
+    removed_envs: Vec<OsString>,
     cwd: Option<OsString>,
     #[cfg(unix)]
     pub(crate) umask: Option<libc::mode_t>,
+    /// Whether the spawned process and all of its descendants should
+    /// be placed into a cgroup (linux) or job object (windows).
+    /// This is a local policy decision and is not serialized.
+    #[cfg_attr(feature = "serde_support", serde(skip))]
+    contain_process_tree: bool,
 }
 
 impl CommandBuilder {
@@ -25,9 +32,11 @@ impl CommandBuilder {
         Self {
             args: vec![program.as_ref().to_owned()],
             envs: vec![],
+            removed_envs: vec![],
             cwd: None,
             #[cfg(unix)]
             umask: None,
+            contain_process_tree: false,
         }
     }
 
@@ -36,9 +45,11 @@ impl CommandBuilder {
         Self {
             args,
             envs: vec![],
+            removed_envs: vec![],
             cwd: None,
             #[cfg(unix)]
             umask: None,
+            contain_process_tree: false,
         }
     }
 
@@ -48,9 +59,11 @@ impl CommandBuilder {
         Self {
             args: vec![],
             envs: vec![],
+            removed_envs: vec![],
             cwd: None,
             #[cfg(unix)]
             umask: None,
+            contain_process_tree: false,
         }
     }
 
@@ -59,6 +72,12 @@ impl CommandBuilder {
         self.args.is_empty()
     }
 
+    /// Returns the argument vector, which is empty for a builder
+    /// created via `new_default_prog`
+    pub fn get_argv(&self) -> &Vec<OsString> {
+        &self.args
+    }
+
     /// Append an argument to the current command line.
     /// Will panic if called on a builder created via `new_default_prog`.
     pub fn arg<S: AsRef<OsStr>>(&mut self, arg: S) {
@@ -85,10 +104,51 @@ impl CommandBuilder {
         K: AsRef<OsStr>,
         V: AsRef<OsStr>,
     {
+        self.removed_envs.retain(|k| k.as_os_str() != key.as_ref());
         self.envs
             .push((key.as_ref().to_owned(), val.as_ref().to_owned()));
     }
 
+    /// Remove an environmental variable, so that the spawned command
+    /// doesn't inherit it, and discard any override of it
+    pub fn env_remove<K>(&mut self, key: K)
+    where
+        K: AsRef<OsStr>,
+    {
+        let key = key.as_ref();
+        self.envs.retain(|(k, _)| k.as_os_str() != key);
+        if !self.removed_envs.iter().any(|k| k.as_os_str() == key) {
+            self.removed_envs.push(key.to_owned());
+        }
+    }
+
+    /// Iterates over the environmental variables that have been
+    /// removed via `env_remove`
+    pub fn iter_removed_env(&self) -> impl Iterator<Item = &OsStr> {
+        self.removed_envs.iter().map(|key| key.as_os_str())
+    }
+
+    /// Returns the value that the environmental variable `key` will
+    /// be set to, if it has been overridden via `env`
+    pub fn get_env<K>(&self, key: K) -> Option<&OsStr>
+    where
+        K: AsRef<OsStr>,
+    {
+        self.envs
+            .iter()
+            .rev()
+            .find(|(k, _)| k.as_os_str() == key.as_ref())
+            .map(|(_, val)| val.as_os_str())
+    }
+
+    /// Iterates over the environmental variable overrides,
+    /// in the order that they were added
+    pub fn iter_env(&self) -> impl Iterator<Item = (&OsStr, &OsStr)> {
+        self.envs
+            .iter()
+            .map(|(key, val)| (key.as_os_str(), val.as_os_str()))
+    }
+
     pub fn cwd<D>(&mut self, dir: D)
     where
         D: AsRef<OsStr>,
@@ -105,6 +165,21 @@ impl CommandBuilder {
         self.umask = mask;
     }
 
+    /// When set to true, the spawned process and any processes that it
+    /// spawns (including those that detach themselves from the session)
+    /// are placed into a cgroup on linux, or a job object on windows,
+    /// so that killing the child reliably kills the whole process tree.
+    /// On other systems, or when the containment cannot be set up,
+    /// this is ignored and the process is spawned normally.
+    pub fn contain_process_tree(&mut self, contain: bool) {
+        self.contain_process_tree = contain;
+    }
+
+    /// Returns true if `contain_process_tree` has been enabled
+    pub fn get_contain_process_tree(&self) -> bool {
+        self.contain_process_tree
+    }
+
     #[cfg(feature = "ssh")]
     pub(crate) fn iter_env_as_str(&self) -> impl Iterator<Item = (&str, &str)> {
         self.envs.iter().filter_map(|(key, val)| {
@@ -162,6 +237,9 @@ impl CommandBuilder {
             cmd
         };
 
+        for key in &self.removed_envs {
+            cmd.env_remove(key);
+        }
         for (key, val) in &self.envs {
             cmd.env(key, val);
         }
@@ -279,6 +357,10 @@ impl CommandBuilder {
             env_hash.insert(lowerkey(&key), Entry { key, value });
         }
 
+        for key in &self.removed_envs {
+            env_hash.remove(&lowerkey(key));
+        }
+
         // override with the specified values
         for (key, value) in &self.envs {
             env_hash.insert(
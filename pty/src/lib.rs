@@ -49,10 +49,13 @@ use libc;
 #[cfg(feature = "serde_support")]
 use serde_derive::*;
 use std::io::Result as IoResult;
+use std::time::Duration;
 
 pub mod cmdbuilder;
 pub use cmdbuilder::CommandBuilder;
 
+#[cfg(target_os = "linux")]
+mod cgroup;
 #[cfg(unix)]
 pub mod unix;
 #[cfg(windows)]
@@ -110,8 +113,18 @@ pub trait MasterPty: std::io::Write {
     /// of the process group or session leader
     #[cfg(unix)]
     fn process_group_leader(&self) -> Option<libc::pid_t>;
+
+    /// If applicable to the type of the tty, return the termios
+    /// settings of the tty, which reflect the modes that the
+    /// program running in it has selected
+    #[cfg(unix)]
+    fn get_termios(&self) -> Option<libc::termios>;
 }
 
+/// How long `Child::kill` waits for a process to exit after asking
+/// it to do so, before forcibly killing it
+pub const DEFAULT_KILL_GRACE_PERIOD: Duration = Duration::from_millis(200);
+
 /// Represents a child process spawned into the pty.
 /// This handle can be used to wait for or terminate that child process.
 pub trait Child: std::fmt::Debug {
@@ -122,9 +135,22 @@ pub trait Child: std::fmt::Debug {
     fn try_wait(&mut self) -> IoResult<Option<ExitStatus>>;
     /// Terminate the child process
     fn kill(&mut self) -> IoResult<()>;
+    /// Terminate the child process, first asking it to exit and allowing
+    /// it up to `grace_period` to do so before it is forcibly killed.
+    /// A zero grace period kills it immediately.
+    /// Implementations that have no way to ask the process to exit
+    /// simply call `kill`.
+    fn kill_with_grace_period(&mut self, _grace_period: Duration) -> IoResult<()> {
+        self.kill()
+    }
     /// Blocks execution until the child process has completed,
     /// yielding its exit status.
     fn wait(&mut self) -> IoResult<ExitStatus>;
+    /// Returns the process identifier of the child process,
+    /// if applicable
+    fn process_id(&self) -> Option<u32> {
+        None
+    }
 }
 
 /// Represents the slave side of a pty.
@@ -135,30 +161,56 @@ pub trait SlavePty {
 }
 
 /// Represents the exit status of a child process.
-/// This is rather anemic in the current version of this crate,
-/// holding only an indicator of success or failure.
 #[derive(Debug, Clone)]
 pub struct ExitStatus {
-    successful: bool,
+    code: u32,
+    signal: Option<i32>,
 }
 
 impl ExitStatus {
     /// Construct an ExitStatus from a process return code
     pub fn with_exit_code(code: u32) -> Self {
+        Self { code, signal: None }
+    }
+
+    /// Construct an ExitStatus for a process that was terminated
+    /// by a signal
+    pub fn with_signal(signal: i32) -> Self {
         Self {
-            successful: code == 0,
+            code: 1,
+            signal: Some(signal),
         }
     }
 
     pub fn success(&self) -> bool {
-        self.successful
+        self.code == 0 && self.signal.is_none()
+    }
+
+    /// Returns the process return code.  This is not meaningful
+    /// if the process was terminated by a signal.
+    pub fn exit_code(&self) -> u32 {
+        self.code
+    }
+
+    /// Returns the signal that terminated the process, if any
+    pub fn signal(&self) -> Option<i32> {
+        self.signal
     }
 }
 
 impl From<std::process::ExitStatus> for ExitStatus {
     fn from(status: std::process::ExitStatus) -> ExitStatus {
-        ExitStatus {
-            successful: status.success(),
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if let Some(signal) = status.signal() {
+                return ExitStatus::with_signal(signal);
+            }
+        }
+
+        match status.code() {
+            Some(code) => ExitStatus::with_exit_code(code as u32),
+            None => ExitStatus::with_exit_code(if status.success() { 0 } else { 1 }),
         }
     }
 }
@@ -189,35 +241,45 @@ impl Child for std::process::Child {
     }
 
     fn kill(&mut self) -> IoResult<()> {
+        self.kill_with_grace_period(DEFAULT_KILL_GRACE_PERIOD)
+    }
+
+    fn kill_with_grace_period(&mut self, grace_period: Duration) -> IoResult<()> {
         #[cfg(unix)]
         {
-            // On unix, we send the SIGHUP signal instead of trying to kill
-            // the process. The default behavior of a process receiving this
-            // signal is to be killed unless it configured a signal handler.
-            let result = unsafe { libc::kill(self.id() as i32, libc::SIGHUP) };
-            if result != 0 {
-                return Err(std::io::Error::last_os_error());
-            }
-
-            // We successfully delivered SIGHUP, but the semantics of Child::kill
-            // are that on success the process is dead or shortly about to
-            // terminate.  Since SIGUP doesn't guarantee termination, we
-            // give the process a bit of a grace period to shutdown or do whatever
-            // it is doing in its signal handler befre we proceed with the
-            // full on kill.
-            for attempt in 0..5 {
-                if attempt > 0 {
-                    std::thread::sleep(std::time::Duration::from_millis(50));
+            if grace_period > Duration::from_secs(0) {
+                // On unix, we send the SIGHUP signal instead of trying to kill
+                // the process. The default behavior of a process receiving this
+                // signal is to be killed unless it configured a signal handler.
+                let result = unsafe { libc::kill(self.id() as i32, libc::SIGHUP) };
+                if result != 0 {
+                    return Err(std::io::Error::last_os_error());
                 }
 
-                if let Ok(Some(_)) = self.try_wait() {
-                    // It completed, so report success!
-                    return Ok(());
+                // We successfully delivered SIGHUP, but the semantics of Child::kill
+                // are that on success the process is dead or shortly about to
+                // terminate.  Since SIGUP doesn't guarantee termination, we
+                // give the process a bit of a grace period to shutdown or do whatever
+                // it is doing in its signal handler befre we proceed with the
+                // full on kill.
+                let deadline = std::time::Instant::now() + grace_period;
+                loop {
+                    if let Ok(Some(_)) = self.try_wait() {
+                        // It completed, so report success!
+                        return Ok(());
+                    }
+                    let now = std::time::Instant::now();
+                    if now >= deadline {
+                        break;
+                    }
+                    std::thread::sleep((deadline - now).min(Duration::from_millis(50)));
                 }
-            }
 
-            // it's still alive after a grace period, so proceed with a kill
+                // it's still alive after a grace period, so proceed with a kill
+            }
         }
+        #[cfg(not(unix))]
+        let _ = grace_period;
 
         std::process::Child::kill(self)
     }
@@ -225,6 +287,10 @@ impl Child for std::process::Child {
     fn wait(&mut self) -> IoResult<ExitStatus> {
         std::process::Child::wait(self).map(Into::into)
     }
+
+    fn process_id(&self) -> Option<u32> {
+        Some(self.id())
+    }
 }
 
 pub fn native_pty_system() -> Box<dyn PtySystem> {
@@ -183,6 +183,12 @@ impl MasterPty for Master {
         // N/A: there is no local process
         None
     }
+
+    #[cfg(unix)]
+    fn get_termios(&self) -> Option<libc::termios> {
+        // N/A: there is no local tty
+        None
+    }
 }
 
 struct Reader {
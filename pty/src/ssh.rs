@@ -188,6 +188,12 @@ impl MasterPty for SshMaster {
         // N/A: there is no local process
         None
     }
+
+    #[cfg(unix)]
+    fn get_termios(&self) -> Option<libc::termios> {
+        // N/A: there is no local tty
+        None
+    }
 }
 
 struct SshSlave {
@@ -191,9 +191,31 @@ impl PtyFd {
         })
     }
 
-    fn spawn_command(&self, builder: CommandBuilder) -> anyhow::Result<std::process::Child> {
+    fn spawn_command(
+        &self,
+        builder: CommandBuilder,
+    ) -> anyhow::Result<Box<dyn Child + Send + Sync>> {
         let configured_umask = builder.umask;
 
+        #[cfg(target_os = "linux")]
+        let cgroup = if builder.get_contain_process_tree() {
+            match crate::cgroup::ProcessCgroup::new() {
+                Ok(cgroup) => Some(cgroup),
+                Err(err) => {
+                    log::warn!(
+                        "Unable to contain the process tree in a cgroup, \
+                         spawning without it: {:#}",
+                        err
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        #[cfg(target_os = "linux")]
+        let cgroup_procs_fd = cgroup.as_ref().map(|cgroup| cgroup.procs_fd());
+
         let mut cmd = builder.as_command()?;
 
         unsafe {
@@ -235,6 +257,18 @@ impl PtyFd {
                         }
                     }
 
+                    // Move ourselves into the cgroup so that everything
+                    // that we spawn is contained by it.  This must happen
+                    // before close_random_fds closes the descriptor.
+                    // Failure to do so is not fatal; we'll merely be
+                    // uncontained.
+                    #[cfg(target_os = "linux")]
+                    {
+                        if let Some(fd) = cgroup_procs_fd {
+                            libc::write(fd, b"0".as_ptr() as *const _, 1);
+                        }
+                    }
+
                     close_random_fds();
 
                     if let Some(mask) = configured_umask {
@@ -256,7 +290,14 @@ impl PtyFd {
         child.stdout.take();
         child.stderr.take();
 
-        Ok(child)
+        #[cfg(target_os = "linux")]
+        {
+            if let Some(cgroup) = cgroup {
+                return Ok(Box::new(cgroup.contain(child)));
+            }
+        }
+
+        Ok(Box::new(child))
     }
 }
 
@@ -296,7 +337,7 @@ impl SlavePty for UnixSlavePty {
         &self,
         builder: CommandBuilder,
     ) -> Result<Box<dyn Child + Send + Sync>, Error> {
-        Ok(Box::new(self.fd.spawn_command(builder)?))
+        self.fd.spawn_command(builder)
     }
 }
 
@@ -325,6 +366,14 @@ impl MasterPty for UnixMasterPty {
             _ => None,
         }
     }
+
+    fn get_termios(&self) -> Option<libc::termios> {
+        let mut termios: libc::termios = unsafe { mem::zeroed() };
+        match unsafe { libc::tcgetattr(self.fd.0.as_raw_fd(), &mut termios) } {
+            0 => Some(termios),
+            _ => None,
+        }
+    }
 }
 
 impl Write for UnixMasterPty {
@@ -6,6 +6,7 @@ use std::pin::Pin;
 use std::sync::Mutex;
 use std::task::{Context, Poll};
 use winapi::shared::minwindef::DWORD;
+use winapi::um::jobapi2::TerminateJobObject;
 use winapi::um::minwinbase::STILL_ACTIVE;
 use winapi::um::processthreadsapi::*;
 use winapi::um::synchapi::WaitForSingleObject;
@@ -20,6 +21,10 @@ use filedescriptor::OwnedHandle;
 #[derive(Debug)]
 pub struct WinChild {
     proc: Mutex<OwnedHandle>,
+    /// The job object that contains the process tree, when
+    /// `CommandBuilder::contain_process_tree` is enabled.
+    /// Closing it terminates any remaining processes in the job.
+    job: Mutex<Option<OwnedHandle>>,
 }
 
 impl WinChild {
@@ -58,6 +63,11 @@ impl Child for WinChild {
     fn kill(&mut self) -> IoResult<()> {
         self.do_kill().ok();
         self.wait()?;
+        if let Some(job) = self.job.lock().unwrap().as_ref() {
+            unsafe {
+                TerminateJobObject(job.as_raw_handle() as _, 1);
+            }
+        }
         Ok(())
     }
 
@@ -77,6 +87,14 @@ impl Child for WinChild {
             Err(IoError::last_os_error())
         }
     }
+
+    fn process_id(&self) -> Option<u32> {
+        let proc = self.proc.lock().unwrap();
+        match unsafe { GetProcessId(proc.as_raw_handle()) } {
+            0 => None,
+            pid => Some(pid),
+        }
+    }
 }
 
 impl std::future::Future for WinChild {
@@ -17,11 +17,16 @@ use std::sync::Mutex;
 use winapi::shared::minwindef::DWORD;
 use winapi::shared::winerror::{HRESULT, S_OK};
 use winapi::um::handleapi::*;
+use winapi::um::jobapi2::{AssignProcessToJobObject, CreateJobObjectW, SetInformationJobObject};
 use winapi::um::processthreadsapi::*;
 use winapi::um::winbase::{
-    CREATE_UNICODE_ENVIRONMENT, EXTENDED_STARTUPINFO_PRESENT, STARTUPINFOEXW,
+    CREATE_SUSPENDED, CREATE_UNICODE_ENVIRONMENT, EXTENDED_STARTUPINFO_PRESENT, STARTUPINFOEXW,
 };
 use winapi::um::wincon::COORD;
+use winapi::um::winnt::{
+    JobObjectExtendedLimitInformation, JOBOBJECT_EXTENDED_LIMIT_INFORMATION,
+    JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE,
+};
 
 pub type HPCON = HANDLE;
 
@@ -58,6 +63,35 @@ lazy_static! {
     static ref CONPTY: ConPtyFuncs = load_conpty();
 }
 
+/// Creates a job object that terminates all of its processes
+/// when the last handle to it is closed
+fn create_kill_on_close_job() -> anyhow::Result<OwnedHandle> {
+    let job = unsafe { CreateJobObjectW(ptr::null_mut(), ptr::null()) };
+    if job.is_null() {
+        bail!("CreateJobObjectW failed: {}", IoError::last_os_error());
+    }
+    let job = unsafe { OwnedHandle::from_raw_handle(job as _) };
+
+    let mut info: JOBOBJECT_EXTENDED_LIMIT_INFORMATION = unsafe { mem::zeroed() };
+    info.BasicLimitInformation.LimitFlags = JOB_OBJECT_LIMIT_KILL_ON_JOB_CLOSE;
+    let res = unsafe {
+        SetInformationJobObject(
+            job.as_raw_handle() as _,
+            JobObjectExtendedLimitInformation,
+            &mut info as *mut _ as *mut _,
+            mem::size_of::<JOBOBJECT_EXTENDED_LIMIT_INFORMATION>() as u32,
+        )
+    };
+    if res == 0 {
+        bail!(
+            "SetInformationJobObject failed: {}",
+            IoError::last_os_error()
+        );
+    }
+
+    Ok(job)
+}
+
 pub struct PsuedoCon {
     con: HPCON,
 }
@@ -118,6 +152,29 @@ impl PsuedoCon {
 
         let cwd = cmd.current_directory();
 
+        let job = if cmd.get_contain_process_tree() {
+            match create_kill_on_close_job() {
+                Ok(job) => Some(job),
+                Err(err) => {
+                    log::warn!(
+                        "Unable to contain the process tree in a job object, \
+                         spawning without it: {:#}",
+                        err
+                    );
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        // When using a job, the process is created suspended so that it
+        // cannot spawn anything before it has been assigned to the job
+        let mut flags = EXTENDED_STARTUPINFO_PRESENT | CREATE_UNICODE_ENVIRONMENT;
+        if job.is_some() {
+            flags |= CREATE_SUSPENDED;
+        }
+
         let res = unsafe {
             CreateProcessW(
                 exe.as_mut_slice().as_mut_ptr(),
@@ -125,7 +182,7 @@ impl PsuedoCon {
                 ptr::null_mut(),
                 ptr::null_mut(),
                 0,
-                EXTENDED_STARTUPINFO_PRESENT | CREATE_UNICODE_ENVIRONMENT,
+                flags,
                 cmd.environment_block().as_mut_slice().as_mut_ptr() as *mut _,
                 cwd.as_ref()
                     .map(|c| c.as_slice().as_ptr())
@@ -148,11 +205,33 @@ impl PsuedoCon {
 
         // Make sure we close out the thread handle so we don't leak it;
         // we do this simply by making it owned
-        let _main_thread = unsafe { OwnedHandle::from_raw_handle(pi.hThread) };
+        let main_thread = unsafe { OwnedHandle::from_raw_handle(pi.hThread) };
         let proc = unsafe { OwnedHandle::from_raw_handle(pi.hProcess) };
 
+        let job = job.and_then(|job| {
+            let res = unsafe {
+                AssignProcessToJobObject(job.as_raw_handle() as _, proc.as_raw_handle() as _)
+            };
+            if res == 0 {
+                log::warn!(
+                    "AssignProcessToJobObject failed, process tree will not be contained: {}",
+                    IoError::last_os_error()
+                );
+                None
+            } else {
+                Some(job)
+            }
+        });
+
+        if flags & CREATE_SUSPENDED != 0 {
+            unsafe {
+                ResumeThread(main_thread.as_raw_handle() as _);
+            }
+        }
+
         Ok(WinChild {
             proc: Mutex::new(proc),
+            job: Mutex::new(job),
         })
     }
 }
@@ -21,6 +21,14 @@ pub trait TerminalConfiguration: std::fmt::Debug {
         3500
     }
 
+    /// Returns the maximum number of bytes of memory that the scrollback
+    /// may occupy.  When exceeded, the oldest lines of scrollback are
+    /// discarded.  None means that only scrollback_size limits the
+    /// scrollback.
+    fn scrollback_memory_limit(&self) -> Option<usize> {
+        None
+    }
+
     /// Return true if the embedding application wants to use CSI-u encoding
     /// for keys that would otherwise be ambiguous.
     /// <http://www.leonerd.org.uk/hacks/fixterms/>
@@ -28,6 +36,13 @@ pub trait TerminalConfiguration: std::fmt::Debug {
         false
     }
 
+    /// Return true if applications are allowed to opt in to the
+    /// progressive keyboard enhancements of the kitty keyboard protocol.
+    /// <https://sw.kovidgoyal.net/kitty/keyboard-protocol/>
+    fn enable_kitty_keyboard(&self) -> bool {
+        false
+    }
+
     /// Returns the current generation and its associated hyperlink rules.
     /// hyperlink rules are used to recognize and automatically generate
     /// hyperlink attributes for runs of text that match the provided rules.
@@ -43,32 +43,41 @@ pub struct MouseEvent {
 
 /// This is a little helper that keeps track of the "click streak",
 /// which is the number of successive clicks of the same mouse button
-/// within the `CLICK_INTERVAL`.  The streak is reset to 1 each time
-/// the mouse button differs from the last click, or when the elapsed
-/// time exceeds `CLICK_INTERVAL`.
+/// within the multi-click interval and radius.  The streak is reset
+/// to 1 each time the mouse button differs from the last click, when
+/// the elapsed time exceeds the interval, or when the click is further
+/// than the radius from the last click.
 #[derive(Debug, Clone)]
 pub struct LastMouseClick {
     pub button: MouseButton,
     time: Instant,
     pub streak: usize,
+    /// Where the click happened, in pixels
+    pub position: (isize, isize),
 }
 
-/// The multi-click interval, measured in milliseconds
-const CLICK_INTERVAL: u64 = 500;
-
 impl LastMouseClick {
-    pub fn new(button: MouseButton) -> Self {
+    pub fn new(button: MouseButton, position: (isize, isize)) -> Self {
         Self {
             button,
             time: Instant::now(),
             streak: 1,
+            position,
         }
     }
 
-    pub fn add(&self, button: MouseButton) -> Self {
+    pub fn add(
+        &self,
+        button: MouseButton,
+        position: (isize, isize),
+        interval: Duration,
+        radius: isize,
+    ) -> Self {
         let now = Instant::now();
         let streak = if button == self.button
-            && now.duration_since(self.time) <= Duration::from_millis(CLICK_INTERVAL)
+            && now.duration_since(self.time) <= interval
+            && (position.0 - self.position.0).abs() <= radius
+            && (position.1 - self.position.1).abs() <= radius
         {
             self.streak + 1
         } else {
@@ -78,6 +87,7 @@ impl LastMouseClick {
             button,
             time: now,
             streak,
+            position,
         }
     }
 }
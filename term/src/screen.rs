@@ -29,15 +29,36 @@ pub struct Screen {
     config: Arc<dyn TerminalConfiguration>,
     allow_scrollback: bool,
 
+    /// The number of screens that share the scrollback_memory_limit,
+    /// such as the panes of a tab
+    memory_limit_share: usize,
+
     /// Physical, visible height of the screen (not including scrollback)
     pub physical_rows: usize,
     /// Physical, visible width of the screen
     pub physical_cols: usize,
 }
 
-fn scrollback_size(config: &Arc<dyn TerminalConfiguration>, allow_scrollback: bool) -> usize {
+/// Returns an estimate of the number of bytes of memory
+/// used to hold a line with the specified number of cells
+fn line_memory_size(num_cells: usize) -> usize {
+    std::mem::size_of::<Line>() + num_cells * std::mem::size_of::<Cell>()
+}
+
+fn scrollback_size(
+    config: &Arc<dyn TerminalConfiguration>,
+    allow_scrollback: bool,
+    physical_cols: usize,
+    memory_limit_share: usize,
+) -> usize {
     if allow_scrollback {
-        config.scrollback_size()
+        let lines = config.scrollback_size();
+        match config.scrollback_memory_limit() {
+            Some(limit) => {
+                lines.min(limit / memory_limit_share.max(1) / line_memory_size(physical_cols))
+            }
+            None => lines,
+        }
     } else {
         0
     }
@@ -56,8 +77,9 @@ impl Screen {
         let physical_rows = physical_rows.max(1);
         let physical_cols = physical_cols.max(1);
 
-        let mut lines =
-            VecDeque::with_capacity(physical_rows + scrollback_size(config, allow_scrollback));
+        let mut lines = VecDeque::with_capacity(
+            physical_rows + scrollback_size(config, allow_scrollback, physical_cols, 1),
+        );
         for _ in 0..physical_rows {
             lines.push_back(Line::with_width(physical_cols));
         }
@@ -66,6 +88,7 @@ impl Screen {
             lines,
             config: Arc::clone(config),
             allow_scrollback,
+            memory_limit_share: 1,
             physical_rows,
             physical_cols,
             stable_row_index_offset: 0,
@@ -73,7 +96,27 @@ impl Screen {
     }
 
     fn scrollback_size(&self) -> usize {
-        scrollback_size(&self.config, self.allow_scrollback)
+        scrollback_size(
+            &self.config,
+            self.allow_scrollback,
+            self.physical_cols,
+            self.memory_limit_share,
+        )
+    }
+
+    /// Divides the scrollback_memory_limit between `share` screens.
+    /// Excess scrollback is discarded as new lines are added.
+    pub fn set_memory_limit_share(&mut self, share: usize) {
+        self.memory_limit_share = share.max(1);
+    }
+
+    /// Returns an estimate of the number of bytes of memory used to
+    /// hold the lines of the screen and its scrollback
+    pub fn memory_usage(&self) -> usize {
+        self.lines
+            .iter()
+            .map(|line| line_memory_size(line.cells().len()))
+            .sum()
     }
 
     fn rewrap_lines(
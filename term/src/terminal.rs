@@ -47,6 +47,23 @@ pub enum Alert {
         /// window/tab/pane that generated it
         focus: bool,
     },
+    /// The title, as returned by TerminalState::get_title,
+    /// was changed by the application
+    TitleChanged(String),
+    /// A user defined variable was set via the iTerm2
+    /// SetUserVar escape sequence
+    SetUserVar {
+        name: String,
+        value: String,
+    },
+    /// A command, as delimited by the semantic prompt escape
+    /// sequences emitted by the shell, has finished
+    CommandCompleted {
+        /// How long the command ran for
+        elapsed: std::time::Duration,
+        /// The exit status, if reported by the shell
+        status: Option<i32>,
+    },
 }
 
 pub trait AlertHandler {
@@ -12,9 +12,11 @@ use std::collections::HashMap;
 use std::fmt::Write;
 use std::sync::mpsc::{channel, Sender};
 use std::sync::Arc;
+use std::time::Instant;
 use termwiz::escape::csi::{
     Cursor, CursorStyle, DecPrivateMode, DecPrivateModeCode, Device, Edit, EraseInDisplay,
-    EraseInLine, Mode, Sgr, TabulationClear, TerminalMode, TerminalModeCode, Window,
+    EraseInLine, Keyboard, KittyKeyboardFlags, KittyKeyboardMode, Mode, Sgr, TabulationClear,
+    TerminalMode, TerminalModeCode, Window, XtermKeyModifierResource,
 };
 use termwiz::escape::osc::{
     ChangeColorPair, ColorOrQuery, FinalTermSemanticPrompt, ITermFileData, ITermProprietary,
@@ -113,6 +115,11 @@ struct ScreenOrAlt {
     alt_screen_is_active: bool,
     saved_cursor: Option<SavedCursor>,
     alt_saved_cursor: Option<SavedCursor>,
+    /// The kitty keyboard flags pushed by applications; the last
+    /// entry holds the flags that are currently in effect.
+    /// The main and alternate screens each have their own stack.
+    keyboard_stack: Vec<KittyKeyboardFlags>,
+    alt_keyboard_stack: Vec<KittyKeyboardFlags>,
 }
 
 impl Deref for ScreenOrAlt {
@@ -152,6 +159,8 @@ impl ScreenOrAlt {
             alt_screen_is_active: false,
             saved_cursor: None,
             alt_saved_cursor: None,
+            keyboard_stack: vec![],
+            alt_keyboard_stack: vec![],
         }
     }
 
@@ -195,6 +204,15 @@ impl ScreenOrAlt {
         self.alt_screen_is_active
     }
 
+    pub fn memory_usage(&self) -> usize {
+        self.screen.memory_usage() + self.alt_screen.memory_usage()
+    }
+
+    pub fn set_memory_limit_share(&mut self, share: usize) {
+        self.screen.set_memory_limit_share(share);
+        self.alt_screen.set_memory_limit_share(share);
+    }
+
     pub fn saved_cursor(&mut self) -> &mut Option<SavedCursor> {
         if self.alt_screen_is_active {
             &mut self.alt_saved_cursor
@@ -202,6 +220,23 @@ impl ScreenOrAlt {
             &mut self.saved_cursor
         }
     }
+
+    pub fn keyboard_stack(&mut self) -> &mut Vec<KittyKeyboardFlags> {
+        if self.alt_screen_is_active {
+            &mut self.alt_keyboard_stack
+        } else {
+            &mut self.keyboard_stack
+        }
+    }
+
+    pub fn kitty_keyboard_flags(&self) -> KittyKeyboardFlags {
+        let stack = if self.alt_screen_is_active {
+            &self.alt_keyboard_stack
+        } else {
+            &self.keyboard_stack
+        };
+        stack.last().copied().unwrap_or(KittyKeyboardFlags::NONE)
+    }
 }
 
 /// Manages the state for the terminal
@@ -259,6 +294,10 @@ pub struct TerminalState {
     /// in the numeric keypad portion of the keyboard.
     application_keypad: bool,
 
+    /// The xterm modifyOtherKeys level requested via `CSI > 4 ; n m`.
+    /// When set, modified keys are reported as `CSI 27 ; mods ; code ~`
+    modify_other_keys: Option<i64>,
+
     /// When set, pasting the clipboard should bracket the data with
     /// designated marker characters.
     bracketed_paste: bool,
@@ -279,6 +318,8 @@ pub struct TerminalState {
 
     /// The terminal title string (OSC 2)
     title: String,
+    /// Whether the application has set `title`
+    title_was_set: bool,
     /// The icon title string (OSC 1)
     icon_title: Option<String>,
 
@@ -293,6 +334,13 @@ pub struct TerminalState {
 
     current_dir: Option<Url>,
 
+    /// User defined variables set via the iTerm2 SetUserVar escape
+    user_vars: HashMap<String, String>,
+
+    /// When the output of the current command started, as
+    /// marked by the semantic prompt escape sequences
+    command_started: Option<Instant>,
+
     term_program: String,
     term_version: String,
 
@@ -301,6 +349,19 @@ pub struct TerminalState {
     image_cache: lru::LruCache<[u8; 32], Arc<ImageData>>,
 }
 
+/// The kitty keyboard enhancements that we implement.
+/// We only see key presses, so REPORT_EVENT_TYPES can't be honored.
+const KITTY_KEYBOARD_SUPPORTED: KittyKeyboardFlags = KittyKeyboardFlags::from_bits_truncate(
+    KittyKeyboardFlags::DISAMBIGUATE_ESCAPE_CODES.bits()
+        | KittyKeyboardFlags::REPORT_ALTERNATE_KEYS.bits()
+        | KittyKeyboardFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES.bits()
+        | KittyKeyboardFlags::REPORT_ASSOCIATED_TEXT.bits(),
+);
+
+/// Limits how many entries an application can push onto the
+/// kitty keyboard stack; the oldest entries are discarded
+const KITTY_KEYBOARD_STACK_LIMIT: usize = 128;
+
 fn encode_modifiers(mods: KeyModifiers) -> u8 {
     let mut number = 0;
     if mods.contains(KeyModifiers::SHIFT) {
@@ -315,6 +376,252 @@ fn encode_modifiers(mods: KeyModifiers) -> u8 {
     number
 }
 
+/// Encodes modifiers as the kitty keyboard protocol expects; unlike
+/// `encode_modifiers` this includes SUPER
+fn kitty_encode_modifiers(mods: KeyModifiers) -> u8 {
+    let mut number = encode_modifiers(mods);
+    if mods.contains(KeyModifiers::SUPER) {
+        number |= 8;
+    }
+    number
+}
+
+/// How the kitty keyboard protocol represents a non-text key
+enum KittyFunctionalKey {
+    /// `CSI number ; modifiers ~`
+    Tilde(u32),
+    /// `CSI 1 ; modifiers letter`, or `CSI letter` when unmodified
+    Letter(char),
+    /// `CSI number ; modifiers u`
+    Unicode(u32),
+}
+
+fn kitty_functional_key(key: KeyCode) -> Option<KittyFunctionalKey> {
+    use crate::KeyCode::*;
+    use KittyFunctionalKey::*;
+    Some(match key {
+        Escape => Unicode(27),
+        Enter => Unicode(13),
+        Tab => Unicode(9),
+        Backspace => Unicode(127),
+        Insert => Tilde(2),
+        Delete => Tilde(3),
+        PageUp => Tilde(5),
+        PageDown => Tilde(6),
+        UpArrow | ApplicationUpArrow => Letter('A'),
+        DownArrow | ApplicationDownArrow => Letter('B'),
+        RightArrow | ApplicationRightArrow => Letter('C'),
+        LeftArrow | ApplicationLeftArrow => Letter('D'),
+        Home => Letter('H'),
+        End => Letter('F'),
+        Function(1) => Letter('P'),
+        Function(2) => Letter('Q'),
+        // `CSI 1 ; modifiers R` would be confused with a cursor
+        // position report, so F3 uses the tilde form
+        Function(3) => Tilde(13),
+        Function(4) => Letter('S'),
+        Function(5) => Tilde(15),
+        Function(6) => Tilde(17),
+        Function(7) => Tilde(18),
+        Function(8) => Tilde(19),
+        Function(9) => Tilde(20),
+        Function(10) => Tilde(21),
+        Function(11) => Tilde(23),
+        Function(12) => Tilde(24),
+        Function(n) if n >= 13 && n <= 35 => Unicode(57376 + u32::from(n) - 13),
+        Numpad0 => Unicode(57399),
+        Numpad1 => Unicode(57400),
+        Numpad2 => Unicode(57401),
+        Numpad3 => Unicode(57402),
+        Numpad4 => Unicode(57403),
+        Numpad5 => Unicode(57404),
+        Numpad6 => Unicode(57405),
+        Numpad7 => Unicode(57406),
+        Numpad8 => Unicode(57407),
+        Numpad9 => Unicode(57408),
+        Decimal => Unicode(57409),
+        Divide => Unicode(57410),
+        Multiply => Unicode(57411),
+        Subtract => Unicode(57412),
+        Add => Unicode(57413),
+        Separator => Unicode(57416),
+        MediaPlayPause => Unicode(57430),
+        MediaStop => Unicode(57432),
+        MediaNextTrack => Unicode(57435),
+        MediaPrevTrack => Unicode(57436),
+        VolumeDown => Unicode(57438),
+        VolumeUp => Unicode(57439),
+        VolumeMute => Unicode(57440),
+        _ => return None,
+    })
+}
+
+/// The codes for the lock and modifier keys, which are only reported
+/// when REPORT_ALL_KEYS_AS_ESCAPE_CODES is in effect
+fn kitty_modifier_key(key: KeyCode) -> Option<u32> {
+    use crate::KeyCode::*;
+    Some(match key {
+        CapsLock => 57358,
+        ScrollLock => 57359,
+        NumLock => 57360,
+        PrintScreen => 57361,
+        Pause => 57362,
+        Applications => 57363,
+        Shift | LeftShift => 57441,
+        Control | LeftControl => 57442,
+        Alt | LeftAlt | Menu | LeftMenu => 57443,
+        Super | LeftWindows => 57444,
+        Hyper => 57445,
+        Meta => 57446,
+        RightShift => 57447,
+        RightControl => 57448,
+        RightAlt | RightMenu => 57449,
+        RightWindows => 57450,
+        _ => return None,
+    })
+}
+
+/// Encodes a key press according to the kitty keyboard protocol
+/// flags that are in effect.
+/// Returns None when the legacy encoding should be used instead.
+/// <https://sw.kovidgoyal.net/kitty/keyboard-protocol/>
+pub(crate) fn kitty_encode_key(
+    key: KeyCode,
+    mods: KeyModifiers,
+    flags: KittyKeyboardFlags,
+) -> Option<String> {
+    use crate::KeyCode::*;
+
+    let all_keys = flags.contains(KittyKeyboardFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES);
+    let key = match key {
+        Char('\x7f') => Delete,
+        Char('\x08') => Backspace,
+        key => key,
+    };
+
+    if let Char(c) = key {
+        // Keys are identified by their unshifted codepoint, with
+        // SHIFT in the modifiers.  We only know the unshifted form of
+        // ascii letters; other shifted symbols are reported as-is, as
+        // the legacy encoding does.
+        let (code, shifted, mods) = if c.is_ascii_uppercase() {
+            (c.to_ascii_lowercase(), Some(c), mods | KeyModifiers::SHIFT)
+        } else if c.is_ascii_lowercase() && mods.contains(KeyModifiers::SHIFT) {
+            (c, Some(c.to_ascii_uppercase()), mods)
+        } else if c.is_ascii_punctuation() {
+            (c, None, mods - KeyModifiers::SHIFT)
+        } else {
+            (c, None, mods)
+        };
+        let text = shifted.unwrap_or(code);
+        let produces_text =
+            !mods.intersects(KeyModifiers::CTRL | KeyModifiers::ALT | KeyModifiers::SUPER);
+
+        if produces_text && !all_keys {
+            return Some(text.to_string());
+        }
+
+        let mut buf = format!("\x1b[{}", code as u32);
+        if let Some(shifted) = shifted {
+            if flags.contains(KittyKeyboardFlags::REPORT_ALTERNATE_KEYS) {
+                write!(buf, ":{}", shifted as u32).ok();
+            }
+        }
+        let encoded_mods = kitty_encode_modifiers(mods);
+        if produces_text && flags.contains(KittyKeyboardFlags::REPORT_ASSOCIATED_TEXT) {
+            write!(buf, ";{};{}", 1 + encoded_mods, text as u32).ok();
+        } else if encoded_mods != 0 {
+            write!(buf, ";{}", 1 + encoded_mods).ok();
+        }
+        buf.push('u');
+        return Some(buf);
+    }
+
+    let encoded_mods = kitty_encode_modifiers(mods);
+    let functional = match kitty_functional_key(key) {
+        Some(functional) => functional,
+        None if all_keys => KittyFunctionalKey::Unicode(kitty_modifier_key(key)?),
+        None => return None,
+    };
+
+    if !all_keys && encoded_mods == 0 {
+        // Unmodified keys keep their legacy encoding so that eg: the
+        // arrow keys still respect DECCKM.  The exceptions are Escape,
+        // which is otherwise ambiguous, and keys that have no legacy
+        // encoding, such as those on the keypad.
+        let keep_legacy = match functional {
+            KittyFunctionalKey::Unicode(_) => match key {
+                Enter | Tab | Backspace => true,
+                _ => false,
+            },
+            _ => true,
+        };
+        if keep_legacy {
+            return None;
+        }
+    }
+
+    Some(match functional {
+        KittyFunctionalKey::Tilde(n) if encoded_mods == 0 => format!("\x1b[{}~", n),
+        KittyFunctionalKey::Tilde(n) => format!("\x1b[{};{}~", n, 1 + encoded_mods),
+        KittyFunctionalKey::Letter(c) if encoded_mods == 0 => format!("\x1b[{}", c),
+        KittyFunctionalKey::Letter(c) => format!("\x1b[1;{}{}", 1 + encoded_mods, c),
+        KittyFunctionalKey::Unicode(n) if encoded_mods == 0 => format!("\x1b[{}u", n),
+        KittyFunctionalKey::Unicode(n) => format!("\x1b[{};{}u", n, 1 + encoded_mods),
+    })
+}
+
+/// Encodes a key press as `CSI 27 ; modifiers ; code ~` according to
+/// the xterm modifyOtherKeys `level`.
+/// Returns None when the usual encoding should be used instead.
+/// <https://invisible-island.net/xterm/modified-keys.html>
+pub(crate) fn modify_other_keys_encode(
+    key: KeyCode,
+    mods: KeyModifiers,
+    level: i64,
+) -> Option<String> {
+    use crate::KeyCode::*;
+
+    let key = key.normalize_shift_to_upper_case(mods);
+    let (code, special) = match key {
+        // Backspace sends DEL, as it does in the usual encoding
+        Char('\x08') | Backspace => (127, true),
+        Char('\x7f') => return None,
+        Enter => (13, true),
+        Tab => (9, true),
+        Escape => (27, true),
+        Char(c) => (c as u32, false),
+        _ => return None,
+    };
+    let encoded_mods = kitty_encode_modifiers(mods);
+    if encoded_mods == 0 {
+        return None;
+    }
+
+    let report = if level >= 2 {
+        // Everything except plain shifted text is reported
+        special || mods - KeyModifiers::SHIFT != KeyModifiers::NONE
+    } else {
+        // Only keys whose usual encoding would lose the modifiers
+        match key {
+            Tab => mods != KeyModifiers::SHIFT && mods != KeyModifiers::ALT,
+            _ if special => mods != KeyModifiers::ALT,
+            _ if mods.contains(KeyModifiers::SUPER) => true,
+            Char(c) if mods.contains(KeyModifiers::CTRL) => match c {
+                'a'..='z' | '@' | '[' | '\\' | ']' | '^' | '_' | ' ' => false,
+                _ => true,
+            },
+            _ => false,
+        }
+    };
+
+    if report {
+        Some(format!("\x1b[27;{};{}~", 1 + encoded_mods, code))
+    } else {
+        None
+    }
+}
+
 /// characters that when masked for CTRL could be an ascii control character
 /// or could be a key that a user legitimately wants to process in their
 /// terminal application
@@ -417,6 +724,7 @@ impl TerminalState {
             use_private_color_registers_for_each_graphic: false,
             color_map,
             application_keypad: false,
+            modify_other_keys: None,
             bracketed_paste: false,
             focus_tracking: false,
             sgr_mouse: false,
@@ -428,6 +736,7 @@ impl TerminalState {
             current_mouse_button: MouseButton::None,
             tabs: TabStop::new(size.physical_cols, 8),
             title: "wezterm".to_string(),
+            title_was_set: false,
             icon_title: None,
             palette: None,
             pixel_height: size.pixel_height,
@@ -436,6 +745,8 @@ impl TerminalState {
             device_control_handler: None,
             alert_handler: None,
             current_dir: None,
+            user_vars: HashMap::new(),
+            command_started: None,
             term_program: term_program.to_string(),
             term_version: term_version.to_string(),
             writer: Box::new(std::io::BufWriter::new(writer)),
@@ -476,6 +787,12 @@ impl TerminalState {
         self.icon_title.as_ref().unwrap_or(&self.title)
     }
 
+    /// Returns true if the application has set a title, rather
+    /// than `get_title` returning the default title
+    pub fn has_title(&self) -> bool {
+        self.icon_title.is_some() || self.title_was_set
+    }
+
     /// Returns the current working directory associated with the
     /// terminal session.  The working directory can be changed by
     /// the applicaiton using the OSC 7 escape sequence.
@@ -483,6 +800,46 @@ impl TerminalState {
         self.current_dir.as_ref()
     }
 
+    /// Returns an estimate of the number of bytes of memory used
+    /// to hold the screen and scrollback content
+    pub fn memory_usage(&self) -> usize {
+        self.screen.memory_usage()
+    }
+
+    /// Sets the number of terminals, such as the panes of a tab,
+    /// between which the scrollback_memory_limit is divided
+    pub fn set_scrollback_memory_share(&mut self, share: usize) {
+        self.screen.set_memory_limit_share(share);
+    }
+
+    /// Returns the user defined variables that were set by the
+    /// application using the iTerm2 SetUserVar escape sequence.
+    pub fn user_vars(&self) -> &HashMap<String, String> {
+        &self.user_vars
+    }
+
+    /// Assigns a user defined variable, as though the application
+    /// had used the iTerm2 SetUserVar escape sequence.
+    pub fn set_user_var(&mut self, name: String, value: String) {
+        self.user_vars.insert(name.clone(), value.clone());
+        if let Some(handler) = self.alert_handler.as_mut() {
+            handler.alert(Alert::SetUserVar { name, value });
+        }
+    }
+
+    /// Called when the shell indicates that the current command has
+    /// finished, either explicitly or by starting the next prompt
+    fn command_completed(&mut self, status: Option<i32>) {
+        if let Some(started) = self.command_started.take() {
+            if let Some(handler) = self.alert_handler.as_mut() {
+                handler.alert(Alert::CommandCompleted {
+                    elapsed: started.elapsed(),
+                    status,
+                });
+            }
+        }
+    }
+
     /// Returns a copy of the palette.
     /// By default we don't keep a copy in the terminal state,
     /// preferring to take the config values from the users
@@ -737,15 +1094,24 @@ impl TerminalState {
         }
     }
 
+    /// Discards the scrollback and clears the viewport, except for
+    /// the line holding the cursor, which becomes the first line.
     pub fn erase_scrollback_and_viewport(&mut self) {
         self.erase_in_display(EraseInDisplay::EraseScrollback);
+        self.erase_viewport();
+    }
 
+    /// Clears the viewport, except for the line holding the cursor,
+    /// which becomes the first line.  The scrollback is retained.
+    pub fn erase_viewport(&mut self) {
         let row_index = self.screen.phys_row(self.cursor.y);
-        let row = self.screen.lines[row_index].clone();
+        let mut row = self.screen.lines[row_index].clone();
 
         self.erase_in_display(EraseInDisplay::EraseDisplay);
 
-        self.screen.lines[0] = row;
+        row.set_dirty();
+        let top = self.screen.phys_row(0);
+        self.screen.lines[top] = row;
 
         self.cursor.y = 0;
     }
@@ -768,6 +1134,12 @@ impl TerminalState {
         self.screen.is_alt_screen_active()
     }
 
+    /// Returns the kitty keyboard protocol enhancements that the
+    /// application has enabled for the active screen
+    pub fn kitty_keyboard_flags(&self) -> KittyKeyboardFlags {
+        self.screen.kitty_keyboard_flags()
+    }
+
     /// Returns true if the associated application has enabled
     /// bracketed paste mode, which can be helpful to the hosting
     /// GUI application to decide about fragmenting a large paste.
@@ -868,6 +1240,23 @@ impl TerminalState {
     pub fn key_down(&mut self, key: KeyCode, mods: KeyModifiers) -> Result<(), Error> {
         use crate::KeyCode::*;
 
+        let flags = self.screen.kitty_keyboard_flags();
+        if !flags.is_empty() && self.config.enable_kitty_keyboard() {
+            if let Some(encoded) = kitty_encode_key(key, mods, flags) {
+                self.writer.write_all(encoded.as_bytes())?;
+                self.writer.flush()?;
+                return Ok(());
+            }
+        }
+
+        if let Some(level) = self.modify_other_keys {
+            if let Some(encoded) = modify_other_keys_encode(key, mods, level) {
+                self.writer.write_all(encoded.as_bytes())?;
+                self.writer.flush()?;
+                return Ok(());
+            }
+        }
+
         let key = key.normalize_shift_to_upper_case(mods);
         // Normalize the modifier state for Char's that are uppercase; remove
         // the SHIFT modifier so that reduce ambiguity below
@@ -1942,6 +2331,18 @@ impl TerminalState {
                 log::warn!("unhandled TerminalMode {:?}", m);
             }
 
+            Mode::XtermKeyMode {
+                resource: XtermKeyModifierResource::OtherKeys,
+                value,
+            } => {
+                // Omitting the value, or setting it to 0, restores
+                // the default behavior
+                self.modify_other_keys = match value {
+                    Some(level) if level > 0 => Some(level),
+                    _ => None,
+                };
+            }
+
             Mode::XtermKeyMode { resource, value } => {
                 log::warn!("unhandled XtermKeyMode {:?} {:?}", resource, value);
             }
@@ -1993,6 +2394,46 @@ impl TerminalState {
         checksum
     }
 
+    fn perform_csi_keyboard(&mut self, keyboard: Keyboard) {
+        if !self.config.enable_kitty_keyboard() {
+            // Not responding to the query tells the application
+            // that the protocol isn't supported
+            log::debug!("ignoring {:?} as enable_kitty_keyboard is false", keyboard);
+            return;
+        }
+        match keyboard {
+            Keyboard::QueryKittySupport => {
+                let flags = self.screen.kitty_keyboard_flags();
+                let response = Keyboard::ReportKittyState(flags);
+                write!(self.writer, "{}", CSI::Keyboard(response)).ok();
+                self.writer.flush().ok();
+            }
+            Keyboard::PushKittyState(flags) => {
+                let stack = self.screen.keyboard_stack();
+                if stack.len() >= KITTY_KEYBOARD_STACK_LIMIT {
+                    stack.remove(0);
+                }
+                stack.push(flags & KITTY_KEYBOARD_SUPPORTED);
+            }
+            Keyboard::PopKittyState(n) => {
+                let stack = self.screen.keyboard_stack();
+                let len = stack.len().saturating_sub(n as usize);
+                stack.truncate(len);
+            }
+            Keyboard::SetKittyState { flags, mode } => {
+                let flags = flags & KITTY_KEYBOARD_SUPPORTED;
+                let stack = self.screen.keyboard_stack();
+                let current = stack.pop().unwrap_or(KittyKeyboardFlags::NONE);
+                stack.push(match mode {
+                    KittyKeyboardMode::AssignAll => flags,
+                    KittyKeyboardMode::SetSpecified => current | flags,
+                    KittyKeyboardMode::ClearSpecified => current - flags,
+                });
+            }
+            Keyboard::ReportKittyState(_) => log::warn!("unhandled {:?}", keyboard),
+        }
+    }
+
     fn perform_csi_window(&mut self, window: Window) {
         match window {
             Window::ReportTextAreaSizeCells => {
@@ -3008,6 +3449,7 @@ impl<'a> Performer<'a> {
             CSI::Device(dev) => self.state.perform_device(*dev),
             CSI::Mouse(mouse) => error!("mouse report sent by app? {:?}", mouse),
             CSI::Window(window) => self.state.perform_csi_window(window),
+            CSI::Keyboard(keyboard) => self.state.perform_csi_keyboard(keyboard),
             CSI::Unspecified(unspec) => {
                 log::warn!("unknown unspecified CSI: {:?}", format!("{}", unspec))
             }
@@ -3082,6 +3524,7 @@ impl<'a> Performer<'a> {
                 self.sixel_scrolling = true;
                 self.dec_ansi_mode = false;
                 self.application_keypad = false;
+                self.modify_other_keys = None;
                 self.bracketed_paste = false;
                 self.focus_tracking = false;
                 self.sgr_mouse = false;
@@ -3095,6 +3538,9 @@ impl<'a> Performer<'a> {
                 self.top_and_bottom_margins = 0..self.screen().physical_rows as VisibleRowIndex;
                 self.left_and_right_margins = 0..self.screen().physical_cols;
 
+                self.screen.keyboard_stack.clear();
+                self.screen.alt_keyboard_stack.clear();
+
                 self.screen.activate_primary_screen();
                 self.erase_in_display(EraseInDisplay::EraseScrollback);
                 self.erase_in_display(EraseInDisplay::EraseDisplay);
@@ -3106,6 +3552,17 @@ impl<'a> Performer<'a> {
 
     fn osc_dispatch(&mut self, osc: OperatingSystemCommand) {
         self.flush_print();
+        let prior_title = self.get_title().to_string();
+        self.osc_dispatch_impl(osc);
+        if let Some(handler) = self.alert_handler.as_mut() {
+            let title = self.icon_title.as_ref().unwrap_or(&self.title);
+            if *title != prior_title {
+                handler.alert(Alert::TitleChanged(title.to_string()));
+            }
+        }
+    }
+
+    fn osc_dispatch_impl(&mut self, osc: OperatingSystemCommand) {
         match osc {
             OperatingSystemCommand::SetIconNameSun(title)
             | OperatingSystemCommand::SetIconName(title) => {
@@ -3118,11 +3575,13 @@ impl<'a> Performer<'a> {
             OperatingSystemCommand::SetIconNameAndWindowTitle(title) => {
                 self.icon_title.take();
                 self.title = title.clone();
+                self.title_was_set = true;
             }
 
             OperatingSystemCommand::SetWindowTitleSun(title)
             | OperatingSystemCommand::SetWindowTitle(title) => {
                 self.title = title.clone();
+                self.title_was_set = true;
             }
             OperatingSystemCommand::SetHyperlink(link) => {
                 self.set_hyperlink(link);
@@ -3150,6 +3609,9 @@ impl<'a> Performer<'a> {
             }
             OperatingSystemCommand::ITermProprietary(iterm) => match iterm {
                 ITermProprietary::File(image) => self.set_image(*image),
+                ITermProprietary::SetUserVar { name, value } => {
+                    self.set_user_var(name, value);
+                }
                 _ => log::warn!("unhandled iterm2: {:?}", iterm),
             },
 
@@ -3159,17 +3621,20 @@ impl<'a> Performer<'a> {
             OperatingSystemCommand::FinalTermSemanticPrompt(
                 FinalTermSemanticPrompt::FreshLineAndStartPrompt { .. },
             ) => {
+                self.command_completed(None);
                 self.fresh_line();
                 self.pen.set_semantic_type(SemanticType::Prompt);
             }
             OperatingSystemCommand::FinalTermSemanticPrompt(
                 FinalTermSemanticPrompt::StartPrompt(_),
             ) => {
+                self.command_completed(None);
                 self.pen.set_semantic_type(SemanticType::Prompt);
             }
             OperatingSystemCommand::FinalTermSemanticPrompt(
                 FinalTermSemanticPrompt::MarkEndOfCommandWithFreshLine { .. },
             ) => {
+                self.command_completed(None);
                 self.fresh_line();
                 self.pen.set_semantic_type(SemanticType::Prompt);
             }
@@ -3182,11 +3647,14 @@ impl<'a> Performer<'a> {
                 FinalTermSemanticPrompt::MarkEndOfInputAndStartOfOutput { .. },
             ) => {
                 self.pen.set_semantic_type(SemanticType::Output);
+                self.command_started = Some(Instant::now());
             }
 
             OperatingSystemCommand::FinalTermSemanticPrompt(
-                FinalTermSemanticPrompt::CommandStatus { .. },
-            ) => {}
+                FinalTermSemanticPrompt::CommandStatus { status, .. },
+            ) => {
+                self.command_completed(Some(status));
+            }
 
             OperatingSystemCommand::FinalTermSemanticPrompt(ft) => {
                 log::warn!("unhandled: {:?}", ft);
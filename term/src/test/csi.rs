@@ -192,3 +192,21 @@ fn test_ed_erase_scrollback() {
     term.print("b");
     assert_all_contents(&term, file!(), line!(), &["111", "222", "ab "]);
 }
+
+#[test]
+fn test_erase_viewport() {
+    let mut term = TestTerm::new(3, 3, 3);
+    term.print("abc\r\ndef\r\nghi\r\n111\r\n222\r\na");
+    term.erase_viewport();
+    assert_all_contents(
+        &term,
+        file!(),
+        line!(),
+        &["abc", "def", "ghi", "a  ", "   ", "   "],
+    );
+    term.print("b");
+    assert_visible_contents(&term, file!(), line!(), &["ab ", "   ", "   "]);
+
+    term.erase_scrollback_and_viewport();
+    assert_all_contents(&term, file!(), line!(), &["ab ", "   ", "   "]);
+}
@@ -0,0 +1,185 @@
+//! Testing the kitty keyboard protocol and xterm modifyOtherKeys
+
+use super::*;
+use crate::terminalstate::kitty_encode_key;
+use termwiz::escape::csi::KittyKeyboardFlags;
+
+const DISAMBIGUATE: KittyKeyboardFlags = KittyKeyboardFlags::DISAMBIGUATE_ESCAPE_CODES;
+
+fn encode(key: KeyCode, mods: KeyModifiers, flags: KittyKeyboardFlags) -> Option<String> {
+    kitty_encode_key(key, mods, flags)
+}
+
+#[test]
+fn test_keyboard_stack() {
+    let mut term = TestTerm::new(4, 4, 0);
+    assert_eq!(term.kitty_keyboard_flags(), KittyKeyboardFlags::NONE);
+
+    term.print("\x1b[>1u");
+    assert_eq!(term.kitty_keyboard_flags(), DISAMBIGUATE);
+    term.print("\x1b[>9u");
+    assert_eq!(
+        term.kitty_keyboard_flags(),
+        DISAMBIGUATE | KittyKeyboardFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES
+    );
+    term.print("\x1b[=8;3u");
+    assert_eq!(term.kitty_keyboard_flags(), DISAMBIGUATE);
+    term.print("\x1b[<u");
+    assert_eq!(term.kitty_keyboard_flags(), DISAMBIGUATE);
+    term.print("\x1b[<5u");
+    assert_eq!(term.kitty_keyboard_flags(), KittyKeyboardFlags::NONE);
+
+    // Event types aren't reported, so they can't be enabled
+    term.print("\x1b[=3u");
+    assert_eq!(term.kitty_keyboard_flags(), DISAMBIGUATE);
+}
+
+#[test]
+fn test_keyboard_stack_per_screen() {
+    let mut term = TestTerm::new(4, 4, 0);
+    term.print("\x1b[>1u");
+    term.set_mode("?1049", true);
+    assert_eq!(term.kitty_keyboard_flags(), KittyKeyboardFlags::NONE);
+    term.print("\x1b[>8u");
+    term.set_mode("?1049", false);
+    assert_eq!(term.kitty_keyboard_flags(), DISAMBIGUATE);
+
+    term.print("\x1bc");
+    assert_eq!(term.kitty_keyboard_flags(), KittyKeyboardFlags::NONE);
+}
+
+#[test]
+fn test_disambiguate() {
+    let none = KeyModifiers::NONE;
+    assert_eq!(
+        encode(KeyCode::Char('a'), none, DISAMBIGUATE),
+        Some("a".into())
+    );
+    assert_eq!(
+        encode(KeyCode::Char('A'), KeyModifiers::SHIFT, DISAMBIGUATE),
+        Some("A".into())
+    );
+    assert_eq!(
+        encode(KeyCode::Char('i'), KeyModifiers::CTRL, DISAMBIGUATE),
+        Some("\x1b[105;5u".into())
+    );
+    assert_eq!(
+        encode(
+            KeyCode::Char('I'),
+            KeyModifiers::CTRL | KeyModifiers::SHIFT,
+            DISAMBIGUATE
+        ),
+        Some("\x1b[105;6u".into())
+    );
+    assert_eq!(
+        encode(KeyCode::Char('x'), KeyModifiers::ALT, DISAMBIGUATE),
+        Some("\x1b[120;3u".into())
+    );
+    assert_eq!(
+        encode(KeyCode::Escape, none, DISAMBIGUATE),
+        Some("\x1b[27u".into())
+    );
+    assert_eq!(
+        encode(KeyCode::Enter, KeyModifiers::SHIFT, DISAMBIGUATE),
+        Some("\x1b[13;2u".into())
+    );
+    assert_eq!(
+        encode(KeyCode::UpArrow, KeyModifiers::ALT, DISAMBIGUATE),
+        Some("\x1b[1;3A".into())
+    );
+    assert_eq!(
+        encode(KeyCode::Function(3), KeyModifiers::CTRL, DISAMBIGUATE),
+        Some("\x1b[13;5~".into())
+    );
+    assert_eq!(
+        encode(KeyCode::Numpad1, none, DISAMBIGUATE),
+        Some("\x1b[57400u".into())
+    );
+
+    // These keep their legacy encoding
+    assert_eq!(encode(KeyCode::Enter, none, DISAMBIGUATE), None);
+    assert_eq!(encode(KeyCode::UpArrow, none, DISAMBIGUATE), None);
+    assert_eq!(encode(KeyCode::LeftShift, none, DISAMBIGUATE), None);
+}
+
+#[test]
+fn test_report_all_keys() {
+    let flags = DISAMBIGUATE | KittyKeyboardFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES;
+    let none = KeyModifiers::NONE;
+    assert_eq!(
+        encode(KeyCode::Char('a'), none, flags),
+        Some("\x1b[97u".into())
+    );
+    assert_eq!(
+        encode(KeyCode::Char('A'), KeyModifiers::SHIFT, flags),
+        Some("\x1b[97;2u".into())
+    );
+    assert_eq!(encode(KeyCode::Enter, none, flags), Some("\x1b[13u".into()));
+    assert_eq!(encode(KeyCode::UpArrow, none, flags), Some("\x1b[A".into()));
+    assert_eq!(
+        encode(KeyCode::LeftShift, KeyModifiers::SHIFT, flags),
+        Some("\x1b[57441;2u".into())
+    );
+
+    let flags = flags
+        | KittyKeyboardFlags::REPORT_ALTERNATE_KEYS
+        | KittyKeyboardFlags::REPORT_ASSOCIATED_TEXT;
+    assert_eq!(
+        encode(KeyCode::Char('A'), KeyModifiers::SHIFT, flags),
+        Some("\x1b[97:65;2;65u".into())
+    );
+    assert_eq!(
+        encode(KeyCode::Char('a'), KeyModifiers::CTRL, flags),
+        Some("\x1b[97;5u".into())
+    );
+}
+
+#[test]
+fn test_modify_other_keys() {
+    use crate::terminalstate::modify_other_keys_encode;
+
+    let ctrl = KeyModifiers::CTRL;
+    let ctrl_shift = KeyModifiers::CTRL | KeyModifiers::SHIFT;
+
+    // Level 1 only reports keys whose usual encoding is lossy
+    assert_eq!(modify_other_keys_encode(KeyCode::Char('a'), ctrl, 1), None);
+    assert_eq!(
+        modify_other_keys_encode(KeyCode::Char('a'), ctrl_shift, 1),
+        Some("\x1b[27;6;65~".into())
+    );
+    assert_eq!(
+        modify_other_keys_encode(KeyCode::Char('1'), ctrl, 1),
+        Some("\x1b[27;5;49~".into())
+    );
+    assert_eq!(
+        modify_other_keys_encode(KeyCode::Enter, ctrl, 1),
+        Some("\x1b[27;5;13~".into())
+    );
+    assert_eq!(
+        modify_other_keys_encode(KeyCode::Tab, KeyModifiers::SHIFT, 1),
+        None
+    );
+
+    // Level 2 reports everything but plain text
+    assert_eq!(
+        modify_other_keys_encode(KeyCode::Char('a'), ctrl, 2),
+        Some("\x1b[27;5;97~".into())
+    );
+    assert_eq!(
+        modify_other_keys_encode(KeyCode::Char('a'), KeyModifiers::ALT, 2),
+        Some("\x1b[27;3;97~".into())
+    );
+    assert_eq!(
+        modify_other_keys_encode(KeyCode::Tab, KeyModifiers::SHIFT, 2),
+        Some("\x1b[27;2;9~".into())
+    );
+    assert_eq!(
+        modify_other_keys_encode(KeyCode::Char('a'), KeyModifiers::SHIFT, 2),
+        None
+    );
+    assert_eq!(
+        modify_other_keys_encode(KeyCode::Char('a'), KeyModifiers::NONE, 2),
+        None
+    );
+    assert_eq!(modify_other_keys_encode(KeyCode::UpArrow, ctrl, 2), None);
+}
@@ -6,6 +6,7 @@ mod c0;
 use bitflags::bitflags;
 mod c1;
 mod csi;
+mod keyboard;
 // mod selection; FIXME: port to render layer
 use crate::color::ColorPalette;
 use pretty_assertions::assert_eq;
@@ -54,19 +55,39 @@ struct TestTerm {
 #[derive(Debug)]
 struct TestTermConfig {
     scrollback: usize,
+    memory_limit: Option<usize>,
 }
 impl TerminalConfiguration for TestTermConfig {
     fn scrollback_size(&self) -> usize {
         self.scrollback
     }
 
+    fn scrollback_memory_limit(&self) -> Option<usize> {
+        self.memory_limit
+    }
+
     fn color_palette(&self) -> ColorPalette {
         ColorPalette::default()
     }
+
+    fn enable_kitty_keyboard(&self) -> bool {
+        true
+    }
 }
 
 impl TestTerm {
     fn new(height: usize, width: usize, scrollback: usize) -> Self {
+        Self::with_config(
+            height,
+            width,
+            TestTermConfig {
+                scrollback,
+                memory_limit: None,
+            },
+        )
+    }
+
+    fn with_config(height: usize, width: usize, config: TestTermConfig) -> Self {
         let _ = pretty_env_logger::formatted_builder()
             .is_test(true)
             .filter_level(log::LevelFilter::Trace)
@@ -79,7 +100,7 @@ impl TestTerm {
                 pixel_width: width * 8,
                 pixel_height: height * 16,
             },
-            Arc::new(TestTermConfig { scrollback }),
+            Arc::new(config),
             "WezTerm",
             "O_o",
             Box::new(Vec::new()),
@@ -905,3 +926,87 @@ fn test_hyperlinks() {
         Compare::TEXT | Compare::ATTRS,
     );
 }
+
+#[test]
+fn test_user_vars() {
+    let mut term = TestTerm::new(2, 10, 0);
+    assert!(term.user_vars().is_empty());
+
+    // "prod" base64 encoded
+    term.print("\x1b]1337;SetUserVar=theme=cHJvZA==\x07");
+    assert_eq!(
+        term.user_vars().get("theme").map(String::as_str),
+        Some("prod")
+    );
+
+    // "dev" base64 encoded
+    term.print("\x1b]1337;SetUserVar=theme=ZGV2\x1b\\");
+    assert_eq!(
+        term.user_vars().get("theme").map(String::as_str),
+        Some("dev")
+    );
+    assert_visible_contents(&term, file!(), line!(), &["          ", "          "]);
+}
+
+#[test]
+fn test_has_title() {
+    let mut term = TestTerm::new(2, 10, 0);
+    assert!(!term.has_title());
+
+    // An application may set the same title as the default
+    term.print("\x1b]2;wezterm\x07");
+    assert_eq!(term.get_title(), "wezterm");
+    assert!(term.has_title());
+}
+
+#[test]
+fn test_scrollback_memory_limit() {
+    let line_size = std::mem::size_of::<Line>() + 5 * std::mem::size_of::<Cell>();
+    // Enough memory for 2 lines of scrollback, despite the
+    // line based limit allowing for 10
+    let mut term = TestTerm::with_config(
+        3,
+        5,
+        TestTermConfig {
+            scrollback: 10,
+            memory_limit: Some(2 * line_size),
+        },
+    );
+    for i in 0..8 {
+        term.print(format!("{}\r\n", i));
+    }
+    assert_all_contents(
+        &term,
+        file!(),
+        line!(),
+        &["4    ", "5    ", "6    ", "7    ", "     "],
+    );
+    // 2 lines of scrollback plus the 3 visible lines of the primary
+    // screen, and the 3 lines of the alternate screen
+    assert_eq!(term.memory_usage(), 8 * line_size);
+}
+
+#[test]
+fn test_scrollback_memory_limit_share() {
+    let line_size = std::mem::size_of::<Line>() + 5 * std::mem::size_of::<Cell>();
+    // Enough memory for 4 lines of scrollback, which is split
+    // evenly with another pane in the same tab
+    let mut term = TestTerm::with_config(
+        3,
+        5,
+        TestTermConfig {
+            scrollback: 10,
+            memory_limit: Some(4 * line_size),
+        },
+    );
+    term.set_scrollback_memory_share(2);
+    for i in 0..8 {
+        term.print(format!("{}\r\n", i));
+    }
+    assert_all_contents(
+        &term,
+        file!(),
+        line!(),
+        &["4    ", "5    ", "6    ", "7    ", "     "],
+    );
+}
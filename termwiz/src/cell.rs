@@ -6,7 +6,9 @@ use crate::image::ImageCell;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std;
 use std::mem;
-use std::sync::Arc;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
 use unicode_width::UnicodeWidthStr;
 
 /// Holds the attributes for a cell.
@@ -560,9 +562,42 @@ pub fn unicode_column_width(s: &str) -> usize {
     s.graphemes(true).map(grapheme_column_width).sum()
 }
 
+lazy_static::lazy_static! {
+    static ref WIDTH_OVERRIDES: RwLock<Vec<(RangeInclusive<u32>, usize)>> = RwLock::new(vec![]);
+}
+static HAS_WIDTH_OVERRIDES: AtomicBool = AtomicBool::new(false);
+
+/// Overrides the width computed by `grapheme_column_width` for graphemes
+/// that begin with a codepoint in one of the specified ranges.
+/// This is process-wide, and is intended to allow the embedding
+/// application to work around fonts whose metrics disagree with
+/// the unicode width of, for example, private use codepoints.
+/// Passing an empty list restores the default widths.
+pub fn set_codepoint_width_overrides(overrides: Vec<(RangeInclusive<u32>, usize)>) {
+    let mut widths = WIDTH_OVERRIDES.write().unwrap();
+    HAS_WIDTH_OVERRIDES.store(!overrides.is_empty(), Ordering::Relaxed);
+    *widths = overrides;
+}
+
+fn overridden_width(s: &str) -> Option<usize> {
+    let c = s.chars().next()? as u32;
+    WIDTH_OVERRIDES
+        .read()
+        .unwrap()
+        .iter()
+        .find(|(range, _)| range.contains(&c))
+        .map(|(_, width)| *width)
+}
+
 /// Returns the number of cells visually occupied by a grapheme.
 /// The input string must be a single grapheme.
 pub fn grapheme_column_width(s: &str) -> usize {
+    if HAS_WIDTH_OVERRIDES.load(Ordering::Relaxed) {
+        if let Some(width) = overridden_width(s) {
+            return width;
+        }
+    }
+
     // Due to this issue:
     // https://github.com/unicode-rs/unicode-width/issues/4
     // we cannot simply use the unicode-width crate to compute
@@ -648,6 +683,19 @@ mod test {
         }
     }
 
+    #[test]
+    fn width_overrides() {
+        // Use a private use codepoint so that the other tests,
+        // which may run concurrently, are unaffected
+        let icon = "\u{f0001}";
+        assert_eq!(grapheme_column_width(icon), 1);
+        set_codepoint_width_overrides(vec![(0xf0000..=0xf00ff, 2)]);
+        assert_eq!(grapheme_column_width(icon), 2);
+        assert_eq!(grapheme_column_width("a"), 1);
+        set_codepoint_width_overrides(vec![]);
+        assert_eq!(grapheme_column_width(icon), 1);
+    }
+
     #[test]
     fn test_width() {
         let foot = "\u{1f9b6}";
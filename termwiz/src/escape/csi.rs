@@ -2,6 +2,7 @@ use super::OneBased;
 use crate::cell::{Blink, Intensity, Underline};
 use crate::color::{AnsiColor, ColorSpec, RgbColor};
 use crate::input::{Modifiers, MouseButtons};
+use bitflags::bitflags;
 use num_derive::*;
 use num_traits::{FromPrimitive, ToPrimitive};
 use std::fmt::{Display, Error as FmtError, Formatter};
@@ -27,6 +28,9 @@ pub enum CSI {
 
     Window(Window),
 
+    /// The progressive keyboard enhancement protocol; see `Keyboard`
+    Keyboard(Keyboard),
+
     /// Unknown or unspecified; should be rare and is rather
     /// large, so it is boxed and kept outside of the enum
     /// body to help reduce space usage in the common cases.
@@ -78,6 +82,7 @@ impl Display for CSI {
             CSI::Mouse(mouse) => mouse.fmt(f)?,
             CSI::Device(dev) => dev.fmt(f)?,
             CSI::Window(window) => window.fmt(f)?,
+            CSI::Keyboard(k) => k.fmt(f)?,
         };
         Ok(())
     }
@@ -505,6 +510,64 @@ impl Display for Mode {
     }
 }
 
+bitflags! {
+    /// The progressive enhancements that an application may request
+    /// via the kitty keyboard protocol.
+    /// <https://sw.kovidgoyal.net/kitty/keyboard-protocol/>
+    pub struct KittyKeyboardFlags: u16 {
+        const NONE = 0;
+        const DISAMBIGUATE_ESCAPE_CODES = 1;
+        const REPORT_EVENT_TYPES = 2;
+        const REPORT_ALTERNATE_KEYS = 4;
+        const REPORT_ALL_KEYS_AS_ESCAPE_CODES = 8;
+        const REPORT_ASSOCIATED_TEXT = 16;
+    }
+}
+
+/// How `Keyboard::SetKittyState` combines its flags with the
+/// current flags
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive, ToPrimitive)]
+pub enum KittyKeyboardMode {
+    AssignAll = 1,
+    SetSpecified = 2,
+    ClearSpecified = 3,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Keyboard {
+    /// `CSI = flags ; mode u`
+    SetKittyState {
+        flags: KittyKeyboardFlags,
+        mode: KittyKeyboardMode,
+    },
+    /// `CSI > flags u`; saves the current flags on a stack
+    /// and then sets them to `flags`
+    PushKittyState(KittyKeyboardFlags),
+    /// `CSI < number u`; restores flags from the stack
+    PopKittyState(u32),
+    /// `CSI ? u`; asks the terminal for the current flags
+    QueryKittySupport,
+    /// `CSI ? flags u`; the terminal's response to `QueryKittySupport`
+    ReportKittyState(KittyKeyboardFlags),
+}
+
+impl Display for Keyboard {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        match self {
+            Self::SetKittyState { flags, mode } => write!(
+                f,
+                "={};{}u",
+                flags.bits(),
+                mode.to_u8().ok_or_else(|| FmtError)?
+            ),
+            Self::PushKittyState(flags) => write!(f, ">{}u", flags.bits()),
+            Self::PopKittyState(n) => write!(f, "<{}u", n),
+            Self::QueryKittySupport => write!(f, "?u"),
+            Self::ReportKittyState(flags) => write!(f, "?{}u", flags.bits()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DecPrivateMode {
     Code(DecPrivateModeCode),
@@ -1438,6 +1501,10 @@ impl<'a> CSIParser<'a> {
             ('m', &[b'<']) | ('M', &[b'<']) => self.mouse_sgr1006(params).map(CSI::Mouse),
             ('m', &[b'>']) => self.xterm_key_modifier(params),
 
+            ('u', &[b'=']) | ('u', &[b'>']) | ('u', &[b'<']) | ('u', &[b'?']) => {
+                self.keyboard(params).map(CSI::Keyboard)
+            }
+
             ('c', &[]) => self
                 .req_primary_device_attributes(params)
                 .map(|dev| CSI::Device(Box::new(dev))),
@@ -1544,6 +1611,52 @@ impl<'a> CSIParser<'a> {
         }
     }
 
+    fn keyboard(&mut self, params: &'a [CsiParam]) -> Result<Keyboard, ()> {
+        fn integer(params: &[CsiParam], idx: usize) -> Result<Option<i64>, ()> {
+            match params.get(idx) {
+                None => Ok(None),
+                Some(p) => p.as_integer().map(Some).ok_or(()),
+            }
+        }
+        fn flags(params: &[CsiParam]) -> Result<KittyKeyboardFlags, ()> {
+            match integer(params, 0)? {
+                None => Ok(KittyKeyboardFlags::NONE),
+                Some(bits) => {
+                    KittyKeyboardFlags::from_bits(u16::from_i64(bits).ok_or(())?).ok_or(())
+                }
+            }
+        }
+
+        let max_params = if self.intermediates == [b'='] { 2 } else { 1 };
+        if params.len() > max_params {
+            return Err(());
+        }
+
+        let result = match self.intermediates {
+            [b'='] => Keyboard::SetKittyState {
+                flags: flags(params)?,
+                mode: match integer(params, 1)? {
+                    None => KittyKeyboardMode::AssignAll,
+                    Some(mode) => FromPrimitive::from_i64(mode).ok_or(())?,
+                },
+            },
+            [b'>'] => Keyboard::PushKittyState(flags(params)?),
+            [b'<'] => Keyboard::PopKittyState(match integer(params, 0)? {
+                None => 1,
+                Some(n) => u32::from_i64(n).ok_or(())?,
+            }),
+            [b'?'] if params.is_empty() => Keyboard::QueryKittySupport,
+            [b'?'] => Keyboard::ReportKittyState(flags(params)?),
+            _ => return Err(()),
+        };
+
+        if params.is_empty() {
+            Ok(result)
+        } else {
+            Ok(self.advance_by(params.len(), params, result))
+        }
+    }
+
     fn decslrm(&mut self, params: &'a [CsiParam]) -> Result<CSI, ()> {
         if params.is_empty() {
             // with no params this is a request to save the cursor
@@ -2415,6 +2528,54 @@ mod test {
         );
     }
 
+    #[test]
+    fn kitty_keyboard() {
+        assert_eq!(
+            parse_int('u', &[1], b'>', "\x1b[>1u"),
+            vec![CSI::Keyboard(Keyboard::PushKittyState(
+                KittyKeyboardFlags::DISAMBIGUATE_ESCAPE_CODES
+            ))]
+        );
+        assert_eq!(
+            parse_int('u', &[2], b'<', "\x1b[<2u"),
+            vec![CSI::Keyboard(Keyboard::PopKittyState(2))]
+        );
+        assert_eq!(
+            parse_int('u', &[], b'<', "\x1b[<1u"),
+            vec![CSI::Keyboard(Keyboard::PopKittyState(1))]
+        );
+        assert_eq!(
+            parse_int('u', &[], b'?', "\x1b[?u"),
+            vec![CSI::Keyboard(Keyboard::QueryKittySupport)]
+        );
+        assert_eq!(
+            parse_int('u', &[9], b'?', "\x1b[?9u"),
+            vec![CSI::Keyboard(Keyboard::ReportKittyState(
+                KittyKeyboardFlags::DISAMBIGUATE_ESCAPE_CODES
+                    | KittyKeyboardFlags::REPORT_ALL_KEYS_AS_ESCAPE_CODES
+            ))]
+        );
+        assert_eq!(
+            parse_int('u', &[5, 3], b'=', "\x1b[=5;3u"),
+            vec![CSI::Keyboard(Keyboard::SetKittyState {
+                flags: KittyKeyboardFlags::DISAMBIGUATE_ESCAPE_CODES
+                    | KittyKeyboardFlags::REPORT_ALTERNATE_KEYS,
+                mode: KittyKeyboardMode::ClearSpecified,
+            })]
+        );
+        assert_eq!(
+            parse_int('u', &[1], b'=', "\x1b[=1;1u"),
+            vec![CSI::Keyboard(Keyboard::SetKittyState {
+                flags: KittyKeyboardFlags::DISAMBIGUATE_ESCAPE_CODES,
+                mode: KittyKeyboardMode::AssignAll,
+            })]
+        );
+        assert_eq!(
+            parse('u', &[], "\x1b[u"),
+            vec![CSI::Cursor(Cursor::RestoreCursor)]
+        );
+    }
+
     #[test]
     fn device_attr() {
         assert_eq!(
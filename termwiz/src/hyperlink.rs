@@ -4,7 +4,7 @@
 //! We use that as the foundation of our hyperlink support, and the game
 //! plan is to then implicitly enable the hyperlink attribute for a cell
 //! as we recognize linkable input text during print() processing.
-use crate::{ensure, format_err, Result};
+use crate::{bail, ensure, format_err, Result};
 use regex::{Captures, Regex};
 #[cfg(feature = "use_serde")]
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
@@ -148,6 +148,18 @@ pub struct Rule {
     /// with ambiguous replacement of `$11` vs `$1` in the case of
     /// more complex regexes.
     format: String,
+    /// If non-empty, the rule only applies to text in panes that
+    /// belong to one of the named domains.  termwiz itself doesn't
+    /// know about domains; the embedding application applies this
+    /// condition by way of `Rule::applies_to`.
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    domains: Vec<String>,
+    /// If non-empty, the rule only applies to text in panes whose
+    /// foreground process is one of the named programs.
+    /// The names are compared without regard to case, and with
+    /// or without their extension, so `ssh` matches `ssh.exe`.
+    #[cfg_attr(feature = "use_serde", serde(default))]
+    programs: Vec<String>,
 }
 
 #[cfg(feature = "use_serde")]
@@ -200,15 +212,36 @@ impl<'t> Match<'t> {
     /// Expand replacements in the format string to yield the URL
     /// The replacement is as described on Rule::format.
     fn expand(&self) -> String {
-        let mut result = self.rule.format.clone();
-        // Start with the highest numbered capture and decrement.
-        // This avoids ambiguity when replacing $11 vs $1.
-        for n in (0..self.captures.len()).rev() {
-            let search = format!("${}", n);
-            result = result.replace(&search, self.captures.get(n).unwrap().as_str());
-        }
-        result
+        expand(&self.rule.format, &self.captures)
+    }
+}
+
+/// Replaces the `$N` references in `format` with the text of the
+/// corresponding captures.  Capture groups that didn't participate
+/// in the match are replaced with the empty string.
+fn expand(format: &str, captures: &Captures) -> String {
+    let mut result = format.to_string();
+    // Start with the highest numbered capture and decrement.
+    // This avoids ambiguity when replacing $11 vs $1.
+    for n in (0..captures.len()).rev() {
+        let search = format!("${}", n);
+        result = result.replace(&search, captures.get(n).map_or("", |c| c.as_str()));
     }
+    result
+}
+
+/// Describes how a rule matched a span of text, including the text of
+/// each of its capture groups.  This is intended to help with writing
+/// and debugging rules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuleMatchDetail {
+    /// The span (measured in bytes) of the matched text
+    pub range: Range<usize>,
+    /// The text of each capture group, starting with `$0`.
+    /// Groups that didn't participate in the match are None.
+    pub captures: Vec<Option<String>>,
+    /// The URI that the format string expanded to
+    pub uri: String,
 }
 
 impl Rule {
@@ -217,9 +250,103 @@ impl Rule {
         Ok(Self {
             regex: Regex::new(regex)?,
             format: format.to_owned(),
+            domains: vec![],
+            programs: vec![],
         })
     }
 
+    /// Returns true if the rule applies regardless of the domain
+    /// and foreground process
+    pub fn is_unconditional(&self) -> bool {
+        self.domains.is_empty() && self.programs.is_empty()
+    }
+
+    /// Returns true if the rule only applies to some foreground processes
+    pub fn depends_on_program(&self) -> bool {
+        !self.programs.is_empty()
+    }
+
+    /// Returns true if the rule applies to text in a pane that belongs
+    /// to `domain` and whose foreground process is `program`.
+    /// None means that the domain or program is unknown, in which case
+    /// rules that are conditional on it don't apply.
+    pub fn applies_to(&self, domain: Option<&str>, program: Option<&str>) -> bool {
+        let domain_ok = self.domains.is_empty()
+            || domain.map_or(false, |domain| self.domains.iter().any(|d| d == domain));
+        let program_ok = self.programs.is_empty()
+            || program.map_or(false, |program| {
+                let stem = std::path::Path::new(program)
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .unwrap_or(program);
+                self.programs
+                    .iter()
+                    .any(|p| p.eq_ignore_ascii_case(program) || p.eq_ignore_ascii_case(stem))
+            });
+        domain_ok && program_ok
+    }
+
+    /// Returns the regex pattern for the rule
+    pub fn pattern(&self) -> &str {
+        self.regex.as_str()
+    }
+
+    /// Returns the format string for the rule
+    pub fn format(&self) -> &str {
+        &self.format
+    }
+
+    /// Checks that each `$N` in the format string refers to a capture
+    /// group that is defined by the regex.
+    pub fn validate(&self) -> Result<()> {
+        let num_captures = self.regex.captures_len();
+        let mut chars = self.format.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '$' {
+                continue;
+            }
+            let mut n = String::new();
+            while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                n.push(d);
+                chars.next();
+            }
+            if n.is_empty() {
+                continue;
+            }
+            let group: usize = n
+                .parse()
+                .map_err(|_| format_err!("invalid capture ${}", n))?;
+            if group >= num_captures {
+                bail!(
+                    "format `{}` refers to ${}, but regex `{}` only has capture groups $0-${}",
+                    self.format,
+                    group,
+                    self.regex,
+                    num_captures - 1
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the details of each match of this rule in `line`
+    pub fn match_details(&self, line: &str) -> Vec<RuleMatchDetail> {
+        self.regex
+            .captures_iter(line)
+            .map(|captures| {
+                let c0 = captures.get(0).unwrap();
+                RuleMatchDetail {
+                    range: c0.start()..c0.end(),
+                    captures: captures
+                        .iter()
+                        .map(|c| c.map(|c| c.as_str().to_string()))
+                        .collect(),
+                    uri: expand(&self.format, &captures),
+                }
+            })
+            .collect()
+    }
+
     /// Given a line of text from the terminal screen, and a set of
     /// rules, return the set of RuleMatches.
     pub fn match_hyperlinks(line: &str, rules: &[Rule]) -> Vec<RuleMatch> {
@@ -282,4 +409,53 @@ mod test {
             ]
         );
     }
+
+    #[test]
+    fn conditions() {
+        let rule = Rule::new(r"\b[A-Z]+-\d+\b", "https://jira.example.com/browse/$0").unwrap();
+        assert!(rule.is_unconditional());
+        assert!(rule.applies_to(None, None));
+
+        let rule = Rule {
+            domains: vec!["work".to_string()],
+            programs: vec!["ssh".to_string()],
+            ..rule
+        };
+        assert!(!rule.is_unconditional());
+        assert!(rule.applies_to(Some("work"), Some("ssh")));
+        assert!(rule.applies_to(Some("work"), Some("SSH.exe")));
+        assert!(!rule.applies_to(Some("local"), Some("ssh")));
+        assert!(!rule.applies_to(Some("work"), Some("zsh")));
+        assert!(!rule.applies_to(Some("work"), None));
+    }
+
+    #[test]
+    fn validate_and_detail() {
+        let rule = Rule::new(r"\b[tT](\d+)(?:/(\w+))?\b", "https://example.com/t/$1/$2").unwrap();
+        assert!(rule.validate().is_ok());
+        assert!(Rule::new(r"\bT(\d+)\b", "https://example.com/t/$2")
+            .unwrap()
+            .validate()
+            .is_err());
+
+        assert_eq!(
+            rule.match_details("see T123 and t5/foo"),
+            vec![
+                RuleMatchDetail {
+                    range: 4..8,
+                    captures: vec![Some("T123".to_string()), Some("123".to_string()), None],
+                    uri: "https://example.com/t/123/".to_string(),
+                },
+                RuleMatchDetail {
+                    range: 13..19,
+                    captures: vec![
+                        Some("t5/foo".to_string()),
+                        Some("5".to_string()),
+                        Some("foo".to_string())
+                    ],
+                    uri: "https://example.com/t/5/foo".to_string(),
+                },
+            ]
+        );
+    }
 }
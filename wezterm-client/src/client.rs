@@ -1,4 +1,5 @@
 use crate::domain::{ClientDomain, ClientDomainConfig};
+use crate::invite::Invitation;
 use crate::pane::ClientPane;
 use crate::UnixStream;
 use anyhow::{anyhow, bail, Context};
@@ -12,7 +13,8 @@ use mux::domain::{alloc_domain_id, DomainId};
 use mux::pane::PaneId;
 use mux::ssh::ssh_connect_with_ui;
 use mux::Mux;
-use openssl::ssl::{SslConnector, SslFiletype, SslMethod};
+use openssl::hash::MessageDigest;
+use openssl::ssl::{SslConnector, SslFiletype, SslMethod, SslVerifyMode};
 use openssl::x509::X509;
 use smol::channel::{bounded, unbounded, Receiver, Sender};
 use smol::prelude::*;
@@ -24,8 +26,10 @@ use std::marker::Unpin;
 use std::net::TcpStream;
 use std::path::Path;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use thiserror::Error;
 
 enum ReaderMessage {
@@ -34,6 +38,8 @@ enum ReaderMessage {
         promise: Sender<anyhow::Result<Pdu>>,
     },
     Readable,
+    /// Sent periodically so that an unresponsive server is detected
+    CheckResponsive,
 }
 
 #[derive(Clone)]
@@ -41,6 +47,11 @@ pub struct Client {
     sender: Sender<ReaderMessage>,
     local_domain_id: DomainId,
     pub is_reconnectable: bool,
+    /// The number of ClientPanes that are using this client.
+    /// This is shared with the client thread so that it can
+    /// tell whether there are panes without having to block
+    /// on the main thread.
+    num_panes: Arc<AtomicUsize>,
 }
 
 #[derive(Error, Debug, Clone, PartialEq, Eq)]
@@ -157,6 +168,13 @@ async fn process_unilateral_inner_async(
 }
 
 fn process_unilateral(local_domain_id: DomainId, decoded: DecodedPdu) -> anyhow::Result<()> {
+    if let Pdu::ServerShuttingDown(ServerShuttingDown { grace }) = decoded.pdu {
+        promise::spawn::spawn_into_main_thread(async move {
+            notify_server_shutting_down(local_domain_id, grace);
+        })
+        .detach();
+        return Ok(());
+    }
     if let Some(pane_id) = decoded.pdu.pane_id() {
         promise::spawn::spawn_into_main_thread(async move {
             process_unilateral_inner(pane_id, local_domain_id, decoded)
@@ -168,6 +186,38 @@ fn process_unilateral(local_domain_id: DomainId, decoded: DecodedPdu) -> anyhow:
     Ok(())
 }
 
+/// Surfaces the shutdown of the server to the user as a toast
+/// notification, associated with one of the panes from that server
+fn notify_server_shutting_down(local_domain_id: DomainId, grace: Duration) {
+    log::warn!(
+        "server for domain {} is shutting down; grace period is {:?}",
+        local_domain_id,
+        grace
+    );
+    let mux = match Mux::get() {
+        Some(mux) => mux,
+        None => return,
+    };
+    let domain_name = match mux.get_domain(local_domain_id) {
+        Some(domain) => domain.domain_name().to_string(),
+        None => return,
+    };
+    if let Some(pane) = mux
+        .iter_panes()
+        .into_iter()
+        .find(|pane| pane.domain_id() == local_domain_id)
+    {
+        mux.notify(mux::MuxNotification::Alert {
+            pane_id: pane.pane_id(),
+            alert: wezterm_term::Alert::ToastNotification {
+                title: Some(format!("{} is shutting down", domain_name)),
+                body: format!("Remaining panes will be closed in {}s", grace.as_secs()),
+                focus: false,
+            },
+        });
+    }
+}
+
 #[derive(Error, Debug, Clone, PartialEq, Eq)]
 enum NotReconnectableError {
     #[error("Client was destroyed")]
@@ -213,26 +263,53 @@ async fn client_thread_async(
 
     let mut stream = reconnectable.take_stream().unwrap();
 
+    // If we have been waiting on a response for longer than this,
+    // then we consider the server to be unresponsive
+    let response_timeout = reconnectable.response_timeout();
+    // When we started waiting for the next response, if any
+    let mut waiting_since: Option<Instant> = None;
+
     loop {
         let rx_msg = rx.recv();
         let wait_for_read = stream
             .wait_for_readable()
             .map(|_| Ok(ReaderMessage::Readable));
+        let check_responsive =
+            smol::Timer::after(response_timeout).map(|_| Ok(ReaderMessage::CheckResponsive));
 
-        match smol::future::or(rx_msg, wait_for_read).await {
+        match smol::future::or(smol::future::or(rx_msg, wait_for_read), check_responsive).await {
             Ok(ReaderMessage::SendPdu { pdu, promise }) => {
                 let serial = next_serial;
                 next_serial += 1;
                 promises.map.insert(serial, promise);
+                if waiting_since.is_none() {
+                    waiting_since.replace(Instant::now());
+                }
 
                 pdu.encode_async(&mut stream, serial)
                     .await
                     .context("encoding a PDU to send to the server")?;
                 stream.flush().await.context("flushing PDU to server")?;
             }
+            Ok(ReaderMessage::CheckResponsive) => {
+                if let Some(since) = waiting_since {
+                    if since.elapsed() >= response_timeout {
+                        let reason =
+                            format!("server did not respond within {:?}", response_timeout);
+                        log::error!("{}", reason);
+                        promises.fail_all(&reason);
+                        bail!("{}", reason);
+                    }
+                }
+            }
             Ok(ReaderMessage::Readable) => match Pdu::decode_async(&mut stream).await {
                 Ok(decoded) => {
                     log::trace!("decoded serial {}", decoded.serial);
+                    waiting_since = if promises.map.is_empty() {
+                        None
+                    } else {
+                        Some(Instant::now())
+                    };
                     if decoded.serial == 0 {
                         process_unilateral(local_domain_id, decoded)
                             .context("processing unilateral PDU from server")
@@ -277,7 +354,15 @@ pub fn unix_connect_with_retry(
         if iter > 0 {
             std::thread::sleep(std::time::Duration::from_millis(iter * 10));
         }
-        match UnixStream::connect(path) {
+        #[cfg(target_os = "linux")]
+        let result = match config::abstract_socket_name(path) {
+            Some(name) => config::connect_abstract_socket(name),
+            None => UnixStream::connect(path),
+        };
+        #[cfg(not(target_os = "linux"))]
+        let result = UnixStream::connect(path);
+
+        match result {
             Ok(stream) => return Ok(stream),
             Err(err) => error = err,
         }
@@ -309,6 +394,9 @@ struct Reconnectable {
     config: ClientDomainConfig,
     stream: Option<Box<dyn AsyncReadAndWrite>>,
     tls_creds: Option<GetTlsCredsResponse>,
+    /// Set when connecting required starting a new server in place
+    /// of one that had died
+    server_was_restarted: bool,
 }
 
 struct SshStream {
@@ -388,6 +476,15 @@ impl Reconnectable {
             config,
             stream,
             tls_creds: None,
+            server_was_restarted: false,
+        }
+    }
+
+    fn response_timeout(&self) -> Duration {
+        match &self.config {
+            ClientDomainConfig::Unix(unix) => unix.read_timeout,
+            ClientDomainConfig::Tls(tls) | ClientDomainConfig::Invite(tls, _) => tls.read_timeout,
+            ClientDomainConfig::Ssh(ssh) => ssh.timeout,
         }
     }
 
@@ -413,9 +510,12 @@ impl Reconnectable {
         match &self.config {
             // It doesn't make sense to reconnect to a unix socket; we only
             // get disconnected it it dies, so respawning it would not preserve
-            // the set of tabs and we'd have confusing and inconsistent state
-            ClientDomainConfig::Unix(_) => false,
+            // the set of tabs and we'd have confusing and inconsistent state.
+            // That is acceptable if the user has opted in to restarting it.
+            ClientDomainConfig::Unix(unix) => unix.restart_server_on_failure,
             ClientDomainConfig::Tls(_) => true,
+            // The invitation token can only be redeemed once
+            ClientDomainConfig::Invite(..) => false,
             // It *does* make sense to reconnect with an ssh session, but we
             // need to grow some smarts about whether the disconnect was because
             // we sent CTRL-D to close the last session, or whether it was a network
@@ -425,10 +525,22 @@ impl Reconnectable {
         }
     }
 
+    /// Returns true if the server closing the connection indicates that
+    /// it has died and that we should try to reconnect
+    fn reconnect_on_eof(&self) -> bool {
+        match &self.config {
+            ClientDomainConfig::Unix(unix) => unix.restart_server_on_failure,
+            ClientDomainConfig::Tls(_)
+            | ClientDomainConfig::Invite(..)
+            | ClientDomainConfig::Ssh(_) => false,
+        }
+    }
+
     fn connect(&mut self, initial: bool, ui: &mut ConnectionUI) -> anyhow::Result<()> {
         match self.config.clone() {
             ClientDomainConfig::Unix(unix_dom) => self.unix_connect(unix_dom, initial, ui),
             ClientDomainConfig::Tls(tls) => self.tls_connect(tls, initial, ui),
+            ClientDomainConfig::Invite(tls, invite) => self.invite_connect(tls, invite, ui),
             ClientDomainConfig::Ssh(ssh) => self.ssh_connect(ssh, initial, ui),
         }
     }
@@ -489,9 +601,14 @@ impl Reconnectable {
         let stream = match unix_connect_with_retry(&sock_path, false) {
             Ok(stream) => stream,
             Err(e) => {
-                if unix_dom.no_serve_automatically || !initial {
+                if unix_dom.no_serve_automatically
+                    || !(initial || unix_dom.restart_server_on_failure)
+                {
                     bail!("failed to connect to {}: {}", sock_path.display(), e);
                 }
+                if !initial {
+                    self.server_was_restarted = true;
+                }
                 log::error!(
                     "While connecting to {}: {}.  Will try spawning the server.",
                     sock_path.display(),
@@ -654,6 +771,65 @@ impl Reconnectable {
         Ok(())
     }
 
+    /// Connects to the TLS server named by an invitation.  No client
+    /// certificate is presented, and rather than verifying the server
+    /// certificate against a CA, it must match the fingerprint from
+    /// the invitation.  The token is redeemed once the connection
+    /// has been established.
+    fn invite_connect(
+        &mut self,
+        tls_client: TlsDomainClient,
+        invite: Invitation,
+        ui: &mut ConnectionUI,
+    ) -> anyhow::Result<()> {
+        openssl::init();
+
+        let remote_address = &invite.remote_address;
+        let mut connector = SslConnector::builder(SslMethod::tls())?;
+        connector.set_verify(SslVerifyMode::NONE);
+        let connector = connector.build();
+        let connector = connector.configure()?.verify_hostname(false);
+
+        ui.output_str(&format!(
+            "Connecting to {} using TLS with an invitation\n",
+            remote_address
+        ));
+        let stream = TcpStream::connect(remote_address)
+            .with_context(|| format!("connecting to {}", remote_address))?;
+        stream.set_nodelay(true)?;
+        stream.set_write_timeout(Some(tls_client.write_timeout))?;
+        stream.set_read_timeout(Some(tls_client.read_timeout))?;
+
+        let host_name = remote_address
+            .rsplitn(2, ':')
+            .last()
+            .unwrap_or(remote_address);
+        let stream = connector
+            .connect(host_name, stream)
+            .with_context(|| format!("SslConnector for {}", remote_address))?;
+
+        let cert = stream
+            .ssl()
+            .peer_certificate()
+            .ok_or_else(|| anyhow!("{} didn't present a certificate", remote_address))?;
+        let fingerprint: String = cert
+            .digest(MessageDigest::sha256())?
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        if fingerprint != invite.fingerprint {
+            bail!(
+                "the certificate presented by {} doesn't match the invitation",
+                remote_address
+            );
+        }
+
+        ui.output_str("TLS Connected!\n");
+        self.stream
+            .replace(Box::new(Async::new(AsyncSslStream::new(stream))?));
+        Ok(())
+    }
+
     fn try_connect(
         &mut self,
         tls_client: &TlsDomainClient,
@@ -756,6 +932,8 @@ impl Client {
     fn new(local_domain_id: DomainId, mut reconnectable: Reconnectable) -> Self {
         let is_reconnectable = reconnectable.reconnectable();
         let (sender, mut receiver) = unbounded();
+        let num_panes = Arc::new(AtomicUsize::new(0));
+        let thread_num_panes = Arc::clone(&num_panes);
 
         thread::spawn(move || {
             const BASE_INTERVAL: Duration = Duration::from_secs(1);
@@ -764,6 +942,14 @@ impl Client {
             let mut backoff = BASE_INTERVAL;
             loop {
                 if let Err(e) = client_thread(&mut reconnectable, local_domain_id, &mut receiver) {
+                    if let Some(NotReconnectableError::ClientWasDestroyed) =
+                        e.root_cause().downcast_ref::<NotReconnectableError>()
+                    {
+                        // The domain dropped the client when it was
+                        // detached, so there is nothing more to do
+                        log::debug!("client thread ended: {}", e);
+                        return;
+                    }
                     if !reconnectable.reconnectable() {
                         log::debug!("client thread ended: {}", e);
                         break;
@@ -771,9 +957,17 @@ impl Client {
 
                     if let Some(ioerr) = e.root_cause().downcast_ref::<std::io::Error>() {
                         if let std::io::ErrorKind::UnexpectedEof = ioerr.kind() {
-                            // Don't reconnect for a simple EOF
-                            log::error!("server closed connection ({})", e);
-                            break;
+                            // Don't reconnect for a simple EOF, unless the
+                            // server may have died while we still have panes
+                            // that we would like to restart it for.
+                            // When the last pane is closed the server exits
+                            // normally, and we don't want to replace it.
+                            if !reconnectable.reconnect_on_eof()
+                                || thread_num_panes.load(Ordering::SeqCst) == 0
+                            {
+                                log::error!("server closed connection ({})", e);
+                                break;
+                            }
                         }
                     }
 
@@ -795,7 +989,12 @@ impl Client {
                             Ok(_) => {
                                 backoff = BASE_INTERVAL;
                                 log::error!("Reconnected!");
+                                let restarted =
+                                    std::mem::take(&mut reconnectable.server_was_restarted);
                                 promise::spawn::spawn_into_main_thread(async move {
+                                    if restarted {
+                                        ClientDomain::forget_panes(local_domain_id).ok();
+                                    }
                                     ClientDomain::reattach(local_domain_id, ui).await.ok();
                                 })
                                 .detach();
@@ -841,6 +1040,16 @@ impl Client {
             sender,
             local_domain_id,
             is_reconnectable,
+            num_panes,
+        }
+    }
+
+    /// Records that a pane has started or stopped using this client
+    pub(crate) fn adjust_pane_count(&self, added: bool) {
+        if added {
+            self.num_panes.fetch_add(1, Ordering::SeqCst);
+        } else {
+            self.num_panes.fetch_sub(1, Ordering::SeqCst);
         }
     }
 
@@ -929,6 +1138,20 @@ impl Client {
         Ok(Self::new(local_domain_id, reconnectable))
     }
 
+    pub fn new_invite(
+        local_domain_id: DomainId,
+        tls_client: &TlsDomainClient,
+        invite: &Invitation,
+        ui: &mut ConnectionUI,
+    ) -> anyhow::Result<Self> {
+        let mut reconnectable = Reconnectable::new(
+            ClientDomainConfig::Invite(tls_client.clone(), invite.clone()),
+            None,
+        );
+        reconnectable.connect(true, ui)?;
+        Ok(Self::new(local_domain_id, reconnectable))
+    }
+
     pub fn new_ssh(
         local_domain_id: DomainId,
         ssh_dom: &SshDomain,
@@ -950,8 +1173,23 @@ impl Client {
     rpc!(ping, Ping = (), Pong);
     rpc!(list_panes, ListPanes = (), ListPanesResponse);
     rpc!(spawn, Spawn, SpawnResponse);
+    rpc!(spawn_v2, SpawnV2, SpawnResponse);
     rpc!(split_pane, SplitPane, SpawnResponse);
+    rpc!(
+        move_pane_to_new_tab,
+        MovePaneToNewTab,
+        MovePaneToNewTabResponse
+    );
+    rpc!(
+        move_pane_to_split,
+        MovePaneToSplit,
+        MovePaneToNewTabResponse
+    );
+    rpc!(move_tab_in_window, MoveTabInWindow, UnitResponse);
     rpc!(write_to_pane, WriteToPane, UnitResponse);
+    rpc!(set_pane_user_var, SetPaneUserVar, UnitResponse);
+    rpc!(set_pane_output_pipe, SetPaneOutputPipe, UnitResponse);
+    rpc!(erase_scrollback, EraseScrollbackRequest, UnitResponse);
     rpc!(send_paste, SendPaste, UnitResponse);
     rpc!(key_down, SendKeyDown, UnitResponse);
     rpc!(mouse_event, SendMouseEvent, UnitResponse);
@@ -965,6 +1203,12 @@ impl Client {
     rpc!(get_lines, GetLines, GetLinesResponse);
     rpc!(get_codec_version, GetCodecVersion, GetCodecVersionResponse);
     rpc!(get_tls_creds, GetTlsCreds = (), GetTlsCredsResponse);
+    rpc!(get_metrics, GetMetrics = (), GetMetricsResponse);
+    rpc!(shutdown_server, ShutdownServer, UnitResponse);
+    rpc!(create_invite, CreateInvite, CreateInviteResponse);
+    rpc!(redeem_invite, RedeemInvite, UnitResponse);
+    rpc!(read_file, ReadFile, ReadFileResponse);
+    rpc!(write_file, WriteFile, UnitResponse);
     rpc!(
         search_scrollback,
         SearchScrollbackRequest,
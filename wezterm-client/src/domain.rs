@@ -1,10 +1,11 @@
 use crate::client::Client;
+use crate::invite::Invitation;
 use crate::pane::ClientPane;
 use anyhow::{anyhow, bail};
 use async_trait::async_trait;
-use codec::{ListPanesResponse, Spawn, SplitPane};
+use codec::{ListPanesResponse, MoveTabInWindow, RedeemInvite, Spawn, SplitPane};
 use config::keyassignment::SpawnTabDomain;
-use config::{SshDomain, TlsDomainClient, UnixDomain};
+use config::{configuration, SshDomain, TlsDomainClient, UnixDomain};
 use mux::connui::ConnectionUI;
 use mux::domain::{alloc_domain_id, Domain, DomainId, DomainState};
 use mux::pane::{Pane, PaneId};
@@ -15,8 +16,10 @@ use portable_pty::{CommandBuilder, PtySize};
 use promise::spawn::spawn_into_new_thread;
 use std::cell::RefCell;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 pub struct ClientInner {
     pub client: Client,
@@ -25,6 +28,8 @@ pub struct ClientInner {
     remote_to_local_window: Mutex<HashMap<WindowId, WindowId>>,
     remote_to_local_tab: Mutex<HashMap<TabId, TabId>>,
     remote_to_local_pane: Mutex<HashMap<PaneId, PaneId>>,
+    /// If true, input is not sent to the panes of this client
+    pub read_only: bool,
 }
 
 impl ClientInner {
@@ -82,6 +87,14 @@ impl ClientInner {
         pane_map.remove(&remote_pane_id);
     }
 
+    /// Forgets all of the remote ids; used when the server has been
+    /// replaced and the ids that it assigns are no longer meaningful
+    fn clear_mappings(&self) {
+        self.remote_to_local_window.lock().unwrap().clear();
+        self.remote_to_local_tab.lock().unwrap().clear();
+        self.remote_to_local_pane.lock().unwrap().clear();
+    }
+
     pub fn remove_old_tab_mapping(&self, remote_tab_id: TabId) {
         let mut tab_map = self.remote_to_local_tab.lock().unwrap();
         tab_map.remove(&remote_tab_id);
@@ -113,38 +126,145 @@ pub enum ClientDomainConfig {
     Unix(UnixDomain),
     Tls(TlsDomainClient),
     Ssh(SshDomain),
+    /// A TLS connection made using an invitation from `wezterm cli invite`
+    Invite(TlsDomainClient, Invitation),
 }
 
 impl ClientDomainConfig {
+    /// Creates the configuration for connecting with an invitation.
+    /// The domain is read-only if the invitation is.
+    pub fn from_invitation(invite: Invitation) -> Self {
+        let tls = TlsDomainClient {
+            name: "invite".to_string(),
+            remote_address: invite.remote_address.clone(),
+            read_only: invite.read_only,
+            read_timeout: Duration::from_secs(60),
+            write_timeout: Duration::from_secs(60),
+            ..Default::default()
+        };
+        ClientDomainConfig::Invite(tls, invite)
+    }
+
     pub fn name(&self) -> &str {
         match self {
             ClientDomainConfig::Unix(unix) => &unix.name,
-            ClientDomainConfig::Tls(tls) => &tls.name,
+            ClientDomainConfig::Tls(tls) | ClientDomainConfig::Invite(tls, _) => &tls.name,
             ClientDomainConfig::Ssh(ssh) => &ssh.name,
         }
     }
 
     pub fn label(&self) -> String {
-        match self {
+        let label = match self {
             ClientDomainConfig::Unix(unix) => format!("unix mux {}", unix.socket_path().display()),
             ClientDomainConfig::Tls(tls) => format!("TLS mux {}", tls.remote_address),
+            ClientDomainConfig::Invite(_, invite) => {
+                format!("TLS mux invitation to {}", invite.remote_address)
+            }
             ClientDomainConfig::Ssh(ssh) => {
                 format!("SSH mux {}@{}", ssh.username, ssh.remote_address)
             }
+        };
+        if self.read_only() {
+            format!("{} (read-only)", label)
+        } else {
+            label
+        }
+    }
+
+    pub fn read_only(&self) -> bool {
+        match self {
+            ClientDomainConfig::Unix(unix) => unix.read_only,
+            ClientDomainConfig::Tls(tls) | ClientDomainConfig::Invite(tls, _) => tls.read_only,
+            ClientDomainConfig::Ssh(ssh) => ssh.read_only,
+        }
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        match self {
+            ClientDomainConfig::Unix(unix) => unix.read_only = read_only,
+            ClientDomainConfig::Tls(tls) | ClientDomainConfig::Invite(tls, _) => {
+                tls.read_only = read_only
+            }
+            ClientDomainConfig::Ssh(ssh) => ssh.read_only = read_only,
         }
     }
 
     pub fn connect_automatically(&self) -> bool {
         match self {
             ClientDomainConfig::Unix(unix) => unix.connect_automatically,
-            ClientDomainConfig::Tls(tls) => tls.connect_automatically,
+            ClientDomainConfig::Tls(tls) | ClientDomainConfig::Invite(tls, _) => {
+                tls.connect_automatically
+            }
             ClientDomainConfig::Ssh(ssh) => ssh.connect_automatically,
         }
     }
+
+    pub fn default_prog(&self) -> Option<&Vec<String>> {
+        match self {
+            ClientDomainConfig::Unix(unix) => unix.default_prog.as_ref(),
+            ClientDomainConfig::Tls(tls) | ClientDomainConfig::Invite(tls, _) => {
+                tls.default_prog.as_ref()
+            }
+            ClientDomainConfig::Ssh(ssh) => ssh.default_prog.as_ref(),
+        }
+    }
+
+    pub fn default_cwd(&self) -> Option<&PathBuf> {
+        match self {
+            ClientDomainConfig::Unix(unix) => unix.default_cwd.as_ref(),
+            ClientDomainConfig::Tls(tls) | ClientDomainConfig::Invite(tls, _) => {
+                tls.default_cwd.as_ref()
+            }
+            ClientDomainConfig::Ssh(ssh) => ssh.default_cwd.as_ref(),
+        }
+    }
+
+    /// Fills in the command and working directory from the
+    /// `default_prog` and `default_cwd` of the domain when they
+    /// were not specified by the caller
+    fn apply_defaults(
+        &self,
+        command: Option<CommandBuilder>,
+        command_dir: Option<String>,
+    ) -> (Option<CommandBuilder>, Option<String>) {
+        let command = command.or_else(|| {
+            self.default_prog()
+                .filter(|prog| !prog.is_empty())
+                .map(|prog| CommandBuilder::from_argv(prog.iter().map(Into::into).collect()))
+        });
+        // The server applies its own configuration when spawning, so pass
+        // along our set_environment_variables and the domain_environment
+        // of this domain.  Without a command, they are carried by a
+        // default_prog builder, which the server resolves to its own
+        // default_prog.
+        let config = configuration();
+        let command = if config.set_environment_variables.is_empty()
+            && !config.domain_environment.contains_key(self.name())
+        {
+            command
+        } else {
+            let mut cmd = command.unwrap_or_else(CommandBuilder::new_default_prog);
+            config.apply_environment_variables(&mut cmd);
+            config.apply_domain_environment(self.name(), &mut cmd);
+            Some(cmd)
+        };
+        let command_has_cwd = command
+            .as_ref()
+            .map(|cmd| cmd.get_cwd().is_some())
+            .unwrap_or(false);
+        let command_dir = match command_dir {
+            Some(dir) => Some(dir),
+            None if command_has_cwd => None,
+            None => self
+                .default_cwd()
+                .map(|cwd| cwd.to_string_lossy().into_owned()),
+        };
+        (command, command_dir)
+    }
 }
 
 impl ClientInner {
-    pub fn new(local_domain_id: DomainId, client: Client) -> Self {
+    pub fn new(local_domain_id: DomainId, client: Client, read_only: bool) -> Self {
         // Assumption: that the domain id on the other end is
         // always the first created default domain.  In the future
         // we'll add a way to discover/enumerate domains to populate
@@ -157,6 +277,7 @@ impl ClientInner {
             remote_to_local_window: Mutex::new(HashMap::new()),
             remote_to_local_tab: Mutex::new(HashMap::new()),
             remote_to_local_pane: Mutex::new(HashMap::new()),
+            read_only,
         }
     }
 }
@@ -226,6 +347,22 @@ impl ClientDomain {
         Ok(())
     }
 
+    /// Removes the panes that belonged to a server that has since been
+    /// replaced by a new instance, so that reattaching doesn't confuse
+    /// them with the panes of the new server, which may reuse their ids.
+    pub fn forget_panes(domain_id: DomainId) -> anyhow::Result<()> {
+        let inner = Self::get_client_inner_for_domain(domain_id)?;
+        inner.clear_mappings();
+
+        let mux = Mux::get().unwrap();
+        for pane in mux.iter_panes() {
+            if pane.domain_id() == domain_id {
+                mux.remove_pane(pane.pane_id());
+            }
+        }
+        Ok(())
+    }
+
     pub async fn resync(&self) -> anyhow::Result<()> {
         if let Some(inner) = self.inner.borrow().as_ref() {
             let panes = inner.client.list_panes().await?;
@@ -336,7 +473,11 @@ impl ClientDomain {
             .downcast_ref::<Self>()
             .ok_or_else(|| anyhow!("domain {} is not a ClientDomain", domain_id))?;
 
-        let inner = Arc::new(ClientInner::new(domain_id, client));
+        let inner = Arc::new(ClientInner::new(
+            domain_id,
+            client,
+            domain.config.read_only(),
+        ));
         *domain.inner.borrow_mut() = Some(Arc::clone(&inner));
 
         Self::process_pane_list(inner, panes)?;
@@ -369,6 +510,10 @@ impl Domain for ClientDomain {
         let inner = self
             .inner()
             .ok_or_else(|| anyhow!("domain is not attached"))?;
+        if inner.read_only {
+            bail!("domain `{}` is read-only", self.config.name());
+        }
+        let (command, command_dir) = self.config.apply_defaults(command, command_dir);
         let result = inner
             .client
             .spawn(Spawn {
@@ -410,6 +555,9 @@ impl Domain for ClientDomain {
         let inner = self
             .inner()
             .ok_or_else(|| anyhow!("domain is not attached"))?;
+        if inner.read_only {
+            bail!("domain `{}` is read-only", self.config.name());
+        }
 
         let mux = Mux::get().unwrap();
 
@@ -423,6 +571,7 @@ impl Domain for ClientDomain {
             .downcast_ref::<ClientPane>()
             .ok_or_else(|| anyhow!("pane_id {} is not a ClientPane", pane_id))?;
 
+        let (command, command_dir) = self.config.apply_defaults(command, command_dir);
         let result = inner
             .client
             .split_pane(SplitPane {
@@ -459,9 +608,58 @@ impl Domain for ClientDomain {
         Ok(pane)
     }
 
+    async fn tab_was_moved(&self, window_id: WindowId, tab_id: TabId) -> anyhow::Result<()> {
+        let inner = self
+            .inner()
+            .ok_or_else(|| anyhow!("domain is not attached"))?;
+        if inner.read_only {
+            return Ok(());
+        }
+
+        // The remote window holds only the tabs from this domain, so
+        // the position of the tab there excludes any other tabs that
+        // are in the local window
+        let mux = Mux::get().unwrap();
+        let mut remote = None;
+        {
+            let window = mux
+                .get_window(window_id)
+                .ok_or_else(|| anyhow!("window_id {} is invalid", window_id))?;
+            let mut tab_idx = 0;
+            for tab in window.iter() {
+                let pane = match tab.get_active_pane() {
+                    Some(pane) => pane,
+                    None => continue,
+                };
+                if pane.domain_id() != self.local_domain_id {
+                    continue;
+                }
+                if tab.tab_id() == tab_id {
+                    remote = pane
+                        .downcast_ref::<ClientPane>()
+                        .map(|pane| (pane.remote_pane_id, tab_idx));
+                    break;
+                }
+                tab_idx += 1;
+            }
+        }
+
+        if let Some((pane_id, tab_idx)) = remote {
+            inner
+                .client
+                .move_tab_in_window(MoveTabInWindow { pane_id, tab_idx })
+                .await?;
+        }
+        Ok(())
+    }
+
     async fn attach(&self) -> anyhow::Result<()> {
         let domain_id = self.local_domain_id;
         let config = self.config.clone();
+        let invite = match &self.config {
+            ClientDomainConfig::Invite(_, invite) => Some(invite.clone()),
+            _ => None,
+        };
 
         let activity = mux::activity::Activity::new();
         let ui = ConnectionUI::new();
@@ -477,6 +675,9 @@ impl Domain for ClientDomain {
                         Client::new_unix_domain(domain_id, unix, initial, &mut cloned_ui)
                     }
                     ClientDomainConfig::Tls(tls) => Client::new_tls(domain_id, tls, &mut cloned_ui),
+                    ClientDomainConfig::Invite(tls, invite) => {
+                        Client::new_invite(domain_id, tls, invite, &mut cloned_ui)
+                    }
                     ClientDomainConfig::Ssh(ssh) => Client::new_ssh(domain_id, ssh, &mut cloned_ui),
                 })
                 .await?;
@@ -484,6 +685,15 @@ impl Domain for ClientDomain {
                 ui.output_str("Checking server version\n");
                 client.verify_version_compat(&ui).await?;
 
+                if let Some(invite) = invite {
+                    ui.output_str("Redeeming invitation\n");
+                    client
+                        .redeem_invite(RedeemInvite {
+                            token: invite.token,
+                        })
+                        .await?;
+                }
+
                 ui.output_str("Version check OK!  Requesting pane list...\n");
                 let panes = client.list_panes().await?;
                 ui.output_str(&format!(
@@ -500,13 +710,22 @@ impl Domain for ClientDomain {
         })?;
 
         ui.output_str("Attached!\n");
+        Mux::get()
+            .expect("attach to be called on the main thread")
+            .domain_was_attached(domain_id);
         drop(activity);
         ui.close();
         Ok(())
     }
 
     fn detach(&self) -> anyhow::Result<()> {
-        bail!("detach not implemented");
+        if self.inner().is_none() {
+            bail!("domain is not attached");
+        }
+        // The panes keep running on the server, and can be
+        // picked up again by attaching to the domain
+        self.perform_detach();
+        Ok(())
     }
 
     fn state(&self) -> DomainState {
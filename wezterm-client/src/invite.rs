@@ -0,0 +1,105 @@
+//! The invitations that are printed by `wezterm cli invite` and
+//! accepted by `wezterm connect --invite`.
+use anyhow::{anyhow, bail, Context};
+use std::str::FromStr;
+use url::Url;
+
+const SCHEME: &str = "wezterm-invite";
+
+/// An invitation to attach to a multiplexer server via TLS.
+/// It has the form `wezterm-invite://HOST:PORT/TOKEN?fingerprint=HEX`,
+/// with `&read_only=1` appended if it only allows output to be viewed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invitation {
+    /// The `host:port` of the TLS server
+    pub remote_address: String,
+    /// The one-time token that is redeemed with the server
+    pub token: String,
+    /// The SHA-256 fingerprint of the server certificate, hex encoded.
+    /// The server is trusted if and only if its certificate matches.
+    pub fingerprint: String,
+    pub read_only: bool,
+}
+
+impl FromStr for Invitation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let url = Url::parse(s).with_context(|| format!("invalid invitation {}", s))?;
+        if url.scheme() != SCHEME {
+            bail!("invitation must start with {}://", SCHEME);
+        }
+        let host = url
+            .host_str()
+            .ok_or_else(|| anyhow!("invitation has no host"))?;
+        let port = url
+            .port()
+            .ok_or_else(|| anyhow!("invitation has no port"))?;
+        let token = url.path().trim_start_matches('/');
+        if token.is_empty() {
+            bail!("invitation has no token");
+        }
+
+        let mut fingerprint = None;
+        let mut read_only = false;
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "fingerprint" => fingerprint = Some(value.to_lowercase()),
+                "read_only" => read_only = value == "1" || value == "true",
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            remote_address: format!("{}:{}", host, port),
+            token: token.to_string(),
+            fingerprint: fingerprint
+                .ok_or_else(|| anyhow!("invitation has no certificate fingerprint"))?,
+            read_only,
+        })
+    }
+}
+
+impl std::fmt::Display for Invitation {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            fmt,
+            "{}://{}/{}?fingerprint={}",
+            SCHEME, self.remote_address, self.token, self.fingerprint
+        )?;
+        if self.read_only {
+            write!(fmt, "&read_only=1")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let invite = Invitation {
+            remote_address: "mux.example.com:8080".to_string(),
+            token: "0123abcd".to_string(),
+            fingerprint: "deadbeef".to_string(),
+            read_only: true,
+        };
+        let s = invite.to_string();
+        assert_eq!(
+            s,
+            "wezterm-invite://mux.example.com:8080/0123abcd?fingerprint=deadbeef&read_only=1"
+        );
+        assert_eq!(s.parse::<Invitation>().unwrap(), invite);
+
+        assert!(
+            "wezterm-invite://mux.example.com/0123abcd?fingerprint=deadbeef"
+                .parse::<Invitation>()
+                .is_err()
+        );
+        assert!("wezterm-invite://mux.example.com:8080/0123abcd"
+            .parse::<Invitation>()
+            .is_err());
+    }
+}
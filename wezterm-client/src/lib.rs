@@ -5,4 +5,5 @@ use uds_windows::UnixStream;
 
 pub mod client;
 pub mod domain;
+pub mod invite;
 pub mod pane;
@@ -5,23 +5,26 @@ use anyhow::bail;
 use async_trait::async_trait;
 use codec::*;
 use config::configuration;
+use config::keyassignment::ScrollbackEraseMode;
 use filedescriptor::Pipe;
 use mux::domain::DomainId;
 use mux::pane::{alloc_pane_id, Pane, PaneId, Pattern, SearchResult};
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::TabId;
+use mux::{Mux, MuxNotification};
 use portable_pty::PtySize;
 use rangeset::RangeSet;
 use ratelim::RateLimiter;
 use std::cell::RefCell;
 use std::cell::RefMut;
+use std::collections::HashMap;
 use std::ops::Range;
 use std::rc::Rc;
 use std::sync::Arc;
 use termwiz::input::KeyEvent;
 use url::Url;
 use wezterm_term::color::ColorPalette;
-use wezterm_term::{Clipboard, KeyCode, KeyModifiers, Line, MouseEvent, StableRowIndex};
+use wezterm_term::{Alert, Clipboard, KeyCode, KeyModifiers, Line, MouseEvent, StableRowIndex};
 
 pub struct ClientPane {
     client: Arc<ClientInner>,
@@ -34,6 +37,18 @@ pub struct ClientPane {
     mouse: Rc<RefCell<MouseState>>,
     clipboard: RefCell<Option<Arc<dyn Clipboard>>>,
     mouse_grabbed: RefCell<bool>,
+    /// Whether the server is piping the output of the pane.  This is
+    /// set when we ask it to, and then kept up to date by the server,
+    /// which stops when the command exits.
+    output_piped: RefCell<bool>,
+    /// The user vars of the pane, as last reported by the server
+    user_vars: RefCell<HashMap<String, String>>,
+}
+
+impl Drop for ClientPane {
+    fn drop(&mut self) {
+        self.client.client.adjust_pane_count(false);
+    }
 }
 
 impl ClientPane {
@@ -76,6 +91,7 @@ impl ClientPane {
         };
 
         let reader = Pipe::new().expect("Pipe::new failed");
+        client.client.adjust_pane_count(true);
 
         Self {
             client: Arc::clone(client),
@@ -88,6 +104,33 @@ impl ClientPane {
             reader,
             clipboard: RefCell::new(None),
             mouse_grabbed: RefCell::new(false),
+            output_piped: RefCell::new(false),
+            user_vars: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Records the user vars reported by the server, and raises an
+    /// alert for each one that changed, just as a local pane would
+    fn update_user_vars(&self, user_vars: &HashMap<String, String>) {
+        let changed: Vec<(String, String)> = {
+            let mut current = self.user_vars.borrow_mut();
+            let changed = user_vars
+                .iter()
+                .filter(|(name, value)| current.get(*name) != Some(*value))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect();
+            *current = user_vars.clone();
+            changed
+        };
+        if changed.is_empty() {
+            return;
+        }
+        let mux = Mux::get().unwrap();
+        for (name, value) in changed {
+            mux.notify(MuxNotification::Alert {
+                pane_id: self.local_pane_id,
+                alert: Alert::SetUserVar { name, value },
+            });
         }
     }
 
@@ -95,6 +138,8 @@ impl ClientPane {
         match pdu {
             Pdu::GetPaneRenderChangesResponse(delta) => {
                 *self.mouse_grabbed.borrow_mut() = delta.mouse_grabbed;
+                *self.output_piped.borrow_mut() = delta.output_piped;
+                self.update_user_vars(&delta.user_vars);
                 self.renderable
                     .borrow()
                     .inner
@@ -155,6 +200,9 @@ impl Pane for ClientPane {
     }
 
     fn send_paste(&self, text: &str) -> anyhow::Result<()> {
+        if self.client.read_only {
+            return Ok(());
+        }
         let client = Arc::clone(&self.client);
         let remote_pane_id = self.remote_pane_id;
         self.renderable
@@ -191,7 +239,89 @@ impl Pane for ClientPane {
         self.writer.borrow_mut()
     }
 
+    fn set_output_pipe(&self, command: Option<String>) -> anyhow::Result<()> {
+        if self.client.read_only {
+            bail!("the connection to the server is read-only");
+        }
+        *self.output_piped.borrow_mut() = command.is_some();
+        let client = Arc::clone(&self.client);
+        let remote_pane_id = self.remote_pane_id;
+        promise::spawn::spawn(async move {
+            if let Err(err) = client
+                .client
+                .set_pane_output_pipe(SetPaneOutputPipe {
+                    pane_id: remote_pane_id,
+                    command,
+                })
+                .await
+            {
+                log::error!("piping output of pane {}: {:#}", remote_pane_id, err);
+            }
+        })
+        .detach();
+        Ok(())
+    }
+
+    fn has_output_pipe(&self) -> bool {
+        *self.output_piped.borrow()
+    }
+
+    fn copy_user_vars(&self) -> HashMap<String, String> {
+        self.user_vars.borrow().clone()
+    }
+
+    fn set_user_var(&self, name: String, value: String) -> anyhow::Result<()> {
+        if self.client.read_only {
+            bail!("the connection to the server is read-only");
+        }
+        let client = Arc::clone(&self.client);
+        let remote_pane_id = self.remote_pane_id;
+        promise::spawn::spawn(async move {
+            if let Err(err) = client
+                .client
+                .set_pane_user_var(SetPaneUserVar {
+                    pane_id: remote_pane_id,
+                    name,
+                    value,
+                })
+                .await
+            {
+                log::error!("setting user var of pane {}: {:#}", remote_pane_id, err);
+            }
+        })
+        .detach();
+        Ok(())
+    }
+
+    fn erase_scrollback(&self, erase_mode: ScrollbackEraseMode) {
+        if self.client.read_only {
+            return;
+        }
+        let render = self.renderable.borrow();
+        let mut inner = render.inner.borrow_mut();
+        let client = Arc::clone(&self.client);
+        let remote_pane_id = self.remote_pane_id;
+        // The server pushes the revised lines once they have been erased
+        inner.make_all_stale();
+        promise::spawn::spawn(async move {
+            if let Err(err) = client
+                .client
+                .erase_scrollback(EraseScrollbackRequest {
+                    pane_id: remote_pane_id,
+                    erase_mode,
+                })
+                .await
+            {
+                log::error!("erasing scrollback of pane {}: {:#}", remote_pane_id, err);
+            }
+        })
+        .detach();
+    }
+
     fn set_zoomed(&self, zoomed: bool) {
+        if self.client.read_only {
+            return;
+        }
         let render = self.renderable.borrow();
         let mut inner = render.inner.borrow_mut();
         let client = Arc::clone(&self.client);
@@ -214,6 +344,11 @@ impl Pane for ClientPane {
     }
 
     fn resize(&self, size: PtySize) -> anyhow::Result<()> {
+        if self.client.read_only {
+            // Leave the remote pane at the size chosen by its
+            // other clients
+            return Ok(());
+        }
         let render = self.renderable.borrow();
         let mut inner = render.inner.borrow_mut();
 
@@ -262,6 +397,9 @@ impl Pane for ClientPane {
     }
 
     fn key_down(&self, key: KeyCode, mods: KeyModifiers) -> anyhow::Result<()> {
+        if self.client.read_only {
+            return Ok(());
+        }
         let input_serial;
         {
             let renderable = self.renderable.borrow();
@@ -295,6 +433,9 @@ impl Pane for ClientPane {
     }
 
     fn mouse_event(&self, event: MouseEvent) -> anyhow::Result<()> {
+        if self.client.read_only {
+            return Ok(());
+        }
         self.mouse.borrow_mut().append(event);
         if MouseState::next(Rc::clone(&self.mouse)) {
             self.renderable
@@ -352,6 +493,9 @@ struct PaneWriter {
 
 impl std::io::Write for PaneWriter {
     fn write(&mut self, data: &[u8]) -> Result<usize, std::io::Error> {
+        if self.client.read_only {
+            return Ok(data.len());
+        }
         promise::spawn::block_on(self.client.client.write_to_pane(WriteToPane {
             pane_id: self.remote_pane_id,
             data: data.to_vec(),
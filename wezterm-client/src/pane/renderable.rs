@@ -4,7 +4,7 @@ use anyhow::anyhow;
 use codec::*;
 use config::{configuration, ConfigHandle};
 use lru::LruCache;
-use mux::renderable::{RenderableDimensions, StableCursorPosition};
+use mux::renderable::{hyperlink_rules_for_pane, RenderableDimensions, StableCursorPosition};
 use mux::tab::TabId;
 use mux::Mux;
 use promise::BrokenPromise;
@@ -431,7 +431,13 @@ impl RenderableInner {
         config: &ConfigHandle,
         fetch_start: Option<Instant>,
     ) {
-        line.scan_and_create_hyperlinks(&config.hyperlink_rules);
+        // The foreground process of the remote pane isn't known here,
+        // so rules that depend on it don't apply
+        let rules =
+            hyperlink_rules_for_pane(&config.hyperlink_rules, self.client.local_domain_id, || {
+                None
+            });
+        line.scan_and_create_hyperlinks(&rules);
 
         let entry = if let Some(fetch_start) = fetch_start {
             // If we're completing a fetch, only replace entries that were
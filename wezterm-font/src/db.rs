@@ -10,6 +10,7 @@ use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::rc::Rc;
+use std::time::SystemTime;
 
 struct Entry {
     names: Names,
@@ -67,6 +68,37 @@ impl Entry {
     }
 }
 
+/// Captures the modification times of the configured font_dirs and
+/// their subdirectories.  Adding or removing a font changes the mtime
+/// of its directory, so if the key is unchanged then a previously
+/// built database can be reused rather than parsing every font again.
+#[derive(PartialEq)]
+struct FontDirsKey(Vec<(PathBuf, Option<SystemTime>)>);
+
+impl FontDirsKey {
+    fn new(config: &Config) -> Self {
+        let mut dirs = vec![];
+        for path in &config.font_dirs {
+            for entry in walkdir::WalkDir::new(path).into_iter() {
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(_) => continue,
+                };
+                if entry.file_type().is_dir() {
+                    let modified = entry.metadata().ok().and_then(|m| m.modified().ok());
+                    dirs.push((entry.path().to_path_buf(), modified));
+                }
+            }
+        }
+        Self(dirs)
+    }
+}
+
+thread_local! {
+    static BUILT_IN: RefCell<Option<Rc<FontDatabase>>> = RefCell::new(None);
+    static FONT_DIRS: RefCell<Option<(FontDirsKey, Rc<FontDatabase>)>> = RefCell::new(None);
+}
+
 pub struct FontDatabase {
     by_family: HashMap<String, Vec<Rc<Entry>>>,
     by_full_name: HashMap<String, Rc<Entry>>,
@@ -127,6 +159,46 @@ impl FontDatabase {
         Ok(db)
     }
 
+    /// Returns the database for the configured font dirs.
+    /// The database is shared with other windows and is reused across
+    /// config reloads for as long as the font dirs are unchanged.
+    pub fn shared_with_font_dirs(config: &Config) -> anyhow::Result<Rc<Self>> {
+        let key = FontDirsKey::new(config);
+        FONT_DIRS.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            if let Some((cached_key, db)) = cache.as_ref() {
+                if *cached_key == key {
+                    return Ok(Rc::clone(db));
+                }
+            }
+
+            let start = std::time::Instant::now();
+            let db = Rc::new(Self::with_font_dirs(config)?);
+            let elapsed = start.elapsed();
+            metrics::histogram!("font.database.font_dirs", elapsed);
+            log::debug!("font_dirs database built in {:?}", elapsed);
+
+            cache.replace((key, Rc::clone(&db)));
+            Ok(db)
+        })
+    }
+
+    /// Returns the database of built-in fonts, building it on first use.
+    /// The built-in fonts never change, so the database is shared
+    /// with other windows.
+    pub fn shared_built_in() -> anyhow::Result<Rc<Self>> {
+        BUILT_IN.with(|cache| {
+            let mut cache = cache.borrow_mut();
+            if let Some(db) = cache.as_ref() {
+                return Ok(Rc::clone(db));
+            }
+
+            let db = Rc::new(Self::with_built_in()?);
+            cache.replace(Rc::clone(&db));
+            Ok(db)
+        })
+    }
+
     pub fn print_available(&self) {
         let mut names = self.by_full_name.keys().collect::<Vec<_>>();
         names.sort();
@@ -3,7 +3,7 @@ use crate::locator::{new_locator, FontDataHandle, FontLocator};
 use crate::rasterizer::{new_rasterizer, FontRasterizer};
 use crate::shaper::{new_shaper, FontShaper};
 use anyhow::{Context, Error};
-use config::{configuration, ConfigHandle, FontRasterizerSelection, TextStyle};
+use config::{configuration, ConfigHandle, FontAttributes, FontRasterizerSelection, TextStyle};
 use std::cell::RefCell;
 use std::collections::{HashMap, HashSet};
 use std::rc::{Rc, Weak};
@@ -26,9 +26,39 @@ pub mod fcwrap;
 pub use crate::rasterizer::RasterizedGlyph;
 pub use crate::shaper::{FallbackIdx, FontMetrics, GlyphInfo};
 
+/// The adjustments configured for one of the fonts in a
+/// fallback list, so that it can be lined up with the primary font
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FontAdjustment {
+    /// Multiplies the size at which glyphs are rendered
+    pub scale: f64,
+    /// Moves glyphs up, as a fraction of the cell height
+    pub baseline_offset: f64,
+}
+
+impl Default for FontAdjustment {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            baseline_offset: 0.0,
+        }
+    }
+}
+
+impl From<&FontAttributes> for FontAdjustment {
+    fn from(attr: &FontAttributes) -> Self {
+        Self {
+            scale: attr.scale.unwrap_or(1.0),
+            baseline_offset: attr.baseline_offset.unwrap_or(0.0),
+        }
+    }
+}
+
 pub struct LoadedFont {
     rasterizers: RefCell<HashMap<FallbackIdx, Box<dyn FontRasterizer>>>,
     handles: RefCell<Vec<FontDataHandle>>,
+    /// Parallel to `handles`
+    adjustments: RefCell<Vec<FontAdjustment>>,
     shaper: RefCell<Box<dyn FontShaper>>,
     metrics: FontMetrics,
     font_size: f64,
@@ -51,6 +81,9 @@ impl LoadedFont {
                         Ok(_parsed) => {
                             let idx = handles.len() - 1;
                             handles.insert(idx, h);
+                            self.adjustments
+                                .borrow_mut()
+                                .insert(idx, FontAdjustment::default());
                             loaded = true;
                         }
                         Err(err) => {
@@ -146,6 +179,15 @@ impl LoadedFont {
             .metrics_for_idx(font_idx, self.font_size, self.dpi)
     }
 
+    /// Returns the configured adjustments for the font at `font_idx`
+    pub fn adjustment_for_idx(&self, font_idx: usize) -> FontAdjustment {
+        self.adjustments
+            .borrow()
+            .get(font_idx)
+            .copied()
+            .unwrap_or_default()
+    }
+
     pub fn rasterize_glyph(
         &self,
         glyph_pos: u32,
@@ -176,8 +218,8 @@ struct FontConfigInner {
     font_scale: RefCell<f64>,
     config: RefCell<ConfigHandle>,
     locator: Box<dyn FontLocator>,
-    font_dirs: RefCell<FontDatabase>,
-    built_in: RefCell<FontDatabase>,
+    font_dirs: RefCell<Rc<FontDatabase>>,
+    built_in: RefCell<Rc<FontDatabase>>,
 }
 
 /// Matches and loads fonts for a given input style
@@ -197,8 +239,8 @@ impl FontConfigInner {
             font_scale: RefCell::new(1.0),
             dpi_scale: RefCell::new(1.0),
             config: RefCell::new(config.clone()),
-            font_dirs: RefCell::new(FontDatabase::with_font_dirs(&config)?),
-            built_in: RefCell::new(FontDatabase::with_built_in()?),
+            font_dirs: RefCell::new(FontDatabase::shared_with_font_dirs(&config)?),
+            built_in: RefCell::new(FontDatabase::shared_built_in()?),
         })
     }
 
@@ -208,7 +250,7 @@ impl FontConfigInner {
         // Config was reloaded, invalidate our caches
         fonts.clear();
         self.metrics.borrow_mut().take();
-        *self.font_dirs.borrow_mut() = FontDatabase::with_font_dirs(config)?;
+        *self.font_dirs.borrow_mut() = FontDatabase::shared_with_font_dirs(config)?;
         Ok(())
     }
 
@@ -237,14 +279,48 @@ impl FontConfigInner {
         let mut loaded = HashSet::new();
 
         let mut handles = vec![];
-        for attrs in &[&preferred_attributes, &fallback_attributes] {
-            self.font_dirs
-                .borrow()
-                .resolve_multiple(attrs, &mut handles, &mut loaded);
-            handles.append(&mut self.locator.load_fonts(attrs, &mut loaded)?);
-            self.built_in
-                .borrow()
-                .resolve_multiple(attrs, &mut handles, &mut loaded);
+        // The fonts are resolved one attribute at a time so that we
+        // know which of them produced each handle, and can apply
+        // the adjustments that were configured for it
+        let mut adjustments = vec![];
+        for (attrs, is_fallback) in &[(&preferred_attributes, false), (&fallback_attributes, true)]
+        {
+            for attr in attrs.iter() {
+                self.font_dirs.borrow().resolve_multiple(
+                    std::slice::from_ref(attr),
+                    &mut handles,
+                    &mut loaded,
+                );
+                adjustments.resize(handles.len(), FontAdjustment::from(attr));
+            }
+            for attr in attrs.iter() {
+                // Only ask the locator for fonts that we haven't already found;
+                // some locators are comparatively expensive to query
+                if loaded.contains(attr) {
+                    continue;
+                }
+                // The default fallback fonts are bundled, so there's no
+                // need to look for them; that saves several fontconfig
+                // queries when starting up.  They are added from the
+                // built in fonts below.
+                if *is_fallback && self.built_in.borrow().resolve(attr).is_some() {
+                    continue;
+                }
+                handles.append(
+                    &mut self
+                        .locator
+                        .load_fonts(std::slice::from_ref(attr), &mut loaded)?,
+                );
+                adjustments.resize(handles.len(), FontAdjustment::from(attr));
+            }
+            for attr in attrs.iter() {
+                self.built_in.borrow().resolve_multiple(
+                    std::slice::from_ref(attr),
+                    &mut handles,
+                    &mut loaded,
+                );
+                adjustments.resize(handles.len(), FontAdjustment::from(attr));
+            }
         }
 
         for attr in &attributes {
@@ -294,7 +370,11 @@ impl FontConfigInner {
 
         let shaper = new_shaper(&*config, &handles)?;
 
-        let font_size = config.font_size * *self.font_scale.borrow();
+        let font_size = match style.fixed_size {
+            // Not subject to the zoom level
+            Some(size) => size as f64 / 100.0,
+            None => config.font_size * *self.font_scale.borrow(),
+        };
         let dpi =
             *self.dpi_scale.borrow() as u32 * config.dpi.unwrap_or_else(|| default_dpi()) as u32;
         let metrics = shaper.metrics(font_size, dpi).with_context(|| {
@@ -307,6 +387,7 @@ impl FontConfigInner {
         let loaded = Rc::new(LoadedFont {
             rasterizers: RefCell::new(HashMap::new()),
             handles: RefCell::new(handles),
+            adjustments: RefCell::new(adjustments),
             shaper: RefCell::new(shaper),
             metrics,
             font_size,
@@ -24,6 +24,10 @@ impl FontLocator for FontConfigFontLocator {
 
         for attr in fonts_selection {
             for &spacing in &SPACING {
+                if loaded.contains(attr) {
+                    // Already matched using a prior spacing
+                    break;
+                }
                 let mut pattern = FontPattern::new()?;
                 let start = std::time::Instant::now();
                 pattern.family(&attr.family)?;
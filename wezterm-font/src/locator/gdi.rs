@@ -211,6 +211,8 @@ impl FontLocator for GdiFontLocator {
                         italic: false,
                         family: font.family_name(),
                         is_fallback: true,
+                        scale: None,
+                        baseline_offset: None,
                     };
 
                     if !resolved.contains(&attr) {
@@ -449,6 +449,8 @@ mod test {
                 bold: false,
                 is_fallback: false,
                 italic: false,
+                scale: None,
+                baseline_offset: None,
             })
             .unwrap()
             .clone();
@@ -1,6 +1,9 @@
-use config::SshParameters;
+use anyhow::{anyhow, bail};
+use config::{SshDomain, SshParameters};
 use std::ffi::OsString;
+use std::path::PathBuf;
 use structopt::StructOpt;
+use url::Url;
 
 /// Helper for parsing config overrides
 pub fn name_equals_value(arg: &str) -> Result<(String, String), String> {
@@ -42,6 +45,12 @@ pub struct StartCommand {
     #[structopt(long = "class")]
     pub class: Option<String>,
 
+    /// Always start a new gui process, even if `single_instance`
+    /// is enabled in the configuration and another instance of
+    /// the gui is already running.
+    #[structopt(long = "always-new-process")]
+    pub always_new_process: bool,
+
     /// Instead of executing your shell, run PROG.
     /// For example: `wezterm start -- bash -l` will spawn bash
     /// as if it were a login shell.
@@ -83,7 +92,18 @@ pub struct SerialCommand {
 pub struct ConnectCommand {
     /// Name of the multiplexer domain section from the configuration
     /// to which you'd like to connect
-    pub domain_name: String,
+    #[structopt(required_unless = "invite")]
+    pub domain_name: Option<String>,
+
+    /// Connect using an invitation from `wezterm cli invite`,
+    /// rather than a domain from the configuration
+    #[structopt(long = "invite", conflicts_with = "domain-name")]
+    pub invite: Option<String>,
+
+    /// Attach in read-only mode: output is displayed, but keyboard
+    /// and mouse input is not sent to the panes
+    #[structopt(long = "read-only")]
+    pub read_only: bool,
 
     /// Instead of executing your shell, run PROG.
     /// For example: `wezterm start -- bash -l` will spawn bash
@@ -91,3 +111,153 @@ pub struct ConnectCommand {
     #[structopt(parse(from_os_str))]
     pub prog: Vec<OsString>,
 }
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct OpenCommand {
+    /// What to open: a directory, a `file://` URL naming a directory
+    /// or an `ssh://[user@]host[:port]` URL.
+    /// A directory starts your shell in that directory, while an
+    /// ssh URL connects to that host, using the matching entry from
+    /// `ssh_domains` if there is one.
+    #[structopt(parse(from_os_str))]
+    pub target: OsString,
+}
+
+/// The resolved target of `wezterm open`
+#[derive(Debug, Clone, PartialEq)]
+pub enum OpenTarget {
+    /// Start the default program in this directory
+    Directory(PathBuf),
+    /// Connect to this host via ssh
+    Ssh {
+        username: Option<String>,
+        host_and_port: String,
+    },
+}
+
+impl OpenCommand {
+    pub fn resolve(&self) -> anyhow::Result<OpenTarget> {
+        let path = match self.target.to_str() {
+            Some(s) if s.starts_with("ssh://") => return parse_ssh_url(s),
+            Some(s) if s.starts_with("file://") => Url::parse(s)?
+                .to_file_path()
+                .map_err(|_| anyhow!("{} is not a local file URL", s))?,
+            _ => PathBuf::from(&self.target),
+        };
+
+        // File managers may pass the selected file rather than
+        // the directory that contains it
+        let path = if path.is_file() {
+            path.parent().map(PathBuf::from).unwrap_or(path)
+        } else {
+            path
+        };
+        if !path.is_dir() {
+            bail!("{} is not a directory", path.display());
+        }
+        Ok(OpenTarget::Directory(path))
+    }
+}
+
+fn parse_ssh_url(s: &str) -> anyhow::Result<OpenTarget> {
+    let url = Url::parse(s)?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("{} doesn't specify a host", s))?;
+    let host_and_port = match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    };
+    let username = match url.username() {
+        "" => None,
+        username => Some(username.to_string()),
+    };
+    Ok(OpenTarget::Ssh {
+        username,
+        host_and_port,
+    })
+}
+
+/// Returns the ssh domain that connects to `host_and_port`, and
+/// as `username` if that was specified
+pub fn matching_ssh_domain<'a>(
+    domains: &'a [SshDomain],
+    username: Option<&str>,
+    host_and_port: &str,
+) -> Option<&'a SshDomain> {
+    let with_default_port = |address: &str| {
+        if address.contains(':') {
+            address.to_string()
+        } else {
+            format!("{}:22", address)
+        }
+    };
+    let host_and_port = with_default_port(host_and_port);
+    domains.iter().find(|dom| {
+        with_default_port(&dom.remote_address) == host_and_port
+            && username.map(|user| user == dom.username).unwrap_or(true)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn resolve(target: &str) -> anyhow::Result<OpenTarget> {
+        OpenCommand {
+            target: target.into(),
+        }
+        .resolve()
+    }
+
+    #[test]
+    fn ssh_urls() {
+        assert_eq!(
+            resolve("ssh://wez@example.com:2222").unwrap(),
+            OpenTarget::Ssh {
+                username: Some("wez".to_string()),
+                host_and_port: "example.com:2222".to_string(),
+            }
+        );
+        assert_eq!(
+            resolve("ssh://example.com/").unwrap(),
+            OpenTarget::Ssh {
+                username: None,
+                host_and_port: "example.com".to_string(),
+            }
+        );
+        assert!(resolve("ssh://").is_err());
+    }
+
+    #[test]
+    fn directories() {
+        let dir = std::env::temp_dir();
+        assert_eq!(
+            resolve(dir.to_str().unwrap()).unwrap(),
+            OpenTarget::Directory(dir.clone())
+        );
+        let url = Url::from_file_path(&dir).unwrap();
+        assert_eq!(
+            resolve(url.as_str()).unwrap(),
+            OpenTarget::Directory(dir.clone())
+        );
+        assert!(resolve(dir.join("no-such-wezterm-dir").to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn ssh_domains() {
+        let domains = vec![SshDomain {
+            name: "prod".to_string(),
+            remote_address: "example.com".to_string(),
+            username: "wez".to_string(),
+            ..Default::default()
+        }];
+        let find = |username, host_and_port| {
+            matching_ssh_domain(&domains, username, host_and_port).map(|dom| dom.name.as_str())
+        };
+        assert_eq!(find(None, "example.com"), Some("prod"));
+        assert_eq!(find(Some("wez"), "example.com:22"), Some("prod"));
+        assert_eq!(find(Some("root"), "example.com"), None);
+        assert_eq!(find(None, "example.com:2222"), None);
+    }
+}
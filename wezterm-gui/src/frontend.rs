@@ -34,7 +34,23 @@ impl GuiFrontEnd {
                             mux.kill_window(mux_window_id);
                         }
                     }
+                    MuxNotification::WindowActivated(_) => {
+                        // Handled by the TermWindow
+                    }
                     MuxNotification::PaneOutput(_) => {}
+                    MuxNotification::PaneRemoved(pane_id) => {
+                        // The scrollback persistence is handled separately
+                        wezterm_mux_server_impl::stats::Stats::forget_pane(pane_id);
+                    }
+                    MuxNotification::ShuttingDown { grace } => {
+                        persistent_toast_notification(
+                            "wezterm",
+                            &format!(
+                                "Shutting down; remaining panes will be closed in {}s",
+                                grace.as_secs()
+                            ),
+                        );
+                    }
                     MuxNotification::Alert {
                         pane_id: _,
                         alert:
@@ -58,6 +74,27 @@ impl GuiFrontEnd {
                         // persistent_toast_notification("Ding!", "This is the bell");
                         log::info!("Ding! (this is the bell)");
                     }
+                    MuxNotification::Alert {
+                        pane_id: _,
+                        alert: Alert::SetUserVar { .. },
+                    } => {
+                        // Handled by the TermWindow that contains the pane
+                    }
+                    MuxNotification::Alert {
+                        pane_id: _,
+                        alert: Alert::CommandCompleted { .. },
+                    } => {
+                        // Handled by the TermWindow that contains the pane
+                    }
+                    MuxNotification::Alert {
+                        pane_id: _,
+                        alert: Alert::TitleChanged(_),
+                    } => {
+                        // The title is picked up when the window next paints
+                    }
+                    MuxNotification::ConfigReloaded => {
+                        // Handled by each TermWindow
+                    }
                 }
                 true
             } else {
@@ -414,6 +414,7 @@ impl<T: Texture2d> GlyphCache<T> {
     ) -> anyhow::Result<Rc<CachedGlyph<T>>> {
         let base_metrics;
         let idx_metrics;
+        let adjustment;
         let glyph;
 
         {
@@ -422,6 +423,7 @@ impl<T: Texture2d> GlyphCache<T> {
             glyph = font.rasterize_glyph(info.glyph_pos, info.font_idx)?;
 
             idx_metrics = font.metrics_for_idx(info.font_idx)?;
+            adjustment = font.adjustment_for_idx(info.font_idx);
         }
 
         let y_scale = base_metrics.cell_height.get() / idx_metrics.cell_height.get();
@@ -449,6 +451,8 @@ impl<T: Texture2d> GlyphCache<T> {
         } else {
             y_scale
         };
+        // Apply the scale that was configured for this particular font
+        let scale = scale * adjustment.scale;
 
         let (cell_width, cell_height) = (base_metrics.cell_width, base_metrics.cell_height);
 
@@ -472,7 +476,8 @@ impl<T: Texture2d> GlyphCache<T> {
             );
 
             let bearing_x = glyph.bearing_x * scale;
-            let bearing_y = glyph.bearing_y * scale;
+            let bearing_y =
+                glyph.bearing_y * scale + base_metrics.cell_height * adjustment.baseline_offset;
             let x_offset = info.x_offset * scale;
             let y_offset = info.y_offset * scale;
 
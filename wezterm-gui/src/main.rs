@@ -3,9 +3,12 @@
 
 use crate::frontend::front_end;
 use ::window::*;
-use anyhow::anyhow;
+use anyhow::{anyhow, bail};
+use config::keyassignment::{SpawnCommand, SpawnTabDomain};
+use config::{StartupSplit, StartupTab};
 use mux::activity::Activity;
-use mux::domain::{Domain, LocalDomain};
+use mux::domain::{Domain, DomainState, LocalDomain};
+use mux::tab::SplitDirection;
 use mux::Mux;
 use portable_pty::cmdbuilder::CommandBuilder;
 use promise::spawn::block_on;
@@ -21,12 +24,14 @@ mod frontend;
 mod glyphcache;
 mod markdown;
 mod overlay;
+mod persist;
 mod quad;
 mod renderstate;
 mod scripting;
 mod scrollbar;
 mod selection;
 mod shapecache;
+mod single_instance;
 mod stats;
 mod tabbar;
 mod termwindow;
@@ -67,6 +72,13 @@ struct Opt {
         number_of_values = 1)]
     config_override: Vec<(String, String)>,
 
+    /// Keep the configuration, logs, sockets and other state in the
+    /// directory that holds the executable, rather than in the home
+    /// directory.  Creating a file named `wezterm-portable` in that
+    /// directory has the same effect
+    #[structopt(long = "portable")]
+    portable: bool,
+
     #[structopt(subcommand)]
     cmd: Option<SubCommand>,
 }
@@ -84,6 +96,12 @@ enum SubCommand {
 
     #[structopt(name = "connect", about = "Connect to wezterm multiplexer")]
     Connect(ConnectCommand),
+
+    #[structopt(
+        name = "open",
+        about = "Open a directory or an ssh:// URL in a new window"
+    )]
+    Open(OpenCommand),
 }
 
 async fn async_run_ssh(opts: SshCommand) -> anyhow::Result<()> {
@@ -194,15 +212,22 @@ fn client_domains(config: &config::ConfigHandle) -> Vec<ClientDomainConfig> {
 }
 
 fn run_mux_client(config: config::ConfigHandle, opts: &ConnectCommand) -> anyhow::Result<()> {
-    let client_config = client_domains(&config)
-        .into_iter()
-        .find(|c| c.name() == opts.domain_name)
-        .ok_or_else(|| {
-            anyhow!(
-                "no multiplexer domain with name `{}` was found in the configuration",
-                opts.domain_name
-            )
-        })?;
+    let mut client_config = match (&opts.invite, &opts.domain_name) {
+        (Some(invite), _) => ClientDomainConfig::from_invitation(invite.parse()?),
+        (None, Some(domain_name)) => client_domains(&config)
+            .into_iter()
+            .find(|c| c.name() == domain_name)
+            .ok_or_else(|| {
+                anyhow!(
+                    "no multiplexer domain with name `{}` was found in the configuration",
+                    domain_name
+                )
+            })?,
+        (None, None) => bail!("either a domain name or --invite must be specified"),
+    };
+    if opts.read_only {
+        client_config.set_read_only(true);
+    }
 
     let domain: Arc<dyn Domain> = Arc::new(ClientDomain::new(client_config));
     let mux = Rc::new(mux::Mux::new(Some(domain.clone())));
@@ -247,6 +272,10 @@ async fn spawn_tab_in_default_domain_if_mux_is_empty(
     }
 
     let config = config::configuration();
+    if cmd.is_none() && !config.startup_windows.is_empty() {
+        return spawn_startup_windows(&config).await;
+    }
+
     let window_id = mux.new_empty_window();
     let _tab = mux
         .default_domain()
@@ -255,6 +284,100 @@ async fn spawn_tab_in_default_domain_if_mux_is_empty(
     Ok(())
 }
 
+/// Resolves the domain, command and working directory for a pane
+/// described by the startup_windows configuration
+async fn resolve_startup_command(
+    spawn: &SpawnCommand,
+) -> anyhow::Result<(Arc<dyn Domain>, Option<CommandBuilder>, Option<String>)> {
+    let mux = Mux::get().unwrap();
+    let domain = match &spawn.domain {
+        SpawnTabDomain::DefaultDomain | SpawnTabDomain::CurrentPaneDomain => mux.default_domain(),
+        SpawnTabDomain::DomainName(name) => mux
+            .get_domain_by_name(name)
+            .ok_or_else(|| anyhow!("startup_windows: domain name {} is invalid", name))?,
+    };
+    if domain.state() == DomainState::Detached {
+        domain.attach().await?;
+    }
+
+    let cwd =
+        match spawn.cwd.as_ref() {
+            Some(cwd) => Some(cwd.to_str().map(|s| s.to_owned()).ok_or_else(|| {
+                anyhow!("startup_windows: cwd must be unicode, but got {:?}", cwd)
+            })?),
+            None => None,
+        };
+
+    let cmd = match spawn.args.as_ref() {
+        Some(args) => {
+            let mut builder = CommandBuilder::from_argv(args.iter().map(Into::into).collect());
+            for (k, v) in spawn.set_environment_variables.iter() {
+                builder.env(k, v);
+            }
+            if let Some(cwd) = spawn.cwd.as_ref() {
+                builder.cwd(cwd);
+            }
+            Some(builder)
+        }
+        None if !spawn.set_environment_variables.is_empty() => {
+            // The domain will run its default_prog with these applied
+            let mut builder = CommandBuilder::new_default_prog();
+            for (k, v) in spawn.set_environment_variables.iter() {
+                builder.env(k, v);
+            }
+            Some(builder)
+        }
+        None => None,
+    };
+
+    Ok((domain, cmd, cwd))
+}
+
+/// Creates the windows, tabs and panes described by the
+/// startup_windows configuration
+async fn spawn_startup_windows(config: &config::ConfigHandle) -> anyhow::Result<()> {
+    let mux = Mux::get().unwrap();
+    let size = config.initial_size();
+
+    for startup_window in &config.startup_windows {
+        // Holding the builder defers announcing the window to the gui
+        // until all of its tabs have been created
+        let window_id = mux.new_empty_window();
+
+        let default_tabs = [StartupTab::default()];
+        let tabs = if startup_window.tabs.is_empty() {
+            &default_tabs[..]
+        } else {
+            &startup_window.tabs[..]
+        };
+
+        for startup_tab in tabs {
+            let mut panes = startup_tab.panes.iter();
+            let first = panes.next().cloned().unwrap_or_default();
+
+            let (domain, cmd, cwd) = resolve_startup_command(&first.command).await?;
+            let tab = domain.spawn(size, cmd, cwd, *window_id).await?;
+            let mut pane_id = tab
+                .get_active_pane()
+                .ok_or_else(|| anyhow!("newly spawned tab to have a pane"))?
+                .pane_id();
+
+            for startup_pane in panes {
+                let direction = match startup_pane.split {
+                    StartupSplit::Horizontal => SplitDirection::Horizontal,
+                    StartupSplit::Vertical => SplitDirection::Vertical,
+                };
+                let (domain, cmd, cwd) = resolve_startup_command(&startup_pane.command).await?;
+                let pane = domain
+                    .split_pane(cmd, cwd, tab.tab_id(), pane_id, direction)
+                    .await?;
+                pane_id = pane.pane_id();
+            }
+        }
+    }
+    Ok(())
+}
+
 async fn async_run_terminal_gui(
     cmd: Option<CommandBuilder>,
     do_auto_connect: bool,
@@ -267,6 +390,10 @@ async fn async_run_terminal_gui(
         Ok(domain)
     }
 
+    for domain in mux::domain::create_custom_domains(&config::configuration()) {
+        mux.add_domain(&domain);
+    }
+
     if do_auto_connect {
         let config = config::configuration();
         for client_config in client_domains(&config) {
@@ -278,7 +405,17 @@ async fn async_run_terminal_gui(
         }
     }
 
-    spawn_tab_in_default_domain_if_mux_is_empty(cmd).await
+    if let Some(name) = &config::configuration().default_domain {
+        match mux.get_domain_by_name(name) {
+            Some(domain) => mux.set_default_domain(&domain),
+            None => log::error!("default_domain: domain name {} is invalid", name),
+        }
+    }
+
+    spawn_tab_in_default_domain_if_mux_is_empty(cmd).await?;
+    persist::recover_scrollback();
+    persist::start();
+    Ok(())
 }
 
 fn run_terminal_gui(opts: StartCommand) -> anyhow::Result<()> {
@@ -286,6 +423,10 @@ fn run_terminal_gui(opts: StartCommand) -> anyhow::Result<()> {
         crate::set_window_class(cls);
     }
 
+    if single_instance::delegate_to_existing_gui(&opts) {
+        return Ok(());
+    }
+
     let unix_socket_path =
         config::RUNTIME_DIR.join(format!("gui-sock-{}", unsafe { libc::getpid() }));
     std::env::set_var("WEZTERM_UNIX_SOCKET", unix_socket_path.clone());
@@ -346,6 +487,44 @@ fn run_terminal_gui(opts: StartCommand) -> anyhow::Result<()> {
     res
 }
 
+/// Starts the gui in the directory, or connected to the host, that
+/// was passed by the file manager or browser
+fn run_open(config: config::ConfigHandle, opts: &OpenCommand) -> anyhow::Result<()> {
+    match opts.resolve()? {
+        OpenTarget::Directory(dir) => run_terminal_gui(StartCommand {
+            cwd: Some(dir.into_os_string()),
+            ..Default::default()
+        }),
+        OpenTarget::Ssh {
+            username,
+            host_and_port,
+        } => {
+            if let Some(dom) =
+                matching_ssh_domain(&config.ssh_domains, username.as_deref(), &host_and_port)
+            {
+                let connect = ConnectCommand {
+                    domain_name: Some(dom.name.clone()),
+                    invite: None,
+                    read_only: false,
+                    prog: vec![],
+                };
+                return run_mux_client(config.clone(), &connect);
+            }
+            let username = match username {
+                Some(username) => username,
+                None => config::username_from_env()?,
+            };
+            run_ssh(SshCommand {
+                user_at_host_and_port: config::SshParameters {
+                    username,
+                    host_and_port,
+                },
+                prog: vec![],
+            })
+        }
+    }
+}
+
 fn fatal_toast_notification(title: &str, message: &str) {
     persistent_toast_notification(title, message);
     // We need a short delay otherwise the notification
@@ -389,6 +568,8 @@ fn maybe_show_configuration_error_window() {
     if let Err(err) = config::configuration_result() {
         let err = format!("{:#}", err);
         mux::connui::show_configuration_error_message(&err);
+    } else if let Some(warnings) = config::configuration_warnings() {
+        mux::connui::show_configuration_error_message(&warnings);
     }
 }
 
@@ -452,12 +633,18 @@ fn run() -> anyhow::Result<()> {
         }
     };
 
+    // The options are parsed first, as portable mode
+    // affects where the log file is written
+    let opts = Opt::from_args();
+    if opts.portable {
+        config::enable_portable_mode()?;
+    }
+
     env_bootstrap::bootstrap();
 
-    stats::Stats::init()?;
+    stats::init()?;
     let _saver = umask::UmaskSaver::new();
 
-    let opts = Opt::from_args();
     config::common_init(
         opts.config_file.as_ref(),
         &opts.config_override,
@@ -465,6 +652,8 @@ fn run() -> anyhow::Result<()> {
     );
     let config = config::configuration();
     ::window::configuration::set_configuration(crate::window_config::ConfigBridge);
+    #[cfg(unix)]
+    config::reload_on_sigusr1()?;
 
     match opts
         .cmd
@@ -479,5 +668,6 @@ fn run() -> anyhow::Result<()> {
         SubCommand::Ssh(ssh) => run_ssh(ssh),
         SubCommand::Serial(serial) => run_serial(config, &serial),
         SubCommand::Connect(connect) => run_mux_client(config, &connect),
+        SubCommand::Open(open) => run_open(config, &open),
     }
 }
@@ -0,0 +1,272 @@
+//! The character selector presents a searchable list of emoji and
+//! other unicode characters, and inserts the chosen character into
+//! the pane from which it was launched.
+//! The list of characters is generated by ci/generate-charselect.py
+use super::fuzzy_score;
+use mux::pane::PaneId;
+use mux::termwiztermtab::TermWizTerminal;
+use mux::Mux;
+use std::io::Write;
+use termwiz::cell::{AttributeChange, CellAttributes};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseEvent};
+use termwiz::surface::{Change, Position};
+use termwiz::terminal::Terminal;
+
+struct CharEntry {
+    c: char,
+    name: &'static str,
+}
+
+fn build_char_list() -> Vec<CharEntry> {
+    let data = include_str!("charselect.txt");
+    data.lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(2, ' ');
+            let codepoint = u32::from_str_radix(fields.next()?, 16).ok()?;
+            let c = std::char::from_u32(codepoint)?;
+            let name = fields.next()?;
+            Some(CharEntry { c, name })
+        })
+        .collect()
+}
+
+fn matches(query: &str, chars: &[CharEntry]) -> Vec<usize> {
+    let query = query.trim().to_ascii_uppercase();
+    if query.is_empty() {
+        return (0..chars.len()).collect();
+    }
+
+    // Allow searching by codepoint, eg: "U+1F525" or "1F525"
+    let codepoint = u32::from_str_radix(query.trim_start_matches("U+"), 16).ok();
+
+    let mut scored: Vec<(usize, usize)> = chars
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, entry)| {
+            if Some(entry.c as u32) == codepoint {
+                return Some((usize::max_value(), idx));
+            }
+            fuzzy_score(&query, entry.name).map(|score| (score, idx))
+        })
+        .collect();
+
+    // Highest score first; shorter names win ties
+    scored.sort_by(|(a_score, a_idx), (b_score, b_idx)| {
+        b_score.cmp(a_score).then_with(|| {
+            chars[*a_idx]
+                .name
+                .len()
+                .cmp(&chars[*b_idx].name.len())
+                .then(a_idx.cmp(b_idx))
+        })
+    });
+    scored.into_iter().map(|(_, idx)| idx).collect()
+}
+
+pub fn char_select(pane_id: PaneId, mut term: TermWizTerminal) -> anyhow::Result<()> {
+    let chars = build_char_list();
+    let mut query = String::new();
+    let mut matching = matches(&query, &chars);
+    let mut selected = 0;
+    let mut top_row = 0;
+
+    term.set_raw_mode()?;
+
+    fn render(
+        query: &str,
+        chars: &[CharEntry],
+        matching: &[usize],
+        selected: usize,
+        top_row: usize,
+        max_rows: usize,
+        term: &mut TermWizTerminal,
+    ) -> termwiz::Result<()> {
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(0),
+            },
+            Change::Text(
+                "Type to search, select a character and press Enter to insert it.  \
+                 Press Escape to cancel\r\n"
+                    .to_string(),
+            ),
+            Change::AllAttributes(CellAttributes::default()),
+        ];
+
+        for (row, idx) in matching.iter().enumerate().skip(top_row).take(max_rows) {
+            let entry = &chars[*idx];
+            if row == selected {
+                changes.push(AttributeChange::Reverse(true).into());
+            }
+
+            changes.push(Change::Text(format!(
+                " {}  {} (U+{:04X})\r\n",
+                entry.c, entry.name, entry.c as u32
+            )));
+
+            if row == selected {
+                changes.push(AttributeChange::Reverse(false).into());
+            }
+        }
+
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(max_rows + 1),
+        });
+        changes.push(Change::Text(format!(
+            "{} matches. Search: {}",
+            matching.len(),
+            query
+        )));
+
+        term.render(&changes)?;
+        term.flush()
+    }
+
+    fn insert_char(pane_id: PaneId, c: char) {
+        promise::spawn::spawn_into_main_thread(async move {
+            let mux = Mux::get().unwrap();
+            if let Some(pane) = mux.get_pane(pane_id) {
+                let mut buf = [0u8; 4];
+                pane.writer()
+                    .write_all(c.encode_utf8(&mut buf).as_bytes())?;
+            }
+            anyhow::Result::<()>::Ok(())
+        })
+        .detach();
+    }
+
+    term.render(&[Change::Title("Select Character".to_string())])?;
+
+    // One row for the heading and one for the search line
+    let max_rows = term.get_screen_size()?.rows.saturating_sub(2).max(1);
+    render(
+        &query, &chars, &matching, selected, top_row, max_rows, &mut term,
+    )?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            }) => {
+                selected = selected.saturating_sub(1);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            }) => {
+                selected = (selected + 1).min(matching.len().saturating_sub(1));
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::PageUp,
+                ..
+            }) => {
+                selected = selected.saturating_sub(max_rows);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::PageDown,
+                ..
+            }) => {
+                selected = (selected + max_rows).min(matching.len().saturating_sub(1));
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }) => {
+                break;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Backspace,
+                ..
+            }) => {
+                query.pop();
+                matching = matches(&query, &chars);
+                selected = 0;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::NONE,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::SHIFT,
+            }) => {
+                query.push(c);
+                matching = matches(&query, &chars);
+                selected = 0;
+            }
+            InputEvent::Mouse(MouseEvent {
+                y, mouse_buttons, ..
+            }) => {
+                let row = top_row + y as usize;
+                if y > 0 && y as usize <= max_rows && row <= matching.len() {
+                    selected = row - 1;
+
+                    if mouse_buttons == MouseButtons::LEFT {
+                        insert_char(pane_id, chars[matching[selected]].c);
+                        break;
+                    }
+                }
+                if mouse_buttons != MouseButtons::NONE {
+                    // Treat any other mouse button as cancel
+                    break;
+                }
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                ..
+            }) => {
+                if let Some(idx) = matching.get(selected) {
+                    insert_char(pane_id, chars[*idx].c);
+                }
+                break;
+            }
+            _ => {}
+        }
+
+        // Keep the selection visible
+        if selected < top_row {
+            top_row = selected;
+        } else if selected >= top_row + max_rows {
+            top_row = selected + 1 - max_rows;
+        }
+
+        render(
+            &query, &chars, &matching, selected, top_row, max_rows, &mut term,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn fuzzy() {
+        assert_eq!(fuzzy_score("FIRE", "FIRE"), Some(10 + 3));
+        assert_eq!(fuzzy_score("XYZ", "FIRE"), None);
+        assert!(fuzzy_score("FIRE", "FIRE") > fuzzy_score("FIRE", "TRIGRAM FOR FIRE"));
+        assert!(
+            fuzzy_score("JOY", "FACE WITH TEARS OF JOY")
+                > fuzzy_score("JOY", "JAPANESE BOOKKEEPING")
+        );
+    }
+
+    #[test]
+    fn search() {
+        let chars = build_char_list();
+        let fire = matches("fire", &chars);
+        assert_eq!(chars[fire[0]].c, '\u{1F525}');
+
+        let by_codepoint = matches("U+1F525", &chars);
+        assert_eq!(chars[by_codepoint[0]].c, '\u{1F525}');
+
+        assert_eq!(matches("", &chars).len(), chars.len());
+    }
+}
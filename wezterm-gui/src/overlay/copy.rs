@@ -1,9 +1,10 @@
 use crate::selection::{SelectionCoordinate, SelectionRange};
 use crate::termwindow::TermWindow;
-use config::keyassignment::ScrollbackEraseMode;
+use config::keyassignment::{ClipboardCopyDestination, ScrollbackEraseMode};
 use mux::domain::DomainId;
 use mux::pane::{Pane, PaneId};
 use mux::renderable::*;
+use mux::Mux;
 use portable_pty::PtySize;
 use rangeset::RangeSet;
 use std::cell::{RefCell, RefMut};
@@ -27,6 +28,8 @@ struct CopyRenderable {
     cursor: StableCursorPosition,
     delegate: Rc<dyn Pane>,
     start: Option<SelectionCoordinate>,
+    /// When true, the selection is extended to cover whole lines
+    line_mode: bool,
     viewport: Option<StableRowIndex>,
     /// We use this to cancel ourselves later
     window: ::window::Window,
@@ -49,6 +52,7 @@ impl CopyOverlay {
             window,
             delegate: Rc::clone(pane),
             start: None,
+            line_mode: false,
             viewport: term_window.get_viewport(pane.pane_id()),
         };
         Rc::new(CopyOverlay {
@@ -92,7 +96,22 @@ impl CopyRenderable {
                 y: self.cursor.y,
             };
 
-            self.adjust_selection(start, SelectionRange { start, end });
+            let range = if self.line_mode {
+                SelectionRange {
+                    start: SelectionCoordinate {
+                        x: 0,
+                        y: start.y.min(end.y),
+                    },
+                    end: SelectionCoordinate {
+                        x: usize::max_value(),
+                        y: start.y.max(end.y),
+                    },
+                }
+            } else {
+                SelectionRange { start, end }
+            };
+
+            self.adjust_selection(start, range);
         } else {
             self.adjust_viewport_for_cursor_position();
             self.window.invalidate();
@@ -365,15 +384,51 @@ impl CopyRenderable {
     }
 
     fn toggle_selection_by_cell(&mut self) {
-        if self.start.take().is_none() {
+        self.toggle_selection(false);
+    }
+
+    fn toggle_selection_by_line(&mut self) {
+        self.toggle_selection(true);
+    }
+
+    /// Starts a selection at the cursor if there isn't one.
+    /// If there is a selection of the other kind, it is switched
+    /// to this kind, otherwise it is cancelled.
+    fn toggle_selection(&mut self, line_mode: bool) {
+        if self.start.is_some() && self.line_mode != line_mode {
+            self.line_mode = line_mode;
+            self.select_to_cursor_pos();
+        } else if self.start.take().is_none() {
             let coord = SelectionCoordinate {
                 x: self.cursor.x,
                 y: self.cursor.y,
             };
             self.start.replace(coord);
+            self.line_mode = line_mode;
             self.select_to_cursor_pos();
         }
     }
+
+    /// Copies the selection to the clipboard and leaves copy mode
+    fn yank(&self) {
+        if self.start.is_none() {
+            return;
+        }
+        let pane_id = self.delegate.pane_id();
+        self.window.apply(move |term_window, _window| {
+            if let Some(term_window) = term_window.downcast_mut::<TermWindow>() {
+                if let Some(pane) = Mux::get().unwrap().get_pane(pane_id) {
+                    let text = term_window.selection_text(&pane);
+                    term_window.copy_to_clipboard(
+                        ClipboardCopyDestination::ClipboardAndPrimarySelection,
+                        text,
+                    );
+                }
+            }
+            Ok(())
+        });
+        self.close();
+    }
 }
 
 impl Pane for CopyOverlay {
@@ -452,6 +507,13 @@ impl Pane for CopyOverlay {
             (KeyCode::Char(' '), KeyModifiers::NONE) | (KeyCode::Char('v'), KeyModifiers::NONE) => {
                 self.render.borrow_mut().toggle_selection_by_cell();
             }
+            (KeyCode::Char('V'), KeyModifiers::SHIFT) | // FIXME: normalize the shift away!
+            (KeyCode::Char('V'), KeyModifiers::NONE) => {
+                self.render.borrow_mut().toggle_selection_by_line();
+            }
+            (KeyCode::Char('y'), KeyModifiers::NONE) => {
+                self.render.borrow().yank();
+            }
             (KeyCode::Char('G'), KeyModifiers::SHIFT) | // FIXME: normalize the shift away!
             (KeyCode::Char('G'), KeyModifiers::NONE) => {
                 self.render.borrow_mut().move_to_bottom();
@@ -519,6 +581,14 @@ impl Pane for CopyOverlay {
         self.delegate.get_current_working_dir()
     }
 
+    fn get_foreground_process_name(&self) -> Option<String> {
+        self.delegate.get_foreground_process_name()
+    }
+
+    fn is_password_input(&self) -> bool {
+        self.delegate.is_password_input()
+    }
+
     fn get_cursor_position(&self) -> StableCursorPosition {
         self.render.borrow_mut().cursor
     }
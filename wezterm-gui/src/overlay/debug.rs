@@ -0,0 +1,95 @@
+//! The debug overlay shows the metrics collected by the gui, such as
+//! how long it takes to paint a frame or to parse the output from a
+//! pane, along with the most recent log entries, and refreshes them
+//! periodically.
+use crate::stats::{format_counters, format_histograms, parse_throughput};
+use mux::termwiztermtab::TermWizTerminal;
+use std::time::{Duration, Instant};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent};
+use termwiz::surface::{Change, Position};
+use termwiz::terminal::Terminal;
+use wezterm_mux_server_impl::stats::{Snapshot, Stats};
+
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+fn render(
+    term: &mut TermWizTerminal,
+    snapshot: &Snapshot,
+    previous: Option<(&Snapshot, Duration)>,
+) -> anyhow::Result<()> {
+    let mut report = String::new();
+    if let Some(throughput) = parse_throughput(snapshot) {
+        report.push_str(&format!(
+            "parse throughput: {:.2} MiB/s\n\n",
+            throughput / (1024. * 1024.)
+        ));
+    }
+    report.push_str(&format_histograms(snapshot));
+    report.push('\n');
+    report.push_str(&format_counters(snapshot, previous));
+
+    let size = term.get_screen_size()?;
+    let rows = size.rows;
+    let mut changes = vec![
+        Change::ClearScreen(ColorAttribute::Default),
+        Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(0),
+        },
+        Change::Text("Press Escape to close this overlay\r\n\r\n".to_string()),
+    ];
+    let report_rows = report.lines().count().min(rows.saturating_sub(3));
+    for line in report.lines().take(report_rows) {
+        changes.push(Change::Text(format!("{}\r\n", line)));
+    }
+
+    // Fill the remaining rows with as many of the most recent log
+    // entries as will fit, truncated to the width of the screen
+    let log_rows = rows.saturating_sub(report_rows + 5);
+    if log_rows > 0 {
+        changes.push(Change::Text("\r\nRecent log entries:\r\n".to_string()));
+        let entries = env_bootstrap::ringlog::get_entries();
+        for entry in entries.iter().skip(entries.len().saturating_sub(log_rows)) {
+            let line: String = entry
+                .to_string()
+                .replace(|c: char| c.is_control(), " ")
+                .chars()
+                .take(size.cols.saturating_sub(1))
+                .collect();
+            changes.push(Change::Text(format!("{}\r\n", line)));
+        }
+    }
+    term.render(&changes)?;
+    Ok(())
+}
+
+pub fn debug_overlay(mut term: TermWizTerminal) -> anyhow::Result<()> {
+    term.render(&[Change::Title("Debug".to_string())])?;
+
+    let mut previous: Option<(Snapshot, Instant)> = None;
+    loop {
+        let snapshot = Stats::snapshot()
+            .ok_or_else(|| anyhow::anyhow!("metrics are not collected by this process"))?;
+        let now = Instant::now();
+        render(
+            &mut term,
+            &snapshot,
+            previous
+                .as_ref()
+                .map(|(prior, taken)| (prior, now.duration_since(*taken))),
+        )?;
+        previous.replace((snapshot, now));
+
+        match term.poll_input(Some(REFRESH_INTERVAL)) {
+            Ok(Some(InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            })))
+            | Err(_) => break,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,74 @@
+//! The domain status overlay lists the domains known to the mux along
+//! with their connection state, and allows attaching to a detached
+//! domain or detaching from an attached one.
+use super::prompt::{confirm, select};
+use crate::termwindow::{attach_domain, detach_domain};
+use mux::domain::{DomainId, DomainState};
+use mux::termwiztermtab::TermWizTerminal;
+use termwiz::surface::Change;
+use termwiz::terminal::Terminal;
+
+/// Describes a domain in the status overlay
+pub struct DomainEntry {
+    pub domain_id: DomainId,
+    pub name: String,
+    pub label: String,
+    pub state: DomainState,
+}
+
+impl DomainEntry {
+    fn label(&self) -> String {
+        let state = match self.state {
+            DomainState::Attached => "attached",
+            DomainState::Detached => "detached",
+        };
+        if self.label.is_empty() || self.label == self.name {
+            format!(" {:<9} {}", state, self.name)
+        } else {
+            format!(" {:<9} {} - {}", state, self.name, self.label)
+        }
+    }
+}
+
+pub fn domain_status(mut term: TermWizTerminal, domains: Vec<DomainEntry>) -> anyhow::Result<()> {
+    let labels: Vec<String> = domains.iter().map(DomainEntry::label).collect();
+
+    term.render(&[Change::Title("Domains".to_string())])?;
+
+    let idx = match select(
+        "Select a domain and press Enter to attach or detach it.  \
+         Press Escape to cancel",
+        &labels,
+        0,
+        &mut term,
+    )? {
+        Some(idx) => idx,
+        None => return Ok(()),
+    };
+
+    let domain = &domains[idx];
+    match domain.state {
+        DomainState::Detached => {
+            let domain_id = domain.domain_id;
+            promise::spawn::spawn_into_main_thread(async move {
+                attach_domain(domain_id);
+            })
+            .detach();
+        }
+        DomainState::Attached => {
+            let message = format!(
+                "Detach from domain `{}`?  Its panes will be closed.",
+                domain.name
+            );
+            if confirm(&message, &mut term)? {
+                let domain_id = domain.domain_id;
+                promise::spawn::spawn_into_main_thread(async move {
+                    detach_domain(domain_id);
+                })
+                .detach();
+            }
+        }
+    }
+
+    Ok(())
+}
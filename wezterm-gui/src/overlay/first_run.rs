@@ -0,0 +1,143 @@
+//! The first-run overlay is shown when wezterm is launched without a
+//! config file.  It offers to write a starter config, letting the user
+//! preview and pick a color scheme and font along the way.
+use super::prompt::{confirm, input_line, select_with_preview};
+use crate::termwindow::TermWindow;
+use mux::termwiztermtab::TermWizTerminal;
+use serde_json::json;
+use termwiz::color::ColorAttribute;
+use termwiz::input::InputEvent;
+use termwiz::surface::{Change, CursorVisibility, Position};
+use termwiz::terminal::Terminal;
+
+/// Fonts that are commonly installed and are suitable for use in
+/// a terminal.  The user can type in the name of any other font.
+pub(super) const SUGGESTED_FONTS: &[&str] = &[
+    "JetBrains Mono",
+    "Fira Code",
+    "Cascadia Code",
+    "Hack",
+    "Source Code Pro",
+    "DejaVu Sans Mono",
+    "Menlo",
+    "Consolas",
+];
+
+const OTHER_FONT: &str = "Other...";
+
+pub fn first_run(mut term: TermWizTerminal, window: ::window::Window) -> anyhow::Result<()> {
+    let result = run(&mut term, &window);
+
+    // Remove the preview; if a config was written then it has been
+    // reloaded and now carries the choices that were made.
+    TermWindow::schedule_config_overrides(window, serde_json::Value::Null);
+    config::mark_first_run_done();
+
+    if let Err(err) = &result {
+        show_message(
+            &format!("Failed to write the starter configuration: {:#}", err),
+            &mut term,
+        )?;
+    }
+    result
+}
+
+fn run(term: &mut TermWizTerminal, window: &::window::Window) -> anyhow::Result<()> {
+    if !confirm(
+        "👋 Welcome to WezTerm!  No configuration file was found.  \
+         Would you like to create a starter configuration, choosing \
+         a color scheme and font?",
+        term,
+    )? {
+        return Ok(());
+    }
+
+    let schemes = config::builtin_color_scheme_names();
+    let labels: Vec<String> = schemes.iter().map(|name| name.to_string()).collect();
+    let current = config::configuration().color_scheme.clone();
+    let selected = current
+        .and_then(|current| schemes.iter().position(|name| *name == current))
+        .unwrap_or(0);
+    let scheme = match select_with_preview(
+        "Choose a color scheme (type to filter, Enter to choose, Escape to cancel)",
+        &labels,
+        selected,
+        term,
+        |idx| {
+            TermWindow::schedule_config_overrides(
+                window.clone(),
+                json!({ "color_scheme": schemes[idx] }),
+            )
+        },
+    )? {
+        Some(idx) => schemes[idx],
+        None => return Ok(()),
+    };
+
+    let mut labels: Vec<String> = SUGGESTED_FONTS
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+    labels.push(OTHER_FONT.to_string());
+    let font_overrides = |family: &str| {
+        json!({
+            "color_scheme": scheme,
+            "font": {"font": [{"family": family, "is_fallback": false}]},
+        })
+    };
+    let font = match select_with_preview(
+        "Choose a font (type to filter, Enter to choose, Escape to cancel)",
+        &labels,
+        0,
+        term,
+        |idx| {
+            if let Some(family) = SUGGESTED_FONTS.get(idx) {
+                TermWindow::schedule_config_overrides(window.clone(), font_overrides(family));
+            }
+        },
+    )? {
+        Some(idx) if idx < SUGGESTED_FONTS.len() => SUGGESTED_FONTS[idx].to_string(),
+        Some(_) => match input_line("Enter the name of the font family", "", term)? {
+            Some(family) if !family.trim().is_empty() => family.trim().to_string(),
+            _ => return Ok(()),
+        },
+        None => return Ok(()),
+    };
+
+    let path = config::write_starter_config(scheme, &font)?;
+    config::reload();
+
+    show_message(
+        &format!(
+            "Your configuration was written to {} and {}.  \
+             Edit them to further customize wezterm.",
+            path.display(),
+            path.with_file_name("wezterm.toml").display()
+        ),
+        term,
+    )
+}
+
+/// Shows `message` until a key is pressed
+fn show_message(message: &str, term: &mut TermWizTerminal) -> anyhow::Result<()> {
+    let wrapped = textwrap::fill(message, term.get_screen_size()?.cols.max(20));
+    term.render(&[
+        Change::ClearScreen(ColorAttribute::Default),
+        Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(0),
+        },
+        Change::CursorVisibility(CursorVisibility::Hidden),
+        Change::Text(format!(
+            "{}\r\n\r\nPress any key to continue",
+            wrapped.replace('\n', "\r\n")
+        )),
+    ])?;
+    term.flush()?;
+    while let Ok(Some(event)) = term.poll_input(None) {
+        if let InputEvent::Key(_) = event {
+            break;
+        }
+    }
+    Ok(())
+}
@@ -11,7 +11,7 @@ use crate::termwindow::TermWindow;
 use anyhow::anyhow;
 use config::configuration;
 use config::keyassignment::{SpawnCommand, SpawnTabDomain};
-use mux::domain::{DomainId, DomainState};
+use mux::domain::{DomainId, DomainState, SpawnTarget};
 use mux::tab::TabId;
 use mux::termwiztermtab::TermWizTerminal;
 use mux::window::WindowId;
@@ -98,12 +98,14 @@ fn enumerate_wsl_entries(entries: &mut Vec<Entry>) -> anyhow::Result<()> {
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn launcher(
     _tab_id: TabId,
     domain_id_of_current_tab: DomainId,
     mut term: TermWizTerminal,
     mux_window_id: WindowId,
     domains: Vec<(DomainId, DomainState, String)>,
+    spawn_targets: Vec<(String, SpawnTarget)>,
     clipboard: ClipboardHelper,
     size: PtySize,
 ) -> anyhow::Result<()> {
@@ -163,6 +165,19 @@ pub fn launcher(
         entries.push(entry);
     }
 
+    for (domain_name, target) in spawn_targets {
+        entries.push(Entry::Spawn {
+            label: format!("{} ({})", target.label, domain_name),
+            command: SpawnCommand {
+                label: Some(target.label),
+                args: Some(target.args),
+                domain: SpawnTabDomain::DomainName(domain_name),
+                ..SpawnCommand::default()
+            },
+            spawn_where: SpawnWhere::NewTab,
+        });
+    }
+
     fn render(
         active_idx: usize,
         entries: &[Entry],
@@ -6,20 +6,71 @@ use portable_pty::PtySize;
 use std::pin::Pin;
 use std::rc::Rc;
 
+mod charselect;
 mod confirm_close_pane;
 mod copy;
+mod debug;
+mod domains;
+mod first_run;
 mod launcher;
+mod preview_theme;
+mod prompt;
+mod quickselect;
 mod search;
 mod tabnavigator;
 
+pub use charselect::char_select;
 pub use confirm_close_pane::confirm_close_pane;
 pub use confirm_close_pane::confirm_close_tab;
 pub use confirm_close_pane::confirm_close_window;
 pub use confirm_close_pane::confirm_quit_program;
+pub use confirm_close_pane::confirm_unlock_input;
 pub use copy::CopyOverlay;
+pub use debug::debug_overlay;
+pub use domains::{domain_status, DomainEntry};
+pub use first_run::first_run;
 pub use launcher::launcher;
+pub use preview_theme::preview_theme;
+pub use quickselect::QuickSelectOverlay;
 pub use search::SearchOverlay;
-pub use tabnavigator::tab_navigator;
+pub use tabnavigator::{tab_navigator, TabEntry};
+
+/// Computes a score for how well `query` matches `name`, or None
+/// if it doesn't match at all.  The query characters must appear
+/// in order within the name; runs of consecutive characters and
+/// matches at the start of words score more highly.
+/// Both `query` and `name` are expected to be upper case.
+pub(crate) fn fuzzy_score(query: &str, name: &str) -> Option<usize> {
+    let mut score = 0;
+    let mut run = 0;
+    let mut prior = None;
+    let mut name_chars = name.chars();
+
+    for q in query.chars() {
+        if q == ' ' {
+            run = 0;
+            continue;
+        }
+        loop {
+            let n = name_chars.next()?;
+            let word_start = prior
+                .map(|p: char| p == ' ' || p == '-' || p == '/')
+                .unwrap_or(true);
+            prior = Some(n);
+            if n == q {
+                run += 1;
+                score += run;
+                if word_start {
+                    score += 3;
+                }
+                break;
+            }
+            run = 0;
+        }
+    }
+
+    Some(score)
+}
 
 pub fn start_overlay<T, F>(
     term_window: &TermWindow,
@@ -0,0 +1,123 @@
+//! The PreviewTheme overlay lets the user try out the bundled color
+//! schemes and some common fonts in the current window.  The choices
+//! are applied as per-window config overrides, so the config file is
+//! never changed.
+use super::first_run::SUGGESTED_FONTS;
+use super::prompt::{confirm, select_with_preview};
+use crate::termwindow::TermWindow;
+use mux::termwiztermtab::TermWizTerminal;
+use serde_json::{json, Map, Value};
+
+/// Returns a copy of `overrides` with `key` set to `value`
+fn with_override(overrides: &Value, key: &str, value: Value) -> Value {
+    let mut map = match overrides {
+        Value::Object(map) => map.clone(),
+        _ => Map::new(),
+    };
+    map.insert(key.to_string(), value);
+    Value::Object(map)
+}
+
+fn font_override(family: &str) -> Value {
+    json!({"font": [{"family": family, "is_fallback": false}]})
+}
+
+/// `overrides` are the window's config overrides prior to showing
+/// the overlay; they are restored if the user cancels.
+pub fn preview_theme(
+    mut term: TermWizTerminal,
+    window: ::window::Window,
+    overrides: Value,
+    color_scheme: Option<String>,
+) -> anyhow::Result<()> {
+    if !choose(&mut term, &window, &overrides, color_scheme)? {
+        TermWindow::schedule_config_overrides(window, overrides);
+    }
+    Ok(())
+}
+
+/// Returns true if the user chose to keep the previewed theme
+fn choose(
+    term: &mut TermWizTerminal,
+    window: &::window::Window,
+    overrides: &Value,
+    color_scheme: Option<String>,
+) -> anyhow::Result<bool> {
+    let schemes = config::builtin_color_scheme_names();
+    let labels: Vec<String> = schemes.iter().map(|name| name.to_string()).collect();
+    let selected = color_scheme
+        .and_then(|current| schemes.iter().position(|name| *name == current))
+        .unwrap_or(0);
+    let scheme = match select_with_preview(
+        "Preview a color scheme (type to filter, Enter to choose, Escape to cancel)",
+        &labels,
+        selected,
+        term,
+        |idx| {
+            TermWindow::schedule_config_overrides(
+                window.clone(),
+                with_override(overrides, "color_scheme", json!(schemes[idx])),
+            )
+        },
+    )? {
+        Some(idx) => schemes[idx],
+        None => return Ok(false),
+    };
+    let overrides = with_override(overrides, "color_scheme", json!(scheme));
+
+    let mut labels = vec!["Keep the current font".to_string()];
+    labels.extend(SUGGESTED_FONTS.iter().map(|name| name.to_string()));
+    let font = match select_with_preview(
+        "Preview a font (type to filter, Enter to choose, Escape to cancel)",
+        &labels,
+        0,
+        term,
+        |idx| {
+            let overrides = match idx.checked_sub(1) {
+                Some(font_idx) => {
+                    with_override(&overrides, "font", font_override(SUGGESTED_FONTS[font_idx]))
+                }
+                None => overrides.clone(),
+            };
+            TermWindow::schedule_config_overrides(window.clone(), overrides);
+        },
+    )? {
+        Some(idx) => idx.checked_sub(1).map(|font_idx| SUGGESTED_FONTS[font_idx]),
+        None => return Ok(false),
+    };
+
+    let message = match font {
+        Some(font) => format!(
+            "Keep using {} with {} in this window?  \
+             Your configuration file will not be changed.",
+            scheme, font
+        ),
+        None => format!(
+            "Keep using {} in this window?  \
+             Your configuration file will not be changed.",
+            scheme
+        ),
+    };
+    confirm(&message, term)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn overrides() {
+        assert_eq!(
+            with_override(&Value::Null, "color_scheme", json!("Dracula")),
+            json!({"color_scheme": "Dracula"})
+        );
+        assert_eq!(
+            with_override(
+                &json!({"font_size": 14, "color_scheme": "Dracula"}),
+                "color_scheme",
+                json!("Gruvbox Dark")
+            ),
+            json!({"font_size": 14, "color_scheme": "Gruvbox Dark"})
+        );
+    }
+}
@@ -0,0 +1,457 @@
+//! Reusable prompts for overlays that need to ask the user something:
+//! a yes/no confirmation, a line of text, or a choice from a list.
+//! Each of these takes over the overlay terminal until the user has
+//! answered or cancelled, and is intended to be called from the
+//! function passed to `start_overlay` or `start_overlay_pane`.
+use super::fuzzy_score;
+use mux::termwiztermtab::TermWizTerminal;
+use termwiz::cell::{AttributeChange, CellAttributes};
+use termwiz::color::ColorAttribute;
+use termwiz::input::{InputEvent, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseEvent};
+use termwiz::surface::{Change, CursorVisibility, Position};
+use termwiz::terminal::Terminal;
+
+/// Asks the user to confirm `message`.
+/// Returns true if they chose yes, and false if they chose no
+/// or cancelled.
+pub fn confirm(message: &str, term: &mut TermWizTerminal) -> anyhow::Result<bool> {
+    term.set_raw_mode()?;
+
+    let size = term.get_screen_size()?;
+
+    // Render 80% wide, centered
+    let text_width = size.cols * 80 / 100;
+    let x_pos = size.cols * 10 / 100;
+
+    // Fit text to the width
+    let wrapped = textwrap::fill(message, text_width);
+
+    let message_rows = wrapped.split("\n").count();
+    // Now we want to vertically center the prompt in the view.
+    // After the prompt there will be a blank line and then the "buttons",
+    // so we add two to the number of rows.
+    let top_row = size.rows.saturating_sub(message_rows + 2) / 2;
+
+    let button_row = top_row + message_rows + 1;
+    let mut active = ActiveButton::None;
+
+    #[derive(Copy, Clone, PartialEq, Eq)]
+    enum ActiveButton {
+        None,
+        Yes,
+        No,
+    }
+
+    let render = |term: &mut TermWizTerminal, active: ActiveButton| -> termwiz::Result<()> {
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorVisibility(CursorVisibility::Hidden),
+        ];
+
+        for (y, row) in wrapped.split("\n").enumerate() {
+            let row = row.trim_end();
+            changes.push(Change::CursorPosition {
+                x: Position::Absolute(x_pos),
+                y: Position::Absolute(top_row + y),
+            });
+            changes.push(Change::Text(row.to_string()));
+        }
+
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(x_pos),
+            y: Position::Absolute(button_row),
+        });
+
+        if active == ActiveButton::Yes {
+            changes.push(AttributeChange::Reverse(true).into());
+        }
+        changes.push(" [Y]es ".into());
+        if active == ActiveButton::Yes {
+            changes.push(AttributeChange::Reverse(false).into());
+        }
+
+        changes.push("        ".into());
+
+        if active == ActiveButton::No {
+            changes.push(AttributeChange::Reverse(true).into());
+        }
+        changes.push(" [N]o ".into());
+        if active == ActiveButton::No {
+            changes.push(AttributeChange::Reverse(false).into());
+        }
+
+        term.render(&changes)?;
+        term.flush()
+    };
+
+    render(term, active)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('y'),
+                ..
+            }) => {
+                return Ok(true);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('n'),
+                ..
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }) => {
+                return Ok(false);
+            }
+            InputEvent::Mouse(MouseEvent {
+                x,
+                y,
+                mouse_buttons,
+                ..
+            }) => {
+                let x = x as usize;
+                let y = y as usize;
+                if y == button_row && x >= x_pos && x <= x_pos + 7 {
+                    active = ActiveButton::Yes;
+                    if mouse_buttons == MouseButtons::LEFT {
+                        return Ok(true);
+                    }
+                } else if y == button_row && x >= x_pos + 14 && x <= x_pos + 22 {
+                    active = ActiveButton::No;
+                    if mouse_buttons == MouseButtons::LEFT {
+                        return Ok(false);
+                    }
+                } else {
+                    active = ActiveButton::None;
+                }
+
+                if mouse_buttons != MouseButtons::NONE {
+                    // Treat any other mouse button as cancel
+                    return Ok(false);
+                }
+            }
+            _ => {}
+        }
+
+        render(term, active)?;
+    }
+
+    Ok(false)
+}
+
+/// Asks the user to enter a line of text, beginning with `initial`.
+/// Returns None if they cancelled.
+pub fn input_line(
+    message: &str,
+    initial: &str,
+    term: &mut TermWizTerminal,
+) -> anyhow::Result<Option<String>> {
+    term.set_raw_mode()?;
+
+    let mut line = initial.to_string();
+
+    let render = |term: &mut TermWizTerminal, line: &str| -> termwiz::Result<()> {
+        term.render(&[
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(0),
+            },
+            Change::Text(format!(
+                "{}\r\nPress Enter to accept or Escape to cancel\r\n\r\n> {}",
+                message, line
+            )),
+            Change::CursorVisibility(CursorVisibility::Visible),
+        ])?;
+        term.flush()
+    };
+
+    render(term, &line)?;
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }) => {
+                return Ok(None);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                ..
+            }) => {
+                return Ok(Some(line));
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Backspace,
+                ..
+            }) => {
+                line.pop();
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char('u'),
+                modifiers: Modifiers::CTRL,
+            }) => {
+                line.clear();
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::NONE,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::SHIFT,
+            }) => {
+                line.push(c);
+            }
+            InputEvent::Paste(text) => {
+                line.push_str(&text.replace(|c| c == '\r' || c == '\n', " "));
+            }
+            _ => {}
+        }
+
+        render(term, &line)?;
+    }
+
+    Ok(None)
+}
+
+/// Returns the indices of the items whose labels fuzzily match `query`,
+/// best match first, or all of the items in order if the query is empty.
+fn filter(query: &str, labels: &[String]) -> Vec<usize> {
+    let query = query.trim().to_uppercase();
+    if query.is_empty() {
+        return (0..labels.len()).collect();
+    }
+
+    let mut scored: Vec<(usize, usize)> = labels
+        .iter()
+        .enumerate()
+        .filter_map(|(idx, label)| {
+            fuzzy_score(&query, &label.to_uppercase()).map(|score| (score, idx))
+        })
+        .collect();
+    // Highest score first; ties keep the original order
+    scored
+        .sort_by(|(a_score, a_idx), (b_score, b_idx)| b_score.cmp(a_score).then(a_idx.cmp(b_idx)));
+    scored.into_iter().map(|(_, idx)| idx).collect()
+}
+
+/// Asks the user to choose one of `labels`, which can be filtered
+/// by typing.  Until a filter has been typed, the digits 1-9 choose
+/// the item at that position.  `selected` is the index of the item
+/// that is initially selected.
+/// Returns the index of the chosen item, or None if they cancelled.
+pub fn select(
+    heading: &str,
+    labels: &[String],
+    selected: usize,
+    term: &mut TermWizTerminal,
+) -> anyhow::Result<Option<usize>> {
+    select_with_preview(heading, labels, selected, term, |_| {})
+}
+
+/// Like `select`, but calls `preview` with the index of the highlighted
+/// item each time that the highlight moves to a different item, so
+/// that the effect of choosing it can be shown before it is chosen.
+pub fn select_with_preview(
+    heading: &str,
+    labels: &[String],
+    mut selected: usize,
+    term: &mut TermWizTerminal,
+    mut preview: impl FnMut(usize),
+) -> anyhow::Result<Option<usize>> {
+    let mut query = String::new();
+    let mut matching = filter(&query, labels);
+    let mut top_row = 0;
+
+    term.set_raw_mode()?;
+
+    // One row for the heading and one for the filter line
+    let max_rows = term.get_screen_size()?.rows.saturating_sub(2).max(1);
+
+    let render = |query: &str,
+                  matching: &[usize],
+                  selected: usize,
+                  top_row: usize,
+                  term: &mut TermWizTerminal|
+     -> termwiz::Result<()> {
+        let mut changes = vec![
+            Change::ClearScreen(ColorAttribute::Default),
+            Change::CursorPosition {
+                x: Position::Absolute(0),
+                y: Position::Absolute(0),
+            },
+            Change::Text(format!("{}\r\n", heading)),
+            Change::AllAttributes(CellAttributes::default()),
+        ];
+
+        for (row, idx) in matching.iter().enumerate().skip(top_row).take(max_rows) {
+            if row == selected {
+                changes.push(AttributeChange::Reverse(true).into());
+            }
+
+            changes.push(Change::Text(format!("{}\r\n", labels[*idx])));
+
+            if row == selected {
+                changes.push(AttributeChange::Reverse(false).into());
+            }
+        }
+
+        changes.push(Change::CursorPosition {
+            x: Position::Absolute(0),
+            y: Position::Absolute(max_rows + 1),
+        });
+        changes.push(Change::Text(format!(
+            "{} of {}. Filter: {}",
+            matching.len(),
+            labels.len(),
+            query
+        )));
+
+        term.render(&changes)?;
+        term.flush()
+    };
+
+    selected = selected.min(labels.len().saturating_sub(1));
+    if selected >= max_rows {
+        top_row = selected + 1 - max_rows;
+    }
+    render(&query, &matching, selected, top_row, term)?;
+
+    let mut previewed = matching.get(selected).copied();
+    if let Some(idx) = previewed {
+        preview(idx);
+    }
+
+    while let Ok(Some(event)) = term.poll_input(None) {
+        match event {
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::UpArrow,
+                ..
+            }) => {
+                selected = selected.saturating_sub(1);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::DownArrow,
+                ..
+            }) => {
+                selected = (selected + 1).min(matching.len().saturating_sub(1));
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::PageUp,
+                ..
+            }) => {
+                selected = selected.saturating_sub(max_rows);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::PageDown,
+                ..
+            }) => {
+                selected = (selected + max_rows).min(matching.len().saturating_sub(1));
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Escape,
+                ..
+            }) => {
+                return Ok(None);
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Backspace,
+                ..
+            }) => {
+                query.pop();
+                matching = filter(&query, labels);
+                selected = 0;
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::NONE,
+            }) if query.is_empty() && c >= '1' && c <= '9' => {
+                let idx = c as usize - '1' as usize;
+                if idx < labels.len() {
+                    return Ok(Some(idx));
+                }
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::NONE,
+            })
+            | InputEvent::Key(KeyEvent {
+                key: KeyCode::Char(c),
+                modifiers: Modifiers::SHIFT,
+            }) => {
+                query.push(c);
+                matching = filter(&query, labels);
+                selected = 0;
+            }
+            InputEvent::Mouse(MouseEvent {
+                y, mouse_buttons, ..
+            }) => {
+                let row = top_row + y as usize;
+                if y > 0 && y as usize <= max_rows && row <= matching.len() {
+                    selected = row - 1;
+
+                    if mouse_buttons == MouseButtons::LEFT {
+                        return Ok(Some(matching[selected]));
+                    }
+                }
+                if mouse_buttons != MouseButtons::NONE {
+                    // Treat any other mouse button as cancel
+                    return Ok(None);
+                }
+            }
+            InputEvent::Key(KeyEvent {
+                key: KeyCode::Enter,
+                ..
+            }) => {
+                return Ok(matching.get(selected).copied());
+            }
+            _ => {}
+        }
+
+        // Keep the selection visible
+        if selected < top_row {
+            top_row = selected;
+        } else if selected >= top_row + max_rows {
+            top_row = selected + 1 - max_rows;
+        }
+
+        let highlighted = matching.get(selected).copied();
+        if highlighted != previewed {
+            previewed = highlighted;
+            if let Some(idx) = highlighted {
+                preview(idx);
+            }
+        }
+
+        render(&query, &matching, selected, top_row, term)?;
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn filtering() {
+        let labels: Vec<String> = vec![
+            " 1. vim  [local]  ~/src/wezterm",
+            " 2. htop  [myserver]",
+            " 3. zsh  [local]  ~/Downloads",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+
+        assert_eq!(filter("", &labels), vec![0, 1, 2]);
+        assert_eq!(filter("top", &labels), vec![1]);
+        assert_eq!(filter("myser", &labels), vec![1]);
+        assert_eq!(filter("wezterm", &labels), vec![0]);
+        assert_eq!(filter("down", &labels), vec![2]);
+        assert!(filter("nomatch", &labels).is_empty());
+    }
+}
@@ -0,0 +1,479 @@
+//! Quick select mode scans the visible lines of a pane for text that
+//! is commonly copied, such as paths, urls and git hashes, and labels
+//! each match with a short sequence of letters.  Typing a label copies
+//! the match to the clipboard; typing it in upper case also pastes it
+//! into the pane.
+use crate::termwindow::TermWindow;
+use config::keyassignment::{ClipboardCopyDestination, ScrollbackEraseMode};
+use mux::domain::DomainId;
+use mux::pane::{Pane, PaneId};
+use mux::renderable::*;
+use portable_pty::PtySize;
+use rangeset::RangeSet;
+use regex::Regex;
+use std::cell::{RefCell, RefMut};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::rc::Rc;
+use std::sync::Arc;
+use termwiz::cell::CellAttributes;
+use termwiz::color::AnsiColor;
+use url::Url;
+use wezterm_term::color::ColorPalette;
+use wezterm_term::{Clipboard, KeyCode, KeyModifiers, Line, MouseEvent, StableRowIndex};
+use window::WindowOps;
+
+/// The letters that are used to build labels, easiest to reach first
+const ALPHABET: &str = "asdfqwerzxcvjklmiuopghtybn";
+
+/// The patterns that are matched unless
+/// `disable_default_quick_select_patterns` is set
+const DEFAULT_PATTERNS: &[&str] = &[
+    // markdown_url
+    r"\[[^\]]*\]\(([^)]+)\)",
+    // url
+    r"(?:https?://|git@|git://|ssh://|ftp://|file:///)\S+",
+    // diff_a and diff_b
+    r"--- a/(\S+)",
+    r"\+\+\+ b/(\S+)",
+    // docker
+    r"sha256:([0-9a-f]{64})",
+    // path
+    r"(?:[.\w\-@~]+)?(?:/[.\w\-@]+)+",
+    // color
+    r"#[0-9a-fA-F]{6}",
+    // uuid
+    r"[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}",
+    // ipfs
+    r"Qm[0-9a-zA-Z]{44}",
+    // sha
+    r"\b[0-9a-f]{7,40}\b",
+    // ip
+    r"\d{1,3}\.\d{1,3}\.\d{1,3}\.\d{1,3}",
+    // ipv6
+    r"[a-fA-F0-9:]+:+[a-fA-F0-9:]+[%\w\d]+",
+    // address
+    r"0x[0-9a-fA-F]+",
+    // number
+    r"[0-9]{4,}",
+];
+
+pub struct QuickSelectOverlay {
+    renderer: RefCell<QuickSelectRenderable>,
+    delegate: Rc<dyn Pane>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MatchResult {
+    label: String,
+    text: String,
+    /// The row and the range of columns that the match occupies
+    y: StableRowIndex,
+    range: Range<usize>,
+}
+
+struct QuickSelectRenderable {
+    delegate: Rc<dyn Pane>,
+    matches: Vec<MatchResult>,
+    /// The part of a label that has been typed so far
+    typed: String,
+    dirty_results: RangeSet<StableRowIndex>,
+    /// We use this to cancel ourselves later
+    window: ::window::Window,
+}
+
+impl QuickSelectOverlay {
+    pub fn with_pane(term_window: &TermWindow, pane: &Rc<dyn Pane>) -> Rc<dyn Pane> {
+        let dims = pane.get_dimensions();
+        let top = term_window
+            .get_viewport(pane.pane_id())
+            .unwrap_or(dims.physical_top);
+        let (top, lines) = pane.get_lines(top..top + dims.viewport_rows as StableRowIndex);
+
+        let config = &term_window.config;
+        let mut patterns = vec![];
+        if !config.disable_default_quick_select_patterns {
+            patterns.extend(DEFAULT_PATTERNS.iter().map(|p| p.to_string()));
+        }
+        patterns.extend(config.quick_select_patterns.iter().cloned());
+        let regexes: Vec<Regex> = patterns
+            .iter()
+            .filter_map(|pattern| match Regex::new(pattern) {
+                Ok(re) => Some(re),
+                Err(err) => {
+                    log::error!("invalid quick_select_patterns entry {}: {}", pattern, err);
+                    None
+                }
+            })
+            .collect();
+
+        let matches = find_matches(top, &lines, &regexes);
+        let mut dirty_results = RangeSet::default();
+        for m in &matches {
+            dirty_results.add(m.y);
+        }
+
+        let window = term_window.window.clone().unwrap();
+        let renderer = QuickSelectRenderable {
+            delegate: Rc::clone(pane),
+            matches,
+            typed: String::new(),
+            dirty_results,
+            window,
+        };
+
+        Rc::new(QuickSelectOverlay {
+            renderer: RefCell::new(renderer),
+            delegate: Rc::clone(pane),
+        })
+    }
+}
+
+/// Returns the text of `line` along with the column of the cell that
+/// holds each of its bytes
+fn line_text_and_columns(line: &Line) -> (String, Vec<usize>) {
+    let mut text = String::new();
+    let mut columns = vec![];
+    for (col, cell) in line.visible_cells() {
+        text.push_str(cell.str());
+        columns.resize(text.len(), col);
+    }
+    (text, columns)
+}
+
+/// Returns labels for `n` matches.  The labels are all the same length
+/// so that none of them is a prefix of another.
+fn compute_labels(n: usize) -> Vec<String> {
+    let alphabet: Vec<char> = ALPHABET.chars().collect();
+    if n <= alphabet.len() {
+        return alphabet.iter().take(n).map(|c| c.to_string()).collect();
+    }
+    let mut labels = vec![];
+    'outer: for a in &alphabet {
+        for b in &alphabet {
+            if labels.len() == n {
+                break 'outer;
+            }
+            labels.push(format!("{}{}", a, b));
+        }
+    }
+    labels
+}
+
+/// Finds the matches for `regexes` in `lines`, the first of which is
+/// the stable row `top`.  If a regex has a capture group, then the first
+/// capture is used as the text of the match.  Where matches overlap, the
+/// one that starts first, or the longest of those, is kept.
+/// Matches of the same text share the same label.
+fn find_matches(top: StableRowIndex, lines: &[Line], regexes: &[Regex]) -> Vec<MatchResult> {
+    let mut found = vec![];
+    for (idx, line) in lines.iter().enumerate() {
+        let (text, columns) = line_text_and_columns(line);
+        let mut spans: Vec<Range<usize>> = vec![];
+        for re in regexes {
+            for captures in re.captures_iter(&text) {
+                let m = match captures.get(1).or_else(|| captures.get(0)) {
+                    Some(m) if !m.as_str().trim().is_empty() => m,
+                    _ => continue,
+                };
+                spans.push(m.start()..m.end());
+            }
+        }
+        spans.sort_by(|a, b| a.start.cmp(&b.start).then(b.end.cmp(&a.end)));
+
+        let mut last_end = 0;
+        for span in spans {
+            if span.start < last_end {
+                continue;
+            }
+            last_end = span.end;
+            let start_col = columns[span.start];
+            let end_col = columns[span.end - 1] + 1;
+            found.push((
+                idx as StableRowIndex + top,
+                start_col..end_col,
+                text[span].to_string(),
+            ));
+        }
+    }
+
+    let mut distinct: HashMap<&str, usize> = HashMap::new();
+    for (_, _, text) in &found {
+        let next = distinct.len();
+        distinct.entry(text.as_str()).or_insert(next);
+    }
+    let labels = compute_labels(distinct.len());
+
+    found
+        .iter()
+        .filter_map(|(y, range, text)| {
+            let label = labels.get(distinct[text.as_str()])?;
+            Some(MatchResult {
+                label: label.clone(),
+                text: text.clone(),
+                y: *y,
+                range: range.clone(),
+            })
+        })
+        .collect()
+}
+
+impl Pane for QuickSelectOverlay {
+    fn pane_id(&self) -> PaneId {
+        self.delegate.pane_id()
+    }
+
+    fn get_title(&self) -> String {
+        format!("Quick select: {}", self.delegate.get_title())
+    }
+
+    fn send_paste(&self, _text: &str) -> anyhow::Result<()> {
+        anyhow::bail!("ignoring paste while quick selecting");
+    }
+
+    fn reader(&self) -> anyhow::Result<Box<dyn std::io::Read + Send>> {
+        panic!("do not call reader on QuickSelectOverlay bar tab instance");
+    }
+
+    fn writer(&self) -> RefMut<dyn std::io::Write> {
+        self.delegate.writer()
+    }
+
+    fn resize(&self, size: PtySize) -> anyhow::Result<()> {
+        self.delegate.resize(size)
+    }
+
+    fn key_down(&self, key: KeyCode, mods: KeyModifiers) -> anyhow::Result<()> {
+        match (key, mods) {
+            (KeyCode::Escape, KeyModifiers::NONE)
+            | (KeyCode::Char('c'), KeyModifiers::CTRL)
+            | (KeyCode::Char('g'), KeyModifiers::CTRL) => self.renderer.borrow().close(),
+            (KeyCode::Backspace, KeyModifiers::NONE) => {
+                let mut r = self.renderer.borrow_mut();
+                r.typed.pop();
+                r.mark_dirty();
+            }
+            (KeyCode::Char(c), KeyModifiers::NONE) | (KeyCode::Char(c), KeyModifiers::SHIFT) => {
+                let paste = c.is_uppercase();
+                let chosen = self.renderer.borrow_mut().type_char(c.to_ascii_lowercase());
+                if let Some(text) = chosen {
+                    let r = self.renderer.borrow();
+                    r.copy(text.clone());
+                    r.close();
+                    if paste {
+                        self.delegate.send_paste(&text)?;
+                    }
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn mouse_event(&self, event: MouseEvent) -> anyhow::Result<()> {
+        self.delegate.mouse_event(event)
+    }
+
+    fn advance_bytes(&self, buf: &[u8]) {
+        self.delegate.advance_bytes(buf)
+    }
+
+    fn is_dead(&self) -> bool {
+        self.delegate.is_dead()
+    }
+
+    fn palette(&self) -> ColorPalette {
+        self.delegate.palette()
+    }
+
+    fn domain_id(&self) -> DomainId {
+        self.delegate.domain_id()
+    }
+
+    fn erase_scrollback(&self, erase_mode: ScrollbackEraseMode) {
+        self.delegate.erase_scrollback(erase_mode)
+    }
+
+    fn is_mouse_grabbed(&self) -> bool {
+        false
+    }
+
+    fn is_alt_screen_active(&self) -> bool {
+        self.delegate.is_alt_screen_active()
+    }
+
+    fn set_clipboard(&self, clipboard: &Arc<dyn Clipboard>) {
+        self.delegate.set_clipboard(clipboard)
+    }
+
+    fn get_current_working_dir(&self) -> Option<Url> {
+        self.delegate.get_current_working_dir()
+    }
+
+    fn get_foreground_process_name(&self) -> Option<String> {
+        self.delegate.get_foreground_process_name()
+    }
+
+    fn is_password_input(&self) -> bool {
+        self.delegate.is_password_input()
+    }
+
+    fn get_cursor_position(&self) -> StableCursorPosition {
+        self.delegate.get_cursor_position()
+    }
+
+    fn get_dirty_lines(&self, lines: Range<StableRowIndex>) -> RangeSet<StableRowIndex> {
+        let mut dirty = self.delegate.get_dirty_lines(lines.clone());
+        dirty.add_set(&self.renderer.borrow().dirty_results);
+        dirty.intersection_with_range(lines)
+    }
+
+    fn get_lines(&self, lines: Range<StableRowIndex>) -> (StableRowIndex, Vec<Line>) {
+        let mut renderer = self.renderer.borrow_mut();
+        let (top, mut lines) = self.delegate.get_lines(lines);
+
+        for (idx, line) in lines.iter_mut().enumerate() {
+            let stable_idx = idx as StableRowIndex + top;
+            renderer.dirty_results.remove(stable_idx);
+            for m in renderer.matches.iter().filter(|m| m.y == stable_idx) {
+                let candidate = m.label.starts_with(&renderer.typed);
+                for cell_idx in m.range.clone() {
+                    if let Some(cell) = line.cells_mut_for_attr_changes_only().get_mut(cell_idx) {
+                        let attrs = cell.attrs_mut();
+                        if candidate {
+                            attrs.set_foreground(AnsiColor::Green);
+                        } else {
+                            attrs.set_foreground(AnsiColor::Grey);
+                        }
+                        attrs.set_reverse(false);
+                    }
+                }
+                if candidate {
+                    let label_attrs = CellAttributes::default()
+                        .set_background(AnsiColor::Yellow)
+                        .set_foreground(AnsiColor::Black)
+                        .clone();
+                    line.overlay_text_with_attribute(m.range.start, &m.label, label_attrs);
+                }
+            }
+        }
+
+        (top, lines)
+    }
+
+    fn get_dimensions(&self) -> RenderableDimensions {
+        self.delegate.get_dimensions()
+    }
+}
+
+impl QuickSelectRenderable {
+    fn close(&self) {
+        TermWindow::schedule_cancel_overlay_for_pane(self.window.clone(), self.delegate.pane_id());
+    }
+
+    fn mark_dirty(&mut self) {
+        for m in &self.matches {
+            self.dirty_results.add(m.y);
+        }
+        self.window.invalidate();
+    }
+
+    /// Adds `c` to the typed label.  Returns the text of the match
+    /// if that completes a label.  If the typed text isn't the start
+    /// of any label, then it is discarded so that the user can start
+    /// over.
+    fn type_char(&mut self, c: char) -> Option<String> {
+        self.typed.push(c);
+        if let Some(m) = self.matches.iter().find(|m| m.label == self.typed) {
+            return Some(m.text.clone());
+        }
+        if !self
+            .matches
+            .iter()
+            .any(|m| m.label.starts_with(&self.typed))
+        {
+            self.typed.clear();
+        }
+        self.mark_dirty();
+        None
+    }
+
+    fn copy(&self, text: String) {
+        self.window.apply(move |term_window, _window| {
+            if let Some(term_window) = term_window.downcast_mut::<TermWindow>() {
+                term_window.copy_to_clipboard(
+                    ClipboardCopyDestination::ClipboardAndPrimarySelection,
+                    text.clone(),
+                );
+            }
+            Ok(())
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn labels() {
+        assert_eq!(compute_labels(3), vec!["a", "s", "d"]);
+        let labels = compute_labels(30);
+        assert_eq!(labels.len(), 30);
+        assert_eq!(&labels[0..2], &["aa", "as"]);
+        assert!(labels.iter().all(|l| l.len() == 2));
+    }
+
+    #[test]
+    fn matching() {
+        let lines = vec![
+            Line::from_text("commit 0123abcd in /tmp/foo", &CellAttributes::default()),
+            Line::from_text("see 0123abcd", &CellAttributes::default()),
+        ];
+        let regexes: Vec<Regex> = DEFAULT_PATTERNS
+            .iter()
+            .map(|p| Regex::new(p).unwrap())
+            .collect();
+        let matches = find_matches(10, &lines, &regexes);
+        assert_eq!(
+            matches,
+            vec![
+                MatchResult {
+                    label: "a".to_string(),
+                    text: "0123abcd".to_string(),
+                    y: 10,
+                    range: 7..15,
+                },
+                MatchResult {
+                    label: "s".to_string(),
+                    text: "/tmp/foo".to_string(),
+                    y: 10,
+                    range: 19..27,
+                },
+                MatchResult {
+                    label: "a".to_string(),
+                    text: "0123abcd".to_string(),
+                    y: 11,
+                    range: 4..12,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn default_patterns() {
+        let lines = vec![Line::from_text(
+            "deadbeef1 xdeadbeefcafe fe80::1ff:fe23:4567:890a",
+            &CellAttributes::default(),
+        )];
+        let regexes: Vec<Regex> = DEFAULT_PATTERNS
+            .iter()
+            .map(|p| Regex::new(p).unwrap())
+            .collect();
+        let matches: Vec<String> = find_matches(0, &lines, &regexes)
+            .into_iter()
+            .map(|m| m.text)
+            .collect();
+        assert_eq!(matches, vec!["deadbeef1", "fe80::1ff:fe23:4567:890a"]);
+    }
+}
@@ -263,6 +263,14 @@ impl Pane for SearchOverlay {
         self.delegate.get_current_working_dir()
     }
 
+    fn get_foreground_process_name(&self) -> Option<String> {
+        self.delegate.get_foreground_process_name()
+    }
+
+    fn is_password_input(&self) -> bool {
+        self.delegate.is_password_input()
+    }
+
     fn get_cursor_position(&self) -> StableCursorPosition {
         // move to the search box
         let renderer = self.renderer.borrow();
@@ -0,0 +1,668 @@
+//! Persists the scrollback of panes to disk, when enabled by the
+//! `persist_scrollback` option or the TogglePersistScrollback key
+//! assignment, so that it can be browsed in a recovered tab after
+//! wezterm crashes or is restarted.
+//!
+//! The content of each pane is held in a pair of files in the
+//! `scrollback` directory beneath the data directory, named for the
+//! pid of this process and the id of the pane.  The lines that scroll
+//! out of the viewport are appended to the `.lines` file, while the
+//! `.screen` file is replaced with the title and the content of the
+//! viewport each time that the pane is persisted.  Both files are
+//! sequences of records, each of which may be encrypted.
+//! Once the `.lines` file holds twice `scrollback_lines` lines, it is
+//! compacted down to the most recent `scrollback_lines` of them.
+//! The files are removed when the pane is closed, so only the panes
+//! that were open when wezterm exited can be recovered.
+use anyhow::{anyhow, bail, Context};
+use config::configuration;
+use mux::pane::{Pane, PaneId};
+use mux::recovered::RecoveredPane;
+use mux::tab::{Tab, TabId};
+use mux::{Mux, MuxNotification};
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+use termwiz::surface::Line;
+use wezterm_term::StableRowIndex;
+
+/// How often the panes that have produced output are persisted
+const PERSIST_INTERVAL: Duration = Duration::from_secs(5);
+
+const KEY_LEN: usize = 32;
+const IV_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+const RECORD_PLAIN: u8 = 0;
+const RECORD_ENCRYPTED: u8 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Screen {
+    title: String,
+    cwd: Option<String>,
+    lines: Vec<Line>,
+}
+
+enum Job {
+    Append {
+        path: PathBuf,
+        lines: Vec<Line>,
+        key: Option<Arc<Vec<u8>>>,
+    },
+    Replace {
+        path: PathBuf,
+        screen: Screen,
+        key: Option<Arc<Vec<u8>>>,
+    },
+    /// Rewrites the file, keeping only the last `keep` lines
+    Compact {
+        path: PathBuf,
+        keep: usize,
+        key: Option<Arc<Vec<u8>>>,
+    },
+    Remove {
+        name: String,
+    },
+}
+
+struct PersistedPane {
+    name: String,
+    /// The first row that has not yet been appended to the `.lines` file
+    next_row: StableRowIndex,
+    /// The number of lines in the `.lines` file
+    num_lines: usize,
+}
+
+#[derive(Default)]
+struct State {
+    panes: HashMap<PaneId, PersistedPane>,
+    /// The panes that have produced output since they were last persisted
+    dirty: HashSet<PaneId>,
+    /// The tabs for which `persist_scrollback` is inverted
+    toggled_tabs: HashSet<TabId>,
+    key: Option<(PathBuf, Arc<Vec<u8>>)>,
+    jobs: Option<Sender<Job>>,
+}
+
+thread_local! {
+    static STATE: RefCell<State> = RefCell::new(State::default());
+}
+
+fn scrollback_dir() -> PathBuf {
+    config::path_with_legacy_fallback(
+        config::STATE_DIR.join("scrollback"),
+        config::DATA_DIR.join("scrollback"),
+    )
+}
+
+/// Starts persisting the panes in the mux
+pub fn start() {
+    let (tx, rx) = channel();
+    std::thread::spawn(move || {
+        while let Ok(job) = rx.recv() {
+            if let Err(err) = run_job(job) {
+                log::error!("Failed to persist scrollback: {:#}", err);
+            }
+        }
+    });
+    STATE.with(|state| state.borrow_mut().jobs.replace(tx));
+
+    let mux = Mux::get().unwrap();
+    mux.subscribe(|n| {
+        match n {
+            MuxNotification::PaneOutput(pane_id) => {
+                STATE.with(|state| state.borrow_mut().dirty.insert(pane_id));
+            }
+            MuxNotification::PaneRemoved(pane_id) => forget_pane(pane_id),
+            _ => {}
+        }
+        true
+    });
+
+    promise::spawn::spawn(async {
+        loop {
+            smol::Timer::after(PERSIST_INTERVAL).await;
+            persist_panes();
+        }
+    })
+    .detach();
+}
+
+/// Inverts whether the panes in the tab are persisted
+pub fn toggle_tab(tab_id: TabId) {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        if !state.toggled_tabs.remove(&tab_id) {
+            state.toggled_tabs.insert(tab_id);
+        }
+    });
+    let mux = Mux::get().unwrap();
+    if let Some(tab) = mux.get_tab(tab_id) {
+        for pos in tab.iter_panes() {
+            let pane_id = pos.pane.pane_id();
+            if should_persist(&mux, &pos.pane) {
+                // Persist it promptly, rather than waiting for output
+                STATE.with(|state| state.borrow_mut().dirty.insert(pane_id));
+                log::info!("Persisting the scrollback of pane {}", pane_id);
+            } else {
+                forget_pane(pane_id);
+                log::info!("No longer persisting the scrollback of pane {}", pane_id);
+            }
+        }
+    }
+}
+
+fn should_persist(mux: &Rc<Mux>, pane: &Rc<dyn Pane>) -> bool {
+    if pane.downcast_ref::<RecoveredPane>().is_some() {
+        return false;
+    }
+    let tab_id = match mux.resolve_pane_id(pane.pane_id()) {
+        Some((_domain_id, _window_id, tab_id)) => tab_id,
+        None => return false,
+    };
+    let toggled = STATE.with(|state| state.borrow().toggled_tabs.contains(&tab_id));
+    configuration().persist_scrollback != toggled
+}
+
+/// Stops persisting the pane and removes its files
+fn forget_pane(pane_id: PaneId) {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        state.dirty.remove(&pane_id);
+        if let Some(persisted) = state.panes.remove(&pane_id) {
+            if let Some(jobs) = state.jobs.as_ref() {
+                jobs.send(Job::Remove {
+                    name: persisted.name,
+                })
+                .ok();
+            }
+        }
+    });
+}
+
+/// Returns the encryption key, if one is configured, loading it
+/// if the configured path has changed
+fn encryption_key() -> anyhow::Result<Option<Arc<Vec<u8>>>> {
+    let path = match configuration().persist_scrollback_key_file.clone() {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        if let Some((key_path, key)) = state.key.as_ref() {
+            if *key_path == path {
+                return Ok(Some(Arc::clone(key)));
+            }
+        }
+        let key = Arc::new(load_or_create_key(&path)?);
+        state.key.replace((path, Arc::clone(&key)));
+        Ok(Some(key))
+    })
+}
+
+fn load_or_create_key(path: &Path) -> anyhow::Result<Vec<u8>> {
+    if path.exists() {
+        return load_key(path);
+    }
+
+    let mut key = vec![0u8; KEY_LEN];
+    openssl::rand::rand_bytes(&mut key)?;
+    if let Some(parent) = path.parent() {
+        config::create_user_owned_dirs(parent)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(path)
+        .and_then(|mut file| file.write_all(&key))
+        .with_context(|| format!("creating scrollback key file {}", path.display()))?;
+    Ok(key)
+}
+
+fn load_key(path: &Path) -> anyhow::Result<Vec<u8>> {
+    let key = std::fs::read(path)
+        .with_context(|| format!("reading scrollback key file {}", path.display()))?;
+    if key.len() != KEY_LEN {
+        bail!(
+            "scrollback key file {} must hold exactly {} bytes",
+            path.display(),
+            KEY_LEN
+        );
+    }
+    Ok(key)
+}
+
+fn persist_panes() {
+    let key = match encryption_key() {
+        Ok(key) => key,
+        Err(err) => {
+            log::error!("Not persisting scrollback: {:#}", err);
+            return;
+        }
+    };
+
+    let mux = Mux::get().unwrap();
+    for pane in mux.iter_panes() {
+        let pane_id = pane.pane_id();
+        if !should_persist(&mux, &pane) {
+            forget_pane(pane_id);
+            continue;
+        }
+        let is_new = STATE.with(|state| !state.borrow().panes.contains_key(&pane_id));
+        let is_dirty = STATE.with(|state| state.borrow_mut().dirty.remove(&pane_id));
+        if (is_new || is_dirty) && !pane.is_alt_screen_active() {
+            persist_pane(&pane, &key);
+        }
+    }
+}
+
+fn persist_pane(pane: &Rc<dyn Pane>, key: &Option<Arc<Vec<u8>>>) {
+    let pane_id = pane.pane_id();
+    let dims = pane.get_dimensions();
+    let viewport = dims.physical_top..dims.physical_top + dims.viewport_rows as StableRowIndex;
+    let (_, mut screen_lines) = match pane.copy_lines(viewport) {
+        Some(lines) => lines,
+        None => return,
+    };
+    while screen_lines
+        .last()
+        .map(|line| line.as_str().trim().is_empty())
+        .unwrap_or(false)
+    {
+        screen_lines.pop();
+    }
+
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        let state = &mut *state;
+        let persisted = state.panes.entry(pane_id).or_insert_with(|| PersistedPane {
+            name: format!("{}-{}", std::process::id(), pane_id),
+            next_row: dims.scrollback_top,
+            num_lines: 0,
+        });
+        let jobs = match state.jobs.as_ref() {
+            Some(jobs) => jobs,
+            None => return,
+        };
+        let dir = scrollback_dir();
+
+        let first = persisted.next_row.max(dims.scrollback_top);
+        if first < dims.physical_top {
+            if let Some((first, lines)) = pane.copy_lines(first..dims.physical_top) {
+                persisted.next_row = first + lines.len() as StableRowIndex;
+                persisted.num_lines += lines.len();
+                let path = dir.join(format!("{}.lines", persisted.name));
+                jobs.send(Job::Append {
+                    path: path.clone(),
+                    lines,
+                    key: key.clone(),
+                })
+                .ok();
+
+                let keep = configuration().scrollback_lines;
+                if persisted.num_lines > keep.saturating_mul(2) {
+                    persisted.num_lines = keep;
+                    jobs.send(Job::Compact {
+                        path,
+                        keep,
+                        key: key.clone(),
+                    })
+                    .ok();
+                }
+            }
+        }
+
+        jobs.send(Job::Replace {
+            path: dir.join(format!("{}.screen", persisted.name)),
+            screen: Screen {
+                title: pane.get_title(),
+                cwd: pane.get_current_working_dir().map(|url| url.to_string()),
+                lines: screen_lines,
+            },
+            key: key.clone(),
+        })
+        .ok();
+    });
+}
+
+fn run_job(job: Job) -> anyhow::Result<()> {
+    match job {
+        Job::Append { path, lines, key } => {
+            config::create_user_owned_dirs(&scrollback_dir())?;
+            let record = encode_record(&lines, key.as_ref().map(|k| k.as_slice()))?;
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(&path)
+                .with_context(|| format!("opening {}", path.display()))?;
+            file.write_all(&record)?;
+        }
+        Job::Replace { path, screen, key } => {
+            config::create_user_owned_dirs(&scrollback_dir())?;
+            let record = encode_record(&screen, key.as_ref().map(|k| k.as_slice()))?;
+            replace_file(&path, &record)?;
+        }
+        Job::Compact { path, keep, key } => {
+            let key = key.as_ref().map(|k| k.as_slice());
+            let lines = last_lines(decode_records(&read_file(&path)?, key)?, keep);
+            replace_file(&path, &encode_record(&lines, key)?)?;
+        }
+        Job::Remove { name } => {
+            let dir = scrollback_dir();
+            for ext in &["lines", "screen"] {
+                let path = dir.join(format!("{}.{}", name, ext));
+                if path.exists() {
+                    std::fs::remove_file(&path)
+                        .with_context(|| format!("removing {}", path.display()))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Replaces the content of `path` with `data`, writing to a temporary
+/// file first so that a crash doesn't leave a truncated file behind
+fn replace_file(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push("-new");
+    let temp = PathBuf::from(temp);
+    std::fs::write(&temp, data).with_context(|| format!("writing {}", temp.display()))?;
+    std::fs::rename(&temp, path)
+        .with_context(|| format!("renaming {} to {}", temp.display(), path.display()))?;
+    Ok(())
+}
+
+/// Flattens the records of a `.lines` file, keeping only the last
+/// `keep` lines
+fn last_lines(records: Vec<Vec<Line>>, keep: usize) -> Vec<Line> {
+    let mut lines: Vec<Line> = records.into_iter().flatten().collect();
+    if lines.len() > keep {
+        lines.drain(0..lines.len() - keep);
+    }
+    lines
+}
+
+/// Encodes `value` as a record, which is a type byte and a
+/// little endian u32 length, followed by the payload.
+/// An encrypted payload is the IV, then the tag, then the ciphertext.
+fn encode_record<T: Serialize>(value: &T, key: Option<&[u8]>) -> anyhow::Result<Vec<u8>> {
+    let data = serde_json::to_vec(value)?;
+    let (kind, payload) = match key {
+        None => (RECORD_PLAIN, data),
+        Some(key) => {
+            let mut iv = [0u8; IV_LEN];
+            openssl::rand::rand_bytes(&mut iv)?;
+            let mut tag = [0u8; TAG_LEN];
+            let ciphertext = encrypt_aead(
+                Cipher::aes_256_gcm(),
+                key,
+                Some(&iv[..]),
+                &[],
+                &data,
+                &mut tag,
+            )?;
+            let mut payload = Vec::with_capacity(IV_LEN + TAG_LEN + ciphertext.len());
+            payload.extend_from_slice(&iv);
+            payload.extend_from_slice(&tag);
+            payload.extend_from_slice(&ciphertext);
+            (RECORD_ENCRYPTED, payload)
+        }
+    };
+
+    let mut record = Vec::with_capacity(5 + payload.len());
+    record.push(kind);
+    record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    record.extend_from_slice(&payload);
+    Ok(record)
+}
+
+/// Decodes the records in `data`.  A truncated record at the end,
+/// which can be left behind by a crash, is ignored.
+fn decode_records<T: for<'de> Deserialize<'de>>(
+    mut data: &[u8],
+    key: Option<&[u8]>,
+) -> anyhow::Result<Vec<T>> {
+    let mut records = vec![];
+    while data.len() >= 5 {
+        let kind = data[0];
+        let mut len = [0u8; 4];
+        len.copy_from_slice(&data[1..5]);
+        let len = u32::from_le_bytes(len) as usize;
+        if data.len() < 5 + len {
+            break;
+        }
+        let payload = &data[5..5 + len];
+        data = &data[5 + len..];
+
+        let decoded = match kind {
+            RECORD_PLAIN => payload.to_vec(),
+            RECORD_ENCRYPTED => {
+                let key = key.ok_or_else(|| {
+                    anyhow!(
+                        "the scrollback is encrypted, but persist_scrollback_key_file is not set"
+                    )
+                })?;
+                if payload.len() < IV_LEN + TAG_LEN {
+                    bail!("encrypted record is too short");
+                }
+                let (iv, rest) = payload.split_at(IV_LEN);
+                let (tag, ciphertext) = rest.split_at(TAG_LEN);
+                decrypt_aead(Cipher::aes_256_gcm(), key, Some(iv), &[], ciphertext, tag)
+                    .context("decrypting scrollback; is persist_scrollback_key_file correct?")?
+            }
+            _ => bail!("unknown record type {}", kind),
+        };
+        records.push(serde_json::from_slice(&decoded)?);
+    }
+    Ok(records)
+}
+
+fn read_file(path: &Path) -> anyhow::Result<Vec<u8>> {
+    let mut data = vec![];
+    match std::fs::File::open(path) {
+        Ok(mut file) => {
+            file.read_to_end(&mut data)
+                .with_context(|| format!("reading {}", path.display()))?;
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err).with_context(|| format!("opening {}", path.display())),
+    }
+    Ok(data)
+}
+
+/// Loads the persisted content named `name`, returning the screen
+/// and up to `scrollback_lines` of the lines that preceded it
+fn load(dir: &Path, name: &str, key: Option<&[u8]>) -> anyhow::Result<(Option<Screen>, Vec<Line>)> {
+    let mut lines = last_lines(
+        decode_records(&read_file(&dir.join(format!("{}.lines", name)))?, key)?,
+        configuration().scrollback_lines,
+    );
+    let screen =
+        decode_records::<Screen>(&read_file(&dir.join(format!("{}.screen", name)))?, key)?.pop();
+    if let Some(screen) = screen.as_ref() {
+        lines.extend(screen.lines.iter().cloned());
+    }
+    Ok((screen, lines))
+}
+
+#[cfg(unix)]
+fn process_is_running(pid: u32) -> bool {
+    let res = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    res == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+fn process_is_running(pid: u32) -> bool {
+    use winapi::um::handleapi::CloseHandle;
+    use winapi::um::minwinbase::STILL_ACTIVE;
+    use winapi::um::processthreadsapi::{GetExitCodeProcess, OpenProcess};
+    use winapi::um::winnt::PROCESS_QUERY_LIMITED_INFORMATION;
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if handle.is_null() {
+            return false;
+        }
+        let mut status = 0;
+        let ok = GetExitCodeProcess(handle, &mut status);
+        CloseHandle(handle);
+        ok != 0 && status == STILL_ACTIVE
+    }
+}
+
+/// Opens a read-only tab in the first window for each pane that was
+/// persisted by a wezterm process that is no longer running, and
+/// then removes its files
+pub fn recover_scrollback() {
+    let dir = scrollback_dir();
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    let mut names: Vec<String> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let ext = path.extension()?.to_str()?;
+            if ext != "lines" && ext != "screen" {
+                return None;
+            }
+            Some(path.file_stem()?.to_str()?.to_string())
+        })
+        .filter(|name| {
+            match name
+                .split('-')
+                .next()
+                .and_then(|pid| pid.parse::<u32>().ok())
+            {
+                Some(pid) => pid != std::process::id() && !process_is_running(pid),
+                None => false,
+            }
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    if names.is_empty() {
+        return;
+    }
+
+    // Don't create a key while recovering; if it doesn't exist,
+    // then the scrollback can't have been encrypted with it
+    let key = match configuration().persist_scrollback_key_file.as_ref() {
+        Some(path) if path.exists() => match load_key(path) {
+            Ok(key) => Some(key),
+            Err(err) => {
+                log::error!("Unable to recover scrollback: {:#}", err);
+                return;
+            }
+        },
+        _ => None,
+    };
+
+    for name in names {
+        match load(&dir, &name, key.as_ref().map(|k| k.as_slice()))
+            .and_then(|(screen, lines)| add_recovered_tab(screen, lines))
+        {
+            Ok(()) => {
+                run_job(Job::Remove { name: name.clone() })
+                    .map_err(|err| log::error!("{:#}", err))
+                    .ok();
+            }
+            Err(err) => {
+                log::error!("Unable to recover scrollback from {}: {:#}", name, err);
+            }
+        }
+    }
+}
+
+fn add_recovered_tab(screen: Option<Screen>, lines: Vec<Line>) -> anyhow::Result<()> {
+    let mux = Mux::get().unwrap();
+    let window_id = *mux
+        .iter_windows()
+        .first()
+        .ok_or_else(|| anyhow!("there is no window to hold the recovered tab"))?;
+    let size = match mux.get_active_tab_for_window(window_id) {
+        Some(tab) => tab.get_size(),
+        None => configuration().initial_size(),
+    };
+
+    let (title, cwd) = match screen {
+        Some(screen) => (
+            screen.title,
+            screen.cwd.and_then(|cwd| url::Url::parse(&cwd).ok()),
+        ),
+        None => (String::new(), None),
+    };
+    let pane: Rc<dyn Pane> = Rc::new(RecoveredPane::new(
+        mux.default_domain().domain_id(),
+        format!("recovered: {}", title),
+        cwd,
+        lines,
+        size,
+    )?);
+    let tab = Rc::new(Tab::new(&size));
+    tab.assign_pane(&pane);
+    mux.add_tab_and_active_pane(&tab)?;
+    mux.add_tab_to_window(&tab, window_id)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn records() -> anyhow::Result<()> {
+        let lines = vec![Line::from_text("hello", &Default::default())];
+        let key = vec![7u8; KEY_LEN];
+
+        let mut data = encode_record(&lines, None)?;
+        data.extend(encode_record(&lines, Some(&key))?);
+        // A partially written record is ignored
+        let truncated = encode_record(&lines, None)?;
+        data.extend_from_slice(&truncated[..truncated.len() - 1]);
+
+        let decoded: Vec<Vec<Line>> = decode_records(&data, Some(&key))?;
+        assert_eq!(decoded.len(), 2);
+        assert_eq!(decoded[0][0].as_str(), "hello");
+        assert_eq!(decoded[1][0].as_str(), "hello");
+
+        assert!(decode_records::<Vec<Line>>(&data, None).is_err());
+        assert!(decode_records::<Vec<Line>>(&data, Some(&[0u8; KEY_LEN])).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn compaction_keeps_the_last_lines() -> anyhow::Result<()> {
+        let record = |texts: &[&str]| -> Vec<Line> {
+            texts
+                .iter()
+                .map(|text| Line::from_text(text, &Default::default()))
+                .collect()
+        };
+        let mut data = encode_record(&record(&["1", "2"]), None)?;
+        data.extend(encode_record(&record(&["3"]), None)?);
+        data.extend(encode_record(&record(&["4", "5"]), None)?);
+
+        let lines = last_lines(decode_records(&data, None)?, 3);
+        let texts: Vec<String> = lines.iter().map(|line| line.as_str()).collect();
+        assert_eq!(texts, vec!["3", "4", "5"]);
+
+        let compacted = encode_record(&lines, None)?;
+        let lines = last_lines(decode_records(&compacted, None)?, 10);
+        assert_eq!(lines.len(), 3);
+        Ok(())
+    }
+}
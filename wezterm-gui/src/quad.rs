@@ -72,6 +72,11 @@ pub struct Quads {
     /// The vertex index for the first vertex of the scroll bar thumb
     pub scroll_thumb: usize,
     pub background_image: usize,
+    /// The vertex index for the first vertex of the cursor trail
+    pub cursor_trail: usize,
+    /// The vertex index for the first vertex of the four quads
+    /// that make up the border around the terminal area
+    pub border: usize,
 }
 
 pub struct MappedQuads<'a> {
@@ -110,6 +115,22 @@ impl<'a> MappedQuads<'a> {
             vert: &mut self.mapping[start..start + VERTICES_PER_CELL],
         }
     }
+
+    pub fn cursor_trail<'b>(&'b mut self) -> Quad<'b> {
+        let start = self.quads.cursor_trail;
+        Quad {
+            vert: &mut self.mapping[start..start + VERTICES_PER_CELL],
+        }
+    }
+
+    /// Returns one of the four edges of the border; `edge` is in
+    /// the range 0..4
+    pub fn border<'b>(&'b mut self, edge: usize) -> Quad<'b> {
+        let start = self.quads.border + edge * VERTICES_PER_CELL;
+        Quad {
+            vert: &mut self.mapping[start..start + VERTICES_PER_CELL],
+        }
+    }
 }
 
 impl Quads {
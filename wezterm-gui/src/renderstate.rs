@@ -36,7 +36,7 @@ impl RenderState {
         loop {
             let glyph_cache =
                 RefCell::new(GlyphCache::new_gl(&context, fonts, atlas_size, metrics)?);
-            let result = UtilSprites::new(&mut *glyph_cache.borrow_mut(), metrics);
+            let result = UtilSprites::new(config, &mut *glyph_cache.borrow_mut(), metrics);
             match result {
                 Ok(util_sprites) => {
                     let background_prog = Self::compile_prog(
@@ -283,6 +283,15 @@ impl RenderState {
         // And a quad for the scrollbar thumb
         quads.scroll_thumb = define_quad(0.0, 0.0, 0.0, 0.0) as usize;
 
+        // And one for the cursor trail, which is drawn over the cells
+        quads.cursor_trail = define_quad(0.0, 0.0, 0.0, 0.0) as usize;
+
+        // And four for the edges of the border around the terminal area
+        quads.border = define_quad(0.0, 0.0, 0.0, 0.0) as usize;
+        for _ in 1..4 {
+            define_quad(0.0, 0.0, 0.0, 0.0);
+        }
+
         Ok((
             VertexBuffer::dynamic(context, &verts)?,
             IndexBuffer::new(
@@ -294,15 +303,20 @@ impl RenderState {
         ))
     }
 
-    pub fn clear_texture_atlas(&mut self, metrics: &RenderMetrics) -> anyhow::Result<()> {
+    pub fn clear_texture_atlas(
+        &mut self,
+        config: &ConfigHandle,
+        metrics: &RenderMetrics,
+    ) -> anyhow::Result<()> {
         let mut glyph_cache = self.glyph_cache.borrow_mut();
         glyph_cache.clear();
-        self.util_sprites = UtilSprites::new(&mut glyph_cache, metrics)?;
+        self.util_sprites = UtilSprites::new(config, &mut glyph_cache, metrics)?;
         Ok(())
     }
 
     pub fn recreate_texture_atlas(
         &mut self,
+        config: &ConfigHandle,
         fonts: &Rc<FontConfiguration>,
         metrics: &RenderMetrics,
         size: Option<usize>,
@@ -316,7 +330,7 @@ impl RenderState {
         let mut size = size;
         let mut attempt = 10;
         loop {
-            match self.recreate_texture_atlas_impl(fonts, metrics, size) {
+            match self.recreate_texture_atlas_impl(config, fonts, metrics, size) {
                 Ok(_) => return Ok(()),
                 Err(err) => {
                     attempt -= 1;
@@ -340,13 +354,14 @@ impl RenderState {
 
     fn recreate_texture_atlas_impl(
         &mut self,
+        config: &ConfigHandle,
         fonts: &Rc<FontConfiguration>,
         metrics: &RenderMetrics,
         size: Option<usize>,
     ) -> anyhow::Result<()> {
         let size = size.unwrap_or_else(|| self.glyph_cache.borrow().atlas.size());
         let mut glyph_cache = GlyphCache::new_gl(&self.context, fonts, size, metrics)?;
-        self.util_sprites = UtilSprites::new(&mut glyph_cache, metrics)?;
+        self.util_sprites = UtilSprites::new(config, &mut glyph_cache, metrics)?;
         *self.glyph_cache.borrow_mut() = glyph_cache;
         Ok(())
     }
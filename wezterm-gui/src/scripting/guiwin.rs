@@ -88,6 +88,10 @@ impl UserData for GuiWin {
                 .await
             },
         );
+        methods.add_async_method("leader_is_active", |_, this, _: ()| async move {
+            this.with_term_window(move |term_window, _ops| Ok(term_window.leader_is_active()))
+                .await
+        });
         methods.add_async_method("effective_config", |_, this, _: ()| async move {
             this.with_term_window(move |term_window, _ops| Ok((*term_window.config).clone()))
                 .await
@@ -328,6 +328,7 @@ mod test {
         config.font = TextStyle {
             font: vec![FontAttributes::new("Fira Code")],
             foreground: None,
+            fixed_size: None,
         };
         config.font_rules.clear();
         config.compute_extra_defaults(None);
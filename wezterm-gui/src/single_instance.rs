@@ -0,0 +1,187 @@
+//! Implements the `single_instance` option, which has `wezterm start`
+//! open a tab in an already running gui rather than starting another
+use anyhow::anyhow;
+use codec::SpawnV2;
+use config::keyassignment::SpawnTabDomain;
+use mux::connui::ConnectionUI;
+use mux::domain::alloc_domain_id;
+use mux::window::WindowId;
+use portable_pty::cmdbuilder::CommandBuilder;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use wezterm_client::client::Client;
+use wezterm_gui_subcommands::StartCommand;
+
+/// Returns the gui sockets found in `dir`, most recently started
+/// first.  Sockets left behind by a gui that didn't shut down
+/// cleanly are included; they will fail to connect.
+fn gui_sockets_in(dir: &Path) -> Vec<PathBuf> {
+    let mut sockets = vec![];
+    if let Ok(dir) = std::fs::read_dir(dir) {
+        for entry in dir.filter_map(Result::ok) {
+            let is_gui_sock = entry
+                .file_name()
+                .to_str()
+                .map(|name| name.starts_with("gui-sock-"))
+                .unwrap_or(false);
+            if is_gui_sock {
+                let modified = entry.metadata().and_then(|m| m.modified()).ok();
+                sockets.push((modified, entry.path()));
+            }
+        }
+    }
+    sockets.sort_by(|a, b| b.0.cmp(&a.0));
+    sockets.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Builds the request that asks the gui to spawn the program
+/// described by `opts` in a new tab of `window_id`.
+/// The gui resolves the domain, honoring its `default_domain`.
+fn spawn_request(opts: &StartCommand, window_id: WindowId, cwd: Option<PathBuf>) -> SpawnV2 {
+    // Start in our working directory, rather than that of the gui
+    let command_dir = match &opts.cwd {
+        Some(cwd) => Some(PathBuf::from(cwd)),
+        None => cwd,
+    }
+    .and_then(|dir| dir.to_str().map(|s| s.to_string()));
+
+    SpawnV2 {
+        domain: SpawnTabDomain::DefaultDomain,
+        window_id: Some(window_id),
+        command: if opts.prog.is_empty() {
+            None
+        } else {
+            Some(CommandBuilder::from_argv(opts.prog.clone()))
+        },
+        command_dir,
+        size: config::configuration().initial_size(),
+    }
+}
+
+/// Asks the gui listening on `socket_path` to spawn the program
+/// described by `opts` in a new tab in one of its windows, which
+/// it then raises
+async fn spawn_tab_in_existing_gui(
+    socket_path: PathBuf,
+    opts: &StartCommand,
+) -> anyhow::Result<()> {
+    let mut ui = ConnectionUI::new_headless();
+    let client = Client::new_unix_domain(
+        alloc_domain_id(),
+        &config::UnixDomain {
+            socket_path: Some(socket_path),
+            no_serve_automatically: true,
+            ..Default::default()
+        },
+        false,
+        &mut ui,
+    )?;
+    client.verify_version_compat(&ui).await?;
+
+    let panes = client.list_panes().await?;
+    let window_id = panes
+        .tabs
+        .iter()
+        .find_map(|tab| tab.window_and_tab_ids().map(|(window_id, _)| window_id))
+        .ok_or_else(|| anyhow!("the existing gui has no windows"))?;
+
+    let spawned = client
+        .spawn_v2(spawn_request(opts, window_id, std::env::current_dir().ok()))
+        .await?;
+    log::debug!("spawned in existing gui: {:?}", spawned);
+    Ok(())
+}
+
+/// When `single_instance` is enabled, try each of the other running
+/// gui processes in turn, returning true once one of them has opened
+/// a tab on our behalf.
+pub fn delegate_to_existing_gui(opts: &StartCommand) -> bool {
+    if opts.always_new_process || !config::configuration().single_instance {
+        return false;
+    }
+
+    // This runs before the gui has set up its scheduler, but the
+    // client dispatches its responses via the main thread, so we
+    // run a simple executor until we're done.  The gui replaces
+    // it with its own scheduler if we go on to start it.
+    let executor = promise::spawn::SimpleExecutor::new();
+    let result = Rc::new(RefCell::new(None));
+    {
+        let result = Rc::clone(&result);
+        let opts = opts.clone();
+        promise::spawn::spawn(async move {
+            let mut delegated = false;
+            for socket_path in gui_sockets_in(&config::RUNTIME_DIR) {
+                match spawn_tab_in_existing_gui(socket_path.clone(), &opts).await {
+                    Ok(()) => {
+                        delegated = true;
+                        break;
+                    }
+                    Err(err) => log::debug!(
+                        "unable to use the gui at {}: {:#}",
+                        socket_path.display(),
+                        err
+                    ),
+                }
+            }
+            result.borrow_mut().replace(delegated);
+        })
+        .detach();
+    }
+
+    loop {
+        if let Some(delegated) = result.borrow_mut().take() {
+            return delegated;
+        }
+        if let Err(err) = executor.tick() {
+            log::error!("while delegating to an existing gui: {:#}", err);
+            return false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn finds_only_gui_sockets() -> anyhow::Result<()> {
+        let dir =
+            std::env::temp_dir().join(format!("wezterm-single-instance-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("gui-sock-123"), b"")?;
+        std::fs::write(dir.join("sock"), b"")?;
+        std::fs::write(dir.join("log"), b"")?;
+
+        let sockets = gui_sockets_in(&dir);
+        std::fs::remove_dir_all(&dir)?;
+        assert_eq!(sockets, vec![dir.join("gui-sock-123")]);
+        assert!(gui_sockets_in(&dir).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn spawn_request_uses_default_domain_and_our_cwd() {
+        let opts = StartCommand {
+            prog: vec!["top".into()],
+            ..Default::default()
+        };
+        let spawn = spawn_request(&opts, 2, Some(PathBuf::from("/home/me")));
+        assert_eq!(spawn.domain, SpawnTabDomain::DefaultDomain);
+        assert_eq!(spawn.window_id, Some(2));
+        assert_eq!(
+            spawn.command,
+            Some(CommandBuilder::from_argv(vec!["top".into()]))
+        );
+        assert_eq!(spawn.command_dir, Some("/home/me".to_string()));
+
+        let opts = StartCommand {
+            cwd: Some("/tmp".into()),
+            ..Default::default()
+        };
+        let spawn = spawn_request(&opts, 2, Some(PathBuf::from("/home/me")));
+        assert_eq!(spawn.command, None);
+        assert_eq!(spawn.command_dir, Some("/tmp".to_string()));
+    }
+}
@@ -1,141 +1,127 @@
+//! Reports the metrics collected by the gui, both periodically to the
+//! log, as controlled by `periodic_stat_logging`, and in the debug overlay.
+//! The metrics are collected by the same recorder as is used by the mux
+//! server, so they are also available via `wezterm cli metrics`.
 use config::configuration;
 use hdrhistogram::Histogram;
-use metrics::{GaugeValue, Key, Recorder, Unit};
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use tabout::{tabulate_output, Alignment, Column};
-
-static ENABLE_STAT_PRINT: AtomicBool = AtomicBool::new(true);
-
-struct Inner {
-    histograms: HashMap<Key, Histogram<u64>>,
-}
+use tabout::{tabulate_output_as_string, Alignment, Column};
+use wezterm_mux_server_impl::stats::{Snapshot, Stats};
 
 fn pctile_latency(histogram: &Histogram<u64>, p: f64) -> Duration {
     Duration::from_nanos(histogram.value_at_percentile(p))
 }
 
-impl Inner {
-    fn run(inner: Arc<Mutex<Inner>>) {
-        let mut last_print = Instant::now();
+fn columns(names: &[&str]) -> Vec<Column> {
+    names
+        .iter()
+        .map(|name| Column {
+            name: name.to_string(),
+            alignment: Alignment::Left,
+        })
+        .collect()
+}
 
-        let cols = vec![
-            Column {
-                name: "STAT".to_string(),
-                alignment: Alignment::Left,
-            },
-            Column {
-                name: "p50".to_string(),
-                alignment: Alignment::Left,
-            },
-            Column {
-                name: "p75".to_string(),
-                alignment: Alignment::Left,
-            },
-            Column {
-                name: "p95".to_string(),
-                alignment: Alignment::Left,
-            },
-        ];
+/// Installs the metrics recorder and starts logging the metrics
+/// periodically
+pub fn init() -> anyhow::Result<()> {
+    Stats::init()?;
+    std::thread::spawn(log_periodically);
+    Ok(())
+}
 
-        loop {
-            std::thread::sleep(Duration::from_secs(10));
-            if !ENABLE_STAT_PRINT.load(Ordering::Acquire) {
-                break;
-            }
+fn log_periodically() {
+    let mut last_print = Instant::now();
+    loop {
+        std::thread::sleep(Duration::from_secs(10));
 
-            let seconds = configuration().periodic_stat_logging;
-            if seconds == 0 {
-                continue;
-            }
-            if last_print.elapsed() >= Duration::from_secs(seconds) {
-                let inner = inner.lock().unwrap();
-                let mut data = vec![];
-                for (key, histogram) in &inner.histograms {
-                    if key.to_string().ends_with(".size") {
-                        let p50 = histogram.value_at_percentile(50.);
-                        let p75 = histogram.value_at_percentile(75.);
-                        let p95 = histogram.value_at_percentile(95.);
-                        data.push(vec![
-                            key.to_string(),
-                            format!("{:.2?}", p50),
-                            format!("{:.2?}", p75),
-                            format!("{:.2?}", p95),
-                        ]);
-                    } else {
-                        let p50 = pctile_latency(histogram, 50.);
-                        let p75 = pctile_latency(histogram, 75.);
-                        let p95 = pctile_latency(histogram, 95.);
-                        data.push(vec![
-                            key.to_string(),
-                            format!("{:.2?}", p50),
-                            format!("{:.2?}", p75),
-                            format!("{:.2?}", p95),
-                        ]);
-                    }
-                }
-                data.sort_by(|a, b| a[0].cmp(&b[0]));
+        let seconds = configuration().periodic_stat_logging;
+        if seconds == 0 {
+            continue;
+        }
+        if last_print.elapsed() >= Duration::from_secs(seconds) {
+            if let Some(snapshot) = Stats::snapshot() {
                 eprintln!();
-                tabulate_output(&cols, &data, &mut std::io::stderr().lock()).ok();
-                last_print = Instant::now();
+                eprint!("{}", format_histograms(&snapshot));
             }
+            last_print = Instant::now();
         }
     }
 }
 
-pub struct Stats {
-    inner: Arc<Mutex<Inner>>,
+/// Formats the histograms as a table of percentiles.
+/// Anything that isn't a size is a duration recorded in nanoseconds.
+pub fn format_histograms(snapshot: &Snapshot) -> String {
+    let data: Vec<Vec<String>> = snapshot
+        .histograms
+        .iter()
+        .map(|(name, histogram)| {
+            let mut row = vec![name.to_string()];
+            for p in &[50., 75., 95.] {
+                if name.ends_with(".size") {
+                    row.push(format!("{}", histogram.value_at_percentile(*p)));
+                } else {
+                    row.push(format!("{:.2?}", pctile_latency(histogram, *p)));
+                }
+            }
+            row.push(histogram.len().to_string());
+            row
+        })
+        .collect();
+    tabulate_output_as_string(&columns(&["STAT", "p50", "p75", "p95", "COUNT"]), &data)
+        .unwrap_or_default()
 }
 
-impl Stats {
-    pub fn new() -> Self {
-        Self {
-            inner: Arc::new(Mutex::new(Inner {
-                histograms: HashMap::new(),
-            })),
-        }
-    }
-
-    pub fn init() -> anyhow::Result<()> {
-        let stats = Self::new();
-        let inner = Arc::clone(&stats.inner);
-        std::thread::spawn(move || Inner::run(inner));
-        let rec = Box::new(stats);
-        metrics::set_boxed_recorder(rec)
-            .map_err(|e| anyhow::anyhow!("Failed to set metrics recorder:{}", e))
-    }
+/// Formats the counters and gauges as a table.  If `previous` is
+/// provided, the rate at which each counter has changed since that
+/// earlier snapshot, which was taken `elapsed` ago, is included.
+pub fn format_counters(snapshot: &Snapshot, previous: Option<(&Snapshot, Duration)>) -> String {
+    let mut data: Vec<Vec<String>> = snapshot
+        .counters
+        .iter()
+        .map(|(name, value)| {
+            let rate = previous
+                .and_then(|(prior, elapsed)| {
+                    let seconds = elapsed.as_secs_f64();
+                    if seconds <= 0. {
+                        return None;
+                    }
+                    let (_, prior) = prior.counters.iter().find(|(n, _)| n == name)?;
+                    Some(format!(
+                        "{:.0}/s",
+                        value.saturating_sub(*prior) as f64 / seconds
+                    ))
+                })
+                .unwrap_or_default();
+            vec![name.to_string(), value.to_string(), rate]
+        })
+        .collect();
+    data.extend(
+        snapshot
+            .gauges
+            .iter()
+            .map(|(name, value)| vec![name.to_string(), format!("{:.2}", value), String::new()]),
+    );
+    tabulate_output_as_string(&columns(&["STAT", "VALUE", "RATE"]), &data).unwrap_or_default()
 }
 
-impl Recorder for Stats {
-    fn register_counter(&self, _key: Key, _unit: Option<Unit>, _description: Option<&'static str>) {
-    }
-
-    fn register_gauge(&self, _key: Key, _unit: Option<Unit>, _description: Option<&'static str>) {}
-
-    fn register_histogram(
-        &self,
-        _key: Key,
-        _unit: Option<Unit>,
-        _description: Option<&'static str>,
-    ) {
-    }
-
-    fn increment_counter(&self, key: Key, value: u64) {
-        log::trace!("counter '{}' -> {}", key, value);
-    }
-
-    fn update_gauge(&self, key: Key, value: GaugeValue) {
-        log::trace!("gauge '{}' -> {:?}", key, value);
-    }
-
-    fn record_histogram(&self, key: Key, value: f64) {
-        let mut inner = self.inner.lock().unwrap();
-        let histogram = inner
+/// Computes the rate, in bytes per second, at which terminal output
+/// is parsed while the parser is busy, or None if nothing has been
+/// parsed yet
+pub fn parse_throughput(snapshot: &Snapshot) -> Option<f64> {
+    let find = |name: &str| {
+        snapshot
             .histograms
-            .entry(key)
-            .or_insert_with(|| Histogram::new(2).expect("failed to crate new Histogram"));
-        histogram.record(value as u64).ok();
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, histogram)| histogram)
+    };
+    let duration = find("pane.parse")?;
+    let size = find("pane.parse.size")?;
+    // Both histograms have a sample for each parsed buffer, so the
+    // ratio of their means is the ratio of their totals
+    if duration.mean() <= 0. {
+        return None;
     }
+    Some(size.mean() * 1e9 / duration.mean())
 }
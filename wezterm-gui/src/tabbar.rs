@@ -1,6 +1,11 @@
-use config::{ConfigHandle, TabBarColors};
+use config::{ConfigHandle, TabBarColors, TabBarOverflow};
+use mux::pane::{format_cwd, Pane, BADGE_USER_VAR};
+use mux::tab::TabId;
 use mux::window::Window as MuxWindow;
+use mux::Mux;
 use std::cell::Ref;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 use termwiz::cell::unicode_column_width;
 use termwiz::cell::{Cell, CellAttributes};
 use termwiz::color::ColorSpec;
@@ -46,6 +51,7 @@ impl TabBarState {
     /// mouse_x is some if the mouse is on the same row as the tab bar.
     /// title_width is the total number of cell columns in the window.
     /// window allows access to the tabs associated with the window.
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         title_width: usize,
         mouse_x: Option<usize>,
@@ -53,6 +59,8 @@ impl TabBarState {
         colors: Option<&TabBarColors>,
         config: &ConfigHandle,
         right_status: &str,
+        completed_commands: &HashMap<TabId, Option<i32>>,
+        input_locked_tabs: &HashSet<TabId>,
     ) -> Self {
         let colors = colors.cloned().unwrap_or_else(TabBarColors::default);
 
@@ -108,23 +116,24 @@ impl TabBarState {
         // menu with tab creation options) and the other three chars
         // are symbols representing minimize, maximize and close.
 
-        let tab_titles: Vec<String> = window
+        let index_base = if config.tab_and_split_indices_are_zero_based {
+            0
+        } else {
+            1
+        };
+        let mut tab_titles: Vec<String> = window
             .iter()
             .enumerate()
             .map(|(idx, tab)| {
                 if let Some(pane) = tab.get_active_pane() {
-                    let mut title = pane.get_title();
-                    if config.show_tab_index_in_tab_bar {
-                        title = format!(
-                            "{}: {}",
-                            idx + if config.tab_and_split_indices_are_zero_based {
-                                0
-                            } else {
-                                1
-                            },
-                            title
-                        );
-                    }
+                    let index = idx + index_base;
+                    let mut title = match &config.tab_title_format {
+                        Some(format) => format_tab_title(format, &TabTitleVars::new(index, &pane)),
+                        None if config.show_tab_index_in_tab_bar => {
+                            format!("{}: {}", index, pane.get_title())
+                        }
+                        None => pane.get_title(),
+                    };
                     // We have a preferred soft minimum on tab width to make it
                     // easier to click on tab titles, but we'll still go below
                     // this if there are too many tabs to fit the window at
@@ -138,31 +147,110 @@ impl TabBarState {
                 }
             })
             .collect();
-        let titles_len: usize = tab_titles.iter().map(|s| unicode_column_width(s)).sum();
+        let mut tab_badges: Vec<String> = window
+            .iter()
+            .map(|tab| {
+                let pane = tab.get_active_pane();
+                let mut badge = pane
+                    .as_ref()
+                    .and_then(|pane| pane.copy_user_vars().remove(BADGE_USER_VAR))
+                    .filter(|badge| !badge.is_empty())
+                    .map(|badge| format!("{} ", badge))
+                    .unwrap_or_default();
+                // Explain why typing isn't being echoed
+                if config.show_password_input_indicator
+                    && pane.map(|pane| pane.is_password_input()).unwrap_or(false)
+                {
+                    badge = format!("\u{1F511} {}", badge);
+                }
+                if input_locked_tabs.contains(&tab.tab_id()) {
+                    badge = format!("\u{1F512} {}", badge);
+                }
+                // Flag tabs in which a long running command has completed
+                match completed_commands.get(&tab.tab_id()) {
+                    Some(Some(0)) | Some(None) => format!("\u{2714} {}", badge),
+                    Some(Some(_)) => format!("\u{2718} {}", badge),
+                    None => badge,
+                }
+            })
+            .collect();
+        let badges_len: usize = tab_badges
+            .iter()
+            .map(|b| parse_status_text(b, CellAttributes::default()).len())
+            .sum();
+        let mut titles_len: usize = badges_len
+            + tab_titles
+                .iter()
+                .map(|s| unicode_column_width(s))
+                .sum::<usize>();
         let number_of_tabs = tab_titles.len();
+        let tab_decoration_len = inactive_tab_left.len() + inactive_tab_right.len();
+        let new_tab_len = new_tab_left.len() + new_tab_right.len() + 1;
+
+        let available_cells = title_width
+            .saturating_sub((number_of_tabs.saturating_sub(1) * tab_decoration_len) + new_tab_len);
+
+        if available_cells < titles_len && config.tab_bar_overflow == TabBarOverflow::Numbers {
+            // Collapse each tab to just its index
+            tab_titles = (0..number_of_tabs)
+                .map(|idx| (idx + index_base).to_string())
+                .collect();
+            tab_badges = vec![String::new(); number_of_tabs];
+            titles_len = tab_titles.iter().map(|s| unicode_column_width(s)).sum();
+        }
 
-        let available_cells = title_width.saturating_sub(
-            (number_of_tabs.saturating_sub(1)
-                * (inactive_tab_left.len() + inactive_tab_right.len()))
-                + (new_tab_left.len() + new_tab_right.len() + 1),
-        );
-        let tab_width_max = if available_cells >= titles_len {
-            // We can render each title with its full width
-            usize::max_value()
+        let tab_width_max =
+            if available_cells >= titles_len || config.tab_bar_overflow == TabBarOverflow::Scroll {
+                // We can render each title with its full width
+                usize::max_value()
+            } else {
+                // We need to clamp the length to balance them out
+                available_cells / number_of_tabs
+            }
+            .min(config.tab_max_width);
+
+        let tab_title_lens: Vec<usize> = tab_titles
+            .iter()
+            .zip(tab_badges.iter())
+            .map(|(tab_title, tab_badge)| {
+                let badge_len = parse_status_text(tab_badge, CellAttributes::default()).len();
+                (badge_len + unicode_column_width(tab_title)).min(tab_width_max)
+            })
+            .collect();
+
+        let active_tab_no = window.get_active_idx();
+        let visible_tabs = if config.tab_bar_overflow == TabBarOverflow::Scroll {
+            let tab_widths: Vec<usize> = tab_title_lens
+                .iter()
+                .map(|len| len + tab_decoration_len)
+                .collect();
+            scrolled_tab_range(
+                &tab_widths,
+                active_tab_no,
+                title_width.saturating_sub(new_tab_len),
+            )
         } else {
-            // We need to clamp the length to balance them out
-            available_cells / number_of_tabs
-        }
-        .min(config.tab_max_width);
+            0..number_of_tabs
+        };
 
         let mut line = Line::with_width(title_width);
 
-        let active_tab_no = window.get_active_idx();
         let mut x = 0;
         let mut items = vec![];
 
-        for (tab_idx, tab_title) in tab_titles.iter().enumerate() {
-            let tab_title_len = unicode_column_width(tab_title).min(tab_width_max);
+        if visible_tabs.start > 0 {
+            // Indicate that there are more tabs to the left
+            line.set_cell(x, Cell::new('\u{2026}', inactive_cell_attrs.clone()));
+            x += 1;
+        }
+
+        for (tab_idx, (tab_title, tab_badge)) in
+            tab_titles.iter().zip(tab_badges.iter()).enumerate()
+        {
+            if !visible_tabs.contains(&tab_idx) {
+                continue;
+            }
+            let tab_title_len = tab_title_lens[tab_idx];
 
             let active = tab_idx == active_tab_no;
             let hover = !active
@@ -198,8 +286,18 @@ impl TabBarState {
                 x += 1;
             }
 
+            // The badge is rendered using the attributes of the tab as
+            // its defaults, so that it only needs to specify the colors
+            // that it wants to change
+            let badge = parse_status_text(tab_badge, cell_attrs.clone());
+            let badge_len = badge.len().min(tab_width_max);
+            for c in badge.into_iter().take(badge_len) {
+                line.set_cell(x, c);
+                x += 1;
+            }
+
             for (idx, sub) in tab_title.graphemes(true).enumerate() {
-                if idx >= tab_width_max {
+                if idx + badge_len >= tab_width_max {
                     break;
                 }
 
@@ -219,6 +317,12 @@ impl TabBarState {
             });
         }
 
+        if visible_tabs.end < number_of_tabs {
+            // Indicate that there are more tabs to the right
+            line.set_cell(x, Cell::new('\u{2026}', inactive_cell_attrs.clone()));
+            x += 1;
+        }
+
         // New tab button
         {
             let hover = mouse_x
@@ -289,6 +393,184 @@ impl TabBarState {
     }
 }
 
+/// Returns the range of tabs that are shown when there isn't room for
+/// all of them, scrolled so that the active tab is visible.
+/// `widths` are the widths of each of the tabs, and `available` is the
+/// number of cells in which they are to be shown.
+/// A cell at either end is reserved to indicate that there are more
+/// tabs in that direction.
+fn scrolled_tab_range(widths: &[usize], active: usize, available: usize) -> Range<usize> {
+    if widths.iter().sum::<usize>() <= available {
+        return 0..widths.len();
+    }
+    let available = available.saturating_sub(2);
+    let active = active.min(widths.len() - 1);
+
+    let mut start = active;
+    let mut end = active + 1;
+    let mut used = widths[active];
+    // Prefer to show the tabs that precede the active tab, so that
+    // the most recently opened tabs stay in view as they are added,
+    // then fill any remaining space with the tabs that follow it
+    while start > 0 && used + widths[start - 1] <= available {
+        start -= 1;
+        used += widths[start];
+    }
+    while end < widths.len() && used + widths[end] <= available {
+        used += widths[end];
+        end += 1;
+    }
+    start..end
+}
+
+/// The values that can be substituted into `tab_title_format`
+struct TabTitleVars {
+    index: usize,
+    title: String,
+    cwd: String,
+    domain: String,
+}
+
+impl TabTitleVars {
+    fn new(index: usize, pane: &std::rc::Rc<dyn Pane>) -> Self {
+        let domain = Mux::get()
+            .and_then(|mux| mux.get_domain(pane.domain_id()))
+            .map(|domain| domain.domain_name().to_string())
+            .unwrap_or_default();
+        Self {
+            index,
+            title: pane.get_title(),
+            cwd: pane
+                .get_current_working_dir()
+                .map(|url| format_cwd(&url))
+                .unwrap_or_default(),
+            domain,
+        }
+    }
+}
+
+/// Expands the placeholders in a `tab_title_format` template.
+/// `{name}` is replaced by the named value and `{name:N}` by the value
+/// truncated to at most N columns; the cwd is truncated from the left
+/// so that the most specific part of the path remains visible.
+/// `{{` and `}}` produce literal braces, and anything that isn't a
+/// recognized placeholder is left as-is.
+fn format_tab_title(format: &str, vars: &TabTitleVars) -> String {
+    let mut result = String::new();
+    let mut chars = format.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                result.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                result.push('}');
+            }
+            '{' => {
+                let mut spec = String::new();
+                let mut closed = false;
+                while let Some(c) = chars.next() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    spec.push(c);
+                }
+                match expand_placeholder(&spec, vars) {
+                    Some(value) if closed => result.push_str(&value),
+                    _ => {
+                        result.push('{');
+                        result.push_str(&spec);
+                        if closed {
+                            result.push('}');
+                        }
+                    }
+                }
+            }
+            c => result.push(c),
+        }
+    }
+
+    result
+}
+
+fn expand_placeholder(spec: &str, vars: &TabTitleVars) -> Option<String> {
+    let (name, width) = match spec.find(':') {
+        Some(colon) => (
+            &spec[..colon],
+            Some(spec[colon + 1..].parse::<usize>().ok()?),
+        ),
+        None => (spec, None),
+    };
+    let value = match name {
+        "index" => vars.index.to_string(),
+        "title" => vars.title.clone(),
+        "cwd" => {
+            return Some(match width {
+                Some(width) => truncate_left(&vars.cwd, width),
+                None => vars.cwd.clone(),
+            })
+        }
+        "domain" => vars.domain.clone(),
+        _ => return None,
+    };
+    Some(match width {
+        Some(width) => truncate_right(&value, width),
+        None => value,
+    })
+}
+
+/// Truncates `s` to at most `width` columns, replacing the
+/// end of the text with an ellipsis if it doesn't fit
+fn truncate_right(s: &str, width: usize) -> String {
+    if unicode_column_width(s) <= width {
+        return s.to_string();
+    }
+    let mut result = String::new();
+    let mut len = 0;
+    for g in s.graphemes(true) {
+        let g_len = unicode_column_width(g);
+        if len + g_len + 1 > width {
+            break;
+        }
+        result.push_str(g);
+        len += g_len;
+    }
+    if width > 0 {
+        result.push('\u{2026}');
+    }
+    result
+}
+
+/// Truncates `s` to at most `width` columns, replacing the
+/// start of the text with an ellipsis if it doesn't fit
+fn truncate_left(s: &str, width: usize) -> String {
+    if unicode_column_width(s) <= width {
+        return s.to_string();
+    }
+    let mut graphemes = vec![];
+    let mut len = 0;
+    for g in s.graphemes(true).rev() {
+        let g_len = unicode_column_width(g);
+        if len + g_len + 1 > width {
+            break;
+        }
+        graphemes.push(g);
+        len += g_len;
+    }
+    let mut result = String::new();
+    if width > 0 {
+        result.push('\u{2026}');
+    }
+    for g in graphemes.into_iter().rev() {
+        result.push_str(g);
+    }
+    result
+}
+
 fn parse_status_text(text: &str, default_cell: CellAttributes) -> Vec<Cell> {
     let mut pen = default_cell.clone();
     let mut cells = vec![];
@@ -386,3 +668,41 @@ fn parse_status_text(text: &str, default_cell: CellAttributes) -> Vec<Cell> {
     flush_print(&mut print_buffer, &mut cells, &pen);
     cells
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tab_title_format() {
+        let vars = TabTitleVars {
+            index: 2,
+            title: "vim README.md".to_string(),
+            cwd: "~/src/wezterm/docs".to_string(),
+            domain: "local".to_string(),
+        };
+
+        assert_eq!(
+            format_tab_title("{index}: {title} ({domain})", &vars),
+            "2: vim README.md (local)"
+        );
+        assert_eq!(format_tab_title("{title:6}", &vars), "vim R\u{2026}");
+        assert_eq!(format_tab_title("{cwd:8}", &vars), "\u{2026}rm/docs");
+        assert_eq!(
+            format_tab_title("{{index}} {nope} {title:x} {cwd", &vars),
+            "{index} {nope} {title:x} {cwd"
+        );
+    }
+
+    #[test]
+    fn scrolled_tabs() {
+        let widths = [10, 10, 10, 10, 10];
+        assert_eq!(scrolled_tab_range(&widths, 4, 50), 0..5);
+        assert_eq!(scrolled_tab_range(&widths, 4, 32), 2..5);
+        assert_eq!(scrolled_tab_range(&widths, 0, 32), 0..3);
+        assert_eq!(scrolled_tab_range(&widths, 2, 22), 1..3);
+        assert_eq!(scrolled_tab_range(&widths, 3, 22), 2..4);
+        // The active tab is shown even if it doesn't fit
+        assert_eq!(scrolled_tab_range(&widths, 3, 5), 3..4);
+    }
+}
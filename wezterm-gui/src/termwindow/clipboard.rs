@@ -89,6 +89,10 @@ impl TermWindow {
     }
 
     pub fn paste_from_clipboard(&mut self, pane: &Rc<dyn Pane>, clipboard: ClipboardPasteSource) {
+        if self.input_is_locked(pane) {
+            self.confirm_input_unlock();
+            return;
+        }
         let pane_id = pane.pane_id();
         let window = self.window.as_ref().unwrap().clone();
         let clipboard = match clipboard {
@@ -109,6 +113,7 @@ impl TermWindow {
                                     mux.get_pane(pane_id)
                                 })
                             {
+                                let clip = term_window.normalize_input(&clip).into_owned();
                                 pane.trickle_paste(clip).ok();
                             }
                         }
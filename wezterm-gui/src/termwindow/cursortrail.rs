@@ -0,0 +1,67 @@
+use config::CursorAnimationEasing;
+use std::time::{Duration, Instant};
+
+/// Tracks the movement of the cursor between cells in the window
+/// so that a short trail can be animated from its prior position.
+/// Positions are in cell units relative to the top left of the window.
+pub struct CursorTrail {
+    from: (f32, f32),
+    to: (f32, f32),
+    started: Instant,
+    animating: bool,
+}
+
+impl CursorTrail {
+    pub fn new() -> Self {
+        Self {
+            from: (0., 0.),
+            to: (0., 0.),
+            started: Instant::now(),
+            animating: false,
+        }
+    }
+
+    /// Records the cell in which the cursor is displayed.
+    /// If it has moved and `animate` is true, a new animation
+    /// starts from the current position of the trail, so that
+    /// rapid movements remain smooth.
+    pub fn update(
+        &mut self,
+        cell: (f32, f32),
+        animate: bool,
+        duration: Duration,
+        easing: CursorAnimationEasing,
+    ) {
+        if cell == self.to {
+            return;
+        }
+        self.from = match self.position(duration, easing) {
+            Some((pos, _)) if animate => pos,
+            _ => self.to,
+        };
+        self.to = cell;
+        self.started = Instant::now();
+        self.animating = animate;
+    }
+
+    /// Returns the current position of the head of the trail and the
+    /// eased progress of the animation, or None if there is no
+    /// animation in progress
+    pub fn position(
+        &self,
+        duration: Duration,
+        easing: CursorAnimationEasing,
+    ) -> Option<((f32, f32), f32)> {
+        if !self.animating || duration.as_millis() == 0 {
+            return None;
+        }
+        let elapsed = self.started.elapsed();
+        if elapsed >= duration {
+            return None;
+        }
+        let progress = easing.apply(elapsed.as_secs_f32() / duration.as_secs_f32());
+        let x = self.from.0 + (self.to.0 - self.from.0) * progress;
+        let y = self.from.1 + (self.to.1 - self.from.1) * progress;
+        Some(((x, y), progress))
+    }
+}
@@ -0,0 +1,108 @@
+//! Tracks where input must be confirmed before it is delivered;
+//! see `ToggleInputLock` and `ToggleClientInputLock`
+use mux::tab::TabId;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set when input to every tab of this client, across all of its
+/// windows, is locked
+static CLIENT_LOCKED: AtomicBool = AtomicBool::new(false);
+
+pub fn client_is_locked() -> bool {
+    CLIENT_LOCKED.load(Ordering::Relaxed)
+}
+
+pub fn toggle_client_lock() {
+    CLIENT_LOCKED.fetch_xor(true, Ordering::Relaxed);
+}
+
+pub fn unlock_client() {
+    CLIENT_LOCKED.store(false, Ordering::Relaxed);
+}
+
+/// The tabs of a window that are locked
+#[derive(Clone, Default)]
+pub struct InputLocks {
+    tabs: HashSet<TabId>,
+}
+
+impl InputLocks {
+    pub fn toggle_tab(&mut self, tab_id: TabId) {
+        if !self.tabs.remove(&tab_id) {
+            self.tabs.insert(tab_id);
+        }
+    }
+
+    /// Returns true if input to `tab_id` is locked, either because
+    /// the tab itself is locked or because the whole client is
+    pub fn is_locked(&self, tab_id: TabId, client_locked: bool) -> bool {
+        client_locked || self.tabs.contains(&tab_id)
+    }
+
+    pub fn unlock(&mut self, tab_id: TabId) {
+        self.tabs.remove(&tab_id);
+    }
+
+    /// Forgets the tabs for which `keep` returns false, such as
+    /// those that have been closed
+    pub fn retain(&mut self, mut keep: impl FnMut(TabId) -> bool) {
+        self.tabs.retain(|&tab_id| keep(tab_id));
+    }
+
+    /// The set of tabs that should be shown as locked, given the
+    /// tabs that are present in the window
+    pub fn locked_tabs(
+        &self,
+        tabs: impl Iterator<Item = TabId>,
+        client_locked: bool,
+    ) -> HashSet<TabId> {
+        tabs.filter(|&tab_id| self.is_locked(tab_id, client_locked))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn tab_locks() {
+        let mut locks = InputLocks::default();
+        assert!(!locks.is_locked(1, false));
+
+        locks.toggle_tab(1);
+        assert!(locks.is_locked(1, false));
+        assert!(!locks.is_locked(2, false));
+
+        locks.toggle_tab(1);
+        assert!(!locks.is_locked(1, false));
+    }
+
+    #[test]
+    fn client_lock_applies_to_every_tab() {
+        let mut locks = InputLocks::default();
+        locks.toggle_tab(1);
+        assert!(locks.is_locked(2, true));
+        assert_eq!(
+            locks.locked_tabs(vec![1, 2, 3].into_iter(), false),
+            vec![1].into_iter().collect()
+        );
+        assert_eq!(
+            locks.locked_tabs(vec![1, 2, 3].into_iter(), true),
+            vec![1, 2, 3].into_iter().collect()
+        );
+
+        locks.unlock(1);
+        assert!(!locks.is_locked(1, false));
+    }
+
+    #[test]
+    fn closed_tabs_are_forgotten() {
+        let mut locks = InputLocks::default();
+        locks.toggle_tab(1);
+        locks.toggle_tab(2);
+        locks.retain(|tab_id| tab_id != 1);
+        assert!(!locks.is_locked(1, false));
+        assert!(locks.is_locked(2, false));
+    }
+}
@@ -1,4 +1,7 @@
 use ::window::{KeyCode, KeyEvent, Modifiers, WindowOps};
+use mux::pane::Pane;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 pub fn window_mods_to_termwiz_mods(modifiers: ::window::Modifiers) -> termwiz::input::Modifiers {
     let mut result = termwiz::input::Modifiers::NONE;
@@ -34,6 +37,34 @@ pub enum Key {
 }
 
 impl super::TermWindow {
+    /// Returns true while the leader key is active, waiting
+    /// for the key that completes a LEADER key assignment
+    pub fn leader_is_active(&self) -> bool {
+        match self.leader_is_down {
+            Some(expiry) => expiry > Instant::now(),
+            None => false,
+        }
+    }
+
+    fn activate_leader(&mut self, duration: Duration) {
+        self.leader_is_down.replace(Instant::now() + duration);
+        // Allow the status area to reflect the leader state
+        self.schedule_status_update();
+    }
+
+    fn cancel_leader(&mut self) {
+        if self.leader_is_down.take().is_some() {
+            self.schedule_status_update();
+        }
+    }
+
+    /// Clears the leader state once its timeout has passed
+    pub fn expire_leader(&mut self) {
+        if self.leader_is_down.is_some() && !self.leader_is_active() {
+            self.cancel_leader();
+        }
+    }
+
     pub fn key_event_impl(&mut self, window_key: &KeyEvent, context: &dyn WindowOps) -> bool {
         if !window_key.key_is_down {
             return false;
@@ -53,17 +84,11 @@ impl super::TermWindow {
         // The leader key is a kind of modal modifier key.
         // It is allowed to be active for up to the leader timeout duration,
         // after which it auto-deactivates.
-        let (leader_active, leader_mod) = match self.leader_is_down.as_ref() {
-            Some(expiry) if *expiry > std::time::Instant::now() => {
-                // Currently active
-                (true, Modifiers::LEADER)
-            }
-            Some(_) => {
-                // Expired; clear out the old expiration time
-                self.leader_is_down.take();
-                (false, Modifiers::NONE)
-            }
-            _ => (false, Modifiers::NONE),
+        self.expire_leader();
+        let (leader_active, leader_mod) = if self.leader_is_active() {
+            (true, Modifiers::LEADER)
+        } else {
+            (false, Modifiers::NONE)
         };
 
         let modifiers = window_mods_to_termwiz_mods(window_key.modifiers);
@@ -85,8 +110,7 @@ impl super::TermWindow {
                     .is_leader(&raw_code_key, window_key.raw_modifiers)
                 {
                     // Yes; record its expiration
-                    self.leader_is_down
-                        .replace(std::time::Instant::now() + duration);
+                    self.activate_leader(duration);
                     return true;
                 }
             }
@@ -101,7 +125,7 @@ impl super::TermWindow {
                 if leader_active {
                     // A successful leader key-lookup cancels the leader
                     // virtual modifier state
-                    self.leader_is_down.take();
+                    self.cancel_leader();
                 }
                 return true;
             }
@@ -114,8 +138,7 @@ impl super::TermWindow {
                 // Check to see if this key-press is the leader activating
                 if let Some(duration) = self.input_map.is_leader(key, window_key.raw_modifiers) {
                     // Yes; record its expiration
-                    self.leader_is_down
-                        .replace(std::time::Instant::now() + duration);
+                    self.activate_leader(duration);
                     return true;
                 }
             }
@@ -130,7 +153,7 @@ impl super::TermWindow {
                 if leader_active {
                     // A successful leader key-lookup cancels the leader
                     // virtual modifier state
-                    self.leader_is_down.take();
+                    self.cancel_leader();
                 }
                 return true;
             }
@@ -160,6 +183,10 @@ impl super::TermWindow {
                         && !config.send_composed_key_when_alt_is_pressed);
 
                 if let Key::Code(term_key) = self.win_key_code_to_termwiz_key_code(&key) {
+                    if bypass_compose && self.swallow_locked_input(&pane, key.is_modifier()) {
+                        context.invalidate();
+                        return true;
+                    }
                     if bypass_compose && pane.key_down(term_key, raw_modifiers).is_ok() {
                         if !key.is_modifier() && self.pane_state(pane.pane_id()).overlay.is_none() {
                             self.maybe_scroll_to_bottom_for_input(&pane);
@@ -178,8 +205,7 @@ impl super::TermWindow {
                 .is_leader(&window_key.key, window_key.modifiers)
             {
                 // Yes; record its expiration
-                self.leader_is_down
-                    .replace(std::time::Instant::now() + duration);
+                self.activate_leader(duration);
                 return true;
             }
         }
@@ -193,7 +219,7 @@ impl super::TermWindow {
             if leader_active {
                 // A successful leader key-lookup cancels the leader
                 // virtual modifier state
-                self.leader_is_down.take();
+                self.cancel_leader();
             }
             true
         } else if leader_active {
@@ -201,14 +227,17 @@ impl super::TermWindow {
                 // Leader was pressed and this non-modifier keypress isn't
                 // a registered key binding; swallow this event and cancel
                 // the leader modifier
-                self.leader_is_down.take();
+                self.cancel_leader();
             }
             true
         } else {
             let key = self.win_key_code_to_termwiz_key_code(&window_key.key);
             match key {
                 Key::Code(key) => {
-                    if pane.key_down(key, modifiers).is_ok() {
+                    if self.swallow_locked_input(&pane, key.is_modifier()) {
+                        context.invalidate();
+                        true
+                    } else if pane.key_down(key, modifiers).is_ok() {
                         if !key.is_modifier() && self.pane_state(pane.pane_id()).overlay.is_none() {
                             self.maybe_scroll_to_bottom_for_input(&pane);
                         }
@@ -223,9 +252,13 @@ impl super::TermWindow {
                         // Leader was pressed and this non-modifier keypress isn't
                         // a registered key binding; swallow this event and cancel
                         // the leader modifier.
-                        self.leader_is_down.take();
+                        self.cancel_leader();
+                    } else if self.swallow_locked_input(&pane, false) {
+                        context.invalidate();
                     } else {
-                        pane.writer().write_all(s.as_bytes()).ok();
+                        pane.writer()
+                            .write_all(self.normalize_input(&s).as_bytes())
+                            .ok();
                         self.maybe_scroll_to_bottom_for_input(&pane);
                         context.invalidate();
                     }
@@ -236,6 +269,18 @@ impl super::TermWindow {
         }
     }
 
+    /// If input to `pane` is locked then the key is swallowed, and
+    /// unless it is a modifier, the user is asked to unlock the tab
+    fn swallow_locked_input(&mut self, pane: &Rc<dyn Pane>, is_modifier: bool) -> bool {
+        if !self.input_is_locked(pane) {
+            return false;
+        }
+        if !is_modifier {
+            self.confirm_input_unlock();
+        }
+        true
+    }
+
     fn win_key_code_to_termwiz_key_code(&self, key: &::window::KeyCode) -> Key {
         use ::termwiz::input::KeyCode as KC;
         use ::window::KeyCode as WK;
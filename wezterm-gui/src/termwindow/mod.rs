@@ -4,8 +4,10 @@ use super::renderstate::*;
 use super::utilsprites::RenderMetrics;
 use crate::glium::texture::SrgbTexture2d;
 use crate::overlay::{
-    confirm_close_pane, confirm_close_tab, confirm_close_window, confirm_quit_program, launcher,
-    start_overlay, start_overlay_pane, tab_navigator, CopyOverlay, SearchOverlay,
+    char_select, confirm_close_pane, confirm_close_tab, confirm_close_window, confirm_quit_program,
+    confirm_unlock_input, debug_overlay, domain_status, first_run, launcher, preview_theme,
+    start_overlay, start_overlay_pane, tab_navigator, CopyOverlay, DomainEntry, QuickSelectOverlay,
+    SearchOverlay, TabEntry,
 };
 use crate::scripting::guiwin::GuiWin;
 use crate::scripting::pane::PaneObject;
@@ -17,19 +19,24 @@ use ::wezterm_term::input::MouseButton as TMB;
 use ::window::*;
 use anyhow::{anyhow, ensure};
 use config::keyassignment::{
-    ClipboardCopyDestination, ClipboardPasteSource, InputMap, KeyAssignment, SpawnCommand,
+    ClipboardCopyDestination, ClipboardPasteSource, InputMap, KeyAssignment, ScrollbackEraseMode,
+    SpawnCommand, SpawnTabDomain,
+};
+use config::{
+    configuration, CommandNotificationStyle, ConfigHandle, HighContrastMode,
+    WindowCloseConfirmation,
 };
-use config::{configuration, ConfigHandle, WindowCloseConfirmation};
 use lru::LruCache;
 use mux::activity::Activity;
-use mux::domain::{DomainId, DomainState};
-use mux::pane::{Pane, PaneId};
+use mux::domain::{DomainId, DomainState, SpawnTarget};
+use mux::pane::{Pane, PaneId, BADGE_USER_VAR, STATUS_USER_VAR};
 use mux::renderable::RenderableDimensions;
 use mux::tab::{PositionedPane, PositionedSplit, SplitDirection, TabId};
 use mux::window::WindowId as MuxWindowId;
-use mux::Mux;
+use mux::{Mux, MuxNotification};
 use portable_pty::PtySize;
 use std::any::Any;
+use std::borrow::Cow;
 use std::cell::{RefCell, RefMut};
 use std::collections::HashMap;
 use std::ops::Add;
@@ -37,14 +44,19 @@ use std::rc::Rc;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::time::{Duration, Instant};
+use termwiz::color::RgbColor;
 use termwiz::hyperlink::Hyperlink;
 use termwiz::image::ImageData;
+use unicode_normalization::UnicodeNormalization;
 use wezterm_font::FontConfiguration;
 use wezterm_term::color::ColorPalette;
 use wezterm_term::input::LastMouseClick;
-use wezterm_term::{StableRowIndex, TerminalConfiguration};
+use wezterm_term::{Alert, Line, StableRowIndex, TerminalConfiguration};
+use wezterm_toast_notification::persistent_toast_notification;
 
 pub mod clipboard;
+mod cursortrail;
+mod inputlock;
 mod keyevent;
 mod mouseevent;
 mod prevcursor;
@@ -52,12 +64,18 @@ mod render;
 pub mod resize;
 mod selection;
 pub mod spawn;
+mod touchevent;
 use clipboard::ClipboardHelper;
+use cursortrail::CursorTrail;
 use prevcursor::PrevCursorPos;
 use spawn::SpawnWhere;
 
 const ATLAS_SIZE: usize = 128;
 
+/// The number of lines for which the result of applying the
+/// highlight_rules is remembered
+const HIGHLIGHT_CACHE_SIZE: usize = 1024;
+
 lazy_static::lazy_static! {
     static ref WINDOW_CLASS: Mutex<String> = Mutex::new("org.wezfurlong.wezterm".to_owned());
 }
@@ -68,6 +86,70 @@ pub fn set_window_class(cls: &str) {
     *WINDOW_CLASS.lock().unwrap() = cls.to_owned();
 }
 
+/// Returns true if the high contrast palette and thicker cursor and
+/// underlines should be used
+pub fn high_contrast_enabled(config: &ConfigHandle) -> bool {
+    match config.high_contrast {
+        HighContrastMode::Always => true,
+        HighContrastMode::Never => false,
+        HighContrastMode::Auto => Connection::get()
+            .map(|conn| conn.prefers_high_contrast())
+            .unwrap_or(false),
+    }
+}
+
+/// Returns the palette with which every pane is rendered while high
+/// contrast mode is in effect.  This is determined when the config
+/// is loaded, rather than each time that a pane is painted.
+fn high_contrast_palette(config: &ConfigHandle) -> Option<ColorPalette> {
+    if high_contrast_enabled(config) {
+        Some(config.high_contrast_palette())
+    } else {
+        None
+    }
+}
+
+/// Attaches the domain in the background, if it isn't already attached
+pub fn attach_domain(domain_id: DomainId) {
+    promise::spawn::spawn(async move {
+        let mux = Mux::get().unwrap();
+        let domain = mux
+            .get_domain(domain_id)
+            .ok_or_else(|| anyhow!("domain id {} is invalid", domain_id))?;
+        if domain.state() == DomainState::Attached {
+            return Ok(());
+        }
+        domain.attach().await.map_err(|err| {
+            log::error!(
+                "Failed to attach domain {}: {:#}",
+                domain.domain_name(),
+                err
+            );
+            err
+        })
+    })
+    .detach();
+}
+
+/// Detaches the domain, closing its panes
+pub fn detach_domain(domain_id: DomainId) {
+    let mux = Mux::get().unwrap();
+    let domain = match mux.get_domain(domain_id) {
+        Some(domain) => domain,
+        None => return,
+    };
+    if domain.state() == DomainState::Detached {
+        return;
+    }
+    if let Err(err) = domain.detach() {
+        log::error!(
+            "Failed to detach domain {}: {:#}",
+            domain.domain_name(),
+            err
+        );
+    }
+}
+
 #[derive(Default, Clone)]
 pub struct PaneState {
     /// If is_some(), the top row of the visible screen.
@@ -114,6 +196,8 @@ pub struct TermWindow {
     /// Window dimensions and dpi
     pub dimensions: Dimensions,
     pub is_full_screen: bool,
+    is_always_on_top: bool,
+    is_visible_on_all_workspaces: bool,
     /// Terminal dimensions
     terminal_size: PtySize,
     pub mux_window_id: MuxWindowId,
@@ -124,19 +208,35 @@ pub struct TermWindow {
     leader_is_down: Option<std::time::Instant>,
     show_tab_bar: bool,
     show_scroll_bar: bool,
+    /// When true, render placeholders for characters that are
+    /// otherwise invisible, such as non-breaking spaces
+    show_invisible_chars: bool,
     tab_bar: TabBarState,
     pub right_status: String,
     last_mouse_coords: (usize, i64),
     last_mouse_terminal_coords: (usize, StableRowIndex),
     scroll_drag_start: Option<isize>,
+    /// Pixels of precise scrolling that don't yet amount to a line
+    scroll_pixel_remainder: f64,
+    touch_state: touchevent::TouchState,
     split_drag_start: Option<PositionedSplit>,
     window_drag_position: Option<MouseEvent>,
+    /// Set while the active tab is being dragged to a new
+    /// position in the tab bar
+    tab_drag_active: bool,
     current_mouse_event: Option<MouseEvent>,
     prev_cursor: PrevCursorPos,
+    cursor_trail: CursorTrail,
     last_scroll_info: RenderableDimensions,
 
     tab_state: RefCell<HashMap<TabId, TabState>>,
     pane_state: RefCell<HashMap<PaneId, PaneState>>,
+    /// Tabs in which a long running command has completed since the
+    /// tab was last active, along with the exit status of the command
+    completed_commands: HashMap<TabId, Option<i32>>,
+    /// Tabs in which input must be confirmed before it is
+    /// delivered; see `ToggleInputLock`
+    input_locks: inputlock::InputLocks,
 
     window_background: Option<Arc<ImageData>>,
 
@@ -145,6 +245,9 @@ pub struct TermWindow {
     clipboard_contents: Arc<Mutex<Option<String>>>,
 
     current_mouse_button: Option<MousePress>,
+    /// Whether the mouse has moved far enough since the button
+    /// was pressed to be considered a drag
+    is_dragging: bool,
 
     /// Keeps track of double and triple clicks
     last_mouse_click: Option<LastMouseClick>,
@@ -154,11 +257,18 @@ pub struct TermWindow {
 
     shape_cache:
         RefCell<LruCache<ShapeCacheKey, anyhow::Result<Rc<Vec<ShapedInfo<SrgbTexture2d>>>>>>,
+    /// The result of applying the highlight_rules to the lines that
+    /// were most recently rendered, keyed by pane and row.  The line
+    /// that was matched is kept so that changed lines are re-matched.
+    highlight_cache: RefCell<LruCache<(PaneId, StableRowIndex), (Line, Option<Rc<Line>>)>>,
 
-    last_blink_paint: Instant,
     last_status_call: Instant,
+    /// Whether the active pane was reading a password when the tab
+    /// bar was last updated
+    last_password_input: bool,
 
     palette: Option<ColorPalette>,
+    high_contrast_palette: Option<ColorPalette>,
 
     event_states: HashMap<String, EventState>,
     has_animation: RefCell<Option<Instant>>,
@@ -230,6 +340,10 @@ impl WindowCallbacks for TermWindow {
         self.mouse_event_impl(event, context)
     }
 
+    fn touch_event(&mut self, event: &TouchEvent, context: &dyn WindowOps) {
+        self.touch_event_impl(event, context)
+    }
+
     fn resize(&mut self, dimensions: Dimensions, is_full_screen: bool) {
         log::trace!(
             "resize event, current cells: {:?}, new dims: {:?} is_full_screen:{}",
@@ -268,37 +382,49 @@ impl WindowCallbacks for TermWindow {
             config_overrides: self.config_overrides.clone(),
             window_background: self.window_background.clone(),
             palette: None,
+            high_contrast_palette: self.high_contrast_palette.clone(),
             focused: None,
             mux_window_id,
             fonts: Rc::clone(&self.fonts),
             render_metrics: self.render_metrics.clone(),
             dimensions,
             is_full_screen: self.is_full_screen,
+            is_always_on_top: self.is_always_on_top,
+            is_visible_on_all_workspaces: self.is_visible_on_all_workspaces,
             terminal_size: self.terminal_size.clone(),
             render_state,
             input_map: InputMap::new(),
             leader_is_down: None,
             show_tab_bar: self.show_tab_bar,
             show_scroll_bar: self.show_scroll_bar,
+            show_invisible_chars: self.show_invisible_chars,
             tab_bar: self.tab_bar.clone(),
             right_status: self.right_status.clone(),
             last_mouse_coords: self.last_mouse_coords.clone(),
             last_mouse_terminal_coords: self.last_mouse_terminal_coords.clone(),
             scroll_drag_start: self.scroll_drag_start.clone(),
+            scroll_pixel_remainder: 0.,
+            touch_state: touchevent::TouchState::default(),
             split_drag_start: self.split_drag_start.clone(),
             window_drag_position: None,
+            tab_drag_active: false,
             current_mouse_event: None,
             prev_cursor: self.prev_cursor.clone(),
+            cursor_trail: CursorTrail::new(),
             last_scroll_info: self.last_scroll_info.clone(),
             clipboard_contents: Arc::clone(&clipboard_contents),
             tab_state: RefCell::new(self.tab_state.borrow().clone()),
             pane_state: RefCell::new(self.pane_state.borrow().clone()),
+            completed_commands: self.completed_commands.clone(),
+            input_locks: self.input_locks.clone(),
             current_mouse_button: self.current_mouse_button.clone(),
+            is_dragging: self.is_dragging,
             last_mouse_click: self.last_mouse_click.clone(),
             current_highlight: self.current_highlight.clone(),
             shape_cache: RefCell::new(LruCache::new(65536)),
-            last_blink_paint: Instant::now(),
+            highlight_cache: RefCell::new(LruCache::new(HIGHLIGHT_CACHE_SIZE)),
             last_status_call: Instant::now(),
+            last_password_input: false,
             event_states: HashMap::new(),
             has_animation: RefCell::new(None),
         });
@@ -335,7 +461,12 @@ impl WindowCallbacks for TermWindow {
     ) -> anyhow::Result<()> {
         self.window.replace(window.clone());
 
-        self.render_state = None;
+        // If we already had a render state, then the opengl context was
+        // lost and this is a new one.  The shaped glyphs reference
+        // textures in the old context, so they must be discarded and
+        // the glyphs uploaded again to the new atlas.
+        let is_context_recovery = self.render_state.take().is_some();
+        self.shape_cache.borrow_mut().clear();
 
         match RenderState::new(
             &self.config,
@@ -362,8 +493,17 @@ impl WindowCallbacks for TermWindow {
         }
 
         window.show();
+        if self.is_always_on_top {
+            window.set_always_on_top(true);
+        }
+        if self.is_visible_on_all_workspaces {
+            window.set_visible_on_all_workspaces(true);
+        }
 
         if self.render_state.is_none() {
+            if is_context_recovery {
+                anyhow::bail!("failed to set up rendering with the new opengl context");
+            }
             panic!("No OpenGL");
         }
 
@@ -489,37 +629,49 @@ impl TermWindow {
                 config: config.clone(),
                 config_overrides: serde_json::Value::default(),
                 palette: None,
+                high_contrast_palette: high_contrast_palette(&config),
                 focused: None,
                 mux_window_id,
                 fonts: fontconfig,
                 render_metrics,
                 dimensions,
                 is_full_screen: false,
+                is_always_on_top: config.window_always_on_top,
+                is_visible_on_all_workspaces: config.window_visible_on_all_workspaces,
                 terminal_size,
                 render_state,
                 input_map: InputMap::new(),
                 leader_is_down: None,
                 show_tab_bar,
                 show_scroll_bar: config.enable_scroll_bar,
+                show_invisible_chars: false,
                 tab_bar: TabBarState::default(),
                 right_status: String::new(),
                 last_mouse_coords: (0, -1),
                 last_mouse_terminal_coords: (0, 0),
                 scroll_drag_start: None,
+                scroll_pixel_remainder: 0.,
+                touch_state: touchevent::TouchState::default(),
                 split_drag_start: None,
                 window_drag_position: None,
+                tab_drag_active: false,
                 current_mouse_event: None,
                 prev_cursor: PrevCursorPos::new(),
+                cursor_trail: CursorTrail::new(),
                 last_scroll_info: RenderableDimensions::default(),
                 clipboard_contents: Arc::clone(&clipboard_contents),
                 tab_state: RefCell::new(HashMap::new()),
                 pane_state: RefCell::new(HashMap::new()),
+                completed_commands: HashMap::new(),
+                input_locks: inputlock::InputLocks::default(),
                 current_mouse_button: None,
+                is_dragging: false,
                 last_mouse_click: None,
                 current_highlight: None,
                 shape_cache: RefCell::new(LruCache::new(65536)),
-                last_blink_paint: Instant::now(),
+                highlight_cache: RefCell::new(LruCache::new(HIGHLIGHT_CACHE_SIZE)),
                 last_status_call: Instant::now(),
+                last_password_input: false,
                 event_states: HashMap::new(),
                 has_animation: RefCell::new(None),
             }),
@@ -528,9 +680,13 @@ impl TermWindow {
 
         Self::apply_icon(&window)?;
         Self::start_periodic_maintenance(window.clone());
+        Self::subscribe_to_user_vars(window.clone(), mux_window_id);
+        Self::subscribe_to_command_completion(window.clone(), mux_window_id);
+        Self::subscribe_to_window_notifications(window.clone(), mux_window_id);
         Self::setup_clipboard(&window, mux_window_id, clipboard_contents);
 
         crate::update::start_update_checker();
+        Self::offer_starter_config(window.clone());
         Ok(())
     }
 
@@ -563,6 +719,28 @@ impl TermWindow {
         }
     }
 
+    /// Shows the first-run overlay in the first window that is opened,
+    /// if wezterm was started without a config file and hasn't offered
+    /// to create one before.
+    fn offer_starter_config(window: Window) {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        static OFFERED: AtomicBool = AtomicBool::new(false);
+        if !config::should_offer_starter_config() || OFFERED.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        promise::spawn::spawn(async move {
+            window
+                .apply(move |tw, _ops| {
+                    if let Some(term_window) = tw.downcast_mut::<TermWindow>() {
+                        term_window.show_first_run();
+                    }
+                    Ok(())
+                })
+                .await
+        })
+        .detach();
+    }
+
     fn start_periodic_maintenance(window: Window) {
         Connection::get()
             .unwrap()
@@ -595,6 +773,184 @@ impl TermWindow {
         }
     }
 
+    /// Arrange to emit the `user-var-changed` event when an application
+    /// in one of the panes in this window sets a user var.
+    /// The tab bar is refreshed when the status or badge user vars change.
+    fn subscribe_to_user_vars(window: Window, mux_window_id: MuxWindowId) {
+        let mux = Mux::get().expect("to be main thread with mux running");
+        mux.subscribe(move |n| {
+            let mux = Mux::get().expect("subscribe to trigger on main thread");
+            if mux.get_window(mux_window_id).is_none() {
+                // The window has gone away; unsubscribe
+                return false;
+            }
+            if let MuxNotification::Alert {
+                pane_id,
+                alert: Alert::SetUserVar { name, value },
+            } = n
+            {
+                match mux.resolve_pane_id(pane_id) {
+                    Some((_domain, window_id, _tab)) if window_id == mux_window_id => {
+                        window.apply(move |tw, _ops| {
+                            if let Some(term_window) = tw.downcast_mut::<TermWindow>() {
+                                if name == STATUS_USER_VAR || name == BADGE_USER_VAR {
+                                    term_window.update_title_post_status();
+                                }
+                                term_window.emit_user_var_event(pane_id, &name, &value);
+                            }
+                            Ok(())
+                        });
+                    }
+                    _ => {}
+                }
+            }
+            true
+        });
+    }
+
+    /// Arrange to notify the user when a long running command
+    /// completes in one of the panes in this window, if that has
+    /// been enabled by `long_running_command_notification_seconds`.
+    fn subscribe_to_command_completion(window: Window, mux_window_id: MuxWindowId) {
+        let mux = Mux::get().expect("to be main thread with mux running");
+        mux.subscribe(move |n| {
+            let mux = Mux::get().expect("subscribe to trigger on main thread");
+            if mux.get_window(mux_window_id).is_none() {
+                // The window has gone away; unsubscribe
+                return false;
+            }
+            if let MuxNotification::Alert {
+                pane_id,
+                alert: Alert::CommandCompleted { elapsed, status },
+            } = n
+            {
+                match mux.resolve_pane_id(pane_id) {
+                    Some((_domain, window_id, tab_id)) if window_id == mux_window_id => {
+                        window.apply(move |tw, _ops| {
+                            if let Some(term_window) = tw.downcast_mut::<TermWindow>() {
+                                term_window.command_completed(pane_id, tab_id, elapsed, status);
+                            }
+                            Ok(())
+                        });
+                    }
+                    _ => {}
+                }
+            }
+            true
+        });
+    }
+
+    /// Arrange to apply the new configuration to this window as soon
+    /// as it has been reloaded, rather than waiting for it to repaint,
+    /// and to raise it when a client activates a tab in it
+    fn subscribe_to_window_notifications(window: Window, mux_window_id: MuxWindowId) {
+        let mux = Mux::get().expect("to be main thread with mux running");
+        mux.subscribe(move |n| {
+            let mux = Mux::get().expect("subscribe to trigger on main thread");
+            if mux.get_window(mux_window_id).is_none() {
+                // The window has gone away; unsubscribe
+                return false;
+            }
+            match n {
+                MuxNotification::ConfigReloaded => {
+                    window.apply(|tw, _ops| {
+                        if let Some(term_window) = tw.downcast_mut::<TermWindow>() {
+                            term_window.check_for_config_reload();
+                        }
+                        Ok(())
+                    });
+                }
+                MuxNotification::WindowActivated(window_id) if window_id == mux_window_id => {
+                    window.show();
+                    window.invalidate();
+                }
+                _ => {}
+            }
+            true
+        });
+    }
+
+    fn command_completed(
+        &mut self,
+        pane_id: PaneId,
+        tab_id: TabId,
+        elapsed: Duration,
+        status: Option<i32>,
+    ) {
+        let threshold = match self.config.long_running_command_notification_seconds {
+            Some(seconds) => Duration::from_secs(seconds),
+            None => return,
+        };
+        if elapsed < threshold {
+            return;
+        }
+
+        let is_focused_pane = self.focused.is_some()
+            && self
+                .get_active_pane_no_overlay()
+                .map(|pane| pane.pane_id() == pane_id)
+                .unwrap_or(false);
+        if is_focused_pane {
+            return;
+        }
+
+        match self.config.long_running_command_notification_style {
+            CommandNotificationStyle::Toast => {
+                let mux = Mux::get().unwrap();
+                let title = match mux.get_pane(pane_id) {
+                    Some(pane) => pane.get_title(),
+                    None => return,
+                };
+                let outcome = match status {
+                    Some(0) | None => "completed".to_string(),
+                    Some(status) => format!("failed with status {}", status),
+                };
+                persistent_toast_notification(
+                    &title,
+                    &format!("Command {} after {}s", outcome, elapsed.as_secs()),
+                );
+            }
+            CommandNotificationStyle::TabBadge => {
+                self.completed_commands.insert(tab_id, status);
+                self.update_title();
+            }
+        }
+    }
+
+    fn emit_user_var_event(&mut self, pane_id: PaneId, name: &str, value: &str) {
+        let mux = Mux::get().unwrap();
+        let pane = match mux.get_pane(pane_id) {
+            Some(pane) => PaneObject::new(&pane),
+            None => return,
+        };
+        let window = GuiWin::new(self);
+        let name = name.to_string();
+        let value = value.to_string();
+
+        async fn do_event(
+            lua: Option<Rc<mlua::Lua>>,
+            window: GuiWin,
+            pane: PaneObject,
+            name: String,
+            value: String,
+        ) -> anyhow::Result<()> {
+            if let Some(lua) = lua {
+                let args = lua.pack_multi((window, pane, name, value))?;
+                if let Err(err) =
+                    config::lua::emit_event(&lua, ("user-var-changed".to_string(), args)).await
+                {
+                    log::error!("while processing user-var-changed event: {:#}", err);
+                }
+            }
+            Ok(())
+        }
+
+        promise::spawn::spawn(config::with_lua_config_on_main_thread(move |lua| {
+            do_event(lua, window, pane, name, value)
+        }))
+        .detach();
+    }
+
     fn emit_status_event(&mut self) {
         self.emit_window_event("update-right-status");
     }
@@ -714,6 +1070,9 @@ impl TermWindow {
             return Ok(());
         }
 
+        // The leader may time out without any further key presses
+        self.expire_leader();
+
         let now = Instant::now();
         if now.duration_since(self.last_status_call)
             > Duration::from_millis(self.config.status_update_interval)
@@ -722,9 +1081,23 @@ impl TermWindow {
             self.schedule_status_update();
         }
 
+        // The tty modes aren't surfaced through any notification,
+        // so poll them to keep the password indicator up to date
+        if self.config.show_password_input_indicator {
+            let password_input = self
+                .get_active_pane_no_overlay()
+                .map(|pane| pane.is_password_input())
+                .unwrap_or(false);
+            if password_input != self.last_password_input {
+                self.last_password_input = password_input;
+                self.update_title_impl();
+            }
+        }
+
         // If self.has_animation is some, then the last render detected
-        // image attachments with multiple frames, so we also need to
-        // invalidate the viewport when the next frame is due
+        // image attachments with multiple frames, or a blinking cursor,
+        // so we also need to invalidate the viewport when the next
+        // frame is due
         if self.focused.is_some() {
             if let Some(next_due) = *self.has_animation.borrow() {
                 if now >= next_due {
@@ -734,28 +1107,6 @@ impl TermWindow {
         }
 
         for pos in panes {
-            // If blinking is permitted, and the cursor shape is set
-            // to a blinking variant, and it's been longer than the
-            // blink rate interval, then invalidate and redraw
-            // so that we will re-evaluate the cursor visibility.
-            // This is pretty heavyweight: it would be nice to only invalidate
-            // the line on which the cursor resides, and then only if the cursor
-            // is within the viewport.
-            if self.config.cursor_blink_rate != 0 && pos.is_active && self.focused.is_some() {
-                let shape = self
-                    .config
-                    .default_cursor_style
-                    .effective_shape(pos.pane.get_cursor_position().shape);
-                if shape.is_blinking() {
-                    if now.duration_since(self.last_blink_paint)
-                        > Duration::from_millis(self.config.cursor_blink_rate)
-                    {
-                        needs_invalidate = true;
-                        self.last_blink_paint = now;
-                    }
-                }
-            }
-
             // If the model is dirty, arrange to re-paint
             let dims = pos.pane.get_dimensions();
             let viewport = self
@@ -767,6 +1118,7 @@ impl TermWindow {
             if !dirty.is_empty() {
                 if pos.pane.downcast_ref::<SearchOverlay>().is_none()
                     && pos.pane.downcast_ref::<CopyOverlay>().is_none()
+                    && pos.pane.downcast_ref::<QuickSelectOverlay>().is_none()
                 {
                     // If any of the changed lines intersect with the
                     // selection, then we need to clear the selection, but not
@@ -819,11 +1171,38 @@ impl TermWindow {
 
     fn palette(&mut self) -> &ColorPalette {
         if self.palette.is_none() {
-            self.palette.replace(config::TermConfig.color_palette());
+            let palette = self
+                .high_contrast_palette
+                .clone()
+                .unwrap_or_else(|| config::TermConfig.color_palette());
+            self.palette.replace(palette);
         }
         self.palette.as_ref().unwrap()
     }
 
+    /// Returns the palette with which to render `pane`; this is
+    /// the pane's own palette unless high contrast mode is in effect
+    fn pane_palette(&self, pane: &Rc<dyn Pane>) -> ColorPalette {
+        match &self.high_contrast_palette {
+            Some(palette) => palette.clone(),
+            None => pane.palette(),
+        }
+    }
+
+    /// Returns the color of the border around the terminal area,
+    /// which defaults to the color of the split lines
+    fn border_color(&mut self) -> RgbColor {
+        let border = if self.high_contrast_palette.is_some() {
+            match &self.config.high_contrast_colors {
+                Some(colors) => colors.border,
+                None => config::Palette::high_contrast().border,
+            }
+        } else {
+            self.config.resolved_palette.border
+        };
+        border.unwrap_or_else(|| self.palette().split)
+    }
+
     pub fn config_was_reloaded(&mut self) {
         log::debug!(
             "config was reloaded, overrides: {:?}",
@@ -840,8 +1219,22 @@ impl TermWindow {
                 configuration()
             }
         };
+        if config.window_always_on_top != self.config.window_always_on_top {
+            self.is_always_on_top = config.window_always_on_top;
+            if let Some(window) = self.window.as_ref() {
+                window.set_always_on_top(self.is_always_on_top);
+            }
+        }
+        if config.window_visible_on_all_workspaces != self.config.window_visible_on_all_workspaces {
+            self.is_visible_on_all_workspaces = config.window_visible_on_all_workspaces;
+            if let Some(window) = self.window.as_ref() {
+                window.set_visible_on_all_workspaces(self.is_visible_on_all_workspaces);
+            }
+        }
+
         self.config = config.clone();
         self.palette.take();
+        self.high_contrast_palette = high_contrast_palette(&config);
 
         self.window_background = reload_background_image(&config, &self.window_background);
 
@@ -858,6 +1251,7 @@ impl TermWindow {
 
         self.show_scroll_bar = config.enable_scroll_bar;
         self.shape_cache.borrow_mut().clear();
+        self.highlight_cache.borrow_mut().clear();
         self.input_map = InputMap::new();
         self.leader_is_down = None;
         let dimensions = self.dimensions;
@@ -911,6 +1305,18 @@ impl TermWindow {
         self.update_title_impl();
     }
 
+    /// Returns the text for the status area of the tab bar.
+    /// Text set via window:set_right_status takes precedence over
+    /// the status assigned to the active pane.
+    fn effective_right_status(&self) -> String {
+        if !self.right_status.is_empty() {
+            return self.right_status.clone();
+        }
+        self.get_active_pane_or_overlay()
+            .and_then(|pane| pane.copy_user_vars().remove(STATUS_USER_VAR))
+            .unwrap_or_default()
+    }
+
     fn update_title_impl(&mut self) {
         let mux = Mux::get().unwrap();
         let window = match mux.get_window(self.mux_window_id) {
@@ -918,6 +1324,19 @@ impl TermWindow {
             _ => return,
         };
 
+        // The badge for a completed command is cleared once the
+        // tab has been activated
+        if let Some(tab) = window.get_active() {
+            self.completed_commands.remove(&tab.tab_id());
+        }
+
+        // Forget the locks of tabs that have been closed
+        let tab_ids: Vec<TabId> = window.iter().map(|tab| tab.tab_id()).collect();
+        self.input_locks.retain(|tab_id| tab_ids.contains(&tab_id));
+        let input_locked_tabs = self
+            .input_locks
+            .locked_tabs(tab_ids.into_iter(), inputlock::client_is_locked());
+
         let new_tab_bar = TabBarState::new(
             self.terminal_size.cols as usize,
             if self.last_mouse_coords.1 == 0 {
@@ -928,7 +1347,9 @@ impl TermWindow {
             &window,
             self.config.colors.as_ref().and_then(|c| c.tab_bar.as_ref()),
             &self.config,
-            &self.right_status,
+            &self.effective_right_status(),
+            &self.completed_commands,
+            &input_locked_tabs,
         );
         if new_tab_bar != self.tab_bar {
             self.tab_bar = new_tab_bar;
@@ -1019,7 +1440,7 @@ impl TermWindow {
         };
 
         if tab_idx < max {
-            window.set_active(tab_idx);
+            window.save_and_then_set_active(tab_idx);
 
             drop(window);
 
@@ -1033,6 +1454,20 @@ impl TermWindow {
         Ok(())
     }
 
+    fn activate_last_tab(&mut self) -> anyhow::Result<()> {
+        let mux = Mux::get().unwrap();
+        let window = mux
+            .get_window(self.mux_window_id)
+            .ok_or_else(|| anyhow!("no such window"))?;
+
+        let last_idx = window.get_last_active_idx();
+        drop(window);
+        match last_idx {
+            Some(idx) => self.activate_tab(idx as isize),
+            None => Ok(()),
+        }
+    }
+
     fn activate_tab_relative(&mut self, delta: isize) -> anyhow::Result<()> {
         let mux = Mux::get().unwrap();
         let window = mux
@@ -1062,14 +1497,30 @@ impl TermWindow {
 
         ensure!(tab_idx < max, "cannot move a tab out of range");
 
-        let tab_inst = window.remove_by_idx(active);
-        window.insert(tab_idx, &tab_inst);
-        window.set_active(tab_idx);
+        window.move_tab(active, tab_idx);
+        let tab = window.get_by_idx(tab_idx).map(Rc::clone);
 
         drop(window);
         self.update_title();
         self.update_scrollbar();
 
+        // Let the domain mirror the new order in its remote window
+        if let Some(tab) = tab {
+            let domain = tab
+                .get_active_pane()
+                .and_then(|pane| mux.get_domain(pane.domain_id()));
+            if let Some(domain) = domain {
+                let window_id = self.mux_window_id;
+                let tab_id = tab.tab_id();
+                promise::spawn::spawn(async move {
+                    if let Err(err) = domain.tab_was_moved(window_id, tab_id).await {
+                        log::error!("Failed to move tab {}: {:#}", tab_id, err);
+                    }
+                })
+                .detach();
+            }
+        }
+
         Ok(())
     }
 
@@ -1088,16 +1539,26 @@ impl TermWindow {
         // overlay, but since the overlay runs in a different thread, accessing
         // the mux list is a bit awkward.  To get the ball rolling we capture
         // the list of tabs up front and live with a static list.
-        let tabs: Vec<(String, TabId, usize)> = window
+        let tabs: Vec<TabEntry> = window
             .iter()
-            .map(|tab| {
-                (
-                    tab.get_active_pane()
-                        .expect("tab to have a pane")
-                        .get_title(),
-                    tab.tab_id(),
-                    tab.count_panes(),
-                )
+            .enumerate()
+            .map(|(idx, tab)| {
+                let pane = tab.get_active_pane().expect("tab to have a pane");
+                let domain = mux
+                    .get_domain(pane.domain_id())
+                    .map(|domain| domain.domain_name().to_string())
+                    .unwrap_or_default();
+                let cwd = pane
+                    .get_current_working_dir()
+                    .map(|url| mux::pane::format_cwd(&url));
+                TabEntry {
+                    tab_id: tab.tab_id(),
+                    idx,
+                    title: pane.get_title(),
+                    domain,
+                    cwd,
+                    num_panes: tab.count_panes(),
+                }
             })
             .collect();
 
@@ -1109,6 +1570,139 @@ impl TermWindow {
         promise::spawn::spawn(future).detach();
     }
 
+    fn show_domain_status(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+
+        let mut domains = mux.iter_domains();
+        domains.sort_by_key(|dom| dom.domain_id());
+        let domains: Vec<DomainEntry> = domains
+            .iter()
+            .map(|dom| DomainEntry {
+                domain_id: dom.domain_id(),
+                name: dom.domain_name().to_string(),
+                label: dom.domain_label().to_string(),
+                state: dom.state(),
+            })
+            .collect();
+
+        let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
+            domain_status(term, domains)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    fn show_debug_overlay(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+
+        let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| debug_overlay(term));
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    fn show_first_run(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+
+        let window = self.window.clone().unwrap();
+        let (overlay, future) =
+            start_overlay(self, &tab, move |_tab_id, term| first_run(term, window));
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    fn show_preview_theme(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+
+        let window = self.window.clone().unwrap();
+        let overrides = self.config_overrides.clone();
+        let color_scheme = self.config.color_scheme.clone();
+        let (overlay, future) = start_overlay(self, &tab, move |_tab_id, term| {
+            preview_theme(term, window, overrides, color_scheme)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    fn toggle_input_lock(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab_id = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab.tab_id(),
+            None => return,
+        };
+        self.input_locks.toggle_tab(tab_id);
+        self.update_title();
+    }
+
+    fn toggle_client_input_lock(&mut self) {
+        inputlock::toggle_client_lock();
+        self.update_title();
+    }
+
+    /// Returns true if input to `pane` must be confirmed before
+    /// it is delivered.  Overlays, such as the unlock prompt
+    /// itself, are never locked.
+    fn input_is_locked(&self, pane: &Rc<dyn Pane>) -> bool {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return false,
+        };
+        if !self
+            .input_locks
+            .is_locked(tab.tab_id(), inputlock::client_is_locked())
+        {
+            return false;
+        }
+        tab.get_active_pane()
+            .map(|active| active.pane_id() == pane.pane_id())
+            .unwrap_or(false)
+            && self.pane_state(pane.pane_id()).overlay.is_none()
+    }
+
+    /// Swallows input to a locked tab and asks whether the
+    /// tab should be unlocked
+    fn confirm_input_unlock(&mut self) {
+        let mux = Mux::get().unwrap();
+        let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
+            Some(tab) => tab,
+            None => return,
+        };
+        let window = self.window.clone().unwrap();
+        let (overlay, future) = start_overlay(self, &tab, move |tab_id, term| {
+            confirm_unlock_input(term, window, tab_id)
+        });
+        self.assign_overlay(tab.tab_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
+    fn show_char_select(&mut self) {
+        let pane = match self.get_active_pane_no_overlay() {
+            Some(pane) => pane,
+            None => return,
+        };
+
+        let (overlay, future) =
+            start_overlay_pane(self, &pane, move |pane_id, term| char_select(pane_id, term));
+        self.assign_overlay_for_pane(pane.pane_id(), overlay);
+        promise::spawn::spawn(future).detach();
+    }
+
     fn show_launcher(&mut self) {
         let mux = Mux::get().unwrap();
         let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
@@ -1138,6 +1732,16 @@ impl TermWindow {
             a.domain_id().cmp(&b.domain_id())
         });
         domains.retain(|dom| dom.spawnable());
+        let spawn_targets: Vec<(String, SpawnTarget)> = domains
+            .iter()
+            .filter(|dom| dom.state() == DomainState::Attached)
+            .flat_map(|dom| {
+                let name = dom.domain_name().to_string();
+                dom.spawn_targets()
+                    .into_iter()
+                    .map(move |target| (name.clone(), target))
+            })
+            .collect();
         let domains: Vec<(DomainId, DomainState, String)> = domains
             .iter()
             .map(|dom| {
@@ -1165,6 +1769,7 @@ impl TermWindow {
                 term,
                 mux_window_id,
                 domains,
+                spawn_targets,
                 clipboard,
                 size,
             )
@@ -1285,6 +1890,20 @@ impl TermWindow {
             ToggleFullScreen => {
                 self.window.as_ref().unwrap().toggle_fullscreen();
             }
+            ToggleAlwaysOnTop => {
+                self.is_always_on_top = !self.is_always_on_top;
+                self.window
+                    .as_ref()
+                    .unwrap()
+                    .set_always_on_top(self.is_always_on_top);
+            }
+            ToggleVisibleOnAllWorkspaces => {
+                self.is_visible_on_all_workspaces = !self.is_visible_on_all_workspaces;
+                self.window
+                    .as_ref()
+                    .unwrap()
+                    .set_visible_on_all_workspaces(self.is_visible_on_all_workspaces);
+            }
             Copy => {
                 let text = self.selection_text(pane);
                 self.copy_to_clipboard(
@@ -1315,6 +1934,7 @@ impl TermWindow {
             ActivateTab(n) => {
                 self.activate_tab(*n)?;
             }
+            ActivateLastTab => self.activate_last_tab()?,
             SendString(s) => pane.writer().write_all(s.as_bytes())?,
             Hide => {
                 if let Some(w) = self.window.as_ref() {
@@ -1337,6 +1957,37 @@ impl TermWindow {
             ScrollToPrompt(n) => self.scroll_to_prompt(*n)?,
             ShowTabNavigator => self.show_tab_navigator(),
             ShowLauncher => self.show_launcher(),
+            ShowDomains => self.show_domain_status(),
+            ShowDebugOverlay => self.show_debug_overlay(),
+            PreviewTheme => self.show_preview_theme(),
+            ToggleInputLock => self.toggle_input_lock(),
+            ToggleClientInputLock => self.toggle_client_input_lock(),
+            TogglePersistScrollback => {
+                let mux = Mux::get().unwrap();
+                if let Some(tab) = mux.get_active_tab_for_window(self.mux_window_id) {
+                    crate::persist::toggle_tab(tab.tab_id());
+                }
+            }
+            AttachDomain(name) => {
+                let mux = Mux::get().unwrap();
+                match mux.get_domain_by_name(name) {
+                    Some(domain) => attach_domain(domain.domain_id()),
+                    None => log::error!("AttachDomain: domain name {} is invalid", name),
+                }
+            }
+            DetachDomain(spawn_domain) => {
+                let mux = Mux::get().unwrap();
+                let domain = match spawn_domain {
+                    SpawnTabDomain::DefaultDomain => Some(mux.default_domain()),
+                    SpawnTabDomain::CurrentPaneDomain => mux.get_domain(pane.domain_id()),
+                    SpawnTabDomain::DomainName(name) => mux.get_domain_by_name(name),
+                };
+                match domain {
+                    Some(domain) => detach_domain(domain.domain_id()),
+                    None => log::error!("DetachDomain: domain {:?} is invalid", spawn_domain),
+                }
+            }
+            CharSelect => self.show_char_select(),
             HideApplication => {
                 let con = Connection::get().expect("call on gui thread");
                 con.hide_application();
@@ -1404,7 +2055,11 @@ impl TermWindow {
                         };
                         if default_click {
                             log::info!("clicking {}", link);
-                            if let Err(err) = open::that(&link) {
+                            let result = match &configuration().open_uri_command {
+                                Some(argv) => open_uri_with_command(argv, &link),
+                                None => open::that(&link).map(|_| ()).map_err(Into::into),
+                            };
+                            if let Err(err) = result {
                                 log::error!("failed to open {}: {:?}", link, err);
                             }
                         }
@@ -1420,6 +2075,9 @@ impl TermWindow {
             EmitEvent(name) => {
                 self.emit_window_event(name);
             }
+            RunProgram(program) => {
+                self.run_program(pane, program);
+            }
             CompleteSelectionOrOpenLinkAtMouseCursor(dest) => {
                 let text = self.selection_text(pane);
                 if !text.is_empty() {
@@ -1444,6 +2102,11 @@ impl TermWindow {
                 let window = self.window.as_ref().unwrap();
                 window.invalidate();
             }
+            ClearScreen => {
+                pane.erase_scrollback(ScrollbackEraseMode::ViewportOnly);
+                let window = self.window.as_ref().unwrap();
+                window.invalidate();
+            }
             Search(pattern) => {
                 if let Some(pane) = self.get_active_pane_no_overlay() {
                     let search = SearchOverlay::with_pane(self, &pane, pattern.clone());
@@ -1456,6 +2119,24 @@ impl TermWindow {
                     self.assign_overlay_for_pane(pane.pane_id(), copy);
                 }
             }
+            QuickSelect => {
+                if let Some(pane) = self.get_active_pane_no_overlay() {
+                    let qs = QuickSelectOverlay::with_pane(self, &pane);
+                    self.assign_overlay_for_pane(pane.pane_id(), qs);
+                }
+            }
+            TogglePaneOutputPipe(command) => {
+                if let Some(pane) = self.get_active_pane_no_overlay() {
+                    let command = if pane.has_output_pipe() {
+                        None
+                    } else {
+                        Some(command.clone())
+                    };
+                    if let Err(err) = pane.set_output_pipe(command) {
+                        log::error!("TogglePaneOutputPipe: {:#}", err);
+                    }
+                }
+            }
             AdjustPaneSize(direction, amount) => {
                 let mux = Mux::get().unwrap();
                 let tab = match mux.get_active_tab_for_window(self.mux_window_id) {
@@ -1490,6 +2171,12 @@ impl TermWindow {
                 };
                 tab.toggle_zoom();
             }
+            ToggleInvisibleCharacters => {
+                self.show_invisible_chars = !self.show_invisible_chars;
+                if let Some(window) = self.window.as_ref() {
+                    window.invalidate();
+                }
+            }
         };
         Ok(())
     }
@@ -1604,6 +2291,16 @@ impl TermWindow {
         }
     }
 
+    /// Applies `normalize_input_to_unicode_nfc` to text that
+    /// is about to be sent to a pane
+    fn normalize_input<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.config.normalize_input_to_unicode_nfc {
+            Cow::Owned(text.nfc().collect())
+        } else {
+            Cow::Borrowed(text)
+        }
+    }
+
     fn maybe_scroll_to_bottom_for_input(&mut self, pane: &Rc<dyn Pane>) {
         if self.config.scroll_to_bottom_on_input {
             self.scroll_to_bottom(pane);
@@ -1723,6 +2420,28 @@ impl TermWindow {
         });
     }
 
+    pub fn schedule_unlock_input(window: Window, tab_id: TabId) {
+        window.apply(move |myself, _| {
+            if let Some(myself) = myself.downcast_mut::<Self>() {
+                myself.input_locks.unlock(tab_id);
+                inputlock::unlock_client();
+                myself.update_title();
+            }
+            Ok(())
+        });
+    }
+
+    /// Replaces the per-window config overrides and applies them
+    pub fn schedule_config_overrides(window: Window, overrides: serde_json::Value) {
+        window.apply(move |myself, _| {
+            if let Some(myself) = myself.downcast_mut::<Self>() {
+                myself.config_overrides = overrides;
+                myself.config_was_reloaded();
+            }
+            Ok(())
+        });
+    }
+
     fn cancel_overlay_for_pane(&self, pane_id: PaneId) {
         if let Some(pane) = self.pane_state(pane_id).overlay.take() {
             // Ungh, when I built the CopyOverlay, its pane doesn't get
@@ -1761,3 +2480,18 @@ impl TermWindow {
         self.update_title();
     }
 }
+
+/// Opens `uri` by running the `open_uri_command` in `argv`
+fn open_uri_with_command(argv: &[String], uri: &str) -> anyhow::Result<()> {
+    let mut args: Vec<String> = argv.iter().map(|arg| arg.replace("$URI", uri)).collect();
+    if !argv.iter().any(|arg| arg.contains("$URI")) {
+        args.push(uri.to_string());
+    }
+    let (prog, args) = args
+        .split_first()
+        .ok_or_else(|| anyhow!("open_uri_command is empty"))?;
+    let mut child = std::process::Command::new(prog).args(args).spawn()?;
+    // Reap the child when it exits, without blocking the gui
+    std::thread::spawn(move || child.wait());
+    Ok(())
+}
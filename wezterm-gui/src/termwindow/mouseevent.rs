@@ -18,7 +18,35 @@ use wezterm_term::input::MouseEventKind as TMEK;
 use wezterm_term::{LastMouseClick, StableRowIndex};
 
 impl super::TermWindow {
+    /// Accumulates a precise scroll of `pixels`, returning the number
+    /// of whole lines to scroll by once it amounts to at least one line
+    fn pixel_scroll_to_lines(&mut self, pixels: i16) -> Option<i16> {
+        let cell_height = self.render_metrics.cell_size.height as f64;
+        let pixels = self.scroll_pixel_remainder + pixels as f64;
+        let lines = (pixels / cell_height).trunc();
+        self.scroll_pixel_remainder = pixels - lines * cell_height;
+        if lines == 0. {
+            None
+        } else {
+            Some(lines as i16)
+        }
+    }
+
     pub fn mouse_event_impl(&mut self, event: &MouseEvent, context: &dyn WindowOps) {
+        if let WMEK::VertPixelWheel(pixels) = event.kind {
+            // Precise scrolling is accumulated until it amounts to a
+            // whole line, so that the viewport tracks the distance
+            // that was scrolled rather than jumping per event
+            if let Some(lines) = self.pixel_scroll_to_lines(pixels) {
+                let event = MouseEvent {
+                    kind: WMEK::VertWheel(lines),
+                    ..event.clone()
+                };
+                self.mouse_event_impl(&event, context);
+            }
+            return;
+        }
+
         let pane = match self.get_active_pane_or_overlay() {
             Some(pane) => pane,
             None => return,
@@ -68,6 +96,10 @@ impl super::TermWindow {
                     // Completed a window drag
                     return;
                 }
+                if press == &MousePress::Left && std::mem::take(&mut self.tab_drag_active) {
+                    // Completed a tab drag
+                    return;
+                }
             }
 
             WMEK::Press(ref press) => {
@@ -81,12 +113,23 @@ impl super::TermWindow {
                 // Perform click counting
                 let button = mouse_press_to_tmb(press);
 
+                let position = (event.coords.x, event.coords.y);
                 let click = match self.last_mouse_click.take() {
-                    None => LastMouseClick::new(button),
-                    Some(click) => click.add(button),
+                    None => LastMouseClick::new(button, position),
+                    Some(click) => click.add(
+                        button,
+                        position,
+                        Duration::from_millis(self.config.double_click_interval_ms),
+                        scale_by_dpi(
+                            self.config.click_radius,
+                            self.dimensions.dpi,
+                            ::window::default_dpi(),
+                        ),
+                    ),
                 };
                 self.last_mouse_click = Some(click);
                 self.current_mouse_button = Some(press.clone());
+                self.is_dragging = self.config.drag_threshold == 0;
             }
 
             WMEK::VertWheel(amount) if !pane.is_mouse_grabbed() && !pane.is_alt_screen_active() => {
@@ -102,6 +145,27 @@ impl super::TermWindow {
             }
 
             WMEK::Move => {
+                if self.tab_drag_active && !event.mouse_buttons.contains(WMB::LEFT) {
+                    // The button was released somewhere that we didn't
+                    // get to see it, such as outside of the window
+                    self.tab_drag_active = false;
+                }
+                if self.tab_drag_active {
+                    // Dragging a tab; move it to the position of the
+                    // tab that is under the mouse
+                    if let TabBarItem::Tab(tab_idx) = self.tab_bar.hit_test(x) {
+                        let active = Mux::get()
+                            .unwrap()
+                            .get_window(self.mux_window_id)
+                            .map(|window| window.get_active_idx());
+                        if in_tab_bar && active != Some(tab_idx) {
+                            self.move_tab(tab_idx).ok();
+                            context.invalidate();
+                        }
+                    }
+                    return;
+                }
+
                 if let Some(start) = self.window_drag_position.as_ref() {
                     // Dragging the window
                     // Compute the distance since the initial event
@@ -194,6 +258,8 @@ impl super::TermWindow {
             WMEK::Press(MousePress::Left) => match self.tab_bar.hit_test(x) {
                 TabBarItem::Tab(tab_idx) => {
                     self.activate_tab(tab_idx as isize).ok();
+                    // Potentially starting to drag the tab to a new position
+                    self.tab_drag_active = true;
                 }
                 TabBarItem::NewTabButton => {
                     self.spawn_tab(&SpawnTabDomain::CurrentPaneDomain);
@@ -342,6 +408,7 @@ impl super::TermWindow {
                         WMEK::Release(_) => {}
                         WMEK::VertWheel(_) => {}
                         WMEK::HorzWheel(_) => {}
+                        WMEK::VertPixelWheel(_) => {}
                     }
                 }
                 x = x.saturating_sub(pos.left);
@@ -428,9 +495,30 @@ impl super::TermWindow {
                 }
             }
             WMEK::Move => {
-                if let Some(LastMouseClick { streak, button, .. }) = self.last_mouse_click.as_ref()
+                if let Some(LastMouseClick {
+                    streak,
+                    button,
+                    position,
+                    ..
+                }) = self.last_mouse_click.as_ref()
                 {
-                    if Some(*button) == self.current_mouse_button.as_ref().map(mouse_press_to_tmb) {
+                    if !self.is_dragging {
+                        // Ignore small movements while the button is held,
+                        // so that a click with a slightly unsteady hand
+                        // doesn't start a selection
+                        let threshold = scale_by_dpi(
+                            self.config.drag_threshold,
+                            self.dimensions.dpi,
+                            ::window::default_dpi(),
+                        );
+                        self.is_dragging = (event.coords.x - position.0).abs() > threshold
+                            || (event.coords.y - position.1).abs() > threshold;
+                    }
+                    if !self.is_dragging {
+                        None
+                    } else if Some(*button)
+                        == self.current_mouse_button.as_ref().map(mouse_press_to_tmb)
+                    {
                         Some(MouseEventTrigger::Drag {
                             streak: *streak,
                             button: *button,
@@ -442,7 +530,7 @@ impl super::TermWindow {
                     None
                 }
             }
-            WMEK::VertWheel(_) | WMEK::HorzWheel(_) => None,
+            WMEK::VertWheel(_) | WMEK::HorzWheel(_) | WMEK::VertPixelWheel(_) => None,
         };
 
         let ignore_grab_modifier = Modifiers::SHIFT;
@@ -470,7 +558,10 @@ impl super::TermWindow {
         let mouse_event = wezterm_term::MouseEvent {
             kind: match event.kind {
                 WMEK::Move => TMEK::Move,
-                WMEK::VertWheel(_) | WMEK::HorzWheel(_) | WMEK::Press(_) => TMEK::Press,
+                WMEK::VertWheel(_)
+                | WMEK::HorzWheel(_)
+                | WMEK::VertPixelWheel(_)
+                | WMEK::Press(_) => TMEK::Press,
                 WMEK::Release(_) => TMEK::Release,
             },
             button: match event.kind {
@@ -493,13 +584,23 @@ impl super::TermWindow {
                         TMB::WheelDown((-amount) as usize)
                     }
                 }
-                WMEK::HorzWheel(_) => TMB::None,
+                WMEK::HorzWheel(_) | WMEK::VertPixelWheel(_) => TMB::None,
             },
             x,
             y,
             modifiers: window_mods_to_termwiz_mods(event.modifiers),
         };
 
+        // Mouse reporting, and the wheel in the alternate screen, send
+        // input to the application, so they are subject to input locks
+        if (pane.is_mouse_grabbed() || pane.is_alt_screen_active()) && self.input_is_locked(&pane) {
+            if let WMEK::Press(_) = event.kind {
+                self.confirm_input_unlock();
+                context.invalidate();
+            }
+            return;
+        }
+
         pane.mouse_event(mouse_event).ok();
 
         match event.kind {
@@ -518,3 +619,35 @@ fn mouse_press_to_tmb(press: &MousePress) -> TMB {
         MousePress::Middle => TMB::Middle,
     }
 }
+
+/// Scales a distance of `pixels`, which is expressed at `default_dpi`,
+/// to the `dpi` of the window, so that the mouse thresholds cover the
+/// same physical distance on high density displays
+fn scale_by_dpi(pixels: u16, dpi: usize, default_dpi: f64) -> isize {
+    (pixels as f64 * dpi as f64 / default_dpi).round() as isize
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn thresholds_scale_with_dpi() {
+        assert_eq!(scale_by_dpi(4, 96, 96.0), 4);
+        assert_eq!(scale_by_dpi(4, 192, 96.0), 8);
+        assert_eq!(scale_by_dpi(4, 144, 96.0), 6);
+        assert_eq!(scale_by_dpi(4, 144, 72.0), 8);
+        assert_eq!(scale_by_dpi(0, 192, 96.0), 0);
+    }
+
+    #[test]
+    fn scaled_click_radius_counts_double_clicks() {
+        let radius = scale_by_dpi(4, 192, 96.0);
+        let interval = Duration::from_millis(500);
+        let click = LastMouseClick::new(TMB::Left, (0, 0));
+        let click = click.add(TMB::Left, (6, 6), interval, radius);
+        assert_eq!(click.streak, 2);
+        let click = click.add(TMB::Left, (15, 6), interval, radius);
+        assert_eq!(click.streak, 1);
+    }
+}
@@ -9,22 +9,24 @@ use ::window::glium::uniforms::{
     MagnifySamplerFilter, MinifySamplerFilter, Sampler, SamplerWrapFunction,
 };
 use ::window::glium::{uniform, BlendingFunction, LinearBlendingFactor, Surface};
+use ::window::{Connection, ConnectionOps};
 use anyhow::anyhow;
-use config::ConfigHandle;
 use config::TextStyle;
-use mux::pane::Pane;
+use config::{ConfigHandle, HighlightRule};
+use mux::pane::{Pane, PaneId};
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::{PositionedPane, PositionedSplit, SplitDirection};
+use mux::termwiztermtab::TermWizTerminalPane;
 use std::ops::Range;
 use std::rc::Rc;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use termwiz::cellcluster::CellCluster;
 use termwiz::surface::{CursorShape, CursorVisibility};
 use wezterm_font::units::PixelLength;
 use wezterm_font::GlyphInfo;
 use wezterm_term::color::{ColorAttribute, ColorPalette, RgbColor};
-use wezterm_term::{CellAttributes, Line, StableRowIndex};
+use wezterm_term::{Cell, CellAttributes, Line, StableRowIndex};
 use window::bitmaps::atlas::SpriteSlice;
 use window::bitmaps::Texture2d;
 use window::Color;
@@ -39,6 +41,9 @@ pub struct RenderScreenLineOpenGLParams<'a> {
     pub dims: &'a RenderableDimensions,
     pub config: &'a ConfigHandle,
     pub pos: &'a PositionedPane,
+    /// If set, the line is rendered using the window_frame font,
+    /// if any, rather than the style selected by the font rules
+    pub frame_font: bool,
 
     pub cursor_border_color: Color,
     pub foreground: Color,
@@ -81,6 +86,12 @@ impl super::TermWindow {
             frame.clear_color_srgb(r, g, b, a);
         }
 
+        if self.render_state.is_none() {
+            // We weren't able to set up rendering after the opengl
+            // context was lost, so there is nothing more that we can draw
+            return;
+        }
+
         for pass in 0.. {
             match self.paint_opengl_pass() {
                 Ok(_) => break,
@@ -133,13 +144,40 @@ impl super::TermWindow {
         }
     }
 
+    /// Applies the highlight_rules to `line`, reusing the result from
+    /// a prior frame if the line at that position hasn't changed
+    fn highlight_line(
+        &self,
+        pane_id: PaneId,
+        stable_row: StableRowIndex,
+        line: &Line,
+    ) -> Option<Rc<Line>> {
+        if self.config.highlight_rules.is_empty() {
+            return None;
+        }
+        let mut cache = self.highlight_cache.borrow_mut();
+        if let Some((matched, highlighted)) = cache.get(&(pane_id, stable_row)) {
+            if matched == line {
+                return highlighted.clone();
+            }
+        }
+        let highlighted = apply_highlight_rules(line, &self.config.highlight_rules).map(Rc::new);
+        cache.put((pane_id, stable_row), (line.clone(), highlighted.clone()));
+        highlighted
+    }
+
     pub fn paint_pane_opengl(&mut self, pos: &PositionedPane) -> anyhow::Result<()> {
+        let palette = self.pane_palette(&pos.pane);
         let config = &self.config;
-        let palette = pos.pane.palette();
 
         let background_color = palette.resolve_bg(wezterm_term::color::ColorAttribute::Default);
         let first_line_offset = if self.show_tab_bar { 1 } else { 0 };
 
+        // Overlays such as the launcher are part of the window frame
+        // rather than terminal content, so they use the frame font
+        // and are not subject to the highlight_rules
+        let is_overlay = pos.pane.downcast_ref::<TermWizTerminalPane>().is_some();
+
         let cursor = pos.pane.get_cursor_position();
         if pos.is_active {
             self.prev_cursor.update(&cursor);
@@ -160,6 +198,30 @@ impl super::TermWindow {
             lines = vp_lines;
         }
 
+        let trail_duration = Duration::from_millis(config.cursor_animation_duration);
+        if pos.is_active {
+            let cursor_row = cursor.y - stable_top;
+            if cursor.visibility == CursorVisibility::Visible
+                && cursor_row >= 0
+                && (cursor_row as usize) < dims.viewport_rows
+            {
+                let cell = (
+                    (pos.left + cursor.x) as f32,
+                    (pos.top + first_line_offset + cursor_row as usize) as f32,
+                );
+                let animate = trail_duration.as_millis() > 0
+                    && !Connection::get()
+                        .map(|conn| conn.prefers_reduced_motion())
+                        .unwrap_or(false);
+                self.cursor_trail.update(
+                    cell,
+                    animate,
+                    trail_duration,
+                    config.cursor_animation_easing,
+                );
+            }
+        }
+
         let gl_state = self.render_state.as_ref().unwrap();
         let mut vb = gl_state.glyph_vertex_buffer.borrow_mut();
         let mut quads = gl_state.quads.map(&mut vb);
@@ -185,6 +247,7 @@ impl super::TermWindow {
                     cursor_border_color,
                     foreground,
                     pos,
+                    frame_font: true,
                     is_active: true,
                 },
                 &mut quads,
@@ -275,6 +338,28 @@ impl super::TermWindow {
                 .map(|sel| sel.cols_for_row(stable_row))
                 .unwrap_or(0..0);
 
+            let highlighted = if is_overlay {
+                None
+            } else {
+                self.highlight_line(pos.pane.pane_id(), stable_row, line)
+            };
+            let line = highlighted.as_deref().unwrap_or(line);
+
+            let revealed;
+            let line = if self.show_invisible_chars {
+                // Only on the cursor line can we tell that blank cells
+                // were produced by spaces or tabs rather than being unused
+                let trailing_limit = if stable_row == cursor.y {
+                    Some(cursor.x)
+                } else {
+                    None
+                };
+                revealed = reveal_invisible_chars(line, trailing_limit);
+                revealed.as_ref().unwrap_or(line)
+            } else {
+                line
+            };
+
             self.render_screen_line_opengl(
                 RenderScreenLineOpenGLParams {
                     line_idx: line_idx + first_line_offset,
@@ -288,12 +373,52 @@ impl super::TermWindow {
                     cursor_border_color,
                     foreground,
                     pos,
+                    frame_font: is_overlay,
                     is_active: pos.is_active,
                 },
                 &mut quads,
             )?;
         }
 
+        if pos.is_active {
+            let mut quad = quads.cursor_trail();
+            let white_space = gl_state.util_sprites.white_space.texture_coords();
+            quad.set_texture(white_space);
+            quad.set_texture_adjust(0., 0., 0., 0.);
+            quad.set_underline(white_space);
+            quad.set_cursor(white_space);
+            quad.set_hsv(None);
+            quad.set_has_color(false);
+
+            match self
+                .cursor_trail
+                .position(trail_duration, config.cursor_animation_easing)
+            {
+                Some(((x, y), progress)) => {
+                    let cell_width = self.render_metrics.cell_size.width as f32;
+                    let cell_height = self.render_metrics.cell_size.height as f32;
+                    let left = (self.dimensions.pixel_width as f32 / -2.)
+                        + config.window_padding.left as f32
+                        + x * cell_width;
+                    let top = (self.dimensions.pixel_height as f32 / -2.)
+                        + config.window_padding.top as f32
+                        + y * cell_height;
+
+                    // The trail fades out as it catches up with the cursor
+                    let alpha = ((1. - progress) * 128.) as u8;
+                    let color = rgbcolor_alpha_to_window_color(palette.cursor_bg, alpha);
+                    quad.set_bg_color(color);
+                    quad.set_fg_color(color);
+                    quad.set_underline_color(color);
+                    quad.set_cursor_color(color);
+                    quad.set_position(left, top, left + cell_width, top + cell_height);
+
+                    self.update_next_frame_time(Some(Instant::now() + Duration::from_millis(16)));
+                }
+                None => quad.set_position(0., 0., 0., 0.),
+            }
+        }
+
         Ok(())
     }
 
@@ -446,7 +571,7 @@ impl super::TermWindow {
         } else {
             "─"
         };
-        let palette = pane.palette();
+        let palette = self.pane_palette(pane);
         let foreground = rgbcolor_to_window_color(palette.split);
         let background = rgbcolor_alpha_to_window_color(
             palette.background,
@@ -457,6 +582,39 @@ impl super::TermWindow {
             },
         );
 
+        let first_row_offset = if self.show_tab_bar { 1 } else { 0 };
+
+        if config.window_frame.split_thickness.is_some() {
+            // Draw a solid line of the configured thickness rather
+            // than using a glyph from the font
+            let sprite = if split.direction == SplitDirection::Horizontal {
+                &gl_state.util_sprites.split_vertical
+            } else {
+                &gl_state.util_sprites.split_horizontal
+            };
+            let line_rect = sprite.texture_coords();
+            let white_space = gl_state.util_sprites.white_space.texture_coords();
+
+            for (x, y) in split_cells(split) {
+                let mut quad = match quads.cell(x, y + first_row_offset) {
+                    Ok(quad) => quad,
+                    Err(_) => break,
+                };
+
+                quad.set_fg_color(foreground);
+                quad.set_underline_color(foreground);
+                quad.set_bg_color(background);
+                quad.set_hsv(None);
+                quad.set_texture(white_space);
+                quad.set_texture_adjust(0., 0., 0., 0.);
+                quad.set_underline(white_space);
+                quad.set_has_color(false);
+                quad.set_cursor(line_rect);
+                quad.set_cursor_color(foreground);
+            }
+            return Ok(());
+        }
+
         let style = self.fonts.match_style(&config, &CellAttributes::default());
         let glyph_info = {
             let key = BorrowedShapeCacheKey { style, text };
@@ -465,7 +623,12 @@ impl super::TermWindow {
                 Some(Err(err)) => return Err(err),
                 None => {
                     let font = self.fonts.resolve_font(style)?;
-                    match font.shape(text) {
+                    // Only cache misses are timed, so this is the cost
+                    // of shaping text that hasn't been seen before
+                    let start = std::time::Instant::now();
+                    let shaped = font.shape(text);
+                    metrics::histogram!("gui.shape", start.elapsed());
+                    match shaped {
                         Ok(info) => {
                             let line = Line::from_text(&text, &CellAttributes::default());
                             let clusters = line.cluster();
@@ -496,7 +659,6 @@ impl super::TermWindow {
                 }
             }
         };
-        let first_row_offset = if self.show_tab_bar { 1 } else { 0 };
 
         for info in glyph_info.iter() {
             let glyph = &info.glyph;
@@ -512,14 +674,7 @@ impl super::TermWindow {
                 .unwrap_or(&gl_state.util_sprites.white_space);
             let underline_tex_rect = gl_state.util_sprites.white_space.texture_coords();
 
-            let x_y_iter: Box<dyn Iterator<Item = (usize, usize)>> = if split.direction
-                == SplitDirection::Horizontal
-            {
-                Box::new(std::iter::repeat(split.left).zip(split.top..split.top + split.size))
-            } else {
-                Box::new((split.left..split.left + split.size).zip(std::iter::repeat(split.top)))
-            };
-            for (x, y) in x_y_iter {
+            for (x, y) in split_cells(split) {
                 let slice = SpriteSlice {
                     cell_idx: 0,
                     num_cells: info.pos.num_cells as usize,
@@ -556,9 +711,68 @@ impl super::TermWindow {
         Ok(())
     }
 
+    /// Positions the quads that draw the border around the terminal
+    /// area that is enabled by `window_frame.border_thickness`
+    fn paint_border_opengl(&mut self) -> anyhow::Result<()> {
+        let thickness = self.config.window_frame.border_thickness as f32;
+        let color = rgbcolor_to_window_color(self.border_color());
+
+        let gl_state = self.render_state.as_ref().unwrap();
+        let mut vb = gl_state.glyph_vertex_buffer.borrow_mut();
+        let mut quads = gl_state.quads.map(&mut vb);
+        let white_space = gl_state.util_sprites.white_space.texture_coords();
+
+        let cell_width = self.render_metrics.cell_size.width as f32;
+        let cell_height = self.render_metrics.cell_size.height as f32;
+        let first_row_offset = if self.show_tab_bar { 1. } else { 0. };
+
+        let left =
+            (self.dimensions.pixel_width as f32 / -2.) + self.config.window_padding.left as f32;
+        let top = (self.dimensions.pixel_height as f32 / -2.)
+            + self.config.window_padding.top as f32
+            + first_row_offset * cell_height;
+        let right = left + self.terminal_size.cols as f32 * cell_width;
+        let bottom = top + self.terminal_size.rows as f32 * cell_height;
+
+        let edges = [
+            (left - thickness, top - thickness, right + thickness, top),
+            (
+                left - thickness,
+                bottom,
+                right + thickness,
+                bottom + thickness,
+            ),
+            (left - thickness, top, left, bottom),
+            (right, top, right + thickness, bottom),
+        ];
+
+        for (idx, &(l, t, r, b)) in edges.iter().enumerate() {
+            let mut quad = quads.border(idx);
+            quad.set_texture(white_space);
+            quad.set_texture_adjust(0., 0., 0., 0.);
+            quad.set_underline(white_space);
+            quad.set_cursor(white_space);
+            quad.set_hsv(None);
+            quad.set_has_color(false);
+            quad.set_bg_color(color);
+            quad.set_fg_color(color);
+            quad.set_underline_color(color);
+            quad.set_cursor_color(color);
+            if thickness > 0. {
+                quad.set_position(l, t, r, b);
+            } else {
+                quad.set_position(0., 0., 0., 0.);
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn paint_opengl_pass(&mut self) -> anyhow::Result<()> {
         let panes = self.get_panes_to_render();
 
+        self.paint_border_opengl()?;
+
         if let Some(pane) = self.get_active_pane_or_overlay() {
             let splits = self.get_splits();
             for split in &splits {
@@ -628,7 +842,15 @@ impl super::TermWindow {
                 (Some(ref this), &Some(ref highlight)) => Arc::ptr_eq(this, highlight),
                 _ => false,
             };
-            let style = self.fonts.match_style(params.config, attrs);
+            let frame_style = if params.frame_font {
+                params.config.window_frame.font_for(attrs)
+            } else {
+                None
+            };
+            let style = match frame_style {
+                Some(style) => style,
+                None => self.fonts.match_style(params.config, attrs),
+            };
 
             let bg_is_default = attrs.background == ColorAttribute::Default;
             let bg_color = params.palette.resolve_bg(attrs.background);
@@ -707,7 +929,10 @@ impl super::TermWindow {
                     Some(Err(err)) => return Err(err),
                     None => {
                         let font = self.fonts.resolve_font(style)?;
-                        match font.shape(&cluster.text) {
+                        let start = std::time::Instant::now();
+                        let shaped = font.shape(&cluster.text);
+                        metrics::histogram!("gui.shape", start.elapsed());
+                        match shaped {
                             Ok(info) => {
                                 let glyphs = self.glyph_infos_to_glyphs(
                                     cluster,
@@ -747,6 +972,23 @@ impl super::TermWindow {
                     - (glyph.y_offset + glyph.bearing_y))
                     .get() as f32;
 
+                // The fonts that provide the single_width_codepoints rarely
+                // agree with the metrics of the primary font, so rather than
+                // sitting on the baseline their glyphs are centered in the cell
+                let centered = glyph.texture.is_some()
+                    && cluster
+                        .text
+                        .get(info.pos.cluster as usize..)
+                        .and_then(|text| text.chars().next())
+                        .map(|c| {
+                            params
+                                .config
+                                .single_width_codepoints
+                                .iter()
+                                .any(|range| range.contains(c))
+                        })
+                        .unwrap_or(false);
+
                 // underline and strikethrough
                 let underline_tex_rect = gl_state
                     .glyph_cache
@@ -843,6 +1085,14 @@ impl super::TermWindow {
                     let pixel_rect = slice.pixel_rect(texture);
                     let texture_rect = texture.texture.to_texture_coords(pixel_rect);
 
+                    let top = if centered {
+                        (self.render_metrics.cell_size.height as f32
+                            - pixel_rect.size.height as f32 * glyph.scale as f32)
+                            / 2.0
+                    } else {
+                        top
+                    };
+
                     let left = if glyph_idx == 0 { left } else { 0.0 };
                     let bottom = (pixel_rect.size.height as f32 * glyph.scale as f32) + top
                         - self.render_metrics.cell_size.height as f32;
@@ -1094,6 +1344,12 @@ impl super::TermWindow {
                         .duration_since(self.prev_cursor.last_cursor_movement())
                         .as_millis();
                     let ticks = milli_uptime / params.config.cursor_blink_rate as u128;
+                    // Arrange to repaint when the cursor is next due to toggle
+                    let next_toggle = self.prev_cursor.last_cursor_movement()
+                        + Duration::from_millis(
+                            ((ticks + 1) * params.config.cursor_blink_rate as u128) as u64,
+                        );
+                    self.update_next_frame_time(Some(next_toggle));
                     (
                         shape,
                         if (ticks & 1) == 0 {
@@ -1176,7 +1432,7 @@ impl super::TermWindow {
     pub fn clear_texture_atlas(&mut self) -> anyhow::Result<()> {
         self.shape_cache.borrow_mut().clear();
         if let Some(render_state) = self.render_state.as_mut() {
-            render_state.clear_texture_atlas(&self.render_metrics)?;
+            render_state.clear_texture_atlas(&self.config, &self.render_metrics)?;
         }
         Ok(())
     }
@@ -1184,7 +1440,12 @@ impl super::TermWindow {
     pub fn recreate_texture_atlas(&mut self, size: Option<usize>) -> anyhow::Result<()> {
         self.shape_cache.borrow_mut().clear();
         if let Some(render_state) = self.render_state.as_mut() {
-            render_state.recreate_texture_atlas(&self.fonts, &self.render_metrics, size)?;
+            render_state.recreate_texture_atlas(
+                &self.config,
+                &self.fonts,
+                &self.render_metrics,
+                size,
+            )?;
         }
         Ok(())
     }
@@ -1197,3 +1458,185 @@ fn rgbcolor_to_window_color(color: RgbColor) -> Color {
 fn rgbcolor_alpha_to_window_color(color: RgbColor, alpha: u8) -> Color {
     Color::rgba(color.red, color.green, color.blue, alpha)
 }
+
+/// Returns the cells occupied by `split`
+fn split_cells(split: &PositionedSplit) -> Box<dyn Iterator<Item = (usize, usize)>> {
+    if split.direction == SplitDirection::Horizontal {
+        Box::new(std::iter::repeat(split.left).zip(split.top..split.top + split.size))
+    } else {
+        Box::new((split.left..split.left + split.size).zip(std::iter::repeat(split.top)))
+    }
+}
+
+/// Shown in place of trailing whitespace
+const TRAILING_WHITESPACE_PLACEHOLDER: char = '\u{b7}';
+
+/// Returns the visible placeholder to render in place of `c`, if `c`
+/// is a character that would otherwise be invisible.
+fn invisible_char_placeholder(c: char) -> Option<char> {
+    match c {
+        // C0 controls map to the Control Pictures block
+        '\u{0}'..='\u{1f}' => std::char::from_u32(0x2400 + c as u32),
+        '\u{7f}' => Some('\u{2421}'),
+        // There are no control pictures for the C1 controls
+        '\u{80}'..='\u{9f}' => Some('\u{bf}'),
+        // No-break space
+        '\u{a0}' => Some('\u{237d}'),
+        // Soft hyphen
+        '\u{ad}' => Some('-'),
+        // Zero width space, word joiner and byte order mark
+        '\u{200b}' | '\u{2060}' | '\u{feff}' => Some('\u{2423}'),
+        _ => None,
+    }
+}
+
+/// Returns a copy of `line` restyled by the `highlight_rules` that
+/// match its text, or None if none of them matched.
+fn apply_highlight_rules(line: &Line, rules: &[HighlightRule]) -> Option<Line> {
+    if rules.is_empty() {
+        return None;
+    }
+
+    // Matches are measured in bytes, so record the byte offset at
+    // which the text of each visible cell begins
+    let mut text = String::new();
+    let mut offsets = vec![];
+    for (idx, cell) in line.visible_cells() {
+        offsets.push((text.len(), idx));
+        text.push_str(cell.str());
+    }
+
+    let mut highlighted: Option<Line> = None;
+    for rule in rules {
+        for m in rule.regex.find_iter(&text) {
+            if m.start() == m.end() {
+                continue;
+            }
+            let cells = highlighted
+                .get_or_insert_with(|| line.clone())
+                .cells_mut_for_attr_changes_only();
+            for &(_, idx) in offsets
+                .iter()
+                .filter(|(offset, _)| *offset >= m.start() && *offset < m.end())
+            {
+                // Restyle the spacer cells of a double width
+                // character along with the character itself
+                let width = cells[idx].width().max(1);
+                for cell in cells.iter_mut().skip(idx).take(width) {
+                    rule.apply(cell.attrs_mut());
+                }
+            }
+        }
+    }
+    highlighted
+}
+
+/// Returns a copy of `line` in which any otherwise invisible characters
+/// are replaced by visible placeholders, or None if there were none.
+/// Blank cells that follow the text of the line and precede
+/// `trailing_limit` are revealed as trailing whitespace.
+fn reveal_invisible_chars(line: &Line, trailing_limit: Option<usize>) -> Option<Line> {
+    let trailing = match trailing_limit {
+        Some(limit) => {
+            let start = line
+                .cells()
+                .iter()
+                .rposition(|cell| cell.str() != " ")
+                .map(|idx| idx + line.cells()[idx].width().max(1))
+                .unwrap_or(0);
+            start..limit
+        }
+        None => 0..0,
+    };
+
+    let mut revealed: Option<Line> = None;
+    for (idx, cell) in line.cells().iter().enumerate() {
+        let mut chars = cell.str().chars();
+        let placeholder = match (chars.next(), chars.next()) {
+            (Some(' '), None) if trailing.contains(&idx) => Some(TRAILING_WHITESPACE_PLACEHOLDER),
+            (Some(c), None) => invisible_char_placeholder(c),
+            _ => None,
+        };
+        if let Some(placeholder) = placeholder {
+            let mut attrs = cell.attrs().clone();
+            attrs.set_reverse(!attrs.reverse());
+            revealed
+                .get_or_insert_with(|| line.clone())
+                .set_cell(idx, Cell::new(placeholder, attrs));
+        }
+    }
+    revealed
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(invisible_char_placeholder('a'), None);
+        assert_eq!(invisible_char_placeholder(' '), None);
+        assert_eq!(invisible_char_placeholder('\t'), Some('\u{2409}'));
+        assert_eq!(invisible_char_placeholder('\u{1b}'), Some('\u{241b}'));
+        assert_eq!(invisible_char_placeholder('\u{7f}'), Some('\u{2421}'));
+        assert_eq!(invisible_char_placeholder('\u{85}'), Some('\u{bf}'));
+        assert_eq!(invisible_char_placeholder('\u{a0}'), Some('\u{237d}'));
+        assert_eq!(invisible_char_placeholder('\u{200b}'), Some('\u{2423}'));
+    }
+
+    fn line_text(line: &Line) -> String {
+        line.cells().iter().map(|cell| cell.str()).collect()
+    }
+
+    #[test]
+    fn trailing_whitespace() {
+        let mut line = Line::from_text("ls\u{a0}-l  ", &CellAttributes::default());
+        line.resize(10);
+
+        assert_eq!(
+            line_text(&reveal_invisible_chars(&line, None).unwrap()),
+            "ls\u{237d}-l     "
+        );
+
+        // Only the blanks before the cursor are trailing whitespace
+        assert_eq!(
+            line_text(&reveal_invisible_chars(&line, Some(7)).unwrap()),
+            "ls\u{237d}-l\u{b7}\u{b7}   "
+        );
+
+        let line = Line::from_text("echo", &CellAttributes::default());
+        assert!(reveal_invisible_chars(&line, Some(4)).is_none());
+    }
+
+    #[test]
+    fn highlight_rules() {
+        let red = RgbColor::new(0xff, 0, 0);
+        let rules = vec![HighlightRule {
+            regex: regex::Regex::new("ERROR").unwrap(),
+            foreground: Some(red),
+            background: None,
+            intensity: None,
+            underline: None,
+            italic: None,
+            reverse: None,
+            strikethrough: None,
+        }];
+
+        let line = Line::from_text("ok", &CellAttributes::default());
+        assert!(apply_highlight_rules(&line, &rules).is_none());
+        assert!(apply_highlight_rules(&line, &[]).is_none());
+
+        let line = Line::from_text("an ERROR here", &CellAttributes::default());
+        let highlighted = apply_highlight_rules(&line, &rules).unwrap();
+        assert_eq!(line_text(&highlighted), "an ERROR here");
+        let restyled: Vec<bool> = highlighted
+            .cells()
+            .iter()
+            .map(|cell| {
+                cell.attrs().foreground == ColorAttribute::TrueColorWithDefaultFallback(red)
+            })
+            .collect();
+        let expected: Vec<bool> = (0..13).map(|idx| (3..8).contains(&idx)).collect();
+        assert_eq!(restyled, expected);
+    }
+}
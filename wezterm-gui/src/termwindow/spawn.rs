@@ -1,12 +1,70 @@
-use crate::termwindow::{ClipboardHelper, MuxWindowId};
+use crate::termwindow::{ClipboardHelper, MuxWindowId, TermWindow};
 use anyhow::{anyhow, bail};
-use config::keyassignment::{SpawnCommand, SpawnTabDomain};
+use config::keyassignment::{RunProgram, RunProgramOutput, SpawnCommand, SpawnTabDomain};
 use mux::activity::Activity;
-use mux::domain::DomainState;
+use mux::domain::{command_dir_from_url, DomainState};
+use mux::pane::Pane;
 use mux::tab::SplitDirection;
 use mux::Mux;
 use portable_pty::{CommandBuilder, PtySize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::rc::Rc;
 use std::sync::Arc;
+use window::WindowOps;
+
+/// The longest selection that is passed to `RunProgram` in full via
+/// `WEZTERM_SELECTION_TEXT`; the length of an environment variable is
+/// limited, to 128KiB on Linux, and exceeding that prevents the program
+/// from being run at all
+const MAX_SELECTION_ENV_LEN: usize = 32 * 1024;
+
+/// Returns the longest prefix of `text` that is no more than `max` bytes
+fn truncate_at_char_boundary(text: &str, max: usize) -> &str {
+    if text.len() <= max {
+        return text;
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Describes the selection to a program run by `RunProgram`.
+/// `WEZTERM_SELECTION_TEXT` holds the selection, truncated if it is
+/// too long to pass via the environment, in which case the complete
+/// text is written to the file named by `WEZTERM_SELECTION_FILE`.
+fn selection_environment(
+    text: &str,
+    pane_id: mux::pane::PaneId,
+    env: &mut HashMap<String, String>,
+) {
+    let truncated = truncate_at_char_boundary(text, MAX_SELECTION_ENV_LEN);
+    env.insert("WEZTERM_SELECTION_TEXT".to_string(), truncated.to_string());
+    if truncated.len() == text.len() {
+        return;
+    }
+
+    // There is one file per pane, which is replaced each time
+    let path = config::RUNTIME_DIR.join(format!("selection-{}-{}", std::process::id(), pane_id));
+    match config::create_user_owned_dirs(&config::RUNTIME_DIR)
+        .and_then(|_| Ok(std::fs::write(&path, text)?))
+    {
+        Ok(()) => {
+            env.insert(
+                "WEZTERM_SELECTION_FILE".to_string(),
+                path.to_string_lossy().into_owned(),
+            );
+        }
+        Err(err) => log::error!(
+            "RunProgram: the selection was truncated, and writing it to {} failed: {:#}",
+            path.display(),
+            err
+        ),
+    }
+}
 
 #[derive(Copy, Debug, Clone, Eq, PartialEq)]
 pub enum SpawnWhere {
@@ -109,21 +167,7 @@ impl super::TermWindow {
                     )
                 })?)
             } else {
-                match cwd {
-                    Some(url) if url.scheme() == "file" => {
-                        let path = url.path().to_string();
-                        // On Windows the file URI can produce a path like:
-                        // `/C:\Users` which is valid in a file URI, but the leading slash
-                        // is not liked by the windows file APIs, so we strip it off here.
-                        let bytes = path.as_bytes();
-                        if bytes.len() > 2 && bytes[0] == b'/' && bytes[2] == b':' {
-                            Some(path[1..].to_owned())
-                        } else {
-                            Some(path)
-                        }
-                    }
-                    Some(_) | None => None,
-                }
+                cwd.as_ref().and_then(command_dir_from_url)
             };
 
             let cmd_builder = if let Some(args) = spawn.args {
@@ -135,6 +179,13 @@ impl super::TermWindow {
                     builder.cwd(cwd);
                 }
                 Some(builder)
+            } else if !spawn.set_environment_variables.is_empty() {
+                // The domain will run its default_prog with these applied
+                let mut builder = CommandBuilder::new_default_prog();
+                for (k, v) in spawn.set_environment_variables.iter() {
+                    builder.env(k, v);
+                }
+                Some(builder)
             } else {
                 None
             };
@@ -171,7 +222,7 @@ impl super::TermWindow {
                             .get_window_mut(target_window_id)
                             .ok_or_else(|| anyhow!("no such window!?"))?;
                         if let Some(idx) = window.idx_by_id(tab_id) {
-                            window.set_active(idx);
+                            window.save_and_then_set_active(idx);
                         }
                     }
                 }
@@ -184,6 +235,105 @@ impl super::TermWindow {
         .detach();
     }
 
+    /// Runs a program on the host with environment variables that describe
+    /// `pane`, disposing of its output as specified by `program.output`
+    pub fn run_program(&mut self, pane: &Rc<dyn Pane>, program: &RunProgram) {
+        if program.args.is_empty() {
+            log::error!("RunProgram requires at least one argument");
+            return;
+        }
+
+        let cwd = pane
+            .get_current_working_dir()
+            .as_ref()
+            .and_then(command_dir_from_url);
+        // The pane may belong to a remote domain, so only run
+        // in its directory if that also exists on this host
+        let local_cwd = cwd.as_ref().map(PathBuf::from).filter(|dir| dir.is_dir());
+
+        let mut env: HashMap<String, String> = HashMap::new();
+        env.insert("WEZTERM_PANE".to_string(), pane.pane_id().to_string());
+        if let Some(cwd) = cwd {
+            env.insert("WEZTERM_PANE_CWD".to_string(), cwd);
+        }
+        selection_environment(&self.selection_text(pane), pane.pane_id(), &mut env);
+        env.extend(program.set_environment_variables.clone());
+
+        if program.output == RunProgramOutput::NewTab {
+            // Host programs always run in the local domain, even if
+            // the active pane belongs to some other domain
+            self.spawn_command(
+                &SpawnCommand {
+                    args: Some(program.args.clone()),
+                    set_environment_variables: env,
+                    cwd: local_cwd,
+                    domain: SpawnTabDomain::DomainName("local".to_string()),
+                    ..Default::default()
+                },
+                SpawnWhere::NewTab,
+            );
+            return;
+        }
+
+        let mut cmd = Command::new(&program.args[0]);
+        cmd.args(&program.args[1..])
+            .envs(&env)
+            .stdin(Stdio::null())
+            .stderr(Stdio::null());
+        if let Some(dir) = local_cwd {
+            cmd.current_dir(dir);
+        }
+
+        let paste = program.output == RunProgramOutput::PasteIntoPane;
+        if !paste {
+            cmd.stdout(Stdio::null());
+        }
+
+        let args = program.args.clone();
+        let pane_id = pane.pane_id();
+        let window = self.window.as_ref().unwrap().clone();
+
+        promise::spawn::spawn(async move {
+            let output = match smol::unblock(move || cmd.output()).await {
+                Ok(output) => output,
+                Err(err) => {
+                    log::error!("RunProgram: failed to run {:?}: {}", args, err);
+                    return Ok(());
+                }
+            };
+            if !output.status.success() {
+                log::error!("RunProgram: {:?} exited with {}", args, output.status);
+            }
+            if !paste || output.stdout.is_empty() {
+                return Ok(());
+            }
+
+            // Drop the trailing newline that most programs emit, so
+            // that pasting doesn't also submit the line
+            let text = String::from_utf8_lossy(&output.stdout)
+                .trim_end_matches(&['\r', '\n'][..])
+                .to_string();
+            window
+                .apply(move |term_window, _window| {
+                    if let Some(term_window) = term_window.downcast_mut::<TermWindow>() {
+                        if let Some(pane) =
+                            term_window.pane_state(pane_id).overlay.clone().or_else(|| {
+                                let mux = Mux::get().unwrap();
+                                mux.get_pane(pane_id)
+                            })
+                        {
+                            let text = term_window.normalize_input(&text).into_owned();
+                            pane.trickle_paste(text).ok();
+                        }
+                    }
+                    Ok(())
+                })
+                .await?;
+            Ok::<(), anyhow::Error>(())
+        })
+        .detach();
+    }
+
     pub fn spawn_tab(&mut self, domain: &SpawnTabDomain) {
         self.spawn_command(
             &SpawnCommand {
@@ -194,3 +344,16 @@ impl super::TermWindow {
         );
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn truncation() {
+        assert_eq!(truncate_at_char_boundary("hello", 10), "hello");
+        assert_eq!(truncate_at_char_boundary("hello", 3), "hel");
+        // Don't split the two byte é
+        assert_eq!(truncate_at_char_boundary("café", 4), "caf");
+    }
+}
@@ -0,0 +1,236 @@
+use ::window::{
+    Modifiers, MouseButtons as WMB, MouseEvent, MouseEventKind as WMEK, MousePress, Point,
+    ScreenPoint, TouchEvent, TouchEventKind, WindowOps,
+};
+use std::time::Duration;
+
+/// How long a finger must rest without moving before it starts a selection
+const LONG_PRESS_DURATION: Duration = Duration::from_millis(500);
+
+/// The font scale changes in steps of at least this ratio while pinching,
+/// so that we don't re-layout the window on every tiny movement
+const PINCH_SCALE_STEP: f64 = 0.05;
+
+#[derive(Debug, Clone, Copy)]
+enum TouchGesture {
+    None,
+    /// A single finger is down, but hasn't yet moved far enough,
+    /// or been held long enough, to be recognized as a gesture
+    Pending {
+        start: TouchPoint,
+    },
+    /// A single finger is dragging the viewport
+    Scroll {
+        last_y: isize,
+    },
+    /// A long press started a selection, which is extended as
+    /// the finger moves
+    Select,
+    /// Two fingers are pinching to change the font size
+    Pinch {
+        distance: f64,
+        font_scale: f64,
+    },
+}
+
+impl Default for TouchGesture {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TouchPoint {
+    coords: Point,
+    screen_coords: ScreenPoint,
+}
+
+impl From<&TouchEvent> for TouchPoint {
+    fn from(event: &TouchEvent) -> Self {
+        Self {
+            coords: event.coords,
+            screen_coords: event.screen_coords,
+        }
+    }
+}
+
+/// Tracks the fingers that are touching the window, so that
+/// they can be recognized as gestures
+#[derive(Debug, Default)]
+pub struct TouchState {
+    points: Vec<(u64, TouchPoint)>,
+    gesture: TouchGesture,
+    /// Incremented each time a touch sequence begins, so that a
+    /// long press timer can tell whether it still applies
+    generation: usize,
+}
+
+impl TouchState {
+    fn pinch_distance(&self) -> Option<f64> {
+        match self.points.as_slice() {
+            [(_, a), (_, b), ..] => {
+                let dx = (a.coords.x - b.coords.x) as f64;
+                let dy = (a.coords.y - b.coords.y) as f64;
+                Some((dx * dx + dy * dy).sqrt())
+            }
+            _ => None,
+        }
+    }
+}
+
+impl super::TermWindow {
+    pub fn touch_event_impl(&mut self, event: &TouchEvent, context: &dyn WindowOps) {
+        let point = TouchPoint::from(event);
+        match event.kind {
+            TouchEventKind::Down => {
+                self.touch_state.points.push((event.id, point));
+                match self.touch_state.points.len() {
+                    1 => {
+                        self.touch_state.generation += 1;
+                        self.touch_state.gesture = TouchGesture::Pending { start: point };
+                        self.schedule_long_press(self.touch_state.generation);
+                    }
+                    2 => match self.touch_state.gesture {
+                        TouchGesture::Pending { .. } | TouchGesture::Scroll { .. } => {
+                            if let Some(distance) = self.touch_state.pinch_distance() {
+                                self.touch_state.gesture = TouchGesture::Pinch {
+                                    distance,
+                                    font_scale: self.fonts.get_font_scale(),
+                                };
+                            }
+                        }
+                        _ => {}
+                    },
+                    _ => {}
+                }
+            }
+            TouchEventKind::Move => {
+                match self
+                    .touch_state
+                    .points
+                    .iter_mut()
+                    .find(|(id, _)| *id == event.id)
+                {
+                    Some((_, p)) => *p = point,
+                    None => return,
+                }
+                self.touch_moved(point, context);
+            }
+            TouchEventKind::Up | TouchEventKind::Cancel => {
+                let len = self.touch_state.points.len();
+                self.touch_state.points.retain(|(id, _)| *id != event.id);
+                if self.touch_state.points.len() == len {
+                    return;
+                }
+                match self.touch_state.gesture {
+                    TouchGesture::Pending { start } if event.kind == TouchEventKind::Up => {
+                        // A tap; treat it as a click
+                        self.synthesize_mouse(WMEK::Press(MousePress::Left), start, context);
+                        self.synthesize_mouse(WMEK::Release(MousePress::Left), point, context);
+                    }
+                    TouchGesture::Select => {
+                        self.synthesize_mouse(WMEK::Release(MousePress::Left), point, context);
+                        self.touch_state.gesture = TouchGesture::None;
+                    }
+                    _ => {}
+                }
+                if self.touch_state.points.is_empty() {
+                    self.touch_state.gesture = TouchGesture::None;
+                }
+            }
+        }
+    }
+
+    fn touch_moved(&mut self, point: TouchPoint, context: &dyn WindowOps) {
+        match self.touch_state.gesture {
+            TouchGesture::None => {}
+            TouchGesture::Pending { start } => {
+                // Allow a little wobble before deciding that the
+                // finger is dragging rather than pressing
+                let threshold = self.render_metrics.cell_size.height / 2;
+                if (point.coords.x - start.coords.x).abs() > threshold
+                    || (point.coords.y - start.coords.y).abs() > threshold
+                {
+                    self.touch_state.gesture = TouchGesture::Scroll {
+                        last_y: start.coords.y,
+                    };
+                    self.touch_moved(point, context);
+                }
+            }
+            TouchGesture::Scroll { last_y } => {
+                // Dragging down reveals the earlier content, which is
+                // the same direction as scrolling the wheel up
+                let pixels = point.coords.y - last_y;
+                if pixels != 0 {
+                    self.touch_state.gesture = TouchGesture::Scroll {
+                        last_y: point.coords.y,
+                    };
+                    self.synthesize_mouse(WMEK::VertPixelWheel(pixels as i16), point, context);
+                }
+            }
+            TouchGesture::Select => {
+                self.synthesize_mouse(WMEK::Move, point, context);
+            }
+            TouchGesture::Pinch {
+                distance,
+                font_scale,
+            } => {
+                let current = match self.touch_state.pinch_distance() {
+                    Some(current) if distance > 0. => current,
+                    _ => return,
+                };
+                let scale = font_scale * current / distance;
+                let ratio = scale / self.fonts.get_font_scale();
+                if (ratio - 1.).abs() >= PINCH_SCALE_STEP {
+                    self.adjust_font_scale(scale);
+                }
+            }
+        }
+    }
+
+    fn schedule_long_press(&self, generation: usize) {
+        let window = match self.window.as_ref() {
+            Some(window) => window.clone(),
+            None => return,
+        };
+        promise::spawn::spawn(async move {
+            smol::Timer::after(LONG_PRESS_DURATION).await;
+            window
+                .apply(move |tw, ops| {
+                    if let Some(term_window) = tw.downcast_mut::<Self>() {
+                        term_window.touch_long_press(generation, ops);
+                    }
+                    Ok(())
+                })
+                .await
+        })
+        .detach();
+    }
+
+    fn touch_long_press(&mut self, generation: usize, context: &dyn WindowOps) {
+        if generation != self.touch_state.generation {
+            return;
+        }
+        if let TouchGesture::Pending { start } = self.touch_state.gesture {
+            self.touch_state.gesture = TouchGesture::Select;
+            self.synthesize_mouse(WMEK::Press(MousePress::Left), start, context);
+        }
+    }
+
+    /// Routes a touch gesture through the mouse handling, so that
+    /// it has the same effect as the equivalent mouse action
+    fn synthesize_mouse(&mut self, kind: WMEK, point: TouchPoint, context: &dyn WindowOps) {
+        let mouse_buttons = match (&kind, self.touch_state.gesture) {
+            (WMEK::Press(_), _) | (WMEK::Move, TouchGesture::Select) => WMB::LEFT,
+            _ => WMB::NONE,
+        };
+        let event = MouseEvent {
+            kind,
+            coords: point.coords,
+            screen_coords: point.screen_coords,
+            mouse_buttons,
+            modifiers: Modifiers::NONE,
+        };
+        self.mouse_event_impl(&event, context);
+    }
+}
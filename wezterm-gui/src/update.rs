@@ -9,6 +9,7 @@ use portable_pty::PtySize;
 use regex::Regex;
 use serde::*;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
@@ -256,12 +257,22 @@ fn show_update_available(release: Release) {
     updater.replace(ui);
 }
 
+/// Records the time of, and the release found by, the last check.
+/// This used to be kept in the runtime directory, which is cleared
+/// on reboot, so that location is only consulted if it still exists.
+fn update_file_name() -> PathBuf {
+    config::path_with_legacy_fallback(
+        config::CACHE_DIR.join("check_update"),
+        config::RUNTIME_DIR.join("check_update"),
+    )
+}
+
 pub fn load_last_release_info_and_set_banner() {
     if !configuration().check_for_updates {
         return;
     }
 
-    let update_file_name = config::RUNTIME_DIR.join("check_update");
+    let update_file_name = update_file_name();
     if let Ok(data) = std::fs::read(update_file_name) {
         let latest: Release = match serde_json::from_slice(&data) {
             Ok(d) => d,
@@ -346,7 +357,7 @@ fn update_checker() {
 
     let force_ui = std::env::var_os("WEZTERM_ALWAYS_SHOW_UPDATE_UI").is_some();
 
-    let update_file_name = config::RUNTIME_DIR.join("check_update");
+    let update_file_name = update_file_name();
     let delay = update_file_name
         .metadata()
         .and_then(|metadata| metadata.modified())
@@ -3,7 +3,7 @@ use ::window::bitmaps::atlas::{OutOfTextureSpace, Sprite};
 use ::window::bitmaps::{BitmapImage, Image, Texture2d};
 use ::window::*;
 use anyhow::Context;
-use config::configuration;
+use config::{configuration, ConfigHandle};
 use std::rc::Rc;
 use termwiz::surface::CursorShape;
 use wezterm_font::units::*;
@@ -25,14 +25,21 @@ impl RenderMetrics {
             .default_font_metrics()
             .context("failed to get font metrics!?")?;
 
-        let line_height = configuration().line_height;
+        let config = configuration();
+        let line_height = config.line_height;
 
         let (cell_height, cell_width) = (
             (metrics.cell_height.get() * line_height).ceil() as usize,
             metrics.cell_width.get().ceil() as usize,
         );
 
-        let underline_height = metrics.underline_thickness.get().round().max(1.) as isize;
+        let mut underline_height = metrics.underline_thickness.get().round().max(1.) as isize;
+        if crate::termwindow::high_contrast_enabled(&config) {
+            // Make underlines and the cursor easier to see.
+            // The cursor sprites derive their thickness from this.
+            underline_height =
+                (underline_height * 2).min((cell_height as isize / 4).max(underline_height));
+        }
 
         let descender_row =
             (cell_height as f64 + (metrics.descender - metrics.underline_position).get()) as isize;
@@ -56,10 +63,17 @@ pub struct UtilSprites<T: Texture2d> {
     pub cursor_box: Sprite<T>,
     pub cursor_i_beam: Sprite<T>,
     pub cursor_underline: Sprite<T>,
+    /// A vertical line of `window_frame.split_thickness`,
+    /// centered in the cell
+    pub split_vertical: Sprite<T>,
+    /// A horizontal line of `window_frame.split_thickness`,
+    /// centered in the cell
+    pub split_horizontal: Sprite<T>,
 }
 
 impl<T: Texture2d> UtilSprites<T> {
     pub fn new(
+        config: &ConfigHandle,
         glyph_cache: &mut GlyphCache<T>,
         metrics: &RenderMetrics,
     ) -> Result<Self, OutOfTextureSpace> {
@@ -168,11 +182,37 @@ impl<T: Texture2d> UtilSprites<T> {
         }
         let cursor_underline = glyph_cache.atlas.allocate(&buffer)?;
 
+        let split_thickness = config.window_frame.split_thickness.unwrap_or(1) as isize;
+
+        buffer.clear_rect(cell_rect, black);
+        let thickness = split_thickness.min(metrics.cell_size.width).max(1);
+        buffer.clear_rect(
+            Rect::new(
+                Point::new((metrics.cell_size.width - thickness) / 2, 0),
+                Size::new(thickness, metrics.cell_size.height),
+            ),
+            white,
+        );
+        let split_vertical = glyph_cache.atlas.allocate(&buffer)?;
+
+        buffer.clear_rect(cell_rect, black);
+        let thickness = split_thickness.min(metrics.cell_size.height).max(1);
+        buffer.clear_rect(
+            Rect::new(
+                Point::new(0, (metrics.cell_size.height - thickness) / 2),
+                Size::new(metrics.cell_size.width, thickness),
+            ),
+            white,
+        );
+        let split_horizontal = glyph_cache.atlas.allocate(&buffer)?;
+
         Ok(Self {
             white_space,
             cursor_box,
             cursor_i_beam,
             cursor_underline,
+            split_vertical,
+            split_horizontal,
         })
     }
 
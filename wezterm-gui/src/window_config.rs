@@ -57,6 +57,10 @@ impl WindowConfiguration for ConfigInstance {
         self.0.front_end == config::FrontEndSelection::Software
     }
 
+    fn gpu_power_preference(&self) -> ::window::GpuPowerPreference {
+        self.0.gpu_power_preference
+    }
+
     fn native_macos_fullscreen_mode(&self) -> bool {
         self.0.native_macos_fullscreen_mode
     }
@@ -65,9 +69,23 @@ impl WindowConfiguration for ConfigInstance {
         self.0.window_background_opacity
     }
 
+    fn window_background_material(&self) -> ::window::WindowBackgroundMaterial {
+        self.0.window_background_material
+    }
+
     fn decorations(&self) -> ::window::WindowDecorations {
         self.0.window_decorations
     }
+
+    fn macos_titlebar_appearance(&self) -> ::window::MacOsTitlebarAppearance {
+        self.0.macos_titlebar_appearance
+    }
+
+    fn macos_window_button_position(&self) -> Option<(f64, f64)> {
+        self.0
+            .macos_window_button_position
+            .map(|pos| (pos.x, pos.y))
+    }
 }
 
 fn global() -> ConfigInstance {
@@ -107,6 +125,10 @@ impl WindowConfiguration for ConfigBridge {
         global().prefer_swrast()
     }
 
+    fn gpu_power_preference(&self) -> ::window::GpuPowerPreference {
+        global().gpu_power_preference()
+    }
+
     fn native_macos_fullscreen_mode(&self) -> bool {
         global().native_macos_fullscreen_mode()
     }
@@ -115,7 +137,19 @@ impl WindowConfiguration for ConfigBridge {
         global().window_background_opacity()
     }
 
+    fn window_background_material(&self) -> ::window::WindowBackgroundMaterial {
+        global().window_background_material()
+    }
+
     fn decorations(&self) -> ::window::WindowDecorations {
         global().decorations()
     }
+
+    fn macos_titlebar_appearance(&self) -> ::window::MacOsTitlebarAppearance {
+        global().macos_titlebar_appearance()
+    }
+
+    fn macos_window_button_position(&self) -> Option<(f64, f64)> {
+        global().macos_window_button_position()
+    }
 }
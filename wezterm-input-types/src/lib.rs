@@ -158,6 +158,10 @@ pub enum MouseEventKind {
     Release(MousePress),
     VertWheel(i16),
     HorzWheel(i16),
+    /// A vertical scroll by this many pixels, reported by devices
+    /// such as trackpads that have precise scrolling deltas.
+    /// Positive values scroll up, as for VertWheel.
+    VertPixelWheel(i16),
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -171,6 +175,31 @@ pub struct MouseEvent {
     pub modifiers: Modifiers,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TouchEventKind {
+    /// A finger touched the screen
+    Down,
+    /// A finger that is touching the screen moved
+    Move,
+    /// A finger was lifted from the screen
+    Up,
+    /// The system took over the touch point, for example
+    /// to perform a gesture of its own
+    Cancel,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TouchEvent {
+    pub kind: TouchEventKind,
+    /// Identifies the touch point; it is the same for all of
+    /// the events from a given finger, from Down through Up
+    pub id: u64,
+    /// Coordinates of the touch relative to the top left of the window
+    pub coords: Point,
+    /// The touch position in screen coordinates
+    pub screen_coords: crate::ScreenPoint,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct KeyEvent {
     /// Which key was pressed.
@@ -290,3 +319,59 @@ impl Default for WindowDecorations {
         WindowDecorations::TITLE | WindowDecorations::RESIZE
     }
 }
+
+/// Controls how the titlebar is drawn on macOS
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum MacOsTitlebarAppearance {
+    /// The regular opaque titlebar with the window title
+    Standard,
+    /// No visible titlebar or title; the window content extends
+    /// underneath the close/minimize/zoom buttons and the window
+    /// keeps its rounded corners and shadow
+    Transparent,
+    /// As Transparent, but the close/minimize/zoom buttons
+    /// are hidden too
+    TransparentWithoutButtons,
+}
+
+impl Default for MacOsTitlebarAppearance {
+    fn default() -> Self {
+        MacOsTitlebarAppearance::Standard
+    }
+}
+
+/// Which GPU to render with on systems that have more than one,
+/// such as laptops with both integrated and discrete graphics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum GpuPowerPreference {
+    /// Prefer the integrated, low power GPU
+    LowPower,
+    /// Prefer the discrete, high performance GPU
+    HighPerformance,
+}
+
+impl Default for GpuPowerPreference {
+    fn default() -> Self {
+        GpuPowerPreference::LowPower
+    }
+}
+
+/// Selects a compositor provided effect to render behind
+/// the (partially transparent) window background.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum WindowBackgroundMaterial {
+    /// No effect; transparent areas show whatever is behind the window
+    None,
+    /// A gaussian blur of the content behind the window
+    Blur,
+    /// Windows 10/11 acrylic: a tinted, noisy blur
+    Acrylic,
+    /// Windows 11 mica: a blur of the desktop wallpaper
+    Mica,
+}
+
+impl Default for WindowBackgroundMaterial {
+    fn default() -> Self {
+        WindowBackgroundMaterial::None
+    }
+}
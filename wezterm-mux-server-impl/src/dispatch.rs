@@ -35,7 +35,34 @@ where
     process_async(stream).await
 }
 
-pub async fn process_async<T>(mut stream: Async<T>) -> anyhow::Result<()>
+/// Like `process`, but the peer must redeem an invitation
+/// before any of its other requests are serviced
+pub async fn process_pending_invite<T>(stream: T) -> anyhow::Result<()>
+where
+    T: 'static,
+    T: std::io::Read,
+    T: std::io::Write,
+    T: AsRawDesc,
+    T: std::fmt::Debug,
+{
+    let stream = smol::Async::new(stream)?;
+    process_session(stream, SessionHandler::new_pending_invite).await
+}
+
+pub async fn process_async<T>(stream: Async<T>) -> anyhow::Result<()>
+where
+    T: 'static,
+    T: std::io::Read,
+    T: std::io::Write,
+    T: std::fmt::Debug,
+{
+    process_session(stream, SessionHandler::new).await
+}
+
+async fn process_session<T>(
+    mut stream: Async<T>,
+    new_handler: fn(PduSender) -> SessionHandler,
+) -> anyhow::Result<()>
 where
     T: 'static,
     T: std::io::Read,
@@ -44,6 +71,17 @@ where
 {
     log::trace!("process_async called");
 
+    metrics::increment_counter!("mux.server.connections");
+    metrics::increment_gauge!("mux.server.clients", 1.0);
+    // Decrement the gauge however this session ends
+    struct ClientGuard;
+    impl Drop for ClientGuard {
+        fn drop(&mut self) {
+            metrics::decrement_gauge!("mux.server.clients", 1.0);
+        }
+    }
+    let _guard = ClientGuard;
+
     let (item_tx, item_rx) = smol::channel::unbounded::<Item>();
 
     let pdu_sender = PduSender::new({
@@ -54,7 +92,7 @@ where
                 .map_err(|e| anyhow::anyhow!("{:?}", e))
         }
     });
-    let mut handler = SessionHandler::new(pdu_sender);
+    let mut handler = new_handler(pdu_sender);
 
     {
         let mux = Mux::get().expect("to be running on gui thread");
@@ -68,24 +106,41 @@ where
 
         match smol::future::or(rx_msg, wait_for_read).await {
             Ok(Item::Readable) => {
-                let decoded = Pdu::decode_async(&mut stream).await?;
+                let decoded = match Pdu::decode_async(&mut stream).await {
+                    Ok(decoded) => decoded,
+                    Err(err) => {
+                        metrics::increment_counter!("mux.server.pdu.decode.errors");
+                        return Err(err);
+                    }
+                };
                 handler.process_one(decoded);
             }
             Ok(Item::WritePdu(decoded)) => {
-                decoded
-                    .pdu
-                    .encode_async(&mut stream, decoded.serial)
-                    .await?;
+                // Encode this PDU along with any others that are already
+                // queued so that they go out in a single write and flush,
+                // rather than paying the syscall and TLS record overhead
+                // for each of them individually.
+                let mut buffer = vec![];
+                decoded.pdu.encode(&mut buffer, decoded.serial)?;
+                let mut num_pdus = 1;
+                while let Ok(item) = item_rx.try_recv() {
+                    match item {
+                        Item::WritePdu(decoded) => {
+                            decoded.pdu.encode(&mut buffer, decoded.serial)?;
+                            num_pdus += 1;
+                        }
+                        Item::Notif(notif) => handle_notification(&mut handler, notif),
+                        Item::Readable => {}
+                    }
+                }
+                log::trace!("writing {} PDUs, {} bytes", num_pdus, buffer.len());
+                stream
+                    .write_all(&buffer)
+                    .await
+                    .context("writing PDUs to client")?;
                 stream.flush().await.context("flushing PDU to client")?;
             }
-            Ok(Item::Notif(MuxNotification::PaneOutput(pane_id))) => {
-                handler.schedule_pane_push(pane_id);
-            }
-            Ok(Item::Notif(MuxNotification::Alert { pane_id, alert: _ })) => {
-                // FIXME: queue notification to send to client!
-                handler.schedule_pane_push(pane_id);
-            }
-            Ok(Item::Notif(MuxNotification::WindowCreated(_window_id))) => {}
+            Ok(Item::Notif(notif)) => handle_notification(&mut handler, notif),
             Err(err) => {
                 log::error!("process_async Err {}", err);
                 return Ok(());
@@ -93,3 +148,22 @@ where
         }
     }
 }
+
+fn handle_notification(handler: &mut SessionHandler, notif: MuxNotification) {
+    match notif {
+        MuxNotification::PaneOutput(pane_id) => {
+            handler.schedule_pane_push(pane_id);
+        }
+        MuxNotification::Alert { pane_id, alert: _ } => {
+            // FIXME: queue notification to send to client!
+            handler.schedule_pane_push(pane_id);
+        }
+        MuxNotification::PaneRemoved(_pane_id) => {}
+        MuxNotification::WindowCreated(_window_id) => {}
+        MuxNotification::WindowActivated(_window_id) => {}
+        MuxNotification::ShuttingDown { grace } => {
+            handler.notify_shutting_down(grace);
+        }
+        MuxNotification::ConfigReloaded => {}
+    }
+}
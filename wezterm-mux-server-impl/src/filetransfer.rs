@@ -0,0 +1,143 @@
+//! Implements the file access that is requested by
+//! `wezterm cli download` and `wezterm cli upload`.
+//! Those commands connect to the default unix domain, so the
+//! files are those on the host of that server.
+use anyhow::Context;
+use codec::{ReadFile, ReadFileResponse, WriteFile};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+/// The most that a single ReadFile request may return, so that
+/// a large request can't force the server to buffer the whole file
+const MAX_READ_LEN: u64 = 4 * 1024 * 1024;
+
+/// Relative paths are relative to the home directory, as with scp,
+/// because the working directory of the server is not meaningful
+/// to the client
+fn resolve_path(path: &str) -> PathBuf {
+    let path = PathBuf::from(path);
+    if path.is_absolute() {
+        path
+    } else {
+        config::HOME_DIR.join(path)
+    }
+}
+
+pub fn read_file(req: ReadFile) -> anyhow::Result<ReadFileResponse> {
+    let path = resolve_path(&req.path);
+    let mut file = File::open(&path).with_context(|| format!("opening {}", path.display()))?;
+    file.seek(SeekFrom::Start(req.offset))?;
+
+    let mut data = vec![];
+    file.take(req.len.min(MAX_READ_LEN))
+        .read_to_end(&mut data)
+        .with_context(|| format!("reading {}", path.display()))?;
+    Ok(ReadFileResponse { data })
+}
+
+pub fn write_file(req: WriteFile) -> anyhow::Result<()> {
+    let path = resolve_path(&req.path);
+    let mut file = if req.offset == 0 {
+        File::create(&path)
+    } else {
+        OpenOptions::new().write(true).open(&path)
+    }
+    .with_context(|| format!("opening {}", path.display()))?;
+    file.seek(SeekFrom::Start(req.offset))?;
+    file.write_all(&req.data)
+        .with_context(|| format!("writing {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path =
+                std::env::temp_dir().join(format!("wezterm-{}-{}", name, std::process::id()));
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+
+        fn file(&self, name: &str) -> String {
+            self.0.join(name).to_str().unwrap().to_string()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            std::fs::remove_dir_all(&self.0).ok();
+        }
+    }
+
+    #[test]
+    fn read_at_offset() {
+        let dir = TempDir::new("read-at-offset");
+        let path = dir.file("data");
+        std::fs::write(&path, b"hello world").unwrap();
+
+        let resp = read_file(ReadFile {
+            path: path.clone(),
+            offset: 6,
+            len: 3,
+        })
+        .unwrap();
+        assert_eq!(resp.data, b"wor");
+
+        // Reading past the end returns what is there, then nothing
+        let resp = read_file(ReadFile {
+            path: path.clone(),
+            offset: 6,
+            len: 100,
+        })
+        .unwrap();
+        assert_eq!(resp.data, b"world");
+
+        let resp = read_file(ReadFile {
+            path,
+            offset: 11,
+            len: 100,
+        })
+        .unwrap();
+        assert!(resp.data.is_empty());
+    }
+
+    #[test]
+    fn write_truncates_at_zero_offset() {
+        let dir = TempDir::new("write-truncates");
+        let path = dir.file("data");
+        std::fs::write(&path, b"a much longer existing file").unwrap();
+
+        write_file(WriteFile {
+            path: path.clone(),
+            offset: 0,
+            data: b"hello".to_vec(),
+        })
+        .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello");
+
+        write_file(WriteFile {
+            path: path.clone(),
+            offset: 5,
+            data: b" world".to_vec(),
+        })
+        .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello world");
+    }
+
+    #[test]
+    fn write_at_offset_requires_existing_file() {
+        let dir = TempDir::new("write-missing");
+        assert!(write_file(WriteFile {
+            path: dir.file("missing"),
+            offset: 5,
+            data: b"data".to_vec(),
+        })
+        .is_err());
+    }
+}
@@ -0,0 +1,165 @@
+//! Implements the one-time invitations minted by `wezterm cli invite`.
+//! A client that connects to a TLS server without a certificate must
+//! redeem an invitation before it can do anything else, and its session
+//! is then limited to what the invitation allows.
+use crate::PKI;
+use anyhow::{anyhow, bail, Context};
+use config::TlsDomainServer;
+use mux::pane::PaneId;
+use mux::window::WindowId;
+use mux::Mux;
+use openssl::hash::MessageDigest;
+use openssl::x509::X509;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The longest time for which an invitation may be valid
+pub const MAX_INVITE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// What an invited client is allowed to do
+#[derive(Debug, Clone, Copy)]
+pub struct InviteScope {
+    /// If set, only the panes in this window are visible
+    pub window_id: Option<WindowId>,
+    /// If true, output may be viewed but input, resizes
+    /// and spawning are rejected
+    pub read_only: bool,
+}
+
+impl InviteScope {
+    /// Returns true if the scope permits access to `pane_id`.
+    /// Must be called on the main thread.
+    pub fn allows_pane(&self, pane_id: PaneId) -> bool {
+        match self.window_id {
+            None => true,
+            Some(window_id) => Mux::get()
+                .unwrap()
+                .resolve_pane_id(pane_id)
+                .map(|(_domain_id, pane_window_id, _tab_id)| pane_window_id == window_id)
+                .unwrap_or(false),
+        }
+    }
+}
+
+struct Invite {
+    scope: InviteScope,
+    expires: Instant,
+}
+
+lazy_static::lazy_static! {
+    static ref INVITES: Mutex<HashMap<String, Invite>> = Mutex::new(HashMap::new());
+}
+
+/// Mints a token that can be redeemed once, within `ttl`,
+/// for a session limited to `scope`
+pub fn create_invite(scope: InviteScope, ttl: Duration) -> anyhow::Result<String> {
+    if ttl > MAX_INVITE_TTL {
+        bail!(
+            "invitations may be valid for at most {} hours",
+            MAX_INVITE_TTL.as_secs() / 3600
+        );
+    }
+
+    let mut bytes = [0u8; 24];
+    openssl::rand::rand_bytes(&mut bytes).context("generating invitation token")?;
+    let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+
+    let now = Instant::now();
+    let mut invites = INVITES.lock().unwrap();
+    invites.retain(|_, invite| invite.expires > now);
+    invites.insert(
+        token.clone(),
+        Invite {
+            scope,
+            expires: now + ttl,
+        },
+    );
+    Ok(token)
+}
+
+/// Consumes the invitation identified by `token`,
+/// returning the scope that it grants
+pub fn redeem_invite(token: &str) -> anyhow::Result<InviteScope> {
+    let invite = INVITES
+        .lock()
+        .unwrap()
+        .remove(token)
+        .ok_or_else(|| anyhow!("invalid or already used invitation"))?;
+    if invite.expires <= Instant::now() {
+        bail!("the invitation has expired");
+    }
+    Ok(invite.scope)
+}
+
+/// Returns the first of the configured TLS servers that
+/// accepts invitations
+pub fn invite_server(tls_servers: &[TlsDomainServer]) -> anyhow::Result<&TlsDomainServer> {
+    tls_servers
+        .iter()
+        .find(|server| {
+            server.allow_invitations && !server.websocket && server.sni_certificates.is_empty()
+        })
+        .ok_or_else(|| {
+            anyhow!("none of the tls_servers in the configuration have allow_invitations = true")
+        })
+}
+
+/// Computes the fingerprint of the certificate presented by `server`,
+/// which allows the invited client to verify the server without
+/// having to be given its CA certificate
+pub fn server_fingerprint(server: &TlsDomainServer) -> anyhow::Result<String> {
+    let cert_file = server.pem_cert.clone().unwrap_or_else(|| PKI.server_pem());
+    let pem = std::fs::read(&cert_file)
+        .with_context(|| format!("reading certificate {}", cert_file.display()))?;
+    let cert = X509::from_pem(&pem)
+        .with_context(|| format!("parsing certificate {}", cert_file.display()))?;
+    let digest = cert.digest(MessageDigest::sha256())?;
+    Ok(digest.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const READ_ONLY: InviteScope = InviteScope {
+        window_id: None,
+        read_only: true,
+    };
+
+    #[test]
+    fn invitations_are_redeemed_once() {
+        let token = create_invite(READ_ONLY, Duration::from_secs(60)).unwrap();
+        assert!(redeem_invite(&token).is_ok());
+        assert!(redeem_invite(&token).is_err());
+    }
+
+    #[test]
+    fn expired_invitations_are_rejected() {
+        let token = create_invite(READ_ONLY, Duration::from_secs(0)).unwrap();
+        let err = redeem_invite(&token).unwrap_err();
+        assert_eq!(err.to_string(), "the invitation has expired");
+    }
+
+    #[test]
+    fn ttl_is_limited() {
+        assert!(create_invite(READ_ONLY, MAX_INVITE_TTL + Duration::from_secs(1)).is_err());
+    }
+
+    #[test]
+    fn redeemed_scope_is_read_only() {
+        let token = create_invite(READ_ONLY, Duration::from_secs(60)).unwrap();
+        let scope = redeem_invite(&token).unwrap();
+        assert!(scope.read_only);
+        assert_eq!(scope.window_id, None);
+
+        let scope = InviteScope {
+            window_id: Some(1),
+            read_only: false,
+        };
+        let token = create_invite(scope, Duration::from_secs(60)).unwrap();
+        let scope = redeem_invite(&token).unwrap();
+        assert!(!scope.read_only);
+        assert_eq!(scope.window_id, Some(1));
+    }
+}
@@ -4,9 +4,14 @@ use std::os::unix::net::{UnixListener, UnixStream};
 use uds_windows::{UnixListener, UnixStream};
 
 pub mod dispatch;
+pub mod filetransfer;
+pub mod invite;
 pub mod local;
+pub mod metricshttp;
 pub mod pki;
 pub mod sessionhandler;
+pub mod shutdown;
+pub mod stats;
 
 lazy_static::lazy_static! {
     pub static ref PKI: pki::Pki = pki::Pki::init().expect("failed to initialize PKI");
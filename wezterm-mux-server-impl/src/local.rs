@@ -2,17 +2,58 @@ use crate::UnixListener;
 use anyhow::{anyhow, Context as _};
 use config::{create_user_owned_dirs, UnixDomain};
 use promise::spawn::spawn_into_main_thread;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+lazy_static::lazy_static! {
+    /// The socket files created by this process, which are
+    /// removed by `remove_socket_files`
+    static ref SOCKET_PATHS: Mutex<Vec<PathBuf>> = Mutex::new(vec![]);
+}
+
+/// Removes the socket files that were created by the listeners
+/// in this process, so that clients don't try to connect to a
+/// server that is shutting down.
+pub fn remove_socket_files() {
+    for path in SOCKET_PATHS.lock().unwrap().drain(..) {
+        log::info!("removing {}", path.display());
+        if let Err(err) = std::fs::remove_file(&path) {
+            log::error!("Unable to remove {}: {}", path.display(), err);
+        }
+    }
+}
 
 pub struct LocalListener {
     listener: UnixListener,
+    /// Set for abstract namespace sockets, which have no
+    /// filesystem permissions to restrict who may connect
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    check_peer_is_same_user: bool,
 }
 
 impl LocalListener {
     pub fn new(listener: UnixListener) -> Self {
-        Self { listener }
+        Self {
+            listener,
+            check_peer_is_same_user: false,
+        }
     }
 
     pub fn with_domain(unix_dom: &UnixDomain) -> anyhow::Result<Self> {
+        #[cfg(target_os = "linux")]
+        {
+            let sock_path = unix_dom.socket_path();
+            if let Some(name) = config::abstract_socket_name(&sock_path) {
+                log::info!("setting up abstract socket {}", sock_path.display());
+                let listener = config::bind_abstract_socket(name)
+                    .with_context(|| format!("Failed to bind to {}", sock_path.display()))?;
+                return Ok(Self {
+                    listener,
+                    check_peer_is_same_user: true,
+                });
+            }
+        }
+
         let listener = safely_create_sock_path(unix_dom)?;
         Ok(Self::new(listener))
     }
@@ -21,6 +62,20 @@ impl LocalListener {
         for stream in self.listener.incoming() {
             match stream {
                 Ok(stream) => {
+                    #[cfg(target_os = "linux")]
+                    if self.check_peer_is_same_user {
+                        match config::peer_is_same_user(&stream) {
+                            Ok(true) => {}
+                            Ok(false) => {
+                                log::error!("rejecting connection from a different user");
+                                continue;
+                            }
+                            Err(err) => {
+                                log::error!("unable to determine peer credentials: {}", err);
+                                continue;
+                            }
+                        }
+                    }
                     spawn_into_main_thread(async move {
                         crate::dispatch::process(stream).await.map_err(|e| {
                             log::error!("{:#}", e);
@@ -85,6 +140,8 @@ fn safely_create_sock_path(unix_dom: &UnixDomain) -> anyhow::Result<UnixListener
         },
     }
 
-    UnixListener::bind(sock_path)
-        .with_context(|| format!("Failed to bind to {}", sock_path.display()))
+    let listener = UnixListener::bind(sock_path)
+        .with_context(|| format!("Failed to bind to {}", sock_path.display()))?;
+    SOCKET_PATHS.lock().unwrap().push(sock_path.to_path_buf());
+    Ok(listener)
 }
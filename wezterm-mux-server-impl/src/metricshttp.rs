@@ -0,0 +1,130 @@
+//! Serves the metrics collected by `stats` over HTTP, so that a
+//! long-running mux server can be scraped by Prometheus.
+//! This is enabled by the `metrics_http_address` configuration.
+use anyhow::Context;
+use promise::spawn::{block_on, spawn_into_main_thread};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::Duration;
+
+/// How long to wait for a client to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The most header lines that we'll read from a request
+const MAX_HEADER_LINES: usize = 100;
+
+/// Listens for HTTP requests on `address` in a background thread
+pub fn spawn_metrics_listener(address: &str) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(address)
+        .with_context(|| format!("binding metrics_http_address {}", address))?;
+    log::info!("serving metrics on http://{}/metrics", address);
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    if let Err(err) = serve(stream) {
+                        log::error!("while serving metrics: {:#}", err);
+                    }
+                }
+                Err(err) => log::error!("accepting metrics connection: {}", err),
+            }
+        }
+    });
+    Ok(())
+}
+
+fn serve(stream: TcpStream) -> anyhow::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The headers don't affect the response, but are read so that
+    // the client sees its request fully consumed before we reply
+    for _ in 0..MAX_HEADER_LINES {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim_end().is_empty() {
+            break;
+        }
+    }
+
+    let response = response_for(&request_line, || {
+        // The metrics are labelled using the mux, which lives
+        // on the main thread.  The main thread never waits for
+        // this thread, so blocking on it here cannot deadlock.
+        block_on(spawn_into_main_thread(async {
+            crate::stats::Stats::render_mux_metrics()
+        }))
+    });
+    let mut stream = reader.into_inner();
+    stream.write_all(response.as_bytes())?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Computes the response to the request that starts with
+/// `request_line`, calling `metrics` to obtain the metrics
+/// if the request is for them
+fn response_for(request_line: &str, metrics: impl FnOnce() -> Option<String>) -> String {
+    let mut words = request_line.split_whitespace();
+    let (status, body) = match (words.next(), words.next()) {
+        (Some("GET"), Some("/metrics")) => match metrics() {
+            Some(metrics) => ("200 OK", metrics),
+            None => (
+                "503 Service Unavailable",
+                "metrics are not collected by this server\n".to_string(),
+            ),
+        },
+        (Some("GET"), Some(_)) => ("404 Not Found", "not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "only GET is supported\n".to_string(),
+        ),
+    };
+    format!(
+        "HTTP/1.1 {}\r\n\
+         Content-Type: text/plain; version=0.0.4\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n\
+         {}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn serves_metrics() {
+        let response = response_for("GET /metrics HTTP/1.1\r\n", || {
+            Some("wezterm_mux_server_clients 1\n".to_string())
+        });
+        assert_eq!(
+            response,
+            "HTTP/1.1 200 OK\r\n\
+             Content-Type: text/plain; version=0.0.4\r\n\
+             Content-Length: 29\r\n\
+             Connection: close\r\n\
+             \r\n\
+             wezterm_mux_server_clients 1\n"
+        );
+    }
+
+    #[test]
+    fn rejects_other_requests() {
+        let metrics = || -> Option<String> { panic!("metrics should not be rendered") };
+        assert!(response_for("GET / HTTP/1.1\r\n", metrics).starts_with("HTTP/1.1 404 "));
+        assert!(response_for("POST /metrics HTTP/1.1\r\n", metrics).starts_with("HTTP/1.1 405 "));
+        assert!(response_for("", metrics).starts_with("HTTP/1.1 405 "));
+    }
+
+    #[test]
+    fn reports_when_not_collecting() {
+        let response = response_for("GET /metrics HTTP/1.1\r\n", || None);
+        assert!(response.starts_with("HTTP/1.1 503 "));
+    }
+}
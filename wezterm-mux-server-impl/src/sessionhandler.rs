@@ -1,10 +1,13 @@
+use crate::invite::InviteScope;
 use crate::PKI;
-use anyhow::anyhow;
+use anyhow::{anyhow, bail};
 use codec::*;
 use config::keyassignment::SpawnTabDomain;
+use mux::domain::command_dir_from_url;
 use mux::pane::{Pane, PaneId};
 use mux::renderable::{RenderableDimensions, StableCursorPosition};
 use mux::tab::TabId;
+use mux::window::WindowId;
 use mux::Mux;
 use portable_pty::PtySize;
 use promise::spawn::spawn_into_main_thread;
@@ -40,9 +43,14 @@ struct PerPane {
     cursor_position: StableCursorPosition,
     title: String,
     working_dir: Option<Url>,
+    user_vars: HashMap<String, String>,
     dimensions: RenderableDimensions,
     dirty_lines: RangeSet<StableRowIndex>,
     mouse_grabbed: bool,
+    output_piped: bool,
+    /// Set while a push of changes for this pane is waiting to run,
+    /// so that a burst of output results in a single push
+    push_scheduled: bool,
 }
 
 impl PerPane {
@@ -57,6 +65,11 @@ impl PerPane {
             changed = true;
         }
 
+        let output_piped = pane.has_output_pipe();
+        if output_piped != self.output_piped {
+            changed = true;
+        }
+
         let dims = pane.get_dimensions();
         if dims != self.dimensions {
             changed = true;
@@ -77,6 +90,11 @@ impl PerPane {
             changed = true;
         }
 
+        let user_vars = pane.copy_user_vars();
+        if user_vars != self.user_vars {
+            changed = true;
+        }
+
         let mut all_dirty_lines =
             pane.get_dirty_lines(0..dims.physical_top + dims.viewport_rows as StableRowIndex);
         let dirty_delta = all_dirty_lines.difference(&self.dirty_lines);
@@ -111,21 +129,25 @@ impl PerPane {
         self.cursor_position = cursor_position;
         self.title = title.clone();
         self.working_dir = working_dir.clone();
+        self.user_vars = user_vars.clone();
         self.dimensions = dims;
         self.dirty_lines = all_dirty_lines;
         self.mouse_grabbed = mouse_grabbed;
+        self.output_piped = output_piped;
 
         let dirty_lines = dirty_delta.iter().cloned().collect();
         let bonus_lines = bonus_lines.into();
         Some(GetPaneRenderChangesResponse {
             pane_id: pane.pane_id(),
             mouse_grabbed,
+            output_piped,
             dirty_lines,
             dimensions: dims,
             cursor_position,
             title,
             bonus_lines,
             working_dir: working_dir.map(Into::into),
+            user_vars,
             input_serial: force_with_input_serial,
         })
     }
@@ -141,7 +163,10 @@ fn maybe_push_pane_changes(
     per_pane: Arc<Mutex<PerPane>>,
 ) -> anyhow::Result<()> {
     let mut per_pane = per_pane.lock().unwrap();
-    if let Some(resp) = per_pane.compute_changes(pane, None) {
+    let start = Instant::now();
+    let changes = per_pane.compute_changes(pane, None);
+    metrics::histogram!("mux.server.pane.render", start.elapsed());
+    if let Some(resp) = changes {
         sender.send(DecodedPdu {
             pdu: Pdu::GetPaneRenderChangesResponse(resp),
             serial: 0,
@@ -150,9 +175,22 @@ fn maybe_push_pane_changes(
     Ok(())
 }
 
+/// Describes how the peer of a session was authenticated
+enum Access {
+    /// The peer presented a trusted certificate, or was able
+    /// to reach the unix domain socket
+    Full,
+    /// The peer connected without a certificate and has yet
+    /// to redeem an invitation
+    PendingInvite,
+    /// The peer redeemed an invitation, which limits what it may do
+    Invited(InviteScope),
+}
+
 pub struct SessionHandler {
     to_write_tx: PduSender,
     per_pane: HashMap<TabId, Arc<Mutex<PerPane>>>,
+    access: Access,
 }
 
 impl SessionHandler {
@@ -160,8 +198,51 @@ impl SessionHandler {
         Self {
             to_write_tx,
             per_pane: HashMap::new(),
+            access: Access::Full,
+        }
+    }
+
+    /// Creates a handler for a peer that must redeem an invitation
+    /// before it is allowed to do anything else
+    pub fn new_pending_invite(to_write_tx: PduSender) -> Self {
+        Self {
+            access: Access::PendingInvite,
+            ..Self::new(to_write_tx)
+        }
+    }
+
+    /// Returns the window to which the session is limited, if any
+    fn scoped_window(&self) -> Option<WindowId> {
+        match &self.access {
+            Access::Invited(scope) => scope.window_id,
+            _ => None,
         }
     }
+
+    /// Returns true if the session may see `pane_id`
+    fn allows_pane(&self, pane_id: PaneId) -> bool {
+        match &self.access {
+            Access::Full => true,
+            Access::PendingInvite => false,
+            Access::Invited(scope) => scope.allows_pane(pane_id),
+        }
+    }
+
+    /// Checks whether the session is permitted to make the request
+    fn check_access(&self, pdu: &Pdu) -> anyhow::Result<()> {
+        let scope = match &self.access {
+            Access::Full => return Ok(()),
+            Access::PendingInvite => {
+                return match pdu {
+                    Pdu::Ping(_) | Pdu::GetCodecVersion(_) | Pdu::RedeemInvite(_) => Ok(()),
+                    _ => Err(anyhow!("an invitation must be redeemed first")),
+                };
+            }
+            Access::Invited(scope) => *scope,
+        };
+        check_invite_scope(scope, pdu)
+    }
+
     fn per_pane(&mut self, pane_id: PaneId) -> Arc<Mutex<PerPane>> {
         Arc::clone(
             self.per_pane
@@ -171,9 +252,21 @@ impl SessionHandler {
     }
 
     pub fn schedule_pane_push(&mut self, pane_id: PaneId) {
+        if !self.allows_pane(pane_id) {
+            return;
+        }
         let sender = self.to_write_tx.clone();
         let per_pane = self.per_pane(pane_id);
+        {
+            let mut per_pane = per_pane.lock().unwrap();
+            if per_pane.push_scheduled {
+                // The pending push will pick up this change too
+                return;
+            }
+            per_pane.push_scheduled = true;
+        }
         spawn_into_main_thread(async move {
+            per_pane.lock().unwrap().push_scheduled = false;
             let mux = Mux::get().unwrap();
             let pane = mux
                 .get_pane(pane_id)
@@ -184,6 +277,16 @@ impl SessionHandler {
         .detach();
     }
 
+    /// Tells the client that the server is shutting down
+    pub fn notify_shutting_down(&mut self, grace: std::time::Duration) {
+        self.to_write_tx
+            .send(DecodedPdu {
+                serial: 0,
+                pdu: Pdu::ServerShuttingDown(ServerShuttingDown { grace }),
+            })
+            .ok();
+    }
+
     pub fn process_one(&mut self, decoded: DecodedPdu) {
         let start = Instant::now();
         let sender = self.to_write_tx.clone();
@@ -208,15 +311,24 @@ impl SessionHandler {
             send_response(f());
         }
 
+        if let Err(err) = self.check_access(&decoded.pdu) {
+            send_response(Err(err));
+            return;
+        }
+
         match decoded.pdu {
             Pdu::Ping(Ping {}) => send_response(Ok(Pdu::Pong(Pong {}))),
             Pdu::ListPanes(ListPanes {}) => {
+                let scoped_window = self.scoped_window();
                 spawn_into_main_thread(async move {
                     catch(
                         move || {
                             let mux = Mux::get().unwrap();
                             let mut tabs = vec![];
                             for window_id in mux.iter_windows().into_iter() {
+                                if scoped_window.map(|w| w != window_id).unwrap_or(false) {
+                                    continue;
+                                }
                                 let window = mux.get_window(window_id).unwrap();
                                 for tab in window.iter() {
                                     tabs.push(tab.codec_pane_tree());
@@ -232,6 +344,7 @@ impl SessionHandler {
             }
 
             Pdu::WriteToPane(WriteToPane { pane_id, data }) => {
+                metrics::counter!("pane.input.bytes", data.len() as u64, "pane_id" => pane_id.to_string());
                 let sender = self.to_write_tx.clone();
                 let per_pane = self.per_pane(pane_id);
                 spawn_into_main_thread(async move {
@@ -251,6 +364,7 @@ impl SessionHandler {
                 .detach();
             }
             Pdu::SendPaste(SendPaste { pane_id, data }) => {
+                metrics::counter!("pane.input.bytes", data.len() as u64, "pane_id" => pane_id.to_string());
                 let sender = self.to_write_tx.clone();
                 let per_pane = self.per_pane(pane_id);
                 spawn_into_main_thread(async move {
@@ -406,6 +520,14 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::SpawnV2(spawn) => {
+                let sender = self.to_write_tx.clone();
+                spawn_into_main_thread(async move {
+                    schedule_domain_spawn_v2(spawn, sender, send_response);
+                })
+                .detach();
+            }
+
             Pdu::SplitPane(split) => {
                 let sender = self.to_write_tx.clone();
                 spawn_into_main_thread(async move {
@@ -414,6 +536,127 @@ impl SessionHandler {
                 .detach();
             }
 
+            Pdu::SetPaneUserVar(SetPaneUserVar {
+                pane_id,
+                name,
+                value,
+            }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            pane.set_user_var(name, value)?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::EraseScrollbackRequest(EraseScrollbackRequest {
+                pane_id,
+                erase_mode,
+            }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            pane.erase_scrollback(erase_mode);
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::SetPaneOutputPipe(SetPaneOutputPipe { pane_id, command }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let pane = mux
+                                .get_pane(pane_id)
+                                .ok_or_else(|| anyhow!("no such pane {}", pane_id))?;
+                            pane.set_output_pipe(command)?;
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::MovePaneToNewTab(MovePaneToNewTab { pane_id, window_id }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let (tab, window_id) = mux.move_pane_to_new_tab(pane_id, window_id)?;
+                            Ok(Pdu::MovePaneToNewTabResponse(MovePaneToNewTabResponse {
+                                tab_id: tab.tab_id(),
+                                window_id,
+                            }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::MovePaneToSplit(MovePaneToSplit {
+                pane_id,
+                target_pane_id,
+                direction,
+            }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let (tab, window_id) =
+                                mux.move_pane_to_split(pane_id, target_pane_id, direction)?;
+                            Ok(Pdu::MovePaneToNewTabResponse(MovePaneToNewTabResponse {
+                                tab_id: tab.tab_id(),
+                                window_id,
+                            }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::MoveTabInWindow(MoveTabInWindow { pane_id, tab_idx }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let mux = Mux::get().unwrap();
+                            let (_domain_id, window_id, tab_id) = mux
+                                .resolve_pane_id(pane_id)
+                                .ok_or_else(|| anyhow!("pane {} not found", pane_id))?;
+                            let mut window = mux
+                                .get_window_mut(window_id)
+                                .ok_or_else(|| anyhow!("window {} not found", window_id))?;
+                            let from_idx = window
+                                .idx_by_id(tab_id)
+                                .ok_or_else(|| anyhow!("tab {} not found", tab_id))?;
+                            let to_idx = tab_idx.min(window.len() - 1);
+                            window.move_tab(from_idx, to_idx);
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
             Pdu::GetPaneRenderChanges(GetPaneRenderChanges { pane_id, .. }) => {
                 let sender = self.to_write_tx.clone();
                 let per_pane = self.per_pane(pane_id);
@@ -477,6 +720,112 @@ impl SessionHandler {
                 })))
             }
 
+            Pdu::GetMetrics(_) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            let metrics =
+                                crate::stats::Stats::render_mux_metrics().ok_or_else(|| {
+                                    anyhow!("metrics are not collected by this server")
+                                })?;
+                            Ok(Pdu::GetMetricsResponse(GetMetricsResponse { metrics }))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            Pdu::ShutdownServer(ShutdownServer { grace }) => {
+                spawn_into_main_thread(async move {
+                    catch(
+                        move || {
+                            crate::shutdown::begin_shutdown(grace);
+                            Ok(Pdu::UnitResponse(UnitResponse {}))
+                        },
+                        send_response,
+                    )
+                })
+                .detach();
+            }
+
+            // The disk I/O happens on the blocking thread pool so that
+            // a slow filesystem doesn't stall the mux
+            Pdu::ReadFile(req) => {
+                spawn_into_main_thread(async move {
+                    let result = smol::unblock(move || crate::filetransfer::read_file(req)).await;
+                    send_response(result.map(Pdu::ReadFileResponse));
+                })
+                .detach();
+            }
+
+            Pdu::WriteFile(req) => {
+                spawn_into_main_thread(async move {
+                    let result = smol::unblock(move || crate::filetransfer::write_file(req)).await;
+                    send_response(result.map(|_| Pdu::UnitResponse(UnitResponse {})));
+                })
+                .detach();
+            }
+
+            Pdu::CreateInvite(CreateInvite {
+                window_id,
+                read_only,
+                ttl,
+            }) => {
+                catch(
+                    move || {
+                        let config = config::configuration();
+                        let server = crate::invite::invite_server(&config.tls_servers)?;
+                        let bind_address = server
+                            .bind_address
+                            .first()
+                            .cloned()
+                            .ok_or_else(|| anyhow!("tls_servers entry has no bind_address"))?;
+                        let fingerprint = crate::invite::server_fingerprint(server)?;
+                        if let Some(window_id) = window_id {
+                            if Mux::get().unwrap().get_window(window_id).is_none() {
+                                bail!("no such window {}", window_id);
+                            }
+                        }
+                        let token = crate::invite::create_invite(
+                            InviteScope {
+                                window_id,
+                                read_only,
+                            },
+                            ttl,
+                        )?;
+                        log::info!(
+                            "created invitation for {} with {} access, valid for {:?}",
+                            window_id
+                                .map(|w| format!("window {}", w))
+                                .unwrap_or_else(|| "all windows".to_string()),
+                            if read_only { "read-only" } else { "read-write" },
+                            ttl
+                        );
+                        Ok(Pdu::CreateInviteResponse(CreateInviteResponse {
+                            token,
+                            bind_address,
+                            fingerprint,
+                        }))
+                    },
+                    send_response,
+                );
+            }
+
+            Pdu::RedeemInvite(RedeemInvite { token }) => {
+                match crate::invite::redeem_invite(&token) {
+                    Ok(scope) => {
+                        log::info!("invitation redeemed: {:?}", scope);
+                        self.access = Access::Invited(scope);
+                        send_response(Ok(Pdu::UnitResponse(UnitResponse {})));
+                    }
+                    Err(err) => {
+                        log::warn!("failed to redeem invitation: {:#}", err);
+                        send_response(Err(err));
+                    }
+                }
+            }
+
             Pdu::GetTlsCreds(_) => {
                 catch(
                     move || {
@@ -496,6 +845,7 @@ impl SessionHandler {
             | Pdu::ListPanesResponse { .. }
             | Pdu::SetClipboard { .. }
             | Pdu::SpawnResponse { .. }
+            | Pdu::MovePaneToNewTabResponse { .. }
             | Pdu::GetPaneRenderChangesResponse { .. }
             | Pdu::UnitResponse { .. }
             | Pdu::LivenessResponse { .. }
@@ -503,6 +853,10 @@ impl SessionHandler {
             | Pdu::GetLinesResponse { .. }
             | Pdu::GetCodecVersionResponse { .. }
             | Pdu::GetTlsCredsResponse { .. }
+            | Pdu::GetMetricsResponse { .. }
+            | Pdu::CreateInviteResponse { .. }
+            | Pdu::ReadFileResponse { .. }
+            | Pdu::ServerShuttingDown { .. }
             | Pdu::ErrorResponse { .. } => {
                 send_response(Err(anyhow!("expected a request, got {:?}", decoded.pdu)))
             }
@@ -510,6 +864,29 @@ impl SessionHandler {
     }
 }
 
+/// Returns the pane that a request operates upon, if any
+fn pdu_pane_id(pdu: &Pdu) -> Option<PaneId> {
+    match pdu {
+        Pdu::WriteToPane(WriteToPane { pane_id, .. })
+        | Pdu::SendPaste(SendPaste { pane_id, .. })
+        | Pdu::SearchScrollbackRequest(SearchScrollbackRequest { pane_id, .. })
+        | Pdu::SetPaneZoomed(SetPaneZoomed { pane_id, .. })
+        | Pdu::Resize(Resize { pane_id, .. })
+        | Pdu::SendKeyDown(SendKeyDown { pane_id, .. })
+        | Pdu::SendMouseEvent(SendMouseEvent { pane_id, .. })
+        | Pdu::SplitPane(SplitPane { pane_id, .. })
+        | Pdu::SetPaneUserVar(SetPaneUserVar { pane_id, .. })
+        | Pdu::SetPaneOutputPipe(SetPaneOutputPipe { pane_id, .. })
+        | Pdu::EraseScrollbackRequest(EraseScrollbackRequest { pane_id, .. })
+        | Pdu::MovePaneToNewTab(MovePaneToNewTab { pane_id, .. })
+        | Pdu::MovePaneToSplit(MovePaneToSplit { pane_id, .. })
+        | Pdu::MoveTabInWindow(MoveTabInWindow { pane_id, .. })
+        | Pdu::GetPaneRenderChanges(GetPaneRenderChanges { pane_id, .. })
+        | Pdu::GetLines(GetLines { pane_id, .. }) => Some(*pane_id),
+        _ => None,
+    }
+}
+
 // Dancing around a little bit here; we can't directly spawn_into_main_thread the domain_spawn
 // function below because the compiler thinks that all of its locals then need to be Send.
 // We need to shimmy through this helper to break that aspect of the compiler flow
@@ -521,6 +898,14 @@ where
     promise::spawn::spawn(async move { send_response(domain_spawn(spawn, sender).await) }).detach();
 }
 
+fn schedule_domain_spawn_v2<SND>(spawn: SpawnV2, sender: PduSender, send_response: SND)
+where
+    SND: Fn(anyhow::Result<Pdu>) + 'static,
+{
+    promise::spawn::spawn(async move { send_response(domain_spawn_v2(spawn, sender).await) })
+        .detach();
+}
+
 fn schedule_split_pane<SND>(split: SplitPane, sender: PduSender, send_response: SND)
 where
     SND: Fn(anyhow::Result<Pdu>) + 'static,
@@ -555,7 +940,61 @@ impl Clipboard for RemoteClipboard {
     }
 }
 
+/// Checks whether a client that redeemed an invitation granting
+/// `scope` is permitted to make the request
+fn check_invite_scope(scope: InviteScope, pdu: &Pdu) -> anyhow::Result<()> {
+    match pdu {
+        Pdu::GetTlsCreds(_)
+        | Pdu::GetMetrics(_)
+        | Pdu::ShutdownServer(_)
+        | Pdu::ReadFile(_)
+        | Pdu::WriteFile(_)
+        | Pdu::SetPaneOutputPipe(_)
+        | Pdu::CreateInvite(_)
+        | Pdu::RedeemInvite(_) => bail!("not permitted for an invited client"),
+        Pdu::WriteToPane(_)
+        | Pdu::SendPaste(_)
+        | Pdu::SendKeyDown(_)
+        | Pdu::SendMouseEvent(_)
+        | Pdu::Resize(_)
+        | Pdu::SetPaneZoomed(_)
+        | Pdu::SetPaneUserVar(_)
+        | Pdu::EraseScrollbackRequest(_)
+        | Pdu::Spawn(_)
+        | Pdu::SpawnV2(_)
+        | Pdu::SplitPane(_)
+        | Pdu::MovePaneToNewTab(_)
+        | Pdu::MovePaneToSplit(_)
+        | Pdu::MoveTabInWindow(_)
+            if scope.read_only =>
+        {
+            bail!("the invitation is read-only")
+        }
+        Pdu::MovePaneToSplit(MovePaneToSplit { target_pane_id, .. })
+            if !scope.allows_pane(*target_pane_id) =>
+        {
+            bail!("no such pane {}", target_pane_id)
+        }
+        Pdu::Spawn(Spawn { window_id, .. })
+        | Pdu::SpawnV2(SpawnV2 { window_id, .. })
+        | Pdu::MovePaneToNewTab(MovePaneToNewTab { window_id, .. })
+            if scope.window_id.is_some() && *window_id != scope.window_id =>
+        {
+            bail!("the invitation doesn't grant access to other windows")
+        }
+        _ => {}
+    }
+
+    match pdu_pane_id(pdu) {
+        Some(pane_id) if !scope.allows_pane(pane_id) => bail!("no such pane {}", pane_id),
+        _ => Ok(()),
+    }
+}
+
 async fn split_pane(split: SplitPane, sender: PduSender) -> anyhow::Result<Pdu> {
+    if crate::shutdown::is_shutting_down() {
+        anyhow::bail!("server is shutting down");
+    }
     let mux = Mux::get().unwrap();
     let (pane_domain_id, window_id, tab_id) = mux
         .resolve_pane_id(split.pane_id)
@@ -571,10 +1010,18 @@ async fn split_pane(split: SplitPane, sender: PduSender) -> anyhow::Result<Pdu>
             .ok_or_else(|| anyhow!("domain name {} is invalid", name))?,
     };
 
+    // Start in the working directory of the pane being split,
+    // unless a directory was specified
+    let command_dir = split.command_dir.or_else(|| {
+        mux.get_pane(split.pane_id)
+            .and_then(|pane| pane.get_current_working_dir())
+            .and_then(|url| command_dir_from_url(&url))
+    });
+
     let pane = domain
         .split_pane(
             split.command,
-            split.command_dir,
+            command_dir,
             tab_id,
             split.pane_id,
             split.direction,
@@ -607,9 +1054,65 @@ async fn domain_spawn(spawn: Spawn, sender: PduSender) -> anyhow::Result<Pdu> {
     let domain = mux
         .get_domain(spawn.domain_id)
         .ok_or_else(|| anyhow!("domain {} not found on this server", spawn.domain_id))?;
+    spawn_in_domain(
+        domain,
+        spawn.window_id,
+        spawn.command,
+        spawn.command_dir,
+        spawn.size,
+        sender,
+    )
+    .await
+}
+
+async fn domain_spawn_v2(spawn: SpawnV2, sender: PduSender) -> anyhow::Result<Pdu> {
+    let mux = Mux::get().unwrap();
+    let domain = match spawn.domain {
+        // There is no current pane to speak of; use the default
+        SpawnTabDomain::DefaultDomain | SpawnTabDomain::CurrentPaneDomain => mux.default_domain(),
+        SpawnTabDomain::DomainName(name) => mux
+            .get_domain_by_name(&name)
+            .ok_or_else(|| anyhow!("domain name {} is invalid", name))?,
+    };
+    let response = spawn_in_domain(
+        domain,
+        spawn.window_id,
+        spawn.command,
+        spawn.command_dir,
+        spawn.size,
+        sender,
+    )
+    .await?;
+
+    if let (Some(window_id), Pdu::SpawnResponse(SpawnResponse { tab_id, .. })) =
+        (spawn.window_id, &response)
+    {
+        if let Some(mut window) = mux.get_window_mut(window_id) {
+            if let Some(idx) = window.idx_by_id(*tab_id) {
+                window.save_and_then_set_active(idx);
+            }
+        }
+        mux.notify(mux::MuxNotification::WindowActivated(window_id));
+    }
+
+    Ok(response)
+}
+
+async fn spawn_in_domain(
+    domain: Arc<dyn mux::domain::Domain>,
+    window_id: Option<WindowId>,
+    command: Option<portable_pty::CommandBuilder>,
+    command_dir: Option<String>,
+    size: PtySize,
+    sender: PduSender,
+) -> anyhow::Result<Pdu> {
+    if crate::shutdown::is_shutting_down() {
+        anyhow::bail!("server is shutting down");
+    }
+    let mux = Mux::get().unwrap();
     let window_builder;
 
-    let window_id = if let Some(window_id) = spawn.window_id {
+    let window_id = if let Some(window_id) = window_id {
         mux.get_window_mut(window_id)
             .ok_or_else(|| anyhow!("window_id {} not found on this server", window_id))?;
         window_id
@@ -618,9 +1121,7 @@ async fn domain_spawn(spawn: Spawn, sender: PduSender) -> anyhow::Result<Pdu> {
         *window_builder
     };
 
-    let tab = domain
-        .spawn(spawn.size, spawn.command, spawn.command_dir, window_id)
-        .await?;
+    let tab = domain.spawn(size, command, command_dir, window_id).await?;
 
     let pane = tab
         .get_active_pane()
@@ -639,3 +1140,47 @@ async fn domain_spawn(spawn: Spawn, sender: PduSender) -> anyhow::Result<Pdu> {
         size: tab.get_size(),
     }))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn read_only_invitations_reject_input() {
+        let scope = InviteScope {
+            window_id: None,
+            read_only: true,
+        };
+        assert!(check_invite_scope(scope, &Pdu::ListPanes(ListPanes {})).is_ok());
+        let err = check_invite_scope(
+            scope,
+            &Pdu::WriteToPane(WriteToPane {
+                pane_id: 0,
+                data: b"ls\n".to_vec(),
+            }),
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "the invitation is read-only");
+        assert!(check_invite_scope(scope, &Pdu::GetMetrics(GetMetrics {})).is_err());
+    }
+
+    #[test]
+    fn invitations_are_limited_to_their_window() {
+        let scope = InviteScope {
+            window_id: Some(1),
+            read_only: false,
+        };
+        let spawn = |window_id| {
+            Pdu::Spawn(Spawn {
+                domain_id: 0,
+                window_id,
+                command: None,
+                command_dir: None,
+                size: PtySize::default(),
+            })
+        };
+        assert!(check_invite_scope(scope, &spawn(Some(1))).is_ok());
+        assert!(check_invite_scope(scope, &spawn(Some(2))).is_err());
+        assert!(check_invite_scope(scope, &spawn(None)).is_err());
+    }
+}
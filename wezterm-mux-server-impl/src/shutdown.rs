@@ -0,0 +1,58 @@
+//! Implements the graceful shutdown that is requested by
+//! `wezterm cli shutdown`.
+use mux::activity::Activity;
+use mux::{Mux, MuxNotification};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+
+/// How often to check whether the remaining panes have exited
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Returns true once a shutdown has been requested, after which
+/// no new panes may be spawned.
+pub fn is_shutting_down() -> bool {
+    SHUTTING_DOWN.load(Ordering::SeqCst)
+}
+
+/// Notifies attached clients that the server is shutting down and
+/// waits for up to `grace` for the panes to exit, killing any that
+/// remain after that time.  The socket files are then removed.
+/// The Activity held while this runs keeps the server alive until
+/// that is done; it exits once the mux is empty.
+/// Must be called on the main thread.
+pub fn begin_shutdown(grace: Duration) {
+    if SHUTTING_DOWN.swap(true, Ordering::SeqCst) {
+        log::info!("shutdown is already in progress");
+        return;
+    }
+    log::info!("shutting down; grace period is {:?}", grace);
+
+    let mux = Mux::get().unwrap();
+    mux.notify(MuxNotification::ShuttingDown { grace });
+
+    let activity = Activity::new();
+    promise::spawn::spawn(async move {
+        let deadline = Instant::now() + grace;
+        let mux = Mux::get().unwrap();
+        while !mux.is_empty() && Instant::now() < deadline {
+            smol::Timer::after(POLL_INTERVAL).await;
+        }
+
+        let panes = mux.iter_panes();
+        if !panes.is_empty() {
+            log::warn!(
+                "grace period expired; killing {} remaining panes",
+                panes.len()
+            );
+            for pane in panes {
+                pane.kill();
+            }
+        }
+
+        crate::local::remove_socket_files();
+        drop(activity);
+    })
+    .detach();
+}
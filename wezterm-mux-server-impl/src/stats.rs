@@ -0,0 +1,233 @@
+//! Collects the metrics recorded by the mux server, or by the gui,
+//! so that they can be reported to `wezterm cli metrics`, or served
+//! by `metricshttp`, in the Prometheus text format.
+use hdrhistogram::Histogram;
+use metrics::{GaugeValue, Key, Recorder, Unit};
+use mux::pane::PaneId;
+use mux::Mux;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+lazy_static::lazy_static! {
+    static ref STATS: Stats = Stats::default();
+}
+
+#[derive(Default)]
+struct Inner {
+    counters: HashMap<Key, u64>,
+    gauges: HashMap<Key, f64>,
+    histograms: HashMap<Key, Histogram<u64>>,
+}
+
+/// A copy of the collected metrics taken at a point in time.
+/// Each list is sorted by the name of the metric.
+pub struct Snapshot {
+    pub counters: Vec<(String, u64)>,
+    pub gauges: Vec<(String, f64)>,
+    pub histograms: Vec<(String, Histogram<u64>)>,
+}
+
+#[derive(Default)]
+pub struct Stats {
+    inner: Mutex<Option<Inner>>,
+}
+
+impl Stats {
+    /// Installs the recorder.  Metrics are only collected, and
+    /// `render_prometheus` only returns them, once this has been called.
+    pub fn init() -> anyhow::Result<()> {
+        STATS.inner.lock().unwrap().replace(Inner::default());
+        metrics::set_recorder(&*STATS)
+            .map_err(|e| anyhow::anyhow!("Failed to set metrics recorder:{}", e))
+    }
+
+    /// Discards the metrics that are labelled with `pane_id`, so that
+    /// they don't accumulate as panes are closed
+    pub fn forget_pane(pane_id: PaneId) {
+        let pane_id = pane_id.to_string();
+        let is_pane = |key: &Key| {
+            key.labels()
+                .any(|label| label.key() == "pane_id" && label.value() == pane_id)
+        };
+        if let Some(inner) = STATS.inner.lock().unwrap().as_mut() {
+            inner.counters.retain(|key, _| !is_pane(key));
+            inner.gauges.retain(|key, _| !is_pane(key));
+            inner.histograms.retain(|key, _| !is_pane(key));
+        }
+    }
+
+    /// Returns a copy of the collected metrics.
+    /// Returns None if metrics are not being collected by this process.
+    pub fn snapshot() -> Option<Snapshot> {
+        let inner = STATS.inner.lock().unwrap();
+        let inner = inner.as_ref()?;
+
+        fn sorted<T: Clone>(map: &HashMap<Key, T>) -> Vec<(String, T)> {
+            let mut items: Vec<_> = map
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.clone()))
+                .collect();
+            items.sort_by(|a, b| a.0.cmp(&b.0));
+            items
+        }
+
+        Some(Snapshot {
+            counters: sorted(&inner.counters),
+            gauges: sorted(&inner.gauges),
+            histograms: sorted(&inner.histograms),
+        })
+    }
+
+    /// Renders the collected metrics in the Prometheus text exposition
+    /// format.  `extra_labels` is called for each metric and can return
+    /// additional labels to include, which is used to associate per-pane
+    /// metrics with the tab that currently contains the pane.
+    /// Returns None if metrics are not being collected by this process.
+    pub fn render_prometheus(
+        extra_labels: impl Fn(&Key) -> Vec<(String, String)>,
+    ) -> Option<String> {
+        let inner = STATS.inner.lock().unwrap();
+        let inner = inner.as_ref()?;
+        let mut result = String::new();
+
+        let mut counters: Vec<_> = inner.counters.iter().collect();
+        counters.sort_by_key(|(key, _)| key.to_string());
+        let mut last_name = None;
+        for (key, value) in counters {
+            let name = metric_name(key, "_total");
+            if last_name.as_ref() != Some(&name) {
+                writeln!(result, "# TYPE {} counter", name).ok();
+            }
+            let labels = labels(key, &extra_labels(key), None);
+            writeln!(result, "{}{} {}", name, labels, value).ok();
+            last_name.replace(name);
+        }
+
+        let mut gauges: Vec<_> = inner.gauges.iter().collect();
+        gauges.sort_by_key(|(key, _)| key.to_string());
+        let mut last_name = None;
+        for (key, value) in gauges {
+            let name = metric_name(key, "");
+            if last_name.as_ref() != Some(&name) {
+                writeln!(result, "# TYPE {} gauge", name).ok();
+            }
+            let labels = labels(key, &extra_labels(key), None);
+            writeln!(result, "{}{} {}", name, labels, value).ok();
+            last_name.replace(name);
+        }
+
+        let mut histograms: Vec<_> = inner.histograms.iter().collect();
+        histograms.sort_by_key(|(key, _)| key.to_string());
+        let mut last_name = None;
+        for (key, histogram) in histograms {
+            // As for the stats printed by the gui, anything that isn't
+            // a size is a duration recorded in nanoseconds
+            let (name, scale) = if key.name().to_string().ends_with(".size") {
+                (metric_name(key, ""), 1.0)
+            } else {
+                (metric_name(key, "_seconds"), 1e-9)
+            };
+            if last_name.as_ref() != Some(&name) {
+                writeln!(result, "# TYPE {} summary", name).ok();
+            }
+            let extra = extra_labels(key);
+            for quantile in &[0.5, 0.9, 0.99] {
+                let value = histogram.value_at_quantile(*quantile) as f64 * scale;
+                let labels = labels(key, &extra, Some(*quantile));
+                writeln!(result, "{}{} {}", name, labels, value).ok();
+            }
+            let labels = labels(key, &extra, None);
+            let sum = histogram.mean() * histogram.len() as f64 * scale;
+            writeln!(result, "{}_sum{} {}", name, labels, sum).ok();
+            writeln!(result, "{}_count{} {}", name, labels, histogram.len()).ok();
+            last_name.replace(name);
+        }
+
+        Some(result)
+    }
+
+    /// Renders the collected metrics as for `render_prometheus`,
+    /// labelling the per-pane metrics with the tab that currently
+    /// holds the pane.  Must be called on the main thread.
+    pub fn render_mux_metrics() -> Option<String> {
+        let mux = Mux::get().unwrap();
+        Self::render_prometheus(|key| {
+            key.labels()
+                .filter(|label| label.key() == "pane_id")
+                .filter_map(|label| label.value().parse::<PaneId>().ok())
+                .filter_map(|pane_id| mux.resolve_pane_id(pane_id))
+                .map(|(_domain_id, _window_id, tab_id)| ("tab_id".to_string(), tab_id.to_string()))
+                .collect()
+        })
+    }
+}
+
+/// Maps eg: `pane.output.bytes` to `wezterm_pane_output_bytes`
+fn metric_name(key: &Key, suffix: &str) -> String {
+    let name: String = key
+        .name()
+        .to_string()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("wezterm_{}{}", name, suffix)
+}
+
+fn labels(key: &Key, extra: &[(String, String)], quantile: Option<f64>) -> String {
+    let mut labels: Vec<String> = key
+        .labels()
+        .map(|label| format!("{}={:?}", label.key(), label.value()))
+        .chain(extra.iter().map(|(k, v)| format!("{}={:?}", k, v)))
+        .collect();
+    if let Some(quantile) = quantile {
+        labels.push(format!("quantile=\"{}\"", quantile));
+    }
+    if labels.is_empty() {
+        String::new()
+    } else {
+        format!("{{{}}}", labels.join(","))
+    }
+}
+
+impl Recorder for Stats {
+    fn register_counter(&self, _key: Key, _unit: Option<Unit>, _description: Option<&'static str>) {
+    }
+
+    fn register_gauge(&self, _key: Key, _unit: Option<Unit>, _description: Option<&'static str>) {}
+
+    fn register_histogram(
+        &self,
+        _key: Key,
+        _unit: Option<Unit>,
+        _description: Option<&'static str>,
+    ) {
+    }
+
+    fn increment_counter(&self, key: Key, value: u64) {
+        if let Some(inner) = self.inner.lock().unwrap().as_mut() {
+            *inner.counters.entry(key).or_insert(0) += value;
+        }
+    }
+
+    fn update_gauge(&self, key: Key, value: GaugeValue) {
+        if let Some(inner) = self.inner.lock().unwrap().as_mut() {
+            let gauge = inner.gauges.entry(key).or_insert(0.);
+            match value {
+                GaugeValue::Absolute(v) => *gauge = v,
+                GaugeValue::Increment(v) => *gauge += v,
+                GaugeValue::Decrement(v) => *gauge -= v,
+            }
+        }
+    }
+
+    fn record_histogram(&self, key: Key, value: f64) {
+        if let Some(inner) = self.inner.lock().unwrap().as_mut() {
+            let histogram = inner
+                .histograms
+                .entry(key)
+                .or_insert_with(|| Histogram::new(2).expect("failed to crate new Histogram"));
+            histogram.record(value as u64).ok();
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use config::configuration;
 use mux::activity::Activity;
 use mux::domain::{Domain, LocalDomain};
-use mux::Mux;
+use mux::{Mux, MuxNotification};
 use portable_pty::cmdbuilder::CommandBuilder;
 use std::ffi::OsString;
 use std::process::Command;
@@ -41,6 +41,13 @@ struct Opt {
         number_of_values = 1)]
     config_override: Vec<(String, String)>,
 
+    /// Keep the configuration, logs, sockets and other state in the
+    /// directory that holds the executable, rather than in the home
+    /// directory.  Creating a file named `wezterm-portable` in that
+    /// directory has the same effect
+    #[structopt(long = "portable")]
+    portable: bool,
+
     /// Detach from the foreground and become a background process
     #[structopt(long = "daemonize")]
     daemonize: bool,
@@ -62,16 +69,23 @@ fn main() {
         log::error!("{:#}", err);
         std::process::exit(1);
     }
+    Mux::shutdown();
 }
 
 fn run() -> anyhow::Result<()> {
+    // The options are parsed first, as portable mode
+    // affects where the log file is written
+    let opts = Opt::from_args();
+    if opts.portable {
+        config::enable_portable_mode()?;
+    }
+
     env_bootstrap::bootstrap();
 
-    //stats::Stats::init()?;
+    wezterm_mux_server_impl::stats::Stats::init()?;
     config::designate_this_as_the_main_thread();
     let _saver = umask::UmaskSaver::new();
 
-    let opts = Opt::from_args();
     config::common_init(
         opts.config_file.as_ref(),
         &opts.config_override,
@@ -144,6 +158,9 @@ fn run() -> anyhow::Result<()> {
         std::env::remove_var(name);
     }
 
+    #[cfg(unix)]
+    config::reload_on_sigusr1()?;
+
     let need_builder = !opts.prog.is_empty() || opts.cwd.is_some();
 
     let cmd = if need_builder {
@@ -163,6 +180,15 @@ fn run() -> anyhow::Result<()> {
     let domain: Arc<dyn Domain> = Arc::new(LocalDomain::new("local")?);
     let mux = Rc::new(mux::Mux::new(Some(domain.clone())));
     Mux::set_mux(&mux);
+    mux.subscribe(|n| {
+        if let MuxNotification::PaneRemoved(pane_id) = n {
+            wezterm_mux_server_impl::stats::Stats::forget_pane(pane_id);
+        }
+        true
+    });
+    for domain in mux::domain::create_custom_domains(&config::configuration()) {
+        mux.add_domain(&domain);
+    }
 
     let executor = promise::spawn::SimpleExecutor::new();
 
@@ -212,6 +238,8 @@ fn terminate_with_error(err: anyhow::Error) -> ! {
 }
 
 mod ossl;
+#[cfg(unix)]
+mod websocket;
 
 pub fn spawn_listener() -> anyhow::Result<()> {
     let config = configuration();
@@ -222,9 +250,21 @@ pub fn spawn_listener() -> anyhow::Result<()> {
         });
     }
 
+    // Let `wezterm cli` invoked from panes spawned by this server
+    // find its way back here, rather than to whichever instance
+    // may have been recorded in the environment that started us
+    match config.unix_domains.first() {
+        Some(unix_dom) => std::env::set_var("WEZTERM_UNIX_SOCKET", unix_dom.socket_path()),
+        None => std::env::remove_var("WEZTERM_UNIX_SOCKET"),
+    }
+
     for tls_server in &config.tls_servers {
         ossl::spawn_tls_listener(tls_server)?;
     }
 
+    if let Some(address) = &config.metrics_http_address {
+        wezterm_mux_server_impl::metricshttp::spawn_metrics_listener(address)?;
+    }
+
     Ok(())
 }
@@ -1,24 +1,44 @@
 use anyhow::{anyhow, Context, Error};
 use async_ossl::AsyncSslStream;
 use config::TlsDomainServer;
-use openssl::ssl::{SslAcceptor, SslFiletype, SslMethod, SslStream, SslVerifyMode};
+use lru::LruCache;
+use openssl::ssl::{
+    NameType, SniError, SslAcceptor, SslAcceptorBuilder, SslFiletype, SslMethod, SslStream,
+    SslVerifyMode,
+};
 use openssl::x509::X509;
 use promise::spawn::spawn_into_main_thread;
-use std::net::TcpListener;
+use ratelim::RateLimiter;
+use std::collections::HashMap;
+use std::net::{IpAddr, TcpListener};
 use std::path::Path;
 use std::sync::Arc;
 use wezterm_mux_server_impl::PKI;
 
+/// Bounds the memory used to track the rate limits of peers.
+/// Once this many addresses are tracked, the least recently seen
+/// is forgotten, which merely resets its rate limit.
+const MAX_RATE_LIMITED_PEERS: usize = 1024;
+
 struct OpenSSLNetListener {
     acceptor: Arc<SslAcceptor>,
     listener: TcpListener,
+    websocket: bool,
+    allow_invitations: bool,
 }
 
 impl OpenSSLNetListener {
-    pub fn new(listener: TcpListener, acceptor: SslAcceptor) -> Self {
+    pub fn new(
+        listener: TcpListener,
+        acceptor: Arc<SslAcceptor>,
+        websocket: bool,
+        allow_invitations: bool,
+    ) -> Self {
         Self {
             listener,
-            acceptor: Arc::new(acceptor),
+            acceptor,
+            websocket,
+            allow_invitations,
         }
     }
 
@@ -31,7 +51,8 @@ impl OpenSSLNetListener {
     ///   user running this mux server instance, or must match
     ///   a special encoded prefix set up by a proprietary PKI
     ///   infrastructure in an environment used by the author.
-    fn verify_peer_cert<T>(stream: &SslStream<T>) -> anyhow::Result<()> {
+    /// Returns the CN of the peer certificate.
+    fn verify_peer_cert<T>(stream: &SslStream<T>) -> anyhow::Result<String> {
         let cert = stream
             .ssl()
             .peer_certificate()
@@ -51,7 +72,7 @@ impl OpenSSLNetListener {
                 cn_str,
                 wanted_unix_name
             );
-            Ok(())
+            Ok(cn_str)
         } else {
             // Some environments that are used by the author of this
             // program encode the CN in the form `user:unixname/DATA`
@@ -62,33 +83,101 @@ impl OpenSSLNetListener {
                     cn_str,
                     wanted_unix_name
                 );
-                Ok(())
+                Ok(cn_str)
             } else {
                 anyhow::bail!("CN `{}` did not match $USER `{}`", cn_str, wanted_unix_name);
             }
         }
     }
 
+    /// Accepts connections and dispatches them to the mux.
+    /// Each connection attempt is logged along with the peer address,
+    /// the CN of its certificate and the outcome, so that there is an
+    /// audit trail of who has connected to the server.
+    /// If invitations are allowed, a peer without a certificate is
+    /// accepted but must redeem an invitation before it can do
+    /// anything else.
     fn run(&mut self) {
+        let mut limiters: LruCache<IpAddr, RateLimiter> = LruCache::new(MAX_RATE_LIMITED_PEERS);
+
         for stream in self.listener.incoming() {
             match stream {
                 Ok(stream) => {
+                    let peer = match stream.peer_addr() {
+                        Ok(peer) => peer,
+                        Err(err) => {
+                            log::error!("tls: unable to determine peer address: {}", err);
+                            continue;
+                        }
+                    };
+
+                    if !limiters.contains(&peer.ip()) {
+                        limiters.put(
+                            peer.ip(),
+                            RateLimiter::new(|config| {
+                                config.ratelimit_tls_connections_per_second.max(1)
+                            }),
+                        );
+                    }
+                    let limiter = limiters.get_mut(&peer.ip()).expect("just inserted");
+                    if !limiter.non_blocking_admittance_check(1) {
+                        log::warn!("tls: rejected connection from {}: rate limited", peer);
+                        continue;
+                    }
+
                     stream.set_nodelay(true).ok();
                     let acceptor = self.acceptor.clone();
 
                     match acceptor.accept(stream) {
                         Ok(stream) => {
-                            if let Err(err) = Self::verify_peer_cert(&stream) {
-                                log::error!("problem with peer cert: {}", err);
-                                break;
+                            let pending_invite =
+                                self.allow_invitations && stream.ssl().peer_certificate().is_none();
+                            if pending_invite {
+                                log::info!(
+                                    "tls: accepted connection from {} without a certificate; \
+                                     awaiting an invitation",
+                                    peer
+                                );
+                            } else {
+                                match Self::verify_peer_cert(&stream) {
+                                    Ok(cn) => {
+                                        log::info!(
+                                            "tls: accepted connection from {} with CN `{}`",
+                                            peer,
+                                            cn
+                                        );
+                                    }
+                                    Err(err) => {
+                                        log::warn!(
+                                            "tls: rejected connection from {}: {:#}",
+                                            peer,
+                                            err
+                                        );
+                                        continue;
+                                    }
+                                }
+                            }
+                            #[cfg(unix)]
+                            if self.websocket {
+                                std::thread::spawn(move || {
+                                    if let Err(err) = crate::websocket::serve(stream) {
+                                        log::error!("websocket: {:#}", err);
+                                    }
+                                });
+                                continue;
                             }
                             spawn_into_main_thread(async move {
                                 log::error!("Making new AsyncSslStream");
-                                wezterm_mux_server_impl::dispatch::process(AsyncSslStream::new(
-                                    stream,
-                                ))
-                                .await
-                                .map_err(|e| {
+                                let stream = AsyncSslStream::new(stream);
+                                let result = if pending_invite {
+                                    wezterm_mux_server_impl::dispatch::process_pending_invite(
+                                        stream,
+                                    )
+                                    .await
+                                } else {
+                                    wezterm_mux_server_impl::dispatch::process(stream).await
+                                };
+                                result.map_err(|e| {
                                     log::error!("process: {:?}", e);
                                     e
                                 })
@@ -96,7 +185,11 @@ impl OpenSSLNetListener {
                             .detach();
                         }
                         Err(e) => {
-                            log::error!("failed TlsAcceptor: {}", e);
+                            log::warn!(
+                                "tls: rejected connection from {}: handshake failed: {}",
+                                peer,
+                                e
+                            );
                         }
                     }
                 }
@@ -109,37 +202,36 @@ impl OpenSSLNetListener {
     }
 }
 
-pub fn spawn_tls_listener(tls_server: &TlsDomainServer) -> Result<(), Error> {
-    openssl::init();
-
+/// Creates an acceptor that presents the specified certificate and that
+/// requires clients to present a certificate that is trusted by the
+/// roots configured for `tls_server`.  If `tls_server` allows
+/// invitations then clients may instead omit the certificate.
+fn build_acceptor(
+    tls_server: &TlsDomainServer,
+    cert_file: &Path,
+    key_file: &Path,
+    chain_file: Option<&Path>,
+) -> anyhow::Result<SslAcceptorBuilder> {
     let mut acceptor = SslAcceptor::mozilla_modern(SslMethod::tls())?;
 
-    let cert_file = tls_server
-        .pem_cert
-        .clone()
-        .unwrap_or_else(|| PKI.server_pem());
     acceptor
-        .set_certificate_file(&cert_file, SslFiletype::PEM)
+        .set_certificate_file(cert_file, SslFiletype::PEM)
         .context(format!(
             "set_certificate_file to {} for TLS listener",
             cert_file.display()
         ))?;
 
-    if let Some(chain_file) = tls_server.pem_ca.as_ref() {
+    if let Some(chain_file) = chain_file {
         acceptor
-            .set_certificate_chain_file(&chain_file)
+            .set_certificate_chain_file(chain_file)
             .context(format!(
                 "set_certificate_chain_file to {} for TLS listener",
                 chain_file.display()
             ))?;
     }
 
-    let key_file = tls_server
-        .pem_private_key
-        .clone()
-        .unwrap_or_else(|| PKI.server_pem());
     acceptor
-        .set_private_key_file(&key_file, SslFiletype::PEM)
+        .set_private_key_file(key_file, SslFiletype::PEM)
         .context(format!(
             "set_private_key_file to {} for TLS listener",
             key_file.display()
@@ -166,23 +258,107 @@ pub fn spawn_tls_listener(tls_server: &TlsDomainServer) -> Result<(), Error> {
         .cert_store_mut()
         .add_cert(load_cert(&PKI.ca_pem())?)?;
 
-    acceptor.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+    if tls_server.allow_invitations {
+        acceptor.set_verify(SslVerifyMode::PEER);
+    } else {
+        acceptor.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+    }
+
+    Ok(acceptor)
+}
 
-    let acceptor = acceptor.build();
+pub fn spawn_tls_listener(tls_server: &TlsDomainServer) -> Result<(), Error> {
+    openssl::init();
 
-    log::error!("listening with TLS on {:?}", tls_server.bind_address);
+    let cert_file = tls_server
+        .pem_cert
+        .clone()
+        .unwrap_or_else(|| PKI.server_pem());
+    let key_file = tls_server
+        .pem_private_key
+        .clone()
+        .unwrap_or_else(|| PKI.server_pem());
+    let mut acceptor = build_acceptor(
+        tls_server,
+        &cert_file,
+        &key_file,
+        tls_server.pem_ca.as_deref(),
+    )?;
 
-    let mut net_listener = OpenSSLNetListener::new(
-        TcpListener::bind(&tls_server.bind_address).with_context(|| {
-            format!(
-                "error binding to mux_server_bind_address {}",
-                tls_server.bind_address,
+    if !tls_server.sni_certificates.is_empty() {
+        let mut contexts = HashMap::new();
+        for sni in &tls_server.sni_certificates {
+            let context = build_acceptor(
+                tls_server,
+                &sni.pem_cert,
+                &sni.pem_private_key,
+                sni.pem_ca.as_deref(),
             )
-        })?,
-        acceptor,
-    );
-    std::thread::spawn(move || {
-        net_listener.run();
-    });
+            .with_context(|| format!("configuring certificate for {}", sni.server_name))?
+            .build()
+            .into_context();
+            contexts.insert(sni.server_name.to_lowercase(), context);
+        }
+
+        // Switch to the certificate for the name requested by the client,
+        // or keep the default certificate if there isn't one
+        acceptor.set_servername_callback(move |ssl, _alert| {
+            let name = ssl
+                .servername(NameType::HOST_NAME)
+                .map(|name| name.to_lowercase());
+            if let Some(context) = name.and_then(|name| contexts.get(&name)) {
+                ssl.set_ssl_context(context)
+                    .map_err(|_| SniError::ALERT_FATAL)?;
+            }
+            Ok(())
+        });
+    }
+
+    let acceptor = Arc::new(acceptor.build());
+
+    if tls_server.bind_address.is_empty() {
+        anyhow::bail!("tls_servers entry has no bind_address");
+    }
+    if tls_server.allow_invitations && tls_server.websocket {
+        anyhow::bail!("allow_invitations is not supported together with websocket in tls_servers");
+    }
+    if tls_server.allow_invitations && !tls_server.sni_certificates.is_empty() {
+        // The invitation pins the fingerprint of pem_cert, which would
+        // not match the certificate that SNI selects for another name
+        anyhow::bail!(
+            "allow_invitations is not supported together with sni_certificates in tls_servers"
+        );
+    }
+
+    for bind_address in &tls_server.bind_address {
+        if tls_server.websocket && cfg!(windows) {
+            anyhow::bail!(
+                "websocket is not supported for tls_servers on this platform ({})",
+                bind_address
+            );
+        }
+
+        log::error!(
+            "listening with TLS{} on {:?}",
+            if tls_server.websocket {
+                " and WebSocket"
+            } else {
+                ""
+            },
+            bind_address
+        );
+
+        let mut net_listener = OpenSSLNetListener::new(
+            TcpListener::bind(bind_address).with_context(|| {
+                format!("error binding to mux_server_bind_address {}", bind_address)
+            })?,
+            Arc::clone(&acceptor),
+            tls_server.websocket,
+            tls_server.allow_invitations,
+        );
+        std::thread::spawn(move || {
+            net_listener.run();
+        });
+    }
     Ok(())
 }
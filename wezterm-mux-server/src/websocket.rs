@@ -0,0 +1,119 @@
+//! Carries the mux protocol over a WebSocket connection.
+//! The session is processed by the regular dispatch code on one end of a
+//! socketpair, and the other end is relayed to and from binary WebSocket
+//! messages, waiting for either side to become ready.
+use anyhow::{anyhow, Context};
+use openssl::ssl::SslStream;
+use promise::spawn::spawn_into_main_thread;
+use smol::io::{AsyncReadExt, AsyncWriteExt};
+use smol::{future, Async};
+use std::io::ErrorKind;
+use std::net::TcpStream;
+use std::os::unix::net::UnixStream;
+use tungstenite::{Message, WebSocket};
+
+/// Performs the WebSocket handshake on an authenticated TLS stream
+/// and then serves the mux protocol over it until either side
+/// disconnects.  This blocks the calling thread.
+pub fn serve(stream: SslStream<TcpStream>) -> anyhow::Result<()> {
+    let mut ws = tungstenite::accept(stream)
+        .map_err(|err| anyhow!("websocket handshake failed: {}", err))?;
+
+    let (mux_side, relay_side) = UnixStream::pair().context("creating socketpair")?;
+    spawn_into_main_thread(async move {
+        wezterm_mux_server_impl::dispatch::process(mux_side)
+            .await
+            .map_err(|e| {
+                log::error!("process: {:?}", e);
+                e
+            })
+    })
+    .detach();
+
+    // The WebSocket can't be split into independent read and write
+    // halves, so it is driven from this thread with the socket in
+    // non-blocking mode, and a clone of the socket is used to
+    // wait for it to become ready
+    let client = Async::new(ws.get_ref().get_ref().try_clone()?)?;
+    let mut mux = Async::new(relay_side)?;
+
+    let result = smol::block_on(relay(&mut ws, &client, &mut mux));
+    // Signal EOF to the session
+    mux.get_ref().shutdown(std::net::Shutdown::Both).ok();
+    client.get_ref().set_nonblocking(false).ok();
+    ws.close(None).ok();
+    ws.write_pending().ok();
+    result
+}
+
+enum Ready {
+    Client,
+    Mux(usize),
+}
+
+/// Returns true if all of the queued data has been sent to the client,
+/// or false if the socket would block
+fn flush(ws: &mut WebSocket<SslStream<TcpStream>>) -> anyhow::Result<bool> {
+    match ws.write_pending() {
+        Ok(()) => Ok(true),
+        Err(tungstenite::Error::Io(err)) if err.kind() == ErrorKind::WouldBlock => Ok(false),
+        Err(err) => Err(err).context("writing to websocket"),
+    }
+}
+
+async fn relay(
+    ws: &mut WebSocket<SslStream<TcpStream>>,
+    client: &Async<TcpStream>,
+    mux: &mut Async<UnixStream>,
+) -> anyhow::Result<()> {
+    let mut buf = [0u8; 8192];
+    loop {
+        // Process everything that is available from the client,
+        // including anything already buffered by the TLS layer,
+        // so that waiting for readiness can't miss it
+        loop {
+            match ws.read_message() {
+                Ok(Message::Binary(data)) => {
+                    mux.write_all(&data).await.context("writing to mux")?
+                }
+                Ok(Message::Close(_)) | Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+                Ok(Message::Text(_)) => anyhow::bail!("websocket client sent a text message"),
+                // Ping and pong are handled by tungstenite
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(err)) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) => return Err(err).context("reading from websocket"),
+            }
+        }
+
+        // Replies to pings are queued while reading, so make
+        // sure that they get sent along with any mux output
+        if !flush(ws)? {
+            // Stop reading from the mux until the client catches up
+            future::or(client.writable(), client.readable())
+                .await
+                .context("waiting for websocket")?;
+            continue;
+        }
+
+        let ready: std::io::Result<Ready> = future::or(
+            async {
+                client.readable().await?;
+                Ok(Ready::Client)
+            },
+            async { Ok(Ready::Mux(mux.read(&mut buf).await?)) },
+        )
+        .await;
+
+        match ready.context("waiting for websocket or mux")? {
+            Ready::Client => {}
+            // The session has ended
+            Ready::Mux(0) => return Ok(()),
+            Ready::Mux(size) => match ws.write_message(Message::Binary(buf[0..size].to_vec())) {
+                Ok(()) => {}
+                // The message is queued and is sent by a later flush
+                Err(tungstenite::Error::Io(err)) if err.kind() == ErrorKind::WouldBlock => {}
+                Err(err) => return Err(err).context("writing to websocket"),
+            },
+        }
+    }
+}
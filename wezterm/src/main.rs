@@ -3,16 +3,24 @@ use config::wezterm_version;
 use mux::activity::Activity;
 use mux::pane::PaneId;
 use mux::tab::SplitDirection;
+use mux::window::WindowId;
 use mux::Mux;
 use portable_pty::cmdbuilder::CommandBuilder;
+use std::collections::HashMap;
 use std::ffi::OsString;
 use std::io::{Read, Write};
+use std::path::PathBuf;
 use std::rc::Rc;
 use structopt::StructOpt;
 use tabout::{tabulate_output, Alignment, Column};
 use umask::UmaskSaver;
 use wezterm_client::client::{unix_connect_with_retry, Client};
+use wezterm_client::invite::Invitation;
 use wezterm_gui_subcommands::*;
+use wezterm_term::StableRowIndex;
+
+mod os_integration;
+mod tmux_conf;
 
 //    let message = "; ❤ 😍🤢\n\x1b[91;mw00t\n\x1b[37;104;m bleet\x1b[0;m.";
 //    terminal.advance_bytes(message);
@@ -46,6 +54,13 @@ struct Opt {
         number_of_values = 1)]
     config_override: Vec<(String, String)>,
 
+    /// Keep the configuration, logs, sockets and other state in the
+    /// directory that holds the executable, rather than in the home
+    /// directory.  Creating a file named `wezterm-portable` in that
+    /// directory has the same effect
+    #[structopt(long = "portable")]
+    portable: bool,
+
     #[structopt(subcommand)]
     cmd: Option<SubCommand>,
 }
@@ -64,6 +79,19 @@ enum SubCommand {
     #[structopt(name = "connect", about = "Connect to wezterm multiplexer")]
     Connect(ConnectCommand),
 
+    #[structopt(
+        name = "open",
+        about = "Open a directory or an ssh:// URL in a new window"
+    )]
+    Open(OpenCommand),
+
+    #[structopt(
+        name = "register-handlers",
+        about = "Register wezterm with the desktop environment as a way \
+                 to open directories and ssh:// URLs"
+    )]
+    RegisterHandlers(os_integration::RegisterHandlersCommand),
+
     #[structopt(name = "cli", about = "Interact with experimental mux server")]
     Cli(CliCommand),
 
@@ -76,6 +104,42 @@ enum SubCommand {
                  emitting an OSC 7 escape sequence"
     )]
     SetCwd(SetCwdCommand),
+
+    #[structopt(
+        name = "show-config",
+        about = "Print the effective configuration as TOML, after \
+                 applying the defaults, includes and command line overrides"
+    )]
+    ShowConfig(ShowConfigCommand),
+
+    #[structopt(
+        name = "import-tmux-conf",
+        about = "Translate the options and key bindings of a tmux \
+                 configuration file into a wezterm configuration"
+    )]
+    ImportTmuxConf(ImportTmuxConfCommand),
+
+    #[structopt(
+        name = "shell-integration",
+        about = "Print the shell integration script for a shell, which \
+                 emits the OSC 7 and OSC 133 escape sequences that \
+                 track the working directory and the prompt"
+    )]
+    ShellIntegration(ShellIntegrationCommand),
+
+    #[structopt(
+        name = "generate-completions",
+        about = "Print a completion script for the wezterm command \
+                 line for a shell"
+    )]
+    GenerateCompletions(GenerateCompletionsCommand),
+
+    #[structopt(
+        name = "test-hyperlink-rules",
+        about = "Run the configured hyperlink_rules against the text read \
+                 from stdin and print what each rule matched"
+    )]
+    TestHyperlinkRules(TestHyperlinkRulesCommand),
 }
 
 #[derive(Debug, StructOpt, Clone)]
@@ -91,7 +155,12 @@ struct CliCommand {
 #[derive(Debug, StructOpt, Clone)]
 enum CliSubCommand {
     #[structopt(name = "list", about = "list windows, tabs and panes")]
-    List,
+    List {
+        /// Include additional information, such as the memory
+        /// used by each pane and tab
+        #[structopt(long = "verbose")]
+        verbose: bool,
+    },
 
     #[structopt(name = "proxy", about = "start rpc proxy pipe")]
     Proxy,
@@ -99,6 +168,26 @@ enum CliSubCommand {
     #[structopt(name = "tlscreds", about = "obtain tls credentials")]
     TlsCreds,
 
+    #[structopt(
+        name = "metrics",
+        about = "output the metrics collected by the mux server, or gui, in the Prometheus text format"
+    )]
+    Metrics,
+
+    #[structopt(
+        name = "shutdown",
+        about = "shut down the mux server.
+Attached clients are notified and no new panes may be spawned.
+Panes that have not exited once the grace period has elapsed are killed"
+    )]
+    Shutdown {
+        /// How long to wait for the panes to exit before killing them.
+        /// Accepts a number of seconds, or a number with an s, m or h
+        /// suffix, such as `90s` or `5m`.
+        #[structopt(long = "grace", default_value = "30s", parse(try_from_str = parse_duration))]
+        grace: std::time::Duration,
+    },
+
     #[structopt(
         name = "split-pane",
         about = "split the current pane.
@@ -116,7 +205,8 @@ Outputs the pane-id for the newly created pane on success"
         horizontal: bool,
 
         /// Specify the current working directory for the initially
-        /// spawned program
+        /// spawned program.  The default is the working directory
+        /// of the pane being split, if it is known.
         #[structopt(long = "cwd", parse(from_os_str))]
         cwd: Option<OsString>,
 
@@ -126,8 +216,204 @@ Outputs the pane-id for the newly created pane on success"
         #[structopt(parse(from_os_str))]
         prog: Vec<OsString>,
     },
+
+    #[structopt(
+        name = "set-status",
+        about = "Set the text shown in the status area of the tab bar
+while the pane is active.  Omit the text to clear the status"
+    )]
+    SetStatus {
+        /// Specify the pane whose status should be set.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        #[structopt(long = "pane-id")]
+        pane_id: Option<PaneId>,
+
+        /// The status text.  It may contain escape sequences to
+        /// set the color and other attributes of the text.
+        #[structopt(default_value = "")]
+        text: String,
+    },
+
+    #[structopt(
+        name = "set-badge",
+        about = "Set the badge that is shown ahead of the tab title
+in the tab bar while the pane is active.  Omitting the text clears the badge"
+    )]
+    SetBadge {
+        /// Specify the pane whose badge should be set.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        #[structopt(long = "pane-id")]
+        pane_id: Option<PaneId>,
+
+        /// The foreground color of the badge, either a named color
+        /// or an rgb color such as #ff0000
+        #[structopt(long = "fg", parse(try_from_str = parse_color))]
+        fg: Option<RgbColor>,
+
+        /// The background color of the badge, either a named color
+        /// or an rgb color such as #ff0000
+        #[structopt(long = "bg", parse(try_from_str = parse_color))]
+        bg: Option<RgbColor>,
+
+        /// The badge text, such as PROD.
+        #[structopt(default_value = "")]
+        text: String,
+    },
+
+    #[structopt(
+        name = "move-pane-to-new-tab",
+        about = "Move a pane into a new tab.
+Outputs the tab-id and window-id of the newly created tab on success"
+    )]
+    MovePaneToNewTab {
+        /// Specify the pane that should be moved.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        #[structopt(long = "pane-id")]
+        pane_id: Option<PaneId>,
+
+        /// Specify the window into which the new tab will be
+        /// created.  If omitted, the tab is created in the
+        /// window that currently contains the pane.
+        #[structopt(long = "window-id")]
+        window_id: Option<WindowId>,
+
+        /// Create the tab in a new window, rather than in the
+        /// window that currently contains the pane.
+        #[structopt(long = "new-window", conflicts_with = "window-id")]
+        new_window: bool,
+    },
+
+    #[structopt(
+        name = "move-pane",
+        about = "Move a pane next to another pane, splitting that pane
+to make room for it.  The other pane may be in any tab or window.
+Outputs the tab-id and window-id of the tab that now contains the pane"
+    )]
+    MovePane {
+        /// Specify the pane that should be moved.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        #[structopt(long = "pane-id")]
+        pane_id: Option<PaneId>,
+
+        /// Specify the pane that should be split to make room
+        /// for the moved pane.
+        #[structopt(long = "target-pane-id")]
+        target_pane_id: PaneId,
+
+        /// Split horizontally rather than vertically
+        #[structopt(long = "horizontal")]
+        horizontal: bool,
+    },
+
+    #[structopt(
+        name = "invite",
+        about = "Create a one-time invitation that allows someone to
+attach to a window of the mux server via TLS, without a certificate.
+Outputs the command that they should run to connect"
+    )]
+    Invite {
+        /// Specify the window that the invitation grants access to.
+        /// The default is the window that contains the current pane,
+        /// based on the environment variable WEZTERM_PANE.
+        #[structopt(long = "window-id")]
+        window_id: Option<WindowId>,
+
+        /// Grant access to all windows rather than a single window
+        #[structopt(long = "all-windows", conflicts_with = "window-id")]
+        all_windows: bool,
+
+        /// Allow the invited client to send input, resize and
+        /// spawn panes.  By default it may only view the output.
+        #[structopt(long = "read-write")]
+        read_write: bool,
+
+        /// How long the invitation may be redeemed for.
+        /// Accepts a number of seconds, or a number with an s, m or h
+        /// suffix, such as `90s` or `5m`.
+        #[structopt(long = "expires", default_value = "5m", parse(try_from_str = parse_duration))]
+        expires: std::time::Duration,
+
+        /// The host name that the invited client should connect to.
+        /// The default is the host name of this machine.
+        #[structopt(long = "host")]
+        host: Option<String>,
+    },
+
+    #[structopt(
+        name = "search",
+        about = "search the scrollback of a pane.
+Outputs the coordinates of each match along with the text of its line"
+    )]
+    Search {
+        /// Specify the pane whose scrollback should be searched.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        #[structopt(long = "pane-id")]
+        pane_id: Option<PaneId>,
+
+        /// The regular expression to search for.
+        /// Prefix it with `(?i)` to ignore case.
+        #[structopt(long = "regex")]
+        regex: String,
+    },
+
+    #[structopt(
+        name = "pipe-pane",
+        about = "send a copy of the output of a pane to the stdin of a command,
+which is run by the shell on the host of the pane.
+Omit the command to stop piping the output of the pane"
+    )]
+    PipePane {
+        /// Specify the pane whose output should be piped.
+        /// The default is to use the current pane based on the
+        /// environment variable WEZTERM_PANE.
+        #[structopt(long = "pane-id")]
+        pane_id: Option<PaneId>,
+
+        /// The command, such as `cat >> pane.log`.  It replaces
+        /// any command that the output is already piped to.
+        command: Option<String>,
+    },
+
+    #[structopt(
+        name = "download",
+        about = "copy a file from the host of the default unix domain mux server.
+Relative remote paths are relative to the home directory on that host"
+    )]
+    Download {
+        /// The file to copy from the host of the mux server
+        remote: String,
+
+        /// Where to save the file.  The default is a file with
+        /// the same name in the current directory.  Use `-` to
+        /// write it to stdout
+        #[structopt(parse(from_os_str))]
+        local: Option<PathBuf>,
+    },
+
+    #[structopt(
+        name = "upload",
+        about = "copy a file to the host of the default unix domain mux server.
+Relative remote paths are relative to the home directory on that host"
+    )]
+    Upload {
+        /// The file to copy.  Use `-` to read it from stdin
+        #[structopt(parse(from_os_str))]
+        local: PathBuf,
+
+        /// Where to save the file on the host of the mux server.
+        /// The default is a file with the same name in the home
+        /// directory on that host
+        remote: Option<String>,
+    },
 }
 
+use termwiz::color::{ColorSpec, RgbColor};
+use termwiz::escape::csi::{Sgr, CSI};
 use termwiz::escape::osc::{
     ITermDimension, ITermFileData, ITermProprietary, OperatingSystemCommand,
 };
@@ -198,6 +484,194 @@ struct SetCwdCommand {
     host: Option<OsString>,
 }
 
+#[derive(Debug, StructOpt, Clone)]
+struct ShowConfigCommand {}
+
+impl ShowConfigCommand {
+    fn run(&self) -> anyhow::Result<()> {
+        // Report a broken configuration rather than printing
+        // the defaults that we fell back to
+        let config = config::configuration_result()?;
+        print!("{}", config.to_toml_string()?);
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt, Clone)]
+struct ImportTmuxConfCommand {
+    /// The tmux configuration file to read.
+    /// The default is ~/.tmux.conf
+    #[structopt(parse(from_os_str))]
+    path: Option<PathBuf>,
+}
+
+impl ImportTmuxConfCommand {
+    fn run(&self) -> anyhow::Result<()> {
+        let path = match self.path.as_ref() {
+            Some(path) => path.clone(),
+            None => config::HOME_DIR.join(".tmux.conf"),
+        };
+        let conf = std::fs::read_to_string(&path)
+            .with_context(|| format!("reading {}", path.display()))?;
+        let translation = tmux_conf::translate(&conf);
+
+        print!("{}", translation.lua);
+        for untranslated in &translation.untranslated {
+            eprintln!(
+                "{}:{}: {}: {}",
+                path.display(),
+                untranslated.line,
+                untranslated.reason,
+                untranslated.text
+            );
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    fn variants() -> Vec<&'static str> {
+        vec!["bash", "zsh", "fish"]
+    }
+
+    /// Returns the script that hooks up shell integration
+    fn integration_script(self) -> &'static str {
+        match self {
+            // The same script works in both bash and zsh
+            Shell::Bash | Shell::Zsh => {
+                include_str!("../../assets/shell-integration/wezterm.sh")
+            }
+            Shell::Fish => include_str!("../../assets/shell-integration/wezterm.fish"),
+        }
+    }
+}
+
+impl std::str::FromStr for Shell {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(anyhow!(
+                "{} is not a supported shell, possible values are {:?}",
+                s,
+                Shell::variants()
+            )),
+        }
+    }
+}
+
+#[derive(Debug, StructOpt, Clone)]
+struct ShellIntegrationCommand {
+    /// The shell to print the script for; one of bash, zsh or fish.
+    /// The default is the shell named by the SHELL environment variable.
+    #[structopt(long = "shell")]
+    shell: Option<Shell>,
+}
+
+impl ShellIntegrationCommand {
+    fn run(&self) -> anyhow::Result<()> {
+        let shell = match self.shell {
+            Some(shell) => shell,
+            None => {
+                let shell = std::env::var_os("SHELL")
+                    .ok_or_else(|| anyhow!("SHELL is not set; use --shell to specify one"))?;
+                let name = std::path::Path::new(&shell)
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or("");
+                name.parse()?
+            }
+        };
+        print!("{}", shell.integration_script());
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt, Clone)]
+struct GenerateCompletionsCommand {
+    /// The shell to generate completions for
+    #[structopt(
+        possible_values = &structopt::clap::Shell::variants(),
+        case_insensitive = true
+    )]
+    shell: structopt::clap::Shell,
+}
+
+impl GenerateCompletionsCommand {
+    fn run(&self) -> anyhow::Result<()> {
+        Opt::clap().gen_completions_to("wezterm", self.shell, &mut std::io::stdout());
+        Ok(())
+    }
+}
+
+#[derive(Debug, StructOpt, Clone)]
+struct TestHyperlinkRulesCommand {
+    /// Test the rules as though the text was in a pane of this domain.
+    /// Rules that are limited to other domains are skipped.
+    #[structopt(long = "domain")]
+    domain: Option<String>,
+
+    /// Test the rules as though this was the foreground process.
+    /// Rules that are limited to other programs are skipped.
+    #[structopt(long = "program")]
+    program: Option<String>,
+}
+
+impl TestHyperlinkRulesCommand {
+    fn run(&self) -> anyhow::Result<()> {
+        // Report a broken configuration, including invalid rules,
+        // rather than testing the default rules
+        let config = config::configuration_result()?;
+        let mut input = String::new();
+        std::io::stdin().read_to_string(&mut input)?;
+
+        let mut matched = false;
+        for (line_idx, line) in input.lines().enumerate() {
+            for (rule_idx, rule) in config.hyperlink_rules.iter().enumerate() {
+                if !rule.applies_to(self.domain.as_deref(), self.program.as_deref()) {
+                    continue;
+                }
+                for detail in rule.match_details(line) {
+                    matched = true;
+                    println!(
+                        "line {}: rule {} `{}` matched `{}` at {}..{}",
+                        line_idx + 1,
+                        rule_idx + 1,
+                        rule.pattern(),
+                        &line[detail.range.clone()],
+                        detail.range.start,
+                        detail.range.end
+                    );
+                    for (n, capture) in detail.captures.iter().enumerate() {
+                        match capture {
+                            Some(text) => println!("    ${} = `{}`", n, text),
+                            None => println!("    ${} did not participate in the match", n),
+                        }
+                    }
+                    println!("    {} -> {}", rule.format(), detail.uri);
+                }
+            }
+        }
+
+        if !matched {
+            eprintln!(
+                "None of the {} hyperlink_rules matched the input",
+                config.hyperlink_rules.len()
+            );
+        }
+        Ok(())
+    }
+}
+
 impl SetCwdCommand {
     fn run(&self) -> anyhow::Result<()> {
         let cwd: std::path::PathBuf = match self.cwd.as_ref() {
@@ -239,11 +713,17 @@ fn main() {
 }
 
 fn run() -> anyhow::Result<()> {
+    // The options are parsed first, as portable mode
+    // affects where the log file is written
+    let opts = Opt::from_args();
+    if opts.portable {
+        config::enable_portable_mode()?;
+    }
+
     env_bootstrap::bootstrap();
 
     let saver = UmaskSaver::new();
 
-    let opts = Opt::from_args();
     config::common_init(
         opts.config_file.as_ref(),
         &opts.config_override,
@@ -260,9 +740,16 @@ fn run() -> anyhow::Result<()> {
         SubCommand::Start(_)
         | SubCommand::Ssh(_)
         | SubCommand::Serial(_)
-        | SubCommand::Connect(_) => delegate_to_gui(saver),
+        | SubCommand::Connect(_)
+        | SubCommand::Open(_) => delegate_to_gui(saver),
         SubCommand::ImageCat(cmd) => cmd.run(),
         SubCommand::SetCwd(cmd) => cmd.run(),
+        SubCommand::ShowConfig(cmd) => cmd.run(),
+        SubCommand::ImportTmuxConf(cmd) => cmd.run(),
+        SubCommand::ShellIntegration(cmd) => cmd.run(),
+        SubCommand::GenerateCompletions(cmd) => cmd.run(),
+        SubCommand::TestHyperlinkRules(cmd) => cmd.run(),
+        SubCommand::RegisterHandlers(cmd) => cmd.run(),
         SubCommand::Cli(cli) => run_cli(config, cli),
     }
 }
@@ -309,13 +796,85 @@ fn delegate_to_gui(saver: UmaskSaver) -> anyhow::Result<()> {
     }
 }
 
+/// Returns the pane id that was passed via --pane-id, or if none was
+/// specified, the current pane based on $WEZTERM_PANE.
+fn resolve_pane_id(pane_id: Option<PaneId>) -> anyhow::Result<PaneId> {
+    match pane_id {
+        Some(p) => Ok(p),
+        None => Ok(std::env::var("WEZTERM_PANE")
+            .map_err(|_| {
+                anyhow!(
+                    "--pane-id was not specified and $WEZTERM_PANE \
+                     is not set in the environment"
+                )
+            })?
+            .parse()?),
+    }
+}
+
+/// Returns the window that currently contains `pane_id`
+async fn window_containing_pane(client: &Client, pane_id: PaneId) -> anyhow::Result<WindowId> {
+    let panes = client.list_panes().await?;
+    for tabroot in panes.tabs {
+        let mut cursor = tabroot.into_tree().cursor();
+        loop {
+            if let Some(entry) = cursor.leaf_mut() {
+                if entry.pane_id == pane_id {
+                    return Ok(entry.window_id);
+                }
+            }
+            match cursor.preorder_next() {
+                Ok(c) => cursor = c,
+                Err(_) => break,
+            }
+        }
+    }
+    Err(anyhow!("pane {} not found", pane_id))
+}
+
+/// Formats a number of bytes using binary unit suffixes, eg: 1.5M
+fn format_memory_size(bytes: usize) -> String {
+    let mut size = bytes as f64;
+    for suffix in &["", "K", "M", "G"] {
+        if size < 1024.0 {
+            return if suffix.is_empty() {
+                format!("{}", bytes)
+            } else {
+                format!("{:.1}{}", size, suffix)
+            };
+        }
+        size /= 1024.0;
+    }
+    format!("{:.1}T", size)
+}
+
+fn parse_color(s: &str) -> anyhow::Result<RgbColor> {
+    RgbColor::from_named_or_rgb_string(s).ok_or_else(|| anyhow!("invalid color {}", s))
+}
+
+fn parse_duration(s: &str) -> anyhow::Result<std::time::Duration> {
+    let (number, scale) = match s.char_indices().last() {
+        Some((idx, 's')) => (&s[..idx], 1),
+        Some((idx, 'm')) => (&s[..idx], 60),
+        Some((idx, 'h')) => (&s[..idx], 60 * 60),
+        _ => (s, 1),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| anyhow!("invalid duration {}", s))?;
+    let secs = number
+        .checked_mul(scale)
+        .ok_or_else(|| anyhow!("duration {} is too large", s))?;
+    Ok(std::time::Duration::from_secs(secs))
+}
+
 async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow::Result<()> {
     let initial = true;
     let mut ui = mux::connui::ConnectionUI::new_headless();
     let client = Client::new_default_unix_domain(initial, &mut ui)?;
     match cli.sub {
-        CliSubCommand::List => {
-            let cols = vec![
+        CliSubCommand::List { verbose } => {
+            let mut cols = vec![
                 Column {
                     name: "WINID".to_string(),
                     alignment: Alignment::Right,
@@ -341,15 +900,28 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
                     alignment: Alignment::Left,
                 },
             ];
+            if verbose {
+                cols.push(Column {
+                    name: "MEMORY".to_string(),
+                    alignment: Alignment::Right,
+                });
+                cols.push(Column {
+                    name: "TABMEMORY".to_string(),
+                    alignment: Alignment::Right,
+                });
+            }
             let mut data = vec![];
             let panes = client.list_panes().await?;
 
             for tabroot in panes.tabs {
                 let mut cursor = tabroot.into_tree().cursor();
+                let tab_start = data.len();
+                let mut tab_memory = 0;
 
                 loop {
                     if let Some(entry) = cursor.leaf_mut() {
-                        data.push(vec![
+                        tab_memory += entry.memory_usage;
+                        let mut row = vec![
                             entry.window_id.to_string(),
                             entry.tab_id.to_string(),
                             entry.pane_id.to_string(),
@@ -361,13 +933,24 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
                                 .map(|url| url.url.as_str())
                                 .unwrap_or("")
                                 .to_string(),
-                        ]);
+                        ];
+                        if verbose {
+                            row.push(format_memory_size(entry.memory_usage));
+                        }
+                        data.push(row);
                     }
                     match cursor.preorder_next() {
                         Ok(c) => cursor = c,
                         Err(_) => break,
                     }
                 }
+
+                if verbose {
+                    let tab_memory = format_memory_size(tab_memory);
+                    for row in &mut data[tab_start..] {
+                        row.push(tab_memory.clone());
+                    }
+                }
             }
 
             tabulate_output(&cols, &data, &mut std::io::stdout().lock())?;
@@ -378,17 +961,7 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
             prog,
             horizontal,
         } => {
-            let pane_id: PaneId = match pane_id {
-                Some(p) => p,
-                None => std::env::var("WEZTERM_PANE")
-                    .map_err(|_| {
-                        anyhow!(
-                            "--pane-id was not specified and $WEZTERM_PANE
-                                    is not set in the environment"
-                        )
-                    })?
-                    .parse()?,
-            };
+            let pane_id = resolve_pane_id(pane_id)?;
 
             let spawned = client
                 .split_pane(codec::SplitPane {
@@ -412,6 +985,94 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
             log::debug!("{:?}", spawned);
             println!("{}", spawned.pane_id);
         }
+        CliSubCommand::SetStatus { pane_id, text } => {
+            let pane_id = resolve_pane_id(pane_id)?;
+            client
+                .set_pane_user_var(codec::SetPaneUserVar {
+                    pane_id,
+                    name: mux::pane::STATUS_USER_VAR.to_string(),
+                    value: text,
+                })
+                .await?;
+        }
+        CliSubCommand::SetBadge {
+            pane_id,
+            fg,
+            bg,
+            text,
+        } => {
+            let pane_id = resolve_pane_id(pane_id)?;
+            let value = if text.is_empty() {
+                text
+            } else {
+                let mut value = String::new();
+                if let Some(fg) = fg {
+                    value
+                        .push_str(&CSI::Sgr(Sgr::Foreground(ColorSpec::TrueColor(fg))).to_string());
+                }
+                if let Some(bg) = bg {
+                    value
+                        .push_str(&CSI::Sgr(Sgr::Background(ColorSpec::TrueColor(bg))).to_string());
+                }
+                value.push_str(&text);
+                if fg.is_some() || bg.is_some() {
+                    value.push_str(&CSI::Sgr(Sgr::Reset).to_string());
+                }
+                value
+            };
+            client
+                .set_pane_user_var(codec::SetPaneUserVar {
+                    pane_id,
+                    name: mux::pane::BADGE_USER_VAR.to_string(),
+                    value,
+                })
+                .await?;
+        }
+        CliSubCommand::MovePaneToNewTab {
+            pane_id,
+            window_id,
+            new_window,
+        } => {
+            let pane_id = resolve_pane_id(pane_id)?;
+
+            let window_id = if new_window {
+                None
+            } else {
+                match window_id {
+                    Some(w) => Some(w),
+                    None => Some(window_containing_pane(&client, pane_id).await?),
+                }
+            };
+
+            let moved = client
+                .move_pane_to_new_tab(codec::MovePaneToNewTab { pane_id, window_id })
+                .await?;
+
+            log::debug!("{:?}", moved);
+            println!("{} {}", moved.tab_id, moved.window_id);
+        }
+        CliSubCommand::MovePane {
+            pane_id,
+            target_pane_id,
+            horizontal,
+        } => {
+            let pane_id = resolve_pane_id(pane_id)?;
+
+            let moved = client
+                .move_pane_to_split(codec::MovePaneToSplit {
+                    pane_id,
+                    target_pane_id,
+                    direction: if horizontal {
+                        SplitDirection::Horizontal
+                    } else {
+                        SplitDirection::Vertical
+                    },
+                })
+                .await?;
+
+            log::debug!("{:?}", moved);
+            println!("{} {}", moved.tab_id, moved.window_id);
+        }
         CliSubCommand::Proxy => {
             // The client object we created above will have spawned
             // the server if needed, so now all we need to do is turn
@@ -446,6 +1107,255 @@ async fn run_cli_async(config: config::ConfigHandle, cli: CliCommand) -> anyhow:
             let creds = client.get_tls_creds().await?;
             codec::Pdu::GetTlsCredsResponse(creds).encode(std::io::stdout().lock(), 0)?;
         }
+        CliSubCommand::Metrics => {
+            let metrics = client.get_metrics().await?;
+            print!("{}", metrics.metrics);
+        }
+        CliSubCommand::PipePane { pane_id, command } => {
+            let pane_id = resolve_pane_id(pane_id)?;
+            client
+                .set_pane_output_pipe(codec::SetPaneOutputPipe { pane_id, command })
+                .await?;
+        }
+        CliSubCommand::Download { remote, local } => {
+            download_file(&client, remote, local).await?;
+        }
+        CliSubCommand::Upload { local, remote } => {
+            upload_file(&client, local, remote).await?;
+        }
+        CliSubCommand::Shutdown { grace } => {
+            client
+                .shutdown_server(codec::ShutdownServer { grace })
+                .await?;
+        }
+        CliSubCommand::Invite {
+            window_id,
+            all_windows,
+            read_write,
+            expires,
+            host,
+        } => {
+            let window_id = if all_windows {
+                None
+            } else {
+                match window_id {
+                    Some(w) => Some(w),
+                    None => Some(window_containing_pane(&client, resolve_pane_id(None)?).await?),
+                }
+            };
+            let read_only = !read_write;
+
+            let created = client
+                .create_invite(codec::CreateInvite {
+                    window_id,
+                    read_only,
+                    ttl: expires,
+                })
+                .await?;
+
+            // The server may be listening on a wildcard address, so
+            // only the port is taken from its bind_address
+            let port = created
+                .bind_address
+                .rsplit(':')
+                .next()
+                .ok_or_else(|| anyhow!("invalid bind_address {}", created.bind_address))?;
+            let host = match host {
+                Some(host) => host,
+                None => hostname::get()?
+                    .into_string()
+                    .map_err(|_| anyhow!("hostname is not representable as unicode"))?,
+            };
+
+            let invite = Invitation {
+                remote_address: format!("{}:{}", host, port),
+                token: created.token,
+                fingerprint: created.fingerprint,
+                read_only,
+            };
+            println!("wezterm connect --invite '{}'", invite);
+        }
+        CliSubCommand::Search { pane_id, regex } => {
+            let pane_id = resolve_pane_id(pane_id)?;
+            let results = client
+                .search_scrollback(codec::SearchScrollbackRequest {
+                    pane_id,
+                    pattern: mux::pane::Pattern::Regex(regex),
+                })
+                .await?
+                .results;
+
+            // Fetch the text of the rows spanned by the matches,
+            // coalescing adjacent and overlapping rows into a
+            // single request
+            let mut ranges: Vec<std::ops::Range<StableRowIndex>> = vec![];
+            let mut spans: Vec<_> = results.iter().map(|r| r.start_y..r.end_y + 1).collect();
+            spans.sort_by_key(|r| r.start);
+            for span in spans {
+                match ranges.last_mut() {
+                    Some(last) if span.start <= last.end => last.end = last.end.max(span.end),
+                    _ => ranges.push(span),
+                }
+            }
+            let lines: HashMap<StableRowIndex, String> = if ranges.is_empty() {
+                HashMap::new()
+            } else {
+                client
+                    .get_lines(codec::GetLines {
+                        pane_id,
+                        lines: ranges,
+                    })
+                    .await?
+                    .lines
+                    .lines()
+                    .into_iter()
+                    .map(|(idx, line)| (idx, line.as_str()))
+                    .collect()
+            };
+
+            let cols = vec![
+                Column {
+                    name: "ROW".to_string(),
+                    alignment: Alignment::Right,
+                },
+                Column {
+                    name: "COL".to_string(),
+                    alignment: Alignment::Right,
+                },
+                Column {
+                    name: "END_ROW".to_string(),
+                    alignment: Alignment::Right,
+                },
+                Column {
+                    name: "END_COL".to_string(),
+                    alignment: Alignment::Right,
+                },
+                Column {
+                    name: "TEXT".to_string(),
+                    alignment: Alignment::Left,
+                },
+            ];
+            let mut data = vec![];
+            for result in &results {
+                // A match only spans multiple rows when the line
+                // was wrapped, so the rows are joined without a separator
+                let text: String = (result.start_y..=result.end_y)
+                    .filter_map(|idx| lines.get(&idx).map(String::as_str))
+                    .collect();
+                data.push(vec![
+                    result.start_y.to_string(),
+                    result.start_x.to_string(),
+                    result.end_y.to_string(),
+                    result.end_x.to_string(),
+                    text.trim_end().to_string(),
+                ]);
+            }
+            tabulate_output(&cols, &data, &mut std::io::stdout().lock())?;
+        }
+    }
+    Ok(())
+}
+
+/// How much of a file is transferred by each request made by
+/// `wezterm cli download` and `wezterm cli upload`
+const FILE_TRANSFER_CHUNK_SIZE: u64 = 256 * 1024;
+
+/// Returns the final component of a path that may have been
+/// written for a different platform than this one
+fn file_name_of(path: &str) -> Option<&str> {
+    path.rsplit(|c| c == '/' || c == '\\')
+        .next()
+        .filter(|name| !name.is_empty() && *name != "." && *name != "..")
+}
+
+async fn download_file(
+    client: &Client,
+    remote: String,
+    local: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    let local = match local {
+        Some(local) => local,
+        None => PathBuf::from(
+            file_name_of(&remote)
+                .ok_or_else(|| anyhow!("cannot determine a local file name for {}", remote))?,
+        ),
+    };
+    let mut output: Box<dyn Write> = if local.as_os_str() == "-" {
+        Box::new(std::io::stdout())
+    } else {
+        Box::new(
+            std::fs::File::create(&local)
+                .with_context(|| format!("creating {}", local.display()))?,
+        )
+    };
+
+    let mut offset = 0;
+    loop {
+        let response = client
+            .read_file(codec::ReadFile {
+                path: remote.clone(),
+                offset,
+                len: FILE_TRANSFER_CHUNK_SIZE,
+            })
+            .await?;
+        output.write_all(&response.data)?;
+        offset += response.data.len() as u64;
+        if (response.data.len() as u64) < FILE_TRANSFER_CHUNK_SIZE {
+            break;
+        }
+    }
+    output.flush()?;
+    Ok(())
+}
+
+async fn upload_file(
+    client: &Client,
+    local: PathBuf,
+    remote: Option<String>,
+) -> anyhow::Result<()> {
+    let remote = match remote {
+        Some(remote) => remote,
+        None => local
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map(|name| name.to_string())
+            .ok_or_else(|| {
+                anyhow!(
+                    "cannot determine a remote file name for {}",
+                    local.display()
+                )
+            })?,
+    };
+    let mut input: Box<dyn Read> = if local.as_os_str() == "-" {
+        Box::new(std::io::stdin())
+    } else {
+        Box::new(
+            std::fs::File::open(&local).with_context(|| format!("opening {}", local.display()))?,
+        )
+    };
+
+    let mut offset = 0;
+    loop {
+        let mut data = vec![];
+        (&mut input)
+            .take(FILE_TRANSFER_CHUNK_SIZE)
+            .read_to_end(&mut data)?;
+        let len = data.len() as u64;
+        // The first request is always made, even when there is no
+        // data, as it is the one that creates the file
+        if len > 0 || offset == 0 {
+            client
+                .write_file(codec::WriteFile {
+                    path: remote.clone(),
+                    offset,
+                    data,
+                })
+                .await?;
+        }
+        offset += len;
+        if len < FILE_TRANSFER_CHUNK_SIZE {
+            break;
+        }
     }
     Ok(())
 }
@@ -0,0 +1,154 @@
+//! Registers wezterm with the desktop environment for
+//! `wezterm register-handlers`, so that "Open Terminal Here" in a file
+//! manager, and `ssh://` links, run `wezterm open`.
+#![cfg_attr(target_os = "macos", allow(unused_imports))]
+use anyhow::{anyhow, bail, Context};
+use std::path::PathBuf;
+use std::process::Command;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt, Clone)]
+pub struct RegisterHandlersCommand {
+    /// Don't register wezterm as the handler for ssh:// URLs;
+    /// only offer it as a way to open directories
+    #[structopt(long = "no-ssh")]
+    #[cfg_attr(target_os = "macos", allow(dead_code))]
+    no_ssh: bool,
+}
+
+impl RegisterHandlersCommand {
+    pub fn run(&self) -> anyhow::Result<()> {
+        self.register()
+    }
+
+    /// The executable that the handlers run; the gui is preferred on
+    /// Windows so that opening a directory doesn't flash a console
+    #[cfg(not(target_os = "macos"))]
+    fn handler_exe() -> anyhow::Result<PathBuf> {
+        let exe = std::env::current_exe()?;
+        if cfg!(windows) {
+            let gui = exe
+                .parent()
+                .ok_or_else(|| anyhow!("exe has no parent dir!?"))?
+                .join("wezterm-gui.exe");
+            if gui.exists() {
+                return Ok(gui);
+            }
+        }
+        Ok(exe)
+    }
+
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn register(&self) -> anyhow::Result<()> {
+        const DESKTOP_FILE: &str = "org.wezfurlong.wezterm.open.desktop";
+
+        let data_home = match std::env::var_os("XDG_DATA_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => config::HOME_DIR.join(".local").join("share"),
+        };
+        let apps = data_home.join("applications");
+        std::fs::create_dir_all(&apps).with_context(|| format!("creating {}", apps.display()))?;
+
+        let mime_types = if self.no_ssh {
+            "inode/directory;"
+        } else {
+            "inode/directory;x-scheme-handler/ssh;"
+        };
+        let path = apps.join(DESKTOP_FILE);
+        std::fs::write(
+            &path,
+            format!(
+                "[Desktop Entry]\n\
+                 Name=WezTerm\n\
+                 Comment=Open a directory or ssh:// URL in WezTerm\n\
+                 Icon=org.wezfurlong.wezterm\n\
+                 Exec=\"{}\" open %u\n\
+                 Type=Application\n\
+                 Terminal=false\n\
+                 NoDisplay=true\n\
+                 MimeType={}\n",
+                Self::handler_exe()?.display(),
+                mime_types
+            ),
+        )
+        .with_context(|| format!("writing {}", path.display()))?;
+        println!("Wrote {}", path.display());
+
+        // The desktop database is what allows file managers to offer
+        // wezterm in their "Open With" menu for directories.  It is
+        // deliberately not made the default for directories, as that
+        // would replace the file manager itself.
+        run_optional(Command::new("update-desktop-database").arg(&apps));
+
+        if !self.no_ssh {
+            run(Command::new("xdg-mime").args(&["default", DESKTOP_FILE, "x-scheme-handler/ssh"]))?;
+            println!("wezterm is now the handler for ssh:// URLs");
+        }
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    fn register(&self) -> anyhow::Result<()> {
+        let exe = Self::handler_exe()?;
+        let open = |arg: &str| format!("\"{}\" open \"{}\"", exe.display(), arg);
+
+        // The context menu of a folder, and of the background of
+        // an open folder
+        for key in &[
+            r"HKCU\Software\Classes\Directory\shell\wezterm",
+            r"HKCU\Software\Classes\Directory\Background\shell\wezterm",
+        ] {
+            reg_add(key, None, "Open WezTerm here")?;
+            reg_add(key, Some("Icon"), &exe.display().to_string())?;
+            reg_add(&format!(r"{}\command", key), None, &open("%V"))?;
+        }
+        println!("Added \"Open WezTerm here\" to the context menu of folders");
+
+        if !self.no_ssh {
+            let key = r"HKCU\Software\Classes\ssh";
+            reg_add(key, None, "URL:ssh")?;
+            reg_add(key, Some("URL Protocol"), "")?;
+            reg_add(&format!(r"{}\shell\open\command", key), None, &open("%1"))?;
+            println!("wezterm is now the handler for ssh:// URLs");
+        }
+        Ok(())
+    }
+
+    #[cfg(target_os = "macos")]
+    fn register(&self) -> anyhow::Result<()> {
+        bail!(
+            "register-handlers is not yet supported on macOS; \
+             `wezterm open` can be run directly instead"
+        );
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn run(cmd: &mut Command) -> anyhow::Result<()> {
+    let status = cmd.status().with_context(|| format!("running {:?}", cmd))?;
+    if !status.success() {
+        bail!("{:?} failed: {}", cmd, status);
+    }
+    Ok(())
+}
+
+/// Runs a command whose failure doesn't prevent the handlers from
+/// working, such as one that isn't installed everywhere
+#[cfg(all(unix, not(target_os = "macos")))]
+fn run_optional(cmd: &mut Command) {
+    if let Err(err) = run(cmd) {
+        log::warn!("{:#}", err);
+    }
+}
+
+#[cfg(windows)]
+fn reg_add(key: &str, value: Option<&str>, data: &str) -> anyhow::Result<()> {
+    let mut cmd = Command::new("reg");
+    cmd.args(&["add", key]);
+    match value {
+        Some(value) => cmd.args(&["/v", value]),
+        None => cmd.arg("/ve"),
+    };
+    cmd.args(&["/d", data, "/f"]);
+    run(&mut cmd)
+}
@@ -0,0 +1,701 @@
+//! Translates a tmux configuration file into the equivalent wezterm
+//! configuration for `wezterm import-tmux-conf`.  Only the options and
+//! key bindings that have a wezterm equivalent are translated; the
+//! remaining lines are reported so that they can be ported by hand.
+use std::fmt::Write;
+
+/// A line of the tmux configuration that couldn't be translated
+#[derive(Debug, PartialEq)]
+pub struct Untranslated {
+    /// The 1-based line number
+    pub line: usize,
+    pub text: String,
+    pub reason: String,
+}
+
+pub struct Translation {
+    /// A wezterm.lua configuration file
+    pub lua: String,
+    pub untranslated: Vec<Untranslated>,
+}
+
+enum Action {
+    /// A lua expression that evaluates to a KeyAssignment
+    Lua(String),
+    /// Sends the prefix to the terminal.  This is resolved once the
+    /// whole file has been read, as the prefix may be set later on.
+    SendPrefix,
+}
+
+struct Binding {
+    key: String,
+    mods: Vec<&'static str>,
+    action: Action,
+    line: usize,
+    text: String,
+}
+
+/// The prefix that tmux uses when none is configured
+const DEFAULT_PREFIX: &str = "C-b";
+
+#[derive(Default)]
+struct Translator {
+    leader: Option<(String, Vec<&'static str>)>,
+    /// The names and lua values of the config options
+    options: Vec<(&'static str, String)>,
+    bindings: Vec<Binding>,
+    /// The index of the first window in tmux, which is used to map
+    /// `select-window` onto the 0-based `ActivateTab`
+    base_index: isize,
+    untranslated: Vec<Untranslated>,
+}
+
+pub fn translate(conf: &str) -> Translation {
+    let mut translator = Translator::default();
+
+    let mut pending = String::new();
+    let mut first_line = 0;
+    for (idx, line) in conf.lines().enumerate() {
+        if pending.is_empty() {
+            first_line = idx + 1;
+        }
+        // A trailing backslash continues the command on the next line
+        if let Some(line) = line.strip_suffix('\\') {
+            pending.push_str(line);
+            continue;
+        }
+        pending.push_str(line);
+        let text = std::mem::take(&mut pending);
+        translator.command(first_line, text.trim());
+    }
+    if !pending.is_empty() {
+        translator.command(first_line, pending.trim());
+    }
+
+    translator.finish()
+}
+
+impl Translator {
+    fn command(&mut self, line: usize, text: &str) {
+        let result = tokenize(text).and_then(|words| match words.split_first() {
+            None => Ok(()),
+            Some((command, args)) => match command.as_str() {
+                "set" | "set-option" | "setw" | "set-window-option" => self.set_option(args),
+                "bind" | "bind-key" => self.bind(line, text, args),
+                "unbind" | "unbind-key" => self.unbind(args),
+                _ => Err(format!("`{}` has no wezterm equivalent", command)),
+            },
+        });
+        if let Err(reason) = result {
+            self.untranslated.push(Untranslated {
+                line,
+                text: text.to_string(),
+                reason,
+            });
+        }
+    }
+
+    fn set(&mut self, name: &'static str, value: String) {
+        self.options.retain(|(n, _)| *n != name);
+        self.options.push((name, value));
+    }
+
+    fn set_option(&mut self, args: &[String]) -> Result<(), String> {
+        let (flags, args) = parse_flags(args, "t")?;
+        for (flag, _) in &flags {
+            if !"gsowq".contains(*flag) {
+                return Err(format!("`set -{}` has no wezterm equivalent", flag));
+            }
+        }
+        let (name, value) = match args {
+            [name, value] => (name.as_str(), value.as_str()),
+            _ => return Err("expected an option name and a value".to_string()),
+        };
+        if name.starts_with('@') {
+            return Err("plugins and user options have no wezterm equivalent".to_string());
+        }
+
+        match name {
+            "prefix" => {
+                self.leader.replace(parse_key(value)?);
+            }
+            "history-limit" => {
+                let lines: usize = value
+                    .parse()
+                    .map_err(|_| format!("invalid history-limit {}", value))?;
+                self.set("scrollback_lines", lines.to_string());
+            }
+            "status" => {
+                let enabled = match value {
+                    "on" => true,
+                    "off" => false,
+                    _ => return Err("only `on` and `off` can be translated".to_string()),
+                };
+                self.set("enable_tab_bar", enabled.to_string());
+            }
+            "default-shell" => {
+                self.set("default_prog", format!("{{{}}}", lua_string(value)));
+            }
+            "base-index" => {
+                self.base_index = value
+                    .parse()
+                    .map_err(|_| format!("invalid base-index {}", value))?;
+            }
+            _ => return Err(format!("the `{}` option has no wezterm equivalent", name)),
+        }
+        Ok(())
+    }
+
+    fn bind(&mut self, line: usize, text: &str, args: &[String]) -> Result<(), String> {
+        let (flags, args) = parse_flags(args, "TN")?;
+        let (key, command) = args
+            .split_first()
+            .ok_or_else(|| "expected a key".to_string())?;
+        let (key, mods) = bound_key(&flags, key)?;
+        if command.iter().any(|word| word == ";") {
+            return Err(
+                "bindings that run several commands have no wezterm equivalent".to_string(),
+            );
+        }
+        let action = self.action(command)?;
+
+        self.bindings.retain(|b| b.key != key || b.mods != mods);
+        self.bindings.push(Binding {
+            key,
+            mods,
+            action,
+            line,
+            text: text.to_string(),
+        });
+        Ok(())
+    }
+
+    fn unbind(&mut self, args: &[String]) -> Result<(), String> {
+        // wezterm doesn't assign any of the keys that tmux binds by
+        // default, so there is only something to do if the key was
+        // bound earlier in this file
+        let (flags, args) = parse_flags(args, "T")?;
+        if flags.iter().any(|(flag, _)| *flag == 'a') {
+            self.bindings.clear();
+            return Ok(());
+        }
+        let key = args.first().ok_or_else(|| "expected a key".to_string())?;
+        let (key, mods) = bound_key(&flags, key)?;
+        self.bindings.retain(|b| b.key != key || b.mods != mods);
+        Ok(())
+    }
+
+    fn action(&self, command: &[String]) -> Result<Action, String> {
+        let (name, args) = command
+            .split_first()
+            .ok_or_else(|| "expected a command".to_string())?;
+        let (flags, args) = parse_flags(args, "cpt")?;
+        let has = |flag: char| flags.iter().any(|(f, _)| *f == flag);
+        let value = |flag: char| flags.iter().find(|(f, _)| *f == flag).and_then(|(_, v)| *v);
+        let allow = |allowed: &str| -> Result<(), String> {
+            for (flag, _) in &flags {
+                if !allowed.contains(*flag) {
+                    return Err(format!("`{} -{}` has no wezterm equivalent", name, flag));
+                }
+            }
+            if let Some(dir) = value('c') {
+                // wezterm already starts new panes in the current directory
+                if dir != "#{pane_current_path}" {
+                    return Err(format!("`{} -c {}` has no wezterm equivalent", name, dir));
+                }
+            }
+            Ok(())
+        };
+        let no_command = || -> Result<(), String> {
+            if args.is_empty() {
+                Ok(())
+            } else {
+                Err(format!(
+                    "`{}` with a command to run has no wezterm equivalent",
+                    name
+                ))
+            }
+        };
+        let direction = || -> Result<&'static str, String> {
+            let directions: Vec<&str> = [('L', "Left"), ('R', "Right"), ('U', "Up"), ('D', "Down")]
+                .iter()
+                .filter(|(flag, _)| has(*flag))
+                .map(|(_, direction)| *direction)
+                .collect();
+            match directions.as_slice() {
+                [direction] => Ok(*direction),
+                _ => Err(format!("`{}` needs one of -L, -R, -U or -D", name)),
+            }
+        };
+        let lua = |lua: &str| Ok(Action::Lua(lua.to_string()));
+
+        match name.as_str() {
+            "split-window" | "splitw" => {
+                allow("hvc")?;
+                no_command()?;
+                // tmux and wezterm have opposite ideas of which split
+                // is horizontal
+                let split = if has('h') {
+                    "SplitHorizontal"
+                } else {
+                    "SplitVertical"
+                };
+                lua(&format!(
+                    "wezterm.action{{{}={{domain=\"CurrentPaneDomain\"}}}}",
+                    split
+                ))
+            }
+            "new-window" | "neww" => {
+                allow("c")?;
+                no_command()?;
+                lua("wezterm.action{SpawnTab=\"CurrentPaneDomain\"}")
+            }
+            "kill-pane" | "killp" => {
+                allow("")?;
+                lua("wezterm.action{CloseCurrentPane={confirm=false}}")
+            }
+            "kill-window" | "killw" => {
+                allow("")?;
+                lua("wezterm.action{CloseCurrentTab={confirm=false}}")
+            }
+            "confirm-before" | "confirm" => {
+                allow("p")?;
+                match args {
+                    [command] if command == "kill-pane" || command == "killp" => {
+                        lua("wezterm.action{CloseCurrentPane={confirm=true}}")
+                    }
+                    [command] if command == "kill-window" || command == "killw" => {
+                        lua("wezterm.action{CloseCurrentTab={confirm=true}}")
+                    }
+                    _ => Err(
+                        "only confirming kill-pane and kill-window can be translated".to_string(),
+                    ),
+                }
+            }
+            "select-pane" | "selectp" => {
+                allow("LRUD")?;
+                lua(&format!(
+                    "wezterm.action{{ActivatePaneDirection=\"{}\"}}",
+                    direction()?
+                ))
+            }
+            "resize-pane" | "resizep" => {
+                if has('Z') {
+                    allow("Z")?;
+                    return lua("\"TogglePaneZoomState\"");
+                }
+                allow("LRUD")?;
+                let amount: usize = match args {
+                    [] => 1,
+                    [amount] => amount
+                        .parse()
+                        .map_err(|_| format!("invalid resize amount {}", amount))?,
+                    _ => return Err("expected a single resize amount".to_string()),
+                };
+                lua(&format!(
+                    "wezterm.action{{AdjustPaneSize={{\"{}\", {}}}}}",
+                    direction()?,
+                    amount
+                ))
+            }
+            "next-window" | "next" => {
+                allow("")?;
+                lua("wezterm.action{ActivateTabRelative=1}")
+            }
+            "previous-window" | "prev" => {
+                allow("")?;
+                lua("wezterm.action{ActivateTabRelative=-1}")
+            }
+            "last-window" | "last" => {
+                allow("")?;
+                lua("\"ActivateLastTab\"")
+            }
+            "select-window" | "selectw" => {
+                allow("t")?;
+                let target = value('t').ok_or_else(|| "expected a target window".to_string())?;
+                let index: isize = target.trim_start_matches(':').parse().map_err(|_| {
+                    format!("only numeric targets can be translated, not {}", target)
+                })?;
+                if index < self.base_index {
+                    return Err(format!("window {} is before the base-index", index));
+                }
+                lua(&format!(
+                    "wezterm.action{{ActivateTab={}}}",
+                    index - self.base_index
+                ))
+            }
+            "copy-mode" => {
+                allow("")?;
+                lua("\"ActivateCopyMode\"")
+            }
+            "paste-buffer" | "pasteb" => {
+                allow("")?;
+                lua("\"Paste\"")
+            }
+            "source-file" | "source" => lua("\"ReloadConfiguration\""),
+            "choose-tree" | "choose-window" | "choose-session" => lua("\"ShowTabNavigator\""),
+            "clear-history" | "clearhist" => {
+                allow("")?;
+                lua("wezterm.action{ClearScrollback=\"ScrollbackOnly\"}")
+            }
+            "send-prefix" => {
+                allow("")?;
+                Ok(Action::SendPrefix)
+            }
+            _ => Err(format!("`{}` has no wezterm equivalent", name)),
+        }
+    }
+
+    fn finish(mut self) -> Translation {
+        let uses_leader = self.bindings.iter().any(|b| b.mods.contains(&"LEADER"));
+        if self.leader.is_none() && uses_leader {
+            self.leader = parse_key(DEFAULT_PREFIX).ok();
+        }
+
+        let mut lua = String::new();
+        lua.push_str("local wezterm = require 'wezterm';\n\nreturn {\n");
+        if let Some((key, mods)) = &self.leader {
+            writeln!(lua, "  leader = {{{}}},", key_fields(key, mods)).ok();
+        }
+        for (name, value) in &self.options {
+            writeln!(lua, "  {} = {},", name, value).ok();
+        }
+        if !self.bindings.is_empty() {
+            lua.push_str("  keys = {\n");
+            for binding in &self.bindings {
+                let action = match &binding.action {
+                    Action::Lua(action) => action.clone(),
+                    Action::SendPrefix => {
+                        match self
+                            .leader
+                            .as_ref()
+                            .and_then(|(key, mods)| key_text(key, mods))
+                        {
+                            Some(text) => {
+                                format!("wezterm.action{{SendString={}}}", lua_string(&text))
+                            }
+                            None => {
+                                self.untranslated.push(Untranslated {
+                                    line: binding.line,
+                                    text: binding.text.clone(),
+                                    reason: "the prefix cannot be sent as text".to_string(),
+                                });
+                                continue;
+                            }
+                        }
+                    }
+                };
+                writeln!(
+                    lua,
+                    "    {{{}, action={}}},",
+                    key_fields(&binding.key, &binding.mods),
+                    action
+                )
+                .ok();
+            }
+            lua.push_str("  },\n");
+        }
+        lua.push_str("}\n");
+
+        self.untranslated.sort_by_key(|u| u.line);
+        Translation {
+            lua,
+            untranslated: self.untranslated,
+        }
+    }
+}
+
+/// Splits the words of a tmux command, following its quoting rules.
+/// A `#` at the start of a word begins a comment.
+fn tokenize(text: &str) -> Result<Vec<String>, String> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '#' if !in_word => break,
+            ' ' | '\t' => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated quote".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => word.push(c),
+                            None => return Err("unterminated quote".to_string()),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err("unterminated quote".to_string()),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                if let Some(c) = chars.next() {
+                    word.push(c);
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+type Flags<'a> = Vec<(char, Option<&'a str>)>;
+
+/// Splits the leading flags, such as `-g` or `-T root`, from `args`.
+/// `with_value` lists the flags that are followed by a value.
+fn parse_flags<'a>(
+    args: &'a [String],
+    with_value: &str,
+) -> Result<(Flags<'a>, &'a [String]), String> {
+    let mut flags = vec![];
+    let mut idx = 0;
+    while idx < args.len() {
+        let arg = &args[idx];
+        if arg == "--" {
+            idx += 1;
+            break;
+        }
+        if !arg.starts_with('-') || arg.len() < 2 {
+            break;
+        }
+        idx += 1;
+        let mut chars = arg[1..].chars();
+        while let Some(flag) = chars.next() {
+            if with_value.contains(flag) {
+                let value = match chars.as_str() {
+                    "" => {
+                        let value = args
+                            .get(idx)
+                            .ok_or_else(|| format!("-{} requires a value", flag))?;
+                        idx += 1;
+                        value.as_str()
+                    }
+                    rest => rest,
+                };
+                flags.push((flag, Some(value)));
+                break;
+            }
+            flags.push((flag, None));
+        }
+    }
+    Ok((flags, &args[idx..]))
+}
+
+/// Parses a tmux key name, such as `C-a` or `M-Left`, into a wezterm
+/// key name and modifiers
+fn parse_key(key: &str) -> Result<(String, Vec<&'static str>), String> {
+    let mut mods = vec![];
+    let mut rest = key;
+    loop {
+        let modifier = if rest.len() > 2 && rest.starts_with("C-") {
+            "CTRL"
+        } else if rest.len() > 2 && rest.starts_with("M-") {
+            "ALT"
+        } else if rest.len() > 2 && rest.starts_with("S-") {
+            "SHIFT"
+        } else {
+            break;
+        };
+        if !mods.contains(&modifier) {
+            mods.push(modifier);
+        }
+        rest = &rest[2..];
+    }
+    if rest.len() == 2 && rest.starts_with('^') {
+        if !mods.contains(&"CTRL") {
+            mods.push("CTRL");
+        }
+        rest = &rest[1..];
+    }
+
+    let name = match rest {
+        "Up" => "UpArrow",
+        "Down" => "DownArrow",
+        "Left" => "LeftArrow",
+        "Right" => "RightArrow",
+        "PageUp" | "PgUp" | "PPage" => "PageUp",
+        "PageDown" | "PgDn" | "NPage" => "PageDown",
+        "Home" => "Home",
+        "End" => "End",
+        "Enter" => "Enter",
+        "Escape" => "Escape",
+        "Tab" => "Tab",
+        "BTab" => {
+            if !mods.contains(&"SHIFT") {
+                mods.push("SHIFT");
+            }
+            "Tab"
+        }
+        "BSpace" => "Backspace",
+        "DC" => "Delete",
+        "IC" => "Insert",
+        "Space" => " ",
+        _ if rest.chars().count() == 1 => rest,
+        _ if rest.starts_with('F')
+            && matches!(rest[1..].parse::<u8>(), Ok(n) if (1..=24).contains(&n)) =>
+        {
+            rest
+        }
+        _ => return Err(format!("the `{}` key has no wezterm equivalent", key)),
+    };
+    Ok((name.to_string(), mods))
+}
+
+/// Parses the key of a `bind` or `unbind` command.  Keys in the
+/// prefix table are only active after the prefix has been pressed,
+/// which is what the wezterm LEADER modifier does.
+fn bound_key(flags: &Flags, key: &str) -> Result<(String, Vec<&'static str>), String> {
+    let table = if flags.iter().any(|(flag, _)| *flag == 'n') {
+        Some("root")
+    } else {
+        flags
+            .iter()
+            .find(|(flag, _)| *flag == 'T')
+            .and_then(|(_, table)| *table)
+    };
+    let (key, mut mods) = parse_key(key)?;
+    match table {
+        None | Some("prefix") => mods.insert(0, "LEADER"),
+        Some("root") => {}
+        Some(table) => {
+            return Err(format!(
+                "the `{}` key table has no wezterm equivalent",
+                table
+            ))
+        }
+    }
+    Ok((key, mods))
+}
+
+/// Returns the text that the terminal receives for a key press,
+/// if it is a simple one
+fn key_text(key: &str, mods: &[&str]) -> Option<String> {
+    let mut chars = key.chars();
+    let c = chars.next()?;
+    if chars.next().is_some() || !c.is_ascii_graphic() {
+        return None;
+    }
+    match mods {
+        [] => Some(c.to_string()),
+        ["CTRL"] if c.is_ascii_alphabetic() => {
+            Some((((c.to_ascii_lowercase() as u8) & 0x1f) as char).to_string())
+        }
+        ["ALT"] => Some(format!("\x1b{}", c)),
+        _ => None,
+    }
+}
+
+fn key_fields(key: &str, mods: &[&str]) -> String {
+    if mods.is_empty() {
+        format!("key={}", lua_string(key))
+    } else {
+        format!("key={}, mods=\"{}\"", lua_string(key), mods.join("|"))
+    }
+}
+
+fn lua_string(s: &str) -> String {
+    let mut result = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            c if c.is_ascii_control() => {
+                write!(result, "\\x{:02x}", c as u32).ok();
+            }
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn translate_tmux_conf() {
+        let translation = translate(
+            r##"# remap the prefix
+unbind C-b
+set -g prefix C-a
+bind C-a send-prefix
+set -g history-limit 10000
+set-option -g base-index 1
+set -g mouse on
+
+bind | split-window -h -c "#{pane_current_path}"
+bind - split-window -v
+bind -r H resize-pane -L 5
+bind -n M-Left select-pane -L
+bind x confirm-before -p "kill-pane #P? (y/n)" kill-pane
+bind 1 select-window -t :1
+bind r source-file ~/.tmux.conf \; display "Reloaded"
+bind -T copy-mode-vi v send -X begin-selection
+"##,
+        );
+
+        assert_eq!(
+            translation.lua,
+            r#"local wezterm = require 'wezterm';
+
+return {
+  leader = {key="a", mods="CTRL"},
+  scrollback_lines = 10000,
+  keys = {
+    {key="a", mods="LEADER|CTRL", action=wezterm.action{SendString="\x01"}},
+    {key="|", mods="LEADER", action=wezterm.action{SplitHorizontal={domain="CurrentPaneDomain"}}},
+    {key="-", mods="LEADER", action=wezterm.action{SplitVertical={domain="CurrentPaneDomain"}}},
+    {key="H", mods="LEADER", action=wezterm.action{AdjustPaneSize={"Left", 5}}},
+    {key="LeftArrow", mods="ALT", action=wezterm.action{ActivatePaneDirection="Left"}},
+    {key="x", mods="LEADER", action=wezterm.action{CloseCurrentPane={confirm=true}}},
+    {key="1", mods="LEADER", action=wezterm.action{ActivateTab=0}},
+  },
+}
+"#
+        );
+
+        let lines: Vec<usize> = translation.untranslated.iter().map(|u| u.line).collect();
+        assert_eq!(lines, vec![7, 15, 16]);
+        assert_eq!(
+            translation.untranslated[0].reason,
+            "the `mouse` option has no wezterm equivalent"
+        );
+    }
+
+    #[test]
+    fn default_prefix() {
+        let translation = translate("bind c new-window\nbind b send-prefix\n");
+        assert!(translation
+            .lua
+            .contains("leader = {key=\"b\", mods=\"CTRL\"},"));
+        assert!(translation.lua.contains(
+            "{key=\"b\", mods=\"LEADER\", action=wezterm.action{SendString=\"\\x02\"}},"
+        ));
+        assert!(translation.untranslated.is_empty());
+    }
+}
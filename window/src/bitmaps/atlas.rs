@@ -27,6 +27,10 @@ where
 
     /// Dimensions of the texture
     side: usize,
+
+    /// The number of pixels that have been allocated, including
+    /// the padding around each sprite
+    used: usize,
 }
 
 impl<T> Atlas<T>
@@ -46,10 +50,13 @@ where
         texture.write(rect, &image);
 
         let allocator = AtlasAllocator::new(AtlasSize::new(side.try_into()?, side.try_into()?));
+        metrics::gauge!("atlas.side", side as f64);
+        metrics::gauge!("atlas.occupancy", 0.);
         Ok(Self {
             texture: Rc::clone(texture),
             side,
             allocator,
+            used: 0,
         })
     }
 
@@ -97,6 +104,8 @@ where
             );
 
             self.texture.write(rect, im);
+            self.used += (reserve_width * reserve_height) as usize;
+            metrics::gauge!("atlas.occupancy", self.occupancy());
 
             Ok(Sprite {
                 texture: Rc::clone(&self.texture),
@@ -115,6 +124,12 @@ where
         self.side
     }
 
+    /// Returns the proportion of the texture that has been allocated,
+    /// in the range 0.0 to 1.0
+    pub fn occupancy(&self) -> f64 {
+        self.used as f64 / (self.side * self.side) as f64
+    }
+
     /// Zero out the texture, and forget all allocated regions
     pub fn clear(&mut self) {
         let iside = self.side as isize;
@@ -122,6 +137,8 @@ where
         let rect = Rect::new(Point::new(0, 0), Size::new(iside, iside));
         self.texture.write(rect, &image);
         self.allocator.clear();
+        self.used = 0;
+        metrics::gauge!("atlas.occupancy", 0.);
     }
 }
 
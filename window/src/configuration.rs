@@ -1,4 +1,6 @@
-use crate::WindowDecorations;
+use crate::{
+    GpuPowerPreference, MacOsTitlebarAppearance, WindowBackgroundMaterial, WindowDecorations,
+};
 use std::sync::{Arc, Mutex};
 
 pub trait WindowConfiguration {
@@ -18,8 +20,9 @@ pub trait WindowConfiguration {
         false
     }
 
-    // Applies to Windows only.
-    // For macos, see send_composed_key_when_XXX_alt_is_pressed
+    // When false, dead keys are sent immediately rather than being
+    // held for composition with the next key.
+    // For macos, see also send_composed_key_when_XXX_alt_is_pressed
     fn use_dead_keys(&self) -> bool {
         true
     }
@@ -36,6 +39,10 @@ pub trait WindowConfiguration {
         false
     }
 
+    fn gpu_power_preference(&self) -> GpuPowerPreference {
+        GpuPowerPreference::default()
+    }
+
     fn native_macos_fullscreen_mode(&self) -> bool {
         false
     }
@@ -50,9 +57,25 @@ pub trait WindowConfiguration {
         1.0
     }
 
+    /// The effect to apply behind the window when it is transparent
+    fn window_background_material(&self) -> WindowBackgroundMaterial {
+        WindowBackgroundMaterial::default()
+    }
+
     fn decorations(&self) -> WindowDecorations {
         WindowDecorations::default()
     }
+
+    fn macos_titlebar_appearance(&self) -> MacOsTitlebarAppearance {
+        MacOsTitlebarAppearance::default()
+    }
+
+    /// If some, the position (in points, relative to the top left
+    /// of the window) at which to place the macOS close/minimize/zoom
+    /// buttons.
+    fn macos_window_button_position(&self) -> Option<(f64, f64)> {
+        None
+    }
 }
 
 lazy_static::lazy_static! {
@@ -41,6 +41,18 @@ pub trait ConnectionOps {
     /// focus away from it.
     fn hide_application(&self) {}
 
+    /// Returns true if the user has asked the system to minimize
+    /// non-essential motion, such as animations
+    fn prefers_reduced_motion(&self) -> bool {
+        false
+    }
+
+    /// Returns true if the user has asked the system to use a high
+    /// contrast color scheme
+    fn prefers_high_contrast(&self) -> bool {
+        false
+    }
+
     // TODO: return a handle that can be used to cancel the timer
     fn schedule_timer<F: FnMut() + 'static>(&self, interval: std::time::Duration, callback: F);
 }
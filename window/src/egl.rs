@@ -1,5 +1,6 @@
+use crate::GpuPowerPreference;
 use anyhow::{anyhow, bail, ensure, Error};
-use std::ffi::c_void;
+use std::ffi::{c_void, CStr};
 use std::rc::Rc;
 
 #[allow(non_camel_case_types, clippy::unreadable_literal)]
@@ -39,6 +40,11 @@ pub mod ffi {
     pub type EGLNativeWindowType = *const raw::c_void;
 }
 
+// From EGL_ANGLE_power_preference, which isn't in the Khronos registry
+const POWER_PREFERENCE_ANGLE: u32 = 0x3482;
+const LOW_POWER_ANGLE: u32 = 0x0001;
+const HIGH_POWER_ANGLE: u32 = 0x0002;
+
 struct EglWrapper {
     _lib: libloading::Library,
     egl: ffi::Egl,
@@ -162,6 +168,21 @@ impl EglWrapper {
         }
     }
 
+    fn has_extension(&self, display: ffi::types::EGLDisplay, name: &str) -> bool {
+        let extensions = unsafe {
+            self.egl
+                .QueryString(display, ffi::EXTENSIONS as ffi::EGLint)
+        };
+        if extensions.is_null() {
+            return false;
+        }
+        let extensions = unsafe { CStr::from_ptr(extensions) };
+        extensions
+            .to_string_lossy()
+            .split_whitespace()
+            .any(|ext| ext == name)
+    }
+
     fn config_attrib(
         &self,
         display: ffi::types::EGLDisplay,
@@ -414,6 +435,18 @@ impl GlState {
         let mut errors = vec![];
         let mut prefer_swrast = crate::configuration::config().prefer_swrast();
 
+        if cfg!(all(not(target_os = "macos"), not(target_os = "windows")))
+            && crate::configuration::config().gpu_power_preference()
+                == GpuPowerPreference::HighPerformance
+            && std::env::var_os("DRI_PRIME").is_none()
+        {
+            // Assuming that we're using Mesa, set an environment
+            // variable that selects the secondary GPU of a PRIME system,
+            // which is the discrete one.  Mesa otherwise renders using
+            // the primary GPU, which is typically the integrated one.
+            std::env::set_var("DRI_PRIME", "1");
+        }
+
         for _ in 0..2 {
             if prefer_swrast {
                 // Assuming that we're using Mesa, set an environment
@@ -570,6 +603,19 @@ impl GlState {
                     attributes.push(a);
                 }
             }
+            if connection
+                .egl
+                .has_extension(connection.display, "EGL_ANGLE_power_preference")
+            {
+                // ANGLE selects the GPU when the context is created
+                attributes.push(POWER_PREFERENCE_ANGLE);
+                attributes.push(
+                    match crate::configuration::config().gpu_power_preference() {
+                        GpuPowerPreference::LowPower => LOW_POWER_ANGLE,
+                        GpuPowerPreference::HighPerformance => HIGH_POWER_ANGLE,
+                    },
+                );
+            }
             attributes.push(ffi::NONE);
 
             let context = match connection.egl.create_context(
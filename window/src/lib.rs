@@ -127,6 +127,9 @@ pub trait WindowCallbacks: Any {
         context.set_cursor(Some(MouseCursor::Arrow));
     }
 
+    /// Called to handle input from a touchscreen
+    fn touch_event(&mut self, event: &TouchEvent, context: &dyn WindowOps) {}
+
     /// Called when the window is created and allows the embedding
     /// app to reference the window and operate upon it.
     fn created(
@@ -146,7 +149,8 @@ pub trait WindowCallbacks: Any {
 }
 
 pub trait WindowOps {
-    /// Show a hidden window
+    /// Show a hidden window, raising it above other windows where
+    /// the platform permits
     fn show(&self) -> Future<()>;
 
     /// Hide a visible window
@@ -211,13 +215,26 @@ pub trait WindowOps {
         Future::ok(())
     }
 
+    /// Ask the window manager to keep the window stacked above
+    /// other windows, or to restore normal stacking.
+    fn set_always_on_top(&self, _on_top: bool) -> Future<()> {
+        Future::ok(())
+    }
+
+    /// Ask the window manager to show the window on all virtual
+    /// desktops/workspaces/spaces, or only on the current one.
+    fn set_visible_on_all_workspaces(&self, _visible: bool) -> Future<()> {
+        Future::ok(())
+    }
+
     fn config_did_change(&self, _config: &WindowConfigHandle) -> Future<()> {
         Future::ok(())
     }
 }
 
 pub trait WindowOpsMut {
-    /// Show a hidden window
+    /// Show a hidden window, raising it above other windows where
+    /// the platform permits
     fn show(&mut self);
 
     /// Hide a visible window
@@ -256,5 +273,9 @@ pub trait WindowOpsMut {
 
     fn toggle_fullscreen(&mut self) {}
 
+    fn set_always_on_top(&mut self, _on_top: bool) {}
+
+    fn set_visible_on_all_workspaces(&mut self, _visible: bool) {}
+
     fn config_did_change(&mut self, _config: &WindowConfigHandle) {}
 }
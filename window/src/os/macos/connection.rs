@@ -5,7 +5,7 @@ use super::window::WindowInner;
 use crate::connection::ConnectionOps;
 use crate::spawn::*;
 use cocoa::appkit::{NSApp, NSApplication, NSApplicationActivationPolicyRegular};
-use cocoa::base::{id, nil};
+use cocoa::base::{id, nil, BOOL, YES};
 use core_foundation::date::CFAbsoluteTimeGetCurrent;
 use core_foundation::runloop::*;
 use objc::*;
@@ -94,6 +94,22 @@ impl ConnectionOps for Connection {
         }
     }
 
+    fn prefers_reduced_motion(&self) -> bool {
+        unsafe {
+            let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+            let reduce: BOOL = msg_send![workspace, accessibilityDisplayShouldReduceMotion];
+            reduce == YES
+        }
+    }
+
+    fn prefers_high_contrast(&self) -> bool {
+        unsafe {
+            let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+            let increase: BOOL = msg_send![workspace, accessibilityDisplayShouldIncreaseContrast];
+            increase == YES
+        }
+    }
+
     fn schedule_timer<F: FnMut() + 'static>(&self, interval: std::time::Duration, callback: F) {
         let secs_f64 =
             (interval.as_secs() as f64) + (f64::from(interval.subsec_nanos()) / 1_000_000_000_f64);
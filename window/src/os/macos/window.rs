@@ -4,20 +4,21 @@
 use super::{nsstring, nsstring_to_str};
 use crate::connection::ConnectionOps;
 use crate::{
-    config, Clipboard, Connection, Dimensions, KeyCode, KeyEvent, Modifiers, MouseButtons,
-    MouseCursor, MouseEvent, MouseEventKind, MousePress, Point, Rect, ScreenPoint, Size,
-    WindowCallbacks, WindowConfigHandle, WindowDecorations, WindowOps, WindowOpsMut,
+    config, Clipboard, Connection, Dimensions, GpuPowerPreference, KeyCode, KeyEvent,
+    MacOsTitlebarAppearance, Modifiers, MouseButtons, MouseCursor, MouseEvent, MouseEventKind,
+    MousePress, Point, Rect, ScreenPoint, Size, WindowBackgroundMaterial, WindowCallbacks,
+    WindowConfigHandle, WindowDecorations, WindowOps, WindowOpsMut,
 };
 use anyhow::{anyhow, bail, ensure};
 use cocoa::appkit::{
     self, NSApplication, NSApplicationActivateIgnoringOtherApps, NSApplicationPresentationOptions,
     NSBackingStoreBuffered, NSEvent, NSEventModifierFlags, NSOpenGLContext, NSOpenGLPixelFormat,
     NSRunningApplication, NSScreen, NSView, NSViewHeightSizable, NSViewWidthSizable, NSWindow,
-    NSWindowStyleMask,
+    NSWindowButton, NSWindowCollectionBehavior, NSWindowStyleMask, NSWindowTitleVisibility,
 };
 use cocoa::base::*;
 use cocoa::foundation::NSAutoreleasePool;
-use cocoa::foundation::{NSArray, NSNotFound, NSPoint, NSRect, NSSize, NSUInteger};
+use cocoa::foundation::{NSArray, NSInteger, NSNotFound, NSPoint, NSRect, NSSize, NSUInteger};
 use core_foundation::base::{CFTypeID, TCFType};
 use core_foundation::bundle::{CFBundleGetBundleWithIdentifier, CFBundleGetFunctionPointerForName};
 use core_foundation::data::{CFData, CFDataGetBytePtr, CFDataRef};
@@ -213,9 +214,16 @@ mod cglbits {
                     24,
                     appkit::NSOpenGLPFAStencilSize as u32,
                     8,
-                    appkit::NSOpenGLPFAAllowOfflineRenderers as u32,
                     appkit::NSOpenGLPFAAccelerated as u32,
                     appkit::NSOpenGLPFADoubleBuffer as u32,
+                    // Allowing offline renderers lets the system keep
+                    // using the integrated GPU; without it, the system
+                    // switches to the discrete GPU while we're running
+                    if config().gpu_power_preference() == GpuPowerPreference::LowPower {
+                        appkit::NSOpenGLPFAAllowOfflineRenderers as u32
+                    } else {
+                        0
+                    },
                     0,
                 ]))
             };
@@ -366,7 +374,8 @@ impl Window {
         };
 
         unsafe {
-            let style_mask = decoration_to_mask(config.decorations());
+            let style_mask =
+                decoration_to_mask(config.decorations(), config.macos_titlebar_appearance());
             let rect = NSRect::new(
                 NSPoint::new(0., 0.),
                 NSSize::new(width as f64, height as f64),
@@ -386,6 +395,7 @@ impl Window {
                 text_cursor_position: Rect::new(Point::new(0, 0), Size::new(0, 0)),
                 hscroll_remainder: 0.,
                 vscroll_remainder: 0.,
+                vscroll_pixel_remainder: 0.,
                 last_wheel: Instant::now(),
                 key_is_down: None,
                 dead_pending: None,
@@ -591,6 +601,20 @@ impl WindowOps for Window {
         })
     }
 
+    fn set_always_on_top(&self, on_top: bool) -> Future<()> {
+        Connection::with_window_inner(self.0, move |inner| {
+            inner.set_always_on_top(on_top);
+            Ok(())
+        })
+    }
+
+    fn set_visible_on_all_workspaces(&self, visible: bool) -> Future<()> {
+        Connection::with_window_inner(self.0, move |inner| {
+            inner.set_visible_on_all_workspaces(visible);
+            Ok(())
+        })
+    }
+
     fn config_did_change(&self, config: &WindowConfigHandle) -> Future<()> {
         let config = Arc::clone(config);
         Connection::with_window_inner(self.0, move |inner| {
@@ -645,7 +669,10 @@ impl WindowInner {
 
     fn apply_decorations(&mut self) {
         if !self.is_fullscreen() {
-            let mask = decoration_to_mask(self.config.decorations());
+            let mask = decoration_to_mask(
+                self.config.decorations(),
+                self.config.macos_titlebar_appearance(),
+            );
             unsafe {
                 self.window.setStyleMask_(mask);
                 /*
@@ -659,9 +686,36 @@ impl WindowInner {
                 );
                 */
             }
+            self.apply_titlebar_appearance();
         }
     }
 
+    fn apply_titlebar_appearance(&mut self) {
+        let appearance = self.config.macos_titlebar_appearance();
+        let transparent = appearance != MacOsTitlebarAppearance::Standard;
+        let hide_buttons = appearance == MacOsTitlebarAppearance::TransparentWithoutButtons;
+        unsafe {
+            self.window
+                .setTitlebarAppearsTransparent_(if transparent { YES } else { NO });
+            self.window.setTitleVisibility_(if transparent {
+                NSWindowTitleVisibility::NSWindowTitleHidden
+            } else {
+                NSWindowTitleVisibility::NSWindowTitleVisible
+            });
+            for kind in &[
+                NSWindowButton::NSWindowCloseButton,
+                NSWindowButton::NSWindowMiniaturizeButton,
+                NSWindowButton::NSWindowZoomButton,
+            ] {
+                let button = self.window.standardWindowButton_(*kind);
+                if button != nil {
+                    let () = msg_send![button, setHidden: if hide_buttons { YES } else { NO }];
+                }
+            }
+        }
+        position_window_buttons(*self.window, &self.config);
+    }
+
     fn toggle_native_fullscreen(&mut self) {
         unsafe {
             NSWindow::toggleFullScreen_(*self.window, nil);
@@ -707,8 +761,10 @@ impl WindowInner {
                 Some(saved_rect) => unsafe {
                     // Restore prior dimensions
                     self.window.orderOut_(nil);
-                    self.window
-                        .setStyleMask_(decoration_to_mask(self.config.decorations()));
+                    self.window.setStyleMask_(decoration_to_mask(
+                        self.config.decorations(),
+                        self.config.macos_titlebar_appearance(),
+                    ));
                     self.window.setFrame_display_(saved_rect, YES);
                     self.window.makeKeyAndOrderFront_(nil);
                     self.window.setOpaque_(NO);
@@ -758,6 +814,27 @@ impl WindowInner {
             self.window.setHasShadow_(is_opaque);
         }
     }
+
+    fn update_background_material(&mut self) {
+        // There is no public API for blurring behind a transparent
+        // window other than layering an NSVisualEffectView under
+        // our GL view, so use the private CoreGraphics function that
+        // the Terminal and iTerm2 also rely upon.
+        // macOS only has the one blur effect; it is used for any of
+        // the materials.
+        let radius = match self.config.window_background_material() {
+            WindowBackgroundMaterial::None => 0,
+            _ => MACOS_BACKGROUND_BLUR_RADIUS,
+        };
+        unsafe {
+            let window_number: NSInteger = msg_send![*self.window, windowNumber];
+            let conn = CGSDefaultConnectionForThread();
+            let status = CGSSetWindowBackgroundBlurRadius(conn, window_number, radius);
+            if status != 0 {
+                log::warn!("CGSSetWindowBackgroundBlurRadius failed: {}", status);
+            }
+        }
+    }
 }
 
 impl WindowOpsMut for WindowInner {
@@ -870,17 +947,53 @@ impl WindowOpsMut for WindowInner {
         }
     }
 
+    fn set_always_on_top(&mut self, on_top: bool) {
+        // NSFloatingWindowLevel and NSNormalWindowLevel respectively
+        let level = if on_top { 3 } else { 0 };
+        unsafe {
+            self.window.setLevel_(level);
+        }
+    }
+
+    fn set_visible_on_all_workspaces(&mut self, visible: bool) {
+        unsafe {
+            let mut behavior = self.window.collectionBehavior();
+            behavior.set(
+                NSWindowCollectionBehavior::NSWindowCollectionBehaviorCanJoinAllSpaces,
+                visible,
+            );
+            self.window.setCollectionBehavior_(behavior);
+        }
+    }
+
     fn config_did_change(&mut self, config: &WindowConfigHandle) {
         self.config = Arc::clone(config);
         if let Some(window_view) = WindowView::get_this(unsafe { &**self.view }) {
             window_view.inner.borrow_mut().config = Arc::clone(config);
         }
         self.update_window_shadow();
+        self.update_background_material();
         self.apply_decorations();
     }
 }
 
-fn decoration_to_mask(decorations: WindowDecorations) -> NSWindowStyleMask {
+fn decoration_to_mask(
+    decorations: WindowDecorations,
+    appearance: MacOsTitlebarAppearance,
+) -> NSWindowStyleMask {
+    let mask = decoration_to_base_mask(decorations);
+    if appearance != MacOsTitlebarAppearance::Standard
+        && mask.contains(NSWindowStyleMask::NSTitledWindowMask)
+    {
+        // Keep the titled style so that we retain the rounded corners
+        // and the window buttons, but let the content fill the titlebar
+        mask | NSWindowStyleMask::NSFullSizeContentViewWindowMask
+    } else {
+        mask
+    }
+}
+
+fn decoration_to_base_mask(decorations: WindowDecorations) -> NSWindowStyleMask {
     if decorations == WindowDecorations::TITLE | WindowDecorations::RESIZE {
         NSWindowStyleMask::NSTitledWindowMask
             | NSWindowStyleMask::NSClosableWindowMask
@@ -902,6 +1015,42 @@ fn decoration_to_mask(decorations: WindowDecorations) -> NSWindowStyleMask {
     }
 }
 
+/// Moves the close/minimize/zoom buttons to the position specified
+/// by the configuration, if any.
+/// AppKit resets the button positions whenever it lays out the
+/// titlebar, so this needs to be called again after a resize.
+fn position_window_buttons(window: id, config: &WindowConfigHandle) {
+    let (x, y) = match config.macos_window_button_position() {
+        Some(pos) => pos,
+        None => return,
+    };
+    unsafe {
+        let close = window.standardWindowButton_(NSWindowButton::NSWindowCloseButton);
+        let miniaturize = window.standardWindowButton_(NSWindowButton::NSWindowMiniaturizeButton);
+        let zoom = window.standardWindowButton_(NSWindowButton::NSWindowZoomButton);
+        if close == nil || miniaturize == nil || zoom == nil {
+            return;
+        }
+
+        // The buttons live in the titlebar container view; grow it
+        // so that the buttons remain clickable when pushed down
+        let superview: id = msg_send![close, superview];
+        let container: id = msg_send![superview, superview];
+        let close_rect = NSView::frame(close);
+        let mut container_rect = NSView::frame(container);
+        container_rect.size.height = close_rect.size.height + y;
+        container_rect.origin.y = NSWindow::frame(window).size.height - container_rect.size.height;
+        let () = msg_send![container, setFrame: container_rect];
+
+        let spacing = NSView::frame(miniaturize).origin.x - close_rect.origin.x;
+        for (idx, button) in [close, miniaturize, zoom].iter().enumerate() {
+            let mut rect = NSView::frame(*button);
+            rect.origin.x = x + (idx as f64 * spacing);
+            let () = msg_send![*button, setFrameOrigin: rect.origin];
+        }
+    }
+}
+
 struct Inner {
     callbacks: Box<dyn WindowCallbacks>,
     view_id: Option<WeakPtr>,
@@ -912,6 +1061,8 @@ struct Inner {
     text_cursor_position: Rect,
     hscroll_remainder: f64,
     vscroll_remainder: f64,
+    /// Fractional pixels left over from precise vertical scrolling
+    vscroll_pixel_remainder: f64,
     last_wheel: Instant,
     /// We use this to avoid double-emitting events when
     /// procesing key-up events.
@@ -979,6 +1130,20 @@ extern "C" {
     fn LMGetKbdType() -> u8;
 }
 
+const MACOS_BACKGROUND_BLUR_RADIUS: NSInteger = 20;
+
+type CGSConnectionID = *mut c_void;
+
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGSDefaultConnectionForThread() -> CGSConnectionID;
+    fn CGSSetWindowBackgroundBlurRadius(
+        connection: CGSConnectionID,
+        window_number: NSInteger,
+        radius: NSInteger,
+    ) -> i32;
+}
+
 impl Inner {
     fn enable_opengl(&mut self) -> anyhow::Result<()> {
         let window = Window(self.window_id);
@@ -1498,6 +1663,39 @@ impl WindowView {
 
     extern "C" fn scroll_wheel(this: &mut Object, _sel: Sel, nsevent: id) {
         let precise = unsafe { nsevent.hasPreciseScrollingDeltas() } == YES;
+        if precise {
+            let vert_points = unsafe { nsevent.scrollingDeltaY() };
+            let horz_points = unsafe { nsevent.scrollingDeltaX() };
+            if vert_points.abs() >= horz_points.abs() {
+                // Report vertical scrolling in pixels, so that the terminal
+                // can scroll by the amount that the fingers moved using the
+                // actual cell height.  This includes the momentum phase of
+                // a kinetic scroll, which the system reports in the same way.
+                let view = this as id;
+                let unit = NSRect::new(NSPoint::new(0., 0.), NSSize::new(1., 1.));
+                let backing_scale = unsafe { NSView::convertRectToBacking(view, unit) }
+                    .size
+                    .height;
+
+                let pixels = match Self::get_this(this) {
+                    Some(myself) => {
+                        let mut inner = myself.inner.borrow_mut();
+                        let pixels = vert_points * backing_scale + inner.vscroll_pixel_remainder;
+                        inner.vscroll_pixel_remainder = pixels.fract();
+                        pixels.trunc()
+                    }
+                    None => return,
+                };
+                if pixels != 0. {
+                    Self::mouse_common(
+                        this,
+                        nsevent,
+                        MouseEventKind::VertPixelWheel(pixels as i16),
+                    );
+                }
+                return;
+            }
+        }
         let scale = if precise {
             // Devices with precise deltas report number of pixels scrolled.
             // At this layer we don't know how many pixels comprise a cell
@@ -1615,7 +1813,8 @@ impl WindowView {
             return;
         };
 
-        let use_ime = config().use_ime();
+        let config = config();
+        let use_ime = config.use_ime();
 
         // `Delete` on macos is really Backspace and emits BS.
         // `Fn-Delete` emits DEL.
@@ -1648,7 +1847,18 @@ impl WindowView {
         let only_alt = (modifiers & !(Modifiers::LEFT_ALT | Modifiers::RIGHT_ALT | Modifiers::ALT))
             == Modifiers::NONE;
 
-        if key_is_down && use_ime && (modifiers.is_empty() || only_alt) {
+        // Only allow the IME to compose an Option-modified key if the
+        // Option key on that side is configured to compose; otherwise
+        // it must be delivered as Alt so that eg: Left-Opt can act as Meta
+        let alt_composes = if modifiers.contains(Modifiers::LEFT_ALT) {
+            config.send_composed_key_when_left_alt_is_pressed()
+        } else if modifiers.contains(Modifiers::RIGHT_ALT) {
+            config.send_composed_key_when_right_alt_is_pressed()
+        } else {
+            true
+        };
+
+        if key_is_down && use_ime && (modifiers.is_empty() || (only_alt && alt_composes)) {
             if let Some(myself) = Self::get_this(this) {
                 let mut inner = myself.inner.borrow_mut();
                 inner.key_is_down.replace(key_is_down);
@@ -1770,6 +1980,12 @@ impl WindowView {
                     style_mask.contains(NSWindowStyleMask::NSFullScreenWindowMask)
                 });
 
+            if !is_fullscreen {
+                if let Some(window) = inner.window.as_ref() {
+                    position_window_buttons(*window.load(), &inner.config);
+                }
+            }
+
             inner.callbacks.resize(
                 Dimensions {
                     pixel_width: width as usize,
@@ -1825,9 +2041,23 @@ impl WindowView {
                 );
 
                 inner.callbacks.paint(&mut frame);
-                frame
-                    .finish()
-                    .expect("frame.finish failed and we don't know how to recover");
+                match frame.finish() {
+                    Ok(()) => {}
+                    Err(glium::SwapBuffersError::ContextLost) => {
+                        // Not all drivers report the loss via is_context_lost,
+                        // so also recover if presenting the frame fails
+                        log::error!("opengl context was lost while presenting; should reinit");
+                        drop(inner.gl_context_pair.take());
+                        if let Err(e) = inner.enable_opengl() {
+                            log::error!("failed to reinit opengl: {}", e);
+                        }
+                        inner.invalidate();
+                    }
+                    Err(err) => panic!(
+                        "frame.finish failed and we don't know how to recover: {}",
+                        err
+                    ),
+                }
             }
         }
     }
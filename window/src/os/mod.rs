@@ -3,6 +3,11 @@ pub mod windows;
 #[cfg(windows)]
 pub use windows::*;
 
+/// How many times in a row a lost opengl context may be replaced
+/// before giving up, so that a driver that keeps losing the
+/// context doesn't leave us spinning
+pub(crate) const MAX_GL_REINIT_ATTEMPTS: usize = 3;
+
 pub mod wayland;
 pub mod x11;
 pub mod x_and_wayland;
@@ -1,6 +1,7 @@
 #![allow(dead_code)]
 use super::keyboard::KeyboardDispatcher;
 use super::pointer::*;
+use super::touch::TouchDispatcher;
 use super::window::*;
 use crate::connection::ConnectionOps;
 use crate::spawn::*;
@@ -38,6 +39,7 @@ pub struct WaylandConnection {
     pub(crate) gl_connection: RefCell<Option<Rc<crate::egl::GlConnection>>>,
     pub(crate) pointer: PointerDispatcher,
     pub(crate) keyboard: KeyboardDispatcher,
+    pub(crate) touch: TouchDispatcher,
     seat_listener: SeatListener,
     pub(crate) environment: RefCell<Environment<MyEnvironment>>,
     event_q: RefCell<EventLoop<()>>,
@@ -51,14 +53,16 @@ impl WaylandConnection {
         let event_loop = toolkit::reexports::calloop::EventLoop::<()>::new()?;
 
         let keyboard = KeyboardDispatcher::new();
+        let touch = TouchDispatcher::new();
         let mut pointer = None;
 
         for seat in environment.get_all_seats() {
-            if let Some((has_kbd, has_ptr, name)) =
+            if let Some((has_kbd, has_ptr, has_touch, name)) =
                 toolkit::seat::with_seat_data(&seat, |seat_data| {
                     (
                         seat_data.has_keyboard && !seat_data.defunct,
                         seat_data.has_pointer && !seat_data.defunct,
+                        seat_data.has_touch && !seat_data.defunct,
                         seat_data.name.clone(),
                     )
                 })
@@ -74,6 +78,9 @@ impl WaylandConnection {
                         environment.require_global(),
                     )?);
                 }
+                if has_touch {
+                    touch.register(&seat, &name);
+                }
             }
         }
 
@@ -81,6 +88,7 @@ impl WaylandConnection {
         {
             let loop_handle = event_loop.handle();
             let keyboard = keyboard.clone();
+            let touch = touch.clone();
             seat_listener = environment.listen_for_seats(move |seat, seat_data, _| {
                 if seat_data.has_keyboard {
                     if seat_data.defunct {
@@ -93,6 +101,13 @@ impl WaylandConnection {
                         }
                     }
                 }
+                if seat_data.has_touch {
+                    if seat_data.defunct {
+                        touch.deregister(&seat_data.name);
+                    } else {
+                        touch.register(&seat, &seat_data.name);
+                    }
+                }
                 if seat_data.has_pointer {
                     // TODO: ideally do something similar to the keyboard state,
                     // but the pointer state has a lot of other stuff floating
@@ -120,6 +135,7 @@ impl WaylandConnection {
             next_window_id: AtomicUsize::new(1),
             windows: RefCell::new(HashMap::new()),
             keyboard,
+            touch,
             pointer: pointer.unwrap(),
             seat_listener,
             gl_connection: RefCell::new(None),
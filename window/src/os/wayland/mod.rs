@@ -7,3 +7,4 @@ pub use connection::*;
 mod copy_and_paste;
 mod keyboard;
 mod pointer;
+mod touch;
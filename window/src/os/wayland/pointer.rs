@@ -106,6 +106,9 @@ pub struct PendingMouse {
     surface_coords: Option<(f64, f64)>,
     button: Vec<(MousePress, DebuggableButtonState)>,
     scroll: Option<(f64, f64)>,
+    /// Whether the most recent scroll came from a device such as
+    /// a touchpad, rather than a mouse wheel
+    continuous_scroll: bool,
 }
 
 impl PendingMouse {
@@ -115,6 +118,7 @@ impl PendingMouse {
             copy_and_paste: Arc::clone(copy_and_paste),
             button: vec![],
             scroll: None,
+            continuous_scroll: false,
             surface_coords: None,
         }))
     }
@@ -186,6 +190,11 @@ impl PendingMouse {
                 self.scroll.replace((x + value, y));
                 changed
             }
+            SendablePointerEvent::AxisSource { axis_source } => {
+                self.continuous_scroll =
+                    matches!(axis_source, AxisSource::Finger | AxisSource::Continuous);
+                false
+            }
             _ => false,
         }
     }
@@ -206,6 +215,12 @@ impl PendingMouse {
     pub fn scroll(pending: &Arc<Mutex<Self>>) -> Option<(f64, f64)> {
         pending.lock().unwrap().scroll.take()
     }
+
+    /// Returns true if the pending scroll is measured in surface
+    /// coordinates rather than being the steps of a mouse wheel
+    pub fn is_continuous_scroll(pending: &Arc<Mutex<Self>>) -> bool {
+        pending.lock().unwrap().continuous_scroll
+    }
 }
 
 impl PointerDispatcher {
@@ -0,0 +1,103 @@
+use crate::os::wayland::connection::WaylandConnection;
+use smithay_client_toolkit as toolkit;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use toolkit::reexports::client::protocol::wl_touch::{Event as WlTouchEvent, WlTouch};
+use wayland_client::protocol::wl_seat::WlSeat;
+use wayland_client::protocol::wl_surface::WlSurface;
+use wayland_client::{Attached, Main};
+use wezterm_input_types::*;
+
+#[derive(Default)]
+struct Inner {
+    surface_to_window_id: HashMap<u32, usize>,
+    /// The window in which each active touch point went down,
+    /// along with its most recent surface coordinates.
+    /// The compositor doesn't tell us the position when
+    /// the touch point is lifted, so we remember it here.
+    touches: HashMap<i32, (usize, f64, f64)>,
+    by_name: HashMap<String, Main<WlTouch>>,
+}
+
+impl Inner {
+    fn handle_event(&mut self, evt: WlTouchEvent) {
+        match evt {
+            WlTouchEvent::Down {
+                surface, id, x, y, ..
+            } => {
+                // Ignore touches on the auxilliary surfaces that are
+                // created for the window decorations
+                if let Some(window_id) = self.surface_to_window_id.get(&surface.as_ref().id()) {
+                    self.touches.insert(id, (*window_id, x, y));
+                    Self::dispatch_to_window(*window_id, TouchEventKind::Down, id, x, y);
+                }
+            }
+            WlTouchEvent::Motion { id, x, y, .. } => {
+                if let Some(touch) = self.touches.get_mut(&id) {
+                    touch.1 = x;
+                    touch.2 = y;
+                    Self::dispatch_to_window(touch.0, TouchEventKind::Move, id, x, y);
+                }
+            }
+            WlTouchEvent::Up { id, .. } => {
+                if let Some((window_id, x, y)) = self.touches.remove(&id) {
+                    Self::dispatch_to_window(window_id, TouchEventKind::Up, id, x, y);
+                }
+            }
+            WlTouchEvent::Cancel => {
+                for (id, (window_id, x, y)) in self.touches.drain() {
+                    Self::dispatch_to_window(window_id, TouchEventKind::Cancel, id, x, y);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn dispatch_to_window(window_id: usize, kind: TouchEventKind, id: i32, x: f64, y: f64) {
+        WaylandConnection::with_window_inner(window_id, move |inner| {
+            inner.dispatch_touch(kind, id, x, y);
+            Ok(())
+        });
+    }
+}
+
+#[derive(Clone)]
+pub struct TouchDispatcher {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl TouchDispatcher {
+    pub fn new() -> Self {
+        let inner = Arc::new(Mutex::new(Inner::default()));
+        Self { inner }
+    }
+
+    pub fn register(&self, seat: &Attached<WlSeat>, name: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.by_name.contains_key(name) {
+            return;
+        }
+
+        let touch = seat.get_touch();
+        touch.quick_assign({
+            let inner = Arc::clone(&self.inner);
+            move |_, evt, _| {
+                inner.lock().unwrap().handle_event(evt);
+            }
+        });
+        inner.by_name.insert(name.to_string(), touch);
+    }
+
+    pub fn deregister(&self, name: &str) {
+        if let Some(touch) = self.inner.lock().unwrap().by_name.remove(name) {
+            touch.release();
+        }
+    }
+
+    pub fn add_window(&self, window_id: usize, surface: &WlSurface) {
+        let mut inner = self.inner.lock().unwrap();
+        inner
+            .surface_to_window_id
+            .insert(surface.as_ref().id(), window_id);
+    }
+}
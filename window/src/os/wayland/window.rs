@@ -3,7 +3,7 @@ use super::keyboard::KeyboardEvent;
 use super::pointer::*;
 use crate::connection::ConnectionOps;
 use crate::os::wayland::connection::WaylandConnection;
-use crate::os::xkeysyms::keysym_to_keycode;
+use crate::os::xkeysyms::{dead_keysym_to_char, is_modifier_keysym, keysym_to_keycode};
 use crate::WindowConfigHandle;
 use crate::{
     Clipboard, Connection, Dimensions, MouseCursor, Point, ScreenPoint, Window, WindowCallbacks,
@@ -86,10 +86,19 @@ pub struct WaylandWindowInner {
     window: Option<toolkit::window::Window<ConceptFrame>>,
     dimensions: Dimensions,
     need_paint: bool,
+    /// The number of consecutive times the opengl context has been
+    /// replaced without successfully presenting a frame
+    gl_reinit_attempts: usize,
     full_screen: bool,
     last_mouse_coords: Point,
     mouse_buttons: MouseButtons,
     modifiers: Modifiers,
+    /// Set when a dead key was sent immediately because `use_dead_keys`
+    /// is disabled; the toolkit's compose state still saw it, so the
+    /// next key must bypass any composed text
+    dead_key_pending: bool,
+    /// Fractional pixels left over from touchpad scrolling
+    scroll_pixel_remainder: f64,
     pending_event: Arc<Mutex<PendingEvent>>,
     pending_mouse: Arc<Mutex<PendingMouse>>,
     // wegl_surface is listed before gl_state because it
@@ -239,6 +248,7 @@ impl WaylandWindow {
 
         // window.new_seat(&conn.seat);
         conn.keyboard.add_window(window_id, &surface);
+        conn.touch.add_window(window_id, &surface);
 
         let copy_and_paste = CopyAndPaste::create();
         let pending_mouse = PendingMouse::create(window_id, &copy_and_paste);
@@ -253,10 +263,13 @@ impl WaylandWindow {
             window: Some(window),
             dimensions,
             need_paint: true,
+            gl_reinit_attempts: 0,
             full_screen: false,
             last_mouse_coords: Point::new(0, 0),
             mouse_buttons: MouseButtons::NONE,
             modifiers: Modifiers::NONE,
+            dead_key_pending: false,
+            scroll_pixel_remainder: 0.,
             pending_event,
             pending_mouse,
             gl_state: None,
@@ -285,6 +298,29 @@ impl WaylandWindowInner {
                     .lock()
                     .unwrap()
                     .update_last_serial(serial);
+                let dead_char = if crate::config().use_dead_keys() {
+                    None
+                } else {
+                    dead_keysym_to_char(keysym)
+                };
+                let utf8 = if let Some(c) = dead_char {
+                    if is_down {
+                        self.dead_key_pending = true;
+                    }
+                    Some(c.to_string())
+                } else if is_down
+                    && !is_modifier_keysym(keysym)
+                    && std::mem::replace(&mut self.dead_key_pending, false)
+                {
+                    // Use the raw keysym rather than the result of
+                    // composing it with the preceding dead key.
+                    // Modifiers such as the shift needed to type the
+                    // next key don't end the compose sequence, so they
+                    // leave dead_key_pending alone.
+                    None
+                } else {
+                    utf8
+                };
                 let raw_key = keysym_to_keycode(keysym);
                 let (key, raw_key) = match utf8 {
                     Some(text) if text.chars().count() == 1 => {
@@ -413,10 +449,29 @@ impl WaylandWindowInner {
                     .mouse_event(&event, &Window::Wayland(WaylandWindow(self.window_id)));
             }
 
-            let discrete_y = value_y.trunc() * factor;
-            if discrete_y != 0. {
+            let kind = if PendingMouse::is_continuous_scroll(&pending_mouse) {
+                // Touchpads report the distance scrolled, so pass that
+                // along in pixels and let the terminal convert it to lines
+                // using the cell height
+                let pixels = value_y * factor + self.scroll_pixel_remainder;
+                self.scroll_pixel_remainder = pixels.fract();
+                let pixels = pixels.trunc();
+                if pixels != 0. {
+                    Some(MouseEventKind::VertPixelWheel(-pixels as i16))
+                } else {
+                    None
+                }
+            } else {
+                let discrete_y = value_y.trunc() * factor;
+                if discrete_y != 0. {
+                    Some(MouseEventKind::VertWheel(-discrete_y as i16))
+                } else {
+                    None
+                }
+            };
+            if let Some(kind) = kind {
                 let event = MouseEvent {
-                    kind: MouseEventKind::VertWheel(-discrete_y as i16),
+                    kind,
                     coords: self.last_mouse_coords,
                     screen_coords: ScreenPoint::new(
                         self.last_mouse_coords.x + self.dimensions.pixel_width as isize,
@@ -431,6 +486,24 @@ impl WaylandWindowInner {
         }
     }
 
+    pub(crate) fn dispatch_touch(&mut self, kind: TouchEventKind, id: i32, x: f64, y: f64) {
+        let coords = Point::new(
+            self.surface_to_pixels(x as i32) as isize,
+            self.surface_to_pixels(y as i32) as isize,
+        );
+        let event = TouchEvent {
+            kind,
+            id: id as u64,
+            coords,
+            screen_coords: ScreenPoint::new(
+                coords.x + self.dimensions.pixel_width as isize,
+                coords.y + self.dimensions.pixel_height as isize,
+            ),
+        };
+        self.callbacks
+            .touch_event(&event, &Window::Wayland(WaylandWindow(self.window_id)));
+    }
+
     fn get_dpi_factor(&self) -> i32 {
         self.dimensions.dpi as i32 / crate::DEFAULT_DPI as i32
     }
@@ -576,13 +649,27 @@ impl WaylandWindowInner {
         self.callbacks.created(&window, gl_state)
     }
 
+    /// Replaces a lost opengl context with a new one, and then
+    /// repaints the window with it
+    fn reinit_opengl(&mut self) -> anyhow::Result<()> {
+        self.gl_reinit_attempts += 1;
+        if self.gl_reinit_attempts > crate::os::MAX_GL_REINIT_ATTEMPTS {
+            anyhow::bail!(
+                "opengl context was lost {} times in a row; giving up",
+                self.gl_reinit_attempts - 1
+            );
+        }
+        drop(self.gl_state.take());
+        self.enable_opengl()?;
+        self.need_paint = true;
+        self.do_paint()
+    }
+
     fn do_paint(&mut self) -> anyhow::Result<()> {
         if let Some(gl_context) = self.gl_state.as_ref() {
             if gl_context.is_context_lost() {
                 log::error!("opengl context was lost; should reinit");
-                drop(self.gl_state.take());
-                self.enable_opengl()?;
-                return self.do_paint();
+                return self.reinit_opengl();
             }
 
             let mut frame = glium::Frame::new(
@@ -594,7 +681,16 @@ impl WaylandWindowInner {
             );
 
             self.callbacks.paint(&mut frame);
-            frame.finish()?;
+            match frame.finish() {
+                Err(glium::SwapBuffersError::ContextLost) => {
+                    // Not all drivers report the loss via is_context_lost,
+                    // so also recover if presenting the frame fails
+                    log::error!("opengl context was lost while presenting; should reinit");
+                    return self.reinit_opengl();
+                }
+                result => result?,
+            }
+            self.gl_reinit_attempts = 0;
             // self.damage();
             self.refresh_frame();
             self.need_paint = false;
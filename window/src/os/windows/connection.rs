@@ -55,6 +55,34 @@ impl ConnectionOps for Connection {
         }
     }
 
+    fn prefers_reduced_motion(&self) -> bool {
+        // This reflects the "Show animations in Windows" setting
+        let mut enabled: BOOL = TRUE;
+        let ok = unsafe {
+            SystemParametersInfoW(
+                SPI_GETCLIENTAREAANIMATION,
+                0,
+                &mut enabled as *mut BOOL as *mut _,
+                0,
+            )
+        };
+        ok != 0 && enabled == FALSE
+    }
+
+    fn prefers_high_contrast(&self) -> bool {
+        let mut hc: HIGHCONTRASTW = unsafe { std::mem::zeroed() };
+        hc.cbSize = std::mem::size_of::<HIGHCONTRASTW>() as UINT;
+        let ok = unsafe {
+            SystemParametersInfoW(
+                SPI_GETHIGHCONTRAST,
+                hc.cbSize,
+                &mut hc as *mut HIGHCONTRASTW as *mut _,
+                0,
+            )
+        };
+        ok != 0 && (hc.dwFlags & HCF_HIGHCONTRASTON) != 0
+    }
+
     fn schedule_timer<F: FnMut() + 'static>(&self, interval: std::time::Duration, callback: F) {
         let millis = interval
             .as_millis()
@@ -3,8 +3,8 @@ use crate::connection::ConnectionOps;
 use crate::WindowConfigHandle;
 use crate::{
     Clipboard, Dimensions, KeyCode, KeyEvent, Modifiers, MouseButtons, MouseCursor, MouseEvent,
-    MouseEventKind, MousePress, Point, Rect, ScreenPoint, WindowCallbacks, WindowDecorations,
-    WindowOps, WindowOpsMut,
+    MouseEventKind, MousePress, Point, Rect, ScreenPoint, TouchEvent, TouchEventKind,
+    WindowBackgroundMaterial, WindowCallbacks, WindowDecorations, WindowOps, WindowOpsMut,
 };
 use anyhow::{bail, Context};
 use lazy_static::lazy_static;
@@ -408,6 +408,10 @@ impl Window {
 
         enable_dark_mode(hwnd.0);
         enable_blur_behind(hwnd.0);
+        // Receive WM_TOUCH rather than having touches converted
+        // into gestures and mouse events
+        unsafe { RegisterTouchWindow(hwnd.0, 0) };
+        apply_background_material(hwnd.0, inner.borrow().config.window_background_material());
 
         Connection::get()
             .expect("Connection::init was not called")
@@ -428,6 +432,9 @@ fn schedule_show_window(hwnd: HWindow, show: bool) {
     promise::spawn::spawn(async move {
         unsafe {
             ShowWindow(hwnd.0, if show { SW_NORMAL } else { SW_MINIMIZE });
+            if show {
+                SetForegroundWindow(hwnd.0);
+            }
         }
     })
     .detach();
@@ -530,6 +537,25 @@ impl WindowOpsMut for WindowInner {
     fn config_did_change(&mut self, config: &WindowConfigHandle) {
         self.config = config.clone();
         self.apply_decoration();
+        apply_background_material(self.hwnd.0, config.window_background_material());
+    }
+
+    fn set_always_on_top(&mut self, on_top: bool) {
+        let hwnd = self.hwnd;
+        promise::spawn::spawn(async move {
+            unsafe {
+                SetWindowPos(
+                    hwnd.0,
+                    if on_top { HWND_TOPMOST } else { HWND_NOTOPMOST },
+                    0,
+                    0,
+                    0,
+                    0,
+                    SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE,
+                );
+            }
+        })
+        .detach();
     }
 
     fn toggle_fullscreen(&mut self) {
@@ -630,6 +656,13 @@ impl WindowOps for Window {
         })
     }
 
+    fn set_always_on_top(&self, on_top: bool) -> Future<()> {
+        Connection::with_window_inner(self.0, move |inner| {
+            inner.set_always_on_top(on_top);
+            Ok(())
+        })
+    }
+
     fn config_did_change(&self, config: &WindowConfigHandle) -> Future<()> {
         let config = Arc::clone(config);
         Connection::with_window_inner(self.0, move |inner| {
@@ -742,6 +775,94 @@ fn enable_blur_behind(hwnd: HWND) {
     }
 }
 
+/// Ask the DWM to render the requested material behind the window.
+/// Windows 11 22H2 and later support DWMWA_SYSTEMBACKDROP_TYPE for
+/// both mica and acrylic; on earlier versions we fall back to the
+/// undocumented accent policy, which can produce blur and acrylic
+/// but not mica.
+fn apply_background_material(hwnd: HWND, material: WindowBackgroundMaterial) {
+    use winapi::um::dwmapi::DwmSetWindowAttribute;
+
+    const DWMWA_SYSTEMBACKDROP_TYPE: DWORD = 38;
+    const DWMSBT_AUTO: DWORD = 0;
+    const DWMSBT_MAINWINDOW: DWORD = 2;
+    const DWMSBT_TRANSIENTWINDOW: DWORD = 3;
+
+    #[allow(non_snake_case)]
+    type WINDOWCOMPOSITIONATTRIB = u32;
+    const WCA_ACCENT_POLICY: WINDOWCOMPOSITIONATTRIB = 19;
+
+    const ACCENT_DISABLED: u32 = 0;
+    const ACCENT_ENABLE_BLURBEHIND: u32 = 3;
+    const ACCENT_ENABLE_ACRYLICBLURBEHIND: u32 = 4;
+
+    #[allow(non_snake_case)]
+    #[repr(C)]
+    pub struct WINDOWCOMPOSITIONATTRIBDATA {
+        Attrib: WINDOWCOMPOSITIONATTRIB,
+        pvData: PVOID,
+        cbData: winapi::shared::basetsd::SIZE_T,
+    }
+
+    #[allow(non_snake_case)]
+    #[repr(C)]
+    struct ACCENT_POLICY {
+        AccentState: u32,
+        AccentFlags: u32,
+        GradientColor: u32,
+        AnimationId: u32,
+    }
+
+    shared_library!(User32,
+        pub fn SetWindowCompositionAttribute(hwnd: HWND, attrib: *mut WINDOWCOMPOSITIONATTRIBDATA) -> BOOL,
+    );
+
+    let backdrop: DWORD = match material {
+        WindowBackgroundMaterial::None | WindowBackgroundMaterial::Blur => DWMSBT_AUTO,
+        WindowBackgroundMaterial::Mica => DWMSBT_MAINWINDOW,
+        WindowBackgroundMaterial::Acrylic => DWMSBT_TRANSIENTWINDOW,
+    };
+
+    let hr = unsafe {
+        DwmSetWindowAttribute(
+            hwnd as _,
+            DWMWA_SYSTEMBACKDROP_TYPE,
+            &backdrop as *const _ as *const _,
+            std::mem::size_of_val(&backdrop) as u32,
+        )
+    };
+    let backdrop_ok = hr == winapi::shared::winerror::S_OK;
+
+    let accent_state = match material {
+        WindowBackgroundMaterial::Blur => ACCENT_ENABLE_BLURBEHIND,
+        WindowBackgroundMaterial::Acrylic if !backdrop_ok => ACCENT_ENABLE_ACRYLICBLURBEHIND,
+        WindowBackgroundMaterial::Mica if !backdrop_ok => {
+            log::warn!("window_background_material = Mica requires Windows 11");
+            ACCENT_DISABLED
+        }
+        _ => ACCENT_DISABLED,
+    };
+
+    if let Ok(user) = User32::open(std::path::Path::new("user32.dll")) {
+        let mut policy = ACCENT_POLICY {
+            AccentState: accent_state,
+            AccentFlags: 0,
+            GradientColor: 0,
+            AnimationId: 0,
+        };
+        unsafe {
+            (user.SetWindowCompositionAttribute)(
+                hwnd,
+                &mut WINDOWCOMPOSITIONATTRIBDATA {
+                    Attrib: WCA_ACCENT_POLICY,
+                    pvData: &mut policy as *mut _ as _,
+                    cbData: std::mem::size_of_val(&policy) as _,
+                },
+            );
+        }
+    }
+}
+
 fn enable_dark_mode(hwnd: HWND) {
     // Prefer to run in dark mode. This could be made configurable without
     // a huge amount of effort, but I think it's fine to just be always
@@ -915,7 +1036,19 @@ unsafe fn wm_paint(hwnd: HWND, _msg: UINT, _wparam: WPARAM, _lparam: LPARAM) ->
                 glium::Frame::new(Rc::clone(&gl_context), (width as u32, height as u32));
 
             inner.callbacks.borrow_mut().paint(&mut frame);
-            frame.finish().expect("frame.finish failed");
+            match frame.finish() {
+                Ok(()) => {}
+                Err(glium::SwapBuffersError::ContextLost) => {
+                    // Not all drivers report the loss via is_context_lost,
+                    // so also recover if presenting the frame fails
+                    log::error!("opengl context was lost while presenting; should reinit");
+                    let _ = inner
+                        .callbacks
+                        .borrow_mut()
+                        .opengl_context_lost(&Window(inner.hwnd));
+                }
+                Err(err) => panic!("frame.finish failed: {}", err),
+            }
         }
 
         EndPaint(hwnd, &mut ps);
@@ -994,7 +1127,21 @@ fn apply_mouse_cursor(cursor: Option<MouseCursor>) {
     }
 }
 
+/// Returns true if the mouse message that is being processed was
+/// synthesized by the system from touch input, which we handle
+/// separately via WM_TOUCH.
+fn is_mouse_message_from_touch() -> bool {
+    // See "System Events and Mouse Messages" in the Windows docs
+    const MI_WP_SIGNATURE: u32 = 0xFF515780;
+    const SIGNATURE_MASK: u32 = 0xFFFFFF80;
+    let extra_info = unsafe { GetMessageExtraInfo() } as u32;
+    extra_info & SIGNATURE_MASK == MI_WP_SIGNATURE
+}
+
 unsafe fn mouse_button(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) -> Option<LRESULT> {
+    if is_mouse_message_from_touch() {
+        return Some(0);
+    }
     if let Some(inner) = rc_from_hwnd(hwnd) {
         // To support dragging the window, capture when the left
         // button goes down and release when it goes up.
@@ -1034,6 +1181,9 @@ unsafe fn mouse_button(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) ->
 }
 
 unsafe fn mouse_move(hwnd: HWND, _msg: UINT, wparam: WPARAM, lparam: LPARAM) -> Option<LRESULT> {
+    if is_mouse_message_from_touch() {
+        return Some(0);
+    }
     if let Some(inner) = rc_from_hwnd(hwnd) {
         let (modifiers, mouse_buttons) = mods_and_buttons(wparam);
         let coords = mouse_coords(lparam);
@@ -1056,6 +1206,50 @@ unsafe fn mouse_move(hwnd: HWND, _msg: UINT, wparam: WPARAM, lparam: LPARAM) ->
     }
 }
 
+unsafe fn touch(hwnd: HWND, _msg: UINT, wparam: WPARAM, lparam: LPARAM) -> Option<LRESULT> {
+    let inner = rc_from_hwnd(hwnd)?;
+    let handle = lparam as HTOUCHINPUT;
+    let count = LOWORD(wparam as DWORD) as usize;
+    let mut inputs: Vec<TOUCHINPUT> = vec![std::mem::zeroed(); count];
+    if GetTouchInputInfo(
+        handle,
+        count as UINT,
+        inputs.as_mut_ptr(),
+        std::mem::size_of::<TOUCHINPUT>() as i32,
+    ) == 0
+    {
+        log::error!("GetTouchInputInfo: {}", IoError::last_os_error());
+        return None;
+    }
+    CloseTouchInputHandle(handle);
+
+    for input in &inputs {
+        let kind = if input.dwFlags & TOUCHEVENTF_DOWN != 0 {
+            TouchEventKind::Down
+        } else if input.dwFlags & TOUCHEVENTF_UP != 0 {
+            TouchEventKind::Up
+        } else if input.dwFlags & TOUCHEVENTF_MOVE != 0 {
+            TouchEventKind::Move
+        } else {
+            continue;
+        };
+        // Touch positions are in hundredths of a screen pixel
+        let screen_coords = ScreenPoint::new((input.x / 100) as isize, (input.y / 100) as isize);
+        let event = TouchEvent {
+            kind,
+            id: input.dwID as u64,
+            coords: screen_to_client(hwnd, screen_coords),
+            screen_coords,
+        };
+        let inner = inner.borrow();
+        inner
+            .callbacks
+            .borrow_mut()
+            .touch_event(&event, &Window::from_hwnd(hwnd));
+    }
+    Some(0)
+}
+
 lazy_static! {
     static ref WHEEL_SCROLL_LINES: i16 = read_scroll_speed("WheelScrollLines").unwrap_or(3);
     static ref WHEEL_SCROLL_CHARS: i16 = read_scroll_speed("WheelScrollChars").unwrap_or(3);
@@ -1082,22 +1276,28 @@ unsafe fn mouse_wheel(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) ->
         } else {
             delta * (*WHEEL_SCROLL_CHARS)
         };
-        let mut position = scaled_delta / WHEEL_DELTA;
-        let remainder = delta % WHEEL_DELTA;
+        // Precision touchpads report deltas that are a fraction of
+        // WHEEL_DELTA; accumulate the scaled delta so that those
+        // fractions of a line add up rather than being rounded away
+        // on each event
+        let (kind, position) = if msg == WM_MOUSEHWHEEL {
+            let mut inner = inner.borrow_mut();
+            inner.hscroll_remainder += scaled_delta;
+            let position = inner.hscroll_remainder / WHEEL_DELTA;
+            inner.hscroll_remainder %= WHEEL_DELTA;
+            (MouseEventKind::HorzWheel(position), position)
+        } else {
+            let mut inner = inner.borrow_mut();
+            inner.vscroll_remainder += scaled_delta;
+            let position = inner.vscroll_remainder / WHEEL_DELTA;
+            inner.vscroll_remainder %= WHEEL_DELTA;
+            (MouseEventKind::VertWheel(position), position)
+        };
+        if position == 0 {
+            return Some(0);
+        }
         let event = MouseEvent {
-            kind: if msg == WM_MOUSEHWHEEL {
-                let mut inner = inner.borrow_mut();
-                inner.hscroll_remainder += remainder;
-                position += inner.hscroll_remainder / WHEEL_DELTA;
-                inner.hscroll_remainder %= WHEEL_DELTA;
-                MouseEventKind::HorzWheel(position)
-            } else {
-                let mut inner = inner.borrow_mut();
-                inner.vscroll_remainder += remainder;
-                position += inner.vscroll_remainder / WHEEL_DELTA;
-                inner.vscroll_remainder %= WHEEL_DELTA;
-                MouseEventKind::VertWheel(position)
-            },
+            kind,
             coords,
             screen_coords,
             mouse_buttons,
@@ -1900,6 +2100,7 @@ unsafe fn do_wnd_proc(hwnd: HWND, msg: UINT, wparam: WPARAM, lparam: LPARAM) ->
         WM_IME_COMPOSITION => ime_composition(hwnd, msg, wparam, lparam),
         WM_MOUSEMOVE => mouse_move(hwnd, msg, wparam, lparam),
         WM_MOUSEHWHEEL | WM_MOUSEWHEEL => mouse_wheel(hwnd, msg, wparam, lparam),
+        WM_TOUCH => touch(hwnd, msg, wparam, lparam),
         WM_LBUTTONDBLCLK | WM_RBUTTONDBLCLK | WM_MBUTTONDBLCLK | WM_LBUTTONDOWN | WM_LBUTTONUP
         | WM_RBUTTONDOWN | WM_RBUTTONUP | WM_MBUTTONDOWN | WM_MBUTTONUP => {
             mouse_button(hwnd, msg, wparam, lparam)
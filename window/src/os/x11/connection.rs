@@ -421,7 +421,9 @@ impl XConnection {
     /// Run through all of the windows and cause them to paint if they need it.
     fn do_paint(&self) {
         for window in self.windows.borrow().values() {
-            window.lock().unwrap().paint().unwrap();
+            if let Err(err) = window.lock().unwrap().paint() {
+                log::error!("paint failed: {:#}", err);
+            }
         }
         self.conn.flush();
     }
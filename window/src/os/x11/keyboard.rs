@@ -1,5 +1,5 @@
-use crate::os::xkeysyms::keysym_to_keycode;
-use crate::{KeyEvent, Modifiers};
+use crate::os::xkeysyms::{dead_keysym_to_char, keysym_to_keycode};
+use crate::{KeyCode, KeyEvent, Modifiers};
 use anyhow::{anyhow, ensure};
 use libc;
 use std::cell::RefCell;
@@ -108,7 +108,16 @@ impl Keyboard {
         let xcode = xkb::Keycode::from(xcb_ev.detail());
         let xsym = self.state.borrow().key_get_one_sym(xcode);
 
-        let ksym = if pressed {
+        // When dead keys are disabled, a dead key bypasses the compose
+        // state machine and is sent immediately as its spacing character.
+        // Other compose sequences (eg: via the Multi_key) still work.
+        let dead_char = if crate::config().use_dead_keys() {
+            None
+        } else {
+            dead_keysym_to_char(xsym)
+        };
+
+        let ksym = if pressed && dead_char.is_none() {
             self.compose_state.borrow_mut().feed(xsym);
 
             let cstate = self.compose_state.borrow().status();
@@ -132,7 +141,10 @@ impl Keyboard {
             xsym
         };
 
-        let kc = keysym_to_keycode(ksym).or_else(|| keysym_to_keycode(xsym))?;
+        let kc = match dead_char {
+            Some(c) => KeyCode::Char(c),
+            None => keysym_to_keycode(ksym).or_else(|| keysym_to_keycode(xsym))?,
+        };
         let raw_modifiers = self.get_key_modifiers();
         // X11 keysyms that map to KeyCode::Char already factor in the SHIFT
         // modifier state.  eg: SHIFT-c in an US layout produces `Char('C')`.
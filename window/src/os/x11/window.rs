@@ -60,6 +60,9 @@ pub(crate) struct XWindowInner {
     copy_and_paste: CopyAndPaste,
     config: WindowConfigHandle,
     gl_state: Option<Rc<glium::backend::Context>>,
+    /// The number of consecutive times the opengl context has been
+    /// replaced without successfully presenting a frame
+    gl_reinit_attempts: usize,
 }
 
 fn enclosing_boundary_with(a: &Rect, b: &Rect) -> Rect {
@@ -126,9 +129,7 @@ impl XWindowInner {
         if let Some(gl_context) = self.gl_state.as_ref() {
             if gl_context.is_context_lost() {
                 log::error!("opengl context was lost; should reinit");
-                drop(self.gl_state.take());
-                self.enable_opengl()?;
-                return self.paint();
+                return self.reinit_opengl();
             }
 
             let mut frame = glium::Frame::new(
@@ -137,12 +138,37 @@ impl XWindowInner {
             );
 
             self.callbacks.paint(&mut frame);
-            frame.finish()?;
+            match frame.finish() {
+                Err(glium::SwapBuffersError::ContextLost) => {
+                    // Not all drivers report the loss via is_context_lost,
+                    // so also recover if presenting the frame fails
+                    log::error!("opengl context was lost while presenting; should reinit");
+                    return self.reinit_opengl();
+                }
+                result => result?,
+            }
+            self.gl_reinit_attempts = 0;
         }
 
         Ok(())
     }
 
+    /// Replaces a lost opengl context with a new one, and then
+    /// repaints the whole window with it
+    fn reinit_opengl(&mut self) -> anyhow::Result<()> {
+        self.gl_reinit_attempts += 1;
+        if self.gl_reinit_attempts > crate::os::MAX_GL_REINIT_ATTEMPTS {
+            anyhow::bail!(
+                "opengl context was lost {} times in a row; giving up",
+                self.gl_reinit_attempts - 1
+            );
+        }
+        drop(self.gl_state.take());
+        self.enable_opengl()?;
+        self.paint_all = true;
+        self.paint()
+    }
+
     /// Add a region to the list of exposed/damaged/dirty regions.
     /// Note that a window resize will likely invalidate the entire window.
     /// If the new region intersects with the prior region, then we expand
@@ -519,20 +545,20 @@ impl XWindowInner {
             .is_some())
     }
 
-    fn set_fullscreen_hint(&mut self, enable: bool) -> anyhow::Result<()> {
+    /// Ask the window manager to add or remove the named
+    /// `_NET_WM_STATE_XXX` state atom for this window.
+    fn set_net_wm_state(&self, state_name: &str, enable: bool) -> anyhow::Result<()> {
         let conn = self.conn();
 
         let net_wm_state = xcb::intern_atom(conn.conn(), false, "_NET_WM_STATE")
             .get_reply()?
             .atom();
-        let net_wm_state_fullscreen =
-            xcb::intern_atom(conn.conn(), false, "_NET_WM_STATE_FULLSCREEN")
-                .get_reply()?
-                .atom();
+        let state_atom = xcb::intern_atom(conn.conn(), false, state_name)
+            .get_reply()?
+            .atom();
 
-        let data: [u32; 5] = [if enable { 1 } else { 0 }, net_wm_state_fullscreen, 0, 0, 0];
+        let data: [u32; 5] = [if enable { 1 } else { 0 }, state_atom, 0, 0, 0];
 
-        // Ask window manager to change our fullscreen state
         xcb::xproto::send_event(
             &conn,
             true,
@@ -546,6 +572,13 @@ impl XWindowInner {
                 xcb::ClientMessageData::from_data32(data),
             ),
         );
+
+        Ok(())
+    }
+
+    fn set_fullscreen_hint(&mut self, enable: bool) -> anyhow::Result<()> {
+        // Ask window manager to change our fullscreen state
+        self.set_net_wm_state("_NET_WM_STATE_FULLSCREEN", enable)?;
         self.adjust_decorations(self.config.decorations())?;
 
         Ok(())
@@ -720,6 +753,7 @@ impl XWindow {
                 copy_and_paste: CopyAndPaste::default(),
                 cursors: CursorInfo::new(&conn),
                 gl_state: None,
+                gl_reinit_attempts: 0,
                 config: Arc::clone(&config),
             }))
         };
@@ -764,6 +798,14 @@ impl WindowOpsMut for XWindowInner {
     fn hide(&mut self) {}
     fn show(&mut self) {
         xcb::map_window(self.conn().conn(), self.window_id);
+        xcb::configure_window(
+            self.conn().conn(),
+            self.window_id,
+            &[(
+                xcb::CONFIG_WINDOW_STACK_MODE as u16,
+                xcb::STACK_MODE_ABOVE as u32,
+            )],
+        );
     }
     fn set_cursor(&mut self, cursor: Option<MouseCursor>) {
         XWindowInner::set_cursor(self, cursor).unwrap();
@@ -783,6 +825,18 @@ impl WindowOpsMut for XWindowInner {
         self.set_fullscreen_hint(!fullscreen).ok();
     }
 
+    fn set_always_on_top(&mut self, on_top: bool) {
+        if let Err(err) = self.set_net_wm_state("_NET_WM_STATE_ABOVE", on_top) {
+            log::error!("Failed to set always-on-top state: {:#}", err);
+        }
+    }
+
+    fn set_visible_on_all_workspaces(&mut self, visible: bool) {
+        if let Err(err) = self.set_net_wm_state("_NET_WM_STATE_STICKY", visible) {
+            log::error!("Failed to set sticky state: {:#}", err);
+        }
+    }
+
     fn config_did_change(&mut self, config: &WindowConfigHandle) {
         self.config = Arc::clone(config);
         let _ = self.adjust_decorations(config.decorations());
@@ -870,6 +924,20 @@ impl WindowOps for XWindow {
         })
     }
 
+    fn set_always_on_top(&self, on_top: bool) -> Future<()> {
+        XConnection::with_window_inner(self.0, move |inner| {
+            inner.set_always_on_top(on_top);
+            Ok(())
+        })
+    }
+
+    fn set_visible_on_all_workspaces(&self, visible: bool) -> Future<()> {
+        XConnection::with_window_inner(self.0, move |inner| {
+            inner.set_visible_on_all_workspaces(visible);
+            Ok(())
+        })
+    }
+
     fn config_did_change(&self, config: &WindowConfigHandle) -> Future<()> {
         let config = Arc::clone(config);
         XConnection::with_window_inner(self.0, move |inner| {
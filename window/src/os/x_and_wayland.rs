@@ -96,6 +96,38 @@ impl ConnectionOps for Connection {
             Self::Wayland(w) => w.run_message_loop(),
         }
     }
+    fn prefers_reduced_motion(&self) -> bool {
+        // There is no standard for this under X11 or Wayland, but the
+        // GNOME setting is also used by GTK, so we consult that.
+        // Querying it requires running a program, so it is only done once.
+        lazy_static::lazy_static! {
+            static ref ANIMATIONS_DISABLED: bool = std::process::Command::new("gsettings")
+                .args(&["get", "org.gnome.desktop.interface", "enable-animations"])
+                .output()
+                .map(|output| {
+                    output.status.success()
+                        && String::from_utf8_lossy(&output.stdout).trim() == "false"
+                })
+                .unwrap_or(false);
+        }
+        *ANIMATIONS_DISABLED
+    }
+
+    fn prefers_high_contrast(&self) -> bool {
+        // As for prefers_reduced_motion, we consult the GNOME setting
+        lazy_static::lazy_static! {
+            static ref HIGH_CONTRAST: bool = std::process::Command::new("gsettings")
+                .args(&["get", "org.gnome.desktop.a11y.interface", "high-contrast"])
+                .output()
+                .map(|output| {
+                    output.status.success()
+                        && String::from_utf8_lossy(&output.stdout).trim() == "true"
+                })
+                .unwrap_or(false);
+        }
+        *HIGH_CONTRAST
+    }
+
     fn schedule_timer<F: FnMut() + 'static>(&self, interval: std::time::Duration, callback: F) {
         match self {
             Self::X11(x) => x.schedule_timer(interval, callback),
@@ -145,6 +177,22 @@ impl WindowOps for Window {
         }
     }
 
+    fn set_always_on_top(&self, on_top: bool) -> Future<()> {
+        match self {
+            Self::X11(x) => x.set_always_on_top(on_top),
+            #[cfg(feature = "wayland")]
+            Self::Wayland(w) => w.set_always_on_top(on_top),
+        }
+    }
+
+    fn set_visible_on_all_workspaces(&self, visible: bool) -> Future<()> {
+        match self {
+            Self::X11(x) => x.set_visible_on_all_workspaces(visible),
+            #[cfg(feature = "wayland")]
+            Self::Wayland(w) => w.set_visible_on_all_workspaces(visible),
+        }
+    }
+
     fn config_did_change(&self, config: &WindowConfigHandle) -> Future<()> {
         match self {
             Self::X11(x) => x.config_did_change(config),
@@ -24,6 +24,40 @@ pub fn modifiers_from_state(state: u16) -> Modifiers {
     mods
 }
 
+/// If keysym is a dead key, returns the spacing character that it
+/// produces when pressed on its own.  This is used to send dead keys
+/// immediately when `use_dead_keys = false`.
+pub fn dead_keysym_to_char(keysym: u32) -> Option<char> {
+    use xkbcommon::xkb::keysyms::*;
+    #[allow(non_upper_case_globals)]
+    Some(match keysym {
+        KEY_dead_grave => '`',
+        KEY_dead_acute => '\u{b4}',
+        KEY_dead_circumflex => '^',
+        KEY_dead_tilde => '~',
+        KEY_dead_macron => '\u{af}',
+        KEY_dead_breve => '\u{2d8}',
+        KEY_dead_abovedot => '\u{2d9}',
+        KEY_dead_diaeresis => '\u{a8}',
+        KEY_dead_abovering => '\u{b0}',
+        KEY_dead_doubleacute => '\u{2dd}',
+        KEY_dead_caron => '\u{2c7}',
+        KEY_dead_cedilla => '\u{b8}',
+        KEY_dead_ogonek => '\u{2db}',
+        _ => return None,
+    })
+}
+
+/// Returns true if keysym is a modifier such as Shift_L or
+/// ISO_Level3_Shift (AltGr), which doesn't end a compose sequence
+pub fn is_modifier_keysym(keysym: u32) -> bool {
+    use xkbcommon::xkb::keysyms::*;
+    matches!(
+        keysym,
+        KEY_Shift_L..=KEY_Hyper_R | KEY_ISO_Lock..=KEY_ISO_Last_Group_Lock | KEY_Mode_switch
+    )
+}
+
 /// Translates non-printable X11 keysym to KeyCode
 /// for missing keys, look into `/usr/include/X11/keysymdef.h`
 /// and/or define them in KeyCode.